@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+/// Image user of the docker-stacks notebooks (jovyan, group users).
+pub const DEFAULT_NOTEBOOK_UID: u32 = 1000;
+pub const DEFAULT_NOTEBOOK_GID: u32 = 100;
+
+const READ: u32 = 0o4;
+const SEARCH: u32 = 0o1;
+
+/// Why a notebook process cannot read a mounted host path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denied {
+    /// A parent directory lacks search (`x`) permission.
+    Traverse(PathBuf),
+    /// The path itself is not readable (and, for directories, listable).
+    Read(PathBuf),
+}
+
+/// Owner, group, and mode of one path component.
+#[derive(Debug, Clone, Copy)]
+pub struct Permissions {
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+}
+
+/// Checks that `uid`/`gid` can reach and read `path`. As root this is an
+/// actual access test run under those IDs, which honours ACLs; otherwise the
+/// mode bits are evaluated. `None` means the path is readable or could not be
+/// inspected.
+pub fn check(path: &Path, uid: u32, gid: u32) -> Option<Denied> {
+    let ancestors: Vec<&Path> = path.ancestors().skip(1).collect();
+    for dir in ancestors.into_iter().rev() {
+        if !allowed(dir, uid, gid, SEARCH)? {
+            return Some(Denied::Traverse(dir.to_path_buf()));
+        }
+    }
+    let wanted = if path.is_dir() { READ | SEARCH } else { READ };
+    if !allowed(path, uid, gid, wanted)? {
+        return Some(Denied::Read(path.to_path_buf()));
+    }
+    None
+}
+
+fn allowed(path: &Path, uid: u32, gid: u32, wanted: u32) -> Option<bool> {
+    if crate::util::is_root() {
+        return probe_as(path, uid, gid, wanted);
+    }
+    Some(mode_allows(&permissions(path)?, uid, gid, wanted))
+}
+
+/// Whether the classic permission bits grant `wanted` (`r`=4, `x`=1) to a
+/// process with only `uid` and primary group `gid`.
+pub fn mode_allows(perms: &Permissions, uid: u32, gid: u32, wanted: u32) -> bool {
+    let bits = if perms.uid == uid {
+        perms.mode >> 6
+    } else if perms.gid == gid {
+        perms.mode >> 3
+    } else {
+        perms.mode
+    } & 0o7;
+    bits & wanted == wanted
+}
+
+#[cfg(unix)]
+fn permissions(path: &Path) -> Option<Permissions> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(Permissions {
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mode: metadata.mode(),
+    })
+}
+
+#[cfg(not(unix))]
+fn permissions(_path: &Path) -> Option<Permissions> {
+    None
+}
+
+/// Runs `test` as `uid`/`gid` with no supplementary groups, the way the
+/// notebook process will see the path.
+#[cfg(unix)]
+fn probe_as(path: &Path, uid: u32, gid: u32, wanted: u32) -> Option<bool> {
+    use std::os::unix::process::CommandExt;
+    let mut command = std::process::Command::new("test");
+    if wanted & READ != 0 {
+        command.arg("-r").arg(path);
+    }
+    if wanted & SEARCH != 0 {
+        if wanted & READ != 0 {
+            command.arg("-a");
+        }
+        command.arg("-x").arg(path);
+    }
+    command.uid(uid).gid(gid).status().ok().map(|status| status.success())
+}
+
+#[cfg(not(unix))]
+fn probe_as(_path: &Path, _uid: u32, _gid: u32, _wanted: u32) -> Option<bool> {
+    None
+}
+
+/// Commands that grant access, from the narrowest to the broadest.
+pub fn remediation(denied: &Denied, uid: u32, gid: u32) -> Vec<String> {
+    match denied {
+        Denied::Traverse(dir) => vec![
+            format!("setfacl -m u:{}:x {}", uid, dir.display()),
+            format!("chmod o+x {}", dir.display()),
+        ],
+        Denied::Read(path) => vec![
+            format!("setfacl -R -m u:{uid}:rX -m d:u:{uid}:rX {}", path.display(), uid = uid),
+            format!("chgrp -R {} {} && chmod -R g+rX {}", gid, path.display(), path.display()),
+            format!("chmod -R o+rX {}", path.display()),
+        ],
+    }
+}
+
+impl Denied {
+    pub fn describe(&self, uid: u32, gid: u32) -> String {
+        match self {
+            Denied::Traverse(dir) => format!("UID {}:{} cannot enter {}", uid, gid, dir.display()),
+            Denied::Read(path) => format!("UID {}:{} cannot read {}", uid, gid, path.display()),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    cli::AdoptOptions,
+    compose::{self, ComposeFile},
+    config::{self, AppConfig},
+    db::Database,
+    deploy,
+    envfile::{self, EnvMap},
+    failure, say, util,
+};
+
+/// Copy of `.env` as it was before `adopt` added settings to it.
+pub const ENV_BACKUP: &str = ".env.pre-adopt";
+const HUB_SERVICE: &str = "jupyterhub";
+const DB_SERVICE: &str = "postgres";
+
+/// A setting `adopt` adds to `.env`, and where it was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Addition {
+    pub key: &'static str,
+    pub value: String,
+    pub source: String,
+}
+
+/// Whether a group of commands works on the adopted deployment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capability {
+    pub name: &'static str,
+    pub managed: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Inspection {
+    pub additions: Vec<Addition>,
+    pub capabilities: Vec<Capability>,
+}
+
+impl Inspection {
+    /// `env` with the additions applied.
+    pub fn merged_env(&self, env: &EnvMap) -> EnvMap {
+        let mut merged = env.clone();
+        for addition in &self.additions {
+            merged.insert(addition.key.to_string(), addition.value.clone());
+        }
+        merged
+    }
+}
+
+pub fn run(opts: AdoptOptions, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    let dir = &opts.dir;
+    if !dir.join(compose::COMPOSE_FILE).exists() {
+        return Err(failure::validation(format!(
+            "{} has no {}; adopt expects a docker-compose deployment",
+            dir.display(),
+            compose::COMPOSE_FILE
+        )));
+    }
+    let dir = std::path::absolute(dir).with_context(|| format!("invalid directory {}", dir.display()))?;
+    let env_path = dir.join(envfile::ENV_FILE);
+    let raw_env = if env_path.exists() { util::read_to_string(&env_path)? } else { String::new() };
+    let env = envfile::parse(&raw_env);
+    let file = compose::load(&dir)?;
+    let inspection = inspect(&dir, &env, &file);
+
+    say!("{} {}", style("Adopting").cyan().bold(), style(dir.display()).dim());
+    if inspection.additions.is_empty() {
+        say!("  {}", style(".env already has everything adopt can infer").dim());
+    }
+    for addition in &inspection.additions {
+        say!("  {}={} {}", addition.key, addition.value, style(format!("(from {})", addition.source)).dim());
+    }
+    say!("\n{}", style("What mvre-hub can manage").bold());
+    for capability in &inspection.capabilities {
+        let marker = if capability.managed { style("yes").green() } else { style("no").yellow() };
+        say!("  {:<22} {:<4} {}", capability.name, marker, capability.detail);
+    }
+
+    let mut name = app_config.deployment_name(&dir).map(str::to_string);
+    if opts.dry_run {
+        say!("\n{}", style("Dry run: nothing was written or registered").dim());
+    } else {
+        if !inspection.additions.is_empty() {
+            if env_path.exists() && !dir.join(ENV_BACKUP).exists() {
+                util::write_string(&dir.join(ENV_BACKUP), &raw_env)?;
+                util::set_file_mode(&dir.join(ENV_BACKUP), 0o600).ok();
+            }
+            let updated = inspection
+                .additions
+                .iter()
+                .fold(raw_env, |raw, addition| envfile::with_value(&raw, addition.key, &addition.value));
+            util::write_string(&env_path, &updated)?;
+            util::set_file_mode(&env_path, 0o600).ok();
+        }
+        let merged = inspection.merged_env(&env);
+        let registered = app_config.register(&dir, envfile::get(&merged, "HUB_DOMAIN"));
+        app_config.remember(&dir);
+        config::save(config_path, app_config)?;
+        if app_config.active.as_deref() == Some(registered.as_str()) {
+            say!("\n{} {}", style("Registered and now using").green(), style(&registered).cyan());
+        } else {
+            say!(
+                "\n{} {}; switch to it with {}",
+                style("Registered as").green(),
+                style(&registered).cyan(),
+                style(format!("mvre-hub use {}", registered)).cyan()
+            );
+        }
+        name = Some(registered);
+    }
+    util::set_result(serde_json::json!({
+        "deploy_dir": dir,
+        "name": name,
+        "dry_run": opts.dry_run,
+        "additions": inspection.additions,
+        "capabilities": inspection.capabilities,
+    }));
+    Ok(())
+}
+
+/// Maps a hand-written deployment onto the settings this tool reads, and
+/// tells which commands will work on it. Settings already in `env` are kept.
+pub fn inspect(deploy_dir: &Path, env: &EnvMap, file: &ComposeFile) -> Inspection {
+    let mut inspection = Inspection::default();
+    let mut add = |key: &'static str, value: String, source: String| {
+        if envfile::get(env, key).is_none() && !value.is_empty() {
+            inspection.additions.push(Addition { key, value, source });
+        }
+    };
+
+    add(
+        "COMPOSE_PROJECT_NAME",
+        compose::project_name(deploy_dir, env),
+        "the directory name, so the containers keep their names".to_string(),
+    );
+    if let Some((domain, source)) = find_domain(env, file) {
+        add("HUB_DOMAIN", domain, source);
+    }
+    let proxy = file
+        .services
+        .iter()
+        .find(|(_, service)| service.image.as_deref().is_some_and(|image| image.starts_with("traefik")));
+    if let Some((name, service)) = proxy {
+        if let Some(port) = service.published_port(80) {
+            add("HTTP_PORT", port.to_string(), format!("the ports of {}", name));
+        }
+        if let Some(port) = service.published_port(443) {
+            add("HTTPS_PORT", port.to_string(), format!("the ports of {}", name));
+        }
+    }
+    if file.services.contains_key(DB_SERVICE) {
+        let from = |key: &str| envfile::get(env, key).unwrap_or_default().to_string();
+        add("ENABLE_POSTGRES", "true".to_string(), format!("the {} service", DB_SERVICE));
+        add("DB_HOST", DB_SERVICE.to_string(), format!("the {} service", DB_SERVICE));
+        add("DB_USER", from("POSTGRES_USER"), "POSTGRES_USER".to_string());
+        add("DB_NAME", from("POSTGRES_DB"), "POSTGRES_DB".to_string());
+        add("DB_PASSWORD", from("POSTGRES_PASSWORD"), "POSTGRES_PASSWORD".to_string());
+    }
+
+    let merged = inspection.merged_env(env);
+    inspection.capabilities = capabilities(deploy_dir, &merged, file, proxy.map(|(name, _)| name.as_str()));
+    inspection
+}
+
+fn capabilities(deploy_dir: &Path, env: &EnvMap, file: &ComposeFile, traefik: Option<&str>) -> Vec<Capability> {
+    let capability = |name: &'static str, managed: bool, detail: String| Capability { name, managed, detail };
+    let mut capabilities = vec![capability(
+        "start, stop, status",
+        !file.services.is_empty(),
+        if file.services.is_empty() {
+            "the compose file defines no services".to_string()
+        } else {
+            format!(
+                "{} compose {}",
+                file.services.len(),
+                if file.services.len() == 1 { "service" } else { "services" }
+            )
+        },
+    )];
+
+    let hub = file
+        .services
+        .iter()
+        .find(|(_, service)| service.image.as_deref().is_some_and(|image| image.contains("jupyterhub")))
+        .map(|(name, _)| name.as_str());
+    capabilities.push(if file.services.contains_key(HUB_SERVICE) {
+        capability("users, tokens, health", true, format!("hub API of the {} service", HUB_SERVICE))
+    } else {
+        capability(
+            "users, tokens, health",
+            false,
+            match hub {
+                Some(name) => format!("the hub service is named {}; these commands expect {}", name, HUB_SERVICE),
+                None => format!("no {} service found", HUB_SERVICE),
+            },
+        )
+    });
+
+    capabilities.push(match traefik {
+        Some(name) => capability("proxy and TLS", true, format!("Traefik in the {} service", name)),
+        None => {
+            let other = file.services.iter().find_map(|(name, service)| {
+                let image = service.image.as_deref()?;
+                ["nginx", "caddy", "haproxy", "apache", "httpd"]
+                    .iter()
+                    .any(|proxy| image.contains(proxy))
+                    .then(|| format!("{} ({})", name, image))
+            });
+            capability(
+                "proxy and TLS",
+                false,
+                match other {
+                    Some(other) => format!("routing and certificates stay with {}", other),
+                    None => "no Traefik service; routing and certificates stay as they are".to_string(),
+                },
+            )
+        }
+    });
+
+    let postgres_elsewhere = file
+        .services
+        .iter()
+        .find(|(name, service)| {
+            name.as_str() != DB_SERVICE && service.image.as_deref().is_some_and(|image| image.starts_with("postgres"))
+        })
+        .map(|(name, _)| name.as_str());
+    capabilities.push(match (Database::from_env(deploy_dir, env), postgres_elsewhere) {
+        (Database::Sqlite { .. }, Some(name)) => capability(
+            "database",
+            false,
+            format!("Postgres runs in the {} service; db commands expect it to be named {}", name, DB_SERVICE),
+        ),
+        (Database::Sqlite { path }, None) if !path.exists() => capability(
+            "database",
+            false,
+            format!(
+                "no Postgres service and no {}",
+                util::path_display(path.strip_prefix(deploy_dir).unwrap_or(&path))
+            ),
+        ),
+        (database, _) => capability("database", true, format!("{} dump and restore", database.kind())),
+    });
+
+    capabilities.push(capability(
+        "backup",
+        true,
+        "archives the deployment directory".to_string(),
+    ));
+    capabilities.push(match deploy::check_inputs(env) {
+        Ok(()) => capability(
+            "reconfigure, upgrade",
+            true,
+            "the first run replaces the hand-written files with this tool's templates; back up first".to_string(),
+        ),
+        Err(err) => capability(
+            "reconfigure, upgrade",
+            false,
+            format!("{}; add it to .env to enable them", err.root_cause()),
+        ),
+    });
+    capabilities
+}
+
+/// Public host name from a Traefik `Host(...)` rule, or from the variables
+/// other proxy setups use.
+fn find_domain(env: &EnvMap, file: &ComposeFile) -> Option<(String, String)> {
+    for (name, service) in &file.services {
+        for label in service.label_values() {
+            let Some((key, rule)) = label.split_once('=') else {
+                continue;
+            };
+            if !key.ends_with(".rule") {
+                continue;
+            }
+            if let Some(host) = rule
+                .split_once("Host(`")
+                .and_then(|(_, rest)| rest.split_once('`'))
+                .map(|(host, _)| host)
+            {
+                return Some((host.to_string(), format!("the router rule of {}", name)));
+            }
+        }
+    }
+    ["DOMAIN", "VIRTUAL_HOST", "LETSENCRYPT_HOST"]
+        .into_iter()
+        .find_map(|key| envfile::get(env, key).map(|host| (host.to_string(), key.to_string())))
+}
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{cli::AnnounceOptions, config::AppConfig, failure, say, services, util};
+
+/// Banner read by the hub on every page render; `/srv/jupyterhub/announcement.html`
+/// inside the container.
+pub const ANNOUNCEMENT_FILE: &str = "jupyterhub_data/announcement.html";
+
+pub fn run(opts: AnnounceOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    if opts.clear {
+        if clear(&deploy_dir)? {
+            say!("{}", style("Announcement removed").green());
+        } else {
+            say!("{}", style("No announcement to remove").dim());
+        }
+        util::set_result(serde_json::json!({ "announcement": null }));
+        return Ok(());
+    }
+
+    let message = opts.message.unwrap_or_default();
+    if message.trim().is_empty() {
+        return Err(failure::validation("The announcement is empty; use --clear to remove it"));
+    }
+    publish(&deploy_dir, &message)?;
+    say!("{}", style("Announcement published").green());
+    util::set_result(serde_json::json!({ "announcement": message.trim() }));
+    Ok(())
+}
+
+/// The banner currently shown, if any.
+pub fn current(deploy_dir: &Path) -> Option<String> {
+    std::fs::read_to_string(deploy_dir.join(ANNOUNCEMENT_FILE)).ok()
+}
+
+pub fn publish(deploy_dir: &Path, message: &str) -> Result<()> {
+    util::atomic_write(&deploy_dir.join(ANNOUNCEMENT_FILE), message.trim().as_bytes())
+}
+
+/// Removes the banner; `false` when there was none.
+pub fn clear(deploy_dir: &Path) -> Result<bool> {
+    let path = deploy_dir.join(ANNOUNCEMENT_FILE);
+    if !path.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    Ok(true)
+}
@@ -0,0 +1,465 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{failure, templates, users, util};
+
+/// Where a group's shared directory appears in its members' servers.
+pub const GROUP_MOUNT_ROOT: &str = "/home/jovyan/groups";
+/// The extra services of a deployment, kept next to `.env` so that
+/// `reconfigure` and `upgrade` render them again; `EXTRA_SERVICES` in `.env`
+/// names the ones in use.
+pub const EXTRA_SERVICES_FILE: &str = "extra-services.toml";
+/// Networks of the deployment extra services may join.
+const EXTRA_SERVICE_NETWORKS: &[&str] = &["proxy", "hub", "users"];
+
+/// Settings that do not fit on the command line, read with `--answers`.
+///
+/// ```toml
+/// [culling]
+/// timeout = "12h"        # everyone, including users outside the groups below
+/// every = "5m"
+///
+/// [culling.groups.students]
+/// timeout = "1h"
+///
+/// [groups.sea-ice]
+/// members = ["alice", "bob"]
+/// shared = "/srv/groups/sea-ice"   # read-write in members' servers only
+///
+/// [limits.groups.power-users]      # instead of the default CPU_LIMIT/MEM_LIMIT
+/// cpu = 8
+/// mem = "32G"
+///
+/// [limits.users.alice]             # wins over her groups
+/// mem = "64G"
+///
+/// [extra_services.docs]            # appended to docker-compose.yml
+/// image = "nginx:alpine"
+/// volumes = ["./docs:/usr/share/nginx/html:ro"]
+/// labels = ["traefik.enable=true", "traefik.http.routers.docs.rule=PathPrefix(`/docs`)"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Answers {
+    #[serde(default)]
+    pub culling: Option<CullingAnswers>,
+    #[serde(default)]
+    pub groups: Option<BTreeMap<String, GroupAnswers>>,
+    #[serde(default)]
+    pub limits: Option<LimitsAnswers>,
+    #[serde(default)]
+    pub extra_services: Option<BTreeMap<String, ExtraService>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CullingAnswers {
+    pub timeout: Option<String>,
+    pub every: Option<String>,
+    #[serde(default)]
+    pub groups: BTreeMap<String, GroupCulling>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupCulling {
+    pub timeout: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupAnswers {
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// Host directory mounted at `GROUP_MOUNT_ROOT/<group>`.
+    pub shared: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsAnswers {
+    #[serde(default)]
+    pub groups: BTreeMap<String, LimitAnswers>,
+    #[serde(default)]
+    pub users: BTreeMap<String, LimitAnswers>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitAnswers {
+    pub cpu: Option<f64>,
+    /// As DockerSpawner takes it: bytes, or a number with K, M, G, or T.
+    pub mem: Option<String>,
+}
+
+/// CPU and memory limits of the servers of one user or group; unset ones
+/// fall back to the next rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceLimit {
+    pub name: String,
+    pub cpu: Option<f64>,
+    pub mem: Option<String>,
+}
+
+/// Limits the hub applies at spawn: a user's own, else the largest of their
+/// groups', else the deployment's default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    pub groups: Vec<ResourceLimit>,
+    pub users: Vec<ResourceLimit>,
+}
+
+/// A small container run next to the hub, such as a docs server or a cron
+/// runner. Values are written to the compose file as they are, so
+/// `${VAR}` picks up settings from `.env`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtraService {
+    pub image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    #[serde(default, alias = "env", skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<String>,
+    /// Networks of the deployment to join; `proxy` when unset, so Traefik
+    /// labels work, and only compose's default network when empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub networks: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+}
+
+/// A hub group the hub creates at startup, and its shared directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HubGroup {
+    pub name: String,
+    pub members: Vec<String>,
+    pub shared: Option<String>,
+}
+
+/// Idle timeouts in seconds. The hub runs one culler per group that only
+/// sees that group's members, next to the fallback culler for everyone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CullingPolicy {
+    pub timeout: Option<u64>,
+    pub every: Option<u64>,
+    pub groups: Vec<(String, u64)>,
+}
+
+pub fn load(path: &Path) -> Result<Answers> {
+    let raw = util::read_to_string(path)?;
+    parse(&raw).with_context(|| format!("failed to parse answers file {}", path.display()))
+}
+
+pub fn parse(raw: &str) -> Result<Answers> {
+    let answers = toml::from_str(raw)?;
+    Ok(answers)
+}
+
+impl CullingAnswers {
+    pub fn resolve(&self) -> Result<CullingPolicy> {
+        let seconds = |key: &str, value: &str| -> Result<u64> {
+            let seconds = util::parse_duration(value).with_context(|| format!("invalid culling {}", key))?;
+            if seconds == 0 {
+                return Err(failure::validation(format!("culling {} must be greater than zero", key)));
+            }
+            Ok(seconds)
+        };
+        let mut groups = Vec::new();
+        for (group, policy) in &self.groups {
+            validate_group("culling group", group)?;
+            groups.push((group.clone(), seconds(&format!("timeout of group {}", group), &policy.timeout)?));
+        }
+        Ok(CullingPolicy {
+            timeout: self.timeout.as_deref().map(|value| seconds("timeout", value)).transpose()?,
+            every: self.every.as_deref().map(|value| seconds("interval", value)).transpose()?,
+            groups,
+        })
+    }
+}
+
+/// Group names end up in service names and role scopes of the hub config,
+/// and in mount paths.
+pub fn validate_group(kind: &str, name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') {
+        return Err(failure::validation(format!("{} '{}' may only contain letters, digits, '-' and '_'", kind, name)));
+    }
+    Ok(())
+}
+
+/// The `[groups]` section, checked so that it survives the trip through `.env`.
+pub fn resolve_groups(groups: &BTreeMap<String, GroupAnswers>) -> Result<Vec<HubGroup>> {
+    let mut resolved = Vec::new();
+    for (name, group) in groups {
+        validate_group("group", name)?;
+        for member in &group.members {
+            users::validate_name(member)?;
+            if member.contains(':') {
+                return Err(failure::validation(format!("Invalid user name '{}' in group {}", member, name)));
+            }
+        }
+        if let Some(shared) = &group.shared {
+            if shared.is_empty() || shared.contains([',', '=']) {
+                return Err(failure::validation(format!(
+                    "shared directory '{}' of group {} may not be empty or contain ',' or '='",
+                    shared, name
+                )));
+            }
+        }
+        resolved.push(HubGroup {
+            name: name.clone(),
+            members: group.members.clone(),
+            shared: group.shared.clone(),
+        });
+    }
+    Ok(resolved)
+}
+
+/// `CULL_GROUPS` in `.env`: `students=3600,pis=43200`.
+pub fn format_cull_groups(groups: &[(String, u64)]) -> String {
+    groups
+        .iter()
+        .map(|(group, timeout)| format!("{}={}", group, timeout))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn parse_cull_groups(value: &str) -> Result<Vec<(String, u64)>> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (group, timeout) = entry.split_once('=').with_context(|| format!("invalid CULL_GROUPS entry '{}'", entry))?;
+            let timeout = timeout
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid CULL_GROUPS timeout '{}'", timeout))?;
+            Ok((group.trim().to_string(), timeout))
+        })
+        .collect()
+}
+
+/// `GROUP_MEMBERS` in `.env`: `sea-ice=alice:bob,pis=carol`.
+pub fn format_group_members(groups: &[HubGroup]) -> String {
+    groups
+        .iter()
+        .map(|group| format!("{}={}", group.name, group.members.join(":")))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// `GROUP_SHARES` in `.env`: `sea-ice=/srv/groups/sea-ice`, for the groups
+/// that have a shared directory.
+pub fn format_group_shares(groups: &[HubGroup]) -> String {
+    groups
+        .iter()
+        .filter_map(|group| Some(format!("{}={}", group.name, group.shared.as_deref()?)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn parse_groups(members: &str, shares: &str) -> Result<Vec<HubGroup>> {
+    let entries = |value: &str, key: &str| -> Result<Vec<(String, String)>> {
+        value
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let (group, value) = entry.split_once('=').with_context(|| format!("invalid {} entry '{}'", key, entry))?;
+                Ok((group.trim().to_string(), value.trim().to_string()))
+            })
+            .collect()
+    };
+    let mut groups: Vec<HubGroup> = entries(members, "GROUP_MEMBERS")?
+        .into_iter()
+        .map(|(name, members)| HubGroup {
+            name,
+            members: members.split(':').filter(|member| !member.is_empty()).map(str::to_string).collect(),
+            shared: None,
+        })
+        .collect();
+    for (name, shared) in entries(shares, "GROUP_SHARES")? {
+        let group = groups
+            .iter_mut()
+            .find(|group| group.name == name)
+            .with_context(|| format!("GROUP_SHARES names group {}, which GROUP_MEMBERS does not list", name))?;
+        group.shared = Some(shared);
+    }
+    Ok(groups)
+}
+
+impl LimitsAnswers {
+    pub fn resolve(&self) -> Result<ResourceLimits> {
+        let resolve = |kind: &str, name: &String, limit: &LimitAnswers| -> Result<ResourceLimit> {
+            if let Some(cpu) = limit.cpu {
+                if !cpu.is_finite() || cpu <= 0.0 {
+                    return Err(failure::validation(format!("cpu of {} {} must be greater than zero", kind, name)));
+                }
+            }
+            if let Some(mem) = &limit.mem {
+                validate_memory(mem).with_context(|| format!("invalid mem of {} {}", kind, name))?;
+            }
+            if limit.cpu.is_none() && limit.mem.is_none() {
+                return Err(failure::validation(format!("{} {} sets neither cpu nor mem", kind, name)));
+            }
+            Ok(ResourceLimit {
+                name: name.clone(),
+                cpu: limit.cpu,
+                mem: limit.mem.clone(),
+            })
+        };
+        let mut limits = ResourceLimits::default();
+        for (name, limit) in &self.groups {
+            validate_group("limits group", name)?;
+            limits.groups.push(resolve("group", name, limit)?);
+        }
+        for (name, limit) in &self.users {
+            users::validate_name(name)?;
+            if name.contains(':') {
+                return Err(failure::validation(format!("Invalid user name '{}' in [limits]", name)));
+            }
+            limits.users.push(resolve("user", name, limit)?);
+        }
+        Ok(limits)
+    }
+}
+
+/// Memory sizes as JupyterHub reads them: a number, optionally with a K,
+/// M, G, or T suffix.
+pub fn validate_memory(value: &str) -> Result<()> {
+    let number = value.strip_suffix(['K', 'M', 'G', 'T']).unwrap_or(value);
+    if !number.parse::<f64>().is_ok_and(|number| number.is_finite() && number > 0.0) {
+        return Err(failure::validation(format!(
+            "'{}' is not a memory size such as 512M or 32G",
+            value
+        )));
+    }
+    Ok(())
+}
+
+/// `USER_LIMITS` and `GROUP_LIMITS` in `.env`: `alice=16:64G,bob=:8G`.
+pub fn format_limits(limits: &[ResourceLimit]) -> String {
+    limits
+        .iter()
+        .map(|limit| {
+            let cpu = limit.cpu.map(|cpu| cpu.to_string()).unwrap_or_default();
+            format!("{}={}:{}", limit.name, cpu, limit.mem.as_deref().unwrap_or(""))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn parse_limits(value: &str, key: &str) -> Result<Vec<ResourceLimit>> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (name, spec) = entry.split_once('=').with_context(|| format!("invalid {} entry '{}'", key, entry))?;
+            let (cpu, mem) = spec.split_once(':').with_context(|| format!("invalid {} entry '{}'", key, entry))?;
+            let cpu = cpu.trim();
+            Ok(ResourceLimit {
+                name: name.trim().to_string(),
+                cpu: if cpu.is_empty() {
+                    None
+                } else {
+                    Some(cpu.parse().with_context(|| format!("invalid {} cpu '{}'", key, cpu))?)
+                },
+                mem: Some(mem.trim().to_string()).filter(|mem| !mem.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// The `[extra_services]` section, checked so that it renders into a valid
+/// compose file next to the generated services.
+pub fn validate_extra_services(services: &BTreeMap<String, ExtraService>) -> Result<()> {
+    for (name, service) in services {
+        let valid_name = name.chars().next().is_some_and(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit())
+            && name.chars().all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-' || ch == '_');
+        if !valid_name {
+            return Err(failure::validation(format!(
+                "extra service '{}' may only contain lowercase letters, digits, '-' and '_'",
+                name
+            )));
+        }
+        if templates::GENERATED_SERVICES.contains(&name.as_str()) {
+            return Err(failure::validation(format!(
+                "extra service {} would replace a service of the deployment; pick another name",
+                name
+            )));
+        }
+        if service.image.trim().is_empty() || service.image.contains(char::is_whitespace) {
+            return Err(failure::validation(format!("extra service {} needs an image", name)));
+        }
+        for key in service.environment.keys() {
+            let valid = key.chars().next().is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+                && key.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+            if !valid {
+                return Err(failure::validation(format!(
+                    "'{}' in extra service {} is not a valid variable name",
+                    key, name
+                )));
+            }
+        }
+        if service.volumes.iter().any(|volume| volume.trim().is_empty()) {
+            return Err(failure::validation(format!("extra service {} has an empty volume", name)));
+        }
+        for network in service.networks.iter().flatten() {
+            if !EXTRA_SERVICE_NETWORKS.contains(&network.as_str()) {
+                return Err(failure::validation(format!(
+                    "extra service {} may only join the {} networks, not {}",
+                    name,
+                    EXTRA_SERVICE_NETWORKS.join(", "),
+                    network
+                )));
+            }
+        }
+        if let Some(label) = service.labels.iter().find(|label| !label.contains('=')) {
+            return Err(failure::validation(format!(
+                "label '{}' of extra service {} is not key=value",
+                label, name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Contents of `EXTRA_SERVICES_FILE`, an answers file with only the
+/// `[extra_services]` section.
+pub fn format_extra_services(services: &BTreeMap<String, ExtraService>) -> String {
+    #[derive(Serialize)]
+    struct File<'a> {
+        extra_services: &'a BTreeMap<String, ExtraService>,
+    }
+    // Strings, lists, and tables of strings always serialize.
+    let body = toml::to_string(&File { extra_services: services }).expect("extra services serialize");
+    format!(
+        "# Services appended to docker-compose.yml; replaced by the [extra_services]\n\
+         # section of the next --answers file. EXTRA_SERVICES in .env names the ones used.\n\n{}",
+        body
+    )
+}
+
+/// The extra services `names` of the deployment at `deploy_dir`.
+pub fn load_extra_services(deploy_dir: &Path, names: &[String]) -> Result<BTreeMap<String, ExtraService>> {
+    if names.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let path = deploy_dir.join(EXTRA_SERVICES_FILE);
+    let mut services = load(&path)
+        .map_err(|err| failure::config(format!("{:#}", err)))?
+        .extra_services
+        .unwrap_or_default();
+    validate_extra_services(&services)?;
+    services.retain(|name, _| names.contains(name));
+    if let Some(missing) = names.iter().find(|name| !services.contains_key(*name)) {
+        return Err(failure::config(format!(
+            "EXTRA_SERVICES names {}, which {} does not define",
+            missing,
+            path.display()
+        )));
+    }
+    Ok(services)
+}
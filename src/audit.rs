@@ -0,0 +1,190 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::AuditOptions, config::AppConfig, say, services, util};
+
+pub const AUDIT_FILE: &str = "audit.log";
+
+static CHANGED_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub user: String,
+    pub command: String,
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deploy_dir: Option<String>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub files_changed: Vec<String>,
+}
+
+/// Notes a file written during the current invocation; called from `util::atomic_write`.
+pub fn record_file(path: &Path) {
+    if let Ok(mut files) = CHANGED_FILES.lock() {
+        files.push(path.to_path_buf());
+    }
+}
+
+/// Files written so far during the current invocation.
+pub fn changed_files() -> Vec<PathBuf> {
+    CHANGED_FILES.lock().map(|files| files.clone()).unwrap_or_default()
+}
+
+/// Appends an entry for the current invocation to the config-dir log and, when
+/// known, the deployment's own log.
+pub fn record(command: &str, outcome: &Result<()>, deploy_dir: Option<&Path>) -> Result<()> {
+    let files_changed = CHANGED_FILES
+        .lock()
+        .map(|files| files.iter().map(|path| util::path_display(path)).collect())
+        .unwrap_or_default();
+
+    let entry = AuditEntry {
+        timestamp: util::format_timestamp(util::unix_now()),
+        user: whoami::username(),
+        command: command.to_string(),
+        args: redact_args(std::env::args().skip(1)),
+        deploy_dir: deploy_dir.map(util::path_display),
+        success: outcome.is_ok(),
+        error: outcome.as_ref().err().map(|err| format!("{:#}", err)),
+        files_changed,
+    };
+    append_entry(&entry, deploy_dir)
+}
+
+/// Records something a long-running command did on its own, such as a
+/// restart by `watch`, as `<command> <args>`.
+pub fn record_event(command: &str, args: &[&str], error: Option<String>, deploy_dir: &Path) -> Result<()> {
+    let entry = AuditEntry {
+        timestamp: util::format_timestamp(util::unix_now()),
+        user: whoami::username(),
+        command: command.to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        deploy_dir: Some(util::path_display(deploy_dir)),
+        success: error.is_none(),
+        error,
+        files_changed: Vec::new(),
+    };
+    append_entry(&entry, Some(deploy_dir))
+}
+
+fn append_entry(entry: &AuditEntry, deploy_dir: Option<&Path>) -> Result<()> {
+    let line = serde_json::to_string(entry).context("failed to serialize audit entry")?;
+
+    append_line(&config_log_path()?, &line)?;
+    if let Some(dir) = deploy_dir.filter(|dir| dir.exists()) {
+        append_line(&dir.join(AUDIT_FILE), &line)?;
+    }
+    Ok(())
+}
+
+pub fn show(opts: AuditOptions, app_config: &AppConfig) -> Result<()> {
+    let path = if opts.deployment {
+        services::resolve_deploy_dir(app_config)?.join(AUDIT_FILE)
+    } else {
+        config_log_path()?
+    };
+    let entries = read_entries(&path)?;
+    let skip = opts.limit.map(|limit| entries.len().saturating_sub(limit)).unwrap_or(0);
+
+    if util::json_output() {
+        util::set_result(serde_json::json!({ "entries": &entries[skip..] }));
+        return Ok(());
+    }
+
+    for entry in entries.iter().skip(skip) {
+        if opts.json {
+            say!("{}", serde_json::to_string(entry)?);
+            continue;
+        }
+        let status = if entry.success {
+            style("ok").green()
+        } else {
+            style("failed").red()
+        };
+        say!(
+            "{}  {:<10} {:<8} {:<6} {}",
+            style(&entry.timestamp).dim(),
+            entry.user,
+            entry.command,
+            status,
+            entry.args.join(" ")
+        );
+        if let Some(dir) = &entry.deploy_dir {
+            say!("    deployment: {}", dir);
+        }
+        if let Some(error) = &entry.error {
+            say!("    {}", style(error).red());
+        }
+        for file in &entry.files_changed {
+            say!("    {}", style(file).dim());
+        }
+    }
+    Ok(())
+}
+
+pub fn read_entries(path: &Path) -> Result<Vec<AuditEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    util::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).with_context(|| format!("corrupt audit entry in {}", path.display())))
+        .collect()
+}
+
+fn config_log_path() -> Result<PathBuf> {
+    let config_path = crate::config::resolve_config_path()?;
+    let dir = config_path.parent().context("config path has no parent directory")?;
+    Ok(dir.join(AUDIT_FILE))
+}
+
+fn append_line(path: &Path, line: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        util::ensure_dir(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open audit log {}", path.display()))?;
+    writeln!(file, "{}", line).with_context(|| format!("failed to append to {}", path.display()))
+}
+
+/// Masks values of flags, and of `NAME=VALUE` arguments, that carry credentials.
+pub fn redact_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let sensitive = |flag: &str| {
+        let flag = flag.to_ascii_lowercase();
+        ["secret", "password", "token"].iter().any(|word| flag.contains(word))
+    };
+    let mut redacted = Vec::new();
+    let mut mask_next = false;
+    for arg in args {
+        if mask_next {
+            redacted.push("***".to_string());
+            mask_next = false;
+        } else if let Some((flag, _)) = arg.split_once('=') {
+            if sensitive(flag) {
+                redacted.push(format!("{}=***", flag));
+            } else {
+                redacted.push(arg);
+            }
+        } else {
+            mask_next = arg.starts_with("--") && sensitive(&arg);
+            redacted.push(arg);
+        }
+    }
+    redacted
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    cli::{BackupCommand, BackupCreateOptions, BackupPruneOptions, BackupTarget, ServerSideEncryption},
+    config::AppConfig,
+    failure, lock,
+    progress::Spinner,
+    repository::Repository,
+    say, services, snapshot, util,
+};
+
+const NAME_PREFIX: &str = "mvre-hub-";
+const NAME_SUFFIX: &str = ".tar.gz";
+/// Database dump location inside the deployment while a repository backup runs.
+const REPOSITORY_STAGING_DIR: &str = ".mvre-backup";
+
+/// Where backups are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    Local(PathBuf),
+    /// Bucket and key prefix (without trailing slash); written with the aws CLI.
+    S3 { bucket: String, prefix: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Backup {
+    pub name: String,
+    pub created_at: u64,
+}
+
+/// Keep the newest backup of each of the last `daily` days and `weekly`
+/// weeks that have backups.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub daily: u32,
+    pub weekly: u32,
+}
+
+pub fn run(command: BackupCommand, app_config: &AppConfig) -> Result<()> {
+    match command {
+        BackupCommand::Init(target) => init(&target),
+        BackupCommand::Create(opts) => create(opts, app_config),
+        BackupCommand::List(target) => list(&target),
+        BackupCommand::Prune(opts) => prune(opts),
+    }
+}
+
+fn init(target: &BackupTarget) -> Result<()> {
+    let repository = Repository::parse(&target.dest)
+        .context("Only restic: and borg: destinations need initialising")?;
+    repository.init(target)?;
+    say!("{}", style(format!("Initialised {} repository {}", repository.program(), repository.location)).green());
+    util::set_result(serde_json::json!({ "repository": target.dest }));
+    Ok(())
+}
+
+fn create(opts: BackupCreateOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    if let Some(repository) = Repository::parse(&opts.target.dest) {
+        return create_in_repository(&repository, &deploy_dir, &opts);
+    }
+    let destination = Destination::parse(&opts.target.dest)?;
+    let policy = opts.retention.policy()?;
+    let backup = Backup::new(util::unix_now());
+
+    let staging = std::env::temp_dir().join(format!("mvre-hub-backup-{}", util::random_token(6)?));
+    let db_dir = staging.join("db");
+    util::ensure_dir(&db_dir)?;
+    let result = snapshot::dump_database(&deploy_dir, &db_dir)
+        .and_then(|_| write_archive(&deploy_dir, &staging, &destination, &backup.name, &opts.target));
+    fs::remove_dir_all(&staging).ok();
+    result?;
+    say!("{} {}", style("Backup written:").green(), destination.location(&backup.name));
+
+    let removed = match policy {
+        Some(policy) => apply_retention(&destination, policy, &opts.target, false)?,
+        None => Vec::new(),
+    };
+    util::set_result(serde_json::json!({
+        "backup": destination.location(&backup.name),
+        "removed": removed,
+    }));
+    Ok(())
+}
+
+/// Repository backups are incremental: only changed chunks of the hub data,
+/// user volumes, and workspaces are stored.
+fn create_in_repository(repository: &Repository, deploy_dir: &Path, opts: &BackupCreateOptions) -> Result<()> {
+    let policy = opts.retention.policy()?;
+    let backup = Backup::new(util::unix_now());
+    let name = backup.name.trim_end_matches(NAME_SUFFIX);
+
+    let staging = deploy_dir.join(REPOSITORY_STAGING_DIR);
+    let db_dir = staging.join("db");
+    util::ensure_dir(&db_dir)?;
+    let result = snapshot::dump_database(deploy_dir, &db_dir).and_then(|_| {
+        let paths = crate::repository::backup_paths(deploy_dir)?;
+        let excluded = [deploy_dir.join(snapshot::SNAPSHOT_DIR), deploy_dir.join(lock::LOCK_FILE)];
+        repository.backup(&opts.target, name, &paths, &excluded)
+    });
+    fs::remove_dir_all(&staging).ok();
+    result?;
+    say!("{} {} ({})", style("Backup written:").green(), name, repository.location);
+
+    if let Some(policy) = policy {
+        repository.prune(&opts.target, policy, false)?;
+    }
+    util::set_result(serde_json::json!({
+        "backup": name,
+        "repository": opts.target.dest,
+        "pruned": policy.is_some(),
+    }));
+    Ok(())
+}
+
+fn list(target: &BackupTarget) -> Result<()> {
+    let backups = match Repository::parse(&target.dest) {
+        Some(repository) => repository.list(target)?,
+        None => Destination::parse(&target.dest)?.list(target)?,
+    };
+    if backups.is_empty() {
+        say!("{}", style("No backups").dim());
+    }
+    for backup in &backups {
+        say!("  {:<40} {}", backup.name, util::format_timestamp(backup.created_at));
+    }
+    util::set_result(serde_json::json!({ "backups": backups }));
+    Ok(())
+}
+
+fn prune(opts: BackupPruneOptions) -> Result<()> {
+    let policy = opts
+        .retention
+        .policy()?
+        .context("Pass --keep-daily and/or --keep-weekly")?;
+    if let Some(repository) = Repository::parse(&opts.target.dest) {
+        repository.prune(&opts.target, policy, opts.dry_run)?;
+        util::set_result(serde_json::json!({ "repository": opts.target.dest, "dry_run": opts.dry_run }));
+        return Ok(());
+    }
+    let destination = Destination::parse(&opts.target.dest)?;
+    let removed = apply_retention(&destination, policy, &opts.target, opts.dry_run)?;
+    if removed.is_empty() {
+        say!("{}", style("Nothing to prune").dim());
+    }
+    util::set_result(serde_json::json!({ "removed": removed, "dry_run": opts.dry_run }));
+    Ok(())
+}
+
+fn apply_retention(
+    destination: &Destination,
+    policy: RetentionPolicy,
+    target: &BackupTarget,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let backups = destination.list(target)?;
+    let mut removed = Vec::new();
+    for backup in expired(&backups, policy) {
+        if dry_run {
+            say!("Would remove {}", destination.location(&backup.name));
+        } else {
+            destination.delete(&backup.name, target)?;
+            say!("Removed {}", style(destination.location(&backup.name)).dim());
+        }
+        removed.push(backup.name.clone());
+    }
+    Ok(removed)
+}
+
+/// Backups the policy does not keep, oldest first.
+pub fn expired(backups: &[Backup], policy: RetentionPolicy) -> Vec<&Backup> {
+    let mut newest_first: Vec<&Backup> = backups.iter().collect();
+    newest_first.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    let mut expired = Vec::new();
+    for backup in newest_first {
+        let day = backup.created_at / 86_400;
+        // Weeks start on Monday; day 0 (1970-01-01) was a Thursday.
+        let week = (day + 3) / 7;
+        let keep_daily = days.len() < policy.daily as usize && days.insert(day);
+        let keep_weekly = weeks.len() < policy.weekly as usize && weeks.insert(week);
+        if !keep_daily && !keep_weekly {
+            expired.push(backup);
+        }
+    }
+    expired.reverse();
+    expired
+}
+
+/// Archives the deployment directory (configuration and `jupyterhub_data`,
+/// not snapshots) together with the database dump in `staging/db`.
+fn write_archive(
+    deploy_dir: &Path,
+    staging: &Path,
+    destination: &Destination,
+    name: &str,
+    target: &BackupTarget,
+) -> Result<()> {
+    let _spinner = Spinner::start("Writing backup");
+    let mut tar = Command::new("tar");
+    let partial = match destination {
+        Destination::Local(dir) => {
+            util::ensure_dir(dir)?;
+            Some(dir.join(format!("{}.partial", name)))
+        }
+        Destination::S3 { .. } => None,
+    };
+    tar.arg("-czf")
+        .arg(partial.as_deref().unwrap_or(Path::new("-")))
+        .arg("-C")
+        .arg(deploy_dir)
+        .arg(format!("--exclude=./{}", snapshot::SNAPSHOT_DIR))
+        .arg(format!("--exclude=./{}", lock::LOCK_FILE))
+        .arg(".")
+        .arg("-C")
+        .arg(staging)
+        .arg("db");
+
+    match destination {
+        Destination::Local(dir) => {
+            let partial = partial.context("local backup without a target file")?;
+            if let Err(err) = run_tool(&mut tar, "tar") {
+                fs::remove_file(&partial).ok();
+                return Err(err);
+            }
+            util::set_file_mode(&partial, 0o600).ok();
+            let dest = dir.join(name);
+            fs::rename(&partial, &dest).with_context(|| format!("failed to write {}", dest.display()))
+        }
+        Destination::S3 { .. } => {
+            let mut tar = tar.stdout(Stdio::piped()).spawn().context("failed to run tar")?;
+            let archive = tar.stdout.take().context("failed to read tar output")?;
+            // Streamed uploads are multipart; the expected size lets the aws
+            // CLI pick parts large enough for archives beyond 50 GB.
+            let expected = util::dir_size(deploy_dir).to_string();
+            let mut upload = s3_command(target);
+            upload
+                .args(["cp", "-", &destination.location(name), "--expected-size", &expected])
+                .args(sse_args(target))
+                .stdin(archive);
+            let uploaded = run_tool(&mut upload, "aws s3 cp");
+            let archived = tar.wait().context("failed to wait for tar")?;
+            uploaded?;
+            if !archived.success() {
+                anyhow::bail!("tar exited with status {}", archived);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Server-side encryption flags for uploads.
+pub fn sse_args(target: &BackupTarget) -> Vec<String> {
+    match target.sse {
+        None => Vec::new(),
+        Some(ServerSideEncryption::Aes256) => vec!["--sse".to_string(), "AES256".to_string()],
+        Some(ServerSideEncryption::Kms) => {
+            let mut args = vec!["--sse".to_string(), "aws:kms".to_string()];
+            if let Some(key) = &target.sse_kms_key_id {
+                args.extend(["--sse-kms-key-id".to_string(), key.clone()]);
+            }
+            args
+        }
+    }
+}
+
+fn s3_command(target: &BackupTarget) -> Command {
+    let mut command = Command::new("aws");
+    if let Some(endpoint) = &target.endpoint_url {
+        command.args(["--endpoint-url", endpoint]);
+    }
+    command.args(["s3"]);
+    command
+}
+
+impl Destination {
+    /// `s3://bucket/prefix` or a local directory.
+    pub fn parse(value: &str) -> Result<Self> {
+        let Some(rest) = value.strip_prefix("s3://") else {
+            return Ok(Destination::Local(PathBuf::from(value)));
+        };
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(failure::validation(format!("Invalid S3 destination '{}' (expected s3://bucket/prefix)", value)));
+        }
+        Ok(Destination::S3 {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+
+    /// Path or URL of the backup called `name`.
+    pub fn location(&self, name: &str) -> String {
+        match self {
+            Destination::Local(dir) => dir.join(name).display().to_string(),
+            Destination::S3 { bucket, prefix } if prefix.is_empty() => format!("s3://{}/{}", bucket, name),
+            Destination::S3 { bucket, prefix } => format!("s3://{}/{}/{}", bucket, prefix, name),
+        }
+    }
+
+    pub fn list(&self, target: &BackupTarget) -> Result<Vec<Backup>> {
+        let names: Vec<String> = match self {
+            Destination::Local(dir) if !dir.exists() => Vec::new(),
+            Destination::Local(dir) => fs::read_dir(dir)
+                .with_context(|| format!("failed to read {}", dir.display()))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect(),
+            Destination::S3 { .. } => {
+                let output = s3_command(target)
+                    .args(["ls", &self.location("")])
+                    .output()
+                    .context("failed to run aws; it is required for S3 backups")?;
+                if !output.status.success() {
+                    anyhow::bail!("aws s3 ls failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+                }
+                parse_s3_listing(&String::from_utf8_lossy(&output.stdout))
+            }
+        };
+        let mut backups: Vec<Backup> = names.iter().filter_map(|name| Backup::from_name(name)).collect();
+        backups.sort_by_key(|backup| backup.created_at);
+        Ok(backups)
+    }
+
+    /// Copies `file` to the destination as `name`, readable by its owner only
+    /// when local.
+    pub fn store(&self, file: &Path, name: &str, target: &BackupTarget) -> Result<()> {
+        match self {
+            Destination::Local(dir) => {
+                util::ensure_dir(dir)?;
+                let partial = dir.join(format!("{}.partial", name));
+                if let Err(err) = fs::copy(file, &partial) {
+                    fs::remove_file(&partial).ok();
+                    return Err(err).with_context(|| format!("failed to write {}", partial.display()));
+                }
+                util::set_file_mode(&partial, 0o600).ok();
+                let dest = dir.join(name);
+                fs::rename(&partial, &dest).with_context(|| format!("failed to write {}", dest.display()))
+            }
+            Destination::S3 { .. } => {
+                let mut upload = s3_command(target);
+                upload
+                    .arg("cp")
+                    .arg(file)
+                    .arg(self.location(name))
+                    .args(sse_args(target));
+                run_tool(&mut upload, "aws s3 cp")
+            }
+        }
+    }
+
+    fn delete(&self, name: &str, target: &BackupTarget) -> Result<()> {
+        match self {
+            Destination::Local(dir) => {
+                let path = dir.join(name);
+                fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))
+            }
+            Destination::S3 { .. } => run_tool(s3_command(target).args(["rm", &self.location(name)]), "aws s3 rm"),
+        }
+    }
+}
+
+/// Object names from `aws s3 ls` output (`date time size name` per line).
+pub fn parse_s3_listing(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _date = fields.next()?;
+            let _time = fields.next()?;
+            fields.next()?.parse::<u64>().ok()?;
+            fields.next().map(str::to_string)
+        })
+        .collect()
+}
+
+impl Backup {
+    pub fn new(created_at: u64) -> Self {
+        let stamp = util::format_timestamp(created_at).replace([':', '-'], "");
+        Self {
+            name: format!("{}{}{}", NAME_PREFIX, stamp, NAME_SUFFIX),
+            created_at,
+        }
+    }
+
+    /// Parses names written by [`Backup::new`], with or without the archive
+    /// suffix (borg archives have none); anything else is ignored.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let stamp = name.strip_prefix(NAME_PREFIX)?;
+        let stamp = stamp.strip_suffix(NAME_SUFFIX).unwrap_or(stamp);
+        let created_at = util::parse_timestamp(stamp)?;
+        Some(Self {
+            name: name.to_string(),
+            created_at,
+        })
+    }
+}
+
+fn run_tool(command: &mut Command, name: &str) -> Result<()> {
+    let output = command.output().with_context(|| format!("failed to run {}", name))?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed: {}", name, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+impl crate::cli::RetentionOptions {
+    /// `None` when no retention was asked for; keeping nothing is refused.
+    pub fn policy(&self) -> Result<Option<RetentionPolicy>> {
+        if self.keep_daily.is_none() && self.keep_weekly.is_none() {
+            return Ok(None);
+        }
+        let policy = RetentionPolicy {
+            daily: self.keep_daily.unwrap_or(0),
+            weekly: self.keep_weekly.unwrap_or(0),
+        };
+        if policy.daily == 0 && policy.weekly == 0 {
+            return Err(failure::validation("The retention policy would remove every backup; keep at least one daily or weekly backup"));
+        }
+        Ok(Some(policy))
+    }
+}
@@ -0,0 +1,217 @@
+use std::{path::Path, thread, time::Duration};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    compose,
+    engine::Engine,
+    envfile, http,
+    progress::Spinner,
+    say, util,
+};
+
+/// Compose file of the temporary hub; removed once the switch is done.
+pub const GREEN_FILE: &str = "docker-compose.green.yml";
+/// Loopback port the new hub is smoke-tested on.
+pub const DEFAULT_PORT: u16 = 18000;
+const HUB_SERVICE: &str = "jupyterhub";
+const HUB_PORT: u16 = 8000;
+const READY_TIMEOUT_SECS: u64 = 180;
+const POLL_SECS: u64 = 3;
+
+pub fn green_project(project: &str) -> String {
+    format!("{}-green", project)
+}
+
+/// Hub health endpoint as seen from the host or from inside a hub container.
+pub fn health_url(internal_tls: bool, host: &str, port: u16) -> String {
+    let scheme = if internal_tls { "https" } else { "http" };
+    format!("{}://{}:{}/hub/health", scheme, host, port)
+}
+
+/// Python one-liner probing the hub from inside its container; the hub
+/// image has no curl.
+pub fn health_script(internal_tls: bool) -> String {
+    format!(
+        "import ssl, urllib.request; urllib.request.urlopen('{}', context=ssl._create_unverified_context(), timeout=5)",
+        health_url(internal_tls, "localhost", HUB_PORT)
+    )
+}
+
+/// Compose file running a copy of the deployment's hub, on the freshly built
+/// image, under its own project. It joins the live stack's networks and
+/// carries the same Traefik labels, but Traefik only routes to it once its
+/// health check passes.
+pub fn green_compose(raw: &str, project: &str, port: u16, internal_tls: bool) -> Result<String> {
+    let file: Value = serde_yaml::from_str(raw).context("failed to parse the compose file")?;
+    let mut hub = file
+        .get("services")
+        .and_then(|services| services.get(HUB_SERVICE))
+        .cloned()
+        .context("the compose file has no jupyterhub service")?;
+    let attached: compose::Service = serde_yaml::from_value(hub.clone()).context("invalid jupyterhub service")?;
+    let service = hub.as_mapping_mut().context("the jupyterhub service is not a mapping")?;
+    service.remove("build");
+    service.remove("depends_on");
+    service.insert("image".into(), format!("{}-{}", project, HUB_SERVICE).into());
+    service.insert(
+        "ports".into(),
+        Value::Sequence(vec![format!("127.0.0.1:{}:{}", port, HUB_PORT).into()]),
+    );
+    let probe = health_script(internal_tls);
+    let mut healthcheck = Mapping::new();
+    healthcheck.insert(
+        "test".into(),
+        Value::Sequence(vec!["CMD".into(), "python3".into(), "-c".into(), probe.into()]),
+    );
+    healthcheck.insert("interval".into(), "5s".into());
+    healthcheck.insert("timeout".into(), "5s".into());
+    healthcheck.insert("retries".into(), 3.into());
+    healthcheck.insert("start_period".into(), "10s".into());
+    service.insert("healthcheck".into(), Value::Mapping(healthcheck));
+
+    // The live stack owns the networks; unnamed ones carry its project prefix.
+    let mut networks = Mapping::new();
+    for name in attached.network_names() {
+        let declared = file.get("networks").and_then(|networks| networks.get(name.as_str()));
+        let actual = declared
+            .and_then(|network| network.get("name"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}_{}", project, name));
+        let mut network = Mapping::new();
+        network.insert("name".into(), actual.into());
+        network.insert("external".into(), true.into());
+        networks.insert(name.into(), Value::Mapping(network));
+    }
+
+    let mut services = Mapping::new();
+    services.insert(HUB_SERVICE.into(), hub);
+    let mut green = Mapping::new();
+    green.insert("services".into(), Value::Mapping(services));
+    green.insert("networks".into(), Value::Mapping(networks));
+    serde_yaml::to_string(&Value::Mapping(green)).context("failed to render the green compose file")
+}
+
+/// Moves the hub to the freshly built image without a gap in service: a
+/// second hub is started next to the live one and smoke-tested, Traefik
+/// routes to both, then the old hub is replaced while the new one serves,
+/// and the temporary one is retired.
+pub fn switch(engine: &Engine, port: u16) -> Result<()> {
+    let deploy_dir = engine.deploy_dir();
+    let env = envfile::load(deploy_dir)?;
+    let project = compose::project_name(deploy_dir, &env);
+    let green = green_project(&project);
+    let internal_tls = envfile::get(&env, "INTERNAL_TLS") == Some("true");
+
+    let raw = util::read_to_string(&deploy_dir.join(compose::COMPOSE_FILE))?;
+    util::write_string(&deploy_dir.join(GREEN_FILE), &green_compose(&raw, &project, port, internal_tls)?)?;
+    // Only the generated file: the operator's override may name services the
+    // green project must not start. The hub that replaces the live one does
+    // get the override.
+    let green_args = |args: &[&'static str]| {
+        let mut all = vec!["-p", green.as_str(), "-f", GREEN_FILE];
+        all.extend_from_slice(args);
+        all
+    };
+    let retire = || {
+        let result = engine.compose_output(&green_args(&["down", "--remove-orphans"]));
+        std::fs::remove_file(deploy_dir.join(GREEN_FILE)).ok();
+        result.map(|_| ())
+    };
+
+    let started = engine
+        .compose_step(&green_args(&["up", "-d"]), "Starting the new hub")
+        .context("failed to start the new hub next to the running one");
+    let smoke = started.and_then(|_| {
+        let url = health_url(internal_tls, "127.0.0.1", port);
+        wait_for(&format!("Smoke-testing {}", url), || {
+            probe(&url).and_then(|_| container_healthy(engine, &green_args(&["ps", "-q", HUB_SERVICE])))
+        })
+        .context("the new hub failed its smoke test; the running hub was left untouched")
+    });
+    if let Err(err) = smoke {
+        if let Ok(logs) = engine.compose_output(&green_args(&["logs", "--tail", "20", HUB_SERVICE])) {
+            eprintln!("{}", style(logs.trim_end()).dim());
+        }
+        retire().ok();
+        return Err(err);
+    }
+    say!("{}", style("New hub passed its smoke test and is taking traffic").green());
+
+    // From here on the new hub serves every request while the old one is replaced.
+    engine
+        .compose_output(&["up", "-d", "--no-deps", HUB_SERVICE])
+        .with_context(|| {
+            format!(
+                "failed to replace the old hub; the new hub keeps serving, retire it with docker-compose -p {} -f {} down once fixed",
+                green, GREEN_FILE
+            )
+        })?;
+    let check = health_script(internal_tls);
+    wait_for("Waiting for the replaced hub", || {
+        engine
+            .compose_output(&["exec", "-T", HUB_SERVICE, "python3", "-c", &check])
+            .map(|_| ())
+    })
+    .context("the replaced hub did not become ready; the new hub keeps serving from the green project")?;
+
+    retire().context("failed to retire the temporary hub")?;
+    say!("{}", style("Switched to the new hub without downtime").green());
+    Ok(())
+}
+
+fn probe(url: &str) -> Result<()> {
+    let response = http::get(
+        url,
+        &http::Options {
+            insecure: true,
+            timeout_secs: 5,
+            ..http::Options::default()
+        },
+    )?;
+    if !response.is_success() {
+        anyhow::bail!("{} returned {}", url, response.status);
+    }
+    Ok(())
+}
+
+/// Traefik skips containers whose health check has not passed yet.
+fn container_healthy(engine: &Engine, ps_args: &[&str]) -> Result<()> {
+    let id = engine.compose_output(ps_args)?;
+    let status = engine.docker_output(&["inspect", "--format", "{{.State.Health.Status}}", id.trim()])?;
+    match status.trim() {
+        "healthy" => Ok(()),
+        other => anyhow::bail!("container health is {}", other),
+    }
+}
+
+fn wait_for(step: &str, mut check: impl FnMut() -> Result<()>) -> Result<()> {
+    let _spinner = Spinner::start(step);
+    let deadline = util::unix_now() + READY_TIMEOUT_SECS;
+    loop {
+        match check() {
+            Ok(()) => return Ok(()),
+            Err(err) if util::unix_now() >= deadline => {
+                return Err(err.context(format!("not ready after {}s", READY_TIMEOUT_SECS)));
+            }
+            Err(_) => thread::sleep(Duration::from_secs(POLL_SECS)),
+        }
+    }
+}
+
+/// Refuses to start over the temporary hub of an interrupted switch.
+pub fn ensure_no_leftover(deploy_dir: &Path) -> Result<()> {
+    if !deploy_dir.join(GREEN_FILE).exists() {
+        return Ok(());
+    }
+    let env = envfile::load(deploy_dir)?;
+    anyhow::bail!(
+        "{} is left over from an interrupted blue/green upgrade; retire that hub with docker-compose -p {} -f {} down and remove the file first",
+        GREEN_FILE,
+        green_project(&compose::project_name(deploy_dir, &env)),
+        GREEN_FILE
+    )
+}
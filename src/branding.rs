@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::{failure, util};
+
+/// Logo, terms of use, and template overrides; mounted read-only into the
+/// hub at [`HUB_BRANDING_DIR`].
+pub const BRANDING_DIR: &str = "branding";
+pub const HUB_BRANDING_DIR: &str = "/etc/mvre-hub/branding";
+pub const TERMS_FILE: &str = "terms.html";
+
+const LOGO_EXTENSIONS: [&str; 6] = ["png", "svg", "jpg", "jpeg", "gif", "webp"];
+
+/// Files given on the command line, copied into the deployment when the
+/// configuration is written.
+#[derive(Debug, Clone, Default)]
+pub struct BrandingSources {
+    pub logo: Option<PathBuf>,
+    pub terms: Option<PathBuf>,
+}
+
+impl BrandingSources {
+    pub fn is_empty(&self) -> bool {
+        self.logo.is_none() && self.terms.is_none()
+    }
+}
+
+/// Name of the logo inside the branding directory, keeping the extension
+/// so the hub serves it with the right content type.
+pub fn logo_name(source: &Path) -> Result<String> {
+    let extension = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .filter(|ext| LOGO_EXTENSIONS.contains(&ext.as_str()))
+        .with_context(|| format!("logo {} must be one of: {}", source.display(), LOGO_EXTENSIONS.join(", ")))?;
+    Ok(format!("logo.{}", extension))
+}
+
+/// Accepts `#rgb` and `#rrggbb`.
+pub fn validate_color(value: &str) -> Result<()> {
+    let digits = value.strip_prefix('#').unwrap_or("");
+    if !matches!(digits.len(), 3 | 6) || !digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Err(failure::validation(format!("invalid color '{}' (use #rrggbb)", value)));
+    }
+    Ok(())
+}
+
+/// Single-line text, as it is stored in `.env`.
+pub fn validate_text(flag: &str, value: &str) -> Result<()> {
+    if value.contains(['\n', '\r']) {
+        return Err(failure::validation(format!("{} must be a single line", flag)));
+    }
+    Ok(())
+}
+
+pub fn install(deploy_dir: &Path, sources: &BrandingSources) -> Result<()> {
+    let dir = deploy_dir.join(BRANDING_DIR);
+    util::ensure_dir(&dir)?;
+    if let Some(logo) = &sources.logo {
+        let target = dir.join(logo_name(logo)?);
+        std::fs::copy(logo, &target).with_context(|| format!("failed to copy logo {}", logo.display()))?;
+    }
+    if let Some(terms) = &sources.terms {
+        let contents = util::read_to_string(terms)?;
+        util::write_string(&dir.join(TERMS_FILE), &contents)?;
+    }
+    Ok(())
+}
+
+/// Template overrides extending JupyterHub's own pages.
+pub fn template_files(deploy_dir: &Path) -> Vec<(PathBuf, String)> {
+    let dir = deploy_dir.join(BRANDING_DIR).join("templates");
+    vec![
+        (dir.join("page.html"), PAGE_TEMPLATE.to_string()),
+        (dir.join("login.html"), LOGIN_TEMPLATE.to_string()),
+    ]
+}
+
+const PAGE_TEMPLATE: &str = r#"{% extends "templates/page.html" %}
+{% block title %}{{ hub_title or super() }}{% endblock %}
+{% block stylesheet %}
+{{ super() }}
+{% if brand_color %}
+<style>
+  :root { --bs-primary: {{ brand_color }}; --jp-brand-color1: {{ brand_color }}; }
+  .btn-jupyter, .btn-primary { background-color: {{ brand_color }}; border-color: {{ brand_color }}; }
+  a, .navbar-brand { color: {{ brand_color }}; }
+</style>
+{% endif %}
+{% endblock %}
+"#;
+
+const LOGIN_TEMPLATE: &str = r#"{% extends "templates/login.html" %}
+{% block login %}
+{% if hub_title %}<h1 class="text-center mt-4">{{ hub_title }}</h1>{% endif %}
+{% if welcome_text %}<p class="text-center lead">{{ welcome_text }}</p>{% endif %}
+{{ super() }}
+{% if terms_of_use %}<div class="container mt-4 small text-muted">{{ terms_of_use | safe }}</div>{% endif %}
+{% endblock %}
+"#;
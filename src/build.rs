@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cli::{BuildOptions, BuildService},
+    compose,
+    config::AppConfig,
+    engine::Engine,
+    envfile::{self, EnvMap},
+    say, services, util,
+};
+
+/// Hashes of the build inputs each image was last built from.
+pub const BUILD_STAMP_FILE: &str = ".mvre-build.json";
+
+/// Buildable compose services and the files their images depend on.
+/// `jupyterhub_config.py` is bind-mounted, so it is deliberately not an input.
+const BUILD_INPUTS: &[(&str, &[&str])] = &[
+    ("jupyterhub", &["hub/Dockerfile"]),
+    (USER_SERVICE, &["user/Dockerfile", "user/requirements.txt"]),
+];
+const USER_SERVICE: &str = "user-image";
+
+pub fn run(opts: BuildOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let engine = Engine::new(&deploy_dir);
+    let selected: Vec<&str> = match opts.service {
+        Some(service) => vec![service.compose_name()],
+        None => BUILD_INPUTS.iter().map(|(service, _)| *service).collect(),
+    };
+
+    build(&engine, &selected, opts.no_cache, opts.pull)?;
+    record(&deploy_dir, &selected)?;
+
+    say!("{}", style(format!("Built {}", selected.join(", "))).green());
+    util::set_result(serde_json::json!({ "built": selected }));
+    Ok(())
+}
+
+/// Builds only the images whose Dockerfiles or requirements changed since the
+/// last recorded build. Returns the services that were rebuilt.
+pub fn build_changed(engine: &Engine) -> Result<Vec<&'static str>> {
+    let deploy_dir = engine.deploy_dir();
+    let stamp = load_stamp(deploy_dir);
+    let mut changed = Vec::new();
+    for (service, inputs) in BUILD_INPUTS {
+        if stamp.get(*service) != Some(&current_hash(deploy_dir, service, inputs)) {
+            changed.push(*service);
+        }
+    }
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+
+    build(engine, &changed, false, false)?;
+    record(deploy_dir, &changed)?;
+    Ok(changed)
+}
+
+/// Builds `services` with compose, except a user image that comes from an
+/// environment repository, which repo2docker builds.
+pub fn build(engine: &Engine, services: &[&str], no_cache: bool, pull: bool) -> Result<()> {
+    let repo = EnvRepo::load(engine.deploy_dir());
+    let (from_repo, composed): (Vec<&str>, Vec<&str>) = services
+        .iter()
+        .partition(|service| **service == USER_SERVICE && repo.is_some());
+    if !composed.is_empty() {
+        let mut args = vec!["build"];
+        if no_cache {
+            args.push("--no-cache");
+        }
+        if pull {
+            args.push("--pull");
+        }
+        args.extend(&composed);
+        engine
+            .compose_step(&args, "Building images")
+            .context("failed to build images")?;
+    }
+    if let (Some(repo), false) = (repo, from_repo.is_empty()) {
+        let env = envfile::load(engine.deploy_dir())?;
+        let image = envfile::get(&env, "USER_IMAGE").unwrap_or("mvre-user:latest");
+        let uid = envfile::get(&env, "NOTEBOOK_UID").unwrap_or("1000");
+        let args = repo2docker_args(&repo, image, uid);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        engine
+            .tool_step("repo2docker", &args, &format!("Building the user image from {}", repo.url))
+            .context("failed to build the user image with repo2docker (install it with pip install jupyter-repo2docker)")?;
+    }
+    Ok(())
+}
+
+/// Repository holding the user environment (`environment.yml`,
+/// `requirements.txt`, ...) that replaces the bundled user Dockerfile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvRepo {
+    pub url: String,
+    /// Branch, tag, or commit; the default branch when unset.
+    pub git_ref: Option<String>,
+}
+
+impl EnvRepo {
+    pub fn from_env(env: &EnvMap) -> Option<Self> {
+        Some(Self {
+            url: envfile::get(env, "USER_ENV_REPO")?.to_string(),
+            git_ref: envfile::get(env, "USER_ENV_REF").map(str::to_string),
+        })
+    }
+
+    fn load(deploy_dir: &Path) -> Option<Self> {
+        Self::from_env(&envfile::load(deploy_dir).ok()?)
+    }
+
+    /// Commit the ref points at now, so a push to the repository counts as
+    /// a change; `None` when the remote cannot be reached.
+    fn resolve(&self) -> Option<String> {
+        let output = Command::new("git")
+            .args(["ls-remote", &self.url, self.git_ref.as_deref().unwrap_or("HEAD")])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .next()
+            .map(str::to_string)
+    }
+}
+
+/// repo2docker arguments building `repo` into `image`. The notebook user is
+/// `jovyan` with the deployment's UID, as in the bundled image, so home
+/// volumes and mounts keep working.
+pub fn repo2docker_args(repo: &EnvRepo, image: &str, uid: &str) -> Vec<String> {
+    let mut args = vec![
+        "--no-run".to_string(),
+        "--image-name".to_string(),
+        image.to_string(),
+        "--user-name".to_string(),
+        "jovyan".to_string(),
+        "--user-id".to_string(),
+        uid.to_string(),
+    ];
+    if let Some(git_ref) = &repo.git_ref {
+        args.push("--ref".to_string());
+        args.push(git_ref.clone());
+    }
+    args.push(repo.url.clone());
+    args
+}
+
+/// Stores the current input hashes for `built` services.
+pub fn record(deploy_dir: &Path, built: &[&str]) -> Result<()> {
+    let mut stamp = load_stamp(deploy_dir);
+    for (service, inputs) in BUILD_INPUTS {
+        if built.contains(service) {
+            stamp.insert(service.to_string(), current_hash(deploy_dir, service, inputs));
+        }
+    }
+    let serialized = serde_json::to_string_pretty(&stamp).context("failed to serialize build stamp")?;
+    util::write_string(&stamp_path(deploy_dir), &serialized)
+}
+
+fn load_stamp(deploy_dir: &Path) -> BTreeMap<String, String> {
+    fs::read_to_string(stamp_path(deploy_dir))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Hash of what `service` is built from: its files, or for a user image from
+/// an environment repository, the repository and the commit it is at.
+fn current_hash(deploy_dir: &Path, service: &str, inputs: &[&str]) -> String {
+    match EnvRepo::load(deploy_dir).filter(|_| service == USER_SERVICE) {
+        Some(repo) => {
+            let source = format!(
+                "{}\n{}\n{}",
+                repo.url,
+                repo.git_ref.as_deref().unwrap_or(""),
+                repo.resolve().unwrap_or_default()
+            );
+            Sha256::digest(source.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+        }
+        None => hash_inputs(deploy_dir, inputs),
+    }
+}
+
+/// Hash over the named files; a missing file hashes differently from any
+/// contents, so compose gets to report it during the build.
+fn hash_inputs(deploy_dir: &Path, inputs: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for input in inputs {
+        hasher.update(input.as_bytes());
+        match fs::read(deploy_dir.join(input)) {
+            Ok(bytes) => {
+                hasher.update((bytes.len() as u64).to_le_bytes());
+                hasher.update(&bytes);
+            }
+            Err(_) => hasher.update(u64::MAX.to_le_bytes()),
+        }
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn stamp_path(deploy_dir: &Path) -> PathBuf {
+    deploy_dir.join(BUILD_STAMP_FILE)
+}
+
+/// Name of a built image on this host: compose names the hub image after
+/// the project, the user image is `USER_IMAGE`.
+pub fn local_image(deploy_dir: &Path, env: &EnvMap, service: BuildService) -> String {
+    match service {
+        BuildService::Hub => format!("{}-{}", compose::project_name(deploy_dir, env), service.compose_name()),
+        BuildService::User => envfile::get(env, "USER_IMAGE").unwrap_or("mvre-user:latest").to_string(),
+    }
+}
+
+impl BuildService {
+    pub fn compose_name(self) -> &'static str {
+        match self {
+            BuildService::Hub => "jupyterhub",
+            BuildService::User => "user-image",
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{CertBackupOptions, CertCommand, CertRestoreOptions},
+    config::AppConfig,
+    engine::Engine,
+    envfile, failure, say, services, tls, util,
+};
+
+const PROXY_SERVICE: &str = "traefik";
+
+/// What `cert backup` archives, relative to the deployment: Traefik's ACME
+/// stores (the account key and every issued certificate) and the local CA of
+/// `--tls self-signed`, which browsers were told to trust.
+pub fn cert_paths() -> Vec<String> {
+    vec![
+        format!("traefik/{}", tls::acme_storage(false)),
+        format!("traefik/{}", tls::acme_storage(true)),
+        tls::LOCAL_TLS_DIR.to_string(),
+    ]
+}
+
+/// The contents of an ACME store that matter for recovery.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AcmeStore {
+    pub account: Option<String>,
+    pub domains: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Resolver {
+    #[serde(rename = "Account", default)]
+    account: Option<Account>,
+    #[serde(rename = "Certificates", default)]
+    certificates: Option<Vec<Certificate>>,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    #[serde(rename = "Email", default)]
+    email: String,
+}
+
+#[derive(Deserialize)]
+struct Certificate {
+    domain: CertificateDomain,
+}
+
+#[derive(Deserialize)]
+struct CertificateDomain {
+    #[serde(default)]
+    main: String,
+    #[serde(default)]
+    sans: Option<Vec<String>>,
+}
+
+/// Reads Traefik's `acme.json`: one entry per certificate resolver, each with
+/// its account and certificates.
+pub fn parse_acme_store(raw: &str) -> Result<AcmeStore> {
+    let resolvers: BTreeMap<String, Option<Resolver>> =
+        serde_json::from_str(raw).context("not a Traefik ACME store")?;
+    let mut store = AcmeStore::default();
+    for resolver in resolvers.into_values().flatten() {
+        if let Some(account) = resolver.account.filter(|account| !account.email.is_empty()) {
+            store.account.get_or_insert(account.email);
+        }
+        for certificate in resolver.certificates.unwrap_or_default() {
+            let domain = certificate.domain;
+            store.domains.push(domain.main);
+            store.domains.extend(domain.sans.unwrap_or_default());
+        }
+    }
+    store.domains.retain(|domain| !domain.is_empty());
+    store.domains.sort();
+    store.domains.dedup();
+    Ok(store)
+}
+
+pub fn run(command: CertCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        CertCommand::Backup(opts) => backup(&deploy_dir, opts),
+        CertCommand::Restore(opts) => restore(&deploy_dir, opts),
+    }
+}
+
+fn backup(deploy_dir: &Path, opts: CertBackupOptions) -> Result<()> {
+    let present: Vec<String> = cert_paths()
+        .into_iter()
+        .filter(|path| deploy_dir.join(path).exists())
+        .collect();
+    if present.is_empty() {
+        return Err(failure::validation(
+            "no certificates to back up; the deployment has no ACME store or local CA yet",
+        ));
+    }
+    let output = opts.output.unwrap_or_else(|| {
+        let stamp = util::format_timestamp(util::unix_now()).replace([':', '-'], "");
+        PathBuf::from(format!("mvre-hub-certs-{}.tar.gz", stamp))
+    });
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&output)
+        .arg("-C")
+        .arg(deploy_dir)
+        .args(&present)
+        .status()
+        .context("failed to run tar")?;
+    if !status.success() {
+        std::fs::remove_file(&output).ok();
+        anyhow::bail!("tar exited with status {}", status);
+    }
+    // The archive holds the ACME account key and certificate private keys.
+    util::set_file_mode(&output, 0o600).ok();
+
+    let store = current_store(deploy_dir);
+    say!("{} {}", style("Certificates backed up to").green(), output.display());
+    if let Some(store) = &store {
+        say!("  ACME account {}", store.account.as_deref().unwrap_or("-"));
+        say!("  Certificates for {}", if store.domains.is_empty() { "-".to_string() } else { store.domains.join(", ") });
+    }
+    util::set_result(serde_json::json!({ "backup": output, "paths": present, "acme": store }));
+    Ok(())
+}
+
+fn restore(deploy_dir: &Path, opts: CertRestoreOptions) -> Result<()> {
+    let entries = archive_entries(&opts.file)?;
+    if let Some(stray) = entries.iter().find(|entry| !is_cert_entry(entry)) {
+        return Err(failure::validation(format!(
+            "{} is not a certificate backup: it contains {}",
+            opts.file.display(),
+            stray
+        )));
+    }
+
+    let acme = deploy_dir.join("traefik").join(tls::acme_storage(false));
+    let replaces_certificates = current_store(deploy_dir).is_some_and(|store| !store.domains.is_empty());
+    if replaces_certificates && !opts.yes {
+        let prompt = format!("Replace the certificates in {} with {}?", util::path_display(deploy_dir), opts.file.display());
+        util::ensure_interactive(&prompt, "pass --yes")?;
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            return Err(failure::validation("Aborted"));
+        }
+    }
+    if acme.exists() {
+        let previous = acme.with_extension("json.pre-restore");
+        std::fs::copy(&acme, &previous).with_context(|| format!("failed to keep a copy of {}", acme.display()))?;
+        util::set_file_mode(&previous, 0o600).ok();
+    }
+
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(&opts.file)
+        .arg("-C")
+        .arg(deploy_dir)
+        .status()
+        .context("failed to run tar")?;
+    if !status.success() {
+        anyhow::bail!("tar exited with status {}", status);
+    }
+    for staging in [false, true] {
+        let store = deploy_dir.join("traefik").join(tls::acme_storage(staging));
+        if store.exists() {
+            // Traefik refuses a store other users can read.
+            util::set_file_mode(&store, 0o600).ok();
+        }
+    }
+
+    let store = current_store(deploy_dir).unwrap_or_default();
+    say!("{} {}", style("Certificates restored from").green(), opts.file.display());
+    let env = envfile::load(deploy_dir)?;
+    if let Some(domain) = envfile::get(&env, "HUB_DOMAIN") {
+        if !store.domains.iter().any(|restored| restored == domain) {
+            say!(
+                "{}",
+                style(format!("The backup has no certificate for {}; Traefik will request one", domain)).yellow()
+            );
+        }
+    }
+
+    let engine = Engine::new(deploy_dir);
+    let proxy_running = engine
+        .ps()
+        .unwrap_or_default()
+        .iter()
+        .any(|container| container.service == PROXY_SERVICE && container.state == "running");
+    if proxy_running {
+        engine
+            .compose_step(&["restart", PROXY_SERVICE], "Restarting the proxy")
+            .context("failed to restart the proxy with the restored certificates")?;
+    }
+    util::set_result(serde_json::json!({ "restored": opts.file, "acme": store, "proxy_restarted": proxy_running }));
+    Ok(())
+}
+
+fn current_store(deploy_dir: &Path) -> Option<AcmeStore> {
+    let raw = std::fs::read_to_string(deploy_dir.join("traefik").join(tls::acme_storage(false))).ok()?;
+    parse_acme_store(&raw).ok()
+}
+
+fn archive_entries(file: &Path) -> Result<Vec<String>> {
+    let output = Command::new("tar")
+        .arg("-tzf")
+        .arg(file)
+        .output()
+        .context("failed to run tar")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "cannot read {}: {}",
+            file.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// Whether an archive entry is one of [`cert_paths`] or inside one, so a
+/// restore cannot write anywhere else in the deployment.
+pub fn is_cert_entry(entry: &str) -> bool {
+    let entry = entry.trim_start_matches("./").trim_end_matches('/');
+    if entry.split('/').any(|part| part == "..") {
+        return false;
+    }
+    cert_paths()
+        .iter()
+        .any(|path| entry == path || entry.starts_with(&format!("{}/", path)))
+}
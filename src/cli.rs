@@ -1,4 +1,6 @@
-use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(name = "mvre-hub")]
@@ -8,6 +10,57 @@ pub struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    /// Inventory file for multi-host commands
+    #[arg(long, global = true)]
+    pub inventory: Option<PathBuf>,
+
+    /// Deployment directory to act on, instead of the active deployment
+    #[arg(long, global = true, env = "MVRE_HUB_DEPLOY_DIR")]
+    pub deploy_dir: Option<PathBuf>,
+
+    /// Suppress decorative output
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Output format; json prints one result document on stdout
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+
+    /// Stream raw tool output instead of progress spinners
+    #[arg(long, global = true)]
+    pub plain: bool,
+
+    /// Pipeline mode: never prompt, no colors or spinners, and a JSON summary
+    /// as the last line of stdout
+    #[arg(long, global = true, env = "MVRE_HUB_CI")]
+    pub ci: bool,
+
+    /// Seconds to wait for another operation on the deployment to finish (default: fail at once)
+    #[arg(long, global = true, default_value_t = 0, env = "MVRE_HUB_LOCK_TIMEOUT")]
+    pub lock_timeout: u64,
+
+    /// Times to retry a failed docker pull, build, push, or start
+    #[arg(long, global = true, default_value_t = 2, env = "MVRE_HUB_RETRIES")]
+    pub retries: u32,
+
+    /// Wait before the first retry, doubled after each one
+    #[arg(long, global = true, default_value = "10s", env = "MVRE_HUB_RETRY_BACKOFF")]
+    pub retry_backoff: String,
+
+    /// Fail at the first docker error instead of retrying
+    #[arg(long, global = true, env = "MVRE_HUB_NO_RETRY")]
+    pub no_retry: bool,
+
+    /// Time limit of a docker operation as OP=DURATION, e.g. pull=45m; 0 removes the limit
+    #[arg(
+        long = "docker-timeout",
+        global = true,
+        value_name = "OP=DURATION",
+        value_delimiter = ',',
+        env = "MVRE_HUB_DOCKER_TIMEOUT"
+    )]
+    pub docker_timeouts: Vec<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -19,17 +72,434 @@ pub enum Commands {
         #[command(flatten)]
         opts: DeployOptions,
     },
+    /// Deploy and start a throwaway local hub with dummy logins, to try things out
+    Dev {
+        #[command(flatten)]
+        opts: DevOptions,
+    },
+    /// Build the hub and user images
+    Build {
+        #[command(flatten)]
+        opts: BuildOptions,
+    },
+    /// Scan the hub, user, proxy, and database images for known vulnerabilities
+    Scan {
+        #[command(flatten)]
+        opts: ScanOptions,
+    },
+    /// Share the built images with other hosts through a registry
+    Image {
+        #[command(subcommand)]
+        command: ImageCommand,
+    },
     /// Start JupyterHub services
-    Start,
+    Start {
+        #[command(flatten)]
+        batch: BatchOptions,
+        #[command(flatten)]
+        opts: StartOptions,
+    },
     /// Stop services (preserve data)
-    Stop,
+    Stop {
+        #[command(flatten)]
+        batch: BatchOptions,
+        #[command(flatten)]
+        opts: StopOptions,
+    },
     /// Full environment cleanup
     Clean {
         #[command(flatten)]
         opts: CleanOptions,
     },
+    /// Change settings of an existing deployment without touching its data
+    Reconfigure {
+        #[command(flatten)]
+        opts: ReconfigureOptions,
+    },
+    /// Show what apply would change to match the deployment manifest
+    Plan {
+        #[command(flatten)]
+        opts: ManifestOptions,
+    },
+    /// Create or reconfigure the deployment to match the deployment manifest
+    Apply {
+        #[command(flatten)]
+        opts: ApplyOptions,
+    },
+    /// Apply the deployment manifest from a git repository whenever it changes
+    Gitops {
+        #[command(flatten)]
+        opts: GitopsOptions,
+    },
+    /// Snapshot, pull and rebuild images, and restart services
+    Upgrade {
+        #[command(flatten)]
+        opts: UpgradeOptions,
+    },
+    /// Restore the deployment from a snapshot
+    Rollback {
+        #[command(flatten)]
+        opts: RollbackOptions,
+    },
+    /// Print where to reach the hub and open it in the default browser
+    Open {
+        #[command(flatten)]
+        opts: OpenOptions,
+    },
     /// Show deployment status
-    Status,
+    Status {
+        #[command(flatten)]
+        batch: BatchOptions,
+    },
+    /// Summarize the deployment's settings from its files, without Docker
+    Info,
+    /// Report disk usage of the deployment, volumes, images, and dataset
+    Du {
+        #[command(flatten)]
+        opts: DuOptions,
+    },
+    /// Show live CPU, memory, and network usage per user and service
+    Top {
+        #[command(flatten)]
+        opts: TopOptions,
+    },
+    /// Record and report per-user resource usage
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Probe hub, proxy, database, and container health
+    Health {
+        #[command(flatten)]
+        opts: HealthOptions,
+    },
+    /// Manage the list of users allowed to log in
+    Users {
+        #[command(subcommand)]
+        command: UsersCommand,
+    },
+    /// Register the hub with an identity provider
+    Oauth {
+        #[command(subcommand)]
+        command: OauthCommand,
+    },
+    /// Manage time-limited guest accounts
+    Guest {
+        #[command(subcommand)]
+        command: GuestCommand,
+    },
+    /// Create and remove temporary accounts for a training event
+    Workshop {
+        #[command(subcommand)]
+        command: WorkshopCommand,
+    },
+    /// Publish a read-only, versioned snapshot of a shared results folder
+    Publish {
+        #[command(flatten)]
+        opts: PublishOptions,
+    },
+    /// Remove resources left behind by deleted users
+    Prune {
+        #[command(subcommand)]
+        command: PruneCommand,
+    },
+    /// Report on user volumes and remove the ones unused for a long time
+    Volumes {
+        #[command(subcommand)]
+        command: VolumesCommand,
+    },
+    /// Show the audit log of state-changing operations
+    Audit {
+        #[command(flatten)]
+        opts: AuditOptions,
+    },
+    /// Emit a diagram of services, networks, volumes, and mounts
+    Graph {
+        #[command(flatten)]
+        opts: GraphOptions,
+    },
+    /// Manage recurring jobs run by systemd timers
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
+    /// List the deployments this tool knows, or forget one
+    Deployments {
+        #[command(subcommand)]
+        command: DeploymentsCommand,
+    },
+    /// Take over a hand-maintained docker-compose JupyterHub deployment
+    Adopt {
+        #[command(flatten)]
+        opts: AdoptOptions,
+    },
+    /// Make a deployment, by name or directory, the one commands act on
+    Use {
+        /// Deployment name (see deployments list) or directory
+        target: String,
+    },
+    /// Manage isolated tenant deployments on this host
+    Tenant {
+        #[command(subcommand)]
+        command: TenantCommand,
+    },
+    /// Install optional host hardening
+    Hardening {
+        #[command(subcommand)]
+        command: HardeningCommand,
+    },
+    /// Restrict inbound traffic to SSH and the hub's web ports
+    Firewall {
+        #[command(subcommand)]
+        command: FirewallCommand,
+    },
+    /// Check the Docker setup of this host and explain what blocks a deployment
+    Doctor,
+    /// Manage per-user ZFS/Btrfs workspaces
+    Workspace {
+        #[command(subcommand)]
+        command: WorkspaceCommand,
+    },
+    /// Back up the deployment to a directory or S3
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Back up and restore the ACME account and TLS certificates
+    Cert {
+        #[command(subcommand)]
+        command: CertCommand,
+    },
+    /// Open, dump, restore, or vacuum the hub database
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// Issue, list, and revoke hub API tokens
+    Token {
+        #[command(subcommand)]
+        command: TokenCommand,
+    },
+    /// Rotate the OAuth client secret and the Postgres password
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommand,
+    },
+    /// Check that the hub API answers and the proxy routes every running server
+    Verify,
+    /// Set environment variables of particular users' and groups' servers
+    UserEnv {
+        #[command(subcommand)]
+        command: UserEnvCommand,
+    },
+    /// Show a banner on every hub page, or remove it
+    Announce {
+        #[command(flatten)]
+        opts: AnnounceOptions,
+    },
+    /// Start or stop users' notebook servers through the hub
+    Server {
+        #[command(subcommand)]
+        command: ServerCommand,
+    },
+    /// Restart crashed services with backoff, until stopped
+    Watch {
+        #[command(flatten)]
+        opts: WatchOptions,
+    },
+    /// Manage URLs told when deploy, start, stop, clean, upgrade, or backup finishes
+    Webhooks {
+        #[command(subcommand)]
+        command: WebhooksCommand,
+    },
+}
+
+impl Commands {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commands::Deploy { .. } => "deploy",
+            Commands::Dev { .. } => "dev",
+            Commands::Build { .. } => "build",
+            Commands::Image { .. } => "image",
+            Commands::Scan { .. } => "scan",
+            Commands::Start { .. } => "start",
+            Commands::Stop { .. } => "stop",
+            Commands::Clean { .. } => "clean",
+            Commands::Reconfigure { .. } => "reconfigure",
+            Commands::Plan { .. } => "plan",
+            Commands::Apply { .. } => "apply",
+            Commands::Gitops { .. } => "gitops",
+            Commands::Upgrade { .. } => "upgrade",
+            Commands::Rollback { .. } => "rollback",
+            Commands::Open { .. } => "open",
+            Commands::Status { .. } => "status",
+            Commands::Info => "info",
+            Commands::Du { .. } => "du",
+            Commands::Top { .. } => "top",
+            Commands::Report { .. } => "report",
+            Commands::Health { .. } => "health",
+            Commands::Users { .. } => "users",
+            Commands::Oauth { .. } => "oauth",
+            Commands::Guest { .. } => "guest",
+            Commands::Workshop { .. } => "workshop",
+            Commands::Publish { .. } => "publish",
+            Commands::Prune { .. } => "prune",
+            Commands::Volumes { .. } => "volumes",
+            Commands::Audit { .. } => "audit",
+            Commands::Graph { .. } => "graph",
+            Commands::Schedule { .. } => "schedule",
+            Commands::Deployments { .. } => "deployments",
+            Commands::Adopt { .. } => "adopt",
+            Commands::Use { .. } => "use",
+            Commands::Tenant { .. } => "tenant",
+            Commands::Hardening { .. } => "hardening",
+            Commands::Firewall { .. } => "firewall",
+            Commands::Doctor => "doctor",
+            Commands::Workspace { .. } => "workspace",
+            Commands::Backup { .. } => "backup",
+            Commands::Cert { .. } => "cert",
+            Commands::Db { .. } => "db",
+            Commands::Token { .. } => "token",
+            Commands::Secrets { .. } => "secrets",
+            Commands::Verify => "verify",
+            Commands::UserEnv { .. } => "user-env",
+            Commands::Announce { .. } => "announce",
+            Commands::Server { .. } => "server",
+            Commands::Watch { .. } => "watch",
+            Commands::Webhooks { .. } => "webhooks",
+        }
+    }
+
+    /// Operation reported to webhooks when it finishes, or `None` for
+    /// commands they do not hear about. Batch runs report on each host.
+    pub fn lifecycle_event(&self) -> Option<LifecycleEvent> {
+        match self {
+            Commands::Deploy { .. } => Some(LifecycleEvent::Deploy),
+            Commands::Start { batch, .. } if !batch.is_batch() => Some(LifecycleEvent::Start),
+            Commands::Stop { batch, .. } if !batch.is_batch() => Some(LifecycleEvent::Stop),
+            Commands::Clean { .. } => Some(LifecycleEvent::Clean),
+            Commands::Upgrade { .. } => Some(LifecycleEvent::Upgrade),
+            Commands::Backup {
+                command: BackupCommand::Create(_),
+            } => Some(LifecycleEvent::Backup),
+            _ => None,
+        }
+    }
+
+    /// Name recorded in the audit log, or `None` for read-only commands.
+    pub fn audit_name(&self) -> Option<&'static str> {
+        match self {
+            Commands::Status { .. }
+            | Commands::Open { .. }
+            | Commands::Info
+            | Commands::Du { .. }
+            | Commands::Top { .. }
+            | Commands::Report { .. }
+            | Commands::Health { .. }
+            | Commands::Audit { .. }
+            | Commands::Graph { .. }
+            | Commands::Scan { .. }
+            | Commands::Plan { .. }
+            | Commands::Deployments {
+                command: DeploymentsCommand::List,
+            }
+            | Commands::Adopt {
+                opts: AdoptOptions { dry_run: true, .. },
+            }
+            | Commands::Doctor
+            | Commands::Webhooks {
+                command: WebhooksCommand::List,
+            }
+            | Commands::UserEnv {
+                command: UserEnvCommand::List,
+            }
+            | Commands::Workshop {
+                command: WorkshopCommand::List,
+            }
+            | Commands::Volumes {
+                command: VolumesCommand::Report | VolumesCommand::Prune(VolumesPruneOptions { dry_run: true, .. }),
+            }
+            | Commands::Verify => None,
+            // Restarts are recorded one by one as they happen.
+            Commands::Watch { opts } if !opts.install && !opts.uninstall => None,
+            // Applied commits are recorded one by one as well.
+            Commands::Gitops { opts } if !opts.install && !opts.uninstall => None,
+            _ => Some(self.name()),
+        }
+    }
+
+    /// Whether the command changes the deployment's files or containers, and
+    /// so must not run alongside another such command on the same deployment.
+    /// Deploy, apply, and tenant remove lock the directory themselves once
+    /// they know which one it is.
+    pub fn locks_deployment(&self) -> bool {
+        match self {
+            Commands::Start { batch, .. } | Commands::Stop { batch, .. } => !batch.is_batch(),
+            Commands::Rollback { opts } => !opts.list,
+            Commands::Image {
+                command: ImageCommand::Pull(_),
+            }
+            | Commands::Backup {
+                command: BackupCommand::Create(_),
+            }
+            | Commands::Db {
+                command: DbCommand::Restore(_),
+            }
+            | Commands::Cert {
+                command: CertCommand::Restore(_),
+            }
+            | Commands::Workspace {
+                command:
+                    WorkspaceCommand::Create { .. }
+                    | WorkspaceCommand::Snapshot { .. }
+                    | WorkspaceCommand::Archive(_)
+                    | WorkspaceCommand::Import { .. },
+            }
+            | Commands::Users {
+                command: UsersCommand::Allow(_) | UsersCommand::Disallow(_),
+            }
+            | Commands::Oauth {
+                command: OauthCommand::RegisterKeycloak(_),
+            }
+            | Commands::UserEnv {
+                command: UserEnvCommand::Set { .. } | UserEnvCommand::Unset { .. },
+            }
+            | Commands::Guest {
+                command: GuestCommand::Prune,
+            }
+            | Commands::Prune {
+                command: PruneCommand::Volumes(PruneVolumesOptions { dry_run: false, .. }),
+            }
+            | Commands::Workshop {
+                command: WorkshopCommand::Create(_) | WorkshopCommand::Teardown(_),
+            }
+            | Commands::Volumes {
+                command: VolumesCommand::Prune(VolumesPruneOptions { dry_run: false, .. }),
+            } => true,
+            Commands::Build { .. }
+            | Commands::Secrets { .. }
+            | Commands::Clean { .. }
+            | Commands::Reconfigure { .. }
+            | Commands::Upgrade { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LifecycleEvent {
+    Deploy,
+    Start,
+    Stop,
+    Clean,
+    Upgrade,
+    Backup,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Human,
+    Json,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -39,45 +509,1973 @@ pub struct DeployOptions {
     pub force: bool,
 
     /// Domain name for the hub (e.g., hub.example.org)
-    #[arg(long)]
+    #[arg(long, env = "MVRE_HUB_DOMAIN")]
     pub domain: Option<String>,
 
     /// ACME email for TLS certificates
-    #[arg(long)]
+    #[arg(long, env = "MVRE_HUB_ACME_EMAIL")]
     pub acme_email: Option<String>,
 
-    /// Helmholtz AAI Client ID
-    #[arg(long)]
+    /// Get certificates from Let's Encrypt's staging endpoint (untrusted; spares the production rate limits)
+    #[arg(long, env = "MVRE_HUB_ACME_STAGING")]
+    pub acme_staging: bool,
+
+    /// Where the public certificate comes from
+    #[arg(long, value_enum, default_value = "acme", env = "MVRE_HUB_TLS")]
+    pub tls: TlsMode,
+
+    /// Primary identity provider; presets fill in endpoints, scopes, and username claim
+    #[arg(long, value_enum, env = "MVRE_HUB_AUTH")]
+    pub auth: Option<AuthPreset>,
+
+    /// Log in with any username and a shared password instead of OAuth (local trials only)
+    #[arg(long, hide = true)]
+    pub dummy_auth: bool,
+
+    /// OAuth client ID of the primary identity provider
+    #[arg(long, env = "MVRE_HUB_CLIENT_ID")]
     pub client_id: Option<String>,
 
-    /// Helmholtz AAI Client Secret
-    #[arg(long)]
+    /// OAuth client secret of the primary identity provider
+    #[arg(long, env = "MVRE_HUB_CLIENT_SECRET", hide_env_values = true)]
     pub client_secret: Option<String>,
 
     /// Host path to MoSAiC dataset (required)
-    #[arg(long)]
+    #[arg(long, env = "MVRE_HUB_DATASET_PATH")]
     pub dataset_path: Option<String>,
 
+    /// Shared notebooks host path
+    #[arg(long, env = "MVRE_HUB_SHARED_PATH")]
+    pub shared_path: Option<String>,
+
+    /// Git repository with the user environment (environment.yml, requirements.txt, ...), built with repo2docker instead of the bundled Dockerfile
+    #[arg(long, env = "MVRE_HUB_USER_ENV_REPO")]
+    pub user_env_repo: Option<String>,
+
+    /// Branch, tag, or commit of --user-env-repo
+    #[arg(long, env = "MVRE_HUB_USER_ENV_REF", requires = "user_env_repo")]
+    pub user_env_ref: Option<String>,
+
+    /// Admin users (comma-separated)
+    #[arg(long, env = "MVRE_HUB_ADMIN_USERS")]
+    pub admin_users: Option<String>,
+
+    /// File listing the only users allowed to log in (one per line)
+    #[arg(long, env = "MVRE_HUB_ALLOWED_USERS")]
+    pub allowed_users: Option<PathBuf>,
+
+    /// TOML answers file with settings such as per-group culling ([culling]),
+    /// hub groups with shared directories ([groups]), and sidecar containers
+    /// ([extra_services])
+    #[arg(long, env = "MVRE_HUB_ANSWERS")]
+    pub answers: Option<PathBuf>,
+
+    /// OAuth authorize URL
+    #[arg(long, env = "MVRE_HUB_OAUTH_AUTHORIZE_URL")]
+    pub oauth_authorize_url: Option<String>,
+
+    /// OAuth token URL
+    #[arg(long, env = "MVRE_HUB_OAUTH_TOKEN_URL")]
+    pub oauth_token_url: Option<String>,
+
+    /// OAuth userinfo URL
+    #[arg(long, env = "MVRE_HUB_OAUTH_USERDATA_URL")]
+    pub oauth_userdata_url: Option<String>,
+
+    /// Only admit users holding one of these entitlements (comma-separated URNs)
+    #[arg(long, env = "MVRE_HUB_REQUIRED_ENTITLEMENT")]
+    pub required_entitlement: Option<String>,
+
+    /// Userinfo claim listing the user's entitlements or groups
+    #[arg(long, env = "MVRE_HUB_ENTITLEMENT_CLAIM")]
+    pub entitlement_claim: Option<String>,
+
+    /// Offer another identity provider at login next to Helmholtz AAI (repeatable)
+    #[arg(long, value_enum, value_delimiter = ',', env = "MVRE_HUB_EXTRA_IDP")]
+    pub extra_idp: Vec<IdpPreset>,
+
+    /// Postgres password (with --production)
+    #[arg(long, env = "MVRE_HUB_DB_PASSWORD", hide_env_values = true)]
+    pub db_password: Option<String>,
+
     /// Allow deployment if dataset path is missing (testing only)
-    #[arg(long)]
+    #[arg(long, env = "MVRE_HUB_ALLOW_MISSING_DATASET")]
     pub allow_missing_dataset: bool,
 
     /// Install bundled MoSAiC notebooks into shared path
-    #[arg(long)]
+    #[arg(long, env = "MVRE_HUB_INSTALL_NOTEBOOKS")]
     pub install_notebooks: bool,
 
     /// Enable production profile (Postgres + culling + limits)
-    #[arg(long)]
+    #[arg(long, env = "MVRE_HUB_PRODUCTION")]
     pub production: bool,
 
     /// Skip systemd setup
     #[arg(long)]
     pub no_systemd: bool,
+
+    /// Install auto-start as a systemd user unit (no root needed; enables lingering)
+    #[arg(long, conflicts_with = "no_systemd")]
+    pub user: bool,
+
+    /// Init system used for auto-start
+    #[arg(long, value_enum, default_value_t = InitKind::Auto)]
+    pub init: InitKind,
+
+    /// Write files without the final review screen
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Refuse new servers when free hub storage drops below this many GB
+    #[arg(long, env = "MVRE_HUB_SPAWN_MIN_FREE_DISK_GB")]
+    pub spawn_min_free_disk_gb: Option<f64>,
+
+    /// Refuse new servers when available host memory drops below this many GB
+    #[arg(long, env = "MVRE_HUB_SPAWN_MIN_FREE_MEM_GB")]
+    pub spawn_min_free_mem_gb: Option<f64>,
+
+    /// What to do with work volumes of users deleted from the hub
+    #[arg(long, value_enum, default_value_t = OrphanPolicy::Never, env = "MVRE_HUB_ORPHAN_VOLUME_POLICY")]
+    pub orphan_volume_policy: OrphanPolicy,
+
+    /// Grace period before orphaned volumes are removed (with --orphan-volume-policy grace)
+    #[arg(long, default_value = "30d", env = "MVRE_HUB_ORPHAN_VOLUME_GRACE")]
+    pub orphan_volume_grace: String,
+
+    /// Serve published result snapshots at https://<domain>/published
+    #[arg(long, env = "MVRE_HUB_ENABLE_PUBLISHING")]
+    pub enable_publishing: bool,
+
+    /// Send HSTS, X-Frame-Options, and related security headers from the proxy
+    #[arg(long, env = "MVRE_HUB_SECURITY_HEADERS")]
+    pub security_headers: bool,
+
+    /// Content-Security-Policy for hub responses (implies --security-headers)
+    #[arg(long, env = "MVRE_HUB_CSP")]
+    pub csp: Option<String>,
+
+    /// Average requests per second allowed per client IP
+    #[arg(long, env = "MVRE_HUB_RATE_LIMIT")]
+    pub rate_limit: Option<u32>,
+
+    /// Requests allowed in a burst above --rate-limit (defaults to twice the rate)
+    #[arg(long, requires = "rate_limit")]
+    pub rate_limit_burst: Option<u32>,
+
+    /// User servers allowed to start at the same time; 0 for no limit (JupyterHub's default is 100)
+    #[arg(long, env = "MVRE_HUB_CONCURRENT_SPAWN_LIMIT")]
+    pub concurrent_spawn_limit: Option<u32>,
+
+    /// User servers allowed to run at once; 0 for no limit (the default)
+    #[arg(long, env = "MVRE_HUB_ACTIVE_SERVER_LIMIT")]
+    pub active_server_limit: Option<u32>,
+
+    /// Encrypt proxy-to-hub and hub-to-notebook traffic with an internal CA
+    #[arg(long, env = "MVRE_HUB_INTERNAL_TLS")]
+    pub internal_tls: bool,
+
+    /// Docker daemon socket mounted into the hub and proxy (detected, including rootless daemons)
+    #[arg(long, env = "MVRE_HUB_DOCKER_SOCKET")]
+    pub docker_socket: Option<String>,
+
+    /// Docker context of a daemon on another machine that runs the hub
+    #[arg(long, env = "MVRE_HUB_DOCKER_CONTEXT", conflicts_with = "docker_host")]
+    pub docker_context: Option<String>,
+
+    /// DOCKER_HOST of a daemon on another machine that runs the hub, e.g. ssh://admin@hub.example.org
+    #[arg(long, env = "MVRE_HUB_DOCKER_HOST")]
+    pub docker_host: Option<String>,
+
+    /// Directory with the client certificates of a TLS-protected --docker-host
+    #[arg(long, env = "MVRE_HUB_DOCKER_CERT_PATH", requires = "docker_host")]
+    pub docker_cert_path: Option<PathBuf>,
+
+    /// Add SELinux :z/:Z relabel options to bind mounts and spawner volumes
+    #[arg(long, value_enum, default_value_t = SelinuxLabels::Auto, env = "MVRE_HUB_SELINUX_LABELS")]
+    pub selinux_labels: SelinuxLabels,
+
+    /// Run user containers without the generated seccomp profile
+    #[arg(long, env = "MVRE_HUB_NO_SANDBOX_PROFILES")]
+    pub no_sandbox_profiles: bool,
+
+    /// Also confine user containers with an AppArmor profile (load it with `hardening apparmor`)
+    #[arg(long, conflicts_with = "no_sandbox_profiles", env = "MVRE_HUB_APPARMOR")]
+    pub apparmor: bool,
+
+    /// UID notebook processes run as (NB_UID), e.g. the owner of the dataset
+    #[arg(long, env = "MVRE_HUB_NOTEBOOK_UID")]
+    pub notebook_uid: Option<u32>,
+
+    /// Primary GID of notebook processes (NB_GID), e.g. the group that may read the dataset
+    #[arg(long, env = "MVRE_HUB_NOTEBOOK_GID")]
+    pub notebook_gid: Option<u32>,
+
+    /// Where user work directories live: Docker volumes, or a ZFS dataset / Btrfs subvolume per user
+    #[arg(long, value_enum, default_value_t = WorkspaceBackend::Volume, env = "MVRE_HUB_WORKSPACE_BACKEND")]
+    pub workspace_backend: WorkspaceBackend,
+
+    /// Parent ZFS dataset (e.g. tank/mvre-hub) or Btrfs directory holding the workspaces
+    #[arg(long, env = "MVRE_HUB_WORKSPACE_ROOT", required_if_eq_any = [("workspace_backend", "zfs"), ("workspace_backend", "btrfs")])]
+    pub workspace_root: Option<String>,
+
+    /// Quota of each workspace (e.g. 20G)
+    #[arg(long, env = "MVRE_HUB_WORKSPACE_QUOTA")]
+    pub workspace_quota: Option<String>,
+
+    /// Host directory for per-user scratch space, mounted at ~/scratch
+    #[arg(long, env = "MVRE_HUB_SCRATCH_ROOT")]
+    pub scratch_root: Option<String>,
+
+    /// Logo shown in the hub's navigation bar (PNG, SVG, JPEG, GIF, or WebP)
+    #[arg(long, env = "MVRE_HUB_LOGO")]
+    pub logo: Option<PathBuf>,
+
+    /// Title of the hub's pages and login screen
+    #[arg(long, env = "MVRE_HUB_TITLE")]
+    pub hub_title: Option<String>,
+
+    /// Accent color of buttons and links (e.g. #005aa0)
+    #[arg(long, env = "MVRE_HUB_BRAND_COLOR")]
+    pub brand_color: Option<String>,
+
+    /// Welcome line on the login page
+    #[arg(long, env = "MVRE_HUB_WELCOME_TEXT")]
+    pub welcome_text: Option<String>,
+
+    /// HTML file with terms of use shown below the login button
+    #[arg(long, env = "MVRE_HUB_TERMS_FILE")]
+    pub terms_file: Option<PathBuf>,
+
+    /// Serve an onboarding site at the bare domain (root) or at /docs
+    #[arg(long, value_enum, env = "MVRE_HUB_LANDING")]
+    pub landing: Option<LandingPath>,
+
+    /// Directory with a static site of your own instead of the generated page
+    #[arg(long, env = "MVRE_HUB_LANDING_SITE", requires = "landing")]
+    pub landing_site: Option<PathBuf>,
+
+    /// Support contact shown on the generated landing page
+    #[arg(long, env = "MVRE_HUB_SUPPORT_EMAIL")]
+    pub support_email: Option<String>,
+
+    /// How to cite the dataset, shown on the generated landing page
+    #[arg(long, env = "MVRE_HUB_DATA_CITATION")]
+    pub data_citation: Option<String>,
+
+    /// Run a Dask Gateway next to the hub, so notebooks can start Dask clusters
+    #[arg(long, env = "MVRE_HUB_DASK_GATEWAY")]
+    pub enable_dask_gateway: bool,
+
+    /// Run an MLflow tracking server at mlflow.<domain>
+    #[arg(long, env = "MVRE_HUB_MLFLOW")]
+    pub enable_mlflow: bool,
+
+    /// Host directory for MLflow artifacts (default: ./mlflow/artifacts)
+    #[arg(long, env = "MVRE_HUB_MLFLOW_ARTIFACTS", requires = "enable_mlflow")]
+    pub mlflow_artifacts: Option<String>,
+
+    /// Install nbgrader in user servers, with an assignment exchange for courses
+    #[arg(long, env = "MVRE_HUB_NBGRADER")]
+    pub enable_nbgrader: bool,
+
+    /// nbgrader course id (default: mosaic)
+    #[arg(long, requires = "enable_nbgrader")]
+    pub nbgrader_course: Option<String>,
+
+    /// Hub group whose members get the formgrader (default: instructors)
+    #[arg(long, requires = "enable_nbgrader")]
+    pub nbgrader_instructors: Option<String>,
+
+    /// Browse and download dataset files at https://<domain>/files after signing in to the hub
+    #[arg(long, env = "MVRE_HUB_FILE_BROWSER")]
+    pub enable_file_browser: bool,
+
+    /// Collect all container logs in Loki, searchable in Grafana at https://<domain>/grafana
+    #[arg(long, env = "MVRE_HUB_LOGGING_STACK")]
+    pub enable_logging_stack: bool,
+
+    /// How long the logging stack keeps logs
+    #[arg(long, default_value = "30d", env = "MVRE_HUB_LOG_RETENTION")]
+    pub log_retention: String,
+
+    /// Logging driver of the hub's containers, user servers included
+    #[arg(long, value_enum, default_value_t = LogDriver::JsonFile, env = "MVRE_HUB_LOG_DRIVER")]
+    pub log_driver: LogDriver,
+
+    /// Size at which a container's log file is rotated (json-file and local drivers)
+    #[arg(long, default_value = "50m", env = "MVRE_HUB_LOG_MAX_SIZE")]
+    pub log_max_size: String,
+
+    /// Rotated log files kept per container (json-file and local drivers)
+    #[arg(long, default_value_t = 5, env = "MVRE_HUB_LOG_MAX_FILE")]
+    pub log_max_file: u32,
+
+    /// Include a database dump in the snapshot taken before --force overwrites
+    #[arg(long)]
+    pub snapshot_db: bool,
+
+    /// How --force settles hand edits that conflict with regenerated files (prompted when not set)
+    #[arg(long, value_enum, env = "MVRE_HUB_ON_CONFLICT")]
+    pub on_conflict: Option<OnConflict>,
+
+    /// Host port published for HTTP
+    #[arg(long, default_value_t = 8080, env = "MVRE_HUB_HTTP_PORT")]
+    pub http_port: u16,
+
+    /// Host port published for HTTPS
+    #[arg(long, default_value_t = 8443, env = "MVRE_HUB_HTTPS_PORT")]
+    pub https_port: u16,
+
+    /// Traefik major version of the reverse proxy
+    #[arg(long, value_enum, env = "MVRE_HUB_PROXY_VERSION")]
+    pub proxy_version: Option<ProxyVersion>,
+
+    /// Compose project name, which also prefixes the stack's networks (default: kept from .env, else the directory name)
+    #[arg(long, env = "MVRE_HUB_PROJECT_NAME")]
+    pub project_name: Option<String>,
+
+    /// Put in front of user volumes, user containers, and the user image;
+    /// set by `tenant add` so tenants on one host share none of them.
+    #[arg(skip)]
+    pub resource_prefix: Option<String>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LandingPath {
+    /// The bare domain, with the site also under /docs
+    Root,
+    /// Only under /docs
+    Docs,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    /// Certificates from Let's Encrypt
+    Acme,
+    /// A local CA and certificate, for hosts without public DNS
+    SelfSigned,
+    /// Plain HTTP on the HTTP port; for local trials only
+    Off,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogDriver {
+    /// Docker's default, rotated by size
+    JsonFile,
+    /// Docker's compressed format, rotated by size
+    Local,
+    /// The host journal, rotated by journald
+    Journald,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scanner {
+    Trivy,
+    Grype,
+}
+
+/// Vulnerability severity, lowest first.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    #[value(skip)]
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Keep the edited file as it is
+    Keep,
+    /// Take the regenerated file, dropping the edits
+    Replace,
+    /// Write both versions with conflict markers, to edit by hand
+    Markers,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyVersion {
+    V2,
+    V3,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DbCommand {
+    /// Open psql (or sqlite3) on the hub database
+    Shell,
+    /// Write the database as plain SQL
+    Dump(DbDumpOptions),
+    /// Replace the database with a dump, stopping the hub meanwhile
+    Restore(DbRestoreOptions),
+    /// Reclaim space and refresh planner statistics
+    Vacuum,
 }
 
 #[derive(Args, Debug, Clone)]
-pub struct CleanOptions {
-    /// Confirm destructive operation
+pub struct DbDumpOptions {
+    /// Output file (defaults to jupyterhub-<timestamp>.sql in the current directory)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CertCommand {
+    /// Write the ACME account, issued certificates, and local CA to an archive
+    Backup(CertBackupOptions),
+    /// Put certificates from `cert backup` back and restart the proxy
+    Restore(CertRestoreOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CertBackupOptions {
+    /// Output file (defaults to mvre-hub-certs-<timestamp>.tar.gz in the current directory)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CertRestoreOptions {
+    /// Archive written by `cert backup`
+    pub file: PathBuf,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DbRestoreOptions {
+    /// SQL dump to restore, as written by `db dump`
+    pub file: PathBuf,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BackupCommand {
+    /// Initialise a restic or borg repository
+    Init(BackupTarget),
+    /// Archive configuration, hub data, and a database dump, then apply the retention policy
+    Create(BackupCreateOptions),
+    /// List backups at the destination
+    List(BackupTarget),
+    /// Remove backups the retention policy does not keep
+    Prune(BackupPruneOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BackupTarget {
+    /// Directory, s3://bucket/prefix, or restic:<repo> / borg:<repo> to keep backups in
+    #[arg(long, env = "MVRE_HUB_BACKUP_DEST")]
+    pub dest: String,
+
+    /// File holding the restic/borg repository password
+    #[arg(long, env = "MVRE_HUB_BACKUP_PASSWORD_FILE")]
+    pub password_file: Option<PathBuf>,
+
+    /// Server-side encryption of uploaded backups
+    #[arg(long, value_enum, env = "MVRE_HUB_BACKUP_SSE")]
+    pub sse: Option<ServerSideEncryption>,
+
+    /// KMS key for --sse kms (defaults to the bucket's AWS managed key)
+    #[arg(long, env = "MVRE_HUB_BACKUP_SSE_KMS_KEY_ID", requires = "sse")]
+    pub sse_kms_key_id: Option<String>,
+
+    /// S3 endpoint of non-AWS object stores (MinIO, Ceph)
+    #[arg(long, env = "MVRE_HUB_BACKUP_ENDPOINT_URL")]
+    pub endpoint_url: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RetentionOptions {
+    /// Keep the newest backup of each of the last N days
+    #[arg(long, env = "MVRE_HUB_BACKUP_KEEP_DAILY")]
+    pub keep_daily: Option<u32>,
+
+    /// Keep the newest backup of each of the last M weeks
+    #[arg(long, env = "MVRE_HUB_BACKUP_KEEP_WEEKLY")]
+    pub keep_weekly: Option<u32>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BackupCreateOptions {
+    #[command(flatten)]
+    pub target: BackupTarget,
+
+    #[command(flatten)]
+    pub retention: RetentionOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BackupPruneOptions {
+    #[command(flatten)]
+    pub target: BackupTarget,
+
+    #[command(flatten)]
+    pub retention: RetentionOptions,
+
+    /// Show what would be removed
     #[arg(long)]
-    pub full_ice: bool,
+    pub dry_run: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerSideEncryption {
+    /// S3 managed keys (SSE-S3)
+    Aes256,
+    /// AWS KMS keys (SSE-KMS)
+    Kms,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkspaceBackend {
+    /// A Docker volume per user
+    Volume,
+    Zfs,
+    Btrfs,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum WorkspaceCommand {
+    /// Create workspaces for users (by default everyone on the allow-list)
+    Create {
+        /// Hub user names
+        names: Vec<String>,
+    },
+    /// Take a read-only snapshot of a user's workspace
+    Snapshot {
+        /// Hub user name
+        name: String,
+
+        /// Snapshot name (defaults to a timestamp)
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// List workspaces with their usage and quota
+    List,
+    /// Archive the workspaces of inactive users to a backup destination and remove them
+    Archive(WorkspaceArchiveOptions),
+    /// Write a user's workspace to a gzipped tar archive
+    Export {
+        /// Hub user name
+        name: String,
+
+        /// Output file (defaults to workspace-<user>-<timestamp>.tar.gz in the current directory)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Fill a user's workspace from a tar archive, such as one from workspace export
+    Import {
+        /// Hub user name
+        name: String,
+
+        /// .tar, .tar.gz, or .tgz archive
+        archive: PathBuf,
+
+        /// Unpack into a workspace that already holds files, replacing files of the same name
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WorkspaceArchiveOptions {
+    /// Archive users whose last activity in the hub is older than this (e.g. 180d)
+    #[arg(long)]
+    pub inactive_for: String,
+
+    #[command(flatten)]
+    pub target: BackupTarget,
+
+    /// List the workspaces that would be archived without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+/// Whether bind mounts get SELinux relabel options.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelinuxLabels {
+    /// When SELinux is enforcing on this host
+    Auto,
+    On,
+    Off,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthPreset {
+    /// Helmholtz AAI or any OAuth provider, with endpoints given explicitly
+    Helmholtz,
+    Orcid,
+    Cilogon,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdpPreset {
+    Orcid,
+    Cilogon,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitKind {
+    Auto,
+    Systemd,
+    Openrc,
+    Runit,
+    Launchd,
+    TaskScheduler,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanPolicy {
+    Immediate,
+    Grace,
+    Never,
+}
+
+impl OrphanPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrphanPolicy::Immediate => "immediate",
+            OrphanPolicy::Grace => "grace",
+            OrphanPolicy::Never => "never",
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CleanOptions {
+    /// Confirm destructive operation
+    #[arg(long)]
+    pub full_ice: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ManifestOptions {
+    /// Manifest describing the deployment
+    #[arg(short, long, default_value = "mvre-hub.toml", env = "MVRE_HUB_MANIFEST")]
+    pub file: PathBuf,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ApplyOptions {
+    #[command(flatten)]
+    pub manifest: ManifestOptions,
+
+    /// Apply the plan without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GitopsOptions {
+    /// Git repository holding the manifest, as git clone takes it
+    #[arg(long, env = "MVRE_HUB_GITOPS_REPO", required_unless_present = "uninstall")]
+    pub repo: Option<String>,
+
+    /// Branch to follow (defaults to the repository's default branch)
+    #[arg(long, env = "MVRE_HUB_GITOPS_BRANCH")]
+    pub branch: Option<String>,
+
+    /// Path of the manifest inside the repository
+    #[arg(long, default_value = "mvre-hub.toml")]
+    pub path: PathBuf,
+
+    /// Time between polls of the repository
+    #[arg(long, default_value = "5m")]
+    pub interval: String,
+
+    /// Local clone of the repository (defaults to one under the config directory)
+    #[arg(long)]
+    pub checkout: Option<PathBuf>,
+
+    /// Poll and apply once, then exit
+    #[arg(long, conflicts_with_all = ["install", "uninstall"])]
+    pub once: bool,
+
+    /// Install a systemd service running `gitops` with these options
+    #[arg(long, conflicts_with = "uninstall")]
+    pub install: bool,
+
+    /// Stop and remove the systemd service
+    #[arg(long)]
+    pub uninstall: bool,
+
+    /// Install a user service instead of a system service
+    #[arg(long, requires = "install")]
+    pub user: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ReconfigureOptions {
+    /// Keep current values for anything not given as a flag instead of prompting
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Domain name for the hub
+    #[arg(long)]
+    pub domain: Option<String>,
+
+    /// ACME email for TLS certificates
+    #[arg(long)]
+    pub acme_email: Option<String>,
+
+    /// Get certificates from Let's Encrypt's staging endpoint
+    #[arg(long, conflicts_with = "no_acme_staging")]
+    pub acme_staging: bool,
+
+    /// Go back to Let's Encrypt's production endpoint
+    #[arg(long)]
+    pub no_acme_staging: bool,
+
+    /// Where the public certificate comes from
+    #[arg(long, value_enum)]
+    pub tls: Option<TlsMode>,
+
+    /// Primary identity provider preset
+    #[arg(long, value_enum)]
+    pub auth: Option<AuthPreset>,
+
+    /// OAuth client ID of the primary identity provider
+    #[arg(long)]
+    pub client_id: Option<String>,
+
+    /// OAuth client secret of the primary identity provider (kept unless given)
+    #[arg(long)]
+    pub client_secret: Option<String>,
+
+    /// Host path to MoSAiC dataset
+    #[arg(long)]
+    pub dataset_path: Option<String>,
+
+    /// Build the user image with repo2docker from this git repository
+    #[arg(long, conflicts_with = "bundled_user_env")]
+    pub user_env_repo: Option<String>,
+
+    /// Branch, tag, or commit of the user environment repository
+    #[arg(long, conflicts_with = "bundled_user_env")]
+    pub user_env_ref: Option<String>,
+
+    /// Build the user image from the bundled Dockerfile again
+    #[arg(long)]
+    pub bundled_user_env: bool,
+
+    /// Admin users (comma-separated; empty to clear)
+    #[arg(long)]
+    pub admin_users: Option<String>,
+
+    /// Replace the login allow-list with the users listed in this file
+    #[arg(long)]
+    pub allowed_users: Option<PathBuf>,
+
+    /// Apply the settings of a TOML answers file, e.g. per-group culling, groups, or extra services
+    #[arg(long)]
+    pub answers: Option<PathBuf>,
+
+    /// OAuth authorize URL
+    #[arg(long)]
+    pub oauth_authorize_url: Option<String>,
+
+    /// OAuth token URL
+    #[arg(long)]
+    pub oauth_token_url: Option<String>,
+
+    /// OAuth userinfo URL
+    #[arg(long)]
+    pub oauth_userdata_url: Option<String>,
+
+    /// Only admit users holding one of these entitlements (comma-separated; empty to clear)
+    #[arg(long)]
+    pub required_entitlement: Option<String>,
+
+    /// Userinfo claim listing the user's entitlements or groups
+    #[arg(long)]
+    pub entitlement_claim: Option<String>,
+
+    /// Replace the additional identity providers offered at login
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "no_extra_idps")]
+    pub extra_idp: Option<Vec<IdpPreset>>,
+
+    /// Offer only Helmholtz AAI at login
+    #[arg(long)]
+    pub no_extra_idps: bool,
+
+    /// Refuse new servers when free hub storage drops below this many GB
+    #[arg(long)]
+    pub spawn_min_free_disk_gb: Option<f64>,
+
+    /// Refuse new servers when available host memory drops below this many GB
+    #[arg(long)]
+    pub spawn_min_free_mem_gb: Option<f64>,
+
+    /// What to do with work volumes of users deleted from the hub
+    #[arg(long, value_enum)]
+    pub orphan_volume_policy: Option<OrphanPolicy>,
+
+    /// Grace period before orphaned volumes are removed
+    #[arg(long)]
+    pub orphan_volume_grace: Option<String>,
+
+    /// Serve published result snapshots at https://<domain>/published
+    #[arg(long, conflicts_with = "disable_publishing")]
+    pub enable_publishing: bool,
+
+    /// Stop serving published result snapshots
+    #[arg(long)]
+    pub disable_publishing: bool,
+
+    /// Send HSTS, X-Frame-Options, and related security headers from the proxy
+    #[arg(long, conflicts_with = "no_security_headers")]
+    pub security_headers: bool,
+
+    /// Stop sending security headers (also drops the Content-Security-Policy)
+    #[arg(long)]
+    pub no_security_headers: bool,
+
+    /// Content-Security-Policy for hub responses; empty to remove
+    #[arg(long)]
+    pub csp: Option<String>,
+
+    /// Average requests per second allowed per client IP; 0 disables
+    #[arg(long)]
+    pub rate_limit: Option<u32>,
+
+    /// Requests allowed in a burst above the rate limit
+    #[arg(long)]
+    pub rate_limit_burst: Option<u32>,
+
+    /// User servers allowed to start at the same time; 0 for no limit
+    #[arg(long)]
+    pub concurrent_spawn_limit: Option<u32>,
+
+    /// User servers allowed to run at once; 0 for no limit
+    #[arg(long)]
+    pub active_server_limit: Option<u32>,
+
+    /// Encrypt proxy-to-hub and hub-to-notebook traffic with an internal CA
+    #[arg(long, conflicts_with = "no_internal_tls")]
+    pub internal_tls: bool,
+
+    /// Go back to plain HTTP inside the deployment
+    #[arg(long)]
+    pub no_internal_tls: bool,
+
+    /// Docker daemon socket mounted into the hub and proxy, e.g. after switching to rootless Docker
+    #[arg(long)]
+    pub docker_socket: Option<String>,
+
+    /// Manage the hub through this docker context
+    #[arg(long, conflicts_with_all = ["docker_host", "local_docker"])]
+    pub docker_context: Option<String>,
+
+    /// Manage the hub through this DOCKER_HOST
+    #[arg(long, conflicts_with = "local_docker")]
+    pub docker_host: Option<String>,
+
+    /// Client certificates of a TLS-protected --docker-host
+    #[arg(long, requires = "docker_host")]
+    pub docker_cert_path: Option<PathBuf>,
+
+    /// Manage the hub through the local Docker daemon again
+    #[arg(long)]
+    pub local_docker: bool,
+
+    /// Add SELinux :z/:Z relabel options to bind mounts and spawner volumes
+    #[arg(long, value_enum)]
+    pub selinux_labels: Option<SelinuxLabels>,
+
+    /// Apply the generated seccomp profile to user containers
+    #[arg(long, conflicts_with = "no_sandbox_profiles")]
+    pub sandbox_profiles: bool,
+
+    /// Run user containers without seccomp and AppArmor profiles
+    #[arg(long)]
+    pub no_sandbox_profiles: bool,
+
+    /// Also confine user containers with an AppArmor profile (implies --sandbox-profiles)
+    #[arg(long, conflicts_with_all = ["no_apparmor", "no_sandbox_profiles"])]
+    pub apparmor: bool,
+
+    /// Stop applying the AppArmor profile
+    #[arg(long)]
+    pub no_apparmor: bool,
+
+    /// UID notebook processes run as (NB_UID)
+    #[arg(long)]
+    pub notebook_uid: Option<u32>,
+
+    /// Primary GID of notebook processes (NB_GID)
+    #[arg(long)]
+    pub notebook_gid: Option<u32>,
+
+    /// Go back to the image's own user (jovyan, 1000:100)
+    #[arg(long, conflicts_with_all = ["notebook_uid", "notebook_gid"])]
+    pub default_notebook_ids: bool,
+
+    /// Where user work directories live
+    #[arg(long, value_enum)]
+    pub workspace_backend: Option<WorkspaceBackend>,
+
+    /// Parent ZFS dataset or Btrfs directory holding the workspaces
+    #[arg(long)]
+    pub workspace_root: Option<String>,
+
+    /// Quota of each new workspace (e.g. 20G)
+    #[arg(long)]
+    pub workspace_quota: Option<String>,
+
+    /// Host directory for per-user scratch space; "" removes the scratch mount
+    #[arg(long)]
+    pub scratch_root: Option<String>,
+
+    /// Logo shown in the hub's navigation bar; "" restores the default
+    #[arg(long)]
+    pub logo: Option<PathBuf>,
+
+    /// Title of the hub's pages and login screen; "" restores the default
+    #[arg(long)]
+    pub hub_title: Option<String>,
+
+    /// Accent color of buttons and links; "" restores the default
+    #[arg(long)]
+    pub brand_color: Option<String>,
+
+    /// Welcome line on the login page; "" removes it
+    #[arg(long)]
+    pub welcome_text: Option<String>,
+
+    /// HTML file with terms of use shown below the login button; "" removes them
+    #[arg(long)]
+    pub terms_file: Option<PathBuf>,
+
+    /// Serve an onboarding site at the bare domain (root) or at /docs
+    #[arg(long, value_enum, conflicts_with = "no_landing")]
+    pub landing: Option<LandingPath>,
+
+    /// Remove the landing site service
+    #[arg(long)]
+    pub no_landing: bool,
+
+    /// Directory with a static site of your own; "" returns to the generated page
+    #[arg(long)]
+    pub landing_site: Option<PathBuf>,
+
+    /// Support contact shown on the generated landing page; "" removes it
+    #[arg(long)]
+    pub support_email: Option<String>,
+
+    /// How to cite the dataset, shown on the generated landing page; "" removes it
+    #[arg(long)]
+    pub data_citation: Option<String>,
+
+    /// Run a Dask Gateway next to the hub, so notebooks can start Dask clusters
+    #[arg(long, conflicts_with = "disable_dask_gateway")]
+    pub enable_dask_gateway: bool,
+
+    /// Remove the Dask Gateway service
+    #[arg(long)]
+    pub disable_dask_gateway: bool,
+
+    /// Run an MLflow tracking server at mlflow.<domain>
+    #[arg(long, conflicts_with = "disable_mlflow")]
+    pub enable_mlflow: bool,
+
+    /// Remove the MLflow service; its data and artifacts are kept
+    #[arg(long)]
+    pub disable_mlflow: bool,
+
+    /// Host directory for MLflow artifacts; "" returns to ./mlflow/artifacts
+    #[arg(long)]
+    pub mlflow_artifacts: Option<String>,
+
+    /// Install nbgrader in user servers, with an assignment exchange for courses
+    #[arg(long, conflicts_with = "disable_nbgrader")]
+    pub enable_nbgrader: bool,
+
+    /// Remove nbgrader; the exchange and course files are kept
+    #[arg(long)]
+    pub disable_nbgrader: bool,
+
+    /// nbgrader course id
+    #[arg(long)]
+    pub nbgrader_course: Option<String>,
+
+    /// Hub group whose members get the formgrader
+    #[arg(long)]
+    pub nbgrader_instructors: Option<String>,
+
+    /// Browse and download dataset files at https://<domain>/files after signing in to the hub
+    #[arg(long, conflicts_with = "disable_file_browser")]
+    pub enable_file_browser: bool,
+
+    /// Remove the dataset file browser
+    #[arg(long)]
+    pub disable_file_browser: bool,
+
+    /// Collect all container logs in Loki, searchable in Grafana at https://<domain>/grafana
+    #[arg(long, conflicts_with = "disable_logging_stack")]
+    pub enable_logging_stack: bool,
+
+    /// Remove Loki, Promtail, and Grafana; collected logs are kept in their volumes
+    #[arg(long)]
+    pub disable_logging_stack: bool,
+
+    /// How long the logging stack keeps logs
+    #[arg(long)]
+    pub log_retention: Option<String>,
+
+    /// Logging driver of the hub's containers, user servers included
+    #[arg(long, value_enum)]
+    pub log_driver: Option<LogDriver>,
+
+    /// Size at which a container's log file is rotated
+    #[arg(long)]
+    pub log_max_size: Option<String>,
+
+    /// Rotated log files kept per container
+    #[arg(long)]
+    pub log_max_file: Option<u32>,
+
+    /// Host port published for HTTP
+    #[arg(long)]
+    pub http_port: Option<u16>,
+
+    /// Host port published for HTTPS
+    #[arg(long)]
+    pub https_port: Option<u16>,
+
+    /// Traefik major version of the reverse proxy
+    #[arg(long, value_enum)]
+    pub proxy_version: Option<ProxyVersion>,
+
+    /// How to settle hand edits that conflict with regenerated files (prompted when not set)
+    #[arg(long, value_enum)]
+    pub on_conflict: Option<OnConflict>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BuildOptions {
+    /// Build without using the layer cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Pull newer versions of the base images
+    #[arg(long)]
+    pub pull: bool,
+
+    /// Build only one image
+    #[arg(long, value_enum)]
+    pub service: Option<BuildService>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ScanOptions {
+    /// Scanner to run (default: trivy if installed, else grype)
+    #[arg(long, value_enum)]
+    pub scanner: Option<Scanner>,
+
+    /// Fail if any vulnerability is at least this severe
+    #[arg(long, value_enum)]
+    pub fail_on: Option<Severity>,
+
+    /// Write every finding as JSON evidence to this file
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ImageCommand {
+    /// Tag the locally built images for the registry and push them
+    Push(ImageOptions),
+    /// Pull the images from the registry and use them instead of building
+    Pull(ImageOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ImageOptions {
+    /// Registry and namespace, e.g. registry.example.org/mvre
+    #[arg(long, env = "MVRE_HUB_REGISTRY")]
+    pub registry: String,
+
+    /// Image tag in the registry
+    #[arg(long, default_value = "latest")]
+    pub tag: String,
+
+    /// Only one image
+    #[arg(long, value_enum)]
+    pub service: Option<BuildService>,
+
+    /// Log in first as this user; the password comes from MVRE_HUB_REGISTRY_PASSWORD or a prompt
+    #[arg(long, env = "MVRE_HUB_REGISTRY_USERNAME")]
+    pub username: Option<String>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildService {
+    Hub,
+    User,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct UpgradeOptions {
+    /// The pre-upgrade snapshot always includes a database dump now
+    #[arg(long, hide = true)]
+    pub snapshot_db: bool,
+
+    /// Upgrade without dumping the database first (the upgrade cannot be fully rolled back)
+    #[arg(long, conflicts_with = "snapshot_db")]
+    pub skip_db_snapshot: bool,
+
+    /// Migrate the reverse proxy to this Traefik version
+    #[arg(long, value_enum)]
+    pub proxy_version: Option<ProxyVersion>,
+
+    /// Start the new hub next to the running one and switch over once it passes a smoke test
+    #[arg(long, conflicts_with = "proxy_version")]
+    pub blue_green: bool,
+
+    /// Loopback port the new hub is smoke-tested on during a blue/green upgrade
+    #[arg(long, default_value_t = crate::bluegreen::DEFAULT_PORT, requires = "blue_green")]
+    pub green_port: u16,
+
+    /// How to settle hand edits that conflict with the new release's templates (prompted when not set)
+    #[arg(long, value_enum)]
+    pub on_conflict: Option<OnConflict>,
+
+    /// Scan the rebuilt images and stop before restarting if any vulnerability is this severe
+    #[arg(long, value_enum)]
+    pub scan_fail_on: Option<Severity>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RollbackOptions {
+    /// Snapshot id to restore (defaults to the most recent)
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// List available snapshots instead of restoring
+    #[arg(long, conflicts_with = "to")]
+    pub list: bool,
+
+    /// Also restore the database dump stored in the snapshot
+    #[arg(long)]
+    pub with_db: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BatchOptions {
+    /// Run on every host in the inventory
+    #[arg(long)]
+    pub all: bool,
+
+    /// Run on the named inventory host (repeatable)
+    #[arg(long, conflicts_with = "all")]
+    pub host: Vec<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct StartOptions {
+    /// Wait until the hub answers through the proxy, so users can log in
+    #[arg(long)]
+    pub wait: bool,
+
+    /// Longest wait for the hub (with --wait)
+    #[arg(long, default_value = "5m", requires = "wait")]
+    pub wait_timeout: String,
+
+    /// Print where to reach the hub and open it in the default browser
+    #[arg(long)]
+    pub open: bool,
+}
+
+impl StartOptions {
+    /// The options as arguments of `mvre-hub start` on another host.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec!["start".to_string()];
+        if self.wait {
+            args.extend(["--wait".to_string(), "--wait-timeout".to_string(), self.wait_timeout.clone()]);
+        }
+        args
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct StopOptions {
+    /// Announce the stop and wait until users' servers have stopped or been culled
+    #[arg(long)]
+    pub when_idle: bool,
+
+    /// Longest wait for servers before stopping anyway (with --when-idle)
+    #[arg(long, default_value = "2h", requires = "when_idle")]
+    pub max_wait: String,
+
+    /// Banner shown to users while waiting (with --when-idle)
+    #[arg(long, requires = "when_idle")]
+    pub message: Option<String>,
+}
+
+impl StopOptions {
+    /// The options as arguments of `mvre-hub stop` on another host.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec!["stop".to_string()];
+        if self.when_idle {
+            args.extend(["--when-idle".to_string(), "--max-wait".to_string(), self.max_wait.clone()]);
+        }
+        if let Some(message) = &self.message {
+            args.extend(["--message".to_string(), message.clone()]);
+        }
+        args
+    }
+}
+
+impl BatchOptions {
+    pub fn is_batch(&self) -> bool {
+        self.all || !self.host.is_empty()
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DevOptions {
+    /// Directory for the trial deployment (default: a new temporary directory)
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+
+    /// Write the deployment without building images or starting it
+    #[arg(long)]
+    pub no_start: bool,
+
+    /// Stop a trial deployment, delete its volumes and local images, and remove its directory
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["dir", "no_start"])]
+    pub remove: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AdoptOptions {
+    /// Directory holding the deployment's docker-compose.yml
+    pub dir: PathBuf,
+
+    /// Report what would be recorded and managed without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WatchOptions {
+    /// Time between checks
+    #[arg(long, default_value = "30s")]
+    pub interval: String,
+
+    /// Longest wait between two restarts of the same service
+    #[arg(long, default_value = "10m")]
+    pub max_backoff: String,
+
+    /// Failed hub health probes in a row before the hub is restarted
+    #[arg(long, default_value_t = 3)]
+    pub hub_failures: u32,
+
+    /// POST each incident as JSON to this URL
+    #[arg(long, env = "MVRE_HUB_WATCH_WEBHOOK")]
+    pub webhook: Option<String>,
+
+    /// Check and recover once, then exit
+    #[arg(long, conflicts_with_all = ["install", "uninstall"])]
+    pub once: bool,
+
+    /// Install a systemd service running `watch` with these options
+    #[arg(long, conflicts_with = "uninstall")]
+    pub install: bool,
+
+    /// Stop and remove the systemd service
+    #[arg(long)]
+    pub uninstall: bool,
+
+    /// Install a user service instead of a system service
+    #[arg(long, requires = "install")]
+    pub user: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct OpenOptions {
+    /// Only print the summary
+    #[arg(long)]
+    pub no_browser: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct HealthOptions {
+    /// Fall back to probing the local proxy without certificate verification
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Fail when any container restarted more often than this
+    #[arg(long, default_value_t = 3)]
+    pub max_restarts: u64,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecretsCommand {
+    /// Replace secrets in .env, apply them, and restart the services that use them
+    Rotate(SecretsRotateOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SecretsRotateOptions {
+    /// Replace the OAuth client secret; issue the new one at the identity provider first
+    #[arg(long)]
+    pub oauth: bool,
+
+    /// Generate a new Postgres password and change the database role to it
+    #[arg(long)]
+    pub db: bool,
+
+    /// Rotate every secret above
+    #[arg(long, conflicts_with_all = ["oauth", "db"])]
+    pub all: bool,
+
+    /// The new OAuth client secret (prompted for when missing)
+    #[arg(long, env = "MVRE_HUB_CLIENT_SECRET", hide_env_values = true)]
+    pub client_secret: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum TokenCommand {
+    /// Issue an API token for a hub user and print it once
+    Create(TokenCreateOptions),
+    /// List a user's API tokens
+    List {
+        /// Hub user name
+        user: String,
+    },
+    /// Revoke one of a user's API tokens
+    Revoke {
+        /// Hub user name
+        user: String,
+        /// Token ID, as shown by `token list`
+        id: String,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TokenCreateOptions {
+    /// Hub user name
+    pub user: String,
+
+    /// Lifetime of the token (e.g. 12h, 30d); never expires if omitted
+    #[arg(long)]
+    pub expires_in: Option<String>,
+
+    /// Note shown next to the token in the hub's token page
+    #[arg(long, default_value = "mvre-hub")]
+    pub note: String,
+
+    /// Restrict the token to a scope (repeatable, e.g. read:users); defaults
+    /// to the user's own permissions
+    #[arg(long = "scope")]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ServerCommand {
+    /// Spawn servers, e.g. to pre-warm them before a workshop
+    Start(ServerStartOptions),
+    /// Stop servers
+    Stop(ServerOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServerOptions {
+    /// Hub user names
+    #[arg(required = true)]
+    pub users: Vec<String>,
+
+    /// Named server to act on instead of the default one
+    #[arg(long, default_value = "")]
+    pub server_name: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServerStartOptions {
+    #[command(flatten)]
+    pub server: ServerOptions,
+
+    /// Wait until the servers are ready (e.g. 5m); return once requested if omitted
+    #[arg(long)]
+    pub wait: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum UserEnvCommand {
+    /// Set variables, applied at the next server start
+    Set {
+        #[command(flatten)]
+        target: UserEnvTarget,
+
+        /// NAME=VALUE pairs
+        #[arg(required = true)]
+        vars: Vec<String>,
+    },
+    /// Remove variables
+    Unset {
+        #[command(flatten)]
+        target: UserEnvTarget,
+
+        /// Variable names
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// List the variables of every user and group, credentials masked
+    List,
+}
+
+#[derive(Args, Debug, Clone)]
+#[group(required = true, multiple = false)]
+pub struct UserEnvTarget {
+    /// Hub user whose servers get the variables
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// Hub group whose members' servers get the variables
+    #[arg(long)]
+    pub group: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AnnounceOptions {
+    /// Banner text; may contain HTML
+    #[arg(required_unless_present = "clear", conflicts_with = "clear")]
+    pub message: Option<String>,
+
+    /// Remove the current banner
+    #[arg(long)]
+    pub clear: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum GuestCommand {
+    /// Create a guest account and print a login link
+    Create(GuestCreateOptions),
+    /// List guest accounts and their expiry
+    List,
+    /// Remove expired guest accounts and their volumes
+    Prune,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GuestCreateOptions {
+    /// Lifetime of the account (e.g. 12h, 2d, 1w)
+    #[arg(long, default_value = "2d")]
+    pub expires: String,
+
+    /// Guest username (random if omitted)
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Do not install the hourly expiry timer
+    #[arg(long)]
+    pub no_timer: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum WorkshopCommand {
+    /// Create numbered accounts (ws-01, ws-02, ...) for a workshop
+    Create(WorkshopCreateOptions),
+    /// List workshops and their expiry
+    List,
+    /// Remove a workshop's accounts and their volumes
+    Teardown(WorkshopTeardownOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WorkshopCreateOptions {
+    /// Number of accounts
+    #[arg(long)]
+    pub count: u32,
+
+    /// Account name prefix; also names the workshop
+    #[arg(long, default_value = "ws")]
+    pub prefix: String,
+
+    /// Lifetime of the login links (e.g. 12h, 2d, 1w)
+    #[arg(long, default_value = "7d")]
+    pub expires: String,
+
+    /// How participants log in
+    #[arg(long, value_enum, default_value = "link")]
+    pub access: WorkshopAccess,
+
+    /// Start every account's server right away
+    #[arg(long)]
+    pub spawn: bool,
+
+    /// Do not pull the user image ahead of the workshop
+    #[arg(long)]
+    pub no_pull: bool,
+
+    /// Write the accounts and login links to this CSV file instead of printing them
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkshopAccess {
+    /// A login link per account, valid until the workshop expires
+    Link,
+    /// Put the accounts on the login allow-list, e.g. for the shared-password login
+    AllowList,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WorkshopTeardownOptions {
+    /// Prefix the workshop was created with
+    pub prefix: String,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PublishOptions {
+    /// Folder to publish, relative to the shared directory
+    pub path: PathBuf,
+
+    /// Name of the published collection (defaults to the folder name)
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PruneCommand {
+    /// Apply the orphaned-volume policy to volumes of deleted users
+    Volumes(PruneVolumesOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PruneVolumesOptions {
+    /// Report what would be removed without deleting anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Override the deployment's policy for this run
+    #[arg(long, value_enum)]
+    pub policy: Option<OrphanPolicy>,
+
+    /// Grace period to use with --policy grace
+    #[arg(long)]
+    pub grace: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum VolumesCommand {
+    /// List user volumes with their size, last server start, and last hub activity
+    Report,
+    /// Remove user volumes unused for longer than --older-than
+    Prune(VolumesPruneOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct VolumesPruneOptions {
+    /// Remove volumes whose last use is older than this (e.g. 1y, 180d)
+    #[arg(long)]
+    pub older_than: String,
+
+    /// Export each volume to --dest before removing it
+    #[arg(long, requires = "dest")]
+    pub archive_first: bool,
+
+    /// Directory or s3://bucket/prefix the archives go to
+    #[arg(long, env = "MVRE_HUB_BACKUP_DEST")]
+    pub dest: Option<String>,
+
+    /// Server-side encryption of uploaded archives
+    #[arg(long, value_enum, env = "MVRE_HUB_BACKUP_SSE")]
+    pub sse: Option<ServerSideEncryption>,
+
+    /// KMS key for --sse kms (defaults to the bucket's AWS managed key)
+    #[arg(long, env = "MVRE_HUB_BACKUP_SSE_KMS_KEY_ID", requires = "sse")]
+    pub sse_kms_key_id: Option<String>,
+
+    /// S3 endpoint of non-AWS object stores (MinIO, Ceph)
+    #[arg(long, env = "MVRE_HUB_BACKUP_ENDPOINT_URL")]
+    pub endpoint_url: Option<String>,
+
+    /// List the volumes that would be removed without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AuditOptions {
+    /// Read the deployment's log instead of the config-dir log
+    #[arg(long)]
+    pub deployment: bool,
+
+    /// Show only the most recent N entries
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+
+    /// Print raw JSON lines
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DuOptions {
+    /// Skip walking the dataset path, which can be slow on large archives
+    #[arg(long)]
+    pub no_dataset: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TopOptions {
+    /// Print one sample and exit
+    #[arg(long)]
+    pub once: bool,
+
+    /// Seconds between refreshes
+    #[arg(long, default_value_t = 2)]
+    pub interval: u64,
+
+    /// Column to sort by
+    #[arg(long, value_enum, default_value_t = TopSort::Cpu)]
+    pub sort: TopSort,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopSort {
+    Cpu,
+    Memory,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReportCommand {
+    /// Record one usage sample of every running user server
+    Sample,
+    /// Summarize a month of usage per user
+    Usage(UsageReportOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct UsageReportOptions {
+    /// Month to report (YYYY-MM, defaults to the current month)
+    #[arg(long)]
+    pub month: Option<String>,
+
+    /// Emit CSV instead of a table
+    #[arg(long)]
+    pub csv: bool,
+
+    /// Write the report to a file (CSV with --csv, otherwise JSON)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UsersCommand {
+    /// Add users to the login allow-list
+    Allow(UsersOptions),
+    /// Remove users from the login allow-list
+    Disallow(UsersOptions),
+    /// Show the login allow-list
+    List,
+    /// Show hub accounts with their server state and last activity
+    Status,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct UsersOptions {
+    /// Hub user names
+    #[arg(required = true)]
+    pub names: Vec<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum HardeningCommand {
+    /// Install a fail2ban jail that bans IPs with repeated failed logins
+    Fail2ban(Fail2banOptions),
+    /// Load the AppArmor profile for user containers into the kernel
+    Apparmor,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct Fail2banOptions {
+    /// Where fail2ban reads the hub log from
+    #[arg(long, value_enum, default_value_t = LogSource::File)]
+    pub source: LogSource,
+
+    /// Failed logins before an IP is banned
+    #[arg(long, default_value_t = 5)]
+    pub max_retry: u32,
+
+    /// Window in which failures are counted (fail2ban time, e.g. 10m)
+    #[arg(long, default_value = "10m")]
+    pub find_time: String,
+
+    /// Ban duration (fail2ban time, e.g. 1h)
+    #[arg(long, default_value = "1h")]
+    pub ban_time: String,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSource {
+    /// The hub log file on the data volume
+    File,
+    /// The hub container's journald entries (Docker journald log driver)
+    Journald,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum FirewallCommand {
+    /// Generate and install host firewall rules
+    Apply(FirewallOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct FirewallOptions {
+    /// Firewall to configure (auto picks ufw when installed)
+    #[arg(long, value_enum, default_value_t = FirewallBackend::Auto)]
+    pub backend: FirewallBackend,
+
+    /// Print the rules instead of installing them
+    #[arg(long)]
+    pub print: bool,
+
+    /// SSH port to keep open
+    #[arg(long, default_value_t = 22)]
+    pub ssh_port: u16,
+
+    /// External network interface (defaults to the one with the default route)
+    #[arg(long)]
+    pub interface: Option<String>,
+
+    /// Install without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirewallBackend {
+    Auto,
+    Ufw,
+    Nftables,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum OauthCommand {
+    /// Create the hub's OAuth client in a Keycloak realm and store its credentials
+    RegisterKeycloak(KeycloakOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct KeycloakOptions {
+    /// Realm the hub's users log in to
+    #[arg(long)]
+    pub realm: String,
+
+    /// Keycloak base URL (e.g., https://sso.example.org)
+    #[arg(long)]
+    pub admin_url: String,
+
+    /// Realm of the admin account
+    #[arg(long, default_value = "master")]
+    pub admin_realm: String,
+
+    /// Admin account user name
+    #[arg(long, default_value = "admin", env = "MVRE_HUB_KEYCLOAK_ADMIN_USER")]
+    pub admin_user: String,
+
+    /// Admin account password (prompted when not set)
+    #[arg(long, env = "MVRE_HUB_KEYCLOAK_ADMIN_PASSWORD", hide_env_values = true)]
+    pub admin_password: Option<String>,
+
+    /// Client ID to create or update
+    #[arg(long, default_value = "mvre-hub")]
+    pub client_id: String,
+
+    /// Skip TLS verification of the Keycloak server
+    #[arg(long)]
+    pub insecure: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GraphOptions {
+    /// Diagram syntax
+    #[arg(long, value_enum, default_value_t = GraphFormat::Mermaid)]
+    pub diagram: GraphFormat,
+
+    /// Write the diagram to a file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ScheduleCommand {
+    /// Install a timer that runs a job on a calendar schedule
+    Add(ScheduleAddOptions),
+    /// List scheduled jobs
+    List,
+    /// Disable and delete a scheduled job
+    Remove(ScheduleRemoveOptions),
+    /// Stop the hub for a recurring maintenance window, announcing it beforehand
+    Maintenance(ScheduleMaintenanceOptions),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ScheduleAddOptions {
+    /// Job name (letters, digits, '-', '_')
+    pub name: String,
+
+    /// systemd calendar expression (e.g. daily, hourly, "*-*-* 02:30")
+    #[arg(long, default_value = "daily")]
+    pub on_calendar: String,
+
+    /// Built-in job to run
+    #[arg(long, value_enum, conflicts_with = "command")]
+    pub job: Option<ScheduleJob>,
+
+    /// Shell command to run instead of a built-in job (e.g. an rsync mirror or backup script)
+    #[arg(long)]
+    pub command: Option<String>,
+
+    /// Install as a systemd user timer
+    #[arg(long)]
+    pub user: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleJob {
+    /// Probe the hub endpoint and its TLS certificate
+    CertCheck,
+    /// Apply the orphaned-volume policy
+    PruneVolumes,
+    /// Remove expired guest accounts
+    GuestPrune,
+    /// Record per-user resource usage for accounting reports
+    UsageSample,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ScheduleRemoveOptions {
+    /// Job name
+    pub name: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ScheduleMaintenanceOptions {
+    /// When to stop the hub: "Sat 02:00" for a weekly window, "02:00" for a daily one
+    #[arg(long, required_unless_present = "remove")]
+    pub stop: Option<String>,
+
+    /// When to start the hub again, in the same form as --stop
+    #[arg(long, required_unless_present = "remove")]
+    pub start: Option<String>,
+
+    /// How long before the stop to announce the window (under a day)
+    #[arg(long, default_value = "1h")]
+    pub notice: String,
+
+    /// Announcement text (defaults to one naming the window)
+    #[arg(long)]
+    pub message: Option<String>,
+
+    /// Install as systemd user timers
+    #[arg(long)]
+    pub user: bool,
+
+    /// Remove the maintenance window
+    #[arg(long, conflicts_with_all = ["stop", "start", "message"])]
+    pub remove: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum WebhooksCommand {
+    /// Add a URL, or change the events an existing one receives
+    Add(WebhookAddOptions),
+    /// Show the configured URLs and their events
+    List,
+    /// Remove a URL
+    Remove {
+        /// URL as shown by webhooks list
+        url: String,
+    },
+    /// Send a test event to one URL, or to all of them
+    Test {
+        /// URL as shown by webhooks list (defaults to all)
+        url: Option<String>,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WebhookAddOptions {
+    /// URL receiving a JSON POST per event
+    pub url: String,
+
+    /// Operations to report (defaults to all)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub events: Vec<LifecycleEvent>,
+
+    /// Report failures only
+    #[arg(long)]
+    pub failures_only: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DeploymentsCommand {
+    /// Show known deployments; the active one is marked
+    List,
+    /// Stop tracking a deployment; its files are left alone
+    Forget {
+        /// Deployment name
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum TenantCommand {
+    /// Create a tenant with its own group, directory, ports, and network
+    Add(Box<TenantAddOptions>),
+    /// Stop a tenant and remove its group
+    Remove(TenantRemoveOptions),
+    /// List tenants on this host
+    List,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TenantAddOptions {
+    /// Tenant name (lowercase letters, digits, '-')
+    pub name: String,
+
+    /// Directory under which tenant deployments are created
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub deploy: DeployOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TenantRemoveOptions {
+    /// Tenant name
+    pub name: String,
+
+    /// Also delete the tenant's directory, volumes, and images
+    #[arg(long)]
+    pub purge: bool,
 }
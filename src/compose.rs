@@ -0,0 +1,218 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::{
+    envfile::{self, EnvMap},
+    failure, util,
+};
+
+pub const COMPOSE_FILE: &str = "docker-compose.yml";
+/// Site-specific changes kept by the operator next to the generated compose
+/// file. mvre-hub never writes it, and every compose call layers it on top.
+pub const OVERRIDE_FILE: &str = "docker-compose.override.yml";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ComposeFile {
+    #[serde(default)]
+    pub services: BTreeMap<String, Service>,
+    #[serde(default)]
+    pub volumes: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub networks: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Service {
+    pub image: Option<String>,
+    pub build: Option<Value>,
+    #[serde(default)]
+    pub depends_on: Option<Value>,
+    #[serde(default)]
+    pub volumes: Vec<Value>,
+    #[serde(default)]
+    pub networks: Option<Value>,
+    #[serde(default)]
+    pub ports: Vec<Value>,
+    #[serde(default)]
+    pub labels: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub source: String,
+    pub target: String,
+    pub read_only: bool,
+    pub named: bool,
+}
+
+pub fn load(deploy_dir: &Path) -> Result<ComposeFile> {
+    let path = deploy_dir.join(COMPOSE_FILE);
+    let raw = util::read_to_string(&path)?;
+    parse(&raw).map_err(|err| failure::config(format!("failed to parse {}: {:#}", path.display(), err)))
+}
+
+/// The operator's override file, if `deploy_dir` has one.
+pub fn load_override(deploy_dir: &Path) -> Result<Option<ComposeFile>> {
+    let path = deploy_dir.join(OVERRIDE_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = util::read_to_string(&path)?;
+    parse(&raw)
+        .map(Some)
+        .map_err(|err| failure::config(format!("failed to parse {}: {:#}", path.display(), err)))
+}
+
+/// `-f` options naming the override file after the generated one, for a
+/// compose call with `args`. Compose would pick the override up by itself,
+/// but not once a `-f` is given anywhere, nor from another working
+/// directory; calls that name their own files are left alone.
+pub fn file_args(deploy_dir: &Path, args: &[&str]) -> Vec<&'static str> {
+    let own_files = args.iter().any(|arg| *arg == "-f" || *arg == "--file" || arg.starts_with("--file="));
+    if own_files || !deploy_dir.join(OVERRIDE_FILE).exists() {
+        return Vec::new();
+    }
+    vec!["-f", COMPOSE_FILE, "-f", OVERRIDE_FILE]
+}
+
+pub fn parse(raw: &str) -> Result<ComposeFile> {
+    let file = serde_yaml::from_str(raw)?;
+    Ok(file)
+}
+
+/// Compose project name: `COMPOSE_PROJECT_NAME`, or the directory name
+/// normalized the way compose does it.
+pub fn project_name(deploy_dir: &Path, env: &EnvMap) -> String {
+    if let Some(project) = envfile::get(env, "COMPOSE_PROJECT_NAME") {
+        return project.to_string();
+    }
+    deploy_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_'))
+        .collect()
+}
+
+impl Service {
+    pub fn dependencies(&self) -> Vec<String> {
+        keys_or_items(self.depends_on.as_ref())
+    }
+
+    pub fn network_names(&self) -> Vec<String> {
+        keys_or_items(self.networks.as_ref())
+    }
+
+    pub fn mounts(&self) -> Vec<Mount> {
+        self.volumes.iter().filter_map(parse_mount).collect()
+    }
+
+    /// Labels as `key=value`, whether given as a list or a mapping.
+    pub fn label_values(&self) -> Vec<String> {
+        match &self.labels {
+            Some(Value::Sequence(items)) => items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect(),
+            Some(Value::Mapping(map)) => map
+                .iter()
+                .filter_map(|(key, value)| {
+                    let value = match value {
+                        Value::String(text) => text.clone(),
+                        Value::Bool(flag) => flag.to_string(),
+                        Value::Number(number) => number.to_string(),
+                        _ => return None,
+                    };
+                    Some(format!("{}={}", key.as_str()?, value))
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Host port published for the given container port, if any.
+    pub fn published_port(&self, container_port: u16) -> Option<u16> {
+        self.ports.iter().find_map(|port| match port {
+            Value::String(spec) => {
+                let mut parts = spec.rsplitn(3, ':');
+                let container = parts.next()?.split('/').next()?.parse::<u16>().ok()?;
+                let host = parts.next()?.parse::<u16>().ok()?;
+                (container == container_port).then_some(host)
+            }
+            Value::Mapping(map) => {
+                let target = map.get("target").and_then(Value::as_u64)?;
+                let published = map.get("published").and_then(|value| match value {
+                    Value::Number(number) => number.as_u64(),
+                    Value::String(text) => text.parse().ok(),
+                    _ => None,
+                })?;
+                (target == u64::from(container_port)).then_some(published as u16)
+            }
+            _ => None,
+        })
+    }
+}
+
+fn keys_or_items(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::Mapping(map)) => map
+            .keys()
+            .filter_map(|key| key.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn parse_mount(value: &Value) -> Option<Mount> {
+    match value {
+        Value::String(spec) => {
+            // The source may be an interpolation such as `${DOCKER_SOCKET:-/var/run/docker.sock}`.
+            let split = source_end(spec)?;
+            let source = spec[..split].to_string();
+            let mut parts = spec[split + 1..].splitn(2, ':');
+            let target = parts.next()?.to_string();
+            let read_only = parts
+                .next()
+                .map(|mode| mode.split(',').any(|opt| opt == "ro"))
+                .unwrap_or(false);
+            let named = is_named_volume(&source);
+            Some(Mount { source, target, read_only, named })
+        }
+        Value::Mapping(map) => {
+            let get = |key: &str| map.get(key).and_then(Value::as_str).map(str::to_string);
+            let source = get("source")?;
+            let target = get("target")?;
+            let read_only = map.get("read_only").and_then(Value::as_bool).unwrap_or(false);
+            let named = get("type").map(|kind| kind == "volume").unwrap_or_else(|| is_named_volume(&source));
+            Some(Mount { source, target, read_only, named })
+        }
+        _ => None,
+    }
+}
+
+/// Index of the `:` ending the source of a short volume spec, skipping any
+/// inside `${...}`.
+fn source_end(spec: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, ch) in spec.char_indices() {
+        match ch {
+            '{' if spec[..index].ends_with('$') => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            ':' if depth == 0 => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_named_volume(source: &str) -> bool {
+    !(source.starts_with('/') || source.starts_with('.') || source.starts_with('~') || source.starts_with('$'))
+}
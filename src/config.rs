@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
@@ -6,17 +7,193 @@ use std::{
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::{failure, util};
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct AppConfig {
+    /// Directory of the active deployment, kept in step with `active` for
+    /// releases that only know this field.
     pub last_deploy_dir: Option<PathBuf>,
     pub last_domain: Option<String>,
+    /// Known deployments by name; commands act on the `active` one.
+    #[serde(default)]
+    pub deployments: BTreeMap<String, Deployment>,
+    #[serde(default)]
+    pub active: Option<String>,
+    /// `--deploy-dir` of the running command; wins over `active` and is
+    /// never saved.
+    #[serde(skip)]
+    pub deploy_dir_override: Option<PathBuf>,
+    /// Root directory under which tenant deployments are created.
+    #[serde(default)]
+    pub tenant_root: Option<PathBuf>,
+    #[serde(default)]
+    pub tenants: BTreeMap<String, Tenant>,
+    /// Window of configs written before each deployment had its own; moved
+    /// to the active deployment on load.
+    #[serde(default, rename = "maintenance", skip_serializing)]
+    pub legacy_maintenance: Option<MaintenanceWindow>,
+    /// URLs told when lifecycle operations finish.
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Deployment {
+    pub deploy_dir: PathBuf,
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// Recurring window installed by `schedule maintenance`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<MaintenanceWindow>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    pub stop: String,
+    pub start: String,
+    pub notice: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    /// Operations reported, by name; all of them when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub failures_only: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Tenant {
+    pub deploy_dir: PathBuf,
+    pub domain: String,
+    pub group: String,
+    pub project_name: String,
+    pub http_port: u16,
+    pub https_port: u16,
+}
+
+impl AppConfig {
+    /// Directory of the active deployment.
+    pub fn active_dir(&self) -> Option<&Path> {
+        self.active
+            .as_ref()
+            .and_then(|name| self.deployments.get(name))
+            .map(|deployment| deployment.deploy_dir.as_path())
+            .or(self.last_deploy_dir.as_deref())
+    }
+
+    /// Name under which `deploy_dir` is known.
+    pub fn deployment_name(&self, deploy_dir: &Path) -> Option<&str> {
+        self.deployments
+            .iter()
+            .find(|(_, deployment)| deployment.deploy_dir == deploy_dir)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Maintenance window of the deployment in `deploy_dir`.
+    pub fn maintenance(&self, deploy_dir: &Path) -> Option<&MaintenanceWindow> {
+        let name = self.deployment_name(deploy_dir)?;
+        self.deployments.get(name)?.maintenance.as_ref()
+    }
+
+    /// Adds `deploy_dir` under a name derived from its directory name, unless
+    /// it is known already, and returns that name.
+    pub fn register(&mut self, deploy_dir: &Path, domain: Option<&str>) -> String {
+        if let Some(name) = self.deployment_name(deploy_dir).map(str::to_string) {
+            if let Some(domain) = domain {
+                self.deployments.get_mut(&name).expect("known deployment").domain = Some(domain.to_string());
+            }
+            return name;
+        }
+        let stem = deployment_stem(deploy_dir);
+        let name = std::iter::once(stem.clone())
+            .chain((2..).map(|n| format!("{}-{}", stem, n)))
+            .find(|name| !self.deployments.contains_key(name))
+            .expect("unbounded candidates");
+        self.deployments.insert(
+            name.clone(),
+            Deployment {
+                deploy_dir: deploy_dir.to_path_buf(),
+                domain: domain.map(str::to_string),
+                maintenance: None,
+            },
+        );
+        name
+    }
+
+    /// Makes the named deployment the one commands act on.
+    pub fn activate(&mut self, name: &str) -> Result<()> {
+        let deployment = self
+            .deployments
+            .get(name)
+            .with_context(|| format!("unknown deployment '{}'; see mvre-hub deployments list", name))?;
+        self.last_deploy_dir = Some(deployment.deploy_dir.clone());
+        if deployment.domain.is_some() {
+            self.last_domain = deployment.domain.clone();
+        }
+        self.active = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Registers `deploy_dir` and, when no deployment is active yet, activates it.
+    pub fn remember(&mut self, deploy_dir: &Path) {
+        let name = self.register(deploy_dir, None);
+        if self.active_dir().is_none() {
+            self.activate(&name).ok();
+        }
+    }
+
+    /// Drops `deploy_dir` from the known deployments; it stops being active.
+    pub fn forget(&mut self, deploy_dir: &Path) {
+        self.deployments.retain(|_, deployment| deployment.deploy_dir != deploy_dir);
+        if self.active.as_ref().is_some_and(|name| !self.deployments.contains_key(name)) {
+            self.active = None;
+        }
+        if self.last_deploy_dir.as_deref() == Some(deploy_dir) {
+            self.last_deploy_dir = None;
+        }
+    }
+
+    /// Configs written before deployments had names only know the last one.
+    fn migrate(&mut self) {
+        if self.active.is_none() {
+            if let Some(dir) = self.last_deploy_dir.clone() {
+                let name = self.register(&dir, self.last_domain.clone().as_deref());
+                self.active = Some(name);
+            }
+        }
+        // The old global window's timers acted on the active deployment.
+        if let Some(window) = self.legacy_maintenance.take() {
+            let active = self.active.clone().and_then(|name| self.deployments.get_mut(&name));
+            if let Some(deployment) = active {
+                deployment.maintenance.get_or_insert(window);
+            }
+        }
+    }
+}
+
+/// Deployment name from its directory: `/srv/hubs/geo` becomes `geo`.
+fn deployment_stem(deploy_dir: &Path) -> String {
+    let stem: String = deploy_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' { ch } else { '-' })
+        .collect();
+    let stem = stem.trim_matches('-').to_string();
+    if stem.is_empty() {
+        "default".to_string()
+    } else {
+        stem
+    }
 }
 
 pub fn resolve_config_path() -> Result<PathBuf> {
-    let base = std::env::var_os("XDG_CONFIG_HOME")
-        .map(PathBuf::from)
-        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
-        .context("unable to resolve config directory (XDG_CONFIG_HOME or HOME)")?;
+    let base = util::config_home()?;
 
     Ok(base.join("mvre-hub").join("config.json"))
 }
@@ -28,7 +205,9 @@ pub fn load() -> Result<AppConfig> {
     }
 
     let raw = fs::read_to_string(&path).with_context(|| format!("failed to read config at {}", path.display()))?;
-    let cfg = serde_json::from_str(&raw).with_context(|| format!("failed to parse config at {}", path.display()))?;
+    let mut cfg: AppConfig = serde_json::from_str(&raw)
+        .map_err(|err| failure::config(format!("failed to parse config at {}: {}", path.display(), err)))?;
+    cfg.migrate();
     Ok(cfg)
 }
 
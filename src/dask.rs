@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+/// Image build files and config of the `dask-gateway` service.
+pub const GATEWAY_DIR: &str = "dask-gateway";
+/// Server and client must match, so both are pinned to the same release.
+pub const GATEWAY_VERSION: &str = "2024.1.0";
+/// Where the hub's proxy exposes the gateway API and cluster dashboards.
+pub const SERVICE_PREFIX: &str = "/services/dask-gateway";
+
+pub fn files(deploy_dir: &Path) -> Vec<(PathBuf, String)> {
+    let dir = deploy_dir.join(GATEWAY_DIR);
+    vec![
+        (dir.join("Dockerfile"), dockerfile()),
+        (dir.join("dask_gateway_config.py"), gateway_config()),
+    ]
+}
+
+/// Built from the user image's requirements, so scheduler and workers run
+/// the same libraries as the notebooks that submit to them.
+pub fn dockerfile() -> String {
+    format!(
+        r#"FROM jupyter/minimal-notebook:latest
+
+COPY user/requirements.txt /tmp/requirements.txt
+RUN pip install --no-cache-dir -r /tmp/requirements.txt "dask-gateway-server[local]=={version}" \
+ && rm -rf /home/jovyan/.cache/pip
+"#,
+        version = GATEWAY_VERSION
+    )
+}
+
+/// Client package added to the user image.
+pub fn client_requirement() -> String {
+    format!("dask-gateway=={}", GATEWAY_VERSION)
+}
+
+/// Clusters run as processes inside the gateway container; users
+/// authenticate with their JupyterHub API token.
+pub fn gateway_config() -> String {
+    format!(
+        r#"import os
+
+c = get_config()
+
+c.Proxy.address = ":8000"
+c.Proxy.tcp_address = ":8786"
+c.Proxy.prefix = "{prefix}"
+
+c.DaskGateway.backend_class = "dask_gateway_server.backends.local.UnsafeLocalBackend"
+c.DaskGateway.authenticator_class = "dask_gateway_server.auth.JupyterHubAuthenticator"
+c.JupyterHubAuthenticator.jupyterhub_api_token = os.environ["DASK_GATEWAY_API_TOKEN"]
+c.JupyterHubAuthenticator.jupyterhub_api_url = "http://jupyterhub:8000/hub/api"
+if os.environ.get("INTERNAL_TLS", "false").lower() == "true":
+    c.JupyterHubAuthenticator.jupyterhub_api_url = "https://jupyterhub:8000/hub/api"
+    c.JupyterHubAuthenticator.tls_ca = "/etc/mvre-hub/tls/ca.crt"
+"#,
+        prefix = SERVICE_PREFIX
+    )
+}
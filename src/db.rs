@@ -0,0 +1,218 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+
+use crate::{
+    cli::{DbCommand, DbDumpOptions, DbRestoreOptions},
+    config::AppConfig,
+    engine::{self, Engine, Remote},
+    envfile::{self, EnvMap},
+    failure, say, services, util,
+};
+
+const SQLITE_FILE: &str = "jupyterhub_data/jupyterhub.sqlite";
+
+/// The hub database of a deployment, as configured in `.env`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Database {
+    /// The deployment's own `postgres` service, reached with `docker-compose exec`.
+    Compose { user: String, name: String },
+    /// A Postgres server elsewhere, reached with the local client tools.
+    External { url: String },
+    Sqlite { path: PathBuf },
+}
+
+pub fn run(command: DbCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let env = envfile::load(&deploy_dir)?;
+    let database = Database::from_env(&deploy_dir, &env);
+    if let Database::Sqlite { path } = &database {
+        if !path.exists() && !matches!(command, DbCommand::Restore(_)) {
+            return Err(failure::config(format!("No hub database at {}; start the hub once to create it", path.display())));
+        }
+    }
+    match command {
+        DbCommand::Shell => {
+            let status = command_in(&deploy_dir, &database.shell_command())
+                .status()
+                .with_context(|| format!("failed to run {}", database.shell_command()[0]))?;
+            if !status.success() {
+                anyhow::bail!("database shell exited with status {}", status);
+            }
+            Ok(())
+        }
+        DbCommand::Dump(opts) => dump(&deploy_dir, &database, opts),
+        DbCommand::Restore(opts) => restore(&deploy_dir, &database, opts),
+        DbCommand::Vacuum => {
+            let _spinner = crate::progress::Spinner::start("Vacuuming database");
+            run_with(&deploy_dir, &database.vacuum_command(), Stdio::null(), engine::terminal_stdout())?;
+            say!("{}", style("Database vacuumed and analysed").green());
+            util::set_result(serde_json::json!({ "database": database.kind() }));
+            Ok(())
+        }
+    }
+}
+
+impl Database {
+    pub fn from_env(deploy_dir: &Path, env: &EnvMap) -> Self {
+        let value = |key: &str| envfile::get(env, key).unwrap_or("").to_string();
+        if value("ENABLE_POSTGRES") == "true" && value("DB_HOST") == "postgres" {
+            return Database::Compose {
+                user: value("DB_USER"),
+                name: value("DB_NAME"),
+            };
+        }
+        match envfile::get(env, "JUPYTERHUB_DB_URL") {
+            Some(url) if url.starts_with("postgres") => Database::External { url: url.to_string() },
+            _ => Database::Sqlite {
+                path: deploy_dir.join(SQLITE_FILE),
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Database::Compose { .. } => "postgres",
+            Database::External { .. } => "external postgres",
+            Database::Sqlite { .. } => "sqlite",
+        }
+    }
+
+    pub fn shell_command(&self) -> Vec<String> {
+        match self {
+            Database::Compose { user, name } => args(&["docker-compose", "exec", "postgres", "psql", "-U", user, name]),
+            Database::External { url } => args(&["psql", url]),
+            Database::Sqlite { path } => args(&["sqlite3", &path.to_string_lossy()]),
+        }
+    }
+
+    /// Plain SQL on stdout. Postgres dumps drop objects before recreating
+    /// them, so they restore into a populated database.
+    pub fn dump_command(&self) -> Vec<String> {
+        match self {
+            Database::Compose { user, name } => args(&[
+                "docker-compose", "exec", "-T", "postgres",
+                "pg_dump", "--clean", "--if-exists", "-U", user, name,
+            ]),
+            Database::External { url } => args(&["pg_dump", "--clean", "--if-exists", url]),
+            Database::Sqlite { path } => args(&["sqlite3", &path.to_string_lossy(), ".dump"]),
+        }
+    }
+
+    /// Reads SQL from stdin; stops at the first error.
+    pub fn restore_command(&self) -> Vec<String> {
+        match self {
+            Database::Compose { user, name } => args(&[
+                "docker-compose", "exec", "-T", "postgres",
+                "psql", "-v", "ON_ERROR_STOP=1", "-U", user, "-d", name,
+            ]),
+            Database::External { url } => args(&["psql", "-v", "ON_ERROR_STOP=1", url]),
+            Database::Sqlite { path } => args(&["sqlite3", "-bail", &path.to_string_lossy()]),
+        }
+    }
+
+    pub fn vacuum_command(&self) -> Vec<String> {
+        match self {
+            Database::Compose { user, name } => args(&[
+                "docker-compose", "exec", "-T", "postgres",
+                "psql", "-U", user, "-d", name, "-c", "VACUUM ANALYZE",
+            ]),
+            Database::External { url } => args(&["psql", url, "-c", "VACUUM ANALYZE"]),
+            Database::Sqlite { path } => args(&["sqlite3", &path.to_string_lossy(), "VACUUM; ANALYZE;"]),
+        }
+    }
+}
+
+fn dump(deploy_dir: &Path, database: &Database, opts: DbDumpOptions) -> Result<()> {
+    let output = opts.output.unwrap_or_else(|| {
+        let stamp = util::format_timestamp(util::unix_now()).replace([':', '-'], "");
+        PathBuf::from(format!("jupyterhub-{}.sql", stamp))
+    });
+    let file = File::create(&output).with_context(|| format!("failed to create {}", output.display()))?;
+    util::set_file_mode(&output, 0o600).ok();
+    let result = {
+        let _spinner = crate::progress::Spinner::start("Dumping database");
+        run_with(deploy_dir, &database.dump_command(), Stdio::null(), Stdio::from(file))
+    };
+    if let Err(err) = result {
+        std::fs::remove_file(&output).ok();
+        return Err(err);
+    }
+    say!("{} {}", style("Database dumped to").green(), output.display());
+    util::set_result(serde_json::json!({ "database": database.kind(), "dump": output }));
+    Ok(())
+}
+
+/// Replaces the database with a dump. The hub is stopped meanwhile, so it
+/// neither writes to nor caches the old state.
+fn restore(deploy_dir: &Path, database: &Database, opts: DbRestoreOptions) -> Result<()> {
+    let input = File::open(&opts.file).with_context(|| format!("failed to open {}", opts.file.display()))?;
+    let prompt = format!("Replace the {} hub database with {}?", database.kind(), opts.file.display());
+    if !opts.yes {
+        util::ensure_interactive(&prompt, "pass --yes")?;
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            return Err(failure::validation("Aborted"));
+        }
+    }
+
+    let engine = Engine::new(deploy_dir);
+    engine.compose(&["stop", "jupyterhub"]).context("failed to stop the hub")?;
+    if let Database::Sqlite { path } = database {
+        // sqlite3 appends to an existing file; start from an empty one.
+        if path.exists() {
+            let previous = path.with_extension("sqlite.bak");
+            std::fs::rename(path, &previous).with_context(|| format!("failed to move {} aside", path.display()))?;
+            say!("Previous database kept as {}", style(previous.display()).dim());
+        }
+    }
+    let restored = run_with(deploy_dir, &database.restore_command(), Stdio::from(input), Stdio::null());
+    let started = engine.compose(&["start", "jupyterhub"]).context("failed to start the hub");
+    restored?;
+    started?;
+    say!("{}", style(format!("Database restored from {}", opts.file.display())).green());
+    util::set_result(serde_json::json!({ "database": database.kind(), "restored": opts.file }));
+    Ok(())
+}
+
+/// Compose commands run in the deployment; the others get paths as given.
+fn command_in(deploy_dir: &Path, argv: &[String]) -> Command {
+    let mut command = Command::new(&argv[0]);
+    command.args(&argv[1..]);
+    if argv[0] == "docker-compose" {
+        command.current_dir(deploy_dir);
+        Remote::load(deploy_dir).apply(&mut command);
+    }
+    command
+}
+
+/// Runs one of the commands of [`Database`] with `stdin` and `stdout`,
+/// failing with its stderr.
+pub fn run_with(deploy_dir: &Path, argv: &[String], stdin: Stdio, stdout: Stdio) -> Result<()> {
+    let output = command_in(deploy_dir, argv)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .output()
+        .with_context(|| match argv[0].as_str() {
+            "docker-compose" => "failed to invoke docker-compose".to_string(),
+            tool => format!("failed to run {}; install the {} client", tool, if tool == "sqlite3" { "SQLite" } else { "PostgreSQL" }),
+        })?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed: {}", argv[0], String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+fn args(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
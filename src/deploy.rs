@@ -1,20 +1,46 @@
 use std::{
-    fs,
+    collections::BTreeMap,
     path::{Path, PathBuf},
 };
 
 use anyhow::{Context, Result};
 use console::style;
-use dialoguer::{Confirm, Password, theme::ColorfulTheme};
+use dialoguer::{Confirm, Password, Select, theme::ColorfulTheme};
 
 use crate::{
-    cli::DeployOptions,
+    access, answers, audit,
+    branding::{self, BrandingSources},
+    dask, failure, filebrowser,
+    lock,
+    logconfig::{self, ContainerLogging},
+    logstack,
+    landing::{self, LandingPage},
+    merge,
+    mlflow,
+    nbgrader,
+    engine::Remote,
+    cli::{
+        AuthPreset, DeployOptions, IdpPreset, InitKind, LandingPath, LogDriver, OnConflict, ProxyVersion,
+        ReconfigureOptions, TlsMode, WorkspaceBackend,
+    },
+    compose,
     config::{self, AppConfig},
-    systemd,
-    templates,
+    envfile::{self, EnvMap},
+    hardening,
+    idp::{self, ExtraIdp},
+    init,
+    report,
+    rootless::{self, DockerMode},
+    say, selinux, services, snapshot,
+    templates, tls,
+    users,
     util,
+    workspace,
 };
 
+/// Helmholtz AAI publishes VO memberships in this userinfo claim.
+const DEFAULT_ENTITLEMENT_CLAIM: &str = "eduperson_entitlement";
+
 const BANNER: &str = r#"
     __/___
   _____/_____
@@ -26,6 +52,10 @@ const BANNER: &str = r#"
 struct DeployInputs {
     domain: String,
     acme_email: String,
+    /// Certificates from Let's Encrypt's staging endpoint.
+    acme_staging: bool,
+    tls_mode: TlsMode,
+    auth: AuthPreset,
     client_id: String,
     client_secret: String,
     dataset_path: String,
@@ -33,11 +63,18 @@ struct DeployInputs {
     shared_path: Option<String>,
     shared_mount: String,
     admin_users: Option<String>,
+    allowed_users: Option<String>,
     user_image: String,
+    /// Repository the user image is built from with repo2docker.
+    user_env_repo: Option<String>,
+    user_env_ref: Option<String>,
     oauth_authorize_url: Option<String>,
     oauth_token_url: Option<String>,
     oauth_userdata_url: Option<String>,
     oauth_username_key: String,
+    required_entitlements: Option<String>,
+    entitlement_claim: String,
+    extra_idps: Vec<ExtraIdp>,
     install_notebooks: bool,
     allow_missing_dataset: bool,
     production: bool,
@@ -50,6 +87,77 @@ struct DeployInputs {
     mem_limit: Option<String>,
     cull_timeout: Option<u64>,
     cull_every: Option<u64>,
+    cull_groups: Vec<(String, u64)>,
+    groups: Vec<answers::HubGroup>,
+    /// Per-user and per-group overrides of `cpu_limit` and `mem_limit`.
+    limits: answers::ResourceLimits,
+    extra_services: BTreeMap<String, answers::ExtraService>,
+    hub_api_token: String,
+    spawn_min_free_disk_gb: Option<f64>,
+    spawn_min_free_mem_gb: Option<f64>,
+    orphan_volume_policy: String,
+    orphan_volume_grace: String,
+    publishing: bool,
+    http_port: u16,
+    https_port: u16,
+    proxy_version: ProxyVersion,
+    security_headers: bool,
+    csp: Option<String>,
+    rate_limit: Option<u32>,
+    rate_limit_burst: Option<u32>,
+    concurrent_spawn_limit: Option<u32>,
+    active_server_limit: Option<u32>,
+    internal_tls: bool,
+    docker_socket: String,
+    /// Daemon the deployment is managed through, when not the local one.
+    docker_remote: Remote,
+    selinux: bool,
+    sandbox_profiles: bool,
+    apparmor: bool,
+    notebook_uid: Option<u32>,
+    notebook_gid: Option<u32>,
+    workspace_backend: WorkspaceBackend,
+    workspace_root: Option<String>,
+    workspace_host_path: Option<String>,
+    scratch_root: Option<String>,
+    workspace_quota: Option<String>,
+    hub_title: Option<String>,
+    brand_color: Option<String>,
+    /// Logo file name in the branding directory.
+    brand_logo: Option<String>,
+    welcome_text: Option<String>,
+    terms_of_use: bool,
+    branding_sources: BrandingSources,
+    landing: Option<LandingPath>,
+    /// Host directory of an operator-provided site; the page is generated otherwise.
+    landing_site: Option<String>,
+    support_email: Option<String>,
+    data_citation: Option<String>,
+    dask_gateway: bool,
+    /// Token the gateway checks users' hub tokens with.
+    dask_gateway_token: String,
+    mlflow: bool,
+    /// Artifact directory as given; `./mlflow/artifacts` when unset.
+    mlflow_artifacts: Option<String>,
+    nbgrader: bool,
+    nbgrader_course: String,
+    nbgrader_instructors: String,
+    file_browser: bool,
+    /// Client secret of the file browser's OAuth client at the hub.
+    file_browser_oauth_secret: String,
+    /// Signs the file browser's login cookies.
+    file_browser_cookie_secret: String,
+    logging_stack: bool,
+    log_retention: String,
+    grafana_admin_password: String,
+    log_driver: LogDriver,
+    log_max_size: String,
+    log_max_file: u32,
+    /// Shared password of the dummy authenticator, which replaces OAuth when set.
+    dummy_password: Option<String>,
+    project_name: Option<String>,
+    /// `mvre-<tenant>-` for tenants, empty otherwise.
+    resource_prefix: String,
 }
 
 pub fn run(
@@ -57,81 +165,980 @@ pub fn run(
     config_path: &Path,
     app_config: &mut AppConfig,
 ) -> Result<()> {
-    println!("\n{}", style(BANNER).cyan());
-    println!("{}", style("MVRE Polar Drift Hub").cyan().bold());
-    println!("{}", style("Arctic Mission Deployment Route").dim());
+    let target = app_config.deploy_dir_override.clone();
+    run_in(target, opts, config_path, app_config)
+}
 
-    let deploy_dir = resolve_deploy_dir(opts.force, app_config.last_deploy_dir.clone())?;
-    let inputs = collect_inputs(&opts, app_config.last_domain.clone())?;
+/// Deploys into `target`, or prompts for the directory when `None`.
+pub fn run_in(
+    target: Option<PathBuf>,
+    opts: DeployOptions,
+    config_path: &Path,
+    app_config: &mut AppConfig,
+) -> Result<()> {
+    say!("\n{}", style(BANNER).cyan());
+    say!("{}", style("MVRE Polar Drift Hub").cyan().bold());
+    say!("{}", style("Arctic Mission Deployment Route").dim());
+
+    let started = util::unix_now();
+    let written_before = audit::changed_files().len();
+    let (deploy_dir, previous) = resolve_deploy_dir(&opts, target, app_config.active_dir().map(Path::to_path_buf))?;
+    // A new directory has nothing to guard yet; a second deploy into it
+    // stops at "Deployment exists".
+    let _lock = if deploy_dir.exists() {
+        Some(lock::acquire(&deploy_dir, &lock::invocation())?)
+    } else {
+        None
+    };
+    let mut inputs = collect_inputs(&opts, app_config.last_domain.clone(), previous.as_ref())?;
+    // Recording the project compose would pick anyway keeps the network
+    // names apart from other deployments on the host.
+    let current_project = previous
+        .as_ref()
+        .map(|env| compose::project_name(&deploy_dir, env));
+    if inputs.project_name.is_none() {
+        inputs.project_name = current_project
+            .clone()
+            .or_else(|| Some(compose::project_name(&deploy_dir, &EnvMap::new())))
+            .filter(|name| !name.is_empty());
+    }
+    if let (Some(current), Some(project)) = (&current_project, &inputs.project_name) {
+        if current != project {
+            say!(
+                "{}",
+                style(format!(
+                    "The compose project changes from {} to {}; stop the old services first with docker-compose -p {} down",
+                    current, project, current
+                ))
+                .yellow()
+            );
+        }
+    }
+
+    if !opts.yes && !review(&deploy_dir, &mut inputs)? {
+        return Err(failure::validation("Deployment aborted; nothing was written"));
+    }
+    // Hand edits of a previous deployment are merged in, so conflicts are
+    // settled before anything is replaced.
+    let mut updates = merge::plan_files(&deploy_dir, generated_files(&deploy_dir, &inputs));
+    merge::resolve(&deploy_dir, &mut updates, opts.on_conflict, !opts.yes)?;
+    if deploy_dir.exists() {
+        replace_existing(&deploy_dir, previous.is_some(), opts.snapshot_db)?;
+    }
 
     create_dirs(&deploy_dir, inputs.shared_path.is_some())?;
-    write_configs(&deploy_dir, &inputs)?;
+    write_configs(&deploy_dir, &inputs, &updates)?;
     chown_dir(&deploy_dir)?;
 
-    app_config.last_deploy_dir = Some(deploy_dir.clone());
-    app_config.last_domain = Some(inputs.domain.clone());
+    let previous_active = app_config.active.clone();
+    let registered = std::path::absolute(&deploy_dir).unwrap_or_else(|_| deploy_dir.clone());
+    let name = app_config.register(&registered, Some(&inputs.domain));
+    // A deploy aimed with --deploy-dir leaves the active deployment alone.
+    if app_config.deploy_dir_override.is_none() || previous_active.is_none() {
+        app_config.activate(&name)?;
+    }
     config::save(config_path, app_config)?;
+    match previous_active {
+        Some(previous) if previous != name && app_config.active.as_deref() == Some(name.as_str()) => say!(
+            "Active deployment is now {} (was {}; switch back with {})",
+            style(&name).cyan(),
+            previous,
+            style(format!("mvre-hub use {}", previous)).cyan()
+        ),
+        _ => {}
+    }
 
-    if !opts.no_systemd {
-        maybe_setup_systemd(&deploy_dir)?;
+    if inputs.docker_remote.is_set() {
+        // Units on this machine would start the stack on the local daemon.
+        if !opts.no_systemd {
+            say!("{}", style("Skipping auto-start: the hub runs on a remote Docker daemon").dim());
+        }
+    } else if !opts.no_systemd {
+        let rootless = DockerMode::from_socket(&inputs.docker_socket).rootless;
+        maybe_setup_autostart(&deploy_dir, &opts, rootless)?;
+    }
+    let written = audit::changed_files().split_off(written_before);
+    let report = report::write(&deploy_dir, &envfile::load(&deploy_dir)?, started, &written)?;
+
+    say!("\n{}", style("Drift Established").green().bold());
+    say!("1. Start services: {}", style("mvre-hub start").cyan());
+    say!("2. Access hub: {}", style(inputs.public_url()).cyan());
+    if inputs.acme_staging {
+        say!("{}", style(tls::ACME_STAGING_NOTICE).yellow());
     }
+    if !inputs.extra_idps.is_empty() {
+        say!("Register these OAuth callback URLs with each identity provider:");
+        for (provider, url) in idp::callback_urls(&inputs.domain, inputs.auth, &inputs.extra_idps) {
+            say!("  {:<14} {}", provider, style(url).dim());
+        }
+    }
+    say!("Deploy report: {}", style(report.display()).dim());
+    util::set_result(serde_json::json!({
+        "deploy_dir": deploy_dir,
+        "domain": inputs.domain,
+        "acme_staging": inputs.acme_staging,
+        "report": report,
+    }));
+
+    Ok(())
+}
 
-    println!("\n{}", style("Drift Established").green().bold());
-    println!("1. Start services: {}", style("mvre-hub start").cyan());
-    println!("2. Access hub: {}", style(format!("https://{}", inputs.domain)).cyan());
+/// Re-renders the input-dependent files of an existing deployment, keeping
+/// data directories, volumes, and generated secrets in place.
+pub fn reconfigure(opts: ReconfigureOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let env = envfile::load(&deploy_dir)?;
+    let inputs = reconfigured_inputs(&deploy_dir, opts.clone(), &env)?;
 
+    let Some((snapshot, updated)) =
+        apply_inputs(&deploy_dir, &inputs, "pre-reconfigure", opts.on_conflict, !opts.yes)?
+    else {
+        say!("{}", style("Configuration unchanged").dim());
+        return Ok(());
+    };
+
+    say!("{}", style("Reconfigured").green());
+    if opts.acme_staging {
+        say!("{}", style(tls::ACME_STAGING_NOTICE).yellow());
+    }
+    if opts.auth.is_some() || opts.extra_idp.is_some() || opts.no_extra_idps {
+        say!("Register these OAuth callback URLs with each identity provider:");
+        for (provider, url) in idp::callback_urls(&inputs.domain, inputs.auth, &inputs.extra_idps) {
+            say!("  {:<14} {}", provider, style(url).dim());
+        }
+    }
+    util::set_result(serde_json::json!({ "snapshot": snapshot, "updated": updated }));
+    say!("Apply with: {}", style("mvre-hub start").cyan());
     Ok(())
 }
 
-fn resolve_deploy_dir(force: bool, default: Option<PathBuf>) -> Result<PathBuf> {
-    let default_dir = default.unwrap_or_else(|| PathBuf::from("./mvre-hub"));
-    let deploy_dir = util::prompt_or_use(
-        Some(util::path_display(&default_dir)),
-        "Enter deployment directory",
-        false,
+/// What `reconfigure` with `opts` would write, computed without writing.
+#[derive(Debug, Clone, Default)]
+pub struct RenderPlan {
+    /// `.env` as it would be afterwards.
+    pub env: EnvMap,
+    /// Files whose contents would change.
+    pub changed: Vec<PathBuf>,
+    /// Files whose hand edits overlap the change and need `--on-conflict`.
+    pub conflicts: Vec<PathBuf>,
+}
+
+pub fn plan_reconfigure(deploy_dir: &Path, opts: ReconfigureOptions) -> Result<RenderPlan> {
+    let env = envfile::load(deploy_dir)?;
+    let inputs = reconfigured_inputs(deploy_dir, opts, &env)?;
+    let updates = merge::plan_files(deploy_dir, generated_files(deploy_dir, &inputs));
+    let env_path = deploy_dir.join(envfile::ENV_FILE);
+    let mut plan = RenderPlan {
+        env: updates
+            .iter()
+            .find(|update| update.path == env_path)
+            .map(|update| envfile::parse(update.contents()))
+            .unwrap_or(env),
+        ..RenderPlan::default()
+    };
+    for update in updates.iter().filter(|update| update.changes_file()) {
+        if matches!(update.action, merge::Action::Conflict(_)) {
+            plan.conflicts.push(update.path.clone());
+        }
+        plan.changed.push(update.path.clone());
+    }
+    Ok(plan)
+}
+
+/// The inputs of the deployment described by `env` with the flags of `opts`
+/// applied, prompting for the main settings unless `--yes` was given.
+fn reconfigured_inputs(deploy_dir: &Path, opts: ReconfigureOptions, env: &EnvMap) -> Result<DeployInputs> {
+    let mut inputs = DeployInputs::from_deployment(deploy_dir, env)?;
+    let interactive = !opts.yes;
+
+    inputs.domain = reconfigure_value(opts.domain, inputs.domain, "Domain name", interactive)?;
+    if let Some(mode) = opts.tls {
+        inputs.tls_mode = mode;
+    }
+    inputs.acme_email = if inputs.tls_mode == TlsMode::Acme {
+        reconfigure_value(opts.acme_email, inputs.acme_email, "ACME email (for TLS)", interactive)?
+    } else {
+        opts.acme_email.unwrap_or(inputs.acme_email)
+    };
+    if opts.acme_staging {
+        inputs.acme_staging = true;
+    }
+    if opts.no_acme_staging {
+        inputs.acme_staging = false;
+    }
+    validate_tls(&inputs)?;
+    if let Some(auth) = opts.auth {
+        inputs.auth = auth;
+    }
+    inputs.client_id = reconfigure_value(
+        opts.client_id,
+        inputs.client_id,
+        &format!("{} Client ID", inputs.auth.label()),
+        interactive,
     )?;
+    if let Some(secret) = opts.client_secret {
+        inputs.client_secret = secret;
+    }
+    inputs.dataset_path =
+        reconfigure_value(opts.dataset_path, inputs.dataset_path, "MoSAiC dataset host path", interactive)?;
+    inputs.admin_users = reconfigure_optional(
+        opts.admin_users,
+        inputs.admin_users,
+        "Admin users (comma-separated, optional)",
+        interactive,
+    )?;
+    if let Some(path) = &opts.allowed_users {
+        let names = users::read_list_file(path)?;
+        inputs.allowed_users = Some(names.join(",")).filter(|list| !list.is_empty());
+    }
+    if inputs.auth.has_endpoints() {
+        inputs.oauth_authorize_url = None;
+        inputs.oauth_token_url = None;
+        inputs.oauth_userdata_url = None;
+    } else {
+        inputs.oauth_authorize_url = Some(reconfigure_value(
+            opts.oauth_authorize_url,
+            inputs.oauth_authorize_url.unwrap_or_default(),
+            "OAuth authorize URL",
+            interactive,
+        )?);
+        inputs.oauth_token_url = Some(reconfigure_value(
+            opts.oauth_token_url,
+            inputs.oauth_token_url.unwrap_or_default(),
+            "OAuth token URL",
+            interactive,
+        )?);
+        inputs.oauth_userdata_url = Some(reconfigure_value(
+            opts.oauth_userdata_url,
+            inputs.oauth_userdata_url.unwrap_or_default(),
+            "OAuth userinfo URL",
+            interactive,
+        )?);
+    }
+
+    if let Some(entitlements) = opts.required_entitlement {
+        inputs.required_entitlements = Some(entitlements).filter(|value| !value.trim().is_empty());
+    }
+    if let Some(claim) = opts.entitlement_claim {
+        inputs.entitlement_claim = claim;
+    }
+    if let Some(presets) = &opts.extra_idp {
+        inputs.extra_idps = idp::collect(presets, Some(env))?;
+    }
+    if opts.no_extra_idps {
+        inputs.extra_idps.clear();
+    }
+    if opts.spawn_min_free_disk_gb.is_some() {
+        inputs.spawn_min_free_disk_gb = opts.spawn_min_free_disk_gb;
+    }
+    if opts.spawn_min_free_mem_gb.is_some() {
+        inputs.spawn_min_free_mem_gb = opts.spawn_min_free_mem_gb;
+    }
+    if let Some(policy) = opts.orphan_volume_policy {
+        inputs.orphan_volume_policy = policy.as_str().to_string();
+    }
+    if let Some(grace) = opts.orphan_volume_grace {
+        util::parse_duration(&grace).context("invalid --orphan-volume-grace")?;
+        inputs.orphan_volume_grace = grace;
+    }
+    if opts.enable_publishing {
+        inputs.publishing = true;
+    }
+    if opts.disable_publishing {
+        inputs.publishing = false;
+    }
+    if opts.security_headers {
+        inputs.security_headers = true;
+    }
+    if opts.no_security_headers {
+        inputs.security_headers = false;
+        inputs.csp = None;
+    }
+    if let Some(csp) = opts.csp {
+        inputs.csp = Some(csp).filter(|csp| !csp.trim().is_empty());
+        inputs.security_headers |= inputs.csp.is_some();
+    }
+    if let Some(rate) = opts.rate_limit {
+        inputs.rate_limit = Some(rate).filter(|rate| *rate > 0);
+    }
+    if opts.rate_limit_burst.is_some() {
+        inputs.rate_limit_burst = opts.rate_limit_burst;
+    }
+    if opts.concurrent_spawn_limit.is_some() {
+        inputs.concurrent_spawn_limit = opts.concurrent_spawn_limit;
+    }
+    if opts.active_server_limit.is_some() {
+        inputs.active_server_limit = opts.active_server_limit;
+    }
+    if opts.internal_tls {
+        inputs.internal_tls = true;
+    }
+    if opts.no_internal_tls {
+        inputs.internal_tls = false;
+    }
+    if let Some(socket) = opts.docker_socket {
+        inputs.docker_socket = socket;
+    }
+    if opts.bundled_user_env {
+        inputs.user_env_repo = None;
+        inputs.user_env_ref = None;
+    }
+    if let Some(url) = &opts.user_env_repo {
+        inputs.user_env_repo = Some(validate_user_env_repo(url)?);
+        inputs.user_env_ref = None;
+    }
+    if opts.user_env_ref.is_some() {
+        if inputs.user_env_repo.is_none() {
+            return Err(failure::validation("--user-env-ref needs a user environment repository; pass --user-env-repo"));
+        }
+        inputs.user_env_ref = opts.user_env_ref;
+    }
+    if opts.local_docker {
+        inputs.docker_remote = Remote::default();
+    }
+    if opts.docker_context.is_some() || opts.docker_host.is_some() {
+        inputs.docker_remote = remote_from_flags(opts.docker_context, opts.docker_host, opts.docker_cert_path.as_deref())?;
+    }
+    if let Some(labels) = opts.selinux_labels {
+        inputs.selinux = selinux::resolve(labels);
+    }
+    if opts.sandbox_profiles || opts.apparmor {
+        inputs.sandbox_profiles = true;
+    }
+    if opts.apparmor {
+        inputs.apparmor = true;
+    }
+    if opts.no_sandbox_profiles {
+        inputs.sandbox_profiles = false;
+        inputs.apparmor = false;
+    }
+    if opts.no_apparmor {
+        inputs.apparmor = false;
+    }
+    if opts.default_notebook_ids {
+        inputs.notebook_uid = None;
+        inputs.notebook_gid = None;
+    }
+    inputs.notebook_uid = opts.notebook_uid.or(inputs.notebook_uid);
+    inputs.notebook_gid = opts.notebook_gid.or(inputs.notebook_gid);
+    validate_notebook_uid(inputs.notebook_uid)?;
+    if opts.workspace_backend.is_some() || opts.workspace_root.is_some() {
+        inputs.workspace_backend = opts.workspace_backend.unwrap_or(inputs.workspace_backend);
+        inputs.workspace_root = opts.workspace_root.or(inputs.workspace_root);
+        inputs.workspace_host_path = resolve_workspace_host(inputs.workspace_backend, inputs.workspace_root.as_deref())?;
+    }
+    if let Some(root) = opts.scratch_root {
+        inputs.scratch_root = if root.is_empty() {
+            None
+        } else {
+            Some(resolve_scratch_root(&root)?)
+        };
+    }
+    if let Some(quota) = opts.workspace_quota {
+        inputs.workspace_quota = Some(quota).filter(|quota| !quota.is_empty());
+    }
+    if let Some(path) = &opts.answers {
+        apply_answers(&mut inputs, path)?;
+    }
+    if let Some(logo) = opts.logo {
+        inputs.brand_logo = None;
+        if !logo.as_os_str().is_empty() {
+            inputs.brand_logo = Some(branding::logo_name(&logo)?);
+            inputs.branding_sources.logo = Some(logo);
+        }
+    }
+    if let Some(title) = opts.hub_title {
+        branding::validate_text("--hub-title", &title)?;
+        inputs.hub_title = Some(title).filter(|title| !title.is_empty());
+    }
+    if let Some(color) = opts.brand_color {
+        if !color.is_empty() {
+            branding::validate_color(&color)?;
+        }
+        inputs.brand_color = Some(color).filter(|color| !color.is_empty());
+    }
+    if let Some(text) = opts.welcome_text {
+        branding::validate_text("--welcome-text", &text)?;
+        inputs.welcome_text = Some(text).filter(|text| !text.is_empty());
+    }
+    if opts.landing.is_some() {
+        inputs.landing = opts.landing;
+    }
+    if opts.no_landing {
+        inputs.landing = None;
+    }
+    if let Some(site) = opts.landing_site {
+        inputs.landing_site = if site.as_os_str().is_empty() {
+            None
+        } else {
+            Some(resolve_landing_site(&site)?)
+        };
+    }
+    if let Some(email) = opts.support_email {
+        branding::validate_text("--support-email", &email)?;
+        inputs.support_email = Some(email).filter(|email| !email.is_empty());
+    }
+    if let Some(citation) = opts.data_citation {
+        branding::validate_text("--data-citation", &citation)?;
+        inputs.data_citation = Some(citation).filter(|citation| !citation.is_empty());
+    }
+    if opts.enable_dask_gateway {
+        inputs.dask_gateway = true;
+    }
+    if opts.disable_dask_gateway {
+        inputs.dask_gateway = false;
+    }
+    if opts.enable_mlflow {
+        inputs.mlflow = true;
+    }
+    if opts.disable_mlflow {
+        inputs.mlflow = false;
+    }
+    if let Some(artifacts) = opts.mlflow_artifacts {
+        inputs.mlflow_artifacts = Some(artifacts).filter(|path| !path.trim().is_empty());
+    }
+    if opts.enable_nbgrader {
+        inputs.nbgrader = true;
+    }
+    if opts.disable_nbgrader {
+        inputs.nbgrader = false;
+    }
+    if let Some(course) = opts.nbgrader_course {
+        nbgrader::validate_name("--nbgrader-course", &course)?;
+        inputs.nbgrader_course = course;
+    }
+    if let Some(group) = opts.nbgrader_instructors {
+        nbgrader::validate_name("--nbgrader-instructors", &group)?;
+        inputs.nbgrader_instructors = group;
+    }
+    if opts.enable_file_browser {
+        inputs.file_browser = true;
+    }
+    if opts.disable_file_browser {
+        inputs.file_browser = false;
+    }
+    if opts.enable_logging_stack {
+        inputs.logging_stack = true;
+    }
+    if opts.disable_logging_stack {
+        inputs.logging_stack = false;
+    }
+    if let Some(retention) = opts.log_retention {
+        logstack::retention_hours(&retention)?;
+        inputs.log_retention = retention;
+    }
+    inputs.log_driver = opts.log_driver.unwrap_or(inputs.log_driver);
+    if let Some(size) = opts.log_max_size {
+        logconfig::validate_max_size(&size)?;
+        inputs.log_max_size = size;
+    }
+    if let Some(files) = opts.log_max_file {
+        validate_log_max_file(files)?;
+        inputs.log_max_file = files;
+    }
+    if let Some(terms) = opts.terms_file {
+        inputs.terms_of_use = !terms.as_os_str().is_empty();
+        inputs.branding_sources.terms = Some(terms).filter(|_| inputs.terms_of_use);
+    }
+    inputs.http_port = opts.http_port.unwrap_or(inputs.http_port);
+    inputs.https_port = opts.https_port.unwrap_or(inputs.https_port);
+    inputs.proxy_version = opts.proxy_version.unwrap_or(inputs.proxy_version);
+    Ok(inputs)
+}
+
+/// Rewrites the login allow-list of an existing deployment through `update`.
+/// Returns the resulting list and whether any file changed.
+pub fn update_allowed_users(deploy_dir: &Path, update: impl FnOnce(&mut Vec<String>)) -> Result<(Vec<String>, bool)> {
+    let env = envfile::load(deploy_dir)?;
+    let mut inputs = DeployInputs::from_deployment(deploy_dir, &env)?;
+    let mut names = users::split_list(inputs.allowed_users.as_deref().unwrap_or(""));
+    update(&mut names);
+    inputs.allowed_users = Some(names.join(",")).filter(|list| !list.is_empty());
+    let changed = apply_inputs(deploy_dir, &inputs, "pre-users", None, true)?.is_some();
+    Ok((names, changed))
+}
+
+/// OAuth client issued by an identity provider, with its endpoints.
+#[derive(Debug, Clone)]
+pub struct OauthClient {
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorize_url: String,
+    pub token_url: String,
+    pub userdata_url: String,
+}
+
+/// Makes `client` the primary login of the deployment. Returns whether any
+/// file changed.
+pub fn set_oauth_client(deploy_dir: &Path, client: OauthClient) -> Result<bool> {
+    let env = envfile::load(deploy_dir)?;
+    let mut inputs = DeployInputs::from_deployment(deploy_dir, &env)?;
+    inputs.auth = AuthPreset::Helmholtz;
+    inputs.client_id = client.client_id;
+    inputs.client_secret = client.client_secret;
+    inputs.oauth_authorize_url = Some(client.authorize_url);
+    inputs.oauth_token_url = Some(client.token_url);
+    inputs.oauth_userdata_url = Some(client.userdata_url);
+    Ok(apply_inputs(deploy_dir, &inputs, "pre-oauth", None, true)?.is_some())
+}
+
+/// Whether `env` holds every setting `reconfigure` and `upgrade` need to
+/// render the deployment again.
+pub fn check_inputs(env: &EnvMap) -> Result<()> {
+    DeployInputs::from_env(env).map(|_| ())
+}
+
+/// Re-renders the files of an existing deployment from its `.env`, merging
+/// in hand edits, so it picks up the templates of this release. The caller
+/// takes the snapshot.
+pub fn regenerate(deploy_dir: &Path, on_conflict: Option<OnConflict>) -> Result<Vec<PathBuf>> {
+    let inputs = DeployInputs::from_deployment(deploy_dir, &envfile::load(deploy_dir)?)?;
+    let mut updates = merge::plan_files(deploy_dir, generated_files(deploy_dir, &inputs));
+    merge::resolve(deploy_dir, &mut updates, on_conflict, true)?;
+    merge::write(deploy_dir, &updates)?;
+    util::set_file_mode(&deploy_dir.join(envfile::ENV_FILE), 0o600).ok();
+
+    let changed: Vec<PathBuf> = updates
+        .iter()
+        .filter(|update| update.changes_file())
+        .map(|update| update.path.clone())
+        .collect();
+    for path in &changed {
+        say!("  updated {}", style(path.strip_prefix(deploy_dir).unwrap_or(path).display()).cyan());
+    }
+    Ok(changed)
+}
+
+/// Writes the rendered files whose contents differ from `inputs`, after a
+/// snapshot, merging in hand edits. Returns the snapshot id and updated
+/// paths, or `None` when nothing changed.
+fn apply_inputs(
+    deploy_dir: &Path,
+    inputs: &DeployInputs,
+    reason: &str,
+    on_conflict: Option<OnConflict>,
+    interactive: bool,
+) -> Result<Option<(String, Vec<PathBuf>)>> {
+    let dataset_host = resolve_host_path(deploy_dir, &inputs.dataset_path);
+    // Host paths of a remote daemon cannot be checked from here.
+    validate_dataset_path(&dataset_host, inputs.allow_missing_dataset || inputs.docker_remote.is_set(), deploy_dir)?;
+    let shared_host = inputs
+        .shared_path
+        .as_ref()
+        .map(|value| resolve_host_path(deploy_dir, value));
+    check_mount_access(inputs, &dataset_host, shared_host.as_deref());
+
+    let mut updates = merge::plan_files(deploy_dir, generated_files(deploy_dir, inputs));
+    merge::resolve(deploy_dir, &mut updates, on_conflict, interactive)?;
+    let changed: Vec<PathBuf> = updates
+        .iter()
+        .filter(|update| update.changes_file())
+        .map(|update| update.path.clone())
+        .collect();
+    if changed.is_empty() && inputs.branding_sources.is_empty() {
+        // Still records the renderings, which deployments made before they
+        // were kept lack.
+        merge::write(deploy_dir, &updates)?;
+        return Ok(None);
+    }
+
+    let snapshot = snapshot::create(deploy_dir, reason, false)?;
+    if inputs.internal_tls && tls::ensure_internal_certs(deploy_dir)? {
+        say!("Generated internal CA in {}", style(tls::INTERNAL_TLS_DIR).cyan());
+    }
+    say!("Previous configuration saved as snapshot {}", style(&snapshot.id).dim());
+    if !inputs.branding_sources.is_empty() {
+        branding::install(deploy_dir, &inputs.branding_sources)?;
+        say!("  updated {}", style(branding::BRANDING_DIR).cyan());
+    }
+
+    merge::write(deploy_dir, &updates)?;
+    for path in &changed {
+        say!("  updated {}", style(path.strip_prefix(deploy_dir).unwrap_or(path).display()).cyan());
+    }
+    util::set_file_mode(&deploy_dir.join(envfile::ENV_FILE), 0o600).ok();
+    if inputs.publishing {
+        util::ensure_dir(&deploy_dir.join("published"))?;
+    }
+    if inputs.mlflow {
+        ensure_mlflow_dirs(deploy_dir, inputs)?;
+    }
+    if inputs.nbgrader {
+        nbgrader::ensure_exchange(deploy_dir)?;
+    }
+    ensure_group_dirs(deploy_dir, inputs)?;
+    match inputs.tls_mode {
+        TlsMode::Acme => ensure_acme_storage(deploy_dir, inputs.acme_staging)?,
+        TlsMode::SelfSigned => ensure_local_certs(deploy_dir, &inputs.domain)?,
+        TlsMode::Off => {}
+    }
+    Ok(Some((snapshot.id, changed)))
+}
 
-    let deploy_path = PathBuf::from(deploy_dir);
+/// Traefik refuses a certificate store other users can read.
+fn ensure_acme_storage(deploy_dir: &Path, staging: bool) -> Result<()> {
+    let certs = deploy_dir.join("traefik").join(tls::acme_storage(staging));
+    if !certs.exists() {
+        util::write_string(&certs, "{}")?;
+    }
+    util::set_file_mode(&certs, 0o600).ok();
+    Ok(())
+}
 
+/// Generates the local CA and server certificate and tells how to trust them.
+fn ensure_local_certs(deploy_dir: &Path, domain: &str) -> Result<()> {
+    if tls::ensure_local_certs(deploy_dir, domain)? {
+        say!("Generated a self-signed certificate for {} in {}", domain, style(tls::LOCAL_TLS_DIR).cyan());
+        say!("Browsers warn until the local CA is trusted:");
+        for line in tls::trust_instructions(deploy_dir) {
+            say!("  {}", style(line).dim());
+        }
+    }
+    Ok(())
+}
+
+fn reconfigure_value(flag: Option<String>, current: String, prompt: &str, interactive: bool) -> Result<String> {
+    match flag {
+        Some(value) => Ok(value),
+        None if interactive => util::prompt_or_use(Some(current), prompt, false),
+        None => Ok(current),
+    }
+}
+
+fn reconfigure_optional(
+    flag: Option<String>,
+    current: Option<String>,
+    prompt: &str,
+    interactive: bool,
+) -> Result<Option<String>> {
+    let value = match flag {
+        Some(value) => value,
+        None if interactive => util::prompt_or_use(current, prompt, true)?,
+        None => return Ok(current),
+    };
+    Ok(Some(value).filter(|value| !value.trim().is_empty()))
+}
+
+/// Returns the deployment directory and, when `--force` replaces an existing
+/// deployment, its previous `.env` for use as prompt defaults. Nothing is
+/// removed until the inputs have been confirmed.
+pub fn resolve_deploy_dir(
+    opts: &DeployOptions,
+    target: Option<PathBuf>,
+    default: Option<PathBuf>,
+) -> Result<(PathBuf, Option<EnvMap>)> {
+    let deploy_path = match target {
+        Some(path) => path,
+        None => {
+            let default_dir = default.unwrap_or_else(|| PathBuf::from("./mvre-hub"));
+            PathBuf::from(util::prompt_or_use(
+                Some(util::path_display(&default_dir)),
+                "Enter deployment directory",
+                false,
+            )?)
+        }
+    };
+
+    let mut previous = None;
     if deploy_path.exists() {
-        if !force {
-            anyhow::bail!("Deployment exists. Use --force to overwrite.");
+        if !opts.force {
+            return Err(failure::validation("Deployment exists. Use --force to overwrite."));
+        }
+        if deploy_path.join(envfile::ENV_FILE).exists() {
+            previous = Some(envfile::load(&deploy_path)?);
+        }
+    }
+
+    Ok((deploy_path, previous))
+}
+
+/// The `key` secret of the replaced deployment, so services and volumes
+/// that already use it keep working; a new `bytes`-byte token otherwise.
+pub fn kept_or_generated(previous: Option<&EnvMap>, key: &str, bytes: usize) -> Result<String> {
+    match previous.and_then(|env| envfile::get(env, key)) {
+        Some(value) => Ok(value.to_string()),
+        None => util::random_token(bytes),
+    }
+}
+
+fn replace_existing(deploy_path: &Path, was_deployed: bool, with_db: bool) -> Result<()> {
+    if was_deployed {
+        let snapshot = snapshot::create(deploy_path, "pre-deploy", with_db)?;
+        say!("Previous deployment saved as snapshot {}", style(&snapshot.id).dim());
+    }
+    snapshot::clear_all_but_snapshots(deploy_path)
+}
+
+/// Shows the collected inputs and the files about to be written, looping
+/// until the operator confirms (`true`) or aborts (`false`).
+fn review(deploy_path: &Path, inputs: &mut DeployInputs) -> Result<bool> {
+    let theme = ColorfulTheme::default();
+    loop {
+        say!("\n{}", style("Review").cyan().bold());
+        for (label, value) in summary(deploy_path, inputs) {
+            say!("  {:<22} {}", label, value);
+        }
+        say!("\n  {}", style("Files to be written:").bold());
+        for path in planned_files(deploy_path, inputs) {
+            say!("    {}", style(path.strip_prefix(deploy_path).unwrap_or(&path).display()).dim());
+        }
+        if deploy_path.exists() {
+            say!(
+                "\n  {}",
+                style("Existing contents will be replaced (a snapshot is taken first)").yellow()
+            );
+            if deploy_path.join(compose::OVERRIDE_FILE).exists() {
+                say!("  {}", style(format!("{} is kept as it is", compose::OVERRIDE_FILE)).dim());
+            }
+        }
+
+        util::ensure_interactive("Proceed?", "pass --yes")?;
+        let choice = Select::with_theme(&theme)
+            .with_prompt("Proceed?")
+            .items(&["Write files", "Edit a value", "Abort"])
+            .default(0)
+            .interact()?;
+        match choice {
+            0 => return Ok(true),
+            1 => {
+                let labels: Vec<&str> = ReviewField::ALL.iter().map(|field| field.label()).collect();
+                let index = Select::with_theme(&theme)
+                    .with_prompt("Which value?")
+                    .items(&labels)
+                    .default(0)
+                    .interact()?;
+                ReviewField::ALL[index].edit(inputs)?;
+            }
+            _ => return Ok(false),
         }
-        fs::remove_dir_all(&deploy_path).with_context(|| format!("failed to remove {}", deploy_path.display()))?;
     }
+}
 
-    Ok(deploy_path)
+#[derive(Debug, Clone, Copy)]
+enum ReviewField {
+    Domain,
+    AcmeEmail,
+    ClientId,
+    ClientSecret,
+    DatasetPath,
+    SharedPath,
+    AdminUsers,
+    AllowedUsers,
+    RequiredEntitlements,
+    OauthAuthorizeUrl,
+    OauthTokenUrl,
+    OauthUserdataUrl,
 }
 
-fn collect_inputs(opts: &DeployOptions, default_domain: Option<String>) -> Result<DeployInputs> {
+impl ReviewField {
+    const ALL: [ReviewField; 12] = [
+        ReviewField::Domain,
+        ReviewField::AcmeEmail,
+        ReviewField::ClientId,
+        ReviewField::ClientSecret,
+        ReviewField::DatasetPath,
+        ReviewField::SharedPath,
+        ReviewField::AdminUsers,
+        ReviewField::AllowedUsers,
+        ReviewField::RequiredEntitlements,
+        ReviewField::OauthAuthorizeUrl,
+        ReviewField::OauthTokenUrl,
+        ReviewField::OauthUserdataUrl,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ReviewField::Domain => "Domain",
+            ReviewField::AcmeEmail => "ACME email",
+            ReviewField::ClientId => "OAuth client ID",
+            ReviewField::ClientSecret => "OAuth client secret",
+            ReviewField::DatasetPath => "Dataset path",
+            ReviewField::SharedPath => "Shared path",
+            ReviewField::AdminUsers => "Admin users",
+            ReviewField::AllowedUsers => "Allowed users",
+            ReviewField::RequiredEntitlements => "Required entitlements",
+            ReviewField::OauthAuthorizeUrl => "OAuth authorize URL",
+            ReviewField::OauthTokenUrl => "OAuth token URL",
+            ReviewField::OauthUserdataUrl => "OAuth userinfo URL",
+        }
+    }
+
+    fn edit(self, inputs: &mut DeployInputs) -> Result<()> {
+        let optional = |value: String| Some(value).filter(|value| !value.trim().is_empty());
+        match self {
+            ReviewField::Domain => inputs.domain = util::prompt_or_use(Some(inputs.domain.clone()), self.label(), false)?,
+            ReviewField::AcmeEmail => {
+                inputs.acme_email = util::prompt_or_use(Some(inputs.acme_email.clone()), self.label(), false)?
+            }
+            ReviewField::ClientId => {
+                inputs.client_id = util::prompt_or_use(Some(inputs.client_id.clone()), self.label(), false)?
+            }
+            ReviewField::ClientSecret => {
+                inputs.client_secret = keep_or_replace_secret(self.label(), inputs.client_secret.clone())?
+            }
+            ReviewField::DatasetPath => {
+                inputs.dataset_path = util::prompt_or_use(Some(inputs.dataset_path.clone()), self.label(), false)?
+            }
+            ReviewField::SharedPath => {
+                inputs.shared_path = optional(util::prompt_or_use(inputs.shared_path.clone(), self.label(), true)?)
+            }
+            ReviewField::AdminUsers => {
+                inputs.admin_users = optional(util::prompt_or_use(inputs.admin_users.clone(), self.label(), true)?)
+            }
+            ReviewField::AllowedUsers => {
+                inputs.allowed_users = optional(util::prompt_or_use(
+                    inputs.allowed_users.clone(),
+                    "Allowed users (comma-separated, empty for any account)",
+                    true,
+                )?)
+            }
+            ReviewField::RequiredEntitlements => {
+                inputs.required_entitlements = optional(util::prompt_or_use(
+                    inputs.required_entitlements.clone(),
+                    "Required entitlements (comma-separated, empty for none)",
+                    true,
+                )?)
+            }
+            ReviewField::OauthAuthorizeUrl => {
+                inputs.oauth_authorize_url =
+                    Some(util::prompt_or_use(inputs.oauth_authorize_url.clone(), self.label(), false)?)
+            }
+            ReviewField::OauthTokenUrl => {
+                inputs.oauth_token_url = Some(util::prompt_or_use(inputs.oauth_token_url.clone(), self.label(), false)?)
+            }
+            ReviewField::OauthUserdataUrl => {
+                inputs.oauth_userdata_url =
+                    Some(util::prompt_or_use(inputs.oauth_userdata_url.clone(), self.label(), false)?)
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The settings the deploy review lists for the deployment in `deploy_dir`,
+/// with secrets masked.
+pub fn review_summary(deploy_dir: &Path, env: &EnvMap) -> Result<Vec<(String, String)>> {
+    let inputs = DeployInputs::from_deployment(deploy_dir, env)?;
+    Ok(summary(deploy_dir, &inputs))
+}
+
+fn summary(deploy_path: &Path, inputs: &DeployInputs) -> Vec<(String, String)> {
+    let mut rows = vec![
+        ("Deployment directory".to_string(), util::path_display(deploy_path)),
+        ("Identity provider".to_string(), inputs.auth.label().to_string()),
+    ];
+    rows.extend(review_rows(inputs).into_iter().map(|(field, value)| (field.label().to_string(), value)));
+    rows.extend(inputs.extra_idps.iter().map(|extra| {
+        (
+            format!("{} client ID", extra.preset.label()),
+            format!("{} (secret {})", extra.client_id, mask_secret(&extra.client_secret)),
+        )
+    }));
+    rows
+}
+
+fn review_rows(inputs: &DeployInputs) -> Vec<(ReviewField, String)> {
+    let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "(none)".to_string());
+    let endpoint = |value: &Option<String>| {
+        if inputs.auth.has_endpoints() {
+            format!("(from {} preset)", inputs.auth.name())
+        } else {
+            or_none(value)
+        }
+    };
+    vec![
+        (ReviewField::Domain, inputs.domain.clone()),
+        (ReviewField::AcmeEmail, inputs.acme_email.clone()),
+        (ReviewField::ClientId, inputs.client_id.clone()),
+        (ReviewField::ClientSecret, mask_secret(&inputs.client_secret)),
+        (ReviewField::DatasetPath, inputs.dataset_path.clone()),
+        (ReviewField::SharedPath, or_none(&inputs.shared_path)),
+        (ReviewField::AdminUsers, or_none(&inputs.admin_users)),
+        (
+            ReviewField::AllowedUsers,
+            inputs.allowed_users.clone().unwrap_or_else(|| "(any account)".to_string()),
+        ),
+        (
+            ReviewField::RequiredEntitlements,
+            match &inputs.required_entitlements {
+                Some(entitlements) => format!("{} (claim {})", entitlements, inputs.entitlement_claim),
+                None => "(none)".to_string(),
+            },
+        ),
+        (ReviewField::OauthAuthorizeUrl, endpoint(&inputs.oauth_authorize_url)),
+        (ReviewField::OauthTokenUrl, endpoint(&inputs.oauth_token_url)),
+        (ReviewField::OauthUserdataUrl, endpoint(&inputs.oauth_userdata_url)),
+    ]
+}
+
+fn mask_secret(value: &str) -> String {
+    if value.is_empty() {
+        "(empty)".to_string()
+    } else {
+        "********".to_string()
+    }
+}
+
+fn collect_inputs(
+    opts: &DeployOptions,
+    default_domain: Option<String>,
+    previous: Option<&EnvMap>,
+) -> Result<DeployInputs> {
+    let previous_value = |key: &str| previous.and_then(|env| envfile::get(env, key)).map(str::to_string);
+
     let domain = match &opts.domain {
         Some(value) => value.clone(),
-        None => util::prompt_or_use(default_domain, "Domain name (e.g., hub.example.org)", false)?,
+        None => util::prompt_or_use(
+            previous_value("HUB_DOMAIN").or(default_domain),
+            "Domain name (e.g., hub.example.org)",
+            false,
+        )?,
     };
 
     let acme_email = match &opts.acme_email {
         Some(value) => value.clone(),
-        None => util::prompt_or_use(None, "ACME email (for TLS)", false)?,
+        // Nothing to register with when the certificate is made locally.
+        None if opts.tls != TlsMode::Acme => previous_value("ACME_EMAIL").unwrap_or_default(),
+        None => util::prompt_or_use(previous_value("ACME_EMAIL"), "ACME email (for TLS)", false)?,
     };
 
+    let auth = opts
+        .auth
+        .or_else(|| previous_value("OAUTH_PRESET").and_then(|name| AuthPreset::from_name(&name)))
+        .unwrap_or(AuthPreset::Helmholtz);
+
     let client_id = match &opts.client_id {
         Some(value) => value.clone(),
-        None => util::prompt_or_use(None, "Helmholtz AAI Client ID", false)?,
+        None if opts.dummy_auth => String::new(),
+        None => util::prompt_or_use(
+            previous_value("OAUTH_CLIENT_ID"),
+            &format!("{} Client ID", auth.label()),
+            false,
+        )?,
     };
 
-    let client_secret = match &opts.client_secret {
-        Some(value) => value.clone(),
-        None => util::prompt_or_use(None, "Helmholtz AAI Client Secret", false)?,
+    let secret_prompt = format!("{} Client Secret", auth.label());
+    let client_secret = match (&opts.client_secret, previous_value("OAUTH_CLIENT_SECRET")) {
+        (Some(value), _) => value.clone(),
+        (None, _) if opts.dummy_auth => String::new(),
+        (None, Some(current)) => keep_or_replace_secret(&secret_prompt, current)?,
+        (None, None) => util::prompt_or_use(None, &secret_prompt, false)?,
     };
 
     let dataset_path = match &opts.dataset_path {
         Some(value) => value.clone(),
-        None => util::prompt_or_use(None, "MoSAiC dataset host path", false)?,
+        None => util::prompt_or_use(previous_value("DATASET_HOST_PATH"), "MoSAiC dataset host path", false)?,
     };
 
     let mut shared_path = {
-        let prompt = "Shared notebooks host path (optional)";
-        let value = util::prompt_or_use(None, prompt, true)?;
+        let value = match &opts.shared_path {
+            Some(value) => value.clone(),
+            None => util::prompt_or_use(
+                previous_value("SHARED_HOST_PATH"),
+                "Shared notebooks host path (optional)",
+                true,
+            )?,
+        };
         if value.trim().is_empty() {
             None
         } else {
@@ -144,7 +1151,14 @@ fn collect_inputs(opts: &DeployOptions, default_domain: Option<String>) -> Resul
     }
 
     let admin_users = {
-        let value = util::prompt_or_use(None, "Admin users (comma-separated, optional)", true)?;
+        let value = match &opts.admin_users {
+            Some(value) => value.clone(),
+            None => util::prompt_or_use(
+                previous_value("ADMIN_USERS"),
+                "Admin users (comma-separated, optional)",
+                true,
+            )?,
+        };
         if value.trim().is_empty() {
             None
         } else {
@@ -152,9 +1166,37 @@ fn collect_inputs(opts: &DeployOptions, default_domain: Option<String>) -> Resul
         }
     };
 
-    let oauth_authorize_url = Some(util::prompt_or_use(None, "OAuth authorize URL", false)?);
-    let oauth_token_url = Some(util::prompt_or_use(None, "OAuth token URL", false)?);
-    let oauth_userdata_url = Some(util::prompt_or_use(None, "OAuth userinfo URL", false)?);
+    let allowed_users = match &opts.allowed_users {
+        Some(path) => Some(users::read_list_file(path)?.join(",")),
+        None => previous_value("ALLOWED_USERS"),
+    };
+
+    let requested_idps: Vec<IdpPreset> = if opts.extra_idp.is_empty() {
+        previous
+            .map(|env| idp::from_env(env).into_iter().map(|idp| idp.preset).collect())
+            .unwrap_or_default()
+    } else {
+        opts.extra_idp.clone()
+    };
+    let requested_idps: Vec<IdpPreset> = requested_idps
+        .into_iter()
+        .filter(|preset| preset.name() != auth.name())
+        .collect();
+    let extra_idps = idp::collect(&requested_idps, previous)?;
+
+    // Presets carry their endpoints in the hub config.
+    let endpoint = |flag: &Option<String>, key: &str, prompt: &str| -> Result<Option<String>> {
+        if auth.has_endpoints() || opts.dummy_auth {
+            return Ok(None);
+        }
+        Ok(Some(match flag {
+            Some(value) => value.clone(),
+            None => util::prompt_or_use(previous_value(key), prompt, false)?,
+        }))
+    };
+    let oauth_authorize_url = endpoint(&opts.oauth_authorize_url, "OAUTH_AUTHORIZE_URL", "OAuth authorize URL")?;
+    let oauth_token_url = endpoint(&opts.oauth_token_url, "OAUTH_TOKEN_URL", "OAuth token URL")?;
+    let oauth_userdata_url = endpoint(&opts.oauth_userdata_url, "OAUTH_USERDATA_URL", "OAuth userinfo URL")?;
 
     let production = opts.production;
     let db_user = if production { "mvre".to_string() } else { "".to_string() };
@@ -162,12 +1204,17 @@ fn collect_inputs(opts: &DeployOptions, default_domain: Option<String>) -> Resul
     let db_host = if production { "postgres".to_string() } else { "".to_string() };
     let db_port = 5432;
 
-    let db_password = if production {
-        Password::with_theme(&ColorfulTheme::default())
-            .with_prompt("Postgres password")
-            .interact()?
-    } else {
-        String::new()
+    // The Postgres volume outlives a forced redeploy, so keep its password by default.
+    let db_password = match (production, &opts.db_password, previous_value("DB_PASSWORD")) {
+        (false, _, _) => String::new(),
+        (true, Some(value), _) => value.clone(),
+        (true, None, Some(current)) => keep_or_replace_secret("Postgres password", current)?,
+        (true, None, None) => {
+            util::ensure_interactive("Postgres password", "pass --db-password")?;
+            Password::with_theme(&ColorfulTheme::default())
+                .with_prompt("Postgres password")
+                .interact()?
+        }
     };
 
     let cpu_limit = if production { Some("2".to_string()) } else { None };
@@ -175,9 +1222,50 @@ fn collect_inputs(opts: &DeployOptions, default_domain: Option<String>) -> Resul
     let cull_timeout = if production { Some(3600) } else { None };
     let cull_every = if production { Some(300) } else { None };
 
-    Ok(DeployInputs {
+    util::parse_duration(&opts.orphan_volume_grace).context("invalid --orphan-volume-grace")?;
+    logstack::retention_hours(&opts.log_retention)?;
+    logconfig::validate_max_size(&opts.log_max_size)?;
+
+    let docker_remote = if opts.docker_context.is_some() || opts.docker_host.is_some() {
+        remote_from_flags(opts.docker_context.clone(), opts.docker_host.clone(), opts.docker_cert_path.as_deref())?
+    } else {
+        previous.map(Remote::from_env).unwrap_or_default()
+    };
+    let docker_socket = match &opts.docker_socket {
+        Some(socket) => socket.clone(),
+        // The local daemon says nothing about a remote one.
+        None if docker_remote.is_set() => rootless::ROOTFUL_SOCKET.to_string(),
+        None => {
+            let mode = DockerMode::detect();
+            if mode.rootless {
+                warn_rootless_ports(&[opts.http_port, opts.https_port]);
+            }
+            mode.socket.to_string_lossy().to_string()
+        }
+    };
+
+    if let Some(color) = &opts.brand_color {
+        branding::validate_color(color)?;
+    }
+    for (flag, value) in [("--hub-title", &opts.hub_title), ("--welcome-text", &opts.welcome_text)] {
+        if let Some(value) = value {
+            branding::validate_text(flag, value)?;
+        }
+    }
+    let brand_logo = opts.logo.as_deref().map(branding::logo_name).transpose()?;
+    for (flag, value) in [("--support-email", &opts.support_email), ("--data-citation", &opts.data_citation)] {
+        if let Some(value) = value {
+            branding::validate_text(flag, value)?;
+        }
+    }
+    let landing_site = opts.landing_site.as_deref().map(resolve_landing_site).transpose()?;
+
+    let mut inputs = DeployInputs {
         domain,
         acme_email,
+        acme_staging: opts.acme_staging,
+        tls_mode: opts.tls,
+        auth,
         client_id,
         client_secret,
         dataset_path,
@@ -185,11 +1273,31 @@ fn collect_inputs(opts: &DeployOptions, default_domain: Option<String>) -> Resul
         shared_path,
         shared_mount: "/home/jovyan/shared".to_string(),
         admin_users,
+        allowed_users,
         user_image: "mvre-user:latest".to_string(),
+        user_env_repo: match &opts.user_env_repo {
+            Some(url) => Some(validate_user_env_repo(url)?),
+            None => previous_value("USER_ENV_REPO"),
+        },
+        user_env_ref: opts
+            .user_env_ref
+            .clone()
+            .or_else(|| opts.user_env_repo.is_none().then(|| previous_value("USER_ENV_REF")).flatten()),
         oauth_authorize_url,
         oauth_token_url,
         oauth_userdata_url,
         oauth_username_key: "preferred_username".to_string(),
+        required_entitlements: opts
+            .required_entitlement
+            .clone()
+            .or_else(|| previous_value("REQUIRED_ENTITLEMENTS"))
+            .filter(|value| !value.trim().is_empty()),
+        entitlement_claim: opts
+            .entitlement_claim
+            .clone()
+            .or_else(|| previous_value("ENTITLEMENT_CLAIM"))
+            .unwrap_or_else(|| DEFAULT_ENTITLEMENT_CLAIM.to_string()),
+        extra_idps,
         install_notebooks: opts.install_notebooks,
         allow_missing_dataset: opts.allow_missing_dataset,
         production,
@@ -202,9 +1310,469 @@ fn collect_inputs(opts: &DeployOptions, default_domain: Option<String>) -> Resul
         mem_limit,
         cull_timeout,
         cull_every,
+        cull_groups: Vec::new(),
+        groups: Vec::new(),
+        limits: answers::ResourceLimits::default(),
+        extra_services: BTreeMap::new(),
+        hub_api_token: kept_or_generated(previous, "HUB_API_TOKEN", 32)?,
+        spawn_min_free_disk_gb: opts.spawn_min_free_disk_gb,
+        spawn_min_free_mem_gb: opts.spawn_min_free_mem_gb,
+        orphan_volume_policy: opts.orphan_volume_policy.as_str().to_string(),
+        orphan_volume_grace: opts.orphan_volume_grace.clone(),
+        publishing: opts.enable_publishing,
+        http_port: opts.http_port,
+        https_port: opts.https_port,
+        proxy_version: opts
+            .proxy_version
+            .or_else(|| previous_value("PROXY_VERSION").and_then(|name| ProxyVersion::from_name(&name)))
+            .unwrap_or(ProxyVersion::V2),
+        security_headers: opts.security_headers || opts.csp.is_some(),
+        csp: opts.csp.clone().filter(|csp| !csp.trim().is_empty()),
+        rate_limit: opts.rate_limit.filter(|rate| *rate > 0),
+        rate_limit_burst: opts.rate_limit_burst,
+        concurrent_spawn_limit: opts
+            .concurrent_spawn_limit
+            .or_else(|| previous_value("CONCURRENT_SPAWN_LIMIT").and_then(|limit| limit.parse().ok())),
+        active_server_limit: opts
+            .active_server_limit
+            .or_else(|| previous_value("ACTIVE_SERVER_LIMIT").and_then(|limit| limit.parse().ok())),
+        internal_tls: opts.internal_tls,
+        docker_socket,
+        docker_remote,
+        selinux: selinux::resolve(opts.selinux_labels),
+        sandbox_profiles: !opts.no_sandbox_profiles,
+        apparmor: opts.apparmor,
+        notebook_uid: validate_notebook_uid(opts.notebook_uid)?,
+        notebook_gid: opts.notebook_gid,
+        workspace_backend: opts.workspace_backend,
+        workspace_root: opts.workspace_root.clone(),
+        workspace_host_path: resolve_workspace_host(opts.workspace_backend, opts.workspace_root.as_deref())?,
+        scratch_root: opts.scratch_root.as_deref().map(resolve_scratch_root).transpose()?,
+        workspace_quota: opts.workspace_quota.clone(),
+        hub_title: opts.hub_title.clone().filter(|title| !title.is_empty()),
+        brand_color: opts.brand_color.clone(),
+        brand_logo,
+        welcome_text: opts.welcome_text.clone().filter(|text| !text.is_empty()),
+        terms_of_use: opts.terms_file.is_some(),
+        branding_sources: BrandingSources {
+            logo: opts.logo.clone(),
+            terms: opts.terms_file.clone(),
+        },
+        landing: opts.landing,
+        landing_site,
+        support_email: opts.support_email.clone().filter(|email| !email.is_empty()),
+        data_citation: opts.data_citation.clone().filter(|citation| !citation.is_empty()),
+        dask_gateway: opts.enable_dask_gateway,
+        dask_gateway_token: kept_or_generated(previous, "DASK_GATEWAY_API_TOKEN", 32)?,
+        mlflow: opts.enable_mlflow,
+        mlflow_artifacts: opts.mlflow_artifacts.clone().filter(|path| !path.trim().is_empty()),
+        nbgrader: opts.enable_nbgrader,
+        nbgrader_course: match &opts.nbgrader_course {
+            Some(course) => {
+                nbgrader::validate_name("--nbgrader-course", course)?;
+                course.clone()
+            }
+            None => nbgrader::DEFAULT_COURSE.to_string(),
+        },
+        nbgrader_instructors: match &opts.nbgrader_instructors {
+            Some(group) => {
+                nbgrader::validate_name("--nbgrader-instructors", group)?;
+                group.clone()
+            }
+            None => nbgrader::DEFAULT_INSTRUCTORS.to_string(),
+        },
+        file_browser: opts.enable_file_browser,
+        file_browser_oauth_secret: kept_or_generated(previous, "FILE_BROWSER_OAUTH_SECRET", 32)?,
+        file_browser_cookie_secret: kept_or_generated(previous, "FILE_BROWSER_COOKIE_SECRET", 32)?,
+        logging_stack: opts.enable_logging_stack,
+        log_retention: opts.log_retention.clone(),
+        log_driver: opts.log_driver,
+        log_max_size: opts.log_max_size.clone(),
+        log_max_file: validate_log_max_file(opts.log_max_file)?,
+        grafana_admin_password: kept_or_generated(previous, "GRAFANA_ADMIN_PASSWORD", 24)?,
+        dummy_password: if opts.dummy_auth {
+            Some(kept_or_generated(previous, "DUMMY_PASSWORD", 8)?)
+        } else {
+            None
+        },
+        project_name: match &opts.project_name {
+            Some(name) => Some(validate_project_name(name)?),
+            None => previous_value("COMPOSE_PROJECT_NAME"),
+        },
+        resource_prefix: opts
+            .resource_prefix
+            .clone()
+            .or_else(|| previous_value("RESOURCE_PREFIX"))
+            .unwrap_or_default(),
+    };
+    if !inputs.resource_prefix.is_empty() {
+        inputs.user_image = format!("{}user:latest", inputs.resource_prefix);
+    }
+    validate_tls(&inputs)?;
+    if let Some(path) = &opts.answers {
+        apply_answers(&mut inputs, path)?;
+    }
+    Ok(inputs)
+}
+
+/// Applies the `[culling]`, `[groups]`, `[limits]`, and `[extra_services]`
+/// sections of an answers file; each replaces what was configured before.
+fn apply_answers(inputs: &mut DeployInputs, path: &Path) -> Result<()> {
+    let answers = answers::load(path)?;
+    if let Some(groups) = &answers.groups {
+        inputs.groups = answers::resolve_groups(groups)?;
+    }
+    if let Some(limits) = &answers.limits {
+        inputs.limits = limits.resolve()?;
+    }
+    if let Some(services) = answers.extra_services {
+        answers::validate_extra_services(&services)?;
+        inputs.extra_services = services;
+    }
+    let Some(culling) = answers.culling else {
+        return Ok(());
+    };
+    let policy = culling.resolve()?;
+    if policy.timeout.is_some() {
+        inputs.cull_timeout = policy.timeout;
+    }
+    inputs.cull_every = policy.every.or(inputs.cull_every).or(Some(300));
+    inputs.cull_groups = policy.groups;
+    // The fallback culler sees every user, so it must not undercut a group.
+    if let Some(fallback) = inputs.cull_timeout {
+        if let Some((group, timeout)) = inputs.cull_groups.iter().find(|(_, timeout)| *timeout > fallback) {
+            return Err(failure::validation(format!(
+                "culling group {} has a {}s timeout, but everyone is culled after {}s; raise [culling] timeout",
+                group, timeout, fallback
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Absolute path of an operator-provided landing site.
+fn resolve_landing_site(path: &Path) -> Result<String> {
+    if !path.join("index.html").is_file() {
+        return Err(failure::validation(format!("Landing site {} has no index.html", path.display())));
+    }
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", path.display()))?;
+    Ok(util::compose_host_path(&path.to_string_lossy()))
+}
+
+/// Host directory holding the per-user datasets or subvolumes; `None` for
+/// Docker volumes.
+fn resolve_workspace_host(backend: WorkspaceBackend, root: Option<&str>) -> Result<Option<String>> {
+    if backend == WorkspaceBackend::Volume {
+        return Ok(None);
+    }
+    let root = root.context("--workspace-root is required with a ZFS or Btrfs workspace backend")?;
+    workspace::host_path(backend, root).map(Some)
+}
+
+/// The hub creates the per-user directories inside the scratch root, so it
+/// must be an absolute host path.
+fn resolve_scratch_root(root: &str) -> Result<String> {
+    if !Path::new(root).is_absolute() {
+        return Err(failure::validation(format!("--scratch-root must be an absolute path, got '{}'", root)));
+    }
+    Ok(util::compose_host_path(root.trim_end_matches('/')))
+}
+
+fn validate_tls(inputs: &DeployInputs) -> Result<()> {
+    if inputs.acme_staging && inputs.tls_mode != TlsMode::Acme {
+        return Err(failure::validation("--acme-staging only applies to --tls acme"));
+    }
+    Ok(())
+}
+
+/// The remote daemon given on the command line. The certificate directory
+/// is made absolute, as compose runs from the deployment directory.
+fn remote_from_flags(context: Option<String>, host: Option<String>, cert_path: Option<&Path>) -> Result<Remote> {
+    let cert_path = cert_path
+        .map(|path| std::path::absolute(path).with_context(|| format!("invalid --docker-cert-path {}", path.display())))
+        .transpose()?;
+    Ok(Remote {
+        context,
+        host,
+        cert_path: cert_path.as_deref().map(util::path_display),
     })
 }
 
+/// repo2docker takes URLs and local directories; what it cannot read fails
+/// at build time.
+fn validate_user_env_repo(url: &str) -> Result<String> {
+    let url = url.trim();
+    if url.is_empty() || url.starts_with('-') {
+        return Err(failure::validation(format!("invalid --user-env-repo '{}'", url)));
+    }
+    Ok(url.to_string())
+}
+
+/// Compose accepts lowercase letters, digits, dashes, and underscores,
+/// starting with a letter or digit.
+fn validate_project_name(name: &str) -> Result<String> {
+    let valid = name.starts_with(|ch: char| ch.is_ascii_lowercase() || ch.is_ascii_digit())
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || matches!(ch, '-' | '_'));
+    if !valid {
+        return Err(failure::validation(format!(
+            "--project-name '{}' must be lowercase letters, digits, dashes, or underscores, starting with a letter or digit",
+            name
+        )));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_log_max_file(files: u32) -> Result<u32> {
+    if files == 0 {
+        return Err(failure::validation("--log-max-file must be at least 1"));
+    }
+    Ok(files)
+}
+
+/// Notebook servers must not run as root, which NB_UID=0 would do.
+fn validate_notebook_uid(uid: Option<u32>) -> Result<Option<u32>> {
+    if uid == Some(0) {
+        return Err(failure::validation(
+            "--notebook-uid 0 would run notebooks as root; pick an unprivileged UID",
+        ));
+    }
+    Ok(uid)
+}
+
+/// Rootless daemons cannot publish privileged ports unless the host lowered
+/// the limit; `doctor` explains the remaining caveats.
+fn warn_rootless_ports(ports: &[u16]) {
+    let blocked = rootless::privileged_ports(ports, rootless::unprivileged_port_start());
+    say!("Detected rootless Docker; run {} to check the host", style("mvre-hub doctor").cyan());
+    if !blocked.is_empty() {
+        tracing::warn!(
+            "rootless Docker cannot publish port(s) {:?}; use --http-port/--https-port of 1024 or above",
+            blocked
+        );
+    }
+}
+
+/// Prompts for a secret without echoing it; an empty answer keeps `current`.
+fn keep_or_replace_secret(prompt: &str, current: String) -> Result<String> {
+    util::ensure_interactive(prompt, "pass it as a flag")?;
+    let value = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("{} (leave empty to keep current)", prompt))
+        .allow_empty_password(true)
+        .interact()?;
+    Ok(if value.is_empty() { current } else { value })
+}
+
+impl DeployInputs {
+    fn has_branding(&self) -> bool {
+        self.hub_title.is_some()
+            || self.brand_color.is_some()
+            || self.brand_logo.is_some()
+            || self.welcome_text.is_some()
+            || self.terms_of_use
+    }
+
+    /// Where users reach the hub; `--tls off` serves plain HTTP on the HTTP port.
+    fn public_url(&self) -> String {
+        match (self.tls_mode, self.http_port) {
+            (TlsMode::Off, 80) => format!("http://{}", self.domain),
+            (TlsMode::Off, port) => format!("http://{}:{}", self.domain, port),
+            _ => format!("https://{}", self.domain),
+        }
+    }
+
+    /// Rebuilds the inputs of the deployment at `deploy_dir` from its `.env`
+    /// and the extra services it names.
+    fn from_deployment(deploy_dir: &Path, env: &EnvMap) -> Result<Self> {
+        let mut inputs = Self::from_env(env)?;
+        let names = users::split_list(envfile::get(env, "EXTRA_SERVICES").unwrap_or(""));
+        inputs.extra_services = answers::load_extra_services(deploy_dir, &names)?;
+        Ok(inputs)
+    }
+
+    /// Rebuilds the inputs of an existing deployment from its `.env`.
+    fn from_env(env: &EnvMap) -> Result<Self> {
+        let required = |key: &str| -> Result<String> {
+            envfile::get(env, key)
+                .map(str::to_string)
+                .with_context(|| format!("{} missing from .env", key))
+        };
+        let optional = |key: &str| envfile::get(env, key).map(str::to_string);
+        let flag = |key: &str| envfile::get(env, key) == Some("true");
+
+        let parse_port = |key: &str, default: u16| -> Result<u16> {
+            match envfile::get(env, key) {
+                Some(value) => value.parse().with_context(|| format!("invalid {} in .env", key)),
+                None => Ok(default),
+            }
+        };
+        let parse_u64 = |key: &str| -> Result<Option<u64>> {
+            optional(key)
+                .map(|value| value.parse().with_context(|| format!("invalid {} in .env", key)))
+                .transpose()
+        };
+        let parse_u32 = |key: &str| -> Result<Option<u32>> {
+            optional(key)
+                .map(|value| value.parse().with_context(|| format!("invalid {} in .env", key)))
+                .transpose()
+        };
+        let parse_f64 = |key: &str| -> Result<Option<f64>> {
+            optional(key)
+                .map(|value| value.parse().with_context(|| format!("invalid {} in .env", key)))
+                .transpose()
+        };
+        let tls_mode = optional("TLS_MODE")
+            .and_then(|name| TlsMode::from_name(&name))
+            .unwrap_or(TlsMode::Acme);
+        let dummy_password = optional("DUMMY_PASSWORD").filter(|_| flag("ALLOW_DUMMY_AUTH"));
+
+        Ok(DeployInputs {
+            domain: required("HUB_DOMAIN")?,
+            acme_email: if tls_mode == TlsMode::Acme {
+                required("ACME_EMAIL").context("redeploy once so the ACME email is recorded in .env")?
+            } else {
+                optional("ACME_EMAIL").unwrap_or_default()
+            },
+            acme_staging: flag("ACME_STAGING"),
+            tls_mode,
+            auth: optional("OAUTH_PRESET")
+                .and_then(|name| AuthPreset::from_name(&name))
+                .unwrap_or(AuthPreset::Helmholtz),
+            client_id: if dummy_password.is_some() {
+                optional("OAUTH_CLIENT_ID").unwrap_or_default()
+            } else {
+                required("OAUTH_CLIENT_ID")?
+            },
+            client_secret: if dummy_password.is_some() {
+                optional("OAUTH_CLIENT_SECRET").unwrap_or_default()
+            } else {
+                required("OAUTH_CLIENT_SECRET")?
+            },
+            dataset_path: required("DATASET_HOST_PATH")?,
+            dataset_mount: optional("DATASET_MOUNT_PATH").unwrap_or_else(|| "/data/mosaic".to_string()),
+            shared_path: optional("SHARED_HOST_PATH"),
+            shared_mount: optional("SHARED_MOUNT_PATH").unwrap_or_else(|| "/home/jovyan/shared".to_string()),
+            admin_users: optional("ADMIN_USERS"),
+            allowed_users: optional("ALLOWED_USERS"),
+            user_image: optional("USER_IMAGE").unwrap_or_else(|| "mvre-user:latest".to_string()),
+            user_env_repo: optional("USER_ENV_REPO"),
+            user_env_ref: optional("USER_ENV_REF"),
+            oauth_authorize_url: optional("OAUTH_AUTHORIZE_URL"),
+            oauth_token_url: optional("OAUTH_TOKEN_URL"),
+            oauth_userdata_url: optional("OAUTH_USERDATA_URL"),
+            oauth_username_key: optional("OAUTH_USERNAME_KEY").unwrap_or_else(|| "preferred_username".to_string()),
+            required_entitlements: optional("REQUIRED_ENTITLEMENTS"),
+            entitlement_claim: optional("ENTITLEMENT_CLAIM").unwrap_or_else(|| DEFAULT_ENTITLEMENT_CLAIM.to_string()),
+            extra_idps: idp::from_env(env),
+            install_notebooks: false,
+            allow_missing_dataset: flag("ALLOW_MISSING_DATASET"),
+            production: flag("ENABLE_POSTGRES"),
+            db_user: optional("DB_USER").unwrap_or_default(),
+            db_name: optional("DB_NAME").unwrap_or_default(),
+            db_password: optional("DB_PASSWORD").unwrap_or_default(),
+            db_host: optional("DB_HOST").unwrap_or_default(),
+            db_port: parse_port("DB_PORT", 5432)?,
+            cpu_limit: optional("CPU_LIMIT"),
+            mem_limit: optional("MEM_LIMIT"),
+            cull_timeout: parse_u64("CULL_TIMEOUT")?,
+            cull_every: parse_u64("CULL_EVERY")?,
+            cull_groups: answers::parse_cull_groups(&optional("CULL_GROUPS").unwrap_or_default())?,
+            groups: answers::parse_groups(
+                &optional("GROUP_MEMBERS").unwrap_or_default(),
+                &optional("GROUP_SHARES").unwrap_or_default(),
+            )?,
+            limits: answers::ResourceLimits {
+                groups: answers::parse_limits(&optional("GROUP_LIMITS").unwrap_or_default(), "GROUP_LIMITS")?,
+                users: answers::parse_limits(&optional("USER_LIMITS").unwrap_or_default(), "USER_LIMITS")?,
+            },
+            // Definitions live next to `.env`; see `from_deployment`.
+            extra_services: BTreeMap::new(),
+            hub_api_token: match optional("HUB_API_TOKEN") {
+                Some(token) => token,
+                None => util::random_token(32)?,
+            },
+            spawn_min_free_disk_gb: parse_f64("SPAWN_MIN_FREE_DISK_GB")?,
+            spawn_min_free_mem_gb: parse_f64("SPAWN_MIN_FREE_MEM_GB")?,
+            orphan_volume_policy: optional("ORPHAN_VOLUME_POLICY").unwrap_or_else(|| "never".to_string()),
+            orphan_volume_grace: optional("ORPHAN_VOLUME_GRACE").unwrap_or_else(|| "30d".to_string()),
+            publishing: flag("ENABLE_PUBLISHING"),
+            http_port: parse_port("HTTP_PORT", 8080)?,
+            https_port: parse_port("HTTPS_PORT", 8443)?,
+            proxy_version: optional("PROXY_VERSION")
+                .and_then(|name| ProxyVersion::from_name(&name))
+                .unwrap_or(ProxyVersion::V2),
+            security_headers: flag("SECURITY_HEADERS"),
+            csp: optional("CONTENT_SECURITY_POLICY"),
+            rate_limit: parse_u32("RATE_LIMIT")?,
+            rate_limit_burst: parse_u32("RATE_LIMIT_BURST")?,
+            concurrent_spawn_limit: parse_u32("CONCURRENT_SPAWN_LIMIT")?,
+            active_server_limit: parse_u32("ACTIVE_SERVER_LIMIT")?,
+            internal_tls: flag("INTERNAL_TLS"),
+            docker_socket: optional("DOCKER_SOCKET").unwrap_or_else(|| rootless::ROOTFUL_SOCKET.to_string()),
+            docker_remote: Remote::from_env(env),
+            selinux: flag("SELINUX_LABELS"),
+            sandbox_profiles: flag("SANDBOX_PROFILES"),
+            apparmor: optional("APPARMOR_PROFILE").is_some(),
+            notebook_uid: parse_u32("NOTEBOOK_UID")?,
+            notebook_gid: parse_u32("NOTEBOOK_GID")?,
+            workspace_backend: optional("WORKSPACE_BACKEND")
+                .and_then(|name| WorkspaceBackend::from_name(&name))
+                .unwrap_or(WorkspaceBackend::Volume),
+            workspace_root: optional("WORKSPACE_ROOT"),
+            workspace_host_path: optional("WORKSPACE_HOST_PATH"),
+            scratch_root: optional("SCRATCH_ROOT"),
+            workspace_quota: optional("WORKSPACE_QUOTA"),
+            hub_title: optional("HUB_TITLE"),
+            brand_color: optional("BRAND_COLOR"),
+            brand_logo: optional("BRAND_LOGO"),
+            welcome_text: optional("WELCOME_TEXT"),
+            terms_of_use: flag("TERMS_OF_USE"),
+            branding_sources: BrandingSources::default(),
+            landing: optional("LANDING").and_then(|name| LandingPath::from_name(&name)),
+            landing_site: optional("LANDING_SITE").filter(|site| site != landing::GENERATED_SITE_MOUNT),
+            support_email: optional("SUPPORT_EMAIL"),
+            data_citation: optional("DATA_CITATION"),
+            dask_gateway: flag("ENABLE_DASK_GATEWAY"),
+            dask_gateway_token: match optional("DASK_GATEWAY_API_TOKEN") {
+                Some(token) => token,
+                None => util::random_token(32)?,
+            },
+            mlflow: flag("ENABLE_MLFLOW"),
+            mlflow_artifacts: optional("MLFLOW_ARTIFACTS").filter(|path| path != mlflow::DEFAULT_ARTIFACTS),
+            nbgrader: flag("ENABLE_NBGRADER"),
+            nbgrader_course: optional("NBGRADER_COURSE").unwrap_or_else(|| nbgrader::DEFAULT_COURSE.to_string()),
+            nbgrader_instructors: optional("NBGRADER_INSTRUCTORS")
+                .unwrap_or_else(|| nbgrader::DEFAULT_INSTRUCTORS.to_string()),
+            file_browser: flag("ENABLE_FILE_BROWSER"),
+            file_browser_oauth_secret: match optional("FILE_BROWSER_OAUTH_SECRET") {
+                Some(secret) => secret,
+                None => util::random_token(32)?,
+            },
+            file_browser_cookie_secret: match optional("FILE_BROWSER_COOKIE_SECRET") {
+                Some(secret) => secret,
+                None => util::random_token(32)?,
+            },
+            logging_stack: flag("ENABLE_LOGGING_STACK"),
+            log_retention: optional("LOG_RETENTION").unwrap_or_else(|| logstack::DEFAULT_RETENTION.to_string()),
+            grafana_admin_password: match optional("GRAFANA_ADMIN_PASSWORD") {
+                Some(password) => password,
+                None => util::random_token(24)?,
+            },
+            log_driver: optional("LOG_DRIVER")
+                .and_then(|name| LogDriver::from_name(&name))
+                .unwrap_or(LogDriver::JsonFile),
+            log_max_size: optional("LOG_MAX_SIZE").unwrap_or_else(|| "50m".to_string()),
+            log_max_file: parse_u64("LOG_MAX_FILE")?.map(|files| files as u32).unwrap_or(5),
+            dummy_password,
+            project_name: optional("COMPOSE_PROJECT_NAME"),
+            resource_prefix: optional("RESOURCE_PREFIX").unwrap_or_default(),
+        })
+    }
+}
+
 fn create_dirs(deploy_path: &Path, needs_shared: bool) -> Result<()> {
     util::ensure_dir(deploy_path)?;
     util::ensure_dir(&deploy_path.join("traefik"))?;
@@ -217,17 +1785,15 @@ fn create_dirs(deploy_path: &Path, needs_shared: bool) -> Result<()> {
     Ok(())
 }
 
-fn write_configs(deploy_path: &Path, inputs: &DeployInputs) -> Result<()> {
-    let compose = templates::docker_compose(&inputs.domain, &inputs.acme_email, inputs.production);
-    util::write_string(&deploy_path.join("docker-compose.yml"), &compose)?;
-
+fn write_configs(deploy_path: &Path, inputs: &DeployInputs, updates: &[merge::Update]) -> Result<()> {
     let shared_host = inputs
         .shared_path
         .as_ref()
         .map(|value| resolve_host_path(deploy_path, value));
 
     let dataset_host = resolve_host_path(deploy_path, &inputs.dataset_path);
-    validate_dataset_path(&dataset_host, inputs.allow_missing_dataset, deploy_path)?;
+    // Host paths of a remote daemon cannot be checked from here.
+    validate_dataset_path(&dataset_host, inputs.allow_missing_dataset || inputs.docker_remote.is_set(), deploy_path)?;
 
     if let Some(shared_path) = &shared_host {
         let path = Path::new(shared_path);
@@ -235,22 +1801,174 @@ fn write_configs(deploy_path: &Path, inputs: &DeployInputs) -> Result<()> {
             util::ensure_dir(path)?;
         }
     }
+    check_mount_access(inputs, &dataset_host, shared_host.as_deref());
+    ensure_group_dirs(deploy_path, inputs)?;
+    if let Some(root) = &inputs.scratch_root {
+        util::ensure_dir(Path::new(root))?;
+    }
+
+    merge::write(deploy_path, updates)?;
+    if !inputs.branding_sources.is_empty() {
+        branding::install(deploy_path, &inputs.branding_sources)?;
+    }
+    util::set_file_mode(&deploy_path.join(".env"), 0o600).ok();
+
+    match inputs.tls_mode {
+        TlsMode::Acme => ensure_acme_storage(deploy_path, inputs.acme_staging)?,
+        TlsMode::SelfSigned => ensure_local_certs(deploy_path, &inputs.domain)?,
+        TlsMode::Off => {}
+    }
+    if inputs.internal_tls {
+        tls::ensure_internal_certs(deploy_path)?;
+    }
 
+    if inputs.publishing {
+        util::ensure_dir(&deploy_path.join("published"))?;
+    }
+    if inputs.mlflow {
+        ensure_mlflow_dirs(deploy_path, inputs)?;
+    }
+    if inputs.nbgrader {
+        nbgrader::ensure_exchange(deploy_path)?;
+    }
+
+    if inputs.install_notebooks {
+        let target = shared_host
+            .clone()
+            .unwrap_or_else(|| deploy_path.join("shared").to_string_lossy().to_string());
+        write_mosaic_bundle(Path::new(&target))?;
+    }
+
+    Ok(())
+}
+
+/// Every file rendered into the deployment directory: those depending on
+/// `inputs`, and the static ones, so deployments pick up template changes
+/// (such as new authenticator support) on reconfigure.
+fn generated_files(deploy_path: &Path, inputs: &DeployInputs) -> Vec<(PathBuf, String)> {
+    let dataset_host = resolve_host_path(deploy_path, &inputs.dataset_path);
+    let shared_host = inputs
+        .shared_path
+        .as_ref()
+        .map(|value| resolve_host_path(deploy_path, value));
+    let static_files = static_configs()
+        .into_iter()
+        .map(|(relative, contents)| (deploy_path.join(relative), contents));
+    render_configs(deploy_path, inputs, &dataset_host, shared_host.as_deref())
+        .into_iter()
+        .chain(static_files)
+        .collect()
+}
+
+/// Files that are the same for every deployment.
+fn static_configs() -> Vec<(&'static str, String)> {
+    vec![
+        ("hub/jupyterhub_config.py", templates::jupyterhub_config()),
+        ("hub/Dockerfile", templates::hub_dockerfile()),
+        ("user/Dockerfile", templates::user_dockerfile()),
+    ]
+}
+
+/// Every file a deploy with `inputs` writes, for the review screen.
+fn planned_files(deploy_path: &Path, inputs: &DeployInputs) -> Vec<PathBuf> {
+    let shared_host = inputs
+        .shared_path
+        .as_ref()
+        .map(|value| resolve_host_path(deploy_path, value));
+    let dataset_host = resolve_host_path(deploy_path, &inputs.dataset_path);
+
+    let mut files: Vec<PathBuf> = render_configs(deploy_path, inputs, &dataset_host, shared_host.as_deref())
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+    files.push(deploy_path.join("traefik").join(tls::acme_storage(inputs.acme_staging)));
+    files.extend(static_configs().into_iter().map(|(relative, _)| deploy_path.join(relative)));
+    if inputs.install_notebooks {
+        let target = shared_host.map(PathBuf::from).unwrap_or_else(|| deploy_path.join("shared"));
+        files.push(target.join("README.txt"));
+        files.push(target.join("mosaic_quickstart.ipynb"));
+    }
+    files.push(deploy_path.join(report::REPORT_FILE));
+    files
+}
+
+/// Files whose contents depend on the deployment inputs.
+fn render_configs(
+    deploy_path: &Path,
+    inputs: &DeployInputs,
+    dataset_host: &str,
+    shared_host: Option<&str>,
+) -> Vec<(PathBuf, String)> {
+    let extra_services: Vec<(String, answers::ExtraService)> = inputs.extra_services.clone().into_iter().collect();
+    let compose = templates::docker_compose(&templates::ComposeValues {
+        domain: &inputs.domain,
+        acme_email: &inputs.acme_email,
+        acme_staging: inputs.acme_staging,
+        tls: inputs.tls_mode,
+        production: inputs.production,
+        publishing: inputs.publishing,
+        http_port: inputs.http_port,
+        https_port: inputs.https_port,
+        proxy_version: inputs.proxy_version,
+        security_headers: inputs.security_headers,
+        csp: inputs.csp.as_deref(),
+        rate_limit: inputs
+            .rate_limit
+            .map(|average| (average, inputs.rate_limit_burst.unwrap_or(average * 2))),
+        internal_tls: inputs.internal_tls,
+        selinux: inputs.selinux,
+        sandbox_profiles: inputs.sandbox_profiles,
+        workspaces: inputs.workspace_host_path.is_some(),
+        scratch: inputs.scratch_root.is_some(),
+        branding: inputs.has_branding(),
+        landing: inputs.landing,
+        dask_gateway: inputs.dask_gateway,
+        mlflow: inputs.mlflow,
+        file_browser: inputs.file_browser,
+        logging_stack: inputs.logging_stack,
+        logging: Some(ContainerLogging {
+            driver: inputs.log_driver,
+            max_size: &inputs.log_max_size,
+            max_file: inputs.log_max_file,
+        }),
+        extra_services: &extra_services,
+    });
+
+    let mlflow_artifacts = mlflow_artifacts_host(deploy_path, inputs);
+    // DockerSpawner mounts host paths as they are, relative ones included.
+    let groups: Vec<answers::HubGroup> = inputs
+        .groups
+        .iter()
+        .map(|group| answers::HubGroup {
+            shared: group.shared.as_ref().map(|shared| resolve_host_path(deploy_path, shared)),
+            ..group.clone()
+        })
+        .collect();
     let env = templates::env_file(&templates::EnvValues {
+        auth_preset: inputs.auth.name(),
         client_id: &inputs.client_id,
         client_secret: &inputs.client_secret,
         domain: &inputs.domain,
+        acme_email: &inputs.acme_email,
+        acme_staging: inputs.acme_staging,
+        tls_mode: inputs.tls_mode.name(),
         user_image: &inputs.user_image,
-        dataset_host: &dataset_host,
+        user_env_repo: inputs.user_env_repo.as_deref(),
+        user_env_ref: inputs.user_env_ref.as_deref(),
+        dataset_host,
         dataset_mount: &inputs.dataset_mount,
         allow_missing_dataset: inputs.allow_missing_dataset,
-        shared_host: shared_host.as_deref(),
+        shared_host,
         shared_mount: &inputs.shared_mount,
         admin_users: inputs.admin_users.as_deref(),
+        allowed_users: inputs.allowed_users.as_deref(),
         oauth_authorize_url: inputs.oauth_authorize_url.as_deref(),
         oauth_token_url: inputs.oauth_token_url.as_deref(),
         oauth_userdata_url: inputs.oauth_userdata_url.as_deref(),
         oauth_username_key: &inputs.oauth_username_key,
+        required_entitlements: inputs.required_entitlements.as_deref(),
+        entitlement_claim: &inputs.entitlement_claim,
+        extra_idps: &inputs.extra_idps,
         production: inputs.production,
         db_user: &inputs.db_user,
         db_name: &inputs.db_name,
@@ -259,43 +1977,161 @@ fn write_configs(deploy_path: &Path, inputs: &DeployInputs) -> Result<()> {
         db_port: inputs.db_port,
         cpu_limit: inputs.cpu_limit.as_deref(),
         mem_limit: inputs.mem_limit.as_deref(),
+        user_limits: &answers::format_limits(&inputs.limits.users),
+        group_limits: &answers::format_limits(&inputs.limits.groups),
         cull_timeout: inputs.cull_timeout,
         cull_every: inputs.cull_every,
+        cull_groups: &answers::format_cull_groups(&inputs.cull_groups),
+        group_members: &answers::format_group_members(&inputs.groups),
+        group_shares: &answers::format_group_shares(&groups),
+        extra_services: &inputs.extra_services.keys().cloned().collect::<Vec<_>>().join(","),
+        hub_api_token: &inputs.hub_api_token,
+        spawn_min_free_disk_gb: inputs.spawn_min_free_disk_gb,
+        spawn_min_free_mem_gb: inputs.spawn_min_free_mem_gb,
+        orphan_volume_policy: &inputs.orphan_volume_policy,
+        orphan_volume_grace: &inputs.orphan_volume_grace,
+        publishing: inputs.publishing,
+        http_port: inputs.http_port,
+        https_port: inputs.https_port,
+        proxy_version: inputs.proxy_version.name(),
+        security_headers: inputs.security_headers,
+        csp: inputs.csp.as_deref(),
+        rate_limit: inputs.rate_limit,
+        rate_limit_burst: inputs.rate_limit_burst,
+        concurrent_spawn_limit: inputs.concurrent_spawn_limit,
+        active_server_limit: inputs.active_server_limit,
+        internal_tls: inputs.internal_tls,
+        docker_socket: &inputs.docker_socket,
+        docker_context: inputs.docker_remote.context.as_deref(),
+        docker_host: inputs.docker_remote.host.as_deref(),
+        docker_cert_path: inputs.docker_remote.cert_path.as_deref(),
+        selinux: inputs.selinux,
+        sandbox_profiles: inputs.sandbox_profiles,
+        apparmor: inputs.apparmor,
+        notebook_uid: inputs.notebook_uid,
+        notebook_gid: inputs.notebook_gid,
+        workspace_backend: inputs.workspace_backend.name(),
+        workspace_root: inputs.workspace_root.as_deref(),
+        workspace_host_path: inputs.workspace_host_path.as_deref(),
+        scratch_root: inputs.scratch_root.as_deref(),
+        workspace_quota: inputs.workspace_quota.as_deref(),
+        hub_title: inputs.hub_title.as_deref(),
+        brand_color: inputs.brand_color.as_deref(),
+        brand_logo: inputs.brand_logo.as_deref(),
+        welcome_text: inputs.welcome_text.as_deref(),
+        terms_of_use: inputs.terms_of_use,
+        landing: inputs.landing.map(LandingPath::name),
+        landing_site: inputs.landing_site.as_deref().unwrap_or(landing::GENERATED_SITE_MOUNT),
+        support_email: inputs.support_email.as_deref(),
+        data_citation: inputs.data_citation.as_deref(),
+        dask_gateway: inputs.dask_gateway,
+        dask_gateway_token: &inputs.dask_gateway_token,
+        mlflow: inputs.mlflow,
+        mlflow_artifacts: &mlflow_artifacts,
+        nbgrader: inputs.nbgrader,
+        nbgrader_course: &inputs.nbgrader_course,
+        nbgrader_instructors: &inputs.nbgrader_instructors,
+        nbgrader_host: &resolve_host_path(deploy_path, nbgrader::NBGRADER_DIR),
+        file_browser: inputs.file_browser,
+        file_browser_oauth_secret: &inputs.file_browser_oauth_secret,
+        file_browser_cookie_secret: &inputs.file_browser_cookie_secret,
+        logging_stack: inputs.logging_stack,
+        log_retention: &inputs.log_retention,
+        grafana_admin_password: &inputs.grafana_admin_password,
+        log_driver: inputs.log_driver.name(),
+        log_max_size: &inputs.log_max_size,
+        log_max_file: inputs.log_max_file,
+        dummy_password: inputs.dummy_password.as_deref(),
+        project_name: inputs.project_name.as_deref(),
+        resource_prefix: &inputs.resource_prefix,
     });
-    let env_path = deploy_path.join(".env");
-    util::write_string(&env_path, &env)?;
-    util::set_file_mode(&env_path, 0o600).ok();
 
-    let certs = deploy_path.join("traefik").join("acme.json");
-    if !certs.exists() {
-        util::write_string(&certs, "{}")?;
+    let mut files = vec![
+        (deploy_path.join(compose::COMPOSE_FILE), compose),
+        (deploy_path.join(envfile::ENV_FILE), env),
+    ];
+    files.extend(hardening::fail2ban_files(deploy_path));
+    if !inputs.extra_services.is_empty() {
+        files.push((
+            deploy_path.join(answers::EXTRA_SERVICES_FILE),
+            answers::format_extra_services(&inputs.extra_services),
+        ));
     }
-    util::set_file_mode(&certs, 0o600).ok();
-
-    let hub_dir = deploy_path.join("hub");
-    util::write_string(&hub_dir.join("jupyterhub_config.py"), &templates::jupyterhub_config())?;
-    util::write_string(&hub_dir.join("Dockerfile"), &templates::hub_dockerfile())?;
-
-    let user_dir = deploy_path.join("user");
-    util::write_string(&user_dir.join("Dockerfile"), &templates::user_dockerfile())?;
-    util::write_string(&user_dir.join("requirements.txt"), &templates::user_requirements())?;
+    if inputs.sandbox_profiles {
+        files.extend(hardening::sandbox_files(deploy_path, inputs.apparmor));
+    }
+    if inputs.has_branding() {
+        files.extend(branding::template_files(deploy_path));
+    }
+    let mut requirements = templates::user_requirements(inputs.dask_gateway);
+    if inputs.nbgrader {
+        requirements.push_str(nbgrader::REQUIREMENT);
+        requirements.push('\n');
+        files.extend(nbgrader::files(deploy_path));
+    }
+    files.push((deploy_path.join("user").join("requirements.txt"), requirements));
+    if inputs.dask_gateway {
+        files.extend(dask::files(deploy_path));
+    }
+    if inputs.mlflow {
+        files.extend(mlflow::files(deploy_path));
+    }
+    if inputs.file_browser {
+        files.extend(filebrowser::files(deploy_path));
+    }
+    if inputs.logging_stack {
+        // Validated when the inputs were collected.
+        let hours = logstack::retention_hours(&inputs.log_retention).unwrap_or(720);
+        files.extend(logstack::files(deploy_path, hours));
+    }
+    if inputs.landing.is_some() {
+        let page = LandingPage {
+            title: inputs.hub_title.as_deref().unwrap_or("MVRE Polar Drift Hub"),
+            domain: &inputs.domain,
+            dataset_mount: &inputs.dataset_mount,
+            support_email: inputs.support_email.as_deref(),
+            data_citation: inputs.data_citation.as_deref(),
+        };
+        files.extend(landing::files(deploy_path, inputs.landing_site.is_none().then_some(&page)));
+    }
+    if inputs.publishing {
+        files.push((
+            deploy_path.join("publish").join("nginx.conf"),
+            templates::publish_nginx_config(),
+        ));
+    }
+    if inputs.tls_mode == TlsMode::SelfSigned {
+        files.push((deploy_path.join("traefik").join(tls::LOCAL_TLS_CONFIG), tls::local_tls_config()));
+    }
+    files
+}
 
-    if inputs.install_notebooks {
-        let target = shared_host
-            .clone()
-            .unwrap_or_else(|| deploy_path.join("shared").to_string_lossy().to_string());
-        write_mosaic_bundle(Path::new(&target))?;
+/// `MLFLOW_ARTIFACTS`: the default stays relative, like the other paths
+/// inside the deployment in the compose file.
+fn mlflow_artifacts_host(deploy_path: &Path, inputs: &DeployInputs) -> String {
+    match &inputs.mlflow_artifacts {
+        Some(path) => resolve_host_path(deploy_path, path),
+        None => mlflow::DEFAULT_ARTIFACTS.to_string(),
     }
+}
 
-    Ok(())
+fn ensure_mlflow_dirs(deploy_path: &Path, inputs: &DeployInputs) -> Result<()> {
+    util::ensure_dir(&deploy_path.join(mlflow::MLFLOW_DIR).join("data"))?;
+    let artifacts = mlflow_artifacts_host(deploy_path, inputs);
+    let artifacts = Path::new(&artifacts);
+    if artifacts.is_absolute() {
+        util::ensure_dir(artifacts)
+    } else {
+        util::ensure_dir(&deploy_path.join(artifacts))
+    }
 }
 
 fn resolve_host_path(deploy_path: &Path, value: &str) -> String {
     let path = Path::new(value);
     if path.is_absolute() {
-        return value.to_string();
+        return util::compose_host_path(value);
     }
-    deploy_path.join(value).to_string_lossy().to_string()
+    util::compose_host_path(&deploy_path.join(value).to_string_lossy())
 }
 
 fn validate_dataset_path(value: &str, allow_missing: bool, deploy_path: &Path) -> Result<()> {
@@ -316,7 +2152,63 @@ fn validate_dataset_path(value: &str, allow_missing: bool, deploy_path: &Path) -
             }
             return Ok(());
         }
-        anyhow::bail!("Dataset path does not exist: {}", path.display());
+        return Err(failure::validation(format!("Dataset path does not exist: {}", path.display())));
+    }
+    Ok(())
+}
+
+/// Warns, with commands that fix it, when notebook processes will not be able
+/// to read the dataset or shared folder; existence alone says little.
+fn check_mount_access(inputs: &DeployInputs, dataset_host: &str, shared_host: Option<&str>) {
+    let uid = inputs.notebook_uid.unwrap_or(access::DEFAULT_NOTEBOOK_UID);
+    let gid = inputs.notebook_gid.unwrap_or(access::DEFAULT_NOTEBOOK_GID);
+    for host in std::iter::once(dataset_host).chain(shared_host) {
+        let path = Path::new(host);
+        if !path.exists() {
+            continue;
+        }
+        let Some(denied) = access::check(path, uid, gid) else {
+            continue;
+        };
+        tracing::warn!("{}; notebooks will fail with permission denied", denied.describe(uid, gid));
+        say!("{}", style("Grant access with one of:").dim());
+        for command in access::remediation(&denied, uid, gid) {
+            say!("  {}", style(command).cyan());
+        }
+        say!(
+            "{}",
+            style("or run notebooks as the owning user/group with --notebook-uid/--notebook-gid").dim()
+        );
+    }
+}
+
+/// Creates missing shared directories of groups, owned by the notebook user
+/// so that members can write to them; existing ones are left as they are.
+fn ensure_group_dirs(deploy_path: &Path, inputs: &DeployInputs) -> Result<()> {
+    let uid = inputs.notebook_uid.unwrap_or(access::DEFAULT_NOTEBOOK_UID);
+    let gid = inputs.notebook_gid.unwrap_or(access::DEFAULT_NOTEBOOK_GID);
+    for shared in inputs.groups.iter().filter_map(|group| group.shared.as_ref()) {
+        let path = PathBuf::from(resolve_host_path(deploy_path, shared));
+        if path.exists() {
+            continue;
+        }
+        util::ensure_dir(&path)?;
+        #[cfg(unix)]
+        if let Err(err) = nix::unistd::chown(
+            &path,
+            Some(nix::unistd::Uid::from_raw(uid)),
+            Some(nix::unistd::Gid::from_raw(gid)),
+        ) {
+            tracing::warn!(
+                "could not give {} to {}:{} ({}); group members may not be able to write to it",
+                path.display(),
+                uid,
+                gid,
+                err
+            );
+        }
+        #[cfg(not(unix))]
+        let _ = (uid, gid);
     }
     Ok(())
 }
@@ -341,7 +2233,8 @@ fn chown_dir(deploy_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn maybe_setup_systemd(deploy_path: &Path) -> Result<()> {
+fn maybe_setup_autostart(deploy_path: &Path, opts: &DeployOptions, rootless: bool) -> Result<()> {
+    util::ensure_interactive("Enable auto-start on boot?", "pass --no-systemd")?;
     let theme = ColorfulTheme::default();
 
     let enable = Confirm::with_theme(&theme)
@@ -353,14 +2246,17 @@ fn maybe_setup_systemd(deploy_path: &Path) -> Result<()> {
         return Ok(());
     }
 
-    if !util::is_root() {
-        eprintln!("{}", style("Root required for systemd setup").yellow());
-        eprintln!("{}", style("Run with sudo to complete this step").dim());
+    // A rootless daemon runs in the user's systemd instance, so the unit has to as well.
+    let user = opts.user || (rootless && matches!(opts.init, InitKind::Auto | InitKind::Systemd));
+    let init = init::select(opts.init, user)?;
+    if init.needs_root() && !util::is_root() {
+        eprintln!("{}", style(format!("Root required for {} setup", init.name())).yellow());
+        eprintln!("{}", style("Run with sudo, or redeploy with --user for a systemd user unit").dim());
         return Ok(());
     }
 
-    systemd::install_service(deploy_path)?;
-    println!("{}", style("Auto-start configured").cyan());
+    init.install_service(deploy_path)?;
+    say!("{}", style(format!("Auto-start configured ({})", init.name())).cyan());
 
     Ok(())
 }
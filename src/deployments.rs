@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{
+    cli::DeploymentsCommand,
+    config::{self, AppConfig},
+    envfile, failure, say, util,
+};
+
+pub fn run(command: DeploymentsCommand, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    match command {
+        DeploymentsCommand::List => {
+            list(app_config);
+            Ok(())
+        }
+        DeploymentsCommand::Forget { name } => {
+            let deployment = app_config
+                .deployments
+                .get(&name)
+                .cloned()
+                .with_context(|| format!("unknown deployment '{}'", name))?;
+            app_config.forget(&deployment.deploy_dir);
+            config::save(config_path, app_config)?;
+            say!(
+                "{}",
+                style(format!("Forgot {}; files at {} are untouched", name, deployment.deploy_dir.display())).cyan()
+            );
+            util::set_result(serde_json::json!({ "forgotten": name }));
+            Ok(())
+        }
+    }
+}
+
+fn list(app_config: &AppConfig) {
+    util::set_result(serde_json::json!({
+        "active": app_config.active,
+        "deployments": app_config.deployments,
+    }));
+    if app_config.deployments.is_empty() {
+        say!("{}", style("No deployments yet; run mvre-hub deploy").dim());
+        return;
+    }
+    for (name, deployment) in &app_config.deployments {
+        let active = app_config.active.as_deref() == Some(name.as_str());
+        let missing = if deployment.deploy_dir.exists() { "" } else { " (missing)" };
+        say!(
+            "{} {:<16} {:<28} {}{}",
+            if active { style("*").green().bold() } else { style(" ") },
+            name,
+            deployment.domain.as_deref().unwrap_or("-"),
+            style(deployment.deploy_dir.display()).dim(),
+            style(missing).yellow()
+        );
+    }
+}
+
+/// `use`: activates a known deployment by name, or registers and activates
+/// a deployment directory.
+pub fn activate(target: &str, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    let name = if app_config.deployments.contains_key(target) {
+        target.to_string()
+    } else {
+        let dir = Path::new(target);
+        if !dir.join(envfile::ENV_FILE).exists() {
+            return Err(failure::validation(format!(
+                "'{}' is neither a known deployment nor a deployment directory; see mvre-hub deployments list",
+                target
+            )));
+        }
+        let dir = std::path::absolute(dir).with_context(|| format!("invalid directory {}", target))?;
+        let env = envfile::load(&dir)?;
+        app_config.register(&dir, envfile::get(&env, "HUB_DOMAIN"))
+    };
+    app_config.activate(&name)?;
+    config::save(config_path, app_config)?;
+
+    let dir = app_config.active_dir().map(util::path_display).unwrap_or_default();
+    say!("{} {} ({})", style("Now using").green(), style(&name).cyan(), style(&dir).dim());
+    util::set_result(serde_json::json!({ "active": name, "deploy_dir": dir }));
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use console::style;
+
+use crate::{
+    cli::{DeployOptions, DevOptions, StartOptions, TlsMode},
+    config::AppConfig,
+    deploy,
+    engine::Engine,
+    envfile, failure, say, services, util,
+};
+
+/// App config of the trial deployment, kept next to it so the operator's own
+/// `last_deploy_dir` is left alone. Also marks a directory as a trial one.
+const DEV_CONFIG: &str = "mvre-hub-dev.json";
+const DEPLOY_DIR: &str = "hub";
+const DATASET_DIR: &str = "dataset";
+
+/// Deploy defaults, as `deploy` would take them without any flags.
+#[derive(Parser)]
+struct Defaults {
+    #[command(flatten)]
+    deploy: DeployOptions,
+}
+
+pub fn run(opts: DevOptions) -> Result<()> {
+    if let Some(root) = &opts.remove {
+        return remove(root);
+    }
+
+    let suffix = util::random_token(3)?;
+    let root = match opts.dir {
+        Some(dir) => dir,
+        None => default_root(&suffix),
+    };
+    let redeploy = root.join(DEV_CONFIG).exists();
+    if root.exists() && !redeploy && root.read_dir().map(|mut entries| entries.next().is_some()).unwrap_or(true) {
+        return Err(failure::validation(format!("{} exists and is not a trial deployment; pick an empty directory", root.display())));
+    }
+    let root = std::path::absolute(&root).with_context(|| format!("invalid directory {}", root.display()))?;
+    let dataset = root.join(DATASET_DIR);
+    write_sample_dataset(&dataset)?;
+
+    // A redeploy keeps its compose project, so the old containers are replaced.
+    let project_name = redeploy
+        .then(|| envfile::load(&root.join(DEPLOY_DIR)).ok())
+        .flatten()
+        .and_then(|env| envfile::get(&env, "COMPOSE_PROJECT_NAME").map(str::to_string))
+        .unwrap_or_else(|| format!("mvre-dev-{}", suffix));
+    let (http_port, https_port) = free_ports()?;
+    let defaults = Defaults::try_parse_from(["mvre-hub"]).context("failed to prepare deploy options")?;
+    let deploy_opts = DeployOptions {
+        yes: true,
+        force: redeploy,
+        domain: Some("localhost".to_string()),
+        tls: TlsMode::Off,
+        acme_staging: false,
+        dummy_auth: true,
+        dataset_path: Some(util::path_display(&dataset)),
+        shared_path: Some(String::new()),
+        admin_users: Some("admin".to_string()),
+        http_port,
+        https_port,
+        production: false,
+        no_systemd: true,
+        project_name: Some(project_name),
+        ..defaults.deploy
+    };
+
+    let config_path = root.join(DEV_CONFIG);
+    let mut app_config = AppConfig::default();
+    deploy::run_in(Some(root.join(DEPLOY_DIR)), deploy_opts, &config_path, &mut app_config)?;
+    if !opts.no_start {
+        let wait = StartOptions {
+            wait: true,
+            wait_timeout: "5m".to_string(),
+            open: false,
+        };
+        services::start(&wait, &config_path, &app_config)?;
+    }
+
+    let url = format!("http://localhost:{}", http_port);
+    let env = envfile::load(&root.join(DEPLOY_DIR))?;
+    let password = envfile::get(&env, "DUMMY_PASSWORD").unwrap_or_default().to_string();
+    say!("\n{}", style("Trial hub ready").green().bold());
+    say!("  URL:      {}", style(&url).cyan());
+    say!("  Login:    any username (\"admin\" is an admin), password {}", style(&password).cyan());
+    say!("  Dataset:  {}", style(dataset.display()).dim());
+    if opts.no_start {
+        say!("Start it with: {}", style(format!("mvre-hub dev --dir {}", root.display())).cyan());
+    }
+    say!("Remove it with: {}", style(format!("mvre-hub dev --remove {}", root.display())).cyan());
+    util::set_result(serde_json::json!({
+        "dir": root,
+        "url": url,
+        "password": password,
+        "started": !opts.no_start,
+    }));
+    Ok(())
+}
+
+fn remove(root: &Path) -> Result<()> {
+    if !root.join(DEV_CONFIG).exists() {
+        return Err(failure::validation(format!("{} is not a trial deployment made by mvre-hub dev", root.display())));
+    }
+    let deploy_dir = root.join(DEPLOY_DIR);
+    if deploy_dir.exists() {
+        Engine::new(&deploy_dir)
+            .compose_step(&["down", "-v", "--remove-orphans", "--rmi", "local"], "Removing trial services")
+            .context("failed to stop the trial deployment")?;
+    }
+    std::fs::remove_dir_all(root).with_context(|| format!("failed to remove {}", root.display()))?;
+    say!("{}", style(format!("Removed {}", root.display())).cyan());
+    util::set_result(serde_json::json!({ "removed": root }));
+    Ok(())
+}
+
+/// Two ports nothing listens on right now; both listeners are held until
+/// both are picked, so they differ.
+fn free_ports() -> Result<(u16, u16)> {
+    let first = TcpListener::bind(("127.0.0.1", 0)).context("failed to find a free port")?;
+    let second = TcpListener::bind(("127.0.0.1", 0)).context("failed to find a free port")?;
+    Ok((first.local_addr()?.port(), second.local_addr()?.port()))
+}
+
+/// A few small files standing in for the MoSAiC dataset.
+pub fn write_sample_dataset(dir: &Path) -> Result<()> {
+    util::write_string(
+        &dir.join("README.txt"),
+        "Sample data of an mvre-hub trial deployment; the real MoSAiC dataset is mounted here in production.\n",
+    )?;
+    let mut rows = String::from("time,air_temperature_c,wind_speed_ms\n");
+    for hour in 0..24 {
+        rows.push_str(&format!(
+            "2020-01-15T{:02}:00:00Z,{:.1},{:.1}\n",
+            hour,
+            -28.0 + (hour as f64 / 4.0).sin() * 3.0,
+            6.0 + (hour % 5) as f64 * 0.8
+        ));
+    }
+    util::write_string(&dir.join("met").join("sample.csv"), &rows)
+}
+
+/// Where trial deployments go when `--dir` is not given.
+fn default_root(suffix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mvre-hub-dev-{}", suffix))
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    cli::DuOptions,
+    compose,
+    config::AppConfig,
+    engine::{DiskUsage, Engine},
+    envfile,
+    prune,
+    say, services, util,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageEntry {
+    pub kind: &'static str,
+    pub name: String,
+    pub bytes: u64,
+}
+
+pub fn run(opts: DuOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let env = envfile::load(&deploy_dir).unwrap_or_default();
+
+    let mut entries = vec![UsageEntry {
+        kind: "deployment",
+        name: deploy_dir.display().to_string(),
+        bytes: util::dir_size(&deploy_dir),
+    }];
+    // Paths inside the deployment directory are already counted there.
+    let outside = |path: &&str| !Path::new(path).starts_with(&deploy_dir);
+    if let Some(shared) = envfile::get(&env, "SHARED_HOST_PATH").filter(outside) {
+        entries.push(path_entry("shared", shared));
+    }
+    if !opts.no_dataset {
+        if let Some(dataset) = envfile::get(&env, "DATASET_HOST_PATH").filter(outside) {
+            entries.push(path_entry("dataset", dataset));
+        }
+    }
+
+    let engine = Engine::new(&deploy_dir);
+    match engine.disk_usage() {
+        Ok(usage) => {
+            let images = engine.compose_images().unwrap_or_default();
+            let project = compose::project_name(&deploy_dir, &env);
+            entries.extend(engine_entries(&usage, &project, &prune::user_volume_prefix(&env), &images));
+        }
+        Err(err) => tracing::warn!("volume and image sizes unavailable: {:#}", err),
+    }
+
+    entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    let total: u64 = entries.iter().map(|entry| entry.bytes).sum();
+
+    for entry in &entries {
+        say!(
+            "  {:>9}  {:<10} {}",
+            util::format_size(entry.bytes),
+            entry.kind,
+            entry.name
+        );
+    }
+    say!("  {:>9}  {}", style(util::format_size(total)).bold(), style("total").bold());
+    util::set_result(serde_json::json!({ "entries": entries, "total_bytes": total }));
+    Ok(())
+}
+
+/// Picks out this deployment's user volumes, named with `volume_prefix`,
+/// Postgres volume, and images from the engine-wide usage report.
+pub fn engine_entries(usage: &DiskUsage, project: &str, volume_prefix: &str, images: &[String]) -> Vec<UsageEntry> {
+    let postgres_volume = format!("{}_postgres_data", project);
+    let mut entries = Vec::new();
+    for volume in &usage.volumes {
+        let kind = if volume.name.starts_with(volume_prefix) {
+            "volume"
+        } else if volume.name == postgres_volume {
+            "postgres"
+        } else {
+            continue;
+        };
+        entries.push(UsageEntry {
+            kind,
+            name: volume.name.clone(),
+            bytes: util::parse_size(&volume.size).unwrap_or(0),
+        });
+    }
+    for image in &usage.images {
+        let name = format!("{}:{}", image.repository, image.tag);
+        let wanted = images
+            .iter()
+            .any(|wanted| *wanted == name || (!wanted.contains(':') && format!("{}:latest", wanted) == name));
+        if wanted {
+            entries.push(UsageEntry {
+                kind: "image",
+                name,
+                bytes: util::parse_size(&image.size).unwrap_or(0),
+            });
+        }
+    }
+    entries
+}
+
+fn path_entry(kind: &'static str, path: &str) -> UsageEntry {
+    UsageEntry {
+        kind,
+        name: path.to_string(),
+        bytes: util::dir_size(Path::new(path)),
+    }
+}
@@ -0,0 +1,187 @@
+use std::{path::Path, process::Command};
+
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    cli::SelinuxLabels,
+    config::AppConfig,
+    envfile::{self, EnvMap},
+    rootless::{self, DockerMode},
+    say, selinux, services, systemd, util,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "level", content = "detail", rename_all = "lowercase")]
+pub enum Finding {
+    Ok(String),
+    Warn(String),
+    /// Something that keeps the deployment from working, with the fix.
+    Blocker(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub finding: Finding,
+}
+
+pub fn run(app_config: &AppConfig) -> Result<()> {
+    // Doctor also runs before the first deploy, so the deployment is optional.
+    let env = services::resolve_deploy_dir(app_config)
+        .ok()
+        .and_then(|deploy_dir| envfile::load(&deploy_dir).ok());
+    let checks = collect(env.as_ref());
+
+    say!("{}", style("Doctor").cyan().bold());
+    for check in &checks {
+        let (marker, detail) = match &check.finding {
+            Finding::Ok(detail) => (style("ok").green(), detail),
+            Finding::Warn(detail) => (style("warn").yellow(), detail),
+            Finding::Blocker(detail) => (style("BLOCK").red().bold(), detail),
+        };
+        say!("  {:<16} {:<5} {}", check.name, marker, detail);
+    }
+
+    let blockers = checks
+        .iter()
+        .filter(|check| matches!(check.finding, Finding::Blocker(_)))
+        .count();
+    util::set_result(serde_json::json!({ "checks": checks }));
+    if blockers > 0 {
+        anyhow::bail!("{} problem(s) block the deployment", blockers);
+    }
+    Ok(())
+}
+
+pub fn collect(env: Option<&EnvMap>) -> Vec<Check> {
+    if let Err(detail) = engine_reachable() {
+        return vec![Check {
+            name: "docker engine",
+            finding: Finding::Blocker(detail),
+        }];
+    }
+    let mode = DockerMode::detect();
+    let mut checks = vec![
+        Check {
+            name: "docker engine",
+            finding: Finding::Ok(format!("{} daemon at {}", mode.name(), mode.socket.display())),
+        },
+        Check {
+            name: "socket mount",
+            finding: check_socket(&mode, env),
+        },
+    ];
+    if let Some(env) = env {
+        let labelled = envfile::get(env, "SELINUX_LABELS") == Some("true");
+        checks.push(Check {
+            name: "selinux",
+            finding: check_selinux(selinux::resolve(SelinuxLabels::Auto), labelled),
+        });
+    }
+    if mode.rootless {
+        let ports = env.map(published_ports).unwrap_or_default();
+        checks.push(Check {
+            name: "published ports",
+            finding: check_ports(&ports, rootless::unprivileged_port_start()),
+        });
+        checks.push(Check {
+            name: "client addresses",
+            finding: check_port_driver(&rootless::port_driver()),
+        });
+        checks.push(Check {
+            name: "auto-start",
+            finding: check_autostart(),
+        });
+    }
+    checks
+}
+
+fn engine_reachable() -> Result<(), String> {
+    match Command::new("docker").args(["info", "--format", "{{.ServerVersion}}"]).output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(format!(
+            "docker cannot reach a daemon ({}); start it, or set DOCKER_HOST for rootless Docker",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(_) => Err("docker is not installed or not on PATH".to_string()),
+    }
+}
+
+/// The compose file mounts `DOCKER_SOCKET`; it has to be the socket of the
+/// daemon that runs the stack.
+fn check_socket(mode: &DockerMode, env: Option<&EnvMap>) -> Finding {
+    let Some(configured) = env.map(|env| envfile::get(env, "DOCKER_SOCKET").unwrap_or(rootless::ROOTFUL_SOCKET)) else {
+        return Finding::Ok("no deployment yet; deploy will use the detected socket".to_string());
+    };
+    if Path::new(configured) != mode.socket {
+        return Finding::Blocker(format!(
+            "deployment mounts {} but the daemon listens on {}; run `mvre-hub reconfigure --docker-socket {}`",
+            configured,
+            mode.socket.display(),
+            mode.socket.display()
+        ));
+    }
+    if !Path::new(configured).exists() {
+        return Finding::Blocker(format!("{} does not exist", configured));
+    }
+    Finding::Ok(format!("{} mounted into hub and proxy", configured))
+}
+
+fn published_ports(env: &EnvMap) -> Vec<u16> {
+    ["HTTP_PORT", "HTTPS_PORT"]
+        .iter()
+        .filter_map(|key| envfile::get(env, key).and_then(|value| value.parse().ok()))
+        .collect()
+}
+
+pub fn check_ports(ports: &[u16], unprivileged_start: u16) -> Finding {
+    let blocked = rootless::privileged_ports(ports, unprivileged_start);
+    if blocked.is_empty() {
+        return Finding::Ok(format!("all published ports are at or above {}", unprivileged_start));
+    }
+    let list = blocked.iter().map(u16::to_string).collect::<Vec<_>>().join(", ");
+    Finding::Blocker(format!(
+        "rootless Docker cannot publish {} (below {}); redeploy with --http-port 8080 --https-port 8443, or run `sysctl net.ipv4.ip_unprivileged_port_start={}`",
+        list, unprivileged_start, blocked[0]
+    ))
+}
+
+/// Unlabelled bind mounts fail with permission denied on enforcing hosts,
+/// whatever the UNIX permissions.
+pub fn check_selinux(enforcing: bool, labelled: bool) -> Finding {
+    match (enforcing, labelled) {
+        (true, false) => Finding::Blocker(
+            "SELinux is enforcing but bind mounts are not relabelled; run `mvre-hub reconfigure --selinux-labels on`".to_string(),
+        ),
+        (true, true) => Finding::Ok("enforcing; bind mounts relabelled".to_string()),
+        (false, _) => Finding::Ok("not enforcing".to_string()),
+    }
+}
+
+/// With the builtin port driver every request reaches Traefik from the
+/// RootlessKit gateway, so rate limits and fail2ban see a single client.
+pub fn check_port_driver(driver: &str) -> Finding {
+    if driver == "slirp4netns" {
+        Finding::Ok("slirp4netns port driver keeps client addresses".to_string())
+    } else {
+        Finding::Warn(format!(
+            "{} port driver hides client addresses from the proxy; rate limiting and fail2ban then treat all clients as one. Set DOCKERD_ROOTLESS_ROOTLESSKIT_PORT_DRIVER=slirp4netns (slower) if you rely on them",
+            driver
+        ))
+    }
+}
+
+/// A rootless daemon belongs to the user's systemd instance, which a system
+/// unit cannot depend on.
+fn check_autostart() -> Finding {
+    match systemd::service_state() {
+        Ok(Some(state)) if state.scope == systemd::Scope::System => Finding::Blocker(
+            "auto-start is a system unit, which cannot reach the rootless daemon; redeploy with --user".to_string(),
+        ),
+        Ok(Some(_)) => Finding::Ok("systemd user unit".to_string()),
+        _ => Finding::Ok("no systemd unit installed".to_string()),
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    compose,
+    envfile::{self, EnvMap},
+    failure,
+    progress::Spinner,
+    retry, runlog, util,
+};
+
+/// Thin wrapper around the docker and docker-compose binaries for one deployment.
+///
+/// Anything that needs to inspect engine state goes through the JSON outputs
+/// here instead of scraping human-readable tables, which change with locale and
+/// tool version.
+#[derive(Debug, Clone)]
+pub struct Engine {
+    deploy_dir: PathBuf,
+    remote: Remote,
+}
+
+/// Docker daemon on another machine that a deployment's docker and
+/// docker-compose calls go to. Kept in `.env` under its own keys: the hub
+/// reads `.env` too, and must keep using the socket mounted into it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Remote {
+    /// Name of a `docker context` on this machine.
+    pub context: Option<String>,
+    /// `DOCKER_HOST` URL, such as `ssh://admin@hub.example.org`.
+    pub host: Option<String>,
+    /// Client certificates of a TLS-protected `host`.
+    pub cert_path: Option<String>,
+}
+
+impl Remote {
+    pub fn from_env(env: &EnvMap) -> Self {
+        let get = |key: &str| envfile::get(env, key).map(str::to_string);
+        Self {
+            context: get("REMOTE_DOCKER_CONTEXT"),
+            host: get("REMOTE_DOCKER_HOST"),
+            cert_path: get("REMOTE_DOCKER_CERT_PATH"),
+        }
+    }
+
+    /// The daemon recorded for `deploy_dir`; the local one without a `.env`.
+    pub fn load(deploy_dir: &Path) -> Self {
+        envfile::load(deploy_dir).map(|env| Self::from_env(&env)).unwrap_or_default()
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.context.is_some() || self.host.is_some()
+    }
+
+    /// What the docker CLI shows in `docker context ls` or as `DOCKER_HOST`.
+    pub fn describe(&self) -> Option<String> {
+        match (&self.context, &self.host) {
+            (Some(context), _) => Some(format!("context {}", context)),
+            (None, Some(host)) => Some(host.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Points `command` at the daemon; settings of the caller's shell that
+    /// would pick another one are cleared.
+    pub fn apply(&self, command: &mut Command) {
+        if let Some(context) = &self.context {
+            command.env("DOCKER_CONTEXT", context).env_remove("DOCKER_HOST");
+        } else if let Some(host) = &self.host {
+            command.env("DOCKER_HOST", host).env_remove("DOCKER_CONTEXT");
+            match &self.cert_path {
+                Some(path) => command.env("DOCKER_CERT_PATH", path).env("DOCKER_TLS_VERIFY", "1"),
+                None => command.env_remove("DOCKER_CERT_PATH").env_remove("DOCKER_TLS_VERIFY"),
+            };
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct ContainerState {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub service: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub health: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub exit_code: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerDetails {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub restart_count: u64,
+}
+
+/// `docker system df -v` output; sizes are docker's human-readable strings.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DiskUsage {
+    #[serde(default)]
+    pub images: Vec<ImageUsage>,
+    #[serde(default)]
+    pub volumes: Vec<VolumeUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageUsage {
+    #[serde(default)]
+    pub repository: String,
+    #[serde(default)]
+    pub tag: String,
+    #[serde(default)]
+    pub size: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VolumeUsage {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub size: String,
+}
+
+/// One `docker stats --no-stream` row; values are docker's formatted strings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContainerStats {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "CPUPerc", default)]
+    pub cpu_percent: String,
+    #[serde(rename = "MemUsage", default)]
+    pub mem_usage: String,
+    #[serde(rename = "MemPerc", default)]
+    pub mem_percent: String,
+    #[serde(rename = "NetIO", default)]
+    pub net_io: String,
+}
+
+impl Engine {
+    pub fn new(deploy_dir: &Path) -> Self {
+        Self {
+            deploy_dir: deploy_dir.to_path_buf(),
+            remote: Remote::load(deploy_dir),
+        }
+    }
+
+    pub fn deploy_dir(&self) -> &Path {
+        &self.deploy_dir
+    }
+
+    /// Runs docker-compose, streaming its output to the terminal and the
+    /// run's log under `logs/`. Its stdout is dropped under
+    /// `--quiet`/`--format json` so only the command result reaches stdout.
+    pub fn compose(&self, args: &[&str]) -> Result<()> {
+        self.logged("docker-compose", args, None, None)
+    }
+
+    /// Like [`Engine::compose`], but shows `step` with a spinner and the latest
+    /// output line instead of streaming everything, unless progress is disabled.
+    pub fn compose_step(&self, args: &[&str], step: &str) -> Result<()> {
+        self.logged("docker-compose", args, None, Some(step))
+    }
+
+    /// Like [`Engine::compose`], feeding `input` on stdin.
+    pub fn compose_input(&self, args: &[&str], input: &[u8]) -> Result<()> {
+        self.logged("docker-compose", args, Some(input), None)
+    }
+
+    /// Runs docker-compose and returns its captured stdout.
+    pub fn compose_output(&self, args: &[&str]) -> Result<String> {
+        let output = self
+            .compose_command(args)
+            .output()
+            .map_err(|err| failure::docker(format!("failed to invoke docker-compose: {}", err)))?;
+        capture("docker-compose", output)
+    }
+
+    /// Runs docker (not compose) and returns its captured stdout.
+    pub fn docker_output(&self, args: &[&str]) -> Result<String> {
+        let output = self
+            .docker_command(args)
+            .output()
+            .map_err(|err| failure::docker(format!("failed to invoke docker: {}", err)))?;
+        capture("docker", output)
+    }
+
+    /// Runs docker feeding `input` on stdin and returns its captured stdout;
+    /// for secrets that must not appear in the argument list.
+    pub fn docker_input(&self, args: &[&str], input: &[u8]) -> Result<String> {
+        use std::io::Write;
+
+        let mut child = self
+            .docker_command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| failure::docker(format!("failed to invoke docker: {}", err)))?;
+        child
+            .stdin
+            .take()
+            .context("failed to open docker stdin")?
+            .write_all(input)
+            .context("failed to write to docker stdin")?;
+        let output = child.wait_with_output().context("failed to wait for docker")?;
+        capture("docker", output)
+    }
+
+    /// Runs docker with the given stdin and stdout, such as an archive file
+    /// streamed into or out of a container.
+    pub fn docker_stdio(&self, args: &[&str], stdin: Stdio, stdout: Stdio) -> Result<()> {
+        let output = self
+            .docker_command(args)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|err| failure::docker(format!("failed to invoke docker: {}", err)))?;
+        capture("docker", output).map(|_| ())
+    }
+
+    /// Runs docker showing `step` with a spinner, like [`Engine::compose_step`].
+    pub fn docker_step(&self, args: &[&str], step: &str) -> Result<()> {
+        self.tool_step("docker", args, step)
+    }
+
+    /// Like [`Engine::docker_step`] for another tool that talks to the
+    /// daemon itself, such as repo2docker.
+    pub fn tool_step(&self, program: &str, args: &[&str], step: &str) -> Result<()> {
+        self.logged(program, args, None, Some(step))
+    }
+
+    pub fn ps(&self) -> Result<Vec<ContainerState>> {
+        let raw = self.compose_output(&["ps", "--all", "--format", "json"])?;
+        parse_json_records(&raw).context("failed to parse docker-compose ps output")
+    }
+
+    pub fn inspect(&self, containers: &[&str]) -> Result<Vec<ContainerDetails>> {
+        if containers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut args = vec!["inspect"];
+        args.extend_from_slice(containers);
+        let raw = self.docker_output(&args)?;
+        parse_json_records(&raw).context("failed to parse docker inspect output")
+    }
+
+    pub fn volume_names(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct VolumeRecord {
+            name: String,
+        }
+        let raw = self.docker_output(&["volume", "ls", "--format", "json"])?;
+        let records: Vec<VolumeRecord> =
+            parse_json_records(&raw).context("failed to parse docker volume ls output")?;
+        Ok(records.into_iter().map(|record| record.name).collect())
+    }
+
+    /// Resource usage of every running container on the engine.
+    pub fn stats(&self) -> Result<Vec<ContainerStats>> {
+        let raw = self.docker_output(&["stats", "--no-stream", "--format", "{{json .}}"])?;
+        parse_json_records(&raw).context("failed to parse docker stats output")
+    }
+
+    pub fn disk_usage(&self) -> Result<DiskUsage> {
+        let raw = self.docker_output(&["system", "df", "-v", "--format", "{{json .}}"])?;
+        serde_json::from_str(raw.trim()).context("failed to parse docker system df output")
+    }
+
+    /// Images referenced by the compose file, as compose names them.
+    pub fn compose_images(&self) -> Result<Vec<String>> {
+        let raw = self.compose_output(&["config", "--images"])?;
+        Ok(raw.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect())
+    }
+
+    /// Like [`Engine::docker_output`] for another tool that talks to the daemon.
+    pub fn tool_output(&self, program: &str, args: &[&str]) -> Result<String> {
+        let output = self
+            .tool_command(program, args)
+            .output()
+            .map_err(|err| failure::docker(format!("failed to invoke {}: {}", program, err)))?;
+        capture(program, output)
+    }
+
+    /// Runs `program`, behind a spinner showing `step` if given, keeping its
+    /// whole output in the run's log so a failure in an unattended run can
+    /// still be read later. Pulls, builds, and starts are retried with
+    /// backoff and stopped at their time limit, as `--retries` and
+    /// `--docker-timeout` say.
+    fn logged(&self, program: &str, args: &[&str], input: Option<&[u8]>, step: Option<&str>) -> Result<()> {
+        let policy = retry::policy();
+        let operation = retry::operation(program, args);
+        let attempts = policy.attempts(operation);
+        let mut attempt = 1;
+        loop {
+            let mut command = match program {
+                "docker-compose" => self.compose_command(args),
+                _ => self.tool_command(program, args),
+            };
+            let spinner = step.map(Spinner::start).unwrap_or_else(Spinner::hidden);
+            let log = runlog::Writer::open(&self.deploy_dir, program, args);
+            let result = spinner.run(&mut command, program, input, log, policy.timeout(operation));
+            match result {
+                Err(err) if attempt < attempts && retry::is_transient(&err) => {
+                    let delay = policy.delay(attempt);
+                    tracing::warn!(
+                        "{} {} failed (attempt {} of {}), retrying in {}s: {:#}",
+                        program,
+                        operation.unwrap_or_default(),
+                        attempt,
+                        attempts,
+                        delay.as_secs(),
+                        err
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn docker_command(&self, args: &[&str]) -> Command {
+        self.tool_command("docker", args)
+    }
+
+    fn tool_command(&self, program: &str, args: &[&str]) -> Command {
+        let mut command = locale_neutral(Command::new(program));
+        self.remote.apply(&mut command);
+        command.args(args).current_dir(&self.deploy_dir);
+        command
+    }
+
+    fn compose_command(&self, args: &[&str]) -> Command {
+        let mut command = locale_neutral(Command::new("docker-compose"));
+        self.remote.apply(&mut command);
+        command
+            .args(compose::file_args(&self.deploy_dir, args))
+            .args(args)
+            .current_dir(&self.deploy_dir);
+        command
+    }
+}
+
+/// Parses either a JSON array or newline-delimited JSON objects; compose
+/// switched between the two across v2 releases.
+pub fn parse_json_records<T: DeserializeOwned>(raw: &str) -> Result<Vec<T>> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Ok(Vec::new());
+    }
+    if trimmed.starts_with('[') {
+        return Ok(serde_json::from_str(trimmed)?);
+    }
+    trimmed
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Into::into))
+        .collect()
+}
+
+fn locale_neutral(mut command: Command) -> Command {
+    command.env("LC_ALL", "C").env("LANG", "C");
+    command
+}
+
+fn capture(tool: &str, output: std::process::Output) -> Result<String> {
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(failure::docker(format!(
+            "{} failed: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Stdout for tools whose output the user should see, dropped under
+/// `--quiet`/`--format json`.
+pub fn terminal_stdout() -> Stdio {
+    if util::human_output() {
+        Stdio::inherit()
+    } else {
+        Stdio::null()
+    }
+}
@@ -0,0 +1,59 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+
+use crate::util;
+
+pub const ENV_FILE: &str = ".env";
+
+pub type EnvMap = BTreeMap<String, String>;
+
+pub fn load(deploy_dir: &Path) -> Result<EnvMap> {
+    let raw = util::read_to_string(&deploy_dir.join(ENV_FILE))?;
+    Ok(parse(&raw))
+}
+
+pub fn parse(raw: &str) -> EnvMap {
+    raw.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), unquote(value.trim()).to_string()))
+        .collect()
+}
+
+pub fn get<'a>(env: &'a EnvMap, key: &str) -> Option<&'a str> {
+    env.get(key).map(String::as_str).filter(|value| !value.is_empty())
+}
+
+/// Returns `raw` with `key` set to `value`, replacing an existing assignment
+/// in place or appending one. Other lines and comments are kept.
+pub fn with_value(raw: &str, key: &str, value: &str) -> String {
+    let assignment = format!("{}={}", key, value);
+    let mut found = false;
+    let mut lines: Vec<String> = raw
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((name, _)) if name.trim() == key && !line.trim_start().starts_with('#') => {
+                found = true;
+                assignment.clone()
+            }
+            _ => line.to_string(),
+        })
+        .collect();
+    if !found {
+        lines.push(assignment);
+    }
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    updated
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
@@ -0,0 +1,127 @@
+use std::{fmt, io};
+
+/// Broad class of a failed command, reported in the exit code and under
+/// `--format json` so scripts can tell a bad flag from a broken Docker daemon
+/// or an unreachable host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Other,
+    Validation,
+    Docker,
+    Network,
+    Permission,
+    Busy,
+    /// The config file or the deployment's own files are missing, unreadable,
+    /// or do not allow the operation.
+    Config,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Other => "other",
+            Category::Validation => "validation",
+            Category::Docker => "docker",
+            Category::Network => "network",
+            Category::Permission => "permission",
+            Category::Busy => "busy",
+            Category::Config => "config",
+        }
+    }
+
+    /// Process exit code. Validation shares 2 with clap's usage errors, which
+    /// never get this far.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Category::Other => 1,
+            Category::Validation => 2,
+            Category::Docker => 3,
+            Category::Network => 4,
+            Category::Permission => 5,
+            Category::Busy => 6,
+            Category::Config => 7,
+        }
+    }
+}
+
+/// An error message tagged with its category; found again by [`classify`]
+/// however much context is added on top.
+#[derive(Debug)]
+pub struct Tagged {
+    pub category: Category,
+    message: String,
+}
+
+impl fmt::Display for Tagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Tagged {}
+
+pub fn tagged(category: Category, message: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(Tagged {
+        category,
+        message: message.into(),
+    })
+}
+
+pub fn validation(message: impl Into<String>) -> anyhow::Error {
+    tagged(Category::Validation, message)
+}
+
+/// A failed docker or docker-compose call. A daemon socket the user may not
+/// open is a permission problem rather than a Docker one.
+pub fn docker(message: impl Into<String>) -> anyhow::Error {
+    let message = message.into();
+    let lower = message.to_lowercase();
+    if lower.contains("permission denied") && lower.contains("docker") {
+        return tagged(Category::Permission, message);
+    }
+    tagged(Category::Docker, message)
+}
+
+pub fn network(message: impl Into<String>) -> anyhow::Error {
+    tagged(Category::Network, message)
+}
+
+/// Something only root, or another user, may do.
+pub fn permission(message: impl Into<String>) -> anyhow::Error {
+    tagged(Category::Permission, message)
+}
+
+/// A config file or deployment that cannot be read, or is not set up for
+/// what was asked.
+pub fn config(message: impl Into<String>) -> anyhow::Error {
+    tagged(Category::Config, message)
+}
+
+/// Another operation holds the deployment; retrying later may succeed.
+pub fn busy(message: impl Into<String>) -> anyhow::Error {
+    tagged(Category::Busy, message)
+}
+
+/// The category of `err`: its innermost tag, or else what its I/O errors say.
+pub fn classify(err: &anyhow::Error) -> Category {
+    let mut category = Category::Other;
+    for cause in err.chain() {
+        if let Some(tagged) = cause.downcast_ref::<Tagged>() {
+            category = tagged.category;
+        } else if let Some(io) = cause.downcast_ref::<io::Error>() {
+            category = match io.kind() {
+                io::ErrorKind::PermissionDenied => Category::Permission,
+                io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::AddrNotAvailable
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::HostUnreachable
+                | io::ErrorKind::NetworkUnreachable => Category::Network,
+                _ => category,
+            };
+        }
+    }
+    category
+}
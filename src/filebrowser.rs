@@ -0,0 +1,36 @@
+use std::path::{Path, PathBuf};
+
+/// Config of the `files` service, a read-only index of the dataset.
+pub const FILE_BROWSER_DIR: &str = "filebrowser";
+pub const PATH_PREFIX: &str = "/files";
+/// OAuth client the hub registers for the forward-auth service; the
+/// client secret is `FILE_BROWSER_OAUTH_SECRET`.
+pub const OAUTH_CLIENT_ID: &str = "service-file-browser";
+pub const FORWARD_AUTH_IMAGE: &str = "thomseddon/traefik-forward-auth:2";
+
+/// Callback of the OAuth login; under the prefix, so it passes the
+/// forward-auth middleware instead of reaching the hub.
+pub fn callback_path() -> String {
+    format!("{}/_oauth", PATH_PREFIX)
+}
+
+pub fn files(deploy_dir: &Path) -> Vec<(PathBuf, String)> {
+    vec![(deploy_dir.join(FILE_BROWSER_DIR).join("nginx.conf"), nginx_config())]
+}
+
+pub fn nginx_config() -> String {
+    format!(
+        r#"server {{
+    listen 80;
+
+    location {prefix}/ {{
+        alias /srv/files/;
+        autoindex on;
+        autoindex_exact_size off;
+        autoindex_localtime on;
+    }}
+}}
+"#,
+        prefix = PATH_PREFIX
+    )
+}
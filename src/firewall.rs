@@ -0,0 +1,276 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+
+use crate::{
+    cli::{FirewallBackend, FirewallCommand, FirewallOptions},
+    config::AppConfig,
+    envfile, failure, say, services, util,
+};
+
+const UFW_AFTER_RULES: &str = "/etc/ufw/after.rules";
+const NFT_RULES_PATH: &str = "/etc/mvre-hub.nft";
+const NFT_MAIN_CONFIG: &str = "/etc/nftables.conf";
+const BLOCK_BEGIN: &str = "# BEGIN mvre-hub";
+const BLOCK_END: &str = "# END mvre-hub";
+
+/// Ports reachable from outside: SSH on the host, and the hub's published
+/// HTTP/HTTPS ports, which Docker forwards to Traefik.
+#[derive(Debug, Clone)]
+pub struct FirewallRules {
+    pub interface: String,
+    pub ssh_port: u16,
+    pub web_ports: Vec<u16>,
+}
+
+pub fn run(command: FirewallCommand, app_config: &AppConfig) -> Result<()> {
+    match command {
+        FirewallCommand::Apply(opts) => apply(opts, app_config),
+    }
+}
+
+fn apply(opts: FirewallOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let rules = FirewallRules {
+        interface: match opts.interface {
+            Some(interface) => interface,
+            None => default_interface()?,
+        },
+        ssh_port: opts.ssh_port,
+        web_ports: web_ports(&deploy_dir)?,
+    };
+    let backend = match opts.backend {
+        FirewallBackend::Auto if util::on_path("ufw") => FirewallBackend::Ufw,
+        FirewallBackend::Auto => FirewallBackend::Nftables,
+        backend => backend,
+    };
+    let script = match backend {
+        FirewallBackend::Ufw => ufw_script(&rules),
+        _ => nftables_ruleset(&rules),
+    };
+
+    if opts.print {
+        if !util::json_output() {
+            print!("{}", script);
+        }
+        util::set_result(serde_json::json!({ "backend": backend.name(), "rules": script }));
+        return Ok(());
+    }
+
+    if !util::is_root() {
+        return Err(failure::permission("Root required to install firewall rules; use --print to review them"));
+    }
+    say!(
+        "Allowing SSH ({}) and web ({}) on {}; everything else from outside is dropped, including Docker-published ports.",
+        rules.ssh_port,
+        join_ports(&rules.web_ports),
+        rules.interface
+    );
+    let prompt = format!("Install {} rules?", backend.name());
+    if !opts.yes {
+        util::ensure_interactive(&prompt, "pass --yes")?;
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            return Err(failure::validation("Aborted"));
+        }
+    }
+
+    match backend {
+        FirewallBackend::Ufw => install_ufw(&rules)?,
+        _ => install_nftables(&rules)?,
+    }
+    say!("{}", style(format!("Firewall rules installed ({})", backend.name())).green());
+    util::set_result(serde_json::json!({ "backend": backend.name(), "rules": script }));
+    Ok(())
+}
+
+/// ufw commands plus the `after.rules` block that filters Docker-forwarded
+/// traffic, which ufw's own rules never see.
+pub fn ufw_script(rules: &FirewallRules) -> String {
+    let mut script = String::from("#!/bin/sh\nset -e\n");
+    for command in ufw_commands(rules) {
+        script.push_str(&command.join(" "));
+        script.push('\n');
+    }
+    script.push_str(&format!(
+        "# Append to {} (replacing any previous mvre-hub block), then run: ufw reload\ncat <<'EOF'\n{}EOF\n",
+        UFW_AFTER_RULES,
+        docker_user_block(rules)
+    ));
+    script
+}
+
+pub fn ufw_commands(rules: &FirewallRules) -> Vec<Vec<String>> {
+    let command = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let mut commands = vec![
+        command(&["ufw", "default", "deny", "incoming"]),
+        command(&["ufw", "default", "allow", "outgoing"]),
+    ];
+    for port in std::iter::once(rules.ssh_port).chain(rules.web_ports.iter().copied()) {
+        commands.push(command(&["ufw", "allow", &format!("{}/tcp", port)]));
+    }
+    commands.push(command(&["ufw", "--force", "enable"]));
+    commands
+}
+
+/// iptables-restore fragment for the `DOCKER-USER` chain: connections from
+/// the external interface to Docker-published ports are dropped unless their
+/// original destination is one of the web ports.
+pub fn docker_user_block(rules: &FirewallRules) -> String {
+    let interface = &rules.interface;
+    let mut block = format!("{}\n*filter\n:DOCKER-USER - [0:0]\n", BLOCK_BEGIN);
+    block.push_str(&format!(
+        "-A DOCKER-USER -i {} -m conntrack --ctstate RELATED,ESTABLISHED -j RETURN\n",
+        interface
+    ));
+    for port in &rules.web_ports {
+        block.push_str(&format!(
+            "-A DOCKER-USER -i {} -p tcp -m conntrack --ctorigdstport {} -j RETURN\n",
+            interface, port
+        ));
+    }
+    block.push_str(&format!("-A DOCKER-USER -i {} -j DROP\n", interface));
+    block.push_str("-A DOCKER-USER -j RETURN\nCOMMIT\n");
+    block.push_str(BLOCK_END);
+    block.push('\n');
+    block
+}
+
+/// A self-contained nftables table; loading it again replaces the previous
+/// version. The forward chain runs before Docker's rules.
+pub fn nftables_ruleset(rules: &FirewallRules) -> String {
+    let interface = &rules.interface;
+    let web = join_ports(&rules.web_ports);
+    format!(
+        r#"table inet mvre_hub
+delete table inet mvre_hub
+
+table inet mvre_hub {{
+    chain input {{
+        type filter hook input priority 0; policy accept;
+        iifname != "{interface}" accept
+        ct state established,related accept
+        ct state invalid drop
+        meta l4proto {{ icmp, ipv6-icmp }} accept
+        tcp dport {{ {ssh}, {web} }} accept
+        drop
+    }}
+
+    chain forward {{
+        type filter hook forward priority -1; policy accept;
+        iifname "{interface}" ct state established,related accept
+        iifname "{interface}" ct status dnat ct original proto-dst != {{ {web} }} drop
+    }}
+}}
+"#,
+        interface = interface,
+        ssh = rules.ssh_port,
+        web = web,
+    )
+}
+
+fn install_ufw(rules: &FirewallRules) -> Result<()> {
+    for command in ufw_commands(rules) {
+        run_tool(&command[0], &command[1..])?;
+    }
+    let path = Path::new(UFW_AFTER_RULES);
+    let current = util::read_to_string(path).unwrap_or_default();
+    util::write_string(path, &replace_block(&current, &docker_user_block(rules)))?;
+    run_tool("ufw", &["reload".to_string()])
+}
+
+fn install_nftables(rules: &FirewallRules) -> Result<()> {
+    util::write_string(Path::new(NFT_RULES_PATH), &nftables_ruleset(rules))?;
+    run_tool("nft", &["-f".to_string(), NFT_RULES_PATH.to_string()])?;
+
+    // Load the table at boot as well, where the distribution uses the
+    // standard nftables service.
+    let main = Path::new(NFT_MAIN_CONFIG);
+    if let Ok(current) = util::read_to_string(main) {
+        let include = format!("include \"{}\"", NFT_RULES_PATH);
+        if !current.contains(&include) {
+            util::write_string(main, &format!("{}\n{}\n", current.trim_end(), include))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the marked mvre-hub block in `contents`, or appends `block`.
+pub fn replace_block(contents: &str, block: &str) -> String {
+    if let (Some(start), Some(end)) = (contents.find(BLOCK_BEGIN), contents.find(BLOCK_END)) {
+        if start < end {
+            let mut end = end + BLOCK_END.len();
+            if contents[end..].starts_with('\n') {
+                end += 1;
+            }
+            return format!("{}{}{}", &contents[..start], block, &contents[end..]);
+        }
+    }
+    if contents.is_empty() || contents.ends_with('\n') {
+        format!("{}{}", contents, block)
+    } else {
+        format!("{}\n{}", contents, block)
+    }
+}
+
+fn web_ports(deploy_dir: &Path) -> Result<Vec<u16>> {
+    let env = envfile::load(deploy_dir)?;
+    let port = |key: &str, default: u16| -> Result<u16> {
+        match envfile::get(&env, key) {
+            Some(value) => value.parse().with_context(|| format!("invalid {} in .env", key)),
+            None => Ok(default),
+        }
+    };
+    let mut ports = vec![port("HTTP_PORT", 80)?, port("HTTPS_PORT", 443)?];
+    ports.dedup();
+    Ok(ports)
+}
+
+fn default_interface() -> Result<String> {
+    let output = Command::new("ip")
+        .args(["route", "show", "default"])
+        .output()
+        .context("failed to run ip route; pass --interface")?;
+    parse_default_interface(&String::from_utf8_lossy(&output.stdout))
+        .context("could not detect the external interface; pass --interface")
+}
+
+/// Interface of the first default route in `ip route show default` output.
+pub fn parse_default_interface(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        words.find(|word| *word == "dev")?;
+        words.next().map(String::from)
+    })
+}
+
+fn join_ports(ports: &[u16]) -> String {
+    ports.iter().map(u16::to_string).collect::<Vec<_>>().join(", ")
+}
+
+fn run_tool(program: &str, args: &[String]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("failed to run {}", program))?;
+    if status.success() {
+        Ok(())
+    } else {
+        anyhow::bail!("{} failed: {}", program, status)
+    }
+}
+
+impl FirewallBackend {
+    pub fn name(self) -> &'static str {
+        match self {
+            FirewallBackend::Auto => "auto",
+            FirewallBackend::Ufw => "ufw",
+            FirewallBackend::Nftables => "nftables",
+        }
+    }
+}
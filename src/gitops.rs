@@ -0,0 +1,249 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audit,
+    cli::{ApplyOptions, GitopsOptions, ManifestOptions},
+    config::AppConfig,
+    failure::{self, Category},
+    manifest, say, schedule, systemd, util,
+};
+
+const GITOPS_SERVICE: &str = "mvre-hub-gitops";
+/// Last applied commit, kept inside the clone's `.git` directory.
+const APPLIED_FILE: &str = "mvre-hub-applied";
+
+pub fn run(opts: GitopsOptions, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    if opts.uninstall {
+        if systemd::remove_daemon(GITOPS_SERVICE)? {
+            say!("{}", style(format!("Removed {}.service", GITOPS_SERVICE)).green());
+        } else {
+            say!("{}", style("The gitops service is not installed").dim());
+        }
+        return Ok(());
+    }
+
+    let interval = util::parse_duration(&opts.interval).context("invalid --interval")?;
+    if interval == 0 {
+        return Err(failure::validation("--interval must be greater than zero"));
+    }
+    validate_manifest_path(&opts.path)?;
+    if !util::on_path("git") {
+        return Err(failure::validation("gitops needs git; install it first"));
+    }
+    let repo = opts.repo.clone().unwrap_or_default();
+    if opts.install {
+        return install(&opts, &repo);
+    }
+
+    let checkout = match &opts.checkout {
+        Some(dir) => dir.clone(),
+        None => config_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("gitops")
+            .join(checkout_name(&repo)),
+    };
+    let mut follower = Follower {
+        applied: util::read_to_string(&checkout.join(".git").join(APPLIED_FILE))
+            .ok()
+            .map(|commit| commit.trim().to_string()),
+        failed: None,
+        checkout,
+        repo: &repo,
+        opts: &opts,
+    };
+    if !opts.once {
+        say!(
+            "{}",
+            style(format!("Following {} in {} every {}", opts.path.display(), repo, opts.interval)).cyan()
+        );
+    }
+    loop {
+        if let Err(err) = follower.round(config_path, app_config) {
+            tracing::warn!("gitops round failed: {:#}", err);
+            if opts.once {
+                return Err(err);
+            }
+        }
+        if opts.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+fn install(opts: &GitopsOptions, repo: &str) -> Result<()> {
+    let scope = schedule::scope_for(opts.user)?;
+    let exe = std::env::current_exe().context("failed to resolve mvre-hub binary path")?;
+    let mut command = format!(
+        "{} gitops --repo {} --path {} --interval {}",
+        exe.display(),
+        schedule::exec_arg(repo),
+        schedule::exec_arg(&opts.path.to_string_lossy()),
+        schedule::exec_arg(&opts.interval)
+    );
+    if let Some(branch) = &opts.branch {
+        command.push_str(&format!(" --branch {}", schedule::exec_arg(branch)));
+    }
+    if let Some(checkout) = &opts.checkout {
+        let checkout = std::path::absolute(checkout).unwrap_or_else(|_| checkout.clone());
+        command.push_str(&format!(" --checkout {}", schedule::exec_arg(&checkout.to_string_lossy())));
+    }
+    systemd::install_daemon(scope, GITOPS_SERVICE, "MVRE-Hub GitOps", &command)?;
+    say!("{}", style(format!("Installed and started {}.service", GITOPS_SERVICE)).green());
+    util::set_result(serde_json::json!({ "service": GITOPS_SERVICE, "command": command }));
+    Ok(())
+}
+
+/// The manifest is looked up inside the clone, so its path may not leave it.
+pub fn validate_manifest_path(path: &Path) -> Result<()> {
+    let inside = path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if path.as_os_str().is_empty() || !inside {
+        return Err(failure::validation(format!(
+            "--path '{}' must be a relative path inside the repository",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Directory name of the clone of `repo`: its last path segment, made safe,
+/// and a hash of the whole URL so two repositories of the same name differ.
+pub fn checkout_name(repo: &str) -> String {
+    let last = repo
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".git");
+    let name: String = last
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' { ch } else { '-' })
+        .collect();
+    let hash: String = Sha256::digest(repo.as_bytes()).iter().take(4).map(|b| format!("{:02x}", b)).collect();
+    if name.is_empty() {
+        hash
+    } else {
+        format!("{}-{}", name, hash)
+    }
+}
+
+struct Follower<'a> {
+    checkout: PathBuf,
+    repo: &'a str,
+    opts: &'a GitopsOptions,
+    applied: Option<String>,
+    /// Commit whose apply failed for a reason retrying will not fix; skipped
+    /// until a new commit arrives.
+    failed: Option<String>,
+}
+
+impl Follower<'_> {
+    fn round(&mut self, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+        let commit = sync(&self.checkout, self.repo, self.opts.branch.as_deref())?;
+        if self.applied.as_deref() == Some(commit.as_str()) || self.failed.as_deref() == Some(commit.as_str()) {
+            tracing::debug!("nothing new at {}", commit);
+            return Ok(());
+        }
+        let short = commit[..commit.len().min(12)].to_string();
+        say!(
+            "{} Applying {} at {}",
+            style(util::format_timestamp(util::unix_now())).dim(),
+            self.opts.path.display(),
+            style(&short).bold()
+        );
+
+        let file = self.checkout.join(&self.opts.path);
+        let deploy_dir = manifest::deploy_dir(&file, app_config).ok();
+        let apply = ApplyOptions {
+            manifest: ManifestOptions { file },
+            yes: true,
+        };
+        let result = manifest::apply(apply, config_path, app_config);
+        let error = result.as_ref().err().map(|err| format!("{:#}", err));
+        let action = match &result {
+            Ok(plan) => serde_json::to_value(plan.action)?.as_str().unwrap_or_default().to_string(),
+            Err(_) => "failed".to_string(),
+        };
+        let path = self.opts.path.to_string_lossy();
+        if let Some(dir) = deploy_dir.or_else(|| app_config.active_dir().map(Path::to_path_buf)) {
+            if let Err(err) = audit::record_event("gitops", &["apply", &commit, &path, &action], error.clone(), &dir) {
+                tracing::warn!("failed to record applied commit: {:#}", err);
+            }
+        }
+
+        match result {
+            Ok(_) => {
+                util::write_string(&self.checkout.join(".git").join(APPLIED_FILE), &format!("{}\n", commit))?;
+                self.applied = Some(commit);
+                self.failed = None;
+                Ok(())
+            }
+            Err(err) => {
+                // A busy deployment or a hiccup of Docker or the network
+                // clears up; a bad manifest needs a new commit.
+                if !matches!(failure::classify(&err), Category::Busy | Category::Docker | Category::Network) {
+                    self.failed = Some(commit);
+                }
+                Err(err.context(format!("failed to apply commit {}", short)))
+            }
+        }
+    }
+}
+
+/// Brings the clone to the newest commit of `branch`, or of the remote's
+/// default branch, and returns that commit.
+fn sync(checkout: &Path, repo: &str, branch: Option<&str>) -> Result<String> {
+    if checkout.join(".git").exists() {
+        git(Some(checkout), &["remote", "set-url", "origin", repo])?;
+        git(Some(checkout), &["fetch", "--quiet", "--prune", "origin"])?;
+        let target = branch
+            .map(|branch| format!("origin/{}", branch))
+            .unwrap_or_else(|| "origin/HEAD".to_string());
+        git(Some(checkout), &["reset", "--quiet", "--hard", &target])?;
+        git(Some(checkout), &["clean", "--quiet", "-fd"])?;
+    } else {
+        if let Some(parent) = checkout.parent() {
+            util::ensure_dir(parent)?;
+        }
+        let target = checkout.to_string_lossy();
+        let mut args = vec!["clone", "--quiet"];
+        if let Some(branch) = branch {
+            args.extend(["--branch", branch]);
+        }
+        args.extend([repo, target.as_ref()]);
+        git(None, &args)?;
+    }
+    Ok(git(Some(checkout), &["rev-parse", "HEAD"])?.trim().to_string())
+}
+
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    // Never wait for a password nobody is there to type.
+    let output = command
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = format!("git {} failed: {}", args[0], stderr.trim());
+        return Err(match args[0] {
+            "clone" | "fetch" => failure::network(message),
+            _ => anyhow::anyhow!(message),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,297 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+
+use crate::{
+    cli::{GraphFormat, GraphOptions},
+    compose::{self, ComposeFile, Mount},
+    config::AppConfig,
+    envfile::{self, EnvMap},
+    prune, say,
+    services,
+    util,
+};
+
+const SPAWNER_NODE: &str = "spawner";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Service,
+    Spawner,
+    Volume,
+    HostPath,
+    Network,
+}
+
+#[derive(Debug)]
+struct Node {
+    label: String,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+struct Edge {
+    from: String,
+    to: String,
+    label: Option<String>,
+    dashed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Graph {
+    nodes: BTreeMap<String, Node>,
+    edges: Vec<Edge>,
+}
+
+pub fn run(opts: GraphOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let rendered = render(&deploy_dir, opts.diagram)?;
+
+    match &opts.output {
+        Some(path) => {
+            util::write_string(path, &rendered)?;
+            say!("Graph written to {}", path.display());
+        }
+        None if util::json_output() => util::set_result(serde_json::json!({ "diagram": rendered })),
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
+
+pub fn render(deploy_dir: &Path, format: GraphFormat) -> Result<String> {
+    let compose = compose::load(deploy_dir)?;
+    let env = envfile::load(deploy_dir).unwrap_or_default();
+    let graph = build(&compose, &env);
+
+    Ok(match format {
+        GraphFormat::Dot => to_dot(&graph),
+        GraphFormat::Mermaid => to_mermaid(&graph),
+    })
+}
+
+fn build(compose: &ComposeFile, env: &EnvMap) -> Graph {
+    let mut graph = Graph::default();
+
+    for (name, service) in &compose.services {
+        let label = match &service.image {
+            Some(image) => format!("{}\\n{}", name, image),
+            None => name.clone(),
+        };
+        graph.node(&service_id(name), label, NodeKind::Service);
+
+        for dependency in service.dependencies() {
+            graph.edge(service_id(name), service_id(&dependency), Some("depends_on"), false);
+        }
+
+        for mount in service.mounts() {
+            let source = graph.mount_source(&mount);
+            graph.edge(source, service_id(name), Some(&mount_label(&mount)), false);
+        }
+
+        let networks = service.network_names();
+        if networks.is_empty() {
+            graph.network_edge(name, "default");
+        }
+        for network in networks {
+            graph.network_edge(name, &network);
+        }
+    }
+
+    if compose.services.contains_key("jupyterhub") {
+        add_spawner(&mut graph, compose, env);
+    }
+
+    graph
+}
+
+fn add_spawner(graph: &mut Graph, compose: &ComposeFile, env: &EnvMap) {
+    let image = envfile::get(env, "USER_IMAGE").unwrap_or("mvre-user:latest");
+    graph.node(
+        SPAWNER_NODE,
+        format!("user servers (DockerSpawner)\\n{}", image),
+        NodeKind::Spawner,
+    );
+    graph.edge(service_id("jupyterhub"), SPAWNER_NODE.to_string(), Some("spawns"), false);
+
+    for mount in spawner_mounts(env) {
+        let source = graph.mount_source(&mount);
+        graph.edge(source, SPAWNER_NODE.to_string(), Some(&mount_label(&mount)), false);
+    }
+
+    // Deployments with isolated networks attach user servers to `users`;
+    // others to the project's default network unless told otherwise.
+    let network = if compose.networks.contains_key("users") {
+        "users"
+    } else {
+        envfile::get(env, "DOCKER_NETWORK_NAME").unwrap_or("default")
+    };
+    let id = network_id(network);
+    graph.node(&id, network.to_string(), NodeKind::Network);
+    graph.edge(SPAWNER_NODE.to_string(), id, None, true);
+}
+
+/// The volumes of a user server, by the rules of the generated
+/// jupyterhub_config.py. Group shares and the nbgrader student config are
+/// mounted for some users only but are shown all the same.
+fn spawner_mounts(env: &EnvMap) -> Vec<Mount> {
+    let bind = |source: String, target: &str, read_only: bool| Mount {
+        source,
+        target: target.to_string(),
+        read_only,
+        named: false,
+    };
+
+    let mut mounts = vec![match envfile::get(env, "WORKSPACE_HOST_PATH") {
+        Some(host) => bind(format!("{}/{{username}}", host), "/home/jovyan/work", false),
+        None => Mount {
+            source: format!("{}{{username}}", prune::user_volume_prefix(env)),
+            target: "/home/jovyan/work".to_string(),
+            read_only: false,
+            named: true,
+        },
+    }];
+    if let Some(host) = envfile::get(env, "DATASET_HOST_PATH") {
+        let target = envfile::get(env, "DATASET_MOUNT_PATH").unwrap_or("/data/mosaic");
+        mounts.push(bind(host.to_string(), target, true));
+    }
+    if let Some(root) = envfile::get(env, "SCRATCH_ROOT") {
+        mounts.push(bind(format!("{}/{{username}}", root), "/home/jovyan/scratch", false));
+    }
+    if let Some(host) = envfile::get(env, "SHARED_HOST_PATH") {
+        let target = envfile::get(env, "SHARED_MOUNT_PATH").unwrap_or("/home/jovyan/shared");
+        mounts.push(bind(host.to_string(), target, true));
+    }
+    let nbgrader = envfile::get(env, "ENABLE_NBGRADER").is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    if let Some(host) = envfile::get(env, "NBGRADER_HOST_PATH").filter(|_| nbgrader) {
+        mounts.push(bind(format!("{}/exchange", host), "/srv/nbgrader/exchange", false));
+        mounts.push(bind(format!("{}/nbgrader_config.py", host), "/etc/jupyter/nbgrader_config.py", true));
+        mounts.push(bind(format!("{}/student", host), "/srv/nbgrader/student", true));
+    }
+    for entry in envfile::get(env, "GROUP_SHARES").unwrap_or("").split(',') {
+        if let Some((group, host)) = entry.split_once('=') {
+            mounts.push(bind(host.to_string(), &format!("/home/jovyan/groups/{}", group), false));
+        }
+    }
+    mounts
+}
+
+impl Graph {
+    fn node(&mut self, id: &str, label: String, kind: NodeKind) {
+        self.nodes.entry(id.to_string()).or_insert(Node { label, kind });
+    }
+
+    fn edge(&mut self, from: String, to: String, label: Option<&str>, dashed: bool) {
+        self.edges.push(Edge {
+            from,
+            to,
+            label: label.map(str::to_string),
+            dashed,
+        });
+    }
+
+    fn mount_source(&mut self, mount: &Mount) -> String {
+        if mount.named {
+            let id = format!("vol_{}", sanitize(&mount.source));
+            self.node(&id, mount.source.clone(), NodeKind::Volume);
+            id
+        } else {
+            let id = format!("host_{}", sanitize(&mount.source));
+            self.node(&id, mount.source.clone(), NodeKind::HostPath);
+            id
+        }
+    }
+
+    fn network_edge(&mut self, service: &str, network: &str) {
+        let id = network_id(network);
+        self.node(&id, network.to_string(), NodeKind::Network);
+        self.edge(service_id(service), id, None, true);
+    }
+}
+
+fn service_id(name: &str) -> String {
+    format!("svc_{}", sanitize(name))
+}
+
+fn network_id(name: &str) -> String {
+    format!("net_{}", sanitize(name))
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect()
+}
+
+fn mount_label(mount: &Mount) -> String {
+    if mount.read_only {
+        format!("{} (ro)", mount.target)
+    } else {
+        mount.target.clone()
+    }
+}
+
+fn to_dot(graph: &Graph) -> String {
+    let mut out = String::from("digraph mvre_hub {\n  rankdir=LR;\n  node [fontname=\"Helvetica\"];\n");
+    for (id, node) in &graph.nodes {
+        let shape = match node.kind {
+            NodeKind::Service => "box",
+            NodeKind::Spawner => "box3d",
+            NodeKind::Volume => "cylinder",
+            NodeKind::HostPath => "folder",
+            NodeKind::Network => "ellipse",
+        };
+        out.push_str(&format!(
+            "  {} [label=\"{}\", shape={}];\n",
+            id,
+            node.label.replace('"', "\\\""),
+            shape
+        ));
+    }
+    for edge in &graph.edges {
+        let mut attrs = Vec::new();
+        if let Some(label) = &edge.label {
+            attrs.push(format!("label=\"{}\"", label.replace('"', "\\\"")));
+        }
+        if edge.dashed {
+            attrs.push("style=dashed".to_string());
+            attrs.push("arrowhead=none".to_string());
+        }
+        if attrs.is_empty() {
+            out.push_str(&format!("  {} -> {};\n", edge.from, edge.to));
+        } else {
+            out.push_str(&format!("  {} -> {} [{}];\n", edge.from, edge.to, attrs.join(", ")));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn to_mermaid(graph: &Graph) -> String {
+    let mut out = String::from("flowchart LR\n");
+    for (id, node) in &graph.nodes {
+        let label = node.label.replace("\\n", "<br/>").replace('"', "#quot;");
+        let shape = match node.kind {
+            NodeKind::Service | NodeKind::Spawner => format!("[\"{}\"]", label),
+            NodeKind::Volume => format!("[(\"{}\")]", label),
+            NodeKind::HostPath => format!("[/\"{}\"/]", label),
+            NodeKind::Network => format!("{{{{\"{}\"}}}}", label),
+        };
+        out.push_str(&format!("  {}{}\n", id, shape));
+    }
+    for edge in &graph.edges {
+        let arrow = if edge.dashed { "-.-" } else { "-->" };
+        match &edge.label {
+            Some(label) => out.push_str(&format!(
+                "  {} {}|\"{}\"| {}\n",
+                edge.from,
+                arrow,
+                label.replace('"', "#quot;"),
+                edge.to
+            )),
+            None => out.push_str(&format!("  {} {} {}\n", edge.from, arrow, edge.to)),
+        }
+    }
+    out
+}
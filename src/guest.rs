@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{GuestCommand, GuestCreateOptions},
+    compose,
+    config::AppConfig,
+    engine::Engine,
+    envfile, failure,
+    hubapi::HubClient,
+    say, schedule, services, systemd, util, workspace,
+};
+
+const GUESTS_FILE: &str = "guests.json";
+/// Expiry timer, suffixed with the deployment's Compose project name.
+const PRUNE_TIMER: &str = "mvre-hub-guest-prune";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestRecord {
+    pub name: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+pub fn run(command: GuestCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        GuestCommand::Create(opts) => {
+            // The unsuffixed timer of older releases pruned the active deployment.
+            let legacy = app_config.active_dir() == Some(deploy_dir.as_path());
+            create(&deploy_dir, &opts, legacy)
+        }
+        GuestCommand::List => list(&deploy_dir),
+        GuestCommand::Prune => prune(&deploy_dir),
+    }
+}
+
+fn create(deploy_dir: &Path, opts: &GuestCreateOptions, legacy: bool) -> Result<()> {
+    let lifetime = util::parse_duration(&opts.expires)?;
+    if lifetime == 0 {
+        return Err(failure::validation("--expires must be greater than zero"));
+    }
+    let name = match &opts.name {
+        Some(name) => name.clone(),
+        None => format!("guest-{}", &util::random_token(3)?),
+    };
+
+    let client = HubClient::from_deployment(deploy_dir)?;
+    client.create_user(&name)?;
+    let token = client.create_token(&name, lifetime, "mvre-hub guest access")?;
+
+    let now = util::unix_now();
+    let expires_at = now.saturating_add(lifetime);
+    let mut guests = load(deploy_dir)?;
+    guests.push(GuestRecord {
+        name: name.clone(),
+        created_at: now,
+        expires_at,
+    });
+    save(deploy_dir, &guests)?;
+
+    let env = envfile::load(deploy_dir)?;
+    let domain = envfile::get(&env, "HUB_DOMAIN").unwrap_or("localhost");
+    say!("{}", style(format!("Guest {} created", name)).green());
+    say!("Expires: {}", util::format_timestamp(expires_at));
+    let link = format!("https://{}/hub/spawn?token={}", domain, token);
+    say!("Login link: {}", style(&link).cyan());
+    util::set_result(serde_json::json!({
+        "name": name,
+        "expires_at": util::format_timestamp(expires_at),
+        "login_link": link,
+    }));
+
+    if !opts.no_timer {
+        ensure_prune_timer(deploy_dir, &env, legacy);
+    }
+
+    Ok(())
+}
+
+fn list(deploy_dir: &Path) -> Result<()> {
+    let guests = load(deploy_dir)?;
+    util::set_result(serde_json::json!({ "guests": guests }));
+    if guests.is_empty() {
+        say!("{}", style("No guest accounts").dim());
+        return Ok(());
+    }
+    let now = util::unix_now();
+    for guest in guests {
+        let state = if guest.expires_at <= now {
+            style("expired").red()
+        } else {
+            style("active").green()
+        };
+        say!(
+            "  {:<20} {:<8} expires {}",
+            guest.name,
+            state,
+            util::format_timestamp(guest.expires_at)
+        );
+    }
+    Ok(())
+}
+
+/// Deletes expired guests from the hub along with their workspace volumes.
+fn prune(deploy_dir: &Path) -> Result<()> {
+    let now = util::unix_now();
+    let (expired, active): (Vec<_>, Vec<_>) = load(deploy_dir)?
+        .into_iter()
+        .partition(|guest| guest.expires_at <= now);
+    if expired.is_empty() {
+        say!("{}", style("No expired guests").dim());
+        return Ok(());
+    }
+
+    let env = envfile::load(deploy_dir)?;
+    let client = HubClient::from_deployment(deploy_dir)?;
+    let engine = Engine::new(deploy_dir);
+    let mut remaining = active;
+    let mut removed = Vec::new();
+    let mut failed_volumes = Vec::new();
+    for guest in expired {
+        let _ = client.stop_server(&guest.name, "");
+        match client.delete_user(&guest.name) {
+            Ok(()) => {
+                let volume = workspace::user_volume(&env, &guest.name);
+                if let Err(err) = engine.docker_output(&["volume", "rm", &volume]) {
+                    tracing::warn!("failed to remove volume {} of guest {}: {:#}", volume, guest.name, err);
+                    failed_volumes.push(volume);
+                }
+                say!("Removed guest {}", guest.name);
+                removed.push(guest.name);
+            }
+            Err(err) => {
+                tracing::warn!("failed to remove guest {}: {:#}", guest.name, err);
+                remaining.push(guest);
+            }
+        }
+    }
+    util::set_result(serde_json::json!({ "removed": removed, "failed_volumes": failed_volumes }));
+    save(deploy_dir, &remaining)
+}
+
+/// Installs an hourly `guest prune` of this deployment, replacing the
+/// deployment-agnostic timer of older releases when `legacy` is set.
+fn ensure_prune_timer(deploy_dir: &Path, env: &envfile::EnvMap, legacy: bool) {
+    let unit = prune_timer(&compose::project_name(deploy_dir, env));
+    if systemd::timer_installed(systemd::Scope::System, &unit) {
+        return;
+    }
+    if !util::is_root() {
+        tracing::warn!("root required to install the guest expiry timer; run 'mvre-hub guest prune' periodically instead");
+        return;
+    }
+
+    let result = schedule::hub_command(deploy_dir, "guest prune").and_then(|command| {
+        systemd::install_timer(
+            systemd::Scope::System,
+            &unit,
+            "MVRE-Hub guest account expiry",
+            &command,
+            "hourly",
+        )?;
+        if legacy && systemd::timer_installed(systemd::Scope::System, PRUNE_TIMER) {
+            systemd::remove_timer(systemd::Scope::System, PRUNE_TIMER)?;
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => say!("{}", style("Guest expiry timer installed").cyan()),
+        Err(err) => tracing::warn!("failed to install the guest expiry timer: {:#}", err),
+    }
+}
+
+/// Expiry timer unit of the deployment with Compose project `project`.
+pub fn prune_timer(project: &str) -> String {
+    format!("{}-{}", PRUNE_TIMER, project)
+}
+
+fn guests_path(deploy_dir: &Path) -> PathBuf {
+    deploy_dir.join(GUESTS_FILE)
+}
+
+fn load(deploy_dir: &Path) -> Result<Vec<GuestRecord>> {
+    let path = guests_path(deploy_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw = util::read_to_string(&path)?;
+    serde_json::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+fn save(deploy_dir: &Path, guests: &[GuestRecord]) -> Result<()> {
+    let serialized = serde_json::to_string_pretty(guests).context("failed to serialize guests")?;
+    util::write_string(&guests_path(deploy_dir), &serialized)
+}
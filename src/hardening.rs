@@ -0,0 +1,160 @@
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{
+    cli::{Fail2banOptions, HardeningCommand, LogSource},
+    compose,
+    config::AppConfig,
+    envfile, failure, say, services,
+    templates::{self, JailValues},
+    util,
+};
+
+const FAIL2BAN_DIR: &str = "/etc/fail2ban";
+const APPARMOR_DIR: &str = "/etc/apparmor.d";
+const FILTER_FILE: &str = "filter.d/mvre-hub.conf";
+const JAIL_FILE: &str = "jail.d/mvre-hub.local";
+const SANDBOX_DIR: &str = "sandbox";
+const APPARMOR_FILE: &str = "apparmor.profile";
+
+pub fn run(command: HardeningCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        HardeningCommand::Fail2ban(opts) => install_fail2ban(&deploy_dir, opts),
+        HardeningCommand::Apparmor => load_apparmor(&deploy_dir),
+    }
+}
+
+/// Seccomp profile, and with `apparmor` the AppArmor profile, mounted into
+/// the hub for the spawner to apply to user containers.
+pub fn sandbox_files(deploy_dir: &Path, apparmor: bool) -> Vec<(PathBuf, String)> {
+    let dir = deploy_dir.join(SANDBOX_DIR);
+    let mut files = vec![(dir.join("seccomp.json"), templates::seccomp_profile())];
+    if apparmor {
+        files.push((dir.join(APPARMOR_FILE), templates::apparmor_profile()));
+    }
+    files
+}
+
+/// Filter and jail written into the deployment at deploy time (`fail2ban/`),
+/// with default limits, for operators who manage fail2ban themselves.
+pub fn fail2ban_files(deploy_dir: &Path) -> Vec<(PathBuf, String)> {
+    let log_path = hub_log_path(deploy_dir);
+    let jail = templates::fail2ban_jail(&JailValues {
+        log_path: Some(&log_path),
+        container_name: "",
+        max_retry: 5,
+        find_time: "10m",
+        ban_time: "1h",
+    });
+    let dir = deploy_dir.join("fail2ban");
+    vec![
+        (dir.join(FILTER_FILE), templates::fail2ban_filter()),
+        (dir.join(JAIL_FILE), jail),
+    ]
+}
+
+fn install_fail2ban(deploy_dir: &Path, opts: Fail2banOptions) -> Result<()> {
+    if !util::is_root() {
+        return Err(failure::permission("Root required to install the fail2ban jail"));
+    }
+    if !Path::new(FAIL2BAN_DIR).exists() {
+        return Err(failure::validation(format!("fail2ban is not installed ({} missing)", FAIL2BAN_DIR)));
+    }
+
+    let env = envfile::load(deploy_dir)?;
+    let log_path = hub_log_path(deploy_dir);
+    let container_name = format!("{}-jupyterhub-1", compose::project_name(deploy_dir, &env));
+    if opts.source == LogSource::File {
+        // fail2ban refuses to start a jail whose log file does not exist yet.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .with_context(|| format!("failed to create {}", log_path))?;
+    }
+    let jail = templates::fail2ban_jail(&JailValues {
+        log_path: (opts.source == LogSource::File).then_some(log_path.as_str()),
+        container_name: &container_name,
+        max_retry: opts.max_retry,
+        find_time: &opts.find_time,
+        ban_time: &opts.ban_time,
+    });
+
+    let root = Path::new(FAIL2BAN_DIR);
+    util::write_string(&root.join(FILTER_FILE), &templates::fail2ban_filter())?;
+    util::write_string(&root.join(JAIL_FILE), &jail)?;
+    let status = Command::new("fail2ban-client")
+        .arg("reload")
+        .status()
+        .context("failed to run fail2ban-client")?;
+    if !status.success() {
+        anyhow::bail!("fail2ban-client reload failed: {}", status);
+    }
+
+    say!(
+        "{}",
+        style(format!(
+            "fail2ban jail mvre-hub installed: {} failed logins within {} ban an IP for {}",
+            opts.max_retry, opts.find_time, opts.ban_time
+        ))
+        .green()
+    );
+    say!("Check bans with: {}", style("fail2ban-client status mvre-hub").cyan());
+    util::set_result(serde_json::json!({
+        "filter": root.join(FILTER_FILE),
+        "jail": root.join(JAIL_FILE),
+    }));
+    Ok(())
+}
+
+/// AppArmor profiles live in the kernel, so the profile has to be loaded on
+/// every boot; copying it to `/etc/apparmor.d` lets the apparmor service do
+/// that.
+fn load_apparmor(deploy_dir: &Path) -> Result<()> {
+    let env = envfile::load(deploy_dir)?;
+    if envfile::get(&env, "APPARMOR_PROFILE").is_none() {
+        return Err(failure::config("The deployment does not use AppArmor; enable it with `mvre-hub reconfigure --apparmor`"));
+    }
+    if !util::is_root() {
+        return Err(failure::permission("Root required to load AppArmor profiles"));
+    }
+    let installed = Path::new(APPARMOR_DIR).join(templates::APPARMOR_PROFILE);
+    util::write_string(&installed, &templates::apparmor_profile())?;
+    let status = Command::new("apparmor_parser")
+        .arg("--replace")
+        .arg(&installed)
+        .status()
+        .context("failed to run apparmor_parser; is AppArmor installed?")?;
+    if !status.success() {
+        anyhow::bail!("apparmor_parser failed: {}", status);
+    }
+
+    say!(
+        "{}",
+        style(format!("AppArmor profile {} loaded", templates::APPARMOR_PROFILE)).green()
+    );
+    util::set_result(serde_json::json!({
+        "profile": templates::APPARMOR_PROFILE,
+        "path": installed,
+    }));
+    Ok(())
+}
+
+fn hub_log_path(deploy_dir: &Path) -> String {
+    let file_name = Path::new(templates::HUB_LOG_FILE)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    deploy_dir
+        .join("jupyterhub_data")
+        .join(file_name)
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,289 @@
+use std::{path::Path, thread, time::Duration};
+
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    bluegreen,
+    cli::HealthOptions,
+    compose,
+    config::AppConfig,
+    engine::{ContainerState, Engine},
+    envfile::{self, EnvMap},
+    http,
+    progress::Spinner,
+    say, services, util,
+};
+
+const DEFAULT_HTTPS_PORT: u16 = 8443;
+const DEFAULT_HTTP_PORT: u16 = 8080;
+const HUB_SERVICE: &str = "jupyterhub";
+const READY_POLL_SECS: u64 = 3;
+
+/// How far a starting hub has got towards taking logins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Readiness {
+    /// The hub container is not running yet, or not answering inside it.
+    Starting(String),
+    /// The hub answers in its container, but not yet through the proxy.
+    HubUp(String),
+    /// The hub answers through the proxy: users can log in.
+    Ready(String),
+}
+
+impl Readiness {
+    pub fn detail(&self) -> &str {
+        match self {
+            Readiness::Starting(detail) | Readiness::HubUp(detail) | Readiness::Ready(detail) => detail,
+        }
+    }
+}
+
+/// What the hub container's state says about a start in progress.
+pub fn container_readiness(hub: Option<&ContainerState>) -> Option<Readiness> {
+    let Some(hub) = hub else {
+        return Some(Readiness::Starting("hub container not created yet".to_string()));
+    };
+    if hub.state != "running" {
+        return Some(Readiness::Starting(format!("hub container {}", hub.state)));
+    }
+    match hub.health.as_str() {
+        "unhealthy" => Some(Readiness::Starting("hub container running but failing its health check".to_string())),
+        _ => None,
+    }
+}
+
+/// Polls the hub, inside its container and through the proxy, until users
+/// can reach it or `timeout` seconds pass. Progress shows on the spinner, or
+/// as a line per change without one.
+pub fn wait_until_ready(deploy_dir: &Path, timeout: u64) -> Result<()> {
+    let env = envfile::load(deploy_dir)?;
+    let engine = Engine::new(deploy_dir);
+    let spinner = Spinner::start("Waiting for the hub");
+    let started = util::unix_now();
+    let mut last: Option<Readiness> = None;
+    loop {
+        let readiness = poll_readiness(deploy_dir, &engine, &env);
+        spinner.set_detail(readiness.detail());
+        if !spinner.is_active() && last.as_ref() != Some(&readiness) {
+            say!("  {}", style(readiness.detail()).dim());
+        }
+        let elapsed = util::unix_now().saturating_sub(started);
+        if let Readiness::Ready(detail) = &readiness {
+            spinner.clear();
+            say!("{} ({}s): {}", style("Hub ready").green(), elapsed, detail);
+            util::set_result(serde_json::json!({ "ready": true, "seconds": elapsed }));
+            return Ok(());
+        }
+        if elapsed >= timeout {
+            spinner.clear();
+            anyhow::bail!(
+                "the hub was not ready after {}s: {}; see mvre-hub health and the hub's logs",
+                timeout,
+                readiness.detail()
+            );
+        }
+        last = Some(readiness);
+        thread::sleep(Duration::from_secs(READY_POLL_SECS));
+    }
+}
+
+fn poll_readiness(deploy_dir: &Path, engine: &Engine, env: &EnvMap) -> Readiness {
+    let containers = engine.ps().unwrap_or_default();
+    let hub = containers.iter().find(|container| container.service == HUB_SERVICE);
+    if let Some(waiting) = container_readiness(hub) {
+        return waiting;
+    }
+    let internal_tls = envfile::get(env, "INTERNAL_TLS") == Some("true");
+    let script = bluegreen::health_script(internal_tls);
+    if engine
+        .compose_output(&["exec", "-T", HUB_SERVICE, "python3", "-c", &script])
+        .is_err()
+    {
+        return Readiness::Starting("hub container running, /hub/health not answering yet".to_string());
+    }
+    let opts = HealthOptions {
+        insecure: true,
+        max_restarts: 0,
+    };
+    match probe_hub(deploy_dir, env, &opts) {
+        Outcome::Pass(detail) => Readiness::Ready(detail),
+        Outcome::Fail(detail) | Outcome::Skip(detail) => {
+            Readiness::HubUp(format!("hub answering, not yet through the proxy: {}", detail))
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", content = "detail", rename_all = "lowercase")]
+pub enum Outcome {
+    Pass(String),
+    Fail(String),
+    Skip(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+pub fn run(opts: HealthOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let checks = collect(&deploy_dir, &opts)?;
+
+    say!("{}", style("Health").cyan().bold());
+    for check in &checks {
+        let (marker, detail) = match &check.outcome {
+            Outcome::Pass(detail) => (style("ok").green(), detail),
+            Outcome::Fail(detail) => (style("FAIL").red().bold(), detail),
+            Outcome::Skip(detail) => (style("skip").dim(), detail),
+        };
+        say!("  {:<16} {:<5} {}", check.name, marker, detail);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| matches!(check.outcome, Outcome::Fail(_)))
+        .count();
+    util::set_result(serde_json::json!({ "checks": checks }));
+    if failed > 0 {
+        anyhow::bail!("{} health check(s) failed", failed);
+    }
+    Ok(())
+}
+
+pub fn collect(deploy_dir: &Path, opts: &HealthOptions) -> Result<Vec<Check>> {
+    let env = envfile::load(deploy_dir)?;
+    let engine = Engine::new(deploy_dir);
+
+    Ok(vec![
+        Check {
+            name: "hub endpoint",
+            outcome: probe_hub(deploy_dir, &env, opts),
+        },
+        Check {
+            name: "traefik ping",
+            outcome: probe_traefik(&engine),
+        },
+        Check {
+            name: "postgres",
+            outcome: probe_postgres(&engine, &env),
+        },
+        Check {
+            name: "restarts",
+            outcome: check_restarts(&engine, opts.max_restarts),
+        },
+    ])
+}
+
+pub fn probe_hub(deploy_dir: &Path, env: &EnvMap, opts: &HealthOptions) -> Outcome {
+    let Some(domain) = envfile::get(env, "HUB_DOMAIN") else {
+        return Outcome::Fail("HUB_DOMAIN missing from .env".to_string());
+    };
+
+    // `--tls off` serves plain HTTP on the proxy's port 80.
+    let (scheme, proxy_port, default_port) = if envfile::get(env, "TLS_MODE") == Some("off") {
+        ("http", 80, DEFAULT_HTTP_PORT)
+    } else {
+        ("https", 443, DEFAULT_HTTPS_PORT)
+    };
+    let public_url = format!("{}://{}/hub/health", scheme, domain);
+    // Staging and self-signed certificates chain to a root curl does not trust.
+    let untrusted =
+        envfile::get(env, "ACME_STAGING") == Some("true") || envfile::get(env, "TLS_MODE") == Some("self-signed");
+    let public = http::get(
+        &public_url,
+        &http::Options {
+            insecure: untrusted,
+            ..http::Options::default()
+        },
+    );
+    match &public {
+        Ok(response) if response.is_success() => {
+            return Outcome::Pass(format!("{} ({})", public_url, response.status));
+        }
+        _ if !opts.insecure => return Outcome::Fail(describe(&public_url, &public)),
+        _ => {}
+    }
+
+    let port = compose::load(deploy_dir)
+        .ok()
+        .and_then(|file| file.services.get("traefik").and_then(|svc| svc.published_port(proxy_port)))
+        .unwrap_or(default_port);
+    let local_url = format!("{}://{}:{}/hub/health", scheme, domain, port);
+    let local = http::get(
+        &local_url,
+        &http::Options {
+            insecure: true,
+            resolve: Some(format!("{}:{}:127.0.0.1", domain, port)),
+            ..http::Options::default()
+        },
+    );
+    match &local {
+        Ok(response) if response.is_success() => Outcome::Pass(format!(
+            "{} ({}, local fallback; public probe: {})",
+            local_url,
+            response.status,
+            describe(&public_url, &public)
+        )),
+        _ => Outcome::Fail(format!(
+            "{}; local fallback: {}",
+            describe(&public_url, &public),
+            describe(&local_url, &local)
+        )),
+    }
+}
+
+fn probe_traefik(engine: &Engine) -> Outcome {
+    match engine.compose_output(&["exec", "-T", "traefik", "traefik", "healthcheck", "--ping"]) {
+        Ok(_) => Outcome::Pass("ping endpoint responding".to_string()),
+        Err(err) => Outcome::Fail(err.to_string()),
+    }
+}
+
+fn probe_postgres(engine: &Engine, env: &EnvMap) -> Outcome {
+    if envfile::get(env, "ENABLE_POSTGRES") != Some("true") {
+        return Outcome::Skip("production profile disabled".to_string());
+    }
+    let user = envfile::get(env, "DB_USER").unwrap_or("postgres");
+    let db = envfile::get(env, "DB_NAME").unwrap_or("postgres");
+    match engine.compose_output(&["exec", "-T", "postgres", "pg_isready", "-U", user, "-d", db]) {
+        Ok(_) => Outcome::Pass(format!("accepting connections for {}", db)),
+        Err(err) => Outcome::Fail(err.to_string()),
+    }
+}
+
+fn check_restarts(engine: &Engine, max_restarts: u64) -> Outcome {
+    let containers = match engine.ps() {
+        Ok(containers) => containers,
+        Err(err) => return Outcome::Fail(err.to_string()),
+    };
+    let names: Vec<&str> = containers.iter().map(|c| c.name.as_str()).collect();
+    let details = match engine.inspect(&names) {
+        Ok(details) => details,
+        Err(err) => return Outcome::Fail(err.to_string()),
+    };
+
+    let summary = details
+        .iter()
+        .map(|d| format!("{}={}", d.name.trim_start_matches('/'), d.restart_count))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if details.iter().any(|d| d.restart_count > max_restarts) {
+        Outcome::Fail(format!("more than {} restarts: {}", max_restarts, summary))
+    } else if summary.is_empty() {
+        Outcome::Fail("no containers running".to_string())
+    } else {
+        Outcome::Pass(summary)
+    }
+}
+
+fn describe(url: &str, result: &Result<http::Response>) -> String {
+    match result {
+        Ok(response) => format!("{} returned {}", url, response.status),
+        Err(err) => err.to_string(),
+    }
+}
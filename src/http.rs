@@ -0,0 +1,146 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+
+use crate::failure;
+
+/// Options for a single HTTP request made through `curl`.
+///
+/// Headers and bodies are handed to curl on stdin via `--config -` so tokens
+/// never show up in the process list.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub insecure: bool,
+    pub timeout_secs: u64,
+    pub headers: Vec<String>,
+    /// `host:port:address` override, as accepted by `curl --resolve`.
+    pub resolve: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            insecure: false,
+            timeout_secs: 10,
+            headers: Vec::new(),
+            resolve: None,
+        }
+    }
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+pub fn get(url: &str, opts: &Options) -> Result<Response> {
+    request("GET", url, None, opts)
+}
+
+pub fn request(method: &str, url: &str, body: Option<&str>, opts: &Options) -> Result<Response> {
+    send(method, url, body.map(|body| ("application/json", body)), opts)
+}
+
+/// Posts `fields` as an `application/x-www-form-urlencoded` body.
+pub fn post_form(url: &str, fields: &[(&str, &str)], opts: &Options) -> Result<Response> {
+    let body = fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", url_encode(key), url_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    send("POST", url, Some(("application/x-www-form-urlencoded", &body)), opts)
+}
+
+/// The `curl --config` text for a request; `body` is its content type and
+/// data.
+pub fn curl_config(method: &str, url: &str, body: Option<(&str, &str)>, opts: &Options) -> String {
+    let mut config = String::new();
+    config.push_str(&format!("url = {}\n", quote(url)));
+    config.push_str(&format!("request = {}\n", quote(method)));
+    config.push_str(&format!("max-time = {}\n", opts.timeout_secs));
+    for header in &opts.headers {
+        config.push_str(&format!("header = {}\n", quote(header)));
+    }
+    if let Some(resolve) = &opts.resolve {
+        config.push_str(&format!("resolve = {}\n", quote(resolve)));
+    }
+    if let Some((content_type, body)) = body {
+        config.push_str(&format!("header = {}\n", quote(&format!("Content-Type: {}", content_type))));
+        config.push_str(&format!("data-binary = {}\n", quote(body)));
+    }
+    config
+}
+
+fn send(method: &str, url: &str, body: Option<(&str, &str)>, opts: &Options) -> Result<Response> {
+    let config = curl_config(method, url, body, opts);
+    let mut command = Command::new("curl");
+    command.args(["--silent", "--show-error", "--output", "-", "--write-out", "\n%{http_code}", "--config", "-"]);
+    if opts.insecure {
+        command.arg("--insecure");
+    }
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to invoke curl")?;
+    child
+        .stdin
+        .take()
+        .context("failed to open curl stdin")?
+        .write_all(config.as_bytes())
+        .context("failed to pass request to curl")?;
+    let output = child.wait_with_output().context("failed to wait for curl")?;
+
+    if !output.status.success() {
+        return Err(failure::network(format!(
+            "request to {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let raw = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = raw.rsplit_once('\n').unwrap_or(("", raw.as_ref()));
+    let status = status
+        .trim()
+        .parse()
+        .with_context(|| format!("unexpected curl status output: {}", status))?;
+    Ok(Response {
+        status,
+        body: body.to_string(),
+    })
+}
+
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// Percent-encodes `value` for use in a query string or form body.
+pub fn url_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
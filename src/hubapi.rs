@@ -0,0 +1,333 @@
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{envfile, http, util};
+
+/// Page size for list endpoints; the hub caps pages at its own maximum.
+const PAGE_SIZE: usize = 200;
+
+/// Client for the JupyterHub REST API, authenticated as the `mvre-hub-cli`
+/// service whose token is generated at deploy time.
+#[derive(Clone)]
+pub struct HubClient {
+    base_url: String,
+    token: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct User {
+    pub name: String,
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub created: Option<String>,
+    #[serde(default)]
+    pub last_activity: Option<String>,
+    /// `spawn` or `stop` while the default server is changing state.
+    #[serde(default)]
+    pub pending: Option<String>,
+    #[serde(default)]
+    pub servers: BTreeMap<String, Server>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Server {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub ready: bool,
+    #[serde(default)]
+    pub pending: Option<String>,
+    /// Path of the server below the hub's public URL, e.g. `/user/alice/`.
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub started: Option<String>,
+    #[serde(default)]
+    pub last_activity: Option<String>,
+    /// Spawner state; DockerSpawner records the container as `object_name`.
+    #[serde(default)]
+    pub state: Option<serde_json::Value>,
+}
+
+impl Server {
+    pub fn container_name(&self) -> Option<&str> {
+        self.state.as_ref()?.get("object_name")?.as_str()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Group {
+    pub name: String,
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, serde_json::Value>,
+}
+
+/// An API token as listed by the hub; the secret itself is only returned
+/// once, on creation.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Token {
+    pub id: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub created: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    #[serde(default)]
+    pub last_activity: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewToken {
+    pub id: String,
+    pub token: String,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+/// Parameters of a new token. Without scopes the token gets the owner's
+/// default (`inherit`) permissions.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TokenRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<u64>,
+    pub note: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProxyRoute {
+    pub routespec: String,
+    pub target: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// The owner of the client's token (`GET /user`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Identity {
+    pub kind: String,
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HubVersion {
+    pub version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenList {
+    api_tokens: Vec<Token>,
+}
+
+impl fmt::Debug for HubClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HubClient")
+            .field("base_url", &self.base_url)
+            .field("token", &"<redacted>")
+            .finish()
+    }
+}
+
+impl HubClient {
+    pub fn new(base_url: &str, token: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    pub fn from_deployment(deploy_dir: &Path) -> Result<Self> {
+        let env = envfile::load(deploy_dir)?;
+        let domain = envfile::get(&env, "HUB_DOMAIN").context("HUB_DOMAIN missing from .env")?;
+        let token = envfile::get(&env, "HUB_API_TOKEN")
+            .context("HUB_API_TOKEN missing from .env; redeploy to enable hub API access")?;
+        Ok(Self::new(&format!("https://{}/hub/api", domain), token))
+    }
+
+    /// The same hub, authenticated with another token.
+    pub fn with_token(&self, token: &str) -> Self {
+        Self::new(&self.base_url, token)
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn version(&self) -> Result<HubVersion> {
+        self.send_json("GET", "/", None)
+    }
+
+    pub fn whoami(&self) -> Result<Identity> {
+        self.send_json("GET", "/user", None)
+    }
+
+    /// Every user, fetched page by page.
+    pub fn list_users(&self) -> Result<Vec<User>> {
+        self.list_paged("/users")
+    }
+
+    pub fn get_user(&self, name: &str) -> Result<User> {
+        self.send_json("GET", &user_path(name, ""), None)
+    }
+
+    pub fn create_user(&self, name: &str) -> Result<()> {
+        self.send("POST", &user_path(name, ""), None)?;
+        Ok(())
+    }
+
+    pub fn delete_user(&self, name: &str) -> Result<()> {
+        self.send("DELETE", &user_path(name, ""), None)?;
+        Ok(())
+    }
+
+    /// Requests a server (`""` for the default one); the hub may still be
+    /// spawning it when this returns.
+    pub fn start_server(&self, name: &str, server: &str) -> Result<()> {
+        self.send("POST", &server_path(name, server), None)?;
+        Ok(())
+    }
+
+    pub fn stop_server(&self, name: &str, server: &str) -> Result<()> {
+        self.send("DELETE", &server_path(name, server), None)?;
+        Ok(())
+    }
+
+    pub fn list_groups(&self) -> Result<Vec<Group>> {
+        self.list_paged("/groups")
+    }
+
+    pub fn get_group(&self, name: &str) -> Result<Group> {
+        self.send_json("GET", &group_path(name, ""), None)
+    }
+
+    pub fn create_group(&self, name: &str) -> Result<()> {
+        self.send("POST", &group_path(name, ""), None)?;
+        Ok(())
+    }
+
+    pub fn delete_group(&self, name: &str) -> Result<()> {
+        self.send("DELETE", &group_path(name, ""), None)?;
+        Ok(())
+    }
+
+    pub fn add_group_users(&self, group: &str, users: &[String]) -> Result<()> {
+        self.send("POST", &group_path(group, "/users"), Some(&json!({ "users": users })))?;
+        Ok(())
+    }
+
+    pub fn remove_group_users(&self, group: &str, users: &[String]) -> Result<()> {
+        self.send("DELETE", &group_path(group, "/users"), Some(&json!({ "users": users })))?;
+        Ok(())
+    }
+
+    pub fn list_tokens(&self, name: &str) -> Result<Vec<Token>> {
+        let list: TokenList = self.send_json("GET", &user_path(name, "/tokens"), None)?;
+        Ok(list.api_tokens)
+    }
+
+    pub fn request_token(&self, name: &str, request: &TokenRequest) -> Result<NewToken> {
+        let body = serde_json::to_value(request).context("failed to serialize token request")?;
+        self.send_json("POST", &user_path(name, "/tokens"), Some(&body))
+    }
+
+    /// Issues an API token for `name` that the hub expires after `expires_in` seconds.
+    pub fn create_token(&self, name: &str, expires_in: u64, note: &str) -> Result<String> {
+        let request = TokenRequest {
+            expires_in: Some(expires_in),
+            note: note.to_string(),
+            scopes: Vec::new(),
+        };
+        Ok(self.request_token(name, &request)?.token)
+    }
+
+    pub fn revoke_token(&self, name: &str, id: &str) -> Result<()> {
+        self.send("DELETE", &user_path(name, &format!("/tokens/{}", http::url_encode(id))), None)?;
+        Ok(())
+    }
+
+    /// Routes in the proxy's table, keyed by route spec in the response.
+    pub fn proxy_routes(&self) -> Result<Vec<ProxyRoute>> {
+        let routes: BTreeMap<String, ProxyRoute> = self.send_json("GET", "/proxy", None)?;
+        Ok(routes.into_values().collect())
+    }
+
+    fn list_paged<T: DeserializeOwned>(&self, path: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        loop {
+            let page: Vec<T> =
+                self.send_json("GET", &format!("{}?offset={}&limit={}", path, items.len(), PAGE_SIZE), None)?;
+            let full = page.len() >= PAGE_SIZE;
+            items.extend(page);
+            if !full {
+                return Ok(items);
+            }
+        }
+    }
+
+    fn send_json<T: DeserializeOwned>(&self, method: &str, path: &str, body: Option<&serde_json::Value>) -> Result<T> {
+        let raw = self.send(method, path, body)?;
+        serde_json::from_str(&raw).with_context(|| format!("unexpected hub API response for {} {}", method, path))
+    }
+
+    fn send(&self, method: &str, path: &str, body: Option<&serde_json::Value>) -> Result<String> {
+        let url = format!("{}{}", self.base_url, path);
+        let opts = http::Options {
+            headers: vec![format!("Authorization: token {}", self.token)],
+            ..http::Options::default()
+        };
+        let body = body.map(|value| value.to_string());
+        let response = http::request(method, &url, body.as_deref(), &opts)?;
+        if !response.is_success() {
+            anyhow::bail!(
+                "hub API {} {} returned {}: {}",
+                method,
+                path,
+                response.status,
+                response.body.trim()
+            );
+        }
+        Ok(response.body)
+    }
+}
+
+/// API path of a user; names may hold characters such as `@` or `/`.
+pub fn user_path(name: &str, rest: &str) -> String {
+    format!("/users/{}{}", http::url_encode(name), rest)
+}
+
+/// API path of a user's default server, or of a named one.
+pub fn server_path(name: &str, server: &str) -> String {
+    if server.is_empty() {
+        user_path(name, "/server")
+    } else {
+        user_path(name, &format!("/servers/{}", http::url_encode(server)))
+    }
+}
+
+pub fn group_path(name: &str, rest: &str) -> String {
+    format!("/groups/{}{}", http::url_encode(name), rest)
+}
+
+/// Hub API times to whole UTC seconds; `-` when unset.
+pub fn display_time(value: Option<&str>) -> String {
+    match value {
+        Some(value) => util::parse_rfc3339(value)
+            .map(util::format_timestamp)
+            .unwrap_or_else(|| value.to_string()),
+        None => "-".to_string(),
+    }
+}
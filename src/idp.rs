@@ -0,0 +1,148 @@
+use anyhow::Result;
+
+use crate::{
+    cli::{AuthPreset, IdpPreset},
+    envfile::{self, EnvMap},
+    util,
+};
+
+/// An additional identity provider offered next to the primary one.
+#[derive(Debug, Clone)]
+pub struct ExtraIdp {
+    pub preset: IdpPreset,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl AuthPreset {
+    pub const ALL: [AuthPreset; 3] = [AuthPreset::Helmholtz, AuthPreset::Orcid, AuthPreset::Cilogon];
+
+    /// `.env` value and login route, e.g. `orcid`.
+    pub fn name(self) -> &'static str {
+        match self {
+            AuthPreset::Helmholtz => "helmholtz",
+            AuthPreset::Orcid => "orcid",
+            AuthPreset::Cilogon => "cilogon",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AuthPreset::Helmholtz => "Helmholtz AAI",
+            AuthPreset::Orcid => "ORCID",
+            AuthPreset::Cilogon => "CILogon",
+        }
+    }
+
+    /// Whether endpoints come from the generated hub config rather than from
+    /// `--oauth-*-url`.
+    pub fn has_endpoints(self) -> bool {
+        self != AuthPreset::Helmholtz
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+}
+
+impl IdpPreset {
+    pub const ALL: [IdpPreset; 2] = [IdpPreset::Orcid, IdpPreset::Cilogon];
+
+    /// Route and `.env` name, e.g. `orcid`.
+    pub fn name(self) -> &'static str {
+        self.auth().name()
+    }
+
+    /// Name shown on the hub login button.
+    pub fn label(self) -> &'static str {
+        self.auth().label()
+    }
+
+    pub fn client_id_key(self) -> &'static str {
+        match self {
+            IdpPreset::Orcid => "ORCID_CLIENT_ID",
+            IdpPreset::Cilogon => "CILOGON_CLIENT_ID",
+        }
+    }
+
+    pub fn client_secret_key(self) -> &'static str {
+        match self {
+            IdpPreset::Orcid => "ORCID_CLIENT_SECRET",
+            IdpPreset::Cilogon => "CILOGON_CLIENT_SECRET",
+        }
+    }
+
+    fn auth(self) -> AuthPreset {
+        match self {
+            IdpPreset::Orcid => AuthPreset::Orcid,
+            IdpPreset::Cilogon => AuthPreset::Cilogon,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+}
+
+/// Credentials for each requested provider: `MVRE_HUB_<KEY>` from the
+/// environment, then the previous `.env`, then a prompt.
+pub fn collect(requested: &[IdpPreset], previous: Option<&EnvMap>) -> Result<Vec<ExtraIdp>> {
+    let previous_value = |key: &str| previous.and_then(|env| envfile::get(env, key)).map(str::to_string);
+    let from_env = |key: &str| std::env::var(format!("MVRE_HUB_{}", key)).ok().filter(|value| !value.is_empty());
+
+    let mut idps = Vec::new();
+    for &preset in requested {
+        if idps.iter().any(|idp: &ExtraIdp| idp.preset == preset) {
+            continue;
+        }
+        let client_id = match from_env(preset.client_id_key()) {
+            Some(value) => value,
+            None => util::prompt_or_use(
+                previous_value(preset.client_id_key()),
+                &format!("{} client ID", preset.label()),
+                false,
+            )?,
+        };
+        let client_secret = match (from_env(preset.client_secret_key()), previous_value(preset.client_secret_key())) {
+            (Some(value), _) | (None, Some(value)) => value,
+            (None, None) => {
+                let prompt = format!("{} client secret", preset.label());
+                util::ensure_interactive(&prompt, &format!("set MVRE_HUB_{}", preset.client_secret_key()))?;
+                dialoguer::Password::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                    .with_prompt(prompt)
+                    .interact()?
+            }
+        };
+        idps.push(ExtraIdp {
+            preset,
+            client_id,
+            client_secret,
+        });
+    }
+    Ok(idps)
+}
+
+/// Extra providers recorded in a deployment's `.env`.
+pub fn from_env(env: &EnvMap) -> Vec<ExtraIdp> {
+    envfile::get(env, "EXTRA_IDPS")
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|name| IdpPreset::from_name(name.trim()))
+        .map(|preset| ExtraIdp {
+            preset,
+            client_id: envfile::get(env, preset.client_id_key()).unwrap_or_default().to_string(),
+            client_secret: envfile::get(env, preset.client_secret_key()).unwrap_or_default().to_string(),
+        })
+        .collect()
+}
+
+/// OAuth callback URLs to register with each provider.
+pub fn callback_urls(domain: &str, primary: AuthPreset, extra: &[ExtraIdp]) -> Vec<(String, String)> {
+    if extra.is_empty() {
+        return vec![(primary.label().to_string(), format!("https://{}/hub/oauth_callback", domain))];
+    }
+    std::iter::once((primary.label().to_string(), primary.name()))
+        .chain(extra.iter().map(|idp| (idp.preset.label().to_string(), idp.preset.name())))
+        .map(|(label, route)| (label, format!("https://{}/hub/{}/oauth_callback", domain, route)))
+        .collect()
+}
@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Password};
+use serde::Serialize;
+
+use crate::{
+    build,
+    cli::{BuildService, ImageCommand, ImageOptions},
+    config::AppConfig,
+    engine::Engine,
+    envfile::{self, EnvMap},
+    failure, say, services, util,
+};
+
+const PASSWORD_ENV: &str = "MVRE_HUB_REGISTRY_PASSWORD";
+
+/// A built image under its local name and its name in the registry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageRef {
+    pub service: &'static str,
+    pub local: String,
+    pub remote: String,
+}
+
+pub fn run(command: ImageCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let engine = Engine::new(&deploy_dir);
+    let env = envfile::load(&deploy_dir)?;
+    match command {
+        ImageCommand::Push(opts) => {
+            let images = refs(&deploy_dir, &env, &opts)?;
+            login(&engine, &opts)?;
+            for image in &images {
+                engine
+                    .docker_output(&["image", "inspect", &image.local])
+                    .with_context(|| format!("{} has not been built; run mvre-hub build first", image.local))?;
+                engine.docker_output(&["tag", &image.local, &image.remote])?;
+                engine
+                    .docker_step(&["push", &image.remote], &format!("Pushing {}", image.remote))
+                    .with_context(|| format!("failed to push {}", image.remote))?;
+            }
+            say!("{}", style(format!("Pushed {}", names(&images))).green());
+            util::set_result(serde_json::json!({ "pushed": images }));
+        }
+        ImageCommand::Pull(opts) => {
+            let images = refs(&deploy_dir, &env, &opts)?;
+            login(&engine, &opts)?;
+            for image in &images {
+                engine
+                    .docker_step(&["pull", &image.remote], &format!("Pulling {}", image.remote))
+                    .with_context(|| format!("failed to pull {}", image.remote))?;
+                engine.docker_output(&["tag", &image.remote, &image.local])?;
+            }
+            // The pulled images stand in for a local build, so start does not
+            // rebuild them.
+            let services: Vec<&str> = images.iter().map(|image| image.service).collect();
+            build::record(&deploy_dir, &services)?;
+            say!("{}", style(format!("Pulled {}", names(&images))).green());
+            util::set_result(serde_json::json!({ "pulled": images }));
+        }
+    }
+    Ok(())
+}
+
+/// The images `opts` selects. The hub image carries the compose project in
+/// its local name; in the registry both have fixed names, so hosts with
+/// different projects share them.
+pub fn refs(deploy_dir: &Path, env: &EnvMap, opts: &ImageOptions) -> Result<Vec<ImageRef>> {
+    let registry = opts.registry.trim_end_matches('/');
+    if registry.is_empty() || registry.contains("://") {
+        return Err(failure::validation(format!(
+            "--registry '{}' must be a registry host with an optional namespace, such as registry.example.org/mvre",
+            opts.registry
+        )));
+    }
+    let selected = match opts.service {
+        Some(service) => vec![service],
+        None => vec![BuildService::Hub, BuildService::User],
+    };
+    Ok(selected
+        .into_iter()
+        .map(|service| {
+            let name = match service {
+                BuildService::Hub => "mvre-hub",
+                BuildService::User => "mvre-user",
+            };
+            ImageRef {
+                service: service.compose_name(),
+                local: build::local_image(deploy_dir, env, service),
+                remote: format!("{}/{}:{}", registry, name, opts.tag),
+            }
+        })
+        .collect())
+}
+
+/// Logs in to the registry when a user is given; otherwise docker's stored
+/// credentials are used.
+fn login(engine: &Engine, opts: &ImageOptions) -> Result<()> {
+    let Some(username) = &opts.username else {
+        return Ok(());
+    };
+    let host = opts.registry.split('/').next().unwrap_or_default();
+    let password = match std::env::var(PASSWORD_ENV).ok().filter(|value| !value.is_empty()) {
+        Some(password) => password,
+        None => {
+            let prompt = format!("Password of {} at {}", username, host);
+            util::ensure_interactive(&prompt, &format!("set {}", PASSWORD_ENV))?;
+            Password::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .interact()?
+        }
+    };
+    engine
+        .docker_input(&["login", "--username", username, "--password-stdin", host], password.as_bytes())
+        .with_context(|| format!("failed to log in to {}", host))?;
+    Ok(())
+}
+
+fn names(images: &[ImageRef]) -> String {
+    images.iter().map(|image| image.remote.as_str()).collect::<Vec<_>>().join(", ")
+}
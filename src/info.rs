@@ -0,0 +1,298 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    answers,
+    compose::{self, ComposeFile},
+    config::AppConfig,
+    engine::Remote,
+    envfile::{self, EnvMap},
+    idp,
+    lock::{self, Holder},
+    open, say, services, users, util,
+};
+
+/// Optional services and the `.env` flag that turns each on.
+const OPTIONAL_SERVICES: &[(&str, &str)] = &[
+    ("postgres", "ENABLE_POSTGRES"),
+    ("mlflow", "ENABLE_MLFLOW"),
+    ("dask-gateway", "ENABLE_DASK_GATEWAY"),
+    ("file-browser", "ENABLE_FILE_BROWSER"),
+    ("logging-stack", "ENABLE_LOGGING_STACK"),
+    ("publishing", "ENABLE_PUBLISHING"),
+    ("nbgrader", "ENABLE_NBGRADER"),
+];
+
+/// Overview of a deployment as its files describe it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Info {
+    pub deploy_dir: String,
+    pub name: Option<String>,
+    pub project: String,
+    pub domain: Option<String>,
+    pub hub_url: Option<String>,
+    pub tls_mode: Option<String>,
+    pub acme_staging: bool,
+    pub docker: Option<String>,
+    pub auth: Auth,
+    /// Image of each compose service; built services are marked `built`.
+    pub images: BTreeMap<String, String>,
+    pub user_image: Option<String>,
+    pub user_env: Option<String>,
+    pub services: Vec<String>,
+    /// Services the operator's `docker-compose.override.yml` changes; `None`
+    /// without one.
+    pub compose_override: Option<Vec<String>>,
+    pub storage: Vec<(String, String)>,
+    pub production: Vec<(String, String)>,
+    /// The operation that last held the deployment lock.
+    pub last_operation: Option<Holder>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Auth {
+    pub provider: Option<String>,
+    pub extra_providers: Vec<String>,
+    pub dummy: bool,
+    pub admin_users: Vec<String>,
+    /// Empty when everyone the provider admits may log in.
+    pub allowed_users: Vec<String>,
+    pub required_entitlements: Option<String>,
+}
+
+pub fn run(app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let env = envfile::load(&deploy_dir)?;
+    let compose = compose::load(&deploy_dir).ok();
+    let mut info = gather(&deploy_dir, &env, compose.as_ref());
+    let absolute = std::path::absolute(&deploy_dir).unwrap_or_else(|_| deploy_dir.clone());
+    info.name = app_config.deployment_name(&absolute).map(str::to_string);
+    info.last_operation = lock::holder(&deploy_dir);
+    print(&info);
+    util::set_result(serde_json::to_value(&info)?);
+    Ok(())
+}
+
+/// Reads the overview from the `.env` and compose file of `deploy_dir`; the
+/// name and last operation are left for the caller.
+pub fn gather(deploy_dir: &Path, env: &EnvMap, compose: Option<&ComposeFile>) -> Info {
+    let get = |key: &str| envfile::get(env, key).filter(|value| !value.is_empty()).map(str::to_string);
+    let flag = |key: &str| envfile::get(env, key) == Some("true");
+    let on_off = |key: &str| if flag(key) { "on" } else { "off" }.to_string();
+
+    let images = compose
+        .map(|file| {
+            file.services
+                .iter()
+                .map(|(name, service)| {
+                    let image = match (&service.image, &service.build) {
+                        (Some(image), None) => image.clone(),
+                        (Some(image), Some(_)) => format!("{} (built)", image),
+                        (None, _) => "built".to_string(),
+                    };
+                    (name.clone(), image)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut services: Vec<String> = OPTIONAL_SERVICES
+        .iter()
+        .filter(|(_, key)| flag(key))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    if let Some(landing) = get("LANDING") {
+        services.push(format!("landing ({})", landing));
+    }
+    services.extend(
+        users::split_list(&get("EXTRA_SERVICES").unwrap_or_default())
+            .into_iter()
+            .map(|name| format!("{} (extra)", name)),
+    );
+
+    let mut storage = Vec::new();
+    let mut mount = |label: &str, host_key: &str, mount_key: &str| {
+        if let (Some(host), Some(mount)) = (get(host_key), get(mount_key)) {
+            storage.push((label.to_string(), format!("{} -> {}", host, mount)));
+        }
+    };
+    mount("Dataset", "DATASET_HOST_PATH", "DATASET_MOUNT_PATH");
+    mount("Shared", "SHARED_HOST_PATH", "SHARED_MOUNT_PATH");
+    let groups = answers::parse_groups(
+        &get("GROUP_MEMBERS").unwrap_or_default(),
+        &get("GROUP_SHARES").unwrap_or_default(),
+    );
+    if let Ok(groups) = groups {
+        for group in groups {
+            if let Some(shared) = group.shared {
+                let mount = format!("{}/{}", answers::GROUP_MOUNT_ROOT, group.name);
+                storage.push((format!("Group {}", group.name), format!("{} -> {}", shared, mount)));
+            }
+        }
+    }
+    let backend = get("WORKSPACE_BACKEND").unwrap_or_else(|| "volume".to_string());
+    let workspaces = match (get("WORKSPACE_ROOT"), get("WORKSPACE_QUOTA")) {
+        (Some(root), Some(quota)) => format!("{} at {} (quota {})", backend, root, quota),
+        (Some(root), None) => format!("{} at {}", backend, root),
+        _ => backend,
+    };
+    storage.push(("Workspaces".to_string(), workspaces));
+    if let Some(scratch) = get("SCRATCH_ROOT") {
+        storage.push(("Scratch".to_string(), scratch));
+    }
+    if flag("ENABLE_MLFLOW") {
+        if let Some(artifacts) = get("MLFLOW_ARTIFACTS") {
+            storage.push(("MLflow artifacts".to_string(), artifacts));
+        }
+    }
+    if flag("ENABLE_POSTGRES") {
+        let database = format!(
+            "{}:{}/{}",
+            get("DB_HOST").unwrap_or_default(),
+            get("DB_PORT").unwrap_or_default(),
+            get("DB_NAME").unwrap_or_default()
+        );
+        storage.push(("Database".to_string(), database));
+    }
+
+    let mut limits = match (get("CPU_LIMIT"), get("MEM_LIMIT")) {
+        (None, None) => "none".to_string(),
+        (cpu, mem) => format!(
+            "{} CPU, {} memory",
+            cpu.unwrap_or_else(|| "any".to_string()),
+            mem.unwrap_or_else(|| "any".to_string())
+        ),
+    };
+    let overrides = ["USER_LIMITS", "GROUP_LIMITS"]
+        .iter()
+        .map(|key| get(key).map_or(0, |value| value.split(',').filter(|entry| !entry.is_empty()).count()))
+        .sum::<usize>();
+    if overrides > 0 {
+        limits.push_str(&format!(" ({} overrides)", overrides));
+    }
+    let production = vec![
+        ("Postgres".to_string(), on_off("ENABLE_POSTGRES")),
+        ("Internal TLS".to_string(), on_off("INTERNAL_TLS")),
+        ("Security headers".to_string(), on_off("SECURITY_HEADERS")),
+        ("Rate limit".to_string(), get("RATE_LIMIT").unwrap_or_else(|| "off".to_string())),
+        ("Spawn limit".to_string(), get("CONCURRENT_SPAWN_LIMIT").unwrap_or_else(|| "off".to_string())),
+        ("SELinux labels".to_string(), on_off("SELINUX_LABELS")),
+        ("Sandbox profiles".to_string(), on_off("SANDBOX_PROFILES")),
+        ("AppArmor".to_string(), get("APPARMOR_PROFILE").unwrap_or_else(|| "off".to_string())),
+        ("Resource limits".to_string(), limits),
+        (
+            "Idle culling".to_string(),
+            get("CULL_TIMEOUT")
+                .map(|secs| format!("after {}s", secs))
+                .unwrap_or_else(|| "off".to_string()),
+        ),
+    ];
+
+    Info {
+        deploy_dir: util::path_display(deploy_dir),
+        name: None,
+        project: compose::project_name(deploy_dir, env),
+        hub_url: open::access(env).ok().map(|access| access.hub_url),
+        domain: get("HUB_DOMAIN"),
+        tls_mode: get("TLS_MODE"),
+        acme_staging: flag("ACME_STAGING"),
+        docker: Remote::from_env(env).describe(),
+        auth: Auth {
+            provider: get("OAUTH_PRESET"),
+            extra_providers: idp::from_env(env).iter().map(|idp| idp.preset.name().to_string()).collect(),
+            dummy: flag("ALLOW_DUMMY_AUTH"),
+            admin_users: users::split_list(&get("ADMIN_USERS").unwrap_or_default()),
+            allowed_users: users::split_list(&get("ALLOWED_USERS").unwrap_or_default()),
+            required_entitlements: get("REQUIRED_ENTITLEMENTS"),
+        },
+        images,
+        user_image: get("USER_IMAGE"),
+        user_env: get("USER_ENV_REPO").map(|repo| match get("USER_ENV_REF") {
+            Some(reference) => format!("{}@{}", repo, reference),
+            None => repo,
+        }),
+        services,
+        compose_override: compose::load_override(deploy_dir)
+            .map(|file| file.map(|file| file.services.into_keys().collect()))
+            .unwrap_or_else(|_| Some(Vec::new())),
+        storage,
+        production,
+        last_operation: None,
+    }
+}
+
+fn print(info: &Info) {
+    let row = |label: &str, value: &str| say!("  {:<18} {}", format!("{}:", label), value);
+    let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let list = |values: &[String]| if values.is_empty() { "-".to_string() } else { values.join(", ") };
+
+    say!("{}", style("Deployment").cyan().bold());
+    row("Directory", &info.deploy_dir);
+    if let Some(name) = &info.name {
+        row("Name", name);
+    }
+    row("Project", &info.project);
+    row("Domain", &or_none(&info.domain));
+    row("URL", &or_none(&info.hub_url));
+    let tls = match (&info.tls_mode, info.acme_staging) {
+        (Some(mode), true) => format!("{} (staging)", mode),
+        (mode, _) => or_none(mode),
+    };
+    row("TLS", &tls);
+    row("Docker", info.docker.as_deref().unwrap_or("local"));
+    if let Some(services) = &info.compose_override {
+        let changes = if services.is_empty() { String::new() } else { format!(" ({})", services.join(", ")) };
+        row("Override", &format!("{}{}", compose::OVERRIDE_FILE, changes));
+    }
+
+    say!("{}", style("Authentication").cyan().bold());
+    let provider = if info.auth.dummy {
+        "dummy (shared password)".to_string()
+    } else {
+        or_none(&info.auth.provider)
+    };
+    row("Provider", &provider);
+    if !info.auth.extra_providers.is_empty() {
+        row("Extra providers", &info.auth.extra_providers.join(", "));
+    }
+    row("Admins", &list(&info.auth.admin_users));
+    if info.auth.allowed_users.is_empty() {
+        row("Allowed users", "anyone the provider admits");
+    } else {
+        row("Allowed users", &format!("{} listed", info.auth.allowed_users.len()));
+    }
+    if let Some(entitlements) = &info.auth.required_entitlements {
+        row("Entitlements", entitlements);
+    }
+
+    say!("{}", style("Images").cyan().bold());
+    row("User image", &or_none(&info.user_image));
+    if let Some(user_env) = &info.user_env {
+        row("User environment", user_env);
+    }
+    for (service, image) in &info.images {
+        row(service, image);
+    }
+
+    say!("{}", style("Services").cyan().bold());
+    row("Optional", &list(&info.services));
+
+    say!("{}", style("Storage").cyan().bold());
+    for (label, value) in &info.storage {
+        row(label, value);
+    }
+
+    say!("{}", style("Production").cyan().bold());
+    for (label, value) in &info.production {
+        row(label, value);
+    }
+
+    if let Some(holder) = &info.last_operation {
+        say!("{}", style("Last operation").cyan().bold());
+        row("Command", &holder.command);
+        row("By", &format!("{} at {}", holder.user, holder.started));
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{cli::InitKind, failure, launchd, openrc, runit, systemd, taskscheduler};
+
+/// A service manager that can start the deployment at boot.
+pub trait InitSystem {
+    fn name(&self) -> &'static str;
+    /// Whether installing needs root; user-level managers don't.
+    fn needs_root(&self) -> bool;
+    fn install_service(&self, deploy_dir: &Path) -> Result<()>;
+    fn remove_service(&self) -> Result<()>;
+    /// `None` when the service is not installed under this manager.
+    fn service_state(&self) -> Result<Option<ServiceState>>;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceState {
+    pub enabled: bool,
+    pub running: bool,
+    pub detail: String,
+}
+
+pub struct Systemd(pub systemd::Scope);
+
+impl InitSystem for Systemd {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            systemd::Scope::System => "systemd",
+            systemd::Scope::User => "systemd user unit",
+        }
+    }
+
+    fn needs_root(&self) -> bool {
+        self.0 == systemd::Scope::System
+    }
+
+    fn install_service(&self, deploy_dir: &Path) -> Result<()> {
+        systemd::install_service(deploy_dir, self.0)
+    }
+
+    fn remove_service(&self) -> Result<()> {
+        systemd::remove_service()
+    }
+
+    fn service_state(&self) -> Result<Option<ServiceState>> {
+        Ok(systemd::service_state()?.map(|state| ServiceState {
+            enabled: state.unit_file_state == "enabled",
+            running: state.active_state == "active",
+            detail: match state.scope {
+                systemd::Scope::System => format!("{} ({})", state.unit_file_state, state.active_state),
+                systemd::Scope::User => format!("{} ({}, user unit)", state.unit_file_state, state.active_state),
+            },
+        }))
+    }
+}
+
+/// Picks the manager for `kind`, probing the host when `kind` is `Auto`.
+pub fn select(kind: InitKind, user: bool) -> Result<Box<dyn InitSystem>> {
+    let kind = match kind {
+        InitKind::Auto => detect(),
+        other => other,
+    };
+    if user && kind != InitKind::Systemd {
+        return Err(failure::validation("--user is only supported with systemd"));
+    }
+    Ok(match kind {
+        InitKind::Systemd if user => Box::new(Systemd(systemd::Scope::User)),
+        InitKind::Systemd | InitKind::Auto => Box::new(Systemd(systemd::Scope::System)),
+        InitKind::Openrc => Box::new(openrc::OpenRc),
+        InitKind::Runit => Box::new(runit::Runit),
+        InitKind::Launchd => Box::new(launchd::Launchd),
+        InitKind::TaskScheduler => Box::new(taskscheduler::TaskScheduler),
+    })
+}
+
+/// launchd on macOS and Task Scheduler on Windows; on Linux, systemd when it
+/// is PID 1, otherwise OpenRC or runit by their runtime directories, falling
+/// back to systemd.
+pub fn detect() -> InitKind {
+    if cfg!(target_os = "macos") {
+        InitKind::Launchd
+    } else if cfg!(windows) {
+        InitKind::TaskScheduler
+    } else if Path::new("/run/systemd/system").exists() {
+        InitKind::Systemd
+    } else if Path::new("/run/openrc").exists() || Path::new("/sbin/openrc-run").exists() {
+        InitKind::Openrc
+    } else if Path::new("/run/runit").exists() || Path::new("/etc/runit").exists() {
+        InitKind::Runit
+    } else {
+        InitKind::Systemd
+    }
+}
+
+/// Managers that can exist on this platform.
+fn all() -> Vec<Box<dyn InitSystem>> {
+    if cfg!(target_os = "macos") {
+        vec![Box::new(launchd::Launchd)]
+    } else if cfg!(windows) {
+        vec![Box::new(taskscheduler::TaskScheduler)]
+    } else {
+        vec![
+            Box::new(Systemd(systemd::Scope::System)),
+            Box::new(openrc::OpenRc),
+            Box::new(runit::Runit),
+        ]
+    }
+}
+
+/// The first manager that has the service installed, with its state.
+pub fn installed_service() -> Result<Option<(&'static str, ServiceState)>> {
+    for init in all() {
+        if let Some(state) = init.service_state()? {
+            return Ok(Some((init.name(), state)));
+        }
+    }
+    Ok(None)
+}
+
+/// Removes the service from every manager that has it; errors are collected
+/// so one unreachable manager doesn't block the rest.
+pub fn remove_service() -> Result<()> {
+    let mut errors = Vec::new();
+    for init in all() {
+        if let Ok(Some(_)) = init.service_state() {
+            if let Err(err) = init.remove_service() {
+                errors.push(format!("{}: {}", init.name(), err));
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        anyhow::bail!("failed to remove auto-start service: {}", errors.join("; "))
+    }
+}
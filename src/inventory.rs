@@ -0,0 +1,163 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::BatchOptions, failure, say, util};
+
+pub const INVENTORY_FILE: &str = "inventory.toml";
+
+/// Hosts managed together, e.g. `test`, `shore`, and `ship`.
+///
+/// ```toml
+/// [vars]
+/// MVRE_HUB_ACME_EMAIL = "it@example.org"
+///
+/// [hosts.ship]
+/// address = "admin@ship.example.org"
+/// [hosts.ship.vars]
+/// MVRE_HUB_DOMAIN = "hub.ship.example.org"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Inventory {
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    #[serde(default)]
+    pub hosts: BTreeMap<String, Host>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Host {
+    /// SSH destination, or `local` to run on this machine.
+    pub address: String,
+    /// mvre-hub binary on the target host.
+    #[serde(default = "default_binary")]
+    pub binary: String,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostOutcome {
+    pub host: String,
+    pub success: bool,
+    pub output: String,
+}
+
+fn default_binary() -> String {
+    "mvre-hub".to_string()
+}
+
+pub fn resolve_inventory_path(explicit: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = explicit {
+        return Ok(path.to_path_buf());
+    }
+    let config_path = crate::config::resolve_config_path()?;
+    let dir = config_path.parent().context("config path has no parent directory")?;
+    Ok(dir.join(INVENTORY_FILE))
+}
+
+pub fn load(path: &Path) -> Result<Inventory> {
+    let raw = util::read_to_string(path)?;
+    parse(&raw).with_context(|| format!("failed to parse inventory at {}", path.display()))
+}
+
+pub fn parse(raw: &str) -> Result<Inventory> {
+    let inventory = toml::from_str(raw)?;
+    Ok(inventory)
+}
+
+impl Inventory {
+    /// Selects hosts by name; an empty filter selects every host.
+    pub fn select(&self, names: &[String]) -> Result<Vec<(&String, &Host)>> {
+        if names.is_empty() {
+            return Ok(self.hosts.iter().collect());
+        }
+        names
+            .iter()
+            .map(|name| {
+                self.hosts
+                    .get_key_value(name)
+                    .with_context(|| format!("host '{}' not found in inventory", name))
+            })
+            .collect()
+    }
+
+    /// Global vars overlaid with the host's own overrides.
+    pub fn vars_for(&self, host: &Host) -> BTreeMap<String, String> {
+        let mut vars = self.vars.clone();
+        vars.extend(host.vars.clone());
+        vars
+    }
+}
+
+/// Runs one mvre-hub subcommand on every selected host and prints an aggregate summary.
+pub fn run_batch(inventory_path: Option<&Path>, opts: &BatchOptions, args: &[&str]) -> Result<()> {
+    let path = resolve_inventory_path(inventory_path)?;
+    let inventory = load(&path)?;
+    let hosts = inventory.select(&opts.host)?;
+    if hosts.is_empty() {
+        return Err(failure::validation(format!("Inventory {} lists no hosts", path.display())));
+    }
+
+    let outcomes: Vec<HostOutcome> = hosts
+        .into_iter()
+        .map(|(name, host)| run_on_host(name, host, &inventory.vars_for(host), args))
+        .collect();
+
+    for outcome in &outcomes {
+        let marker = if outcome.success {
+            style("ok").green()
+        } else {
+            style("failed").red()
+        };
+        say!("{} [{}]", style(&outcome.host).cyan().bold(), marker);
+        for line in outcome.output.lines() {
+            say!("  {}", line);
+        }
+    }
+
+    util::set_result(serde_json::json!({ "hosts": outcomes }));
+    let failed = outcomes.iter().filter(|outcome| !outcome.success).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} hosts failed", failed, outcomes.len());
+    }
+    Ok(())
+}
+
+fn run_on_host(name: &str, host: &Host, vars: &BTreeMap<String, String>, args: &[&str]) -> HostOutcome {
+    let mut command = if host.address == "local" {
+        let mut command = Command::new(&host.binary);
+        command.args(args).envs(vars);
+        command
+    } else {
+        let mut command = Command::new("ssh");
+        command.args(["-o", "BatchMode=yes", &host.address, "env"]);
+        command.args(vars.iter().map(|(key, value)| format!("{}={}", key, util::shell_quote(value))));
+        // ssh joins the remote command into one shell line.
+        command.arg(&host.binary).args(args.iter().map(|arg| util::shell_quote(arg)));
+        command
+    };
+
+    match command.output() {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            HostOutcome {
+                host: name.to_string(),
+                success: output.status.success(),
+                output: text,
+            }
+        }
+        Err(err) => HostOutcome {
+            host: name.to_string(),
+            success: false,
+            output: format!("failed to reach host: {}", err),
+        },
+    }
+}
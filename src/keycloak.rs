@@ -0,0 +1,223 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Password};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::{
+    cli::{AuthPreset, KeycloakOptions, OauthCommand},
+    config::AppConfig,
+    deploy::{self, OauthClient},
+    envfile, http, idp, say, services, util,
+};
+
+/// Client for the Keycloak admin REST API, authenticated with a short-lived
+/// admin token.
+struct AdminClient {
+    base_url: String,
+    realm: String,
+    token: String,
+    insecure: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdminToken {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientSummary {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientSecret {
+    value: String,
+}
+
+pub fn run(command: OauthCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        OauthCommand::RegisterKeycloak(opts) => register(&deploy_dir, opts),
+    }
+}
+
+fn register(deploy_dir: &Path, opts: KeycloakOptions) -> Result<()> {
+    let env = envfile::load(deploy_dir)?;
+    let domain = envfile::get(&env, "HUB_DOMAIN").context("HUB_DOMAIN missing from .env")?;
+    let callbacks: Vec<String> = idp::callback_urls(domain, AuthPreset::Helmholtz, &idp::from_env(&env))
+        .into_iter()
+        .map(|(_, url)| url)
+        .collect();
+    // With several providers only the first route belongs to Keycloak.
+    let callback = callbacks[0].clone();
+
+    let password = match opts.admin_password {
+        Some(password) => password,
+        None => {
+            let prompt = format!("Keycloak password for {}", opts.admin_user);
+            util::ensure_interactive(&prompt, "pass --admin-password")?;
+            Password::with_theme(&ColorfulTheme::default()).with_prompt(prompt).interact()?
+        }
+    };
+    let admin = AdminClient::login(&opts.admin_url, &opts.admin_realm, &opts.admin_user, &password, opts.insecure)?
+        .for_realm(&opts.realm);
+
+    let representation = client_representation(&opts.client_id, domain, &callback);
+    let id = match admin.find_client(&opts.client_id)? {
+        Some(id) => {
+            admin.send("PUT", &format!("/clients/{}", id), Some(&representation))?;
+            say!("Updated Keycloak client {}", style(&opts.client_id).bold());
+            id
+        }
+        None => {
+            admin.send("POST", "/clients", Some(&representation))?;
+            say!("Created Keycloak client {}", style(&opts.client_id).bold());
+            admin
+                .find_client(&opts.client_id)?
+                .context("Keycloak did not return the created client")?
+        }
+    };
+    let secret: ClientSecret = admin.send_json("GET", &format!("/clients/{}/client-secret", id), None)?;
+
+    let (authorize_url, token_url, userdata_url) = realm_endpoints(&opts.admin_url, &opts.realm);
+    let changed = deploy::set_oauth_client(
+        deploy_dir,
+        OauthClient {
+            client_id: opts.client_id.clone(),
+            client_secret: secret.value,
+            authorize_url: authorize_url.clone(),
+            token_url,
+            userdata_url,
+        },
+    )?;
+
+    say!("  {:<14} {}", "Callback URL", style(&callback).dim());
+    say!("  {:<14} {}", "Authorize URL", style(&authorize_url).dim());
+    if changed {
+        say!("{}", style("Client credentials written to the deployment").green());
+        say!("Apply with: {}", style("mvre-hub start").cyan());
+    } else {
+        say!("{}", style("Configuration unchanged").dim());
+    }
+    util::set_result(json!({
+        "client_id": opts.client_id,
+        "realm": opts.realm,
+        "callback_url": callback,
+        "changed": changed,
+    }));
+    Ok(())
+}
+
+/// Confidential OpenID Connect client with the standard flow only, allowed
+/// to redirect to the hub's callback.
+pub fn client_representation(client_id: &str, domain: &str, callback: &str) -> serde_json::Value {
+    json!({
+        "clientId": client_id,
+        "name": "MVRE Hub",
+        "protocol": "openid-connect",
+        "enabled": true,
+        "publicClient": false,
+        "clientAuthenticatorType": "client-secret",
+        "standardFlowEnabled": true,
+        "directAccessGrantsEnabled": false,
+        "implicitFlowEnabled": false,
+        "serviceAccountsEnabled": false,
+        "rootUrl": format!("https://{}", domain),
+        "redirectUris": [callback],
+        "webOrigins": [format!("https://{}", domain)],
+    })
+}
+
+/// Authorize, token, and userinfo endpoints of `realm`.
+pub fn realm_endpoints(base_url: &str, realm: &str) -> (String, String, String) {
+    let base = format!(
+        "{}/realms/{}/protocol/openid-connect",
+        base_url.trim_end_matches('/'),
+        realm
+    );
+    (
+        format!("{}/auth", base),
+        format!("{}/token", base),
+        format!("{}/userinfo", base),
+    )
+}
+
+impl AdminClient {
+    fn login(base_url: &str, admin_realm: &str, user: &str, password: &str, insecure: bool) -> Result<Self> {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        let url = format!("{}/realms/{}/protocol/openid-connect/token", base_url, admin_realm);
+        let opts = http::Options {
+            insecure,
+            ..http::Options::default()
+        };
+        let response = http::post_form(
+            &url,
+            &[
+                ("grant_type", "password"),
+                ("client_id", "admin-cli"),
+                ("username", user),
+                ("password", password),
+            ],
+            &opts,
+        )?;
+        if !response.is_success() {
+            anyhow::bail!(
+                "Keycloak login as {} failed ({}): {}",
+                user,
+                response.status,
+                response.body.trim()
+            );
+        }
+        let token: AdminToken =
+            serde_json::from_str(&response.body).context("unexpected Keycloak token response")?;
+        Ok(Self {
+            base_url,
+            realm: String::new(),
+            token: token.access_token,
+            insecure,
+        })
+    }
+
+    fn for_realm(self, realm: &str) -> Self {
+        Self {
+            realm: realm.to_string(),
+            ..self
+        }
+    }
+
+    /// Internal id of the client with `client_id`, if it exists.
+    fn find_client(&self, client_id: &str) -> Result<Option<String>> {
+        let path = format!("/clients?clientId={}", http::url_encode(client_id));
+        let clients: Vec<ClientSummary> = self.send_json("GET", &path, None)?;
+        Ok(clients.into_iter().next().map(|client| client.id))
+    }
+
+    fn send_json<T: DeserializeOwned>(&self, method: &str, path: &str, body: Option<&serde_json::Value>) -> Result<T> {
+        let raw = self.send(method, path, body)?;
+        serde_json::from_str(&raw).with_context(|| format!("unexpected Keycloak response for {} {}", method, path))
+    }
+
+    fn send(&self, method: &str, path: &str, body: Option<&serde_json::Value>) -> Result<String> {
+        let url = format!("{}/admin/realms/{}{}", self.base_url, self.realm, path);
+        let opts = http::Options {
+            insecure: self.insecure,
+            headers: vec![format!("Authorization: Bearer {}", self.token)],
+            ..http::Options::default()
+        };
+        let body = body.map(|value| value.to_string());
+        let response = http::request(method, &url, body.as_deref(), &opts)?;
+        if !response.is_success() {
+            anyhow::bail!(
+                "Keycloak {} {} returned {}: {}",
+                method,
+                path,
+                response.status,
+                response.body.trim()
+            );
+        }
+        Ok(response.body)
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use crate::cli::LandingPath;
+
+/// Generated site and nginx config of the `landing` service.
+pub const LANDING_DIR: &str = "landing";
+pub const GENERATED_SITE: &str = "landing/site";
+/// `LANDING_SITE` of the generated page, relative to the deployment.
+pub const GENERATED_SITE_MOUNT: &str = "./landing/site";
+
+/// What the generated onboarding page says about this hub.
+pub struct LandingPage<'a> {
+    pub title: &'a str,
+    pub domain: &'a str,
+    pub dataset_mount: &'a str,
+    pub support_email: Option<&'a str>,
+    pub data_citation: Option<&'a str>,
+}
+
+impl LandingPath {
+    /// `.env` value.
+    pub fn name(self) -> &'static str {
+        match self {
+            LandingPath::Root => "root",
+            LandingPath::Docs => "docs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "root" => Some(LandingPath::Root),
+            "docs" => Some(LandingPath::Docs),
+            _ => None,
+        }
+    }
+
+    /// Traefik rule of the landing router. The site always lives under
+    /// `/docs`; at the root it also answers the bare domain, which otherwise
+    /// redirects to the hub.
+    pub fn router_rule(self, domain: &str) -> String {
+        match self {
+            LandingPath::Root => format!("Host(`{}`) && (Path(`/`) || PathPrefix(`/docs`))", domain),
+            LandingPath::Docs => format!("Host(`{}`) && PathPrefix(`/docs`)", domain),
+        }
+    }
+}
+
+/// Files of the landing service; the page is only generated when no site
+/// directory of the operator's own is used.
+pub fn files(deploy_dir: &Path, page: Option<&LandingPage>) -> Vec<(PathBuf, String)> {
+    let mut files = vec![(deploy_dir.join(LANDING_DIR).join("nginx.conf"), nginx_config())];
+    if let Some(page) = page {
+        files.push((deploy_dir.join(GENERATED_SITE).join("index.html"), render_page(page)));
+    }
+    files
+}
+
+pub fn nginx_config() -> String {
+    r#"
+server {
+    listen 80;
+    root /srv/landing;
+
+    location = / {
+        try_files /index.html =404;
+    }
+
+    location /docs/ {
+        alias /srv/landing/;
+        index index.html;
+    }
+
+    location = /docs {
+        return 301 /docs/;
+    }
+}
+"#
+    .trim_start()
+    .to_string()
+}
+
+pub fn render_page(page: &LandingPage) -> String {
+    let title = escape(page.title);
+    let mut sections = vec![format!(
+        r#"  <section>
+    <h2>Getting started</h2>
+    <ol>
+      <li>Open <a href="https://{domain}/hub/">the hub</a> and sign in with your institutional account.</li>
+      <li>Start your server; it opens JupyterLab in your browser.</li>
+      <li>Keep your work in <code>work/</code>, which is kept between sessions.</li>
+      <li>The dataset is mounted read-only at <code>{dataset}</code>.</li>
+    </ol>
+  </section>
+"#,
+        domain = escape(page.domain),
+        dataset = escape(page.dataset_mount),
+    )];
+    if let Some(citation) = page.data_citation {
+        sections.push(format!(
+            "  <section>\n    <h2>Citing the data</h2>\n    <blockquote>{}</blockquote>\n  </section>\n",
+            escape(citation)
+        ));
+    }
+    if let Some(email) = page.support_email {
+        let email = escape(email);
+        sections.push(format!(
+            "  <section>\n    <h2>Support</h2>\n    <p>Questions or problems: <a href=\"mailto:{0}\">{0}</a></p>\n  </section>\n",
+            email
+        ));
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{title}</title>
+  <style>
+    body {{ font-family: system-ui, sans-serif; max-width: 44rem; margin: 3rem auto; padding: 0 1rem; line-height: 1.5; color: #222; }}
+    a.button {{ display: inline-block; padding: 0.6rem 1.2rem; background: #005aa0; color: #fff; border-radius: 4px; text-decoration: none; }}
+    blockquote {{ margin: 0; padding-left: 1rem; border-left: 3px solid #ccc; }}
+  </style>
+</head>
+<body>
+  <h1>{title}</h1>
+  <p><a class="button" href="https://{domain}/hub/">Go to the hub</a></p>
+{sections}</body>
+</html>
+"#,
+        title = title,
+        domain = escape(page.domain),
+        sections = sections.concat(),
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,106 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    failure,
+    init::{InitSystem, ServiceState},
+    util,
+};
+
+const LABEL: &str = "org.mvre.hub";
+
+/// A per-user launch agent, started at login by launchd on macOS.
+pub struct Launchd;
+
+impl InitSystem for Launchd {
+    fn name(&self) -> &'static str {
+        "launchd"
+    }
+
+    fn needs_root(&self) -> bool {
+        false
+    }
+
+    fn install_service(&self, deploy_dir: &Path) -> Result<()> {
+        let path = plist_path()?;
+        util::write_string(&path, &plist(deploy_dir))?;
+        let status = Command::new("launchctl")
+            .arg("load")
+            .arg("-w")
+            .arg(&path)
+            .status()
+            .context("failed to run launchctl load")?;
+        if !status.success() {
+            return Err(failure::permission(format!("launchctl load failed: {}", status)));
+        }
+        Ok(())
+    }
+
+    fn remove_service(&self) -> Result<()> {
+        let path = plist_path()?;
+        if path.exists() {
+            let _ = Command::new("launchctl").arg("unload").arg("-w").arg(&path).status();
+            fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn service_state(&self) -> Result<Option<ServiceState>> {
+        if !plist_path()?.exists() {
+            return Ok(None);
+        }
+        let loaded = Command::new("launchctl")
+            .args(["list", LABEL])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+        Ok(Some(ServiceState {
+            enabled: true,
+            running: loaded,
+            detail: format!("installed ({})", if loaded { "loaded" } else { "not loaded" }),
+        }))
+    }
+}
+
+/// The launch agent: `docker-compose up` at login, logging to the
+/// deployment directory.
+pub fn plist(deploy_dir: &Path) -> String {
+    let compose = deploy_dir.join("docker-compose.yml");
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+<plist version=\"1.0\">\n<dict>\n\
+\t<key>Label</key>\n\t<string>{label}</string>\n\
+\t<key>ProgramArguments</key>\n\t<array>\n\
+\t\t<string>/usr/bin/env</string>\n\t\t<string>docker-compose</string>\n\
+\t\t<string>-f</string>\n\t\t<string>{compose}</string>\n\
+\t\t<string>up</string>\n\t</array>\n\
+\t<key>WorkingDirectory</key>\n\t<string>{dir}</string>\n\
+\t<key>RunAtLoad</key>\n\t<true/>\n\
+\t<key>StandardErrorPath</key>\n\t<string>{dir}/launchd.log</string>\n\
+</dict>\n</plist>\n",
+        label = LABEL,
+        compose = xml_escape(&compose.to_string_lossy()),
+        dir = xml_escape(&deploy_dir.to_string_lossy()),
+    )
+}
+
+fn plist_path() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME is not set")?;
+    Ok(PathBuf::from(home)
+        .join("Library")
+        .join("LaunchAgents")
+        .join(format!("{}.plist", LABEL)))
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -1,10 +1,88 @@
+pub mod access;
+pub mod adopt;
+pub mod announce;
+pub mod answers;
+pub mod audit;
+pub mod backup;
+pub mod bluegreen;
+pub mod branding;
+pub mod build;
+pub mod cert;
 pub mod cli;
+pub mod compose;
 pub mod config;
+pub mod dask;
+pub mod db;
 pub mod deploy;
+pub mod deployments;
+pub mod dev;
+pub mod disk;
+pub mod doctor;
+pub mod engine;
+pub mod envfile;
+pub mod failure;
+pub mod filebrowser;
+pub mod firewall;
+pub mod gitops;
+pub mod graph;
+pub mod guest;
+pub mod hardening;
+pub mod health;
+pub mod http;
+pub mod hubapi;
+pub mod idp;
+pub mod image;
+pub mod info;
+pub mod init;
+pub mod keycloak;
+pub mod inventory;
+pub mod landing;
+pub mod launchd;
+pub mod lock;
+pub mod logconfig;
+pub mod logstack;
+pub mod manifest;
+pub mod merge;
+pub mod mlflow;
+pub mod nbgrader;
+pub mod open;
+pub mod openrc;
+pub mod progress;
+pub mod proxy;
+pub mod prune;
+pub mod publish;
+pub mod report;
+pub mod repository;
+pub mod retry;
+pub mod rootless;
+pub mod runit;
+pub mod runlog;
+pub mod scan;
+pub mod schedule;
+pub mod secrets;
+pub mod selinux;
+pub mod server;
 pub mod services;
+pub mod snapshot;
 pub mod systemd;
+pub mod taskscheduler;
 pub mod templates;
+pub mod tls;
+pub mod token;
+pub mod tenant;
+pub mod top;
+pub mod usage;
+pub mod userenv;
+pub mod users;
 pub mod util;
+pub mod verify;
+pub mod volumes;
+pub mod watch;
+pub mod webhook;
+pub mod workshop;
+pub mod workspace;
+
+use std::path::Path;
 
 use anyhow::Result;
 use clap::Parser;
@@ -12,32 +90,308 @@ use tracing::info;
 
 pub fn run() -> Result<()> {
     let cli = cli::Cli::parse();
+    util::configure_output(cli.quiet, cli.format == cli::OutputFormat::Json, cli.plain);
+    util::configure_ci(cli.ci);
+    lock::configure_timeout(cli.lock_timeout);
+    retry::configure(retry::Policy::from_flags(
+        cli.retries,
+        cli.no_retry,
+        &cli.retry_backoff,
+        &cli.docker_timeouts,
+    )?);
     util::init_logging(cli.verbose);
 
+    let command_name = cli.command.name();
+
+    let audited = cli.command.audit_name();
+    let lifecycle = cli.command.lifecycle_event();
+    let started = util::unix_now();
+    runlog::start(command_name, started);
+    let previous_deploy_dir = cli
+        .deploy_dir
+        .clone()
+        .or_else(|| config::load().ok().and_then(|cfg| cfg.active_dir().map(Path::to_path_buf)));
+    let deploy_dir_override = cli.deploy_dir.clone();
+
+    let result = dispatch(cli);
+
+    if audited.is_some() || lifecycle.is_some() {
+        // Loaded again: the command may have added or switched deployments.
+        let app_config = config::load().ok();
+        let deploy_dir = deploy_dir_override.or_else(|| {
+            app_config
+                .as_ref()
+                .and_then(|cfg| cfg.active_dir().map(Path::to_path_buf))
+                .or(previous_deploy_dir)
+        });
+        if let Some(command) = audited {
+            if let Err(err) = audit::record(command, &result, deploy_dir.as_deref()) {
+                tracing::warn!("failed to write audit log: {:#}", err);
+            }
+        }
+        if let (Some(event), Some(app_config)) = (lifecycle, &app_config) {
+            let error = result.as_ref().err().map(|err| format!("{:#}", err));
+            webhook::notify(app_config, event, error.as_deref(), deploy_dir.as_deref(), started);
+        }
+    }
+
+    if util::json_output() || util::ci() {
+        let mut document = serde_json::json!({
+            "command": command_name,
+            "ok": result.is_ok(),
+        });
+        if let Err(err) = &result {
+            document["error"] = serde_json::Value::String(format!("{:#}", err));
+            let category = failure::classify(err);
+            document["category"] = category.name().into();
+            document["exit_code"] = category.exit_code().into();
+        }
+        if let Some(value) = util::take_result() {
+            document["result"] = value;
+        }
+        println!("{}", document);
+    }
+
+    result
+}
+
+/// Process exit code for a failed command: that of its failure category.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    failure::classify(err).exit_code()
+}
+
+fn dispatch(cli: cli::Cli) -> Result<()> {
     let mut app_config = config::load()?;
+    app_config.deploy_dir_override = cli.deploy_dir.clone();
     let config_path = config::resolve_config_path()?;
+    // Held until the command returns. Without a deployment there is nothing
+    // to lock, and the command reports that itself.
+    let locked_dir = cli
+        .command
+        .locks_deployment()
+        .then(|| services::resolve_deploy_dir(&app_config).ok())
+        .flatten();
+    let _lock = match locked_dir {
+        Some(deploy_dir) => Some(lock::acquire(&deploy_dir, &lock::invocation())?),
+        None => None,
+    };
 
     match cli.command {
         cli::Commands::Deploy { opts } => {
             info!("starting deploy");
             deploy::run(opts, &config_path, &mut app_config)?;
         }
-        cli::Commands::Start => {
+        cli::Commands::Dev { opts } => {
+            info!("starting a local trial deployment");
+            dev::run(opts)?;
+        }
+        cli::Commands::Build { opts } => {
+            info!("building images");
+            build::run(opts, &app_config)?;
+        }
+        cli::Commands::Scan { opts } => {
+            info!("scanning images for vulnerabilities");
+            scan::run(opts, &app_config)?;
+        }
+        cli::Commands::Image { command } => {
+            info!("sharing images through a registry");
+            image::run(command, &app_config)?;
+        }
+        cli::Commands::Start { batch, opts } if batch.is_batch() => {
+            info!("starting services on inventory hosts");
+            let args = opts.to_args();
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            inventory::run_batch(cli.inventory.as_deref(), &batch, &args)?;
+        }
+        cli::Commands::Start { opts, .. } => {
             info!("starting services");
-            services::start(&config_path, &app_config)?;
+            services::start(&opts, &config_path, &app_config)?;
+        }
+        cli::Commands::Stop { batch, opts } if batch.is_batch() => {
+            info!("stopping services on inventory hosts");
+            let args = opts.to_args();
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            inventory::run_batch(cli.inventory.as_deref(), &batch, &args)?;
         }
-        cli::Commands::Stop => {
+        cli::Commands::Stop { opts, .. } => {
             info!("stopping services");
-            services::stop(&config_path, &app_config)?;
+            services::stop(opts, &config_path, &app_config)?;
         }
         cli::Commands::Clean { opts } => {
             info!("cleaning deployment");
             services::clean(opts, &config_path, &app_config)?;
         }
-        cli::Commands::Status => {
+        cli::Commands::Reconfigure { opts } => {
+            info!("reconfiguring deployment");
+            deploy::reconfigure(opts, &app_config)?;
+        }
+        cli::Commands::Plan { opts } => {
+            info!("planning changes from the manifest");
+            manifest::plan(opts, &app_config)?;
+        }
+        cli::Commands::Apply { opts } => {
+            info!("applying the manifest");
+            manifest::apply(opts, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Gitops { opts } => {
+            info!("following the manifest in a git repository");
+            gitops::run(opts, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Upgrade { opts } => {
+            info!("upgrading deployment");
+            services::upgrade(opts, &config_path, &app_config)?;
+        }
+        cli::Commands::Rollback { opts } => {
+            info!("rolling back deployment");
+            snapshot::rollback(opts, &app_config)?;
+        }
+        cli::Commands::Open { opts } => {
+            info!("summarizing access to the hub");
+            open::run(opts, &app_config)?;
+        }
+        cli::Commands::Status { batch } if batch.is_batch() => {
+            info!("checking status on inventory hosts");
+            inventory::run_batch(cli.inventory.as_deref(), &batch, &["status"])?;
+        }
+        cli::Commands::Status { .. } => {
             info!("checking status");
             services::status(&app_config)?;
         }
+        cli::Commands::Info => {
+            info!("summarizing deployment");
+            info::run(&app_config)?;
+        }
+        cli::Commands::Health { opts } => {
+            info!("probing health");
+            health::run(opts, &app_config)?;
+        }
+        cli::Commands::Users { command } => {
+            info!("managing allowed users");
+            users::run(command, &app_config)?;
+        }
+        cli::Commands::Oauth { command } => {
+            info!("registering oauth client");
+            keycloak::run(command, &app_config)?;
+        }
+        cli::Commands::Guest { command } => {
+            info!("managing guest accounts");
+            guest::run(command, &app_config)?;
+        }
+        cli::Commands::Workshop { command } => {
+            info!("managing workshop accounts");
+            workshop::run(command, &app_config)?;
+        }
+        cli::Commands::Publish { opts } => {
+            info!("publishing results");
+            publish::run(opts, &app_config)?;
+        }
+        cli::Commands::Prune { command } => {
+            info!("pruning");
+            prune::run(command, &app_config)?;
+        }
+        cli::Commands::Volumes { command } => {
+            info!("managing user volumes");
+            volumes::run(command, &app_config)?;
+        }
+        cli::Commands::Audit { opts } => {
+            audit::show(opts, &app_config)?;
+        }
+        cli::Commands::Du { opts } => {
+            info!("reporting disk usage");
+            disk::run(opts, &app_config)?;
+        }
+        cli::Commands::Top { opts } => {
+            info!("sampling resource usage");
+            top::run(opts, &app_config)?;
+        }
+        cli::Commands::Report { command } => {
+            info!("running usage report command");
+            usage::run(command, &app_config)?;
+        }
+        cli::Commands::Graph { opts } => {
+            info!("rendering service graph");
+            graph::run(opts, &app_config)?;
+        }
+        cli::Commands::Schedule { command } => {
+            info!("managing scheduled jobs");
+            schedule::run(command, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Deployments { command } => {
+            info!("managing known deployments");
+            deployments::run(command, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Adopt { opts } => {
+            info!("adopting an existing deployment");
+            adopt::run(opts, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Use { target } => {
+            info!("switching the active deployment");
+            deployments::activate(&target, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Tenant { command } => {
+            info!("managing tenants");
+            tenant::run(command, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Hardening { command } => {
+            info!("installing hardening");
+            hardening::run(command, &app_config)?;
+        }
+        cli::Commands::Firewall { command } => {
+            info!("applying firewall rules");
+            firewall::run(command, &app_config)?;
+        }
+        cli::Commands::Doctor => {
+            info!("checking docker setup");
+            doctor::run(&app_config)?;
+        }
+        cli::Commands::Workspace { command } => {
+            info!("managing workspaces");
+            workspace::run(command, &app_config)?;
+        }
+        cli::Commands::Backup { command } => {
+            info!("managing backups");
+            backup::run(command, &app_config)?;
+        }
+        cli::Commands::Cert { command } => {
+            info!("managing certificate backups");
+            cert::run(command, &app_config)?;
+        }
+        cli::Commands::Db { command } => {
+            info!("managing the hub database");
+            db::run(command, &app_config)?;
+        }
+        cli::Commands::Token { command } => {
+            info!("managing hub API tokens");
+            token::run(command, &app_config)?;
+        }
+        cli::Commands::Secrets { command } => {
+            info!("rotating secrets");
+            secrets::run(command, &app_config)?;
+        }
+        cli::Commands::Verify => {
+            info!("verifying hub API and proxy routes");
+            verify::run(&app_config)?;
+        }
+        cli::Commands::UserEnv { command } => {
+            info!("managing per-user environment variables");
+            userenv::run(command, &app_config)?;
+        }
+        cli::Commands::Announce { opts } => {
+            info!("updating the hub announcement");
+            announce::run(opts, &app_config)?;
+        }
+        cli::Commands::Watch { opts } => {
+            info!("watching services");
+            watch::run(opts, &app_config)?;
+        }
+        cli::Commands::Webhooks { command } => {
+            info!("managing webhooks");
+            webhook::run(command, &config_path, &mut app_config)?;
+        }
+        cli::Commands::Server { command } => {
+            info!("managing user servers");
+            server::run(command, &app_config)?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,147 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{audit, failure, say, util};
+
+/// Lock file in the deployment directory. The lock is the operating system's
+/// lock on the open file, so it goes away with the process however that ends;
+/// the file itself stays and only describes the last holder.
+pub const LOCK_FILE: &str = ".mvre-hub.lock";
+
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+/// Deployment directories this process holds the lock of, so nested
+/// operations (deploy starting the services) do not wait on themselves.
+static HELD: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Who holds the lock, as written into the lock file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holder {
+    pub pid: u32,
+    pub user: String,
+    pub command: String,
+    pub started: String,
+}
+
+/// Held lock of one deployment directory; released when dropped.
+#[derive(Debug)]
+pub struct OperationLock {
+    dir: PathBuf,
+    file: Option<File>,
+}
+
+impl Drop for OperationLock {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            file.unlock().ok();
+            if let Ok(mut held) = HELD.lock() {
+                held.retain(|dir| dir != &self.dir);
+            }
+        }
+    }
+}
+
+/// How long [`acquire`] waits for another operation to finish before giving up.
+pub fn configure_timeout(secs: u64) {
+    TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+}
+
+/// Takes the lock of `deploy_dir` for `command`, waiting up to the configured
+/// timeout while another process holds it.
+pub fn acquire(deploy_dir: &Path, command: &str) -> Result<OperationLock> {
+    let dir = std::path::absolute(deploy_dir).unwrap_or_else(|_| deploy_dir.to_path_buf());
+    if HELD.lock().map(|held| held.contains(&dir)).unwrap_or(false) {
+        return Ok(OperationLock { dir, file: None });
+    }
+
+    let path = dir.join(LOCK_FILE);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let timeout = Duration::from_secs(TIMEOUT_SECS.load(Ordering::Relaxed));
+    let started = Instant::now();
+    let mut announced = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                if !announced {
+                    say!(
+                        "{}",
+                        style(format!("Waiting for {} to finish", describe(read_holder(&mut file).as_ref()))).dim()
+                    );
+                    announced = true;
+                }
+                std::thread::sleep(Duration::from_millis(500));
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(failure::busy(format!(
+                    "another operation is in progress on {}: {}; wait for it to finish or pass --lock-timeout",
+                    util::path_display(&dir),
+                    describe(read_holder(&mut file).as_ref())
+                )));
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).with_context(|| format!("failed to lock {}", path.display()));
+            }
+        }
+    }
+
+    let holder = Holder {
+        pid: std::process::id(),
+        user: whoami::username(),
+        command: command.to_string(),
+        started: util::format_timestamp(util::unix_now()),
+    };
+    let json = serde_json::to_string(&holder).context("failed to serialize the lock holder")?;
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", json).with_context(|| format!("failed to write {}", path.display()))?;
+    if let Ok(mut held) = HELD.lock() {
+        held.push(dir.clone());
+    }
+    Ok(OperationLock { dir, file: Some(file) })
+}
+
+/// This process's command line as the lock file records it, secrets masked.
+pub fn invocation() -> String {
+    format!("mvre-hub {}", audit::redact_args(std::env::args().skip(1)).join(" "))
+}
+
+/// The holder recorded in the lock file of `deploy_dir`, if any.
+pub fn holder(deploy_dir: &Path) -> Option<Holder> {
+    let mut file = File::open(deploy_dir.join(LOCK_FILE)).ok()?;
+    read_holder(&mut file)
+}
+
+fn read_holder(file: &mut File) -> Option<Holder> {
+    let mut raw = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut raw).ok()?;
+    serde_json::from_str(raw.trim()).ok()
+}
+
+fn describe(holder: Option<&Holder>) -> String {
+    match holder {
+        Some(holder) => format!(
+            "`{}` by {} (pid {}, since {})",
+            holder.command, holder.user, holder.pid, holder.started
+        ),
+        None => "another mvre-hub process".to_string(),
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::Result;
+
+use crate::{cli::LogDriver, failure};
+
+/// Logging options of every container of the deployment, so long-running
+/// hubs do not fill the disk with container logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContainerLogging<'a> {
+    pub driver: LogDriver,
+    /// Size of one log file before it is rotated, e.g. `50m`.
+    pub max_size: &'a str,
+    /// Rotated files kept per container.
+    pub max_file: u32,
+}
+
+impl LogDriver {
+    /// `.env` value and Docker driver name.
+    pub fn name(self) -> &'static str {
+        match self {
+            LogDriver::JsonFile => "json-file",
+            LogDriver::Local => "local",
+            LogDriver::Journald => "journald",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json-file" => Some(LogDriver::JsonFile),
+            "local" => Some(LogDriver::Local),
+            "journald" => Some(LogDriver::Journald),
+            _ => None,
+        }
+    }
+
+    /// journald rotates on its own.
+    pub fn rotates_files(self) -> bool {
+        self != LogDriver::Journald
+    }
+}
+
+/// Docker's size syntax: a number with an optional `k`, `m`, or `g`.
+pub fn validate_max_size(value: &str) -> Result<()> {
+    let digits = value.trim_end_matches(['k', 'm', 'g']);
+    if digits.is_empty() || value.len() - digits.len() > 1 || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+        return Err(failure::validation(format!("invalid --log-max-size '{}' (use e.g. 50m)", value)));
+    }
+    Ok(())
+}
+
+impl ContainerLogging<'_> {
+    /// Compose extension the services refer to with `*default-logging`.
+    pub fn compose_extension(&self) -> String {
+        let mut block = format!("x-logging: &default-logging\n  driver: {}\n", self.driver.name());
+        if self.driver.rotates_files() {
+            block.push_str(&format!(
+                "  options:\n    max-size: \"{}\"\n    max-file: \"{}\"\n",
+                self.max_size, self.max_file
+            ));
+        }
+        block.push('\n');
+        block
+    }
+}
+
+/// Adds the logging extension to a rendered compose file and refers every
+/// service to it.
+pub fn apply(compose: &str, logging: &ContainerLogging) -> String {
+    let mut out = logging.compose_extension();
+    let mut in_services = false;
+    for line in compose.lines() {
+        out.push_str(line);
+        out.push('\n');
+        if !line.starts_with(' ') && !line.is_empty() {
+            in_services = line == "services:";
+            continue;
+        }
+        let is_service = line.len() > 2
+            && line.starts_with("  ")
+            && !line[2..].starts_with(' ')
+            && line.ends_with(':')
+            && !line.trim_start().starts_with('-');
+        if in_services && is_service {
+            out.push_str("    logging: *default-logging\n");
+        }
+    }
+    out
+}
@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::{failure, util};
+
+/// Loki, Promtail, and Grafana configs of the logging stack.
+pub const LOGGING_DIR: &str = "logging";
+pub const LOKI_IMAGE: &str = "grafana/loki:3.0.0";
+pub const PROMTAIL_IMAGE: &str = "grafana/promtail:3.0.0";
+pub const GRAFANA_IMAGE: &str = "grafana/grafana:11.1.0";
+/// Grafana is served under the hub's domain.
+pub const GRAFANA_PREFIX: &str = "/grafana";
+pub const DEFAULT_RETENTION: &str = "30d";
+
+/// Loki keeps whole hours, at least one day.
+pub fn retention_hours(value: &str) -> Result<u64> {
+    let seconds = util::parse_duration(value).context("invalid --log-retention")?;
+    if seconds < 86_400 {
+        return Err(failure::validation("--log-retention must be at least 1d"));
+    }
+    Ok(seconds.div_ceil(3600))
+}
+
+pub fn files(deploy_dir: &Path, retention_hours: u64) -> Vec<(PathBuf, String)> {
+    let dir = deploy_dir.join(LOGGING_DIR);
+    vec![
+        (dir.join("loki.yaml"), loki_config(retention_hours)),
+        (dir.join("promtail.yaml"), promtail_config()),
+        (dir.join("grafana-datasources.yaml"), grafana_datasources()),
+    ]
+}
+
+pub fn loki_config(retention_hours: u64) -> String {
+    format!(
+        r#"auth_enabled: false
+
+server:
+  http_listen_port: 3100
+
+common:
+  path_prefix: /loki
+  storage:
+    filesystem:
+      chunks_directory: /loki/chunks
+      rules_directory: /loki/rules
+  replication_factor: 1
+  ring:
+    kvstore:
+      store: inmemory
+
+schema_config:
+  configs:
+    - from: 2024-01-01
+      store: tsdb
+      object_store: filesystem
+      schema: v13
+      index:
+        prefix: index_
+        period: 24h
+
+limits_config:
+  retention_period: {retention_hours}h
+
+compactor:
+  working_directory: /loki/compactor
+  retention_enabled: true
+  delete_request_store: filesystem
+"#,
+        retention_hours = retention_hours
+    )
+}
+
+/// Every container on the host, hub-spawned user servers included, labelled
+/// with its name and compose service.
+pub fn promtail_config() -> String {
+    r#"server:
+  http_listen_port: 9080
+  grpc_listen_port: 0
+
+positions:
+  filename: /var/lib/promtail/positions.yaml
+
+clients:
+  - url: http://loki:3100/loki/api/v1/push
+
+scrape_configs:
+  - job_name: docker
+    docker_sd_configs:
+      - host: unix:///var/run/docker.sock
+        refresh_interval: 10s
+    relabel_configs:
+      - source_labels: ["__meta_docker_container_name"]
+        regex: "/(.*)"
+        target_label: container
+      - source_labels: ["__meta_docker_container_label_com_docker_compose_service"]
+        target_label: service
+      - source_labels: ["__meta_docker_container_label_com_docker_compose_project"]
+        target_label: project
+      - source_labels: ["__meta_docker_container_log_stream"]
+        target_label: stream
+"#
+    .to_string()
+}
+
+pub fn grafana_datasources() -> String {
+    r#"apiVersion: 1
+
+datasources:
+  - name: Loki
+    type: loki
+    access: proxy
+    url: http://loki:3100
+    isDefault: true
+"#
+    .to_string()
+}
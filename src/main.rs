@@ -1,3 +1,11 @@
-fn main() -> anyhow::Result<()> {
-    mvre_hub::run()
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match mvre_hub::run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(mvre_hub::exit_code(&err))
+        }
+    }
 }
@@ -0,0 +1,428 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::{Command, CommandFactory, Parser};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{ApplyOptions, Cli, Commands, DeployOptions, ManifestOptions, ReconfigureOptions},
+    compose,
+    config::AppConfig,
+    deploy,
+    envfile::{self, EnvMap},
+    failure, lock, report, say, util,
+};
+
+/// Settings naming files, which are relative to the manifest.
+const PATH_SETTINGS: &[&str] = &["allowed-users", "answers", "docker-cert-path", "landing-site", "logo", "terms-file"];
+/// Options about running the command rather than about the deployment.
+const RESERVED_SETTINGS: &[&str] = &["force", "help", "yes"];
+
+/// Desired state of a deployment, kept in version control and converged by
+/// `apply`.
+///
+/// ```toml
+/// deploy_dir = "/srv/mvre-hub"
+///
+/// [settings]                   # long options of `deploy`, without the dashes
+/// domain = "hub.example.org"
+/// acme-email = "ops@example.org"
+/// extra-idp = ["orcid"]
+/// enable-mlflow = true
+/// concurrent-spawn-limit = 20
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub deploy_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    Create,
+    Reconfigure,
+    Unchanged,
+}
+
+/// One setting of `.env` that differs, with credentials masked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// What `apply` would do to the deployment.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub deploy_dir: PathBuf,
+    pub action: PlanAction,
+    pub settings: Vec<SettingChange>,
+    pub files: Vec<String>,
+    pub conflicts: Vec<String>,
+    /// Settings the action has no option for, such as `shared-path`, which an
+    /// existing deployment keeps as it is.
+    pub skipped: Vec<String>,
+    /// Files in the deployment that `apply` leaves alone but that change what
+    /// runs, such as `docker-compose.override.yml`.
+    pub kept: Vec<String>,
+}
+
+pub fn load(path: &Path) -> Result<Manifest> {
+    let raw = util::read_to_string(path)?;
+    parse(&raw).with_context(|| format!("failed to parse manifest {}", path.display()))
+}
+
+pub fn parse(raw: &str) -> Result<Manifest> {
+    let manifest = toml::from_str(raw)?;
+    Ok(manifest)
+}
+
+pub fn plan(opts: ManifestOptions, app_config: &AppConfig) -> Result<()> {
+    let (manifest, base_dir) = open(&opts)?;
+    let deploy_dir = target_dir(&manifest, &base_dir, app_config)?;
+    let plan = compute(&manifest, &base_dir, &deploy_dir)?;
+    show(&plan, &opts.file);
+    util::set_result(serde_json::to_value(&plan)?);
+    Ok(())
+}
+
+/// Creates the deployment the manifest describes, or reconfigures it to
+/// match; does nothing when it already does.
+pub fn apply(opts: ApplyOptions, config_path: &Path, app_config: &mut AppConfig) -> Result<Plan> {
+    let (manifest, base_dir) = open(&opts.manifest)?;
+    let deploy_dir = target_dir(&manifest, &base_dir, app_config)?;
+    // Planned under the lock, so the plan still holds when it is applied.
+    let _lock = if deploy_dir.exists() {
+        Some(lock::acquire(&deploy_dir, &lock::invocation())?)
+    } else {
+        None
+    };
+    let plan = compute(&manifest, &base_dir, &deploy_dir)?;
+    show(&plan, &opts.manifest.file);
+    if plan.action == PlanAction::Unchanged {
+        util::set_result(serde_json::to_value(&plan)?);
+        return Ok(plan);
+    }
+    if !opts.yes {
+        let prompt = "Apply these changes?";
+        util::ensure_interactive(prompt, "pass --yes")?;
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            return Err(failure::validation("Aborted; nothing was changed"));
+        }
+    }
+
+    match plan.action {
+        PlanAction::Create => deploy::run_in(
+            Some(deploy_dir.clone()),
+            deploy_options(&manifest, &base_dir, deploy_dir.exists())?,
+            config_path,
+            app_config,
+        ),
+        PlanAction::Reconfigure => {
+            let mut target = app_config.clone();
+            target.deploy_dir_override = Some(deploy_dir.clone());
+            deploy::reconfigure(reconfigure_options(&manifest, &base_dir)?, &target)
+        }
+        PlanAction::Unchanged => Ok(()),
+    }?;
+    Ok(plan)
+}
+
+/// The deployment directory the manifest at `file` applies to.
+pub fn deploy_dir(file: &Path, app_config: &AppConfig) -> Result<PathBuf> {
+    let opts = ManifestOptions { file: file.to_path_buf() };
+    let (manifest, base_dir) = open(&opts)?;
+    target_dir(&manifest, &base_dir, app_config)
+}
+
+fn open(opts: &ManifestOptions) -> Result<(Manifest, PathBuf)> {
+    if !opts.file.exists() {
+        return Err(failure::validation(format!(
+            "no manifest at {}; write one or pass --file",
+            opts.file.display()
+        )));
+    }
+    let manifest = load(&opts.file)?;
+    let base_dir = opts
+        .file
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    Ok((manifest, base_dir))
+}
+
+/// `--deploy-dir`, else the manifest's `deploy_dir`, else the active deployment.
+fn target_dir(manifest: &Manifest, base_dir: &Path, app_config: &AppConfig) -> Result<PathBuf> {
+    if let Some(dir) = &app_config.deploy_dir_override {
+        return Ok(dir.clone());
+    }
+    if let Some(dir) = &manifest.deploy_dir {
+        return Ok(base_dir.join(dir));
+    }
+    app_config
+        .active_dir()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| failure::validation("the manifest sets no deploy_dir and there is no active deployment"))
+}
+
+fn compute(manifest: &Manifest, base_dir: &Path, deploy_dir: &Path) -> Result<Plan> {
+    if !deploy_dir.join(envfile::ENV_FILE).exists() {
+        let occupied = std::fs::read_dir(deploy_dir).is_ok_and(|mut entries| entries.next().is_some());
+        if occupied {
+            return Err(failure::validation(format!(
+                "{} exists but holds no deployment; empty it or set another deploy_dir",
+                util::path_display(deploy_dir)
+            )));
+        }
+        // Parsing checks the settings before anything is promised.
+        deploy_options(manifest, base_dir, false)?;
+        let (args, skipped) = to_args(manifest, "deploy", base_dir)?;
+        let settings = args
+            .iter()
+            .map(|arg| {
+                let arg = arg.trim_start_matches("--");
+                let (key, value) = arg.split_once('=').unwrap_or((arg, "true"));
+                SettingChange {
+                    key: key.to_string(),
+                    from: None,
+                    to: Some(value.to_string()),
+                }
+            })
+            .collect();
+        return Ok(Plan {
+            deploy_dir: deploy_dir.to_path_buf(),
+            action: PlanAction::Create,
+            settings,
+            files: Vec::new(),
+            conflicts: Vec::new(),
+            skipped,
+            kept: Vec::new(),
+        });
+    }
+
+    let (_, skipped) = to_args(manifest, "reconfigure", base_dir)?;
+    let current = envfile::load(deploy_dir)?;
+    let rendered = deploy::plan_reconfigure(deploy_dir, reconfigure_options(manifest, base_dir)?)?;
+    let relative = |paths: &[PathBuf]| -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.strip_prefix(deploy_dir).unwrap_or(path).display().to_string())
+            .collect()
+    };
+    let files = relative(&rendered.changed);
+    Ok(Plan {
+        deploy_dir: deploy_dir.to_path_buf(),
+        action: if files.is_empty() {
+            PlanAction::Unchanged
+        } else {
+            PlanAction::Reconfigure
+        },
+        settings: env_changes(&current, &rendered.env),
+        files,
+        conflicts: relative(&rendered.conflicts),
+        skipped,
+        kept: [compose::OVERRIDE_FILE]
+            .iter()
+            .filter(|file| deploy_dir.join(file).exists())
+            .map(|file| file.to_string())
+            .collect(),
+    })
+}
+
+fn show(plan: &Plan, manifest: &Path) {
+    let dir = util::path_display(&plan.deploy_dir);
+    match plan.action {
+        PlanAction::Create => say!("{} {}", style("Create").green().bold(), dir),
+        PlanAction::Reconfigure => say!("{} {}", style("Reconfigure").yellow().bold(), dir),
+        PlanAction::Unchanged => {
+            say!("{}", style(format!("{} matches {}", dir, manifest.display())).green());
+            return;
+        }
+    }
+    for change in &plan.settings {
+        match (&change.from, &change.to) {
+            (None, Some(to)) => say!("  {} {} = {}", style("+").green(), change.key, to),
+            (Some(from), None) => say!("  {} {} (was {})", style("-").red(), change.key, from),
+            (Some(from), Some(to)) => say!("  {} {}: {} -> {}", style("~").yellow(), change.key, from, to),
+            (None, None) => {}
+        }
+    }
+    for file in &plan.files {
+        say!("  updates {}", style(file).cyan());
+    }
+    for file in &plan.kept {
+        say!("  keeps {}", style(file).dim());
+    }
+    if !plan.conflicts.is_empty() {
+        say!(
+            "{}",
+            style(format!(
+                "Hand edits conflict in {}; set on-conflict in the manifest",
+                plan.conflicts.join(", ")
+            ))
+            .yellow()
+        );
+    }
+    if !plan.skipped.is_empty() {
+        let when = match plan.action {
+            PlanAction::Create => "Only applied to an existing deployment",
+            _ => "Only applied when the deployment is created",
+        };
+        say!("{}", style(format!("{}: {}", when, plan.skipped.join(", "))).dim());
+    }
+}
+
+/// `.env` keys whose values differ between `current` and `desired`.
+pub fn env_changes(current: &EnvMap, desired: &EnvMap) -> Vec<SettingChange> {
+    let (shown_current, shown_desired) = (report::redact_env(current), report::redact_env(desired));
+    let keys: BTreeSet<&String> = current.keys().chain(desired.keys()).collect();
+    keys.into_iter()
+        .filter(|key| current.get(*key) != desired.get(*key))
+        .map(|key| SettingChange {
+            key: key.clone(),
+            from: shown_current.get(key).cloned(),
+            to: shown_desired.get(key).cloned(),
+        })
+        .collect()
+}
+
+pub fn deploy_options(manifest: &Manifest, base_dir: &Path, into_empty_dir: bool) -> Result<DeployOptions> {
+    let (mut args, _) = to_args(manifest, "deploy", base_dir)?;
+    if into_empty_dir {
+        // Deploy refuses an existing directory otherwise; there is nothing in
+        // it to replace.
+        args.push("--force".to_string());
+    }
+    match parse_command("deploy", args)? {
+        Commands::Deploy { opts } => Ok(opts),
+        _ => unreachable!("parsed as deploy"),
+    }
+}
+
+pub fn reconfigure_options(manifest: &Manifest, base_dir: &Path) -> Result<ReconfigureOptions> {
+    let (args, _) = to_args(manifest, "reconfigure", base_dir)?;
+    match parse_command("reconfigure", args)? {
+        Commands::Reconfigure { opts } => Ok(opts),
+        _ => unreachable!("parsed as reconfigure"),
+    }
+}
+
+/// Options of `command` (`deploy` or `reconfigure`) for the manifest's
+/// settings, and the settings it has no option for.
+///
+/// A switch set to `false` turns into its opposite option where there is one
+/// (`enable-mlflow` into `--disable-mlflow`, `internal-tls` into
+/// `--no-internal-tls`) and is left out otherwise, which is its default.
+pub fn to_args(manifest: &Manifest, command: &str, base_dir: &Path) -> Result<(Vec<String>, Vec<String>)> {
+    let cli = Cli::command();
+    let find = |name: &str| cli.find_subcommand(name).expect("subcommand of mvre-hub");
+    let (deploy, reconfigure, target) = (find("deploy"), find("reconfigure"), find(command));
+    let mut args = Vec::new();
+    let mut skipped = Vec::new();
+    for (key, value) in &manifest.settings {
+        let name = key.replace('_', "-");
+        if RESERVED_SETTINGS.contains(&name.as_str()) {
+            return Err(failure::validation(format!(
+                "'{}' is not a deployment setting; pass it to the command instead",
+                key
+            )));
+        }
+        let Some(known) = option(deploy, &name).or_else(|| option(reconfigure, &name)) else {
+            return Err(failure::validation(format!("unknown setting '{}' in the manifest", key)));
+        };
+        if known.is_hide_env_values_set() {
+            let hint = known
+                .get_env()
+                .map(|env| format!("set {} instead", env.to_string_lossy()))
+                .unwrap_or_else(|| "pass it on the command line".to_string());
+            return Err(failure::validation(format!(
+                "keep '{}' out of the manifest, which is meant to be checked in; {}",
+                key, hint
+            )));
+        }
+        let Some(arg) = option(target, &name) else {
+            skipped.push(name);
+            continue;
+        };
+        let switch = !arg.get_action().takes_values();
+        match value {
+            toml::Value::Boolean(true) if switch => args.push(format!("--{}", name)),
+            toml::Value::Boolean(false) if switch => {
+                if let Some(opposite) = opposite(target, &name) {
+                    args.push(format!("--{}", opposite));
+                }
+            }
+            _ if switch => {
+                return Err(failure::validation(format!("setting '{}' is a switch; set it to true or false", key)))
+            }
+            value => {
+                let mut value = scalar(key, value)?;
+                if PATH_SETTINGS.contains(&name.as_str()) && !value.is_empty() {
+                    value = base_dir.join(&value).to_string_lossy().to_string();
+                }
+                args.push(format!("--{}={}", name, value));
+            }
+        }
+    }
+    Ok((args, skipped))
+}
+
+fn option<'a>(command: &'a Command, name: &str) -> Option<&'a clap::Arg> {
+    command.get_arguments().find(|arg| arg.get_long() == Some(name))
+}
+
+fn opposite<'a>(command: &'a Command, name: &str) -> Option<&'a str> {
+    let candidates = [
+        Some(format!("no-{}", name)),
+        name.strip_prefix("no-").map(str::to_string),
+        name.strip_prefix("enable-").map(|rest| format!("disable-{}", rest)),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .find_map(|candidate| option(command, &candidate))
+        .and_then(clap::Arg::get_long)
+}
+
+fn scalar(key: &str, value: &toml::Value) -> Result<String> {
+    match value {
+        toml::Value::String(text) => Ok(text.clone()),
+        toml::Value::Integer(number) => Ok(number.to_string()),
+        toml::Value::Float(number) => Ok(number.to_string()),
+        toml::Value::Array(items) => Ok(items
+            .iter()
+            .map(|item| scalar(key, item))
+            .collect::<Result<Vec<_>>>()?
+            .join(",")),
+        _ => Err(failure::validation(format!(
+            "setting '{}' must be a string, number, or list",
+            key
+        ))),
+    }
+}
+
+fn parse_command(command: &str, args: Vec<String>) -> Result<Commands> {
+    let argv = ["mvre-hub", command, "--yes"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(args);
+    Cli::try_parse_from(argv).map(|cli| cli.command).map_err(|err| {
+        let message = err.to_string();
+        let first = message.lines().next().unwrap_or_default();
+        failure::validation(format!("invalid manifest setting: {}", first.trim_start_matches("error: ")))
+    })
+}
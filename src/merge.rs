@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Select};
+
+use crate::{cli::OnConflict, failure, say, util};
+
+/// Last rendered content of each generated file, at the same relative path.
+/// It is the common ancestor when hand edits meet a regenerated template.
+pub const RENDERED_DIR: &str = ".mvre-rendered";
+
+const OURS_MARKER: &str = "<<<<<<< your edits";
+const SEPARATOR: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>> regenerated";
+
+/// Result of a line-based three-way merge; `text` carries conflict markers
+/// when `conflicts` is non-zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+    pub text: String,
+    pub conflicts: usize,
+}
+
+/// What regenerating one file does to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// The file already has the rendered content.
+    Unchanged,
+    /// No hand edits to keep; the rendered content replaces the file.
+    Replace,
+    /// Hand edits kept as they are; the template did not change the file.
+    KeepEdits,
+    /// Hand edits and template changes merged without overlap.
+    Merged(String),
+    /// Hand edits and template changes touch the same lines.
+    Conflict(Merged),
+}
+
+/// A generated file about to be regenerated.
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub path: PathBuf,
+    /// What was on disk when the update was planned.
+    pub current: Option<String>,
+    pub rendered: String,
+    pub action: Action,
+}
+
+impl Update {
+    /// What the file holds once the update is written.
+    pub fn contents(&self) -> &str {
+        match &self.action {
+            Action::Unchanged | Action::KeepEdits => self.current.as_deref().unwrap_or(&self.rendered),
+            Action::Replace => &self.rendered,
+            Action::Merged(text) => text,
+            Action::Conflict(merged) => &merged.text,
+        }
+    }
+
+    /// Whether writing the update changes the file as it was planned.
+    pub fn changes_file(&self) -> bool {
+        self.current.as_deref() != Some(self.contents())
+    }
+}
+
+/// Decides how to regenerate a file from its last rendered content (`base`),
+/// what is on disk (`current`), and the new rendering. Without a `base`, as
+/// in deployments made before it was recorded, the file is replaced.
+pub fn plan(base: Option<&str>, current: Option<&str>, rendered: &str) -> Action {
+    let (Some(base), Some(current)) = (base, current) else {
+        return match current {
+            Some(current) if current == rendered => Action::Unchanged,
+            _ => Action::Replace,
+        };
+    };
+    if current == rendered {
+        Action::Unchanged
+    } else if current == base {
+        Action::Replace
+    } else if rendered == base {
+        Action::KeepEdits
+    } else {
+        let merged = merge3(base, current, rendered);
+        if merged.conflicts == 0 {
+            Action::Merged(merged.text)
+        } else {
+            Action::Conflict(merged)
+        }
+    }
+}
+
+/// Plans every file of `files`, given as absolute paths below `deploy_dir`
+/// with their rendered content.
+pub fn plan_files(deploy_dir: &Path, files: Vec<(PathBuf, String)>) -> Vec<Update> {
+    files
+        .into_iter()
+        .map(|(path, rendered)| {
+            let base = std::fs::read_to_string(base_path(deploy_dir, &path)).ok();
+            let current = std::fs::read_to_string(&path).ok();
+            let action = plan(base.as_deref(), current.as_deref(), &rendered);
+            Update {
+                path,
+                current,
+                rendered,
+                action,
+            }
+        })
+        .collect()
+}
+
+/// Settles every conflict with `choice`, or by asking when none was given.
+/// Nothing is written here, so a refusal leaves the deployment untouched.
+pub fn resolve(deploy_dir: &Path, updates: &mut [Update], choice: Option<OnConflict>, interactive: bool) -> Result<()> {
+    let conflicted: Vec<String> = updates
+        .iter()
+        .filter(|update| matches!(update.action, Action::Conflict(_)))
+        .map(|update| relative(deploy_dir, &update.path))
+        .collect();
+    if conflicted.is_empty() {
+        return Ok(());
+    }
+    if choice.is_none() && !interactive {
+        return Err(failure::validation(format!(
+            "hand edits conflict with regenerated templates in {}; pass --on-conflict keep, replace, or markers",
+            conflicted.join(", ")
+        )));
+    }
+
+    let theme = ColorfulTheme::default();
+    for update in updates.iter_mut() {
+        let Action::Conflict(merged) = &update.action else {
+            continue;
+        };
+        let choice = match choice {
+            Some(choice) => choice,
+            None => {
+                let name = relative(deploy_dir, &update.path);
+                say!(
+                    "\n{} {} ({} conflicting {})",
+                    style("Conflict in").yellow().bold(),
+                    style(&name).cyan(),
+                    merged.conflicts,
+                    if merged.conflicts == 1 { "hunk" } else { "hunks" }
+                );
+                for line in conflict_hunks(&merged.text) {
+                    say!("  {}", style(line.trim_end_matches('\n')).dim());
+                }
+                let prompt = format!("Regenerate {}?", name);
+                util::ensure_interactive(&prompt, "pass --on-conflict")?;
+                let answer = Select::with_theme(&theme)
+                    .with_prompt(prompt)
+                    .items(&[
+                        "Keep my version",
+                        "Take the regenerated version",
+                        "Write both with conflict markers",
+                        "Abort",
+                    ])
+                    .default(0)
+                    .interact()?;
+                match answer {
+                    0 => OnConflict::Keep,
+                    1 => OnConflict::Replace,
+                    2 => OnConflict::Markers,
+                    _ => return Err(failure::validation("Aborted; nothing was written")),
+                }
+            }
+        };
+        update.action = match choice {
+            OnConflict::Keep => Action::KeepEdits,
+            OnConflict::Replace => Action::Replace,
+            OnConflict::Markers => Action::Conflict(merged.clone()),
+        };
+    }
+    Ok(())
+}
+
+/// Writes the planned contents, also where the files were removed since
+/// planning (as `deploy --force` does), and records the renderings as the
+/// new bases. Reports kept and merged edits and files left with conflicts.
+pub fn write(deploy_dir: &Path, updates: &[Update]) -> Result<()> {
+    for update in updates {
+        if std::fs::read_to_string(&update.path).ok().as_deref() != Some(update.contents()) {
+            util::write_string(&update.path, update.contents())?;
+        }
+        let name = relative(deploy_dir, &update.path);
+        match &update.action {
+            Action::KeepEdits => say!("  kept your edits to {}", style(&name).cyan()),
+            Action::Merged(_) => say!("  merged your edits into {}", style(&name).cyan()),
+            Action::Conflict(_) => say!(
+                "  {} {}; resolve the conflict markers before starting",
+                style("wrote conflict markers into").yellow(),
+                style(&name).cyan()
+            ),
+            Action::Unchanged | Action::Replace => {}
+        }
+
+        let base = base_path(deploy_dir, &update.path);
+        if std::fs::read_to_string(&base).ok().as_deref() != Some(update.rendered.as_str()) {
+            util::write_string(&base, &update.rendered)?;
+            // Copies of .env hold its secrets.
+            util::set_file_mode(&base, 0o600).ok();
+        }
+    }
+    Ok(())
+}
+
+fn base_path(deploy_dir: &Path, path: &Path) -> PathBuf {
+    deploy_dir
+        .join(RENDERED_DIR)
+        .join(path.strip_prefix(deploy_dir).unwrap_or(path))
+}
+
+fn relative(deploy_dir: &Path, path: &Path) -> String {
+    util::path_display(path.strip_prefix(deploy_dir).unwrap_or(path))
+}
+
+/// The conflict regions of a merged text, markers included.
+fn conflict_hunks(text: &str) -> Vec<&str> {
+    let mut inside = false;
+    text.split_inclusive('\n')
+        .filter(|line| {
+            if line.starts_with(OURS_MARKER) {
+                inside = true;
+            }
+            let keep = inside;
+            if line.starts_with(THEIRS_MARKER) {
+                inside = false;
+            }
+            keep
+        })
+        .collect()
+}
+
+/// Line-based three-way merge of `ours` and `theirs`, both derived from
+/// `base`: a region changed on one side only takes that side, identical
+/// changes are taken once, and different changes to the same region become
+/// a conflict with `ours` first.
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> Merged {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let in_ours = matching_lines(&base, &ours);
+    let in_theirs = matching_lines(&base, &theirs);
+
+    let mut merged = Merged {
+        text: String::new(),
+        conflicts: 0,
+    };
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    loop {
+        while b < base.len() && in_ours[b] == Some(o) && in_theirs[b] == Some(t) {
+            merged.text.push_str(base[b]);
+            b += 1;
+            o += 1;
+            t += 1;
+        }
+        if b == base.len() && o == ours.len() && t == theirs.len() {
+            return merged;
+        }
+
+        // The next base line both sides still have ends the changed region.
+        let (end_b, end_o, end_t) = (b..base.len())
+            .find_map(|line| Some((line, in_ours[line]?, in_theirs[line]?)))
+            .unwrap_or((base.len(), ours.len(), theirs.len()));
+        let (base_part, ours_part, theirs_part) = (&base[b..end_b], &ours[o..end_o], &theirs[t..end_t]);
+        if ours_part == base_part || ours_part == theirs_part {
+            push_lines(&mut merged.text, theirs_part);
+        } else if theirs_part == base_part {
+            push_lines(&mut merged.text, ours_part);
+        } else {
+            merged.conflicts += 1;
+            push_marker(&mut merged.text, OURS_MARKER);
+            push_lines(&mut merged.text, ours_part);
+            push_marker(&mut merged.text, SEPARATOR);
+            push_lines(&mut merged.text, theirs_part);
+            push_marker(&mut merged.text, THEIRS_MARKER);
+        }
+        (b, o, t) = (end_b, end_o, end_t);
+    }
+}
+
+fn push_lines(text: &mut String, lines: &[&str]) {
+    for line in lines {
+        text.push_str(line);
+    }
+}
+
+fn push_marker(text: &mut String, marker: &str) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(marker);
+    text.push('\n');
+}
+
+/// For each line of `a`, the line of `b` it is paired with in a longest
+/// common subsequence of the two.
+fn matching_lines(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut pairs = vec![None; a.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
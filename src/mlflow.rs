@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+/// Image build files and tracking data of the `mlflow` service.
+pub const MLFLOW_DIR: &str = "mlflow";
+pub const MLFLOW_VERSION: &str = "v2.16.2";
+/// `MLFLOW_ARTIFACTS` unless `--mlflow-artifacts` names another directory.
+pub const DEFAULT_ARTIFACTS: &str = "./mlflow/artifacts";
+/// What user containers log to; the server is on the users network.
+pub const TRACKING_URI: &str = "http://mlflow:5000";
+/// Tracking database on the hub's Postgres in production.
+pub const DATABASE: &str = "mlflow";
+
+/// Public host of the tracking UI.
+pub fn host(domain: &str) -> String {
+    format!("mlflow.{}", domain)
+}
+
+pub fn files(deploy_dir: &Path) -> Vec<(PathBuf, String)> {
+    let dir = deploy_dir.join(MLFLOW_DIR);
+    vec![
+        (dir.join("Dockerfile"), dockerfile()),
+        (dir.join("start.py"), start_script()),
+    ]
+}
+
+pub fn dockerfile() -> String {
+    format!(
+        r#"FROM ghcr.io/mlflow/mlflow:{version}
+
+RUN pip install --no-cache-dir psycopg2-binary
+COPY start.py /usr/local/bin/mlflow-start.py
+CMD ["python", "/usr/local/bin/mlflow-start.py"]
+"#,
+        version = MLFLOW_VERSION
+    )
+}
+
+/// Uses a database of its own on the hub's Postgres, created on first
+/// start, and SQLite next to the artifacts otherwise. Artifacts are proxied
+/// by the server, so clients need no access to the directory.
+pub fn start_script() -> String {
+    format!(
+        r#"import os
+import time
+from urllib.parse import quote
+
+backend = "sqlite:////mlflow/data/mlflow.db"
+if os.environ.get("ENABLE_POSTGRES", "false").lower() == "true":
+    import psycopg2
+
+    params = {{
+        "host": os.environ["DB_HOST"],
+        "port": os.environ["DB_PORT"],
+        "user": os.environ["DB_USER"],
+        "password": os.environ["DB_PASSWORD"],
+    }}
+    for attempt in range(30):
+        try:
+            conn = psycopg2.connect(dbname=os.environ["DB_NAME"], **params)
+            break
+        except psycopg2.OperationalError:
+            time.sleep(2)
+    else:
+        raise SystemExit("Postgres did not become reachable")
+    conn.autocommit = True
+    with conn.cursor() as cur:
+        cur.execute("SELECT 1 FROM pg_database WHERE datname = %s", ("{database}",))
+        if cur.fetchone() is None:
+            cur.execute('CREATE DATABASE "{database}"')
+    conn.close()
+    backend = "postgresql://{{}}:{{}}@{{}}:{{}}/{database}".format(
+        quote(params["user"], safe=""),
+        quote(params["password"], safe=""),
+        params["host"],
+        params["port"],
+    )
+
+os.execvp(
+    "mlflow",
+    [
+        "mlflow",
+        "server",
+        "--host=0.0.0.0",
+        "--port=5000",
+        f"--backend-store-uri={{backend}}",
+        "--artifacts-destination=/mlflow/artifacts",
+        "--serve-artifacts",
+    ],
+)
+"#,
+        database = DATABASE
+    )
+}
@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::{failure, util};
+
+/// Course configuration and the exchange, mounted into user servers.
+pub const NBGRADER_DIR: &str = "nbgrader";
+/// Where assignments are released and collected; every server writes to it.
+pub const EXCHANGE_DIR: &str = "exchange";
+/// Config directory that turns the instructor tools off for students.
+pub const STUDENT_DIR: &str = "student";
+pub const DEFAULT_COURSE: &str = "mosaic";
+/// Hub group whose members get the formgrader and assignment creation.
+pub const DEFAULT_INSTRUCTORS: &str = "instructors";
+/// Added to the user image requirements.
+pub const REQUIREMENT: &str = "nbgrader>=0.9,<0.10";
+
+/// Course ids name directories in the exchange, and the instructors group
+/// ends up in the hub config.
+pub fn validate_name(flag: &str, value: &str) -> Result<()> {
+    if value.is_empty() || !value.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') {
+        return Err(failure::validation(format!(
+            "{} '{}' may only contain letters, digits, '-' and '_'",
+            flag, value
+        )));
+    }
+    Ok(())
+}
+
+pub fn files(deploy_dir: &Path) -> Vec<(PathBuf, String)> {
+    let dir = deploy_dir.join(NBGRADER_DIR);
+    let student = dir.join(STUDENT_DIR);
+    vec![
+        (dir.join("nbgrader_config.py"), config()),
+        (
+            student.join("jupyter_server_config.d").join("nbgrader-student.json"),
+            student_server_config(),
+        ),
+        (student.join("labconfig").join("page_config.json"), student_page_config()),
+    ]
+}
+
+/// Creates the exchange writable by everyone, as nbgrader requires: servers
+/// may run under different UIDs, and students only ever add files.
+pub fn ensure_exchange(deploy_dir: &Path) -> Result<()> {
+    let exchange = deploy_dir.join(NBGRADER_DIR).join(EXCHANGE_DIR);
+    util::ensure_dir(&exchange)?;
+    util::set_file_mode(&exchange, 0o777)
+}
+
+/// Mounted at `/etc/jupyter/nbgrader_config.py` in every server.
+pub fn config() -> String {
+    r#"import os
+
+c = get_config()
+c.Exchange.root = "/srv/nbgrader/exchange"
+c.CourseDirectory.course_id = os.environ.get("NBGRADER_COURSE", "mosaic")
+c.CourseDirectory.root = os.path.expanduser("~/work/course")
+"#
+    .to_string()
+}
+
+/// Students' servers start with this directory on `JUPYTER_CONFIG_PATH`,
+/// ahead of the image's own config that enables every nbgrader extension.
+pub fn student_server_config() -> String {
+    r#"{
+  "ServerApp": {
+    "jpserver_extensions": {
+      "nbgrader.server_extensions.formgrader": false,
+      "nbgrader.server_extensions.course_list": false
+    }
+  }
+}
+"#
+    .to_string()
+}
+
+pub fn student_page_config() -> String {
+    r#"{
+  "disabledExtensions": {
+    "@jupyter/nbgrader:formgrader": true,
+    "@jupyter/nbgrader:create-assignment": true,
+    "@jupyter/nbgrader:course-list": true
+  }
+}
+"#
+    .to_string()
+}
@@ -0,0 +1,141 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    cli::OpenOptions,
+    config::AppConfig,
+    envfile::{self, EnvMap},
+    failure, filebrowser, logstack, mlflow, say, services, util,
+};
+
+/// Where users and operators reach a deployment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Access {
+    pub hub_url: String,
+    pub admin_users: Vec<String>,
+    /// Path of the dataset inside user servers, and where it is on the host.
+    pub dataset: Option<(String, String)>,
+    pub shared: Option<(String, String)>,
+    pub mlflow_url: Option<String>,
+    pub grafana_url: Option<String>,
+    pub files_url: Option<String>,
+    /// The certificate is one browsers do not trust out of the box.
+    pub untrusted_certificate: bool,
+}
+
+/// Reads the access details of a deployment from its `.env`.
+pub fn access(env: &EnvMap) -> Result<Access> {
+    let domain = envfile::get(env, "HUB_DOMAIN")
+        .filter(|domain| !domain.is_empty())
+        .ok_or_else(|| failure::config("HUB_DOMAIN missing from .env"))?;
+    let plain = envfile::get(env, "TLS_MODE") == Some("off");
+    // `--tls off` serves plain HTTP on the HTTP port, as `deploy` reports it.
+    let url = |host: &str| match envfile::get(env, "HTTP_PORT").unwrap_or("8080") {
+        _ if !plain => format!("https://{}", host),
+        "80" => format!("http://{}", host),
+        port => format!("http://{}:{}", host, port),
+    };
+    let flag = |key: &str| envfile::get(env, key) == Some("true");
+    let mount = |host_key: &str, mount_key: &str| {
+        let host = envfile::get(env, host_key).filter(|path| !path.is_empty())?;
+        let mount = envfile::get(env, mount_key).filter(|path| !path.is_empty())?;
+        Some((mount.to_string(), host.to_string()))
+    };
+    let hub_url = url(domain);
+    Ok(Access {
+        admin_users: envfile::get(env, "ADMIN_USERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|user| !user.is_empty())
+            .map(str::to_string)
+            .collect(),
+        dataset: mount("DATASET_HOST_PATH", "DATASET_MOUNT_PATH"),
+        shared: mount("SHARED_HOST_PATH", "SHARED_MOUNT_PATH"),
+        mlflow_url: flag("ENABLE_MLFLOW").then(|| url(&mlflow::host(domain))),
+        grafana_url: flag("ENABLE_LOGGING_STACK").then(|| format!("{}{}/", hub_url, logstack::GRAFANA_PREFIX)),
+        files_url: flag("ENABLE_FILE_BROWSER").then(|| format!("{}{}/", hub_url, filebrowser::PATH_PREFIX)),
+        untrusted_certificate: !plain && (flag("ACME_STAGING") || envfile::get(env, "TLS_MODE") == Some("self-signed")),
+        hub_url,
+    })
+}
+
+pub fn run(opts: OpenOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    summary(&deploy_dir, !opts.no_browser)
+}
+
+/// Prints how to reach the deployment and, with `browser` and a desktop
+/// session, opens the hub in the default browser.
+pub fn summary(deploy_dir: &Path, browser: bool) -> Result<()> {
+    let access = access(&envfile::load(deploy_dir)?)?;
+    say!("{}", style("Access").cyan().bold());
+    say!("  Hub:      {}", style(&access.hub_url).cyan());
+    let admins = if access.admin_users.is_empty() { "-".to_string() } else { access.admin_users.join(", ") };
+    say!("  Admins:   {}", admins);
+    if let Some((mount, host)) = &access.dataset {
+        say!("  Dataset:  {} {}", mount, style(format!("(host {})", host)).dim());
+    }
+    if let Some((mount, host)) = &access.shared {
+        say!("  Shared:   {} {}", mount, style(format!("(host {})", host)).dim());
+    }
+    for (name, url) in [("MLflow", &access.mlflow_url), ("Grafana", &access.grafana_url), ("Files", &access.files_url)] {
+        if let Some(url) = url {
+            say!("  {:<9} {}", format!("{}:", name), style(url).cyan());
+        }
+    }
+    if access.untrusted_certificate {
+        say!("{}", style("The certificate is not trusted by browsers; expect a warning").yellow());
+    }
+
+    let opened = browser && has_desktop();
+    if opened {
+        if let Err(err) = launch(&access.hub_url) {
+            tracing::warn!("failed to open a browser: {:#}", err);
+        }
+    } else if browser {
+        say!("{}", style("No desktop session; open the hub URL in a browser").dim());
+    }
+    util::set_result(serde_json::json!({ "access": access, "opened": opened }));
+    Ok(())
+}
+
+/// Whether a browser opened here would show up in front of someone.
+pub fn has_desktop() -> bool {
+    if util::ci() || !util::human_output() {
+        return false;
+    }
+    if cfg!(target_os = "macos") || cfg!(windows) {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()))
+        && util::on_path("xdg-open")
+}
+
+fn launch(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to start the browser")?;
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::{fs, path::Path, process::Command};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    failure,
+    init::{InitSystem, ServiceState},
+    util,
+};
+
+const SCRIPT_PATH: &str = "/etc/init.d/mvre-hub";
+
+pub struct OpenRc;
+
+impl InitSystem for OpenRc {
+    fn name(&self) -> &'static str {
+        "openrc"
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn install_service(&self, deploy_dir: &Path) -> Result<()> {
+        let content = init_script(deploy_dir);
+        util::atomic_write(Path::new(SCRIPT_PATH), content.as_bytes())
+            .with_context(|| format!("failed to write {}", SCRIPT_PATH))?;
+        util::set_file_mode(Path::new(SCRIPT_PATH), 0o755)?;
+        rc_update("add")
+    }
+
+    fn remove_service(&self) -> Result<()> {
+        if Path::new(SCRIPT_PATH).exists() {
+            let _ = rc_update("del");
+            fs::remove_file(SCRIPT_PATH).with_context(|| format!("failed to remove {}", SCRIPT_PATH))?;
+        }
+        Ok(())
+    }
+
+    fn service_state(&self) -> Result<Option<ServiceState>> {
+        if !Path::new(SCRIPT_PATH).exists() {
+            return Ok(None);
+        }
+        let running = Command::new("rc-service")
+            .args(["mvre-hub", "status"])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+        let enabled = Command::new("rc-update")
+            .args(["show", "default"])
+            .env("LC_ALL", "C")
+            .output()
+            .map(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .any(|line| line.split('|').next().map(str::trim) == Some("mvre-hub"))
+            })
+            .unwrap_or(false);
+        Ok(Some(ServiceState {
+            enabled,
+            running,
+            detail: format!(
+                "{} ({})",
+                if enabled { "enabled" } else { "disabled" },
+                if running { "started" } else { "stopped" }
+            ),
+        }))
+    }
+}
+
+/// The `/etc/init.d` script: runs `docker-compose up` in the background as
+/// the deploying user and takes the stack down once stopped.
+pub fn init_script(deploy_dir: &Path) -> String {
+    let compose = deploy_dir.join("docker-compose.yml");
+    format!(
+        "#!/sbin/openrc-run\n\
+description=\"MVRE-Hub\"\n\
+command=\"/usr/bin/env\"\n\
+command_args=\"docker-compose -f {compose} up\"\n\
+command_background=true\n\
+command_user=\"{user}\"\n\
+directory=\"{dir}\"\n\
+pidfile=\"/run/mvre-hub.pid\"\n\n\
+depend() {{\n\tneed docker\n\tafter net\n}}\n\n\
+stop_post() {{\n\t/usr/bin/env docker-compose -f {compose} down\n}}\n",
+        compose = compose.display(),
+        user = whoami::username(),
+        dir = deploy_dir.display(),
+    )
+}
+
+fn rc_update(action: &str) -> Result<()> {
+    let status = Command::new("rc-update")
+        .args([action, "mvre-hub", "default"])
+        .status()
+        .context("failed to run rc-update")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(failure::permission(format!("rc-update {} failed: {}", action, status)))
+    }
+}
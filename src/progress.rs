@@ -0,0 +1,209 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::{failure, runlog, util};
+
+/// Lines of captured output replayed when a step fails.
+const TAIL_LINES: usize = 40;
+
+/// Spinner on stderr for a long-running step, showing elapsed time and the
+/// latest line of output. Inactive under `--plain`, `--quiet`, `--format json`,
+/// or when stderr is not a terminal; callers then stream output as before.
+pub struct Spinner {
+    bar: Option<ProgressBar>,
+}
+
+impl Spinner {
+    pub fn start(step: &str) -> Self {
+        let bar = util::progress_enabled().then(|| {
+            let bar = ProgressBar::new_spinner();
+            let style = ProgressStyle::with_template("{spinner:.cyan} {prefix} {elapsed:.dim} {wide_msg:.dim}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner());
+            bar.set_style(style);
+            bar.set_prefix(step.to_string());
+            bar.enable_steady_tick(Duration::from_millis(120));
+            bar
+        });
+        Self { bar }
+    }
+
+    /// A spinner that never shows, for [`Spinner::run`] with output passed
+    /// straight through.
+    pub fn hidden() -> Self {
+        Self { bar: None }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bar.is_some()
+    }
+
+    pub fn set_detail(&self, detail: &str) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(detail.trim().to_string());
+        }
+    }
+
+    /// Runs `command` with stdout and stderr captured, feeding `input` on
+    /// stdin, and copies every line to `log`. Behind an active spinner only
+    /// the latest line shows and the last lines are replayed on stderr if the
+    /// command fails; otherwise lines pass through to the terminal as they
+    /// arrive, stdout dropped under `--quiet`/`--format json`. A command
+    /// still running after `timeout` is killed.
+    pub fn run(
+        &self,
+        command: &mut Command,
+        tool: &str,
+        input: Option<&[u8]>,
+        mut log: Option<runlog::Writer>,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let mut child = command
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| invoke_error(tool, err))?;
+        // Fed from its own thread: a database dump can outgrow the pipe
+        // while the command is still writing output of its own.
+        let writer = match input {
+            Some(input) => {
+                let mut stdin = child.stdin.take().with_context(|| format!("failed to open {} stdin", tool))?;
+                let input = input.to_vec();
+                Some(thread::spawn(move || stdin.write_all(&input)))
+            }
+            None => None,
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let readers = [
+            child.stdout.take().map(|out| forward_lines(out, false, sender.clone())),
+            child.stderr.take().map(|err| forward_lines(err, true, sender.clone())),
+        ];
+        drop(sender);
+
+        let deadline = timeout.map(|limit| Instant::now() + limit);
+        let mut timed_out = false;
+        let mut tail = VecDeque::with_capacity(TAIL_LINES);
+        loop {
+            let next = match deadline {
+                Some(deadline) => receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let (from_stderr, line) = match next {
+                Ok(next) => next,
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    // Helpers the command started may keep its output open,
+                    // so the readers are left behind rather than waited for.
+                    child.kill().ok();
+                    timed_out = true;
+                    break;
+                }
+            };
+            if let Some(log) = &mut log {
+                log.line(&line);
+            }
+            if !self.is_active() {
+                if from_stderr {
+                    eprintln!("{}", line);
+                } else if util::human_output() {
+                    println!("{}", line);
+                }
+                continue;
+            }
+            self.set_detail(&line);
+            if tail.len() == TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+        if !timed_out {
+            for reader in readers.into_iter().flatten() {
+                let _ = reader.join();
+            }
+        }
+        let status = child.wait().with_context(|| format!("failed to wait for {}", tool))?;
+        if let Some(writer) = writer {
+            match writer.join() {
+                Ok(Ok(())) => {}
+                // The command stopped reading; its exit status says why.
+                Ok(Err(err)) if err.kind() == std::io::ErrorKind::BrokenPipe && !status.success() => {}
+                Ok(Err(err)) => return Err(err).with_context(|| format!("failed to write to {} stdin", tool)),
+                Err(_) => anyhow::bail!("failed to write to {} stdin", tool),
+            }
+        }
+        if let Some(log) = &mut log {
+            if timed_out {
+                log.line("==> stopped: time limit reached");
+            } else {
+                log.line(&format!("==> {}", status));
+            }
+        }
+
+        if status.success() && !timed_out {
+            return Ok(());
+        }
+        self.clear();
+        for line in &tail {
+            eprintln!("{}", line);
+        }
+        let mut message = match timeout.filter(|_| timed_out) {
+            Some(limit) => format!("{} did not finish within {}s and was stopped", tool, limit.as_secs()),
+            None => format!("{} exited with status {}", tool, status),
+        };
+        if let Some(log) = &log {
+            message.push_str(&format!("; full output in {}", log.path().display()));
+        }
+        if timed_out {
+            // Mostly a pull or build stalled on a slow link.
+            return Err(failure::network(message));
+        }
+        if tool.starts_with("docker") {
+            return Err(failure::docker(message));
+        }
+        anyhow::bail!(message)
+    }
+
+    pub fn clear(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+fn invoke_error(tool: &str, err: std::io::Error) -> anyhow::Error {
+    let message = format!("failed to invoke {}: {}", tool, err);
+    if tool.starts_with("docker") {
+        return failure::docker(message);
+    }
+    anyhow::anyhow!(message)
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+    source: R,
+    from_stderr: bool,
+    sender: mpsc::Sender<(bool, String)>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(source).lines().map_while(|line| line.ok()) {
+            if sender.send((from_stderr, line)).is_err() {
+                break;
+            }
+        }
+    })
+}
@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use anyhow::Result;
+use console::style;
+
+use crate::{cli::ProxyVersion, compose, envfile, say, util};
+
+/// Matchers that took several values in v2 but a single one in v3.
+const MULTI_VALUE_MATCHERS: &[&str] = &["Host", "Path", "PathPrefix", "Method", "ClientIP"];
+
+impl ProxyVersion {
+    /// `.env` value, e.g. `v3`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProxyVersion::V2 => "v2",
+            ProxyVersion::V3 => "v3",
+        }
+    }
+
+    pub fn image(self) -> &'static str {
+        match self {
+            ProxyVersion::V2 => "traefik:v2.9",
+            ProxyVersion::V3 => "traefik:v3.1",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "v2" => Some(ProxyVersion::V2),
+            "v3" => Some(ProxyVersion::V3),
+            _ => None,
+        }
+    }
+}
+
+/// Moves an existing deployment to `target`. The compose file is rewritten
+/// in place rather than re-rendered, and `PROXY_VERSION` is recorded so later
+/// renders keep the new version. Returns whether anything changed.
+pub fn migrate(deploy_dir: &Path, target: ProxyVersion) -> Result<bool> {
+    let env_path = deploy_dir.join(envfile::ENV_FILE);
+    let raw_env = util::read_to_string(&env_path)?;
+    let current = envfile::get(&envfile::parse(&raw_env), "PROXY_VERSION")
+        .and_then(ProxyVersion::from_name)
+        .unwrap_or(ProxyVersion::V2);
+    if current == target {
+        return Ok(false);
+    }
+    if target == ProxyVersion::V2 {
+        anyhow::bail!(
+            "Traefik cannot be downgraded by upgrade; use `mvre-hub rollback` or `mvre-hub reconfigure --proxy-version v2`"
+        );
+    }
+
+    let compose_path = deploy_dir.join(compose::COMPOSE_FILE);
+    let migrated = migrate_compose_to_v3(&util::read_to_string(&compose_path)?);
+    util::write_string(&compose_path, &migrated)?;
+    util::write_string(&env_path, &envfile::with_value(&raw_env, "PROXY_VERSION", target.name()))?;
+    say!("Migrated proxy configuration to Traefik {}", style(target.name()).bold());
+    Ok(true)
+}
+
+/// Rewrites a v2 compose file for Traefik v3: the proxy image and the rule
+/// syntax of every router label. Lines without Traefik settings are kept as
+/// they are, so local edits survive the migration.
+pub fn migrate_compose_to_v3(compose: &str) -> String {
+    let mut migrated: Vec<String> = compose
+        .lines()
+        .map(|line| {
+            if let Some(index) = line.find("image: traefik:v2") {
+                format!("{}image: {}", &line[..index], ProxyVersion::V3.image())
+            } else if line.contains("traefik.http.routers.") {
+                match line.find(".rule") {
+                    Some(index) => format!("{}{}", &line[..index], migrate_rule(&line[index..])),
+                    None => line.to_string(),
+                }
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if compose.ends_with('\n') {
+        migrated.push(String::new());
+    }
+    migrated.join("\n")
+}
+
+/// Converts v2 rule matchers to their v3 form: `Headers` becomes `Header`,
+/// `HostHeader` becomes `Host`, multi-value matchers are split into an `||`
+/// chain, and `HostRegexp` templates become plain regular expressions.
+pub fn migrate_rule(rule: &str) -> String {
+    let mut out = String::new();
+    let mut rest = rule;
+    while let Some(start) = rest.find(|ch: char| ch.is_ascii_alphabetic()) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let name_len = rest
+            .find(|ch: char| !ch.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        let after = &rest[name_len..];
+        match after.strip_prefix('(').and_then(parse_args) {
+            Some((args, consumed)) => {
+                out.push_str(&migrate_matcher(name, &args));
+                rest = &after[1 + consumed..];
+            }
+            None => {
+                out.push_str(name);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn migrate_matcher(name: &str, args: &[String]) -> String {
+    let call = |name: &str| {
+        let quoted: Vec<String> = args.iter().map(|arg| format!("`{}`", arg)).collect();
+        format!("{}({})", name, quoted.join(", "))
+    };
+    match name {
+        "Headers" => call("Header"),
+        "HeadersRegexp" => call("HeaderRegexp"),
+        "HostRegexp" => or_chain("HostRegexp", &args.iter().map(|arg| host_template_regex(arg)).collect::<Vec<_>>()),
+        "HostHeader" => or_chain("Host", args),
+        _ if MULTI_VALUE_MATCHERS.contains(&name) => or_chain(name, args),
+        _ => call(name),
+    }
+}
+
+fn or_chain(name: &str, values: &[String]) -> String {
+    let matchers: Vec<String> = values.iter().map(|value| format!("{}(`{}`)", name, value)).collect();
+    if matchers.len() > 1 {
+        format!("({})", matchers.join(" || "))
+    } else {
+        matchers.concat()
+    }
+}
+
+/// Parses `` `a`, `b`) `` into its values and the number of bytes consumed,
+/// or `None` when the text is not a list of backtick-quoted values.
+fn parse_args(text: &str) -> Option<(Vec<String>, usize)> {
+    let mut args = Vec::new();
+    let mut index = 0;
+    loop {
+        index += text[index..].len() - text[index..].trim_start().len();
+        let rest = &text[index..];
+        if rest.starts_with(')') {
+            return Some((args, index + 1));
+        }
+        if !args.is_empty() {
+            rest.strip_prefix(',')?;
+            index += 1;
+            index += text[index..].len() - text[index..].trim_start().len();
+        }
+        let rest = text[index..].strip_prefix('`')?;
+        let end = rest.find('`')?;
+        args.push(rest[..end].to_string());
+        index += end + 2;
+    }
+}
+
+/// Turns a v2 host template such as `{sub:[a-z]+}.example.org` into an
+/// anchored regular expression.
+fn host_template_regex(template: &str) -> String {
+    if template.starts_with('^') {
+        return template.to_string();
+    }
+    let mut regex = String::from("^");
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        regex.push_str(&escape_regex(&rest[..open]));
+        let Some(close) = rest[open..].find('}') else {
+            rest = &rest[open..];
+            break;
+        };
+        let placeholder = &rest[open + 1..open + close];
+        match placeholder.split_once(':') {
+            Some((_, pattern)) => regex.push_str(pattern),
+            None => regex.push_str("[^.]+"),
+        }
+        rest = &rest[open + close + 1..];
+    }
+    regex.push_str(&escape_regex(rest));
+    regex.push('$');
+    regex
+}
+
+fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::new();
+    for ch in literal.chars() {
+        if "\\.+*?()|[]{}^$".contains(ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
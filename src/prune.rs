@@ -0,0 +1,206 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    cli::{OrphanPolicy, PruneCommand, PruneVolumesOptions},
+    config::AppConfig,
+    engine::Engine,
+    envfile::{self, EnvMap},
+    failure,
+    hubapi::{HubClient, User},
+    say, services, util, workspace,
+};
+
+pub const USER_VOLUME_PREFIX: &str = "jupyterhub-user-";
+/// DockerSpawner's default container name prefix.
+pub const USER_CONTAINER_PREFIX: &str = "jupyter-";
+const STATE_FILE: &str = "prune-state.json";
+const DEFAULT_GRACE: &str = "30d";
+
+/// What to do with the work volume of a user who no longer exists in the hub DB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumePolicy {
+    Immediate,
+    Grace(u64),
+    Never,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PruneReport {
+    pub removed: Vec<String>,
+    /// Orphaned volumes waiting out the grace period, with their removal deadline.
+    pub pending: Vec<(String, u64)>,
+    pub kept: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+/// Prefix of the deployment's user volumes; tenants put their `RESOURCE_PREFIX`
+/// in front.
+pub fn user_volume_prefix(env: &EnvMap) -> String {
+    format!("{}{}", envfile::get(env, "RESOURCE_PREFIX").unwrap_or(""), USER_VOLUME_PREFIX)
+}
+
+pub fn user_container_prefix(env: &EnvMap) -> String {
+    format!("{}{}", envfile::get(env, "RESOURCE_PREFIX").unwrap_or(""), USER_CONTAINER_PREFIX)
+}
+
+impl VolumePolicy {
+    pub fn from_env(env: &EnvMap) -> Result<Self> {
+        let grace = envfile::get(env, "ORPHAN_VOLUME_GRACE").unwrap_or(DEFAULT_GRACE);
+        match envfile::get(env, "ORPHAN_VOLUME_POLICY").unwrap_or("never") {
+            "immediate" => Ok(VolumePolicy::Immediate),
+            "grace" => Ok(VolumePolicy::Grace(util::parse_duration(grace)?)),
+            "never" => Ok(VolumePolicy::Never),
+            other => Err(failure::config(format!("unknown ORPHAN_VOLUME_POLICY '{}'", other))),
+        }
+    }
+
+    pub fn from_options(policy: OrphanPolicy, grace: &str) -> Result<Self> {
+        Ok(match policy {
+            OrphanPolicy::Immediate => VolumePolicy::Immediate,
+            OrphanPolicy::Grace => VolumePolicy::Grace(util::parse_duration(grace)?),
+            OrphanPolicy::Never => VolumePolicy::Never,
+        })
+    }
+}
+
+impl PruneReport {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.pending.is_empty() && self.kept.is_empty() && self.failed.is_empty()
+    }
+
+    /// One-line summary suitable for notifications.
+    pub fn summary(&self) -> String {
+        format!(
+            "orphaned volumes: {} removed, {} in grace period, {} kept, {} failed",
+            self.removed.len(),
+            self.pending.len(),
+            self.kept.len(),
+            self.failed.len()
+        )
+    }
+}
+
+pub fn run(command: PruneCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        PruneCommand::Volumes(opts) => prune_volumes(&deploy_dir, &opts),
+    }
+}
+
+fn prune_volumes(deploy_dir: &Path, opts: &PruneVolumesOptions) -> Result<()> {
+    let policy = match opts.policy {
+        Some(policy) => VolumePolicy::from_options(policy, opts.grace.as_deref().unwrap_or(DEFAULT_GRACE))?,
+        None => VolumePolicy::from_env(&envfile::load(deploy_dir)?)?,
+    };
+    let report = prune_orphaned_volumes(deploy_dir, policy, opts.dry_run)?;
+    util::set_result(serde_json::json!({ "dry_run": opts.dry_run, "report": report }));
+
+    if report.is_empty() {
+        say!("{}", style("No orphaned user volumes").dim());
+        return Ok(());
+    }
+    let verb = if opts.dry_run { "would remove" } else { "removed" };
+    for volume in &report.removed {
+        say!("  {:<40} {}", volume, style(verb).red());
+    }
+    for (volume, deadline) in &report.pending {
+        say!("  {:<40} removal after {}", volume, util::format_timestamp(*deadline));
+    }
+    for volume in &report.kept {
+        say!("  {:<40} {}", volume, style("kept (policy: never)").dim());
+    }
+    for (volume, err) in &report.failed {
+        say!("  {:<40} {}", volume, style(format!("failed: {}", err)).yellow());
+    }
+    say!("{}", report.summary());
+
+    Ok(())
+}
+
+/// The deployment's user volumes, named with `prefix`, whose user is gone
+/// from the hub. Volumes carry DockerSpawner's escaped user name.
+pub fn orphaned_volumes(volumes: &[String], users: &[User], prefix: &str) -> Vec<String> {
+    let owned: BTreeSet<String> = users
+        .iter()
+        .map(|user| format!("{}{}", prefix, workspace::workspace_name(&user.name)))
+        .collect();
+    volumes
+        .iter()
+        .filter(|volume| volume.starts_with(prefix) && !owned.contains(*volume))
+        .cloned()
+        .collect()
+}
+
+/// Applies `policy` to every user volume of the deployment whose user is gone from the hub.
+pub fn prune_orphaned_volumes(deploy_dir: &Path, policy: VolumePolicy, dry_run: bool) -> Result<PruneReport> {
+    let engine = Engine::new(deploy_dir);
+    let client = HubClient::from_deployment(deploy_dir)?;
+
+    let prefix = user_volume_prefix(&envfile::load(deploy_dir)?);
+    let orphans = orphaned_volumes(&engine.volume_names()?, &client.list_users()?, &prefix);
+
+    let now = util::unix_now();
+    let mut first_seen = load_state(deploy_dir)?;
+    first_seen.retain(|volume, _| orphans.contains(volume));
+
+    let mut report = PruneReport::default();
+    for volume in orphans {
+        let due = match policy {
+            VolumePolicy::Never => {
+                report.kept.push(volume);
+                continue;
+            }
+            VolumePolicy::Immediate => true,
+            VolumePolicy::Grace(grace) => {
+                let seen = *first_seen.entry(volume.clone()).or_insert(now);
+                if seen + grace > now {
+                    report.pending.push((volume, seen + grace));
+                    continue;
+                }
+                true
+            }
+        };
+
+        if due && dry_run {
+            report.removed.push(volume);
+        } else if due {
+            match engine.docker_output(&["volume", "rm", &volume]) {
+                Ok(_) => {
+                    first_seen.remove(&volume);
+                    report.removed.push(volume);
+                }
+                Err(err) => report.failed.push((volume, err.to_string())),
+            }
+        }
+    }
+
+    if !dry_run {
+        save_state(deploy_dir, &first_seen)?;
+    }
+    Ok(report)
+}
+
+fn state_path(deploy_dir: &Path) -> PathBuf {
+    deploy_dir.join(STATE_FILE)
+}
+
+fn load_state(deploy_dir: &Path) -> Result<BTreeMap<String, u64>> {
+    let path = state_path(deploy_dir);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let raw = util::read_to_string(&path)?;
+    serde_json::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+fn save_state(deploy_dir: &Path, state: &BTreeMap<String, u64>) -> Result<()> {
+    let serialized = serde_json::to_string_pretty(state).context("failed to serialize prune state")?;
+    util::write_string(&state_path(deploy_dir), &serialized)
+}
@@ -0,0 +1,150 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{cli::PublishOptions, config::AppConfig, envfile, failure, progress::Spinner, say, services, util};
+
+pub const PUBLISHED_DIR: &str = "published";
+
+pub fn run(opts: PublishOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let env = envfile::load(&deploy_dir)?;
+    if envfile::get(&env, "ENABLE_PUBLISHING") != Some("true") {
+        return Err(failure::config("Publishing is not enabled. Redeploy with --enable-publishing."));
+    }
+    let shared = envfile::get(&env, "SHARED_HOST_PATH").context("No shared directory configured for this deployment")?;
+
+    validate_path(&opts.path)?;
+    let source = Path::new(shared).join(&opts.path);
+    if !source.is_dir() {
+        return Err(failure::validation(format!("Not a directory: {}", source.display())));
+    }
+    // Users write the shared directory; a symlinked folder on the way must
+    // not lead the copy out of it.
+    let canonical = source.canonicalize().with_context(|| format!("failed to resolve {}", source.display()))?;
+    let shared_root = Path::new(shared)
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", shared))?;
+    if !canonical.starts_with(&shared_root) {
+        return Err(failure::validation(format!("{} leads outside the shared directory", opts.path.display())));
+    }
+
+    let name = match &opts.name {
+        Some(name) => name.clone(),
+        None => source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .context("cannot derive a name from the publish path; pass --name")?,
+    };
+    validate_name(&name)?;
+
+    let collection = deploy_dir.join(PUBLISHED_DIR).join(&name);
+    let version = next_version(&collection)?;
+    let target = collection.join(format!("v{}", version));
+    let spinner = Spinner::start(&format!("Copying {} v{}", name, version));
+    let skipped = copy_tree(&canonical, &target)?;
+    make_read_only(&target)?;
+    spinner.clear();
+    for link in &skipped {
+        tracing::warn!("skipped symlink {}", link.display());
+    }
+
+    let latest = collection.join("latest");
+    if fs::symlink_metadata(&latest).is_ok() {
+        fs::remove_file(&latest).with_context(|| format!("failed to replace {}", latest.display()))?;
+    }
+    util::maybe_symlink(Path::new(&format!("v{}", version)), &latest)
+        .with_context(|| format!("failed to link {}", latest.display()))?;
+
+    let domain = envfile::get(&env, "HUB_DOMAIN").unwrap_or("localhost");
+    say!("{}", style(format!("Published {} v{}", name, version)).green());
+    say!("Citable URL: {}", style(format!("https://{}/published/{}/v{}/", domain, name, version)).cyan());
+    say!("Latest:      {}", style(format!("https://{}/published/{}/latest/", domain, name)).dim());
+    util::set_result(serde_json::json!({
+        "name": name,
+        "version": version,
+        "url": format!("https://{}/published/{}/v{}/", domain, name, version),
+        "latest_url": format!("https://{}/published/{}/latest/", domain, name),
+        "skipped_symlinks": skipped,
+    }));
+
+    Ok(())
+}
+
+/// Publish paths stay inside the shared directory.
+pub fn validate_path(path: &Path) -> Result<()> {
+    if path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(failure::validation(format!("Publish path must be relative to the shared directory: {}", path.display())));
+    }
+    Ok(())
+}
+
+/// Names become a URL path segment and a directory under `published/`.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+        || name.starts_with('.')
+    {
+        return Err(failure::validation(format!("Invalid publish name '{}' (use letters, digits, '-', '_', '.')", name)));
+    }
+    Ok(())
+}
+
+/// Copies the folder to publish, leaving out symlinks: a user could point
+/// one at any file the admin running `publish` can read, and the copy is
+/// public. Returns the symlinks skipped.
+pub fn copy_tree(from: &Path, to: &Path) -> Result<Vec<PathBuf>> {
+    let mut skipped = Vec::new();
+    util::ensure_dir(to)?;
+    for entry in fs::read_dir(from).with_context(|| format!("failed to read dir {}", from.display()))? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        let kind = fs::symlink_metadata(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?
+            .file_type();
+        if kind.is_symlink() {
+            skipped.push(path);
+        } else if kind.is_dir() {
+            skipped.extend(copy_tree(&path, &target)?);
+        } else if kind.is_file() {
+            fs::copy(&path, &target).with_context(|| format!("failed to copy {} to {}", path.display(), target.display()))?;
+        }
+    }
+    Ok(skipped)
+}
+
+/// One more than the highest `v<N>` in `collection`.
+pub fn next_version(collection: &Path) -> Result<u32> {
+    if !collection.exists() {
+        return Ok(1);
+    }
+    let mut highest = 0;
+    for entry in fs::read_dir(collection).with_context(|| format!("failed to read {}", collection.display()))? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Some(version) = name.strip_prefix('v').and_then(|v| v.parse::<u32>().ok()) {
+            highest = highest.max(version);
+        }
+    }
+    Ok(highest + 1)
+}
+
+fn make_read_only(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path).with_context(|| format!("failed to read {}", path.display()))? {
+            make_read_only(&entry?.path())?;
+        }
+        util::set_file_mode(path, 0o555)
+    } else {
+        util::set_file_mode(path, 0o444)
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    backup::{Backup, RetentionPolicy},
+    cli::BackupTarget,
+    engine::{self, Engine},
+    envfile,
+    prune,
+    util,
+};
+
+/// Tag (restic) or archive name prefix (borg) marking this tool's backups.
+const TAG: &str = "mvre-hub";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepositoryEngine {
+    Restic,
+    Borg,
+}
+
+/// A deduplicating, encrypted backup repository, given as `restic:<repo>` or
+/// `borg:<repo>` in the repository syntax of the tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    pub engine: RepositoryEngine,
+    pub location: String,
+}
+
+impl Repository {
+    /// `None` for destinations that are not repositories.
+    pub fn parse(dest: &str) -> Option<Self> {
+        let (engine, location) = if let Some(location) = dest.strip_prefix("restic:") {
+            (RepositoryEngine::Restic, location)
+        } else if let Some(location) = dest.strip_prefix("borg:") {
+            (RepositoryEngine::Borg, location)
+        } else {
+            return None;
+        };
+        Some(Self {
+            engine,
+            location: location.to_string(),
+        })
+    }
+
+    pub fn init_args(&self) -> Vec<String> {
+        match self.engine {
+            RepositoryEngine::Restic => args(&["-r", &self.location, "init"]),
+            RepositoryEngine::Borg => args(&["init", "--encryption", "repokey-blake2", &self.location]),
+        }
+    }
+
+    /// Backs up `paths`, skipping `excluded`, as the snapshot/archive `name`.
+    pub fn backup_args(&self, name: &str, paths: &[PathBuf], excluded: &[PathBuf]) -> Vec<String> {
+        let mut command = match self.engine {
+            RepositoryEngine::Restic => args(&["-r", &self.location, "backup", "--tag", TAG]),
+            RepositoryEngine::Borg => args(&["create", "--compression", "zstd"]),
+        };
+        for path in excluded {
+            command.push("--exclude".to_string());
+            command.push(path.display().to_string());
+        }
+        if self.engine == RepositoryEngine::Borg {
+            command.push(format!("{}::{}", self.location, name));
+        }
+        command.extend(paths.iter().map(|path| path.display().to_string()));
+        command
+    }
+
+    /// Removes what `policy` does not keep and frees the space.
+    pub fn prune_args(&self, policy: RetentionPolicy, dry_run: bool) -> Vec<Vec<String>> {
+        let mut keep = Vec::new();
+        if policy.daily > 0 {
+            keep.extend(["--keep-daily".to_string(), policy.daily.to_string()]);
+        }
+        if policy.weekly > 0 {
+            keep.extend(["--keep-weekly".to_string(), policy.weekly.to_string()]);
+        }
+        match self.engine {
+            RepositoryEngine::Restic => {
+                let mut forget = args(&["-r", &self.location, "forget", "--tag", TAG]);
+                forget.extend(keep);
+                forget.push(if dry_run { "--dry-run" } else { "--prune" }.to_string());
+                vec![forget]
+            }
+            RepositoryEngine::Borg => {
+                let mut prune = args(&["prune", "--list", "--glob-archives", &format!("{}-*", TAG)]);
+                if dry_run {
+                    prune.push("--dry-run".to_string());
+                }
+                prune.extend(keep);
+                prune.push(self.location.clone());
+                let mut commands = vec![prune];
+                if !dry_run {
+                    commands.push(args(&["compact", &self.location]));
+                }
+                commands
+            }
+        }
+    }
+
+    pub fn list_args(&self) -> Vec<String> {
+        match self.engine {
+            RepositoryEngine::Restic => args(&["-r", &self.location, "snapshots", "--json", "--tag", TAG]),
+            RepositoryEngine::Borg => args(&["list", "--json", "--glob-archives", &format!("{}-*", TAG), &self.location]),
+        }
+    }
+
+    pub fn program(&self) -> &'static str {
+        match self.engine {
+            RepositoryEngine::Restic => "restic",
+            RepositoryEngine::Borg => "borg",
+        }
+    }
+
+    /// The tool, reading the repository password from `--password-file` when
+    /// given, or else from its own environment variables.
+    fn command(&self, target: &BackupTarget) -> Command {
+        let mut command = Command::new(self.program());
+        if let Some(file) = &target.password_file {
+            match self.engine {
+                RepositoryEngine::Restic => command.env("RESTIC_PASSWORD_FILE", file),
+                RepositoryEngine::Borg => command.env("BORG_PASSCOMMAND", format!("cat {}", file.display())),
+            };
+        }
+        command
+    }
+
+    pub fn init(&self, target: &BackupTarget) -> Result<()> {
+        self.run(target, &self.init_args())
+    }
+
+    pub fn backup(&self, target: &BackupTarget, name: &str, paths: &[PathBuf], excluded: &[PathBuf]) -> Result<()> {
+        self.run(target, &self.backup_args(name, paths, excluded))
+    }
+
+    pub fn prune(&self, target: &BackupTarget, policy: RetentionPolicy, dry_run: bool) -> Result<()> {
+        for command in self.prune_args(policy, dry_run) {
+            self.run(target, &command)?;
+        }
+        Ok(())
+    }
+
+    pub fn list(&self, target: &BackupTarget) -> Result<Vec<Backup>> {
+        let output = self
+            .command(target)
+            .args(self.list_args())
+            .output()
+            .with_context(|| format!("failed to run {}", self.program()))?;
+        if !output.status.success() {
+            anyhow::bail!("{} failed: {}", self.program(), String::from_utf8_lossy(&output.stderr).trim());
+        }
+        let raw = String::from_utf8_lossy(&output.stdout);
+        match self.engine {
+            RepositoryEngine::Restic => parse_restic_snapshots(&raw),
+            RepositoryEngine::Borg => parse_borg_archives(&raw),
+        }
+    }
+
+    fn run(&self, target: &BackupTarget, args: &[String]) -> Result<()> {
+        let status = self
+            .command(target)
+            .args(args)
+            .stdout(engine::terminal_stdout())
+            .stderr(Stdio::inherit())
+            .status()
+            .with_context(|| format!("failed to run {}; it is required for {} destinations", self.program(), self.program()))?;
+        if !status.success() {
+            anyhow::bail!("{} {} failed: {}", self.program(), subcommand(args), status);
+        }
+        Ok(())
+    }
+}
+
+/// Trees worth deduplicating: hub data and configuration, the user volumes,
+/// and per-user workspaces.
+pub fn backup_paths(deploy_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![deploy_dir.to_path_buf()];
+    let engine = Engine::new(deploy_dir);
+    let env = envfile::load(deploy_dir).unwrap_or_default();
+    let prefix = prune::user_volume_prefix(&env);
+    for volume in engine.volume_names()? {
+        if !volume.starts_with(&prefix) {
+            continue;
+        }
+        let mountpoint = engine.docker_output(&["volume", "inspect", "--format", "{{.Mountpoint}}", &volume])?;
+        paths.push(PathBuf::from(mountpoint.trim()));
+    }
+    if let Some(workspaces) = envfile::get(&env, "WORKSPACE_HOST_PATH") {
+        paths.push(PathBuf::from(workspaces));
+    }
+    Ok(paths)
+}
+
+#[derive(Deserialize)]
+struct ResticSnapshot {
+    time: String,
+    short_id: String,
+}
+
+/// Snapshots from `restic snapshots --json`, named by their short ID.
+pub fn parse_restic_snapshots(raw: &str) -> Result<Vec<Backup>> {
+    let snapshots: Vec<ResticSnapshot> = serde_json::from_str(raw).context("failed to parse restic snapshots")?;
+    Ok(snapshots
+        .into_iter()
+        .filter_map(|snapshot| {
+            Some(Backup {
+                created_at: util::parse_rfc3339(&snapshot.time)?,
+                name: snapshot.short_id,
+            })
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct BorgList {
+    archives: Vec<BorgArchive>,
+}
+
+#[derive(Deserialize)]
+struct BorgArchive {
+    name: String,
+}
+
+/// Archives from `borg list --json`; their names carry the UTC creation time.
+pub fn parse_borg_archives(raw: &str) -> Result<Vec<Backup>> {
+    let list: BorgList = serde_json::from_str(raw).context("failed to parse borg archive list")?;
+    Ok(list
+        .archives
+        .into_iter()
+        .filter_map(|archive| Backup::from_name(&archive.name))
+        .collect())
+}
+
+fn subcommand(args: &[String]) -> &str {
+    args.iter()
+        .enumerate()
+        .find(|(index, arg)| !arg.starts_with('-') && (*index == 0 || args[index - 1] != "-r"))
+        .map(|(_, arg)| arg.as_str())
+        .unwrap_or("")
+}
+
+fn args(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
@@ -0,0 +1,141 @@
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    failure::{self, Category},
+    util,
+};
+
+/// Docker operations that go over the network or wait on it: retried after
+/// a failure, and stopped when they run past their time limit.
+const RETRIED: &[&str] = &["pull", "build", "up", "start", "restart", "push"];
+const DEFAULT_TIMEOUTS: &[(&str, u64)] = &[
+    ("pull", 30 * 60),
+    ("push", 30 * 60),
+    ("build", 60 * 60),
+    ("up", 15 * 60),
+    ("start", 10 * 60),
+    ("restart", 10 * 60),
+];
+/// Longest wait between two attempts, however many there were.
+const MAX_BACKOFF_SECS: u64 = 10 * 60;
+
+static POLICY: Mutex<Option<Policy>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// Attempts after the first failed one.
+    pub retries: u32,
+    /// Seconds before the first retry; doubled after each one.
+    pub backoff: u64,
+    /// Time limit in seconds of each operation; operations not listed have none.
+    pub timeouts: BTreeMap<String, u64>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            backoff: 10,
+            timeouts: DEFAULT_TIMEOUTS
+                .iter()
+                .map(|(operation, secs)| (operation.to_string(), *secs))
+                .collect(),
+        }
+    }
+}
+
+impl Policy {
+    /// The policy of the global flags: `--docker-timeout` values such as
+    /// `pull=45m` replace the default limit of that operation, and `0`
+    /// removes it.
+    pub fn from_flags(retries: u32, no_retry: bool, backoff: &str, timeouts: &[String]) -> Result<Self> {
+        let mut policy = Policy {
+            retries: if no_retry { 0 } else { retries },
+            backoff: util::parse_duration(backoff).context("invalid --retry-backoff")?,
+            ..Policy::default()
+        };
+        for value in timeouts {
+            let (operation, limit) = value.split_once('=').ok_or_else(|| {
+                failure::validation(format!("invalid --docker-timeout '{}' (use OP=DURATION, e.g. pull=45m)", value))
+            })?;
+            let operation = operation.trim();
+            if !RETRIED.contains(&operation) {
+                return Err(failure::validation(format!(
+                    "--docker-timeout: unknown operation '{}' (use one of {})",
+                    operation,
+                    RETRIED.join(", ")
+                )));
+            }
+            match util::parse_duration(limit).context("invalid --docker-timeout")? {
+                0 => policy.timeouts.remove(operation),
+                secs => policy.timeouts.insert(operation.to_string(), secs),
+            };
+        }
+        Ok(policy)
+    }
+
+    /// Attempts `operation` gets in all.
+    pub fn attempts(&self, operation: Option<&str>) -> u32 {
+        match operation {
+            Some(operation) if RETRIED.contains(&operation) => self.retries + 1,
+            _ => 1,
+        }
+    }
+
+    /// Wait after failed attempt `attempt` (counted from 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u64.checked_shl(attempt.saturating_sub(1)).unwrap_or(u64::MAX);
+        Duration::from_secs(self.backoff.saturating_mul(factor).min(MAX_BACKOFF_SECS))
+    }
+
+    pub fn timeout(&self, operation: Option<&str>) -> Option<Duration> {
+        operation
+            .and_then(|operation| self.timeouts.get(operation))
+            .map(|secs| Duration::from_secs(*secs))
+    }
+}
+
+pub fn configure(policy: Policy) {
+    if let Ok(mut current) = POLICY.lock() {
+        *current = Some(policy);
+    }
+}
+
+/// The configured policy, or the default one when the flags were never read.
+pub fn policy() -> Policy {
+    POLICY
+        .lock()
+        .ok()
+        .and_then(|policy| policy.clone())
+        .unwrap_or_default()
+}
+
+/// Operation `program args` performs: the subcommand of docker and
+/// docker-compose, skipping the project and file options given before it.
+pub fn operation<'a>(program: &str, args: &[&'a str]) -> Option<&'a str> {
+    match program {
+        "docker" | "docker-compose" => {
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match *arg {
+                    "-f" | "--file" | "-p" | "--project-name" | "--env-file" | "--profile" | "--context" => {
+                        args.next();
+                    }
+                    flag if flag.starts_with('-') => {}
+                    subcommand => return Some(subcommand),
+                }
+            }
+            None
+        }
+        "repo2docker" => Some("build"),
+        _ => None,
+    }
+}
+
+/// Whether another attempt may succeed: Docker and the network have bad
+/// moments, a bad flag or a missing permission does not go away.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    matches!(failure::classify(err), Category::Docker | Category::Network)
+}
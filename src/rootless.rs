@@ -0,0 +1,118 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Socket of the system-wide daemon, and the mount target inside containers.
+pub const ROOTFUL_SOCKET: &str = "/var/run/docker.sock";
+
+/// Lowest port a rootless daemon may bind when the sysctl is unset.
+const DEFAULT_UNPRIVILEGED_PORT_START: u16 = 1024;
+
+/// How the Docker daemon on this host runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DockerMode {
+    pub rootless: bool,
+    pub socket: PathBuf,
+}
+
+impl DockerMode {
+    /// Probes the daemon the docker CLI talks to. An unreachable daemon is
+    /// treated as rootful, which is what the templates assumed before.
+    pub fn detect() -> Self {
+        let rootless = Command::new("docker")
+            .args(["info", "--format", "{{json .SecurityOptions}}"])
+            .env("LC_ALL", "C")
+            .output()
+            .map(|output| output.status.success() && String::from_utf8_lossy(&output.stdout).contains("rootless"))
+            .unwrap_or(false);
+        let docker_host = std::env::var("DOCKER_HOST").ok();
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok();
+        Self {
+            rootless,
+            socket: socket_path(rootless, docker_host.as_deref(), runtime_dir.as_deref()),
+        }
+    }
+
+    /// Mode recorded for a deployment by its `DOCKER_SOCKET` setting.
+    pub fn from_socket(socket: &str) -> Self {
+        Self {
+            rootless: socket != ROOTFUL_SOCKET,
+            socket: PathBuf::from(socket),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        if self.rootless {
+            "rootless"
+        } else {
+            "rootful"
+        }
+    }
+
+    /// `DOCKER_HOST` for processes that run outside the user's shell, such as
+    /// the auto-start unit; `None` for the default socket.
+    pub fn docker_host(&self) -> Option<String> {
+        (self.socket != Path::new(ROOTFUL_SOCKET)).then(|| format!("unix://{}", self.socket.display()))
+    }
+}
+
+/// Daemon socket on the host: an explicit `unix://` `DOCKER_HOST` wins, then
+/// `$XDG_RUNTIME_DIR/docker.sock` for rootless daemons.
+pub fn socket_path(rootless: bool, docker_host: Option<&str>, runtime_dir: Option<&str>) -> PathBuf {
+    if let Some(path) = docker_host.and_then(|host| host.strip_prefix("unix://")) {
+        return PathBuf::from(path);
+    }
+    if rootless {
+        let runtime_dir = runtime_dir.map(PathBuf::from).unwrap_or_else(default_runtime_dir);
+        return runtime_dir.join("docker.sock");
+    }
+    PathBuf::from(ROOTFUL_SOCKET)
+}
+
+fn default_runtime_dir() -> PathBuf {
+    #[cfg(unix)]
+    {
+        PathBuf::from(format!("/run/user/{}", nix::unistd::Uid::current()))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from("/run/user/1000")
+    }
+}
+
+/// `net.ipv4.ip_unprivileged_port_start`; rootless daemons cannot publish
+/// ports below it.
+pub fn unprivileged_port_start() -> u16 {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_UNPRIVILEGED_PORT_START)
+}
+
+/// Published ports a rootless daemon cannot bind.
+pub fn privileged_ports(ports: &[u16], unprivileged_start: u16) -> Vec<u16> {
+    ports.iter().copied().filter(|port| *port < unprivileged_start).collect()
+}
+
+/// RootlessKit port driver of the user's `docker.service`; `builtin` unless
+/// the unit overrides it.
+pub fn port_driver() -> String {
+    Command::new("systemctl")
+        .args(["--user", "show", "docker", "--property=Environment"])
+        .env("LC_ALL", "C")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| parse_port_driver(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_else(|| "builtin".to_string())
+}
+
+/// Port driver from `systemctl show --property=Environment` output.
+pub fn parse_port_driver(environment: &str) -> Option<String> {
+    environment
+        .lines()
+        .flat_map(|line| line.strip_prefix("Environment=").unwrap_or(line).split_whitespace())
+        .find_map(|entry| entry.strip_prefix("DOCKERD_ROOTLESS_ROOTLESSKIT_PORT_DRIVER="))
+        .map(str::to_string)
+}
@@ -0,0 +1,108 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    init::{InitSystem, ServiceState},
+    util,
+};
+
+const SERVICE_DIR: &str = "/etc/sv/mvre-hub";
+/// Directories runsvdir scans on common distributions (Void, Artix, Devuan).
+const SCAN_DIRS: &[&str] = &["/var/service", "/etc/service", "/service"];
+
+pub struct Runit;
+
+impl InitSystem for Runit {
+    fn name(&self) -> &'static str {
+        "runit"
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn install_service(&self, deploy_dir: &Path) -> Result<()> {
+        let service_dir = Path::new(SERVICE_DIR);
+        for (name, content) in service_scripts(deploy_dir) {
+            let path = service_dir.join(name);
+            util::atomic_write(&path, content.as_bytes())
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            util::set_file_mode(&path, 0o755)?;
+        }
+
+        let link = scan_dir()?.join("mvre-hub");
+        if fs::symlink_metadata(&link).is_err() {
+            util::maybe_symlink(service_dir, &link)
+                .with_context(|| format!("failed to link {}", link.display()))?;
+        }
+        Ok(())
+    }
+
+    fn remove_service(&self) -> Result<()> {
+        if let Ok(scan) = scan_dir() {
+            let link = scan.join("mvre-hub");
+            if fs::symlink_metadata(&link).is_ok() {
+                fs::remove_file(&link).with_context(|| format!("failed to remove {}", link.display()))?;
+            }
+        }
+        if Path::new(SERVICE_DIR).exists() {
+            fs::remove_dir_all(SERVICE_DIR).with_context(|| format!("failed to remove {}", SERVICE_DIR))?;
+        }
+        Ok(())
+    }
+
+    fn service_state(&self) -> Result<Option<ServiceState>> {
+        if !Path::new(SERVICE_DIR).join("run").exists() {
+            return Ok(None);
+        }
+        let enabled = scan_dir()
+            .map(|scan| fs::symlink_metadata(scan.join("mvre-hub")).is_ok())
+            .unwrap_or(false);
+        let status = Command::new("sv")
+            .args(["status", "mvre-hub"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+            .unwrap_or_default();
+        let running = status.starts_with("run:");
+        Ok(Some(ServiceState {
+            enabled,
+            running,
+            detail: format!(
+                "{} ({})",
+                if enabled { "enabled" } else { "disabled" },
+                status.split(':').next().filter(|state| !state.is_empty()).unwrap_or("unknown")
+            ),
+        }))
+    }
+}
+
+/// The `run` and `finish` scripts of the service directory: `run` keeps
+/// `docker-compose up` in the foreground as the deploying user, `finish`
+/// takes the stack down.
+pub fn service_scripts(deploy_dir: &Path) -> [(&'static str, String); 2] {
+    let compose = deploy_dir.join("docker-compose.yml");
+    let run = format!(
+        "#!/bin/sh\nexec 2>&1\ncd {dir}\nexec chpst -u {user} /usr/bin/env docker-compose -f {compose} up\n",
+        dir = deploy_dir.display(),
+        user = whoami::username(),
+        compose = compose.display(),
+    );
+    let finish = format!(
+        "#!/bin/sh\n/usr/bin/env docker-compose -f {} down\n",
+        compose.display()
+    );
+    [("run", run), ("finish", finish)]
+}
+
+fn scan_dir() -> Result<PathBuf> {
+    SCAN_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .find(|dir| dir.is_dir())
+        .context("no runit service directory found (/var/service, /etc/service, /service)")
+}
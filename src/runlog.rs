@@ -0,0 +1,96 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::util;
+
+/// Directory of the deployment holding the output of past runs.
+pub const LOG_DIR: &str = "logs";
+/// Run logs kept per deployment; older ones are removed when a new one starts.
+const KEEP_LOGS: usize = 50;
+
+static RUN: OnceLock<(String, u64)> = OnceLock::new();
+
+/// Names the current invocation; docker-compose output is only written to a
+/// log file once this has been called.
+pub fn start(command: &str, started: u64) {
+    let _ = RUN.set((command.to_string(), started));
+}
+
+/// `20261016T020000Z-upgrade.log`: sorts by start time.
+pub fn file_name(command: &str, started: u64) -> String {
+    let stamp = util::format_timestamp(started).replace([':', '-'], "");
+    let command: String = command
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+        .collect();
+    format!("{}-{}.log", stamp, command)
+}
+
+/// Removes all but the newest `keep` run logs in `dir` and returns the removed files.
+pub fn prune(dir: &Path, keep: usize) -> Vec<PathBuf> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+                .collect()
+        })
+        .unwrap_or_default();
+    logs.sort();
+    let excess = logs.len().saturating_sub(keep);
+    logs.into_iter()
+        .take(excess)
+        .filter(|path| fs::remove_file(path).is_ok())
+        .collect()
+}
+
+/// Output of one tool invocation, appended to the run's log file.
+pub struct Writer {
+    file: File,
+    path: PathBuf,
+}
+
+impl Writer {
+    /// Opens the current run's log in `deploy_dir` and writes a header for
+    /// `tool args`. `None` outside a named run or when the log cannot be
+    /// written; the command then runs without one.
+    pub fn open(deploy_dir: &Path, tool: &str, args: &[&str]) -> Option<Self> {
+        let (command, started) = RUN.get()?;
+        let dir = deploy_dir.join(LOG_DIR);
+        let path = dir.join(file_name(command, *started));
+        let opened = util::ensure_dir(&dir).and_then(|_| {
+            let first = !path.exists();
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            if first {
+                prune(&dir, KEEP_LOGS);
+            }
+            Ok(file)
+        });
+        let mut writer = match opened {
+            Ok(file) => Self { file, path },
+            Err(err) => {
+                tracing::debug!("no run log in {}: {:#}", dir.display(), err);
+                return None;
+            }
+        };
+        writer.line(&format!(
+            "==> {} {} {}",
+            util::format_timestamp(util::unix_now()),
+            tool,
+            args.join(" ")
+        ));
+        Some(writer)
+    }
+
+    pub fn line(&mut self, line: &str) {
+        let _ = writeln!(self.file, "{}", line);
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,295 @@
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{
+    build,
+    cli::{BuildService, ScanOptions, Scanner, Severity},
+    compose::ComposeFile,
+    config::AppConfig,
+    engine::Engine,
+    envfile::{self, EnvMap},
+    failure, say, services, util,
+};
+
+/// Compose services running off-the-shelf images worth scanning.
+const PULLED_SERVICES: &[(&str, &str)] = &[("proxy", "traefik"), ("db", "postgres")];
+
+/// One known vulnerability in an image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub id: String,
+    pub package: String,
+    pub installed: String,
+    pub fixed: Option<String>,
+    #[serde(serialize_with = "severity_name")]
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageScan {
+    pub role: &'static str,
+    pub image: String,
+    pub findings: Vec<Finding>,
+}
+
+impl ImageScan {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings.iter().filter(|finding| finding.severity == severity).count()
+    }
+}
+
+pub fn run(opts: ScanOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let engine = Engine::new(&deploy_dir);
+    let scanner = pick_scanner(opts.scanner)?;
+    let scans = scan_all(&engine, scanner)?;
+
+    say!(
+        "{}",
+        style(format!("  {:<36} {:>5} {:>5} {:>5} {:>5}", "IMAGE", "CRIT", "HIGH", "MED", "LOW")).bold()
+    );
+    for scan in &scans {
+        say!(
+            "  {:<36} {:>5} {:>5} {:>5} {:>5}",
+            scan.image,
+            scan.count(Severity::Critical),
+            scan.count(Severity::High),
+            scan.count(Severity::Medium),
+            scan.count(Severity::Low)
+        );
+    }
+    let critical: Vec<(&ImageScan, &Finding)> = scans
+        .iter()
+        .flat_map(|scan| scan.findings.iter().map(move |finding| (scan, finding)))
+        .filter(|(_, finding)| finding.severity == Severity::Critical)
+        .collect();
+    if !critical.is_empty() {
+        say!("\n{}", style("Critical vulnerabilities").red().bold());
+    }
+    for (scan, finding) in &critical {
+        say!(
+            "  {:<10} {:<20} {} {}{}",
+            scan.role,
+            finding.id,
+            finding.package,
+            finding.installed,
+            finding
+                .fixed
+                .as_deref()
+                .map(|fixed| format!(" (fixed in {})", fixed))
+                .unwrap_or_default()
+        );
+    }
+
+    let document = serde_json::json!({
+        "scanner": scanner_name(scanner),
+        "scanned_at": util::format_timestamp(util::unix_now()),
+        "images": scans,
+    });
+    if let Some(path) = &opts.report {
+        let json = serde_json::to_string_pretty(&document).context("failed to serialize the scan report")?;
+        util::write_string(path, &format!("{}\n", json))?;
+        say!("Scan report: {}", style(path.display()).dim());
+    }
+    util::set_result(serde_json::json!({
+        "scanner": scanner_name(scanner),
+        "images": scans
+            .iter()
+            .map(|scan| serde_json::json!({
+                "role": scan.role,
+                "image": scan.image,
+                "critical": scan.count(Severity::Critical),
+                "high": scan.count(Severity::High),
+                "medium": scan.count(Severity::Medium),
+                "low": scan.count(Severity::Low),
+            }))
+            .collect::<Vec<_>>(),
+        "report": opts.report,
+    }));
+    match opts.fail_on {
+        Some(threshold) => enforce(&scans, threshold),
+        None => Ok(()),
+    }
+}
+
+/// Scans the deployment's images and fails if any vulnerability reaches
+/// `threshold`; the gate of `upgrade --scan-fail-on`.
+pub fn check(engine: &Engine, threshold: Severity) -> Result<()> {
+    let scans = scan_all(engine, pick_scanner(None)?)?;
+    enforce(&scans, threshold)?;
+    say!(
+        "{}",
+        style(format!("No vulnerabilities rated {} or higher", threshold.name())).green()
+    );
+    Ok(())
+}
+
+fn enforce(scans: &[ImageScan], threshold: Severity) -> Result<()> {
+    let failing: Vec<String> = scans
+        .iter()
+        .filter_map(|scan| {
+            let count = scan.findings.iter().filter(|finding| finding.severity >= threshold).count();
+            (count > 0).then(|| format!("{} in {}", count, scan.image))
+        })
+        .collect();
+    if failing.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "vulnerabilities rated {} or higher: {}",
+        threshold.name(),
+        failing.join(", ")
+    )
+}
+
+fn scan_all(engine: &Engine, scanner: Scanner) -> Result<Vec<ImageScan>> {
+    let deploy_dir = engine.deploy_dir();
+    let env = envfile::load(deploy_dir)?;
+    let file = crate::compose::load(deploy_dir)?;
+    images(deploy_dir, &env, &file)
+        .into_iter()
+        .map(|(role, image)| {
+            let raw = match scanner {
+                Scanner::Trivy => engine.tool_output(
+                    "trivy",
+                    &["image", "--quiet", "--format", "json", "--scanners", "vuln", &image],
+                ),
+                Scanner::Grype => engine.tool_output("grype", &[&format!("docker:{}", image), "-o", "json", "-q"]),
+            }
+            .with_context(|| format!("failed to scan {}; build or pull it first", image))?;
+            let findings = match scanner {
+                Scanner::Trivy => parse_trivy(&raw),
+                Scanner::Grype => parse_grype(&raw),
+            }
+            .with_context(|| format!("failed to parse the {} report of {}", scanner_name(scanner), image))?;
+            Ok(ImageScan { role, image, findings })
+        })
+        .collect()
+}
+
+/// Images of the deployment: the two built here, then the pulled proxy and
+/// database images as the compose file names them.
+pub fn images(deploy_dir: &Path, env: &EnvMap, file: &ComposeFile) -> Vec<(&'static str, String)> {
+    let mut images = vec![
+        ("hub", build::local_image(deploy_dir, env, BuildService::Hub)),
+        ("user", build::local_image(deploy_dir, env, BuildService::User)),
+    ];
+    for (role, service) in PULLED_SERVICES {
+        let image = file.services.get(*service).and_then(|service| service.image.as_deref());
+        if let Some(image) = image.filter(|image| !image.contains('$')) {
+            images.push((role, image.to_string()));
+        }
+    }
+    images
+}
+
+fn pick_scanner(scanner: Option<Scanner>) -> Result<Scanner> {
+    match scanner {
+        Some(scanner) => Ok(scanner),
+        None if util::on_path("trivy") => Ok(Scanner::Trivy),
+        None if util::on_path("grype") => Ok(Scanner::Grype),
+        None => Err(failure::validation(
+            "no vulnerability scanner found; install trivy or grype, or pass --scanner",
+        )),
+    }
+}
+
+fn scanner_name(scanner: Scanner) -> &'static str {
+    match scanner {
+        Scanner::Trivy => "trivy",
+        Scanner::Grype => "grype",
+    }
+}
+
+/// Findings of `trivy image --format json`.
+pub fn parse_trivy(raw: &str) -> Result<Vec<Finding>> {
+    let report: Value = serde_json::from_str(raw)?;
+    let text = |value: &Value, key: &str| value.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+    let findings = report
+        .get("Results")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|result| result.get("Vulnerabilities").and_then(Value::as_array))
+        .flatten()
+        .map(|vulnerability| Finding {
+            id: text(vulnerability, "VulnerabilityID"),
+            package: text(vulnerability, "PkgName"),
+            installed: text(vulnerability, "InstalledVersion"),
+            fixed: Some(text(vulnerability, "FixedVersion")).filter(|fixed| !fixed.is_empty()),
+            severity: Severity::parse(&text(vulnerability, "Severity")),
+        });
+    Ok(dedup(findings))
+}
+
+/// Findings of `grype -o json`.
+pub fn parse_grype(raw: &str) -> Result<Vec<Finding>> {
+    let report: Value = serde_json::from_str(raw)?;
+    let text = |value: Option<&Value>| value.and_then(Value::as_str).unwrap_or_default().to_string();
+    let findings = report
+        .get("matches")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|entry| {
+            let vulnerability = entry.get("vulnerability");
+            let artifact = entry.get("artifact");
+            let fixed: Vec<&str> = vulnerability
+                .and_then(|vulnerability| vulnerability.pointer("/fix/versions"))
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            Finding {
+                id: text(vulnerability.and_then(|vulnerability| vulnerability.get("id"))),
+                package: text(artifact.and_then(|artifact| artifact.get("name"))),
+                installed: text(artifact.and_then(|artifact| artifact.get("version"))),
+                fixed: (!fixed.is_empty()).then(|| fixed.join(", ")),
+                severity: Severity::parse(&text(vulnerability.and_then(|vulnerability| vulnerability.get("severity")))),
+            }
+        });
+    Ok(dedup(findings))
+}
+
+/// One finding per vulnerability and package; scanners report a package
+/// again for every layer or path it occurs in.
+fn dedup(findings: impl Iterator<Item = Finding>) -> Vec<Finding> {
+    let mut seen = BTreeSet::new();
+    let mut unique: Vec<Finding> = findings
+        .filter(|finding| seen.insert((finding.id.clone(), finding.package.clone())))
+        .collect();
+    unique.sort_by(|a, b| b.severity.cmp(&a.severity).then_with(|| a.id.cmp(&b.id)));
+    unique
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Unknown => "unknown",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+
+    /// Severity as Trivy (`CRITICAL`) or Grype (`Critical`, `Negligible`) spell it.
+    pub fn parse(raw: &str) -> Self {
+        match raw.to_ascii_lowercase().as_str() {
+            "critical" => Severity::Critical,
+            "high" => Severity::High,
+            "medium" => Severity::Medium,
+            "low" | "negligible" => Severity::Low,
+            _ => Severity::Unknown,
+        }
+    }
+}
+
+fn severity_name<S: Serializer>(severity: &Severity, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(severity.name())
+}
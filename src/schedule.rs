@@ -0,0 +1,341 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{
+    cli::{ScheduleAddOptions, ScheduleCommand, ScheduleJob, ScheduleMaintenanceOptions, ScheduleRemoveOptions},
+    config::{self, AppConfig, MaintenanceWindow},
+    failure, say, services,
+    systemd::{self, Scope},
+    util,
+};
+
+/// Prefix of every timer managed by `mvre-hub schedule`.
+const TIMER_PREFIX: &str = "mvre-hub-job-";
+
+/// Jobs installed by `schedule maintenance`, suffixed with the deployment's
+/// name.
+const MAINTENANCE_JOBS: [&str; 3] = ["maintenance-announce", "maintenance-stop", "maintenance-start"];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+pub fn run(command: ScheduleCommand, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    match command {
+        ScheduleCommand::Add(opts) => add(opts, app_config),
+        ScheduleCommand::List => list(),
+        ScheduleCommand::Remove(opts) => remove(opts),
+        ScheduleCommand::Maintenance(opts) => maintenance(opts, config_path, app_config),
+    }
+}
+
+/// A time of a recurring maintenance window: weekly on a weekday, or daily.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceTime {
+    /// 0 for Monday; `None` for every day.
+    pub weekday: Option<usize>,
+    /// Minutes after midnight.
+    pub minutes: u32,
+}
+
+impl MaintenanceTime {
+    /// Parses `Sat 02:00` or `02:00`; weekday names are case-insensitive.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("invalid maintenance time '{}' (use e.g. \"Sat 02:00\" or \"02:00\")", value);
+        let mut parts = value.split_whitespace();
+        let (day, time) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(time), None, None) => (None, time),
+            (Some(day), Some(time), None) => (Some(day), time),
+            _ => return Err(invalid()),
+        };
+        let weekday = match day {
+            Some(day) => Some(
+                WEEKDAYS
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(day.get(..3).unwrap_or(day)))
+                    .ok_or_else(invalid)?,
+            ),
+            None => None,
+        };
+        let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+        let hours: u32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            weekday,
+            minutes: hours * 60 + minutes,
+        })
+    }
+
+    /// systemd `OnCalendar` expression.
+    pub fn calendar(&self) -> String {
+        let time = format!("*-*-* {:02}:{:02}:00", self.minutes / 60, self.minutes % 60);
+        match self.weekday {
+            Some(day) => format!("{} {}", WEEKDAYS[day], time),
+            None => time,
+        }
+    }
+
+    /// The same time `minutes` earlier, moving to the previous day if needed.
+    pub fn earlier_by(&self, minutes: u32) -> Self {
+        let day = 24 * 60;
+        let shifted = self.minutes as i64 - i64::from(minutes % day);
+        let wrapped = shifted < 0;
+        Self {
+            weekday: self.weekday.map(|weekday| if wrapped { (weekday + 6) % 7 } else { weekday }),
+            minutes: shifted.rem_euclid(i64::from(day)) as u32,
+        }
+    }
+}
+
+impl fmt::Display for MaintenanceTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(day) = self.weekday {
+            write!(f, "{} ", WEEKDAYS[day])?;
+        }
+        write!(f, "{:02}:{:02}", self.minutes / 60, self.minutes % 60)
+    }
+}
+
+impl ScheduleJob {
+    fn subcommand(self) -> &'static str {
+        match self {
+            ScheduleJob::CertCheck => "health",
+            ScheduleJob::PruneVolumes => "prune volumes",
+            ScheduleJob::GuestPrune => "guest prune",
+            ScheduleJob::UsageSample => "report sample",
+        }
+    }
+}
+
+fn add(opts: ScheduleAddOptions, app_config: &AppConfig) -> Result<()> {
+    validate_name(&opts.name)?;
+    let scope = scope_for(opts.user)?;
+    let unit = format!("{}{}", TIMER_PREFIX, opts.name);
+    if systemd::timer_installed(scope, &unit) {
+        return Err(failure::validation(format!("Job '{}' already exists; remove it first", opts.name)));
+    }
+
+    let command = match (opts.job, &opts.command) {
+        (Some(job), None) => {
+            let deploy_dir = services::resolve_deploy_dir(app_config)?;
+            hub_command(&deploy_dir, job.subcommand())?
+        }
+        (None, Some(command)) => shell_exec(command),
+        _ => return Err(failure::validation("Pass either --job or --command")),
+    };
+
+    systemd::install_timer(
+        scope,
+        &unit,
+        &format!("MVRE-Hub scheduled job {}", opts.name),
+        &command,
+        &opts.on_calendar,
+    )?;
+    util::set_result(serde_json::json!({ "name": opts.name, "on_calendar": opts.on_calendar, "command": command }));
+    say!(
+        "{}",
+        style(format!("Scheduled {} ({}): {}", opts.name, opts.on_calendar, command)).green()
+    );
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    let timers = systemd::list_timers(TIMER_PREFIX)?;
+    util::set_result(serde_json::json!({ "jobs": timers }));
+    if timers.is_empty() {
+        say!("{}", style("No scheduled jobs").dim());
+        return Ok(());
+    }
+    for timer in timers {
+        let scope = match timer.scope {
+            Scope::System => "system",
+            Scope::User => "user",
+        };
+        say!(
+            "  {:<20} {:<18} {:<7} {}",
+            timer.name.trim_start_matches(TIMER_PREFIX),
+            timer.on_calendar,
+            scope,
+            style(timer.command).dim()
+        );
+    }
+    Ok(())
+}
+
+fn remove(opts: ScheduleRemoveOptions) -> Result<()> {
+    let unit = format!("{}{}", TIMER_PREFIX, opts.name);
+    let scope = [Scope::System, Scope::User]
+        .into_iter()
+        .find(|scope| systemd::timer_installed(*scope, &unit))
+        .with_context(|| format!("Job '{}' not found", opts.name))?;
+    if scope == Scope::System && !util::is_root() {
+        return Err(failure::permission("Root required to remove system timers"));
+    }
+    systemd::remove_timer(scope, &unit)?;
+    say!("{}", style(format!("Removed job {}", opts.name)).cyan());
+    Ok(())
+}
+
+/// Installs timers that announce the window, stop the hub, and start it
+/// again (clearing the announcement); they replace an earlier window of the
+/// same deployment.
+fn maintenance(opts: ScheduleMaintenanceOptions, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    let (exe, deploy_dir) = pinned_paths(&services::resolve_deploy_dir(app_config)?)?;
+    let deployment = app_config.register(&deploy_dir, None);
+    // Timers from before windows were per deployment ran against the active one.
+    let legacy = app_config.active.as_deref() == Some(deployment.as_str());
+    if opts.remove {
+        remove_maintenance_timers(&deployment, legacy)?;
+        app_config.deployments.get_mut(&deployment).expect("registered deployment").maintenance = None;
+        config::save(config_path, app_config)?;
+        say!("{}", style("Maintenance window removed").cyan());
+        util::set_result(serde_json::json!({ "maintenance": null }));
+        return Ok(());
+    }
+
+    let (Some(stop), Some(start)) = (&opts.stop, &opts.start) else {
+        return Err(failure::validation("Pass --stop and --start, or --remove"));
+    };
+    let stop = MaintenanceTime::parse(stop)?;
+    let start = MaintenanceTime::parse(start)?;
+    if stop.weekday.is_some() != start.weekday.is_some() {
+        return Err(failure::validation("Give both --stop and --start with a weekday, or both without one"));
+    }
+    if stop == start {
+        return Err(failure::validation("--stop and --start must differ"));
+    }
+    let notice = util::parse_duration(&opts.notice).context("invalid --notice")?;
+    if notice >= 86_400 {
+        return Err(failure::validation("--notice must be shorter than a day"));
+    }
+    let announce = stop.earlier_by((notice / 60) as u32);
+    let message = opts.message.unwrap_or_else(|| {
+        format!(
+            "Scheduled maintenance: the hub stops at {} and is back at {} (server time). Please save your work.",
+            stop, start
+        )
+    });
+
+    let scope = scope_for(opts.user)?;
+    remove_maintenance_timers(&deployment, legacy)?;
+    let hub = format!(
+        "{} --deploy-dir {}",
+        util::shell_quote(&exe.to_string_lossy()),
+        util::shell_quote(&deploy_dir.to_string_lossy())
+    );
+    let jobs = [
+        (MAINTENANCE_JOBS[0], hub_command(&deploy_dir, &format!("announce {}", exec_arg(&message)))?, announce),
+        (MAINTENANCE_JOBS[1], hub_command(&deploy_dir, "stop")?, stop),
+        (MAINTENANCE_JOBS[2], shell_exec(&format!("{} start && {} announce --clear", hub, hub)), start),
+    ];
+    for (name, command, time) in &jobs {
+        systemd::install_timer(
+            scope,
+            &maintenance_unit(name, &deployment),
+            &format!("MVRE-Hub {} of {}", name.replace('-', " "), deployment),
+            command,
+            &time.calendar(),
+        )?;
+    }
+
+    let window = MaintenanceWindow {
+        stop: stop.to_string(),
+        start: start.to_string(),
+        notice: opts.notice,
+    };
+    say!(
+        "{}",
+        style(format!("Maintenance window {} – {} scheduled, announced at {}", window.stop, window.start, announce)).green()
+    );
+    util::set_result(serde_json::json!({ "maintenance": window, "announce_at": announce.to_string(), "message": message }));
+    app_config.deployments.get_mut(&deployment).expect("registered deployment").maintenance = Some(window);
+    config::save(config_path, app_config)
+}
+
+/// Unit of maintenance `job` for the deployment named `deployment`.
+pub fn maintenance_unit(job: &str, deployment: &str) -> String {
+    format!("{}{}-{}", TIMER_PREFIX, job, deployment)
+}
+
+/// Removes the deployment's maintenance timers and, with `legacy`, the
+/// unsuffixed ones of older releases.
+fn remove_maintenance_timers(deployment: &str, legacy: bool) -> Result<()> {
+    for name in MAINTENANCE_JOBS {
+        let mut units = vec![maintenance_unit(name, deployment)];
+        if legacy {
+            units.push(format!("{}{}", TIMER_PREFIX, name));
+        }
+        for unit in &units {
+            for scope in [Scope::System, Scope::User] {
+                if systemd::timer_installed(scope, unit) {
+                    systemd::remove_timer(scope, unit)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// ExecStart line running `mvre-hub {args}` against `deploy_dir`, so the
+/// job keeps acting on that deployment whichever one is active later.
+pub fn hub_command(deploy_dir: &Path, args: &str) -> Result<String> {
+    let (exe, deploy_dir) = pinned_paths(deploy_dir)?;
+    Ok(format!(
+        "{} --deploy-dir {} {}",
+        exe.display(),
+        exec_arg(&deploy_dir.display().to_string()),
+        args
+    ))
+}
+
+fn pinned_paths(deploy_dir: &Path) -> Result<(PathBuf, PathBuf)> {
+    let exe = std::env::current_exe().context("failed to resolve mvre-hub binary path")?;
+    let deploy_dir = std::path::absolute(deploy_dir)
+        .with_context(|| format!("failed to resolve {}", deploy_dir.display()))?;
+    Ok((exe, deploy_dir))
+}
+
+/// One ExecStart argument, quoted so systemd passes it through verbatim.
+pub fn exec_arg(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
+
+/// ExecStart does not go through a shell; wrap custom commands so pipes and
+/// redirects work, escaping what systemd would otherwise interpret.
+pub fn shell_exec(command: &str) -> String {
+    let escaped = command
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("/bin/sh -c \"{}\"", escaped)
+}
+
+/// System units need root; `--user` picks the invoking user's manager.
+pub fn scope_for(user: bool) -> Result<Scope> {
+    if user {
+        return Ok(Scope::User);
+    }
+    if !util::is_root() {
+        return Err(failure::permission("Root required for system timers; use --user for a user timer"));
+    }
+    Ok(Scope::System)
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') {
+        return Err(failure::validation(format!("Invalid job name '{}' (use letters, digits, '-', '_')", name)));
+    }
+    Ok(())
+}
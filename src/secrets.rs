@@ -0,0 +1,179 @@
+use std::{collections::BTreeSet, path::Path, thread, time::Duration};
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Password};
+
+use crate::{
+    cli::{SecretsCommand, SecretsRotateOptions},
+    compose,
+    config::AppConfig,
+    db::Database,
+    engine::Engine,
+    envfile, failure, say, services, util,
+};
+
+/// Services that read each secret, in the order they are recreated: the
+/// database before its clients.
+const OAUTH_SERVICES: &[&str] = &["jupyterhub"];
+const DB_SERVICES: &[&str] = &["postgres", "jupyterhub", "mlflow"];
+const POSTGRES_READY_SECS: u64 = 60;
+
+pub fn run(command: SecretsCommand, app_config: &AppConfig) -> Result<()> {
+    match command {
+        SecretsCommand::Rotate(opts) => rotate(opts, app_config),
+    }
+}
+
+/// Rotates the chosen secrets. `.env` is written first and put back if the
+/// database refuses the new password, so the two never disagree.
+fn rotate(opts: SecretsRotateOptions, app_config: &AppConfig) -> Result<()> {
+    let (oauth, db) = (opts.oauth || opts.all, opts.db || opts.all);
+    if !oauth && !db {
+        return Err(failure::validation("choose what to rotate: --oauth, --db, or --all"));
+    }
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let engine = Engine::new(&deploy_dir);
+    let env_path = deploy_dir.join(envfile::ENV_FILE);
+    let original = util::read_to_string(&env_path)?;
+    let env = envfile::parse(&original);
+
+    let mut updated = original.clone();
+    let mut rotated = Vec::new();
+    let mut affected: Vec<&str> = Vec::new();
+    if oauth {
+        if envfile::get(&env, "OAUTH_CLIENT_SECRET").is_none() {
+            return Err(failure::validation(
+                "this deployment has no OAuth client secret; it logs in without OAuth",
+            ));
+        }
+        let secret = match opts.client_secret.clone().filter(|secret| !secret.is_empty()) {
+            Some(secret) => secret,
+            None => {
+                let prompt = "New OAuth client secret";
+                util::ensure_interactive(prompt, "pass --client-secret or set MVRE_HUB_CLIENT_SECRET")?;
+                Password::with_theme(&ColorfulTheme::default())
+                    .with_prompt(prompt)
+                    .interact()?
+            }
+        };
+        updated = envfile::with_value(&updated, "OAUTH_CLIENT_SECRET", &secret);
+        rotated.push("oauth");
+        affected.extend(OAUTH_SERVICES);
+    }
+    let mut role = None;
+    if db {
+        let (user, name) = match Database::from_env(&deploy_dir, &env) {
+            Database::Compose { user, name } => (user, name),
+            Database::External { .. } => {
+                return Err(failure::validation(
+                    "the hub database is an external Postgres server; change the password there and in JUPYTERHUB_DB_URL",
+                ))
+            }
+            Database::Sqlite { .. } => {
+                return Err(failure::validation("the hub database is SQLite, which has no password to rotate"))
+            }
+        };
+        let password = util::random_token(24)?;
+        updated = envfile::with_value(&updated, "DB_PASSWORD", &password);
+        role = Some((user, name, password));
+        rotated.push("db");
+        affected.extend(DB_SERVICES);
+    }
+
+    let running: BTreeSet<String> = engine
+        .ps()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|container| container.state == "running")
+        .map(|container| container.service)
+        .collect();
+    write_env(&env_path, &updated)?;
+    if let Some((user, name, password)) = &role {
+        let changed = change_role_password(&engine, running.contains("postgres"), user, name, password);
+        if let Err(err) = changed {
+            write_env(&env_path, &original)?;
+            return Err(err.context("failed to change the Postgres password; .env was left as it was"));
+        }
+        say!("{}", style(format!("Postgres password of {} changed", user)).green());
+    }
+    if oauth {
+        say!("{}", style("OAuth client secret replaced").green());
+    }
+
+    let defined: BTreeSet<String> = compose::load(&deploy_dir)?.services.into_keys().collect();
+    let restarted = restart_order(&affected, &defined, &running);
+    for service in &restarted {
+        engine
+            .compose_step(
+                &["up", "-d", "--no-deps", "--force-recreate", service],
+                &format!("Restarting {}", service),
+            )
+            .with_context(|| format!("failed to restart {} with the new secrets", service))?;
+    }
+    if restarted.is_empty() {
+        say!("{}", style("Nothing was running; the new secrets apply at the next start").dim());
+    } else {
+        say!("Restarted {}", restarted.join(", "));
+    }
+    util::set_result(serde_json::json!({ "rotated": rotated, "restarted": restarted }));
+    Ok(())
+}
+
+/// The running services among `affected`, once each, in the order given.
+pub fn restart_order(affected: &[&str], defined: &BTreeSet<String>, running: &BTreeSet<String>) -> Vec<String> {
+    let mut order: Vec<String> = Vec::new();
+    for service in affected {
+        if defined.contains(*service) && running.contains(*service) && !order.iter().any(|seen| seen == service) {
+            order.push(service.to_string());
+        }
+    }
+    order
+}
+
+/// `ALTER ROLE` setting `password`, quoted for Postgres.
+pub fn alter_role_sql(user: &str, password: &str) -> String {
+    format!(
+        "ALTER ROLE \"{}\" WITH PASSWORD '{}';\n",
+        user.replace('"', "\"\""),
+        password.replace('\'', "''")
+    )
+}
+
+/// Changes the password over the container's local socket, which needs none.
+/// A stopped database is started for it and stopped again afterwards.
+fn change_role_password(engine: &Engine, running: bool, user: &str, name: &str, password: &str) -> Result<()> {
+    if running {
+        return alter_role(engine, user, name, password);
+    }
+    engine.compose_step(&["up", "-d", "--no-deps", "postgres"], "Starting postgres")?;
+    let deadline = util::unix_now() + POSTGRES_READY_SECS;
+    let result = loop {
+        if engine
+            .compose_output(&["exec", "-T", "postgres", "pg_isready", "-U", user, "-d", name])
+            .is_ok()
+        {
+            break alter_role(engine, user, name, password);
+        }
+        if util::unix_now() >= deadline {
+            break Err(anyhow::anyhow!("postgres did not accept connections within {}s", POSTGRES_READY_SECS));
+        }
+        thread::sleep(Duration::from_secs(2));
+    };
+    engine.compose(&["stop", "postgres"]).ok();
+    result
+}
+
+fn alter_role(engine: &Engine, user: &str, name: &str, password: &str) -> Result<()> {
+    // The statement goes through stdin so the password never shows in a process list.
+    engine.compose_input(
+        &["exec", "-T", "postgres", "psql", "-q", "-v", "ON_ERROR_STOP=1", "-U", user, "-d", name],
+        alter_role_sql(user, password).as_bytes(),
+    )
+}
+
+fn write_env(path: &Path, contents: &str) -> Result<()> {
+    util::atomic_write(path, contents.as_bytes())?;
+    util::set_file_mode(path, 0o600).ok();
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use std::path::Path;
+
+use crate::cli::SelinuxLabels;
+
+const ENFORCE_FILE: &str = "/sys/fs/selinux/enforce";
+
+/// Whether the deployment should relabel its mounts for `labels`.
+pub fn resolve(labels: SelinuxLabels) -> bool {
+    match labels {
+        SelinuxLabels::Auto => enforcing(Path::new(ENFORCE_FILE)),
+        SelinuxLabels::On => true,
+        SelinuxLabels::Off => false,
+    }
+}
+
+/// Reads the SELinux `enforce` flag; hosts without SELinux have no such file.
+pub fn enforcing(enforce_file: &Path) -> bool {
+    std::fs::read_to_string(enforce_file)
+        .map(|value| value.trim() == "1")
+        .unwrap_or(false)
+}
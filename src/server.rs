@@ -0,0 +1,156 @@
+use std::{thread, time::Duration};
+
+use anyhow::Result;
+use console::style;
+
+use crate::{
+    cli::{ServerCommand, ServerOptions, ServerStartOptions},
+    config::AppConfig,
+    hubapi::{HubClient, User},
+    say, services, util,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Users stop servers over minutes, not seconds.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+pub fn run(command: ServerCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let client = HubClient::from_deployment(&deploy_dir)?;
+    match command {
+        ServerCommand::Start(opts) => start(&client, opts),
+        ServerCommand::Stop(opts) => stop(&client, opts),
+    }
+}
+
+fn start(client: &HubClient, opts: ServerStartOptions) -> Result<()> {
+    let timeout = opts.wait.as_deref().map(util::parse_duration).transpose()?;
+    let ServerOptions { users, server_name } = opts.server;
+    let mut requested = Vec::new();
+    let mut failed = Vec::new();
+    for name in &users {
+        let result = client.get_user(name).and_then(|user| {
+            if is_active(&user, &server_name) {
+                say!("{} {}", style(label(name, &server_name)).bold(), style("already running").dim());
+                Ok(())
+            } else {
+                client.start_server(name, &server_name)?;
+                say!("{} {}", style(label(name, &server_name)).bold(), style("spawn requested").green());
+                requested.push(name.clone());
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            say!("{} {:#}", style(label(name, &server_name)).bold(), style(&err).red());
+            failed.push(name.clone());
+        }
+    }
+
+    if let Some(timeout) = timeout {
+        let pending = wait_ready(client, requested.clone(), &server_name, timeout);
+        if !pending.is_empty() {
+            say!("{}", style(format!("Not ready in time: {}", pending.join(", "))).yellow());
+            failed.extend(pending);
+        }
+    }
+
+    util::set_result(serde_json::json!({
+        "server_name": server_name,
+        "requested": requested,
+        "failed": failed,
+    }));
+    if !failed.is_empty() {
+        anyhow::bail!("{} server(s) could not be started", failed.len());
+    }
+    Ok(())
+}
+
+fn stop(client: &HubClient, opts: ServerOptions) -> Result<()> {
+    let mut stopped = Vec::new();
+    let mut failed = Vec::new();
+    for name in &opts.users {
+        let result = client.get_user(name).and_then(|user| {
+            if is_active(&user, &opts.server_name) {
+                client.stop_server(name, &opts.server_name)?;
+                say!("{} {}", style(label(name, &opts.server_name)).bold(), style("stopped").green());
+                stopped.push(name.clone());
+            } else {
+                say!("{} {}", style(label(name, &opts.server_name)).bold(), style("not running").dim());
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            say!("{} {:#}", style(label(name, &opts.server_name)).bold(), style(&err).red());
+            failed.push(name.clone());
+        }
+    }
+    util::set_result(serde_json::json!({
+        "server_name": opts.server_name,
+        "stopped": stopped,
+        "failed": failed,
+    }));
+    if !failed.is_empty() {
+        anyhow::bail!("{} server(s) could not be stopped", failed.len());
+    }
+    Ok(())
+}
+
+/// Polls the hub until every server is ready; returns the users whose
+/// server was not ready within `timeout` seconds.
+fn wait_ready(client: &HubClient, mut pending: Vec<String>, server_name: &str, timeout: u64) -> Vec<String> {
+    let spinner = crate::progress::Spinner::start("Waiting for servers");
+    let deadline = util::unix_now() + timeout;
+    loop {
+        pending.retain(|name| match client.get_user(name) {
+            Ok(user) => !is_ready(&user, server_name),
+            Err(_) => true,
+        });
+        if pending.is_empty() || util::unix_now() >= deadline {
+            return pending;
+        }
+        spinner.set_detail(&format!("{} pending", pending.len()));
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Polls the hub until no user server is left; returns the servers still
+/// there after `timeout` seconds. Polls that fail keep the last answer.
+pub fn wait_idle(client: &HubClient, mut active: Vec<String>, timeout: u64) -> Vec<String> {
+    let spinner = crate::progress::Spinner::start("Waiting for user servers to stop");
+    let deadline = util::unix_now() + timeout;
+    loop {
+        if active.is_empty() || util::unix_now() >= deadline {
+            return active;
+        }
+        spinner.set_detail(&format!("{} running", active.len()));
+        thread::sleep(IDLE_POLL_INTERVAL);
+        if let Ok(users) = client.list_users() {
+            active = active_servers(&users);
+        }
+    }
+}
+
+/// Every server the hub has, as `user` or `user/server`.
+pub fn active_servers(users: &[User]) -> Vec<String> {
+    users
+        .iter()
+        .flat_map(|user| user.servers.keys().map(move |server_name| label(&user.name, server_name)))
+        .collect()
+}
+
+/// Whether the hub has the server, ready or on its way up or down.
+pub fn is_active(user: &User, server_name: &str) -> bool {
+    user.servers.contains_key(server_name)
+}
+
+pub fn is_ready(user: &User, server_name: &str) -> bool {
+    user.servers.get(server_name).is_some_and(|server| server.ready)
+}
+
+fn label(user: &str, server_name: &str) -> String {
+    if server_name.is_empty() {
+        user.to_string()
+    } else {
+        format!("{}/{}", user, server_name)
+    }
+}
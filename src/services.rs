@@ -1,68 +1,203 @@
-use std::{
-    path::{Path, PathBuf},
-    process::{Command, Stdio},
-};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use console::style;
 
 use crate::{
-    cli::CleanOptions,
+    announce, bluegreen, build,
+    cli::{CleanOptions, StartOptions, StopOptions, UpgradeOptions},
+    compose,
     config::{self, AppConfig},
-    systemd,
-    util,
+    deploy,
+    engine::{Engine, Remote},
+    failure,
+    envfile, health,
+    hubapi::HubClient,
+    init, open, proxy, say, scan, server, snapshot, tls, util,
 };
 
-pub fn start(config_path: &Path, app_config: &AppConfig) -> Result<()> {
+/// Helper services that only exist to build an image and exit immediately.
+const BUILD_ONLY_SERVICES: &[&str] = &["user-image"];
+
+pub fn start(opts: &StartOptions, config_path: &Path, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = resolve_deploy_dir(app_config)?;
+    let engine = Engine::new(&deploy_dir);
+    if build::build_changed(&engine)?.is_empty() {
+        say!("{}", style("Images up to date, skipping build").dim());
+    }
+    up(&engine)?;
+    if opts.wait {
+        let timeout = util::parse_duration(&opts.wait_timeout).context("invalid --wait-timeout")?;
+        health::wait_until_ready(&deploy_dir, timeout)?;
+    }
+
+    say!("{}", style("Drift engaged").green());
+    say!("Using deployment at {}", style(deploy_dir.display()).dim());
+
+    let mut updated = app_config.clone();
+    updated.remember(&deploy_dir);
+    config::save(config_path, &updated)?;
+
+    if opts.open {
+        open::summary(&deploy_dir, true)?;
+    }
+    Ok(())
+}
+
+pub fn upgrade(opts: UpgradeOptions, config_path: &Path, app_config: &AppConfig) -> Result<()> {
     let deploy_dir = resolve_deploy_dir(app_config)?;
-    run_compose(&deploy_dir, &["build", "jupyterhub", "user-image"])
-        .context("failed to build images")?;
-    run_compose(&deploy_dir, &["up", "-d", "jupyterhub", "traefik"])
-        .context("failed to start services")?;
+    if opts.blue_green {
+        bluegreen::ensure_no_leftover(&deploy_dir)?;
+    }
+    // New JupyterHub releases migrate the database schema on start, so the
+    // dump is what makes an upgrade reversible; no dump, no upgrade.
+    let with_db = !opts.skip_db_snapshot;
+    let snapshot = snapshot::create(&deploy_dir, "pre-upgrade", with_db).context(
+        "Pre-upgrade database backup failed, nothing was changed. Make sure the database is running (mvre-hub start), or pass --skip-db-snapshot to upgrade without it",
+    )?;
+    let rollback = if snapshot.includes_db { "mvre-hub rollback --with-db" } else { "mvre-hub rollback" };
+    say!("Snapshot {} saved (roll back with {})", style(&snapshot.id).dim(), style(rollback).cyan());
+    if with_db && !snapshot.includes_db {
+        say!("{}", style("No hub database found; the snapshot holds configuration only").dim());
+    }
+    // Templates of this release, with the operator's edits merged in.
+    deploy::regenerate(&deploy_dir, opts.on_conflict)?;
+    if let Some(version) = opts.proxy_version {
+        proxy::migrate(&deploy_dir, version)?;
+    }
 
-    println!("{}", style("Drift engaged").green());
-    println!("Using deployment at {}", style(deploy_dir.display()).dim());
+    let engine = Engine::new(&deploy_dir);
+    engine
+        .compose_step(&["pull", "--ignore-buildable"], "Pulling images")
+        .context("failed to pull images")?;
+    build::build(&engine, &["jupyterhub", "user-image"], false, true).context("failed to rebuild images")?;
+    build::record(&deploy_dir, &["jupyterhub", "user-image"])?;
+    if let Some(threshold) = opts.scan_fail_on {
+        scan::check(&engine, threshold)
+            .context("the rebuilt images failed the vulnerability scan; the running services were left as they are")?;
+    }
+    if opts.blue_green {
+        bluegreen::switch(&engine, opts.green_port)?;
+    }
+    // Recreates whatever else changed; the hub is already on the new image.
+    up(&engine)?;
+
+    say!("{}", style("Drift upgraded").green());
 
     let mut updated = app_config.clone();
-    updated.last_deploy_dir = Some(deploy_dir);
+    updated.remember(&deploy_dir);
     config::save(config_path, &updated)?;
 
     Ok(())
 }
 
-pub fn stop(config_path: &Path, app_config: &AppConfig) -> Result<()> {
+/// Brings up every long-running service defined in the compose file.
+fn up(engine: &Engine) -> Result<()> {
+    let file = compose::load(engine.deploy_dir())?;
+    let mut args = vec!["up", "-d"];
+    args.extend(
+        file.services
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !BUILD_ONLY_SERVICES.contains(name)),
+    );
+    engine
+        .compose_step(&args, "Starting services")
+        .context("failed to start services")
+}
+
+pub fn stop(opts: StopOptions, config_path: &Path, app_config: &AppConfig) -> Result<()> {
     let deploy_dir = resolve_deploy_dir(app_config)?;
-    run_compose(&deploy_dir, &["down"]).context("failed to stop services")?;
+    let previous_announcement = announce::current(&deploy_dir);
+    let announced = if opts.when_idle {
+        let max_wait = util::parse_duration(&opts.max_wait).context("invalid --max-wait")?;
+        wait_for_idle(&deploy_dir, &opts, max_wait)?
+    } else {
+        false
+    };
+    Engine::new(&deploy_dir)
+        .compose_step(&["down"], "Stopping services")
+        .context("failed to stop services")?;
+    // The stop notice would greet users after the next start; an earlier
+    // banner comes back instead.
+    if announced {
+        match previous_announcement {
+            Some(previous) => announce::publish(&deploy_dir, &previous)?,
+            None => {
+                announce::clear(&deploy_dir)?;
+            }
+        }
+    }
 
-    println!("{}", style("Drift paused").yellow());
-    println!("Using deployment at {}", style(deploy_dir.display()).dim());
+    say!("{}", style("Drift paused").yellow());
+    say!("Using deployment at {}", style(deploy_dir.display()).dim());
 
     let mut updated = app_config.clone();
-    updated.last_deploy_dir = Some(deploy_dir);
+    updated.remember(&deploy_dir);
     config::save(config_path, &updated)?;
 
     Ok(())
 }
 
+/// Announces the coming stop and waits, at most `max_wait` seconds, until
+/// users have stopped their servers or the culler has. Returns whether the
+/// announcement was published. A hub that cannot be asked is stopped at once.
+fn wait_for_idle(deploy_dir: &Path, opts: &StopOptions, max_wait: u64) -> Result<bool> {
+    let users = HubClient::from_deployment(deploy_dir).and_then(|client| Ok((client.list_users()?, client)));
+    let (users, client) = match users {
+        Ok(found) => found,
+        Err(err) => {
+            say!("{}", style(format!("Cannot list user servers ({:#}); stopping now", err)).yellow());
+            return Ok(false);
+        }
+    };
+    let active = server::active_servers(&users);
+    if active.is_empty() {
+        say!("{}", style("No user servers running").dim());
+        return Ok(false);
+    }
+
+    let message = opts.message.clone().unwrap_or_else(|| {
+        format!(
+            "The hub is stopping for maintenance. Please save your work and stop your server; running servers are stopped in at most {}.",
+            opts.max_wait
+        )
+    });
+    announce::publish(deploy_dir, &message)?;
+    say!("Announced the stop; waiting for {} server(s): {}", active.len(), active.join(", "));
+    let remaining = server::wait_idle(&client, active, max_wait);
+    if remaining.is_empty() {
+        say!("{}", style("All user servers have stopped").green());
+    } else {
+        say!(
+            "{}",
+            style(format!("Still running after {}, stopping anyway: {}", opts.max_wait, remaining.join(", "))).yellow()
+        );
+    }
+    util::set_result(serde_json::json!({ "still_running": remaining }));
+    Ok(true)
+}
+
 pub fn clean(opts: CleanOptions, config_path: &Path, app_config: &AppConfig) -> Result<()> {
     if !opts.full_ice {
-        anyhow::bail!("Safety lock engaged. Use --full-ice to confirm cleanup");
+        return Err(failure::validation("Safety lock engaged. Use --full-ice to confirm cleanup"));
     }
 
     let deploy_dir = resolve_deploy_dir(app_config)?;
 
-    run_compose(&deploy_dir, &["down", "-v", "--rmi", "all"])
+    Engine::new(&deploy_dir)
+        .compose(&["down", "-v", "--rmi", "all"])
         .context("failed to stop services before cleanup")?;
     std::fs::remove_dir_all(&deploy_dir).with_context(|| format!("failed to remove {}", deploy_dir.display()))?;
 
-    if util::is_root() {
-        let _ = systemd::remove_service();
+    if let Err(err) = init::remove_service() {
+        tracing::warn!("{:#}", err);
     }
 
-    println!("{}", style("Environment cleared").cyan());
+    say!("{}", style("Environment cleared").cyan());
 
     let mut updated = app_config.clone();
-    updated.last_deploy_dir = None;
+    updated.forget(&deploy_dir);
     config::save(config_path, &updated)?;
 
     Ok(())
@@ -70,49 +205,89 @@ pub fn clean(opts: CleanOptions, config_path: &Path, app_config: &AppConfig) ->
 
 pub fn status(app_config: &AppConfig) -> Result<()> {
     let deploy_dir = resolve_deploy_dir(app_config)?;
-    let output = Command::new("docker-compose")
-        .args(["ps"])
-        .current_dir(&deploy_dir)
-        .output()
+    let containers = Engine::new(&deploy_dir)
+        .ps()
         .context("failed to query docker-compose status")?;
 
-    if output.status.success() {
-        println!("{}", style("Current status").cyan().bold());
-        println!("{}", String::from_utf8_lossy(&output.stdout));
-        Ok(())
-    } else {
-        anyhow::bail!(
-            "docker-compose status failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
+    say!("{}", style("Current status").cyan().bold());
+    if containers.is_empty() {
+        say!("{}", style("No containers found").dim());
+    }
+    for container in &containers {
+        let state = match container.state.as_str() {
+            "running" => style(container.state.clone()).green(),
+            "exited" | "dead" => style(container.state.clone()).red(),
+            _ => style(container.state.clone()).yellow(),
+        };
+        let health = if container.health.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", container.health)
+        };
+        say!("  {:<14} {}{}  {}", container.service, state, health, style(&container.name).dim());
     }
-}
 
-fn run_compose(deploy_dir: &Path, args: &[&str]) -> Result<()> {
-    let status = Command::new("docker-compose")
-        .args(args)
-        .current_dir(deploy_dir)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .context("failed to invoke docker-compose")?;
-
-    if status.success() {
-        Ok(())
-    } else {
-        anyhow::bail!("docker-compose exited with status {}", status)
+    let autostart = init::installed_service()?;
+    if let Some((name, state)) = &autostart {
+        say!("Auto-start: {} via {}", state.detail, name);
+    }
+    let env = envfile::load(&deploy_dir).unwrap_or_default();
+    let acme_staging = envfile::get(&env, "ACME_STAGING") == Some("true");
+    let self_signed = envfile::get(&env, "TLS_MODE") == Some("self-signed");
+    if acme_staging {
+        say!("Certificates: {}", style("Let's Encrypt staging, not trusted by browsers").yellow());
+    } else if self_signed {
+        say!(
+            "Certificates: {} (clients must trust {}/ca.crt)",
+            style("self-signed").yellow(),
+            tls::LOCAL_TLS_DIR
+        );
+    }
+    let remote = Remote::from_env(&env).describe();
+    if let Some(remote) = &remote {
+        say!("Docker: {}", remote);
     }
+    let absolute = std::path::absolute(&deploy_dir).unwrap_or_else(|_| deploy_dir.clone());
+    let maintenance = app_config.maintenance(&absolute);
+    if let Some(window) = maintenance {
+        say!(
+            "Maintenance: {} – {} (announced {} before)",
+            window.stop,
+            window.start,
+            window.notice
+        );
+    }
+
+    util::set_result(serde_json::json!({
+        "deploy_dir": deploy_dir,
+        "containers": containers,
+        "autostart": autostart.map(|(name, state)| serde_json::json!({ "manager": name, "state": state })),
+        "maintenance": maintenance,
+        "acme_staging": acme_staging,
+        "self_signed": self_signed,
+        "docker_remote": remote,
+    }));
+    Ok(())
 }
 
-fn resolve_deploy_dir(app_config: &AppConfig) -> Result<PathBuf> {
-    if let Some(path) = &app_config.last_deploy_dir {
+pub fn resolve_deploy_dir(app_config: &AppConfig) -> Result<PathBuf> {
+    if let Some(path) = &app_config.deploy_dir_override {
+        if !path.join(envfile::ENV_FILE).exists() {
+            return Err(failure::validation(format!(
+                "--deploy-dir {} is not a deployment directory",
+                path.display()
+            )));
+        }
         return Ok(path.clone());
     }
+    if let Some(path) = app_config.active_dir() {
+        return Ok(path.to_path_buf());
+    }
 
     let default = PathBuf::from("./mvre-hub");
     if default.exists() {
         return Ok(default);
     }
 
-    anyhow::bail!("Deployment not found. Run 'mvre-hub deploy' first.")
+    Err(failure::config("Deployment not found. Run 'mvre-hub deploy' first."))
 }
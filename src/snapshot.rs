@@ -0,0 +1,290 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit, build,
+    cli::RollbackOptions,
+    compose,
+    config::AppConfig,
+    db::{self, Database},
+    envfile, failure, lock, mlflow, nbgrader,
+    progress::Spinner,
+    publish, runlog, say, services, usage, util,
+};
+
+pub const SNAPSHOT_DIR: &str = ".mvre-snapshots";
+const METADATA_FILE: &str = "snapshot.json";
+const DB_DUMP_FILE: &str = "db.sql";
+const SQLITE_FILE: &str = "jupyterhub.sqlite";
+
+/// Directories holding what users and services produce: the hub state,
+/// shared files, published results, usage samples, the nbgrader exchange,
+/// and MLflow runs and artifacts. Neither snapshots nor forced redeploys
+/// touch them; a module that keeps data in the deployment adds its
+/// directory here.
+pub const DATA_DIRS: &[&str] = &[
+    "jupyterhub_data",
+    "shared",
+    publish::PUBLISHED_DIR,
+    usage::USAGE_DIR,
+    nbgrader::NBGRADER_DIR,
+    mlflow::MLFLOW_DIR,
+];
+
+/// Entries never captured in, nor replaced by, a snapshot besides the data
+/// directories: the snapshots themselves, the append-only audit log, run
+/// logs, the build stamp (which describes the images on the host, not the
+/// restored files), the operator's compose override, and the operation lock.
+const PRESERVED: &[&str] = &[
+    SNAPSHOT_DIR,
+    audit::AUDIT_FILE,
+    build::BUILD_STAMP_FILE,
+    compose::OVERRIDE_FILE,
+    lock::LOCK_FILE,
+    runlog::LOG_DIR,
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub created_at: u64,
+    pub reason: String,
+    pub includes_db: bool,
+}
+
+/// Copies the deployment's configuration (and optionally the hub database) into
+/// `.mvre-snapshots/<id>`.
+pub fn create(deploy_dir: &Path, reason: &str, with_db: bool) -> Result<SnapshotInfo> {
+    let created_at = util::unix_now();
+    let id = unique_id(deploy_dir, created_at);
+    let target = snapshots_root(deploy_dir).join(&id);
+    util::ensure_dir(&target)?;
+    let _spinner = Spinner::start("Saving snapshot");
+
+    for entry in managed_entries(deploy_dir)? {
+        let dest = target.join(entry.file_name().context("snapshot entry without a name")?);
+        if entry.is_dir() {
+            util::copy_dir(&entry, &dest)?;
+        } else {
+            fs::copy(&entry, &dest).with_context(|| format!("failed to snapshot {}", entry.display()))?;
+        }
+    }
+
+    let includes_db = if with_db {
+        match dump_database(deploy_dir, &target) {
+            Ok(dumped) => dumped,
+            Err(err) => {
+                // Without metadata the directory is no snapshot; don't leave it behind.
+                fs::remove_dir_all(&target).ok();
+                return Err(err);
+            }
+        }
+    } else {
+        false
+    };
+    let info = SnapshotInfo {
+        id,
+        created_at,
+        reason: reason.to_string(),
+        includes_db,
+    };
+    let serialized = serde_json::to_string_pretty(&info).context("failed to serialize snapshot metadata")?;
+    util::write_string(&target.join(METADATA_FILE), &serialized)?;
+
+    Ok(info)
+}
+
+pub fn list(deploy_dir: &Path) -> Result<Vec<SnapshotInfo>> {
+    let root = snapshots_root(deploy_dir);
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&root).with_context(|| format!("failed to read {}", root.display()))? {
+        let metadata = entry?.path().join(METADATA_FILE);
+        if metadata.exists() {
+            let raw = util::read_to_string(&metadata)?;
+            snapshots.push(serde_json::from_str(&raw).with_context(|| format!("failed to parse {}", metadata.display()))?);
+        }
+    }
+    snapshots.sort_by(|a: &SnapshotInfo, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+    Ok(snapshots)
+}
+
+/// Removes everything from the deployment directory except preserved entries.
+pub fn clear_managed(deploy_dir: &Path) -> Result<()> {
+    for entry in managed_entries(deploy_dir)? {
+        if entry.is_dir() {
+            fs::remove_dir_all(&entry).with_context(|| format!("failed to remove {}", entry.display()))?;
+        } else {
+            fs::remove_file(&entry).with_context(|| format!("failed to remove {}", entry.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Drops the run logs and build stamp too; used when a forced redeploy
+/// starts from scratch. Data directories stay.
+pub fn clear_all_but_snapshots(deploy_dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(deploy_dir).with_context(|| format!("failed to read {}", deploy_dir.display()))? {
+        let path = entry?.path();
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        // The lock file stays: removing it would let another process lock a
+        // new one while this one still holds the old. The override file is
+        // the operator's, and applies to the new deployment as well.
+        if [SNAPSHOT_DIR, audit::AUDIT_FILE, lock::LOCK_FILE, compose::OVERRIDE_FILE].contains(&name.as_str())
+            || DATA_DIRS.contains(&name.as_str())
+        {
+            continue;
+        }
+        if path.is_dir() {
+            fs::remove_dir_all(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        } else {
+            fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn rollback(opts: RollbackOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let snapshots = list(&deploy_dir)?;
+
+    if opts.list {
+        util::set_result(serde_json::json!({ "snapshots": snapshots }));
+        if snapshots.is_empty() {
+            say!("{}", style("No snapshots").dim());
+        }
+        for snapshot in &snapshots {
+            say!(
+                "  {:<24} {}  {}{}",
+                snapshot.id,
+                util::format_timestamp(snapshot.created_at),
+                snapshot.reason,
+                if snapshot.includes_db { " (with db)" } else { "" }
+            );
+        }
+        return Ok(());
+    }
+
+    let chosen = match &opts.to {
+        Some(id) => snapshots
+            .iter()
+            .find(|snapshot| &snapshot.id == id)
+            .with_context(|| format!("snapshot '{}' not found", id))?,
+        None => snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.reason != "pre-rollback")
+            .context("No snapshots to roll back to")?,
+    };
+    if opts.with_db && !chosen.includes_db {
+        return Err(failure::validation(format!("Snapshot {} does not include a database dump", chosen.id)));
+    }
+
+    let safety = create(&deploy_dir, "pre-rollback", false)?;
+    say!("Current state saved as {}", style(&safety.id).dim());
+
+    let source = snapshots_root(&deploy_dir).join(&chosen.id);
+    clear_managed(&deploy_dir)?;
+    for entry in fs::read_dir(&source).with_context(|| format!("failed to read {}", source.display()))? {
+        let path = entry?.path();
+        let name = path.file_name().context("snapshot entry without a name")?;
+        if [METADATA_FILE, DB_DUMP_FILE, SQLITE_FILE].iter().any(|skip| name == *skip) {
+            continue;
+        }
+        let dest = deploy_dir.join(name);
+        if path.is_dir() {
+            util::copy_dir(&path, &dest)?;
+        } else {
+            fs::copy(&path, &dest).with_context(|| format!("failed to restore {}", dest.display()))?;
+        }
+    }
+
+    if opts.with_db {
+        restore_database(&deploy_dir, &source)?;
+    }
+
+    say!("{}", style(format!("Rolled back to {}", chosen.id)).green());
+    util::set_result(serde_json::json!({
+        "restored": chosen.id,
+        "safety_snapshot": safety.id,
+        "with_db": opts.with_db,
+    }));
+    say!("Restart services to apply: {}", style("mvre-hub start").cyan());
+    Ok(())
+}
+
+/// Writes a Postgres dump, or a copy of the SQLite database, into `target`.
+/// Returns whether there was a database to save. The dump drops each object
+/// before recreating it, so it restores over the live database.
+pub fn dump_database(deploy_dir: &Path, target: &Path) -> Result<bool> {
+    let env = envfile::load(deploy_dir).unwrap_or_default();
+    let database = Database::from_env(deploy_dir, &env);
+    if let Database::Sqlite { path } = &database {
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::copy(path, target.join(SQLITE_FILE)).with_context(|| format!("failed to copy {}", path.display()))?;
+        return Ok(true);
+    }
+
+    let output = target.join(DB_DUMP_FILE);
+    let file = File::create(&output).with_context(|| format!("failed to create {}", output.display()))?;
+    util::set_file_mode(&output, 0o600).ok();
+    db::run_with(deploy_dir, &database.dump_command(), Stdio::null(), Stdio::from(file)).context("pg_dump failed")?;
+    Ok(true)
+}
+
+fn restore_database(deploy_dir: &Path, source: &Path) -> Result<()> {
+    let dump = source.join(DB_DUMP_FILE);
+    if dump.exists() {
+        let database = Database::from_env(deploy_dir, &envfile::load(deploy_dir)?);
+        let input = File::open(&dump).with_context(|| format!("failed to read {}", dump.display()))?;
+        return db::run_with(deploy_dir, &database.restore_command(), Stdio::from(input), Stdio::null())
+            .context("failed to restore database dump");
+    }
+
+    let sqlite = source.join(SQLITE_FILE);
+    let dest_dir = deploy_dir.join("jupyterhub_data");
+    util::ensure_dir(&dest_dir)?;
+    fs::copy(&sqlite, dest_dir.join(SQLITE_FILE)).with_context(|| format!("failed to restore {}", sqlite.display()))?;
+    Ok(())
+}
+
+fn managed_entries(deploy_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(deploy_dir).with_context(|| format!("failed to read {}", deploy_dir.display()))? {
+        let path = entry?.path();
+        let preserved = path
+            .file_name()
+            .map(|name| PRESERVED.iter().chain(DATA_DIRS).any(|keep| name == *keep))
+            .unwrap_or(true);
+        if !preserved {
+            entries.push(path);
+        }
+    }
+    Ok(entries)
+}
+
+fn snapshots_root(deploy_dir: &Path) -> PathBuf {
+    deploy_dir.join(SNAPSHOT_DIR)
+}
+
+fn unique_id(deploy_dir: &Path, created_at: u64) -> String {
+    let base = util::format_timestamp(created_at).replace([':', '-'], "");
+    let mut id = base.clone();
+    let mut counter = 1;
+    while snapshots_root(deploy_dir).join(&id).exists() {
+        counter += 1;
+        id = format!("{}-{}", base, counter);
+    }
+    id
+}
@@ -1,63 +1,341 @@
 use std::{
     fs,
-    path::Path,
+    path::{Path, PathBuf},
+    process::Command,
 };
 
 use anyhow::{Context, Result};
+use serde::Serialize;
 
-use crate::util;
+use crate::{envfile, failure, rootless::DockerMode, util};
 
-const SERVICE_PATH: &str = "/etc/systemd/system/mvre-hub.service";
+const SERVICE_NAME: &str = "mvre-hub.service";
+const UNIT_DIR: &str = "/etc/systemd/system";
 
-pub fn install_service(deploy_dir: &Path) -> Result<()> {
-    let user = whoami::username();
-    let content = format!(
-        "[Unit]\nDescription=MVRE-Hub\nAfter=network.target\n\n[Service]\n\
+/// Whether units live in the system manager or the invoking user's manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    System,
+    User,
+}
+
+impl Scope {
+    fn unit_dir(self) -> Result<PathBuf> {
+        match self {
+            Scope::System => Ok(PathBuf::from(UNIT_DIR)),
+            Scope::User => Ok(util::config_home()?.join("systemd").join("user")),
+        }
+    }
+
+    fn systemctl(self) -> Command {
+        let mut command = Command::new("systemctl");
+        if self == Scope::User {
+            command.arg("--user");
+        }
+        command
+    }
+}
+
+pub fn install_service(deploy_dir: &Path, scope: Scope) -> Result<()> {
+    // Deployments on a rootless daemon record its socket in `.env`; the unit
+    // has no login shell to inherit DOCKER_HOST from.
+    let docker_host = envfile::load(deploy_dir)
+        .ok()
+        .and_then(|env| envfile::get(&env, "DOCKER_SOCKET").and_then(|socket| DockerMode::from_socket(socket).docker_host()));
+    let content = service_unit(deploy_dir, scope, docker_host.as_deref());
+
+    let service_path = scope.unit_dir()?.join(SERVICE_NAME);
+    util::atomic_write(&service_path, content.as_bytes())
+        .with_context(|| format!("failed to write {}", service_path.display()))?;
+
+    reload_systemd(scope).context("failed to reload systemd")?;
+    enable_service(scope).context("failed to enable systemd service")?;
+    if scope == Scope::User {
+        enable_linger().context("failed to enable lingering; the hub will only run while you are logged in")?;
+    }
+
+    Ok(())
+}
+
+/// The auto-start unit. With `docker_host` set (rootless Docker), the unit
+/// also orders itself after the daemon, which runs in the same manager.
+pub fn service_unit(deploy_dir: &Path, scope: Scope, docker_host: Option<&str>) -> String {
+    let (user_line, wanted_by) = match scope {
+        Scope::System => (format!("User={}\n", whoami::username()), "multi-user.target"),
+        Scope::User => (String::new(), "default.target"),
+    };
+    let (after, environment) = match docker_host {
+        Some(host) => (
+            "network.target docker.service\nWants=docker.service",
+            format!("Environment=DOCKER_HOST={}\n", host),
+        ),
+        None => ("network.target", String::new()),
+    };
+    format!(
+        "[Unit]\nDescription=MVRE-Hub\nAfter={}\n\n[Service]\n{}\
 ExecStart=/usr/bin/env docker-compose -f {}/docker-compose.yml up\n\
 ExecStop=/usr/bin/env docker-compose -f {}/docker-compose.yml down\n\
-Restart=always\nUser={}\nWorkingDirectory={}\n\n[Install]\nWantedBy=multi-user.target\n",
+Restart=always\n{}WorkingDirectory={}\n\n[Install]\nWantedBy={}\n",
+        after,
+        environment,
         deploy_dir.display(),
         deploy_dir.display(),
-        user,
+        user_line,
         deploy_dir.display(),
+        wanted_by,
+    )
+}
+
+/// User managers stop at logout unless lingering is enabled for the account.
+fn enable_linger() -> Result<()> {
+    let user = whoami::username();
+    let output = Command::new("loginctl")
+        .args(["show-user", &user, "--property=Linger"])
+        .env("LC_ALL", "C")
+        .output()
+        .context("failed to run loginctl show-user")?;
+    if output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "Linger=yes" {
+        return Ok(());
+    }
+
+    let status = Command::new("loginctl")
+        .args(["enable-linger", &user])
+        .status()
+        .context("failed to run loginctl enable-linger")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(failure::permission(format!("loginctl enable-linger {} failed: {}", user, status)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceState {
+    pub scope: Scope,
+    pub active_state: String,
+    pub unit_file_state: String,
+}
+
+/// Queries the unit through `systemctl show`, whose key=value output is stable
+/// across locales. Returns `None` when the unit is installed in neither scope.
+pub fn service_state() -> Result<Option<ServiceState>> {
+    let scope = match installed_scope() {
+        Some(scope) => scope,
+        None => return Ok(None),
+    };
+
+    let output = scope
+        .systemctl()
+        .args(["show", "mvre-hub", "--property=ActiveState,UnitFileState"])
+        .env("LC_ALL", "C")
+        .output()
+        .context("failed to run systemctl show")?;
+    if !output.status.success() {
+        return Err(failure::config(format!("systemctl show failed: {}", output.status)));
+    }
+
+    let raw = String::from_utf8_lossy(&output.stdout);
+    let mut state = ServiceState {
+        scope,
+        active_state: String::new(),
+        unit_file_state: String::new(),
+    };
+    for (key, value) in raw.lines().filter_map(|line| line.split_once('=')) {
+        match key {
+            "ActiveState" => state.active_state = value.to_string(),
+            "UnitFileState" => state.unit_file_state = value.to_string(),
+            _ => {}
+        }
+    }
+    Ok(Some(state))
+}
+
+/// Installs a oneshot `<name>.service` plus `<name>.timer` firing on `on_calendar`.
+pub fn install_timer(scope: Scope, name: &str, description: &str, command: &str, on_calendar: &str) -> Result<()> {
+    let service = format!(
+        "[Unit]\nDescription={}\n\n[Service]\nType=oneshot\nExecStart={}\n",
+        description, command,
+    );
+    let timer = format!(
+        "[Unit]\nDescription={} (timer)\n\n[Timer]\nOnCalendar={}\nPersistent=true\n\n[Install]\nWantedBy=timers.target\n",
+        description, on_calendar,
     );
 
-    util::atomic_write(Path::new(SERVICE_PATH), content.as_bytes())
-        .with_context(|| format!("failed to write {}", SERVICE_PATH))?;
+    let unit_dir = scope.unit_dir()?;
+    let service_path = unit_dir.join(format!("{}.service", name));
+    let timer_path = unit_dir.join(format!("{}.timer", name));
+    util::atomic_write(&service_path, service.as_bytes())
+        .with_context(|| format!("failed to write {}", service_path.display()))?;
+    util::atomic_write(&timer_path, timer.as_bytes())
+        .with_context(|| format!("failed to write {}", timer_path.display()))?;
+
+    reload_systemd(scope).context("failed to reload systemd")?;
+    let unit = format!("{}.timer", name);
+    let status = scope
+        .systemctl()
+        .args(["enable", "--now", &unit])
+        .status()
+        .context("failed to run systemctl enable")?;
+    if !status.success() {
+        return Err(failure::permission(format!("systemctl enable {} failed: {}", unit, status)));
+    }
+    Ok(())
+}
 
-    reload_systemd().context("failed to reload systemd")?;
-    enable_service().context("failed to enable systemd service")?;
+/// Installs and starts a long-running `<name>.service` that systemd restarts
+/// when it exits.
+pub fn install_daemon(scope: Scope, name: &str, description: &str, command: &str) -> Result<()> {
+    let wanted_by = match scope {
+        Scope::System => "multi-user.target",
+        Scope::User => "default.target",
+    };
+    let unit = format!(
+        "[Unit]\nDescription={}\nAfter=network.target docker.service\n\n[Service]\nExecStart={}\n\
+Restart=always\nRestartSec=10\n\n[Install]\nWantedBy={}\n",
+        description, command, wanted_by,
+    );
+    let unit_name = format!("{}.service", name);
+    let path = scope.unit_dir()?.join(&unit_name);
+    util::atomic_write(&path, unit.as_bytes()).with_context(|| format!("failed to write {}", path.display()))?;
 
+    reload_systemd(scope).context("failed to reload systemd")?;
+    let status = scope
+        .systemctl()
+        .args(["enable", "--now", &unit_name])
+        .status()
+        .context("failed to run systemctl enable")?;
+    if !status.success() {
+        return Err(failure::permission(format!("systemctl enable {} failed: {}", unit_name, status)));
+    }
     Ok(())
 }
 
+/// Stops and deletes a service installed by [`install_daemon`] in either
+/// scope. Returns whether one was installed.
+pub fn remove_daemon(name: &str) -> Result<bool> {
+    let unit_name = format!("{}.service", name);
+    let mut removed = false;
+    for scope in [Scope::System, Scope::User] {
+        let path = scope.unit_dir()?.join(&unit_name);
+        if path.exists() {
+            let _ = scope.systemctl().args(["disable", "--now", &unit_name]).status();
+            fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+            reload_systemd(scope).context("failed to reload systemd")?;
+            removed = true;
+        }
+    }
+    Ok(removed)
+}
+
+pub fn timer_installed(scope: Scope, name: &str) -> bool {
+    scope
+        .unit_dir()
+        .map(|dir| dir.join(format!("{}.timer", name)).exists())
+        .unwrap_or(false)
+}
+
+/// Disables and deletes a timer installed by [`install_timer`].
+pub fn remove_timer(scope: Scope, name: &str) -> Result<()> {
+    let unit = format!("{}.timer", name);
+    let _ = scope.systemctl().args(["disable", "--now", &unit]).status();
+
+    let unit_dir = scope.unit_dir()?;
+    for path in [unit_dir.join(&unit), unit_dir.join(format!("{}.service", name))] {
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+    }
+    reload_systemd(scope).context("failed to reload systemd")
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimerUnit {
+    pub scope: Scope,
+    pub name: String,
+    pub on_calendar: String,
+    pub command: String,
+}
+
+/// Timers in either scope whose unit name starts with `prefix`, read back
+/// from the unit files [`install_timer`] wrote.
+pub fn list_timers(prefix: &str) -> Result<Vec<TimerUnit>> {
+    let mut timers = Vec::new();
+    for scope in [Scope::System, Scope::User] {
+        let unit_dir = scope.unit_dir()?;
+        let entries = match fs::read_dir(&unit_dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            let name = match file_name.strip_suffix(".timer") {
+                Some(name) if name.starts_with(prefix) => name.to_string(),
+                _ => continue,
+            };
+            let timer = util::read_to_string(&unit_dir.join(&file_name))?;
+            let service = fs::read_to_string(unit_dir.join(format!("{}.service", name))).unwrap_or_default();
+            timers.push(TimerUnit {
+                scope,
+                on_calendar: unit_value(&timer, "OnCalendar").unwrap_or_default(),
+                command: unit_value(&service, "ExecStart").unwrap_or_default(),
+                name,
+            });
+        }
+    }
+    timers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(timers)
+}
+
+fn unit_value(unit: &str, key: &str) -> Option<String> {
+    unit.lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(candidate, _)| candidate.trim() == key)
+        .map(|(_, value)| value.trim().to_string())
+}
+
+fn installed_scope() -> Option<Scope> {
+    [Scope::System, Scope::User].into_iter().find(|scope| {
+        scope
+            .unit_dir()
+            .map(|dir| dir.join(SERVICE_NAME).exists())
+            .unwrap_or(false)
+    })
+}
+
 pub fn remove_service() -> Result<()> {
-    if Path::new(SERVICE_PATH).exists() {
-        fs::remove_file(SERVICE_PATH).with_context(|| format!("failed to remove {}", SERVICE_PATH))?;
+    for scope in [Scope::System, Scope::User] {
+        let path = scope.unit_dir()?.join(SERVICE_NAME);
+        if path.exists() {
+            let _ = scope.systemctl().args(["disable", "mvre-hub"]).status();
+            fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        }
     }
     Ok(())
 }
 
-fn reload_systemd() -> Result<()> {
-    let status = std::process::Command::new("systemctl")
+fn reload_systemd(scope: Scope) -> Result<()> {
+    let status = scope
+        .systemctl()
         .args(["daemon-reload"])
         .status()
         .context("failed to run systemctl daemon-reload")?;
     if status.success() {
         Ok(())
     } else {
-        anyhow::bail!("systemctl daemon-reload failed: {}", status)
+        Err(failure::permission(format!("systemctl daemon-reload failed: {}", status)))
     }
 }
 
-fn enable_service() -> Result<()> {
-    let status = std::process::Command::new("systemctl")
+fn enable_service(scope: Scope) -> Result<()> {
+    let status = scope
+        .systemctl()
         .args(["enable", "mvre-hub"])
         .status()
         .context("failed to run systemctl enable")?;
     if status.success() {
         Ok(())
     } else {
-        anyhow::bail!("systemctl enable failed: {}", status)
+        Err(failure::permission(format!("systemctl enable failed: {}", status)))
     }
 }
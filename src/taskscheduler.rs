@@ -0,0 +1,73 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    failure,
+    init::{InitSystem, ServiceState},
+};
+
+const TASK_NAME: &str = "MVRE-Hub";
+
+/// A Windows Task Scheduler entry that brings the stack up at logon, once
+/// Docker Desktop is running.
+pub struct TaskScheduler;
+
+impl InitSystem for TaskScheduler {
+    fn name(&self) -> &'static str {
+        "task scheduler"
+    }
+
+    fn needs_root(&self) -> bool {
+        false
+    }
+
+    fn install_service(&self, deploy_dir: &Path) -> Result<()> {
+        let action = logon_action(deploy_dir);
+        schtasks(&["/Create", "/TN", TASK_NAME, "/SC", "ONLOGON", "/TR", &action, "/F"])
+    }
+
+    fn remove_service(&self) -> Result<()> {
+        if self.service_state()?.is_some() {
+            schtasks(&["/Delete", "/TN", TASK_NAME, "/F"])?;
+        }
+        Ok(())
+    }
+
+    fn service_state(&self) -> Result<Option<ServiceState>> {
+        let output = match Command::new("schtasks").args(["/Query", "/TN", TASK_NAME]).output() {
+            Ok(output) if output.status.success() => output,
+            _ => return Ok(None),
+        };
+        let listing = String::from_utf8_lossy(&output.stdout);
+        let enabled = !listing.contains("Disabled");
+        Ok(Some(ServiceState {
+            enabled,
+            running: listing.contains("Running"),
+            detail: if enabled { "enabled" } else { "disabled" }.to_string(),
+        }))
+    }
+}
+
+/// The command the task runs at logon.
+pub fn logon_action(deploy_dir: &Path) -> String {
+    let compose = deploy_dir.join("docker-compose.yml");
+    // Quote the compose path: Windows profile directories often contain spaces.
+    format!("docker-compose -f \"{}\" up -d", compose.display())
+}
+
+fn schtasks(args: &[&str]) -> Result<()> {
+    let status = Command::new("schtasks")
+        .args(args)
+        .status()
+        .context("failed to run schtasks")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(failure::permission(format!(
+            "schtasks {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            status
+        )))
+    }
+}
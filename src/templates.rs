@@ -1,49 +1,223 @@
-pub fn docker_compose(domain: &str, acme_email: &str, production: bool) -> String {
+use crate::{
+    answers::ExtraService,
+    bluegreen, branding,
+    cli::{LandingPath, ProxyVersion, TlsMode},
+    idp::ExtraIdp,
+    dask, filebrowser, landing,
+    logconfig::{self, ContainerLogging},
+    logstack, mlflow, tls, workspace,
+};
+
+/// Waits for Postgres to accept connections rather than just to start, so
+/// its clients do not crash-loop while it initializes on the first start.
+const POSTGRES_DEPENDENCY: &str = "    depends_on:\n      postgres:\n        condition: service_healthy\n";
+
+/// Every service the compose file may have; extra services take other names.
+pub const GENERATED_SERVICES: &[&str] = &[
+    "jupyterhub",
+    "user-image",
+    "traefik",
+    "postgres",
+    "published",
+    "landing",
+    "dask-gateway",
+    "mlflow",
+    "files",
+    "files-auth",
+    "loki",
+    "promtail",
+    "grafana",
+];
+
+/// Hub log file inside the container; `./jupyterhub_data` on the host.
+pub const HUB_LOG_FILE: &str = "/srv/jupyterhub/jupyterhub.log";
+
+pub struct ComposeValues<'a> {
+    pub domain: &'a str,
+    pub acme_email: &'a str,
+    /// Point the cert resolver at Let's Encrypt's staging endpoint.
+    pub acme_staging: bool,
+    /// Where the public certificate comes from, if anywhere.
+    pub tls: TlsMode,
+    pub production: bool,
+    pub publishing: bool,
+    pub http_port: u16,
+    pub https_port: u16,
+    pub proxy_version: ProxyVersion,
+    pub security_headers: bool,
+    pub csp: Option<&'a str>,
+    /// Average and burst requests per second per client IP.
+    pub rate_limit: Option<(u32, u32)>,
+    /// Serve the hub over HTTPS with the deployment's internal CA.
+    pub internal_tls: bool,
+    /// Relabel bind mounts for SELinux; containers holding the Docker socket
+    /// run without label separation instead.
+    pub selinux: bool,
+    /// Mount the seccomp/AppArmor profiles the spawner applies.
+    pub sandbox_profiles: bool,
+    /// Show the hub the per-user ZFS/Btrfs workspaces, so it can refuse
+    /// spawns for users without one.
+    pub workspaces: bool,
+    /// Let the hub create per-user directories under `${SCRATCH_ROOT}`.
+    pub scratch: bool,
+    /// Mount the logo, terms of use, and template overrides.
+    pub branding: bool,
+    /// Serve the onboarding site from `${LANDING_SITE}`.
+    pub landing: Option<LandingPath>,
+    /// Run the Dask Gateway on the users network.
+    pub dask_gateway: bool,
+    /// Run the MLflow tracking server at `mlflow.<domain>`.
+    pub mlflow: bool,
+    /// Serve a read-only index of the dataset behind the hub's login.
+    pub file_browser: bool,
+    /// Run Loki, Promtail, and Grafana on their own network.
+    pub logging_stack: bool,
+    /// Log driver and rotation of every service; Docker's defaults if unset.
+    pub logging: Option<ContainerLogging<'a>>,
+    /// Operator-defined services appended after the generated ones.
+    pub extra_services: &'a [(String, ExtraService)],
+}
+
+pub fn docker_compose(values: &ComposeValues) -> String {
+    let domain = values.domain;
+    let production = values.production;
+    // With internal TLS Traefik verifies the hub against the internal CA.
+    let internal_tls = |lines: &str| {
+        if values.internal_tls {
+            lines.to_string()
+        } else {
+            String::new()
+        }
+    };
+    // Three networks: `proxy` joins Traefik to the hub and publishing server,
+    // `hub` (internal) joins the hub to Postgres, and `users` is the only
+    // network user servers are attached to.
     let mut base = format!(
         r#"services:
   jupyterhub:
     build: ./hub
     env_file: .env
     volumes:
-      - ./hub/jupyterhub_config.py:/etc/jupyterhub/jupyterhub_config.py:ro
-      - ./jupyterhub_data:/srv/jupyterhub
-      - /var/run/docker.sock:/var/run/docker.sock
-    {depends_on}
+      - ./hub/jupyterhub_config.py:/etc/jupyterhub/jupyterhub_config.py{ro_private}
+      - ./jupyterhub_data:/srv/jupyterhub{private}
+      - ${{DOCKER_SOCKET:-/var/run/docker.sock}}:/var/run/docker.sock
+{hub_tls_volume}{sandbox_volume}{workspace_volume}{scratch_volume}{branding_volume}{socket_security}{depends_on}    healthcheck:
+      test: ["CMD", "python3", "-c", "{hub_probe}"]
+      interval: 30s
+      timeout: 10s
+      retries: 3
+      start_period: 60s
+    networks:
+      - proxy
+      - hub
+      - users
     labels:
       - "traefik.enable=true"
+      - "traefik.docker.network=${{PROXY_NETWORK_NAME:-mvre-hub_proxy}}"
       - "traefik.http.routers.jupyterhub.rule=Host(`{domain}`)"
       - "traefik.http.routers.jupyterhub.entrypoints=websecure"
       - "traefik.http.routers.jupyterhub.tls=true"
       - "traefik.http.routers.jupyterhub.tls.certresolver=letsencrypt"
-    command: ["jupyterhub", "-f", "/etc/jupyterhub/jupyterhub_config.py"]
+{hub_middlewares}{hub_tls_label}    command: ["jupyterhub", "-f", "/etc/jupyterhub/jupyterhub_config.py"]
 
   user-image:
     build: ./user
     image: ${{USER_IMAGE}}
     command: ["true"]
+    network_mode: none
 
   traefik:
-    image: traefik:v2.9
+    image: {proxy_image}
     command:
       - "--providers.docker=true"
       - "--providers.docker.exposedbydefault=false"
+      - "--ping=true"
       - "--entrypoints.websecure.address=:443"
-      - "--certificatesresolvers.letsencrypt.acme.tlschallenge=true"
-      - "--certificatesresolvers.letsencrypt.acme.email={acme_email}"
-      - "--certificatesresolvers.letsencrypt.acme.storage=/certs/acme.json"
-    ports:
-      - "8080:80"
-      - "8443:443"
+{certificates_command}{proxy_tls_command}    ports:
+      - "{http_port}:80"
+      - "{https_port}:443"
     volumes:
-      - ./traefik:/certs
-      - /var/run/docker.sock:/var/run/docker.sock:ro
+      - ./traefik:/certs{private}
+      - ${{DOCKER_SOCKET:-/var/run/docker.sock}}:/var/run/docker.sock:ro
+{local_tls_volume}{proxy_tls_volume}{socket_security}    networks:
+      - proxy
+    healthcheck:
+      test: ["CMD", "traefik", "healthcheck", "--ping"]
+      interval: 30s
+      timeout: 5s
+      retries: 3
+      start_period: 10s
 "#,
         domain = domain,
-        depends_on = if production {
-            "depends_on:\n      - postgres"
+        proxy_image = values.proxy_version.image(),
+        certificates_command = certificates_command(values),
+        local_tls_volume = if values.tls == TlsMode::SelfSigned {
+            let options = bind_options(values.selinux, true, 'Z');
+            ["server.crt", "server.key"]
+                .iter()
+                .map(|file| {
+                    format!(
+                        "      - ./{dir}/{file}:{mount}/{file}{options}\n",
+                        dir = tls::LOCAL_TLS_DIR,
+                        mount = tls::LOCAL_TLS_MOUNT,
+                        file = file,
+                        options = options
+                    )
+                })
+                .collect::<String>()
         } else {
-            ""
-        }
+            String::new()
+        },
+        hub_middlewares = hub_middleware_labels(values),
+        ro_private = bind_options(values.selinux, true, 'Z'),
+        private = bind_options(values.selinux, false, 'Z'),
+        sandbox_volume = if values.sandbox_profiles {
+            format!("      - ./sandbox:/etc/mvre-hub/sandbox{}\n", bind_options(values.selinux, true, 'Z'))
+        } else {
+            String::new()
+        },
+        // rslave, so datasets mounted after the hub started show up in it.
+        workspace_volume = if values.workspaces {
+            format!(
+                "      - ${{WORKSPACE_HOST_PATH}}:{}{},rslave\n",
+                workspace::HUB_WORKSPACE_DIR,
+                bind_options(values.selinux, true, 'z')
+            )
+        } else {
+            String::new()
+        },
+        scratch_volume = if values.scratch {
+            format!("      - ${{SCRATCH_ROOT}}:/srv/scratch{}\n", bind_options(values.selinux, false, 'z'))
+        } else {
+            String::new()
+        },
+        branding_volume = if values.branding {
+            format!(
+                "      - ./{}:{}{}\n",
+                branding::BRANDING_DIR,
+                branding::HUB_BRANDING_DIR,
+                bind_options(values.selinux, true, 'Z')
+            )
+        } else {
+            String::new()
+        },
+        socket_security = if values.selinux { "    security_opt:\n      - label=disable\n" } else { "" },
+        hub_tls_volume = internal_tls(&format!(
+            "      - ./{}:/etc/mvre-hub/tls{}\n",
+            tls::INTERNAL_TLS_DIR,
+            bind_options(values.selinux, true, 'z')
+        )),
+        hub_tls_label = internal_tls("      - \"traefik.http.services.jupyterhub.loadbalancer.server.scheme=https\"\n"),
+        proxy_tls_command = internal_tls("      - \"--serversTransport.rootCAs=/internal-ca/ca.crt\"\n"),
+        proxy_tls_volume = internal_tls(&format!(
+            "      - ./{}/ca.crt:/internal-ca/ca.crt{}\n",
+            tls::INTERNAL_TLS_DIR,
+            bind_options(values.selinux, true, 'z')
+        )),
+        http_port = values.http_port,
+        https_port = values.https_port,
+        depends_on = if production { POSTGRES_DEPENDENCY } else { "" },
+        hub_probe = bluegreen::health_script(values.internal_tls),
     );
 
     if production {
@@ -58,31 +232,481 @@ pub fn docker_compose(domain: &str, acme_email: &str, production: bool) -> Strin
       POSTGRES_DB: ${DB_NAME}
     volumes:
       - postgres_data:/var/lib/postgresql/data
-
-volumes:
-  postgres_data:
+    networks:
+      - hub
+    healthcheck:
+      test: ["CMD-SHELL", "pg_isready -U $${POSTGRES_USER} -d $${POSTGRES_DB}"]
+      interval: 10s
+      timeout: 5s
+      retries: 5
+      start_period: 30s
 "#,
         );
     }
 
+    if values.publishing {
+        base.push_str(&format!(
+            r#"
+  published:
+    image: nginx:alpine
+    volumes:
+      - ./published:/srv/published{ro_shared}
+      - ./publish/nginx.conf:/etc/nginx/conf.d/default.conf{ro_private}
+    networks:
+      - proxy
+    labels:
+      - "traefik.enable=true"
+      - "traefik.http.routers.published.rule=Host(`{domain}`) && PathPrefix(`/published`)"
+      - "traefik.http.routers.published.entrypoints=websecure"
+      - "traefik.http.routers.published.tls=true"
+      - "traefik.http.routers.published.tls.certresolver=letsencrypt"
+      - "traefik.http.services.published.loadbalancer.server.port=80"
+"#,
+            domain = domain,
+            ro_shared = bind_options(values.selinux, true, 'z'),
+            ro_private = bind_options(values.selinux, true, 'Z'),
+        ));
+    }
+
+    if let Some(path) = values.landing {
+        base.push_str(&format!(
+            r#"
+  landing:
+    image: nginx:alpine
+    volumes:
+      - ${{LANDING_SITE}}:/srv/landing{ro_shared}
+      - ./{dir}/nginx.conf:/etc/nginx/conf.d/default.conf{ro_private}
+    networks:
+      - proxy
+    labels:
+      - "traefik.enable=true"
+      - "traefik.http.routers.landing.rule={rule}"
+      - "traefik.http.routers.landing.entrypoints=websecure"
+      - "traefik.http.routers.landing.tls=true"
+      - "traefik.http.routers.landing.tls.certresolver=letsencrypt"
+      - "traefik.http.services.landing.loadbalancer.server.port=80"
+"#,
+            dir = landing::LANDING_DIR,
+            rule = path.router_rule(domain),
+            ro_shared = bind_options(values.selinux, true, 'z'),
+            ro_private = bind_options(values.selinux, true, 'Z'),
+        ));
+    }
+
+    if values.dask_gateway {
+        // Workers run user code, so the gateway gets its token rather than
+        // the whole `.env`, and only the CA of the internal certificates.
+        base.push_str(&format!(
+            r#"
+  dask-gateway:
+    build:
+      context: .
+      dockerfile: {dir}/Dockerfile
+    environment:
+      DASK_GATEWAY_API_TOKEN: ${{DASK_GATEWAY_API_TOKEN}}
+      INTERNAL_TLS: ${{INTERNAL_TLS:-false}}
+    volumes:
+      - ./{dir}/dask_gateway_config.py:/etc/dask-gateway/dask_gateway_config.py{ro_private}
+{ca_volume}    networks:
+      - users
+    command: ["dask-gateway-server", "-f", "/etc/dask-gateway/dask_gateway_config.py"]
+"#,
+            dir = dask::GATEWAY_DIR,
+            ro_private = bind_options(values.selinux, true, 'Z'),
+            ca_volume = internal_tls(&format!(
+                "      - ./{}/ca.crt:/etc/mvre-hub/tls/ca.crt{}\n",
+                tls::INTERNAL_TLS_DIR,
+                bind_options(values.selinux, true, 'z')
+            )),
+        ));
+    }
+
+    if values.mlflow {
+        base.push_str(&format!(
+            r#"
+  mlflow:
+    build: ./{dir}
+    environment:
+      ENABLE_POSTGRES: ${{ENABLE_POSTGRES:-false}}
+      DB_HOST: ${{DB_HOST}}
+      DB_PORT: ${{DB_PORT}}
+      DB_USER: ${{DB_USER}}
+      DB_PASSWORD: ${{DB_PASSWORD}}
+      DB_NAME: ${{DB_NAME}}
+    volumes:
+      - ./{dir}/data:/mlflow/data{private}
+      - ${{MLFLOW_ARTIFACTS}}:/mlflow/artifacts{shared}
+{depends_on}    networks:
+      - proxy
+      - users{hub_network}
+    labels:
+      - "traefik.enable=true"
+      - "traefik.docker.network=${{PROXY_NETWORK_NAME:-mvre-hub_proxy}}"
+      - "traefik.http.routers.mlflow.rule=Host(`{host}`)"
+      - "traefik.http.routers.mlflow.entrypoints=websecure"
+      - "traefik.http.routers.mlflow.tls=true"
+      - "traefik.http.routers.mlflow.tls.certresolver=letsencrypt"
+      - "traefik.http.services.mlflow.loadbalancer.server.port=5000"
+"#,
+            dir = mlflow::MLFLOW_DIR,
+            host = mlflow::host(domain),
+            private = bind_options(values.selinux, false, 'Z'),
+            shared = bind_options(values.selinux, false, 'z'),
+            depends_on = if production { POSTGRES_DEPENDENCY } else { "" },
+            hub_network = if production { "\n      - hub" } else { "" },
+        ));
+    }
+
+    if values.file_browser {
+        // Traefik asks `files-auth` about every request; it signs users in
+        // with the hub as OAuth provider. Behind internal TLS it reaches the
+        // hub through the proxy, whose certificate it trusts.
+        let hub_api = if values.internal_tls {
+            format!("https://{}/hub/api", domain)
+        } else {
+            "http://jupyterhub:8000/hub/api".to_string()
+        };
+        base.push_str(&format!(
+            r#"
+  files:
+    image: nginx:alpine
+    volumes:
+      - ${{DATASET_HOST_PATH}}:/srv/files{ro_shared}
+      - ./{dir}/nginx.conf:/etc/nginx/conf.d/default.conf{ro_private}
+    networks:
+      - proxy
+    labels:
+      - "traefik.enable=true"
+      - "traefik.http.routers.files.rule=Host(`{domain}`) && PathPrefix(`{prefix}`)"
+      - "traefik.http.routers.files.entrypoints=websecure"
+      - "traefik.http.routers.files.tls=true"
+      - "traefik.http.routers.files.tls.certresolver=letsencrypt"
+      - "traefik.http.routers.files.middlewares=files-auth"
+      - "traefik.http.services.files.loadbalancer.server.port=80"
+
+  files-auth:
+    image: {auth_image}
+    environment:
+      DEFAULT_PROVIDER: generic-oauth
+      PROVIDERS_GENERIC_OAUTH_AUTH_URL: https://{domain}/hub/api/oauth2/authorize
+      PROVIDERS_GENERIC_OAUTH_TOKEN_URL: {hub_api}/oauth2/token
+      PROVIDERS_GENERIC_OAUTH_USER_URL: {hub_api}/user
+      PROVIDERS_GENERIC_OAUTH_CLIENT_ID: {client_id}
+      PROVIDERS_GENERIC_OAUTH_CLIENT_SECRET: ${{FILE_BROWSER_OAUTH_SECRET}}
+      PROVIDERS_GENERIC_OAUTH_SCOPE: identify
+      SECRET: ${{FILE_BROWSER_COOKIE_SECRET}}
+      URL_PATH: {callback}
+      USER_ID_PATH: name
+    networks:
+      - proxy
+    labels:
+      - "traefik.enable=true"
+      - "traefik.http.middlewares.files-auth.forwardauth.address=http://files-auth:4181"
+      - "traefik.http.middlewares.files-auth.forwardauth.authResponseHeaders=X-Forwarded-User"
+      - "traefik.http.services.files-auth.loadbalancer.server.port=4181"
+"#,
+            dir = filebrowser::FILE_BROWSER_DIR,
+            prefix = filebrowser::PATH_PREFIX,
+            callback = filebrowser::callback_path(),
+            client_id = filebrowser::OAUTH_CLIENT_ID,
+            auth_image = filebrowser::FORWARD_AUTH_IMAGE,
+            hub_api = hub_api,
+            domain = domain,
+            ro_shared = bind_options(values.selinux, true, 'z'),
+            ro_private = bind_options(values.selinux, true, 'Z'),
+        ));
+    }
+
+    if values.logging_stack {
+        // Promtail reads every container's logs through the Docker API; only
+        // Grafana is reachable from outside the internal `logging` network.
+        base.push_str(&format!(
+            r#"
+  loki:
+    image: {loki_image}
+    command: ["-config.file=/etc/loki/loki.yaml"]
+    volumes:
+      - ./{dir}/loki.yaml:/etc/loki/loki.yaml{ro_private}
+      - loki_data:/loki
+    networks:
+      - logging
+
+  promtail:
+    image: {promtail_image}
+    command: ["-config.file=/etc/promtail/promtail.yaml"]
+    volumes:
+      - ./{dir}/promtail.yaml:/etc/promtail/promtail.yaml{ro_private}
+      - ${{DOCKER_SOCKET:-/var/run/docker.sock}}:/var/run/docker.sock:ro
+      - promtail_data:/var/lib/promtail
+{socket_security}    depends_on:
+      - loki
+    networks:
+      - logging
+
+  grafana:
+    image: {grafana_image}
+    environment:
+      GF_SECURITY_ADMIN_PASSWORD: ${{GRAFANA_ADMIN_PASSWORD}}
+      GF_SERVER_ROOT_URL: https://{domain}{prefix}/
+      GF_SERVER_SERVE_FROM_SUB_PATH: "true"
+      GF_USERS_ALLOW_SIGN_UP: "false"
+    volumes:
+      - ./{dir}/grafana-datasources.yaml:/etc/grafana/provisioning/datasources/loki.yaml{ro_private}
+      - grafana_data:/var/lib/grafana
+    depends_on:
+      - loki
+    networks:
+      - proxy
+      - logging
+    labels:
+      - "traefik.enable=true"
+      - "traefik.docker.network=${{PROXY_NETWORK_NAME:-mvre-hub_proxy}}"
+      - "traefik.http.routers.grafana.rule=Host(`{domain}`) && PathPrefix(`{prefix}`)"
+      - "traefik.http.routers.grafana.entrypoints=websecure"
+      - "traefik.http.routers.grafana.tls=true"
+      - "traefik.http.routers.grafana.tls.certresolver=letsencrypt"
+      - "traefik.http.services.grafana.loadbalancer.server.port=3000"
+"#,
+            dir = logstack::LOGGING_DIR,
+            loki_image = logstack::LOKI_IMAGE,
+            promtail_image = logstack::PROMTAIL_IMAGE,
+            grafana_image = logstack::GRAFANA_IMAGE,
+            prefix = logstack::GRAFANA_PREFIX,
+            domain = domain,
+            ro_private = bind_options(values.selinux, true, 'Z'),
+            socket_security = if values.selinux { "    security_opt:\n      - label=disable\n" } else { "" },
+        ));
+    }
+
+    for (name, service) in values.extra_services {
+        base.push_str(&extra_service(name, service));
+    }
+
+    base.push_str(
+        r#"
+networks:
+  proxy:
+    name: ${PROXY_NETWORK_NAME:-mvre-hub_proxy}
+  hub:
+    internal: true
+  users:
+    name: ${DOCKER_NETWORK_NAME:-mvre-hub_users}
+"#,
+    );
+    if values.logging_stack {
+        base.push_str("  logging:\n    internal: true\n");
+    }
+
+    let mut volumes = Vec::new();
+    if production {
+        volumes.push("postgres_data");
+    }
+    if values.logging_stack {
+        volumes.extend(["loki_data", "promtail_data", "grafana_data"]);
+    }
+    for (_, service) in values.extra_services {
+        for volume in named_volumes(service) {
+            if !volumes.contains(&volume) {
+                volumes.push(volume);
+            }
+        }
+    }
+    if !volumes.is_empty() {
+        base.push_str("\nvolumes:\n");
+        for volume in volumes {
+            base.push_str(&format!("  {}:\n", volume));
+        }
+    }
+
+    if let Some(logging) = &values.logging {
+        base = logconfig::apply(&base, logging);
+    }
+    match values.tls {
+        TlsMode::Acme => {}
+        // Routers use Traefik's default certificate without a resolver.
+        TlsMode::SelfSigned => {
+            base = base
+                .lines()
+                .filter(|line| !line.contains(".tls.certresolver=letsencrypt"))
+                .map(|line| format!("{}\n", line))
+                .collect();
+        }
+        TlsMode::Off => {
+            base = base
+                .lines()
+                .filter(|line| !line.contains(".tls=true") && !line.contains(".tls.certresolver=letsencrypt"))
+                .map(|line| format!("{}\n", line.replace(".entrypoints=websecure", ".entrypoints=web")))
+                .collect();
+        }
+    }
+
     base
 }
 
+/// Traefik flags providing the public certificate: an ACME resolver, or the
+/// file provider serving the local certificate.
+fn certificates_command(values: &ComposeValues) -> String {
+    match values.tls {
+        TlsMode::Acme => {}
+        TlsMode::SelfSigned => {
+            return format!("      - \"--providers.file.filename=/certs/{}\"\n", tls::LOCAL_TLS_CONFIG);
+        }
+        TlsMode::Off => return "      - \"--entrypoints.web.address=:80\"\n".to_string(),
+    }
+    let mut lines = format!(
+        r#"      - "--certificatesresolvers.letsencrypt.acme.tlschallenge=true"
+      - "--certificatesresolvers.letsencrypt.acme.email={}"
+      - "--certificatesresolvers.letsencrypt.acme.storage=/certs/{}"
+"#,
+        values.acme_email,
+        tls::acme_storage(values.acme_staging)
+    );
+    if values.acme_staging {
+        lines.push_str(&format!(
+            "      - \"--certificatesresolvers.letsencrypt.acme.caserver={}\"\n",
+            tls::ACME_STAGING_SERVER
+        ));
+    }
+    lines
+}
+
+/// Mode suffix of a bind mount. With SELinux, `Z` relabels the source for
+/// this container alone and `z` for several, including spawned servers.
+/// One operator-defined service. Strings are written as JSON strings, which
+/// YAML reads as double-quoted scalars, so no value can break the file.
+fn extra_service(name: &str, service: &ExtraService) -> String {
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    let mut block = format!("\n  {}:\n    image: {}\n", name, quote(&service.image));
+    if let Some(command) = &service.command {
+        let args: Vec<String> = command.iter().map(|arg| quote(arg)).collect();
+        block.push_str(&format!("    command: [{}]\n", args.join(", ")));
+    }
+    if !service.environment.is_empty() {
+        block.push_str("    environment:\n");
+        for (key, value) in &service.environment {
+            block.push_str(&format!("      {}: {}\n", key, quote(value)));
+        }
+    }
+    let mut list = |key: &str, items: Vec<String>| {
+        if !items.is_empty() {
+            block.push_str(&format!("    {}:\n", key));
+            for item in items {
+                block.push_str(&format!("      - {}\n", item));
+            }
+        }
+    };
+    list("volumes", service.volumes.iter().map(|volume| quote(volume)).collect());
+    // Network names are checked against the deployment's own.
+    list("networks", service.networks.clone().unwrap_or_else(|| vec!["proxy".to_string()]));
+    list("labels", service.labels.iter().map(|label| quote(label)).collect());
+    block
+}
+
+/// Named volumes an extra service mounts, which compose wants declared at
+/// the top level; host paths and `${VAR}` sources are not.
+fn named_volumes(service: &ExtraService) -> Vec<&str> {
+    service
+        .volumes
+        .iter()
+        .filter_map(|volume| volume.split_once(':').map(|(source, _)| source))
+        .filter(|source| !source.is_empty() && !source.starts_with(['.', '/', '~', '$']))
+        .collect()
+}
+
+fn bind_options(selinux: bool, read_only: bool, label: char) -> String {
+    let mut options = Vec::new();
+    if read_only {
+        options.push("ro".to_string());
+    }
+    if selinux {
+        options.push(label.to_string());
+    }
+    if options.is_empty() {
+        String::new()
+    } else {
+        format!(":{}", options.join(","))
+    }
+}
+
+/// Labels defining the hub router's middleware chain: a per-IP rate limit
+/// and security headers, applied in that order.
+fn hub_middleware_labels(values: &ComposeValues) -> String {
+    let mut labels = Vec::new();
+    let mut chain = Vec::new();
+    if let Some((average, burst)) = values.rate_limit {
+        labels.push(format!("traefik.http.middlewares.hub-ratelimit.ratelimit.average={}", average));
+        labels.push(format!("traefik.http.middlewares.hub-ratelimit.ratelimit.burst={}", burst));
+        chain.push("hub-ratelimit");
+    }
+    if values.security_headers || values.csp.is_some() {
+        let prefix = "traefik.http.middlewares.hub-headers.headers";
+        labels.push(format!("{}.stsSeconds=31536000", prefix));
+        labels.push(format!("{}.stsIncludeSubdomains=true", prefix));
+        labels.push(format!("{}.customFrameOptionsValue=SAMEORIGIN", prefix));
+        labels.push(format!("{}.contentTypeNosniff=true", prefix));
+        labels.push(format!("{}.referrerPolicy=strict-origin-when-cross-origin", prefix));
+        if let Some(csp) = values.csp {
+            labels.push(format!("{}.contentSecurityPolicy={}", prefix, csp));
+        }
+        chain.push("hub-headers");
+    }
+    if chain.is_empty() {
+        return String::new();
+    }
+    labels.push(format!("traefik.http.middlewares.hub-secure.chain.middlewares={}", chain.join(",")));
+    labels.push("traefik.http.routers.jupyterhub.middlewares=hub-secure".to_string());
+    labels
+        .iter()
+        .map(|label| format!("      - \"{}\"\n", compose_escape(label)))
+        .collect()
+}
+
+/// Escapes a value for a double-quoted YAML string that compose interpolates.
+fn compose_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "$$")
+}
+
+pub fn publish_nginx_config() -> String {
+    r#"
+server {
+    listen 80;
+
+    location /published/ {
+        alias /srv/published/;
+        autoindex on;
+        autoindex_exact_size off;
+    }
+}
+"#
+    .trim_start()
+    .to_string()
+}
+
 pub struct EnvValues<'a> {
     pub client_id: &'a str,
     pub client_secret: &'a str,
     pub domain: &'a str,
+    pub acme_email: &'a str,
+    pub acme_staging: bool,
+    pub tls_mode: &'a str,
     pub user_image: &'a str,
+    pub user_env_repo: Option<&'a str>,
+    pub user_env_ref: Option<&'a str>,
     pub dataset_host: &'a str,
     pub dataset_mount: &'a str,
     pub allow_missing_dataset: bool,
     pub shared_host: Option<&'a str>,
     pub shared_mount: &'a str,
     pub admin_users: Option<&'a str>,
+    pub allowed_users: Option<&'a str>,
     pub oauth_authorize_url: Option<&'a str>,
     pub oauth_token_url: Option<&'a str>,
     pub oauth_userdata_url: Option<&'a str>,
     pub oauth_username_key: &'a str,
+    pub required_entitlements: Option<&'a str>,
+    pub entitlement_claim: &'a str,
+    pub auth_preset: &'a str,
+    pub extra_idps: &'a [ExtraIdp],
     pub production: bool,
     pub db_user: &'a str,
     pub db_name: &'a str,
@@ -91,61 +715,222 @@ pub struct EnvValues<'a> {
     pub db_port: u16,
     pub cpu_limit: Option<&'a str>,
     pub mem_limit: Option<&'a str>,
+    pub user_limits: &'a str,
+    pub group_limits: &'a str,
     pub cull_timeout: Option<u64>,
     pub cull_every: Option<u64>,
+    pub cull_groups: &'a str,
+    pub group_members: &'a str,
+    pub group_shares: &'a str,
+    /// Names of the extra services, comma-separated.
+    pub extra_services: &'a str,
+    pub hub_api_token: &'a str,
+    pub spawn_min_free_disk_gb: Option<f64>,
+    pub spawn_min_free_mem_gb: Option<f64>,
+    pub orphan_volume_policy: &'a str,
+    pub orphan_volume_grace: &'a str,
+    pub publishing: bool,
+    pub http_port: u16,
+    pub https_port: u16,
+    pub proxy_version: &'a str,
+    pub security_headers: bool,
+    pub csp: Option<&'a str>,
+    pub rate_limit: Option<u32>,
+    pub rate_limit_burst: Option<u32>,
+    pub concurrent_spawn_limit: Option<u32>,
+    pub active_server_limit: Option<u32>,
+    pub internal_tls: bool,
+    pub docker_socket: &'a str,
+    pub docker_context: Option<&'a str>,
+    pub docker_host: Option<&'a str>,
+    pub docker_cert_path: Option<&'a str>,
+    pub selinux: bool,
+    pub sandbox_profiles: bool,
+    pub apparmor: bool,
+    pub notebook_uid: Option<u32>,
+    pub notebook_gid: Option<u32>,
+    pub workspace_backend: &'a str,
+    pub workspace_root: Option<&'a str>,
+    pub workspace_host_path: Option<&'a str>,
+    pub workspace_quota: Option<&'a str>,
+    pub scratch_root: Option<&'a str>,
+    pub hub_title: Option<&'a str>,
+    pub brand_color: Option<&'a str>,
+    pub brand_logo: Option<&'a str>,
+    pub welcome_text: Option<&'a str>,
+    pub terms_of_use: bool,
+    pub landing: Option<&'a str>,
+    pub landing_site: &'a str,
+    pub support_email: Option<&'a str>,
+    pub data_citation: Option<&'a str>,
+    pub dask_gateway: bool,
+    pub dask_gateway_token: &'a str,
+    pub mlflow: bool,
+    pub mlflow_artifacts: &'a str,
+    pub nbgrader: bool,
+    pub nbgrader_course: &'a str,
+    pub nbgrader_instructors: &'a str,
+    /// Host path of the deployment's nbgrader directory.
+    pub nbgrader_host: &'a str,
+    pub file_browser: bool,
+    pub file_browser_oauth_secret: &'a str,
+    pub file_browser_cookie_secret: &'a str,
+    pub logging_stack: bool,
+    pub log_retention: &'a str,
+    pub grafana_admin_password: &'a str,
+    pub log_driver: &'a str,
+    pub log_max_size: &'a str,
+    pub log_max_file: u32,
+    pub dummy_password: Option<&'a str>,
+    pub project_name: Option<&'a str>,
+    pub resource_prefix: &'a str,
 }
 
 pub fn env_file(values: &EnvValues) -> String {
-    format!(
-        "HUB_DOMAIN={}\nOAUTH_CLIENT_ID={}\nOAUTH_CLIENT_SECRET={}\nUSER_IMAGE={}\nDATASET_HOST_PATH={}\nDATASET_MOUNT_PATH={}\nALLOW_MISSING_DATASET={}\nSHARED_HOST_PATH={}\nSHARED_MOUNT_PATH={}\nADMIN_USERS={}\nOAUTH_AUTHORIZE_URL={}\nOAUTH_TOKEN_URL={}\nOAUTH_USERDATA_URL={}\nOAUTH_USERNAME_KEY={}\nENABLE_POSTGRES={}\nDB_USER={}\nDB_PASSWORD={}\nDB_NAME={}\nDB_HOST={}\nDB_PORT={}\nJUPYTERHUB_DB_URL={}\nCPU_LIMIT={}\nMEM_LIMIT={}\nCULL_TIMEOUT={}\nCULL_EVERY={}\nALLOW_DUMMY_AUTH=false\n",
-        values.domain,
-        values.client_id,
-        values.client_secret,
-        values.user_image,
-        values.dataset_host,
-        values.dataset_mount,
-        values.allow_missing_dataset,
-        values.shared_host.unwrap_or(""),
-        values.shared_mount,
-        values.admin_users.unwrap_or(""),
-        values.oauth_authorize_url.unwrap_or(""),
-        values.oauth_token_url.unwrap_or(""),
-        values.oauth_userdata_url.unwrap_or(""),
-        values.oauth_username_key,
-        values.production,
-        values.db_user,
-        values.db_password,
-        values.db_name,
-        values.db_host,
-        values.db_port,
-        if values.production {
-            format!(
-                "postgresql://{}:{}@{}:{}/{}",
-                values.db_user,
-                values.db_password,
-                values.db_host,
-                values.db_port,
-                values.db_name
-            )
-        } else {
-            "".to_string()
-        },
-        values.cpu_limit.unwrap_or(""),
-        values.mem_limit.unwrap_or(""),
-        values
-            .cull_timeout
-            .map(|v| v.to_string())
-            .unwrap_or_else(String::new),
-        values
-            .cull_every
-            .map(|v| v.to_string())
-            .unwrap_or_else(String::new),
-    )
+    let db_url = if values.production {
+        format!(
+            "postgresql://{}:{}@{}:{}/{}",
+            values.db_user,
+            values.db_password,
+            values.db_host,
+            values.db_port,
+            values.db_name
+        )
+    } else {
+        "".to_string()
+    };
+
+    let mut entries: Vec<(&str, String)> = vec![
+        ("HUB_DOMAIN", values.domain.to_string()),
+        ("ACME_EMAIL", values.acme_email.to_string()),
+        ("ACME_STAGING", values.acme_staging.to_string()),
+        ("TLS_MODE", values.tls_mode.to_string()),
+        ("OAUTH_PRESET", values.auth_preset.to_string()),
+        ("OAUTH_CLIENT_ID", values.client_id.to_string()),
+        ("OAUTH_CLIENT_SECRET", values.client_secret.to_string()),
+        ("USER_IMAGE", values.user_image.to_string()),
+        ("USER_ENV_REPO", values.user_env_repo.unwrap_or("").to_string()),
+        ("USER_ENV_REF", values.user_env_ref.unwrap_or("").to_string()),
+        ("DATASET_HOST_PATH", values.dataset_host.to_string()),
+        ("DATASET_MOUNT_PATH", values.dataset_mount.to_string()),
+        ("ALLOW_MISSING_DATASET", values.allow_missing_dataset.to_string()),
+        ("SHARED_HOST_PATH", values.shared_host.unwrap_or("").to_string()),
+        ("SHARED_MOUNT_PATH", values.shared_mount.to_string()),
+        ("ADMIN_USERS", values.admin_users.unwrap_or("").to_string()),
+        ("ALLOWED_USERS", values.allowed_users.unwrap_or("").to_string()),
+        ("OAUTH_AUTHORIZE_URL", values.oauth_authorize_url.unwrap_or("").to_string()),
+        ("OAUTH_TOKEN_URL", values.oauth_token_url.unwrap_or("").to_string()),
+        ("OAUTH_USERDATA_URL", values.oauth_userdata_url.unwrap_or("").to_string()),
+        ("OAUTH_USERNAME_KEY", values.oauth_username_key.to_string()),
+        ("REQUIRED_ENTITLEMENTS", values.required_entitlements.unwrap_or("").to_string()),
+        ("ENTITLEMENT_CLAIM", values.entitlement_claim.to_string()),
+        ("ENABLE_POSTGRES", values.production.to_string()),
+        ("DB_USER", values.db_user.to_string()),
+        ("DB_PASSWORD", values.db_password.to_string()),
+        ("DB_NAME", values.db_name.to_string()),
+        ("DB_HOST", values.db_host.to_string()),
+        ("DB_PORT", values.db_port.to_string()),
+        ("JUPYTERHUB_DB_URL", db_url),
+        ("CPU_LIMIT", values.cpu_limit.unwrap_or("").to_string()),
+        ("MEM_LIMIT", values.mem_limit.unwrap_or("").to_string()),
+        ("USER_LIMITS", values.user_limits.to_string()),
+        ("GROUP_LIMITS", values.group_limits.to_string()),
+        ("CULL_TIMEOUT", optional(values.cull_timeout)),
+        ("CULL_EVERY", optional(values.cull_every)),
+        ("CULL_GROUPS", values.cull_groups.to_string()),
+        ("GROUP_MEMBERS", values.group_members.to_string()),
+        ("GROUP_SHARES", values.group_shares.to_string()),
+        ("EXTRA_SERVICES", values.extra_services.to_string()),
+        ("HUB_API_TOKEN", values.hub_api_token.to_string()),
+        ("SPAWN_MIN_FREE_DISK_GB", optional(values.spawn_min_free_disk_gb)),
+        ("SPAWN_MIN_FREE_MEM_GB", optional(values.spawn_min_free_mem_gb)),
+        ("ORPHAN_VOLUME_POLICY", values.orphan_volume_policy.to_string()),
+        ("ORPHAN_VOLUME_GRACE", values.orphan_volume_grace.to_string()),
+        ("ENABLE_PUBLISHING", values.publishing.to_string()),
+        ("HTTP_PORT", values.http_port.to_string()),
+        ("HTTPS_PORT", values.https_port.to_string()),
+        ("SECURITY_HEADERS", values.security_headers.to_string()),
+        ("CONTENT_SECURITY_POLICY", values.csp.unwrap_or("").to_string()),
+        ("RATE_LIMIT", optional(values.rate_limit)),
+        ("RATE_LIMIT_BURST", optional(values.rate_limit_burst)),
+        ("CONCURRENT_SPAWN_LIMIT", optional(values.concurrent_spawn_limit)),
+        ("ACTIVE_SERVER_LIMIT", optional(values.active_server_limit)),
+        ("INTERNAL_TLS", values.internal_tls.to_string()),
+        ("DOCKER_SOCKET", values.docker_socket.to_string()),
+        ("REMOTE_DOCKER_CONTEXT", values.docker_context.unwrap_or("").to_string()),
+        ("REMOTE_DOCKER_HOST", values.docker_host.unwrap_or("").to_string()),
+        ("REMOTE_DOCKER_CERT_PATH", values.docker_cert_path.unwrap_or("").to_string()),
+        ("SELINUX_LABELS", values.selinux.to_string()),
+        ("SANDBOX_PROFILES", values.sandbox_profiles.to_string()),
+        ("APPARMOR_PROFILE", if values.apparmor { APPARMOR_PROFILE } else { "" }.to_string()),
+        ("NOTEBOOK_UID", optional(values.notebook_uid)),
+        ("NOTEBOOK_GID", optional(values.notebook_gid)),
+        ("WORKSPACE_BACKEND", values.workspace_backend.to_string()),
+        ("WORKSPACE_ROOT", values.workspace_root.unwrap_or("").to_string()),
+        ("WORKSPACE_HOST_PATH", values.workspace_host_path.unwrap_or("").to_string()),
+        ("WORKSPACE_QUOTA", values.workspace_quota.unwrap_or("").to_string()),
+        ("SCRATCH_ROOT", values.scratch_root.unwrap_or("").to_string()),
+        ("HUB_TITLE", values.hub_title.unwrap_or("").to_string()),
+        ("BRAND_COLOR", values.brand_color.unwrap_or("").to_string()),
+        ("BRAND_LOGO", values.brand_logo.unwrap_or("").to_string()),
+        ("WELCOME_TEXT", values.welcome_text.unwrap_or("").to_string()),
+        ("TERMS_OF_USE", values.terms_of_use.to_string()),
+        ("LANDING", values.landing.unwrap_or("").to_string()),
+        ("LANDING_SITE", values.landing_site.to_string()),
+        ("SUPPORT_EMAIL", values.support_email.unwrap_or("").to_string()),
+        ("DATA_CITATION", values.data_citation.unwrap_or("").to_string()),
+        ("ENABLE_DASK_GATEWAY", values.dask_gateway.to_string()),
+        ("DASK_GATEWAY_API_TOKEN", values.dask_gateway_token.to_string()),
+        ("ENABLE_MLFLOW", values.mlflow.to_string()),
+        ("MLFLOW_ARTIFACTS", values.mlflow_artifacts.to_string()),
+        ("ENABLE_NBGRADER", values.nbgrader.to_string()),
+        ("NBGRADER_COURSE", values.nbgrader_course.to_string()),
+        ("NBGRADER_INSTRUCTORS", values.nbgrader_instructors.to_string()),
+        ("NBGRADER_HOST_PATH", values.nbgrader_host.to_string()),
+        ("ENABLE_FILE_BROWSER", values.file_browser.to_string()),
+        ("FILE_BROWSER_OAUTH_SECRET", values.file_browser_oauth_secret.to_string()),
+        ("FILE_BROWSER_COOKIE_SECRET", values.file_browser_cookie_secret.to_string()),
+        ("ENABLE_LOGGING_STACK", values.logging_stack.to_string()),
+        ("LOG_RETENTION", values.log_retention.to_string()),
+        ("GRAFANA_ADMIN_PASSWORD", values.grafana_admin_password.to_string()),
+        ("LOG_DRIVER", values.log_driver.to_string()),
+        ("LOG_MAX_SIZE", values.log_max_size.to_string()),
+        ("LOG_MAX_FILE", values.log_max_file.to_string()),
+        ("PROXY_VERSION", values.proxy_version.to_string()),
+        ("HUB_LOG_FILE", HUB_LOG_FILE.to_string()),
+        ("ALLOW_DUMMY_AUTH", values.dummy_password.is_some().to_string()),
+        ("DUMMY_PASSWORD", values.dummy_password.unwrap_or("").to_string()),
+    ];
+
+    let extra_names: Vec<&str> = values.extra_idps.iter().map(|idp| idp.preset.name()).collect();
+    entries.push(("EXTRA_IDPS", extra_names.join(",")));
+    for idp in values.extra_idps {
+        entries.push((idp.preset.client_id_key(), idp.client_id.clone()));
+        entries.push((idp.preset.client_secret_key(), idp.client_secret.clone()));
+    }
+
+    if let Some(project) = values.project_name {
+        entries.push(("COMPOSE_PROJECT_NAME", project.to_string()));
+    }
+    entries.push(("RESOURCE_PREFIX", values.resource_prefix.to_string()));
+    let network_prefix = values.project_name.unwrap_or("mvre-hub");
+    entries.push(("PROXY_NETWORK_NAME", format!("{}_proxy", network_prefix)));
+    entries.push(("DOCKER_NETWORK_NAME", format!("{}_users", network_prefix)));
+
+    entries
+        .into_iter()
+        .map(|(key, value)| format!("{}={}\n", key, value))
+        .collect()
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
 }
 
 pub fn jupyterhub_config() -> String {
     r#"
 import os
+import shutil
 
 from dockerspawner import DockerSpawner
 from oauthenticator.generic import GenericOAuthenticator
@@ -157,35 +942,431 @@ c.JupyterHub.hub_ip = "0.0.0.0"
 c.JupyterHub.hub_connect_ip = "jupyterhub"
 c.JupyterHub.bind_url = "http://:8000"
 
+# Internal TLS: Traefik reaches the hub over HTTPS with a certificate from
+# the deployment's CA, and JupyterHub encrypts its own traffic to user servers.
+if os.environ.get("INTERNAL_TLS", "false").lower() == "true":
+    c.JupyterHub.bind_url = "https://:8000"
+    c.JupyterHub.ssl_cert = "/etc/mvre-hub/tls/hub.crt"
+    c.JupyterHub.ssl_key = "/etc/mvre-hub/tls/hub.key"
+    c.JupyterHub.internal_ssl = True
+    c.JupyterHub.internal_certs_location = "/srv/jupyterhub/internal-ssl"
+    c.JupyterHub.trusted_alt_names = ["DNS:jupyterhub"]
+
+# Spawn limits for login bursts: further spawns are refused with a "try again"
+# page instead of queueing on the host. 0 means no limit.
+concurrent_spawn_limit = os.environ.get("CONCURRENT_SPAWN_LIMIT")
+if concurrent_spawn_limit:
+    c.JupyterHub.concurrent_spawn_limit = int(concurrent_spawn_limit)
+active_server_limit = os.environ.get("ACTIVE_SERVER_LIMIT")
+if active_server_limit:
+    c.JupyterHub.active_server_limit = int(active_server_limit)
+
+# A copy of the hub log on the data volume, for fail2ban on the host.
+hub_log_file = os.environ.get("HUB_LOG_FILE")
+if hub_log_file:
+    import logging
+    from logging.handlers import WatchedFileHandler
+
+    log_handler = WatchedFileHandler(hub_log_file)
+    log_handler.setFormatter(
+        logging.Formatter(
+            "[%(levelname)1.1s %(asctime)s %(name)s %(module)s:%(lineno)d] %(message)s",
+            datefmt="%Y-%m-%d %H:%M:%S",
+        )
+    )
+    c.JupyterHub.extra_log_handlers = [log_handler]
+
+
+class Announcement:
+    """Banner from `mvre-hub announce`, read on each page render so it
+    changes without a hub restart."""
+
+    path = "/srv/jupyterhub/announcement.html"
+
+    def text(self):
+        try:
+            with open(self.path) as f:
+                return f.read().strip()
+        except OSError:
+            return ""
+
+    def __str__(self):
+        return self.text()
+
+    def __bool__(self):
+        return bool(self.text())
+
+
+c.JupyterHub.template_vars = {"announcement": Announcement()}
+
+# Branding: a logo, page title, accent color, and login page texts, rendered
+# by the template overrides next to them.
+branding_dir = "/etc/mvre-hub/branding"
+brand_logo = os.environ.get("BRAND_LOGO")
+if brand_logo:
+    c.JupyterHub.logo_file = f"{branding_dir}/{brand_logo}"
+terms_of_use = ""
+if os.environ.get("TERMS_OF_USE", "false").lower() == "true":
+    with open(f"{branding_dir}/terms.html") as f:
+        terms_of_use = f.read()
+c.JupyterHub.template_vars.update(
+    {
+        "hub_title": os.environ.get("HUB_TITLE", ""),
+        "brand_color": os.environ.get("BRAND_COLOR", ""),
+        "welcome_text": os.environ.get("WELCOME_TEXT", ""),
+        "terms_of_use": terms_of_use,
+    }
+)
+if os.path.isdir(f"{branding_dir}/templates"):
+    c.JupyterHub.template_paths = [f"{branding_dir}/templates"]
+
 db_url = os.environ.get("JUPYTERHUB_DB_URL")
 if db_url:
     c.JupyterHub.db_url = db_url
 
+# Tenants put their project name in front of user containers and volumes, so
+# users of the same name on two tenants of one host share neither.
+resource_prefix = os.environ.get("RESOURCE_PREFIX", "")
 c.DockerSpawner.image = os.environ.get("USER_IMAGE", "mvre-user:latest")
-c.DockerSpawner.network_name = os.environ.get("DOCKER_NETWORK_NAME", "mvre-hub_default")
+c.DockerSpawner.network_name = os.environ.get("DOCKER_NETWORK_NAME", "mvre-hub_users")
+c.DockerSpawner.prefix = resource_prefix + "jupyter"
 c.DockerSpawner.remove = True
 c.DockerSpawner.use_internal_ip = True
 c.Spawner.notebook_dir = "/home/jovyan/work"
 
-volumes = {"jupyterhub-user-{username}": "/home/jovyan/work"}
+# Work directories are Docker volumes, or per-user ZFS datasets / Btrfs
+# subvolumes under WORKSPACE_HOST_PATH named after the escaped user name.
+workspace_host = os.environ.get("WORKSPACE_HOST_PATH")
+if workspace_host:
+    volumes = {
+        workspace_host + "/{username}": {"bind": "/home/jovyan/work", "mode": "rw"},
+    }
+else:
+    volumes = {resource_prefix + "jupyterhub-user-{username}": "/home/jovyan/work"}
 
 dataset_host = os.environ.get("DATASET_HOST_PATH")
 dataset_mount = os.environ.get("DATASET_MOUNT_PATH", "/data/mosaic")
 if dataset_host:
     volumes[dataset_host] = {"bind": dataset_mount, "mode": "ro"}
 
+# Scratch space for large intermediate outputs, outside the work directory.
+# The hub creates each user's directory before the spawn (see below).
+scratch_root = os.environ.get("SCRATCH_ROOT")
+if scratch_root:
+    volumes[scratch_root + "/{username}"] = {"bind": "/home/jovyan/scratch", "mode": "rw"}
+
 shared_host = os.environ.get("SHARED_HOST_PATH")
 shared_mount = os.environ.get("SHARED_MOUNT_PATH", "/home/jovyan/shared")
 if shared_host:
     volumes[shared_host] = {"bind": shared_mount, "mode": "ro"}
 
+# nbgrader: every server gets the course config and the assignment exchange.
+nbgrader = os.environ.get("ENABLE_NBGRADER", "false").lower() == "true"
+nbgrader_host = os.environ.get("NBGRADER_HOST_PATH")
+nbgrader_instructors = os.environ.get("NBGRADER_INSTRUCTORS", "instructors")
+if nbgrader and nbgrader_host:
+    volumes[nbgrader_host + "/exchange"] = {"bind": "/srv/nbgrader/exchange", "mode": "rw"}
+    volumes[nbgrader_host + "/nbgrader_config.py"] = {
+        "bind": "/etc/jupyter/nbgrader_config.py",
+        "mode": "ro",
+    }
+
+# On SELinux hosts the shared host paths need a label containers may read.
+selinux_labels = os.environ.get("SELINUX_LABELS", "false").lower() == "true"
+if selinux_labels:
+    for spec in volumes.values():
+        if isinstance(spec, dict):
+            spec["mode"] += ",z"
+
 c.DockerSpawner.volumes = volumes
 
+pre_spawn_hooks = []
+
+
+class SpawnRefused(Exception):
+    """Spawn refusal whose message is shown on the hub spawn page."""
+
+    def __init__(self, message):
+        super().__init__(message)
+        self.jupyterhub_message = message
+
+
+min_free_disk_gb = os.environ.get("SPAWN_MIN_FREE_DISK_GB")
+min_free_mem_gb = os.environ.get("SPAWN_MIN_FREE_MEM_GB")
+
+
+def available_memory_gb():
+    with open("/proc/meminfo") as meminfo:
+        for line in meminfo:
+            if line.startswith("MemAvailable:"):
+                return int(line.split()[1]) / 1024**2
+    return float("inf")
+
+
+def check_capacity(spawner):
+    if min_free_disk_gb:
+        free_gb = shutil.disk_usage("/srv/jupyterhub").free / 1024**3
+        if free_gb < float(min_free_disk_gb):
+            raise SpawnRefused(
+                f"The hub is low on storage ({free_gb:.1f} GB free, "
+                f"{min_free_disk_gb} GB required to start a server). "
+                "Please try again later or contact the hub administrators."
+            )
+    if min_free_mem_gb:
+        free_gb = available_memory_gb()
+        if free_gb < float(min_free_mem_gb):
+            raise SpawnRefused(
+                f"The hub is at capacity ({free_gb:.1f} GB memory available, "
+                f"{min_free_mem_gb} GB required to start a server). "
+                "Please try again later or contact the hub administrators."
+            )
+
+
+if min_free_disk_gb or min_free_mem_gb:
+    pre_spawn_hooks.append(check_capacity)
+
+
+def check_workspace(spawner):
+    # Docker would create a missing bind source as a root-owned directory on
+    # the parent dataset, outside any quota.
+    if not os.path.isdir(os.path.join("/srv/workspaces", spawner.escaped_name)):
+        raise SpawnRefused(
+            "Your workspace has not been created yet. "
+            "Please contact the hub administrators."
+        )
+
+
+if workspace_host:
+    pre_spawn_hooks.append(check_workspace)
+
+
+def create_scratch(spawner):
+    # Created here rather than by Docker, which would leave it owned by root.
+    path = os.path.join("/srv/scratch", spawner.escaped_name)
+    if not os.path.isdir(path):
+        os.makedirs(path, mode=0o700)
+        os.chown(
+            path,
+            int(os.environ.get("NOTEBOOK_UID") or 1000),
+            int(os.environ.get("NOTEBOOK_GID") or 100),
+        )
+
+
+if scratch_root:
+    pre_spawn_hooks.append(create_scratch)
+
+# Groups from the [groups] section of an answers file. The hub adds the
+# members at startup; it does not remove users dropped from the list.
+hub_groups = {}
+for entry in filter(None, os.environ.get("GROUP_MEMBERS", "").split(",")):
+    group, members = entry.split("=", 1)
+    hub_groups[group] = {"users": [user for user in members.split(":") if user]}
+# Instructors are added on the admin page, or listed in [groups].
+if nbgrader:
+    hub_groups.setdefault(nbgrader_instructors, {"users": []})
+if hub_groups:
+    c.JupyterHub.load_groups = hub_groups
+
+group_shares = dict(
+    entry.split("=", 1)
+    for entry in filter(None, os.environ.get("GROUP_SHARES", "").split(","))
+)
+
+
+def mount_group_shares(spawner):
+    # Each group's directory goes into its members' servers only, so the
+    # volumes are set per spawner rather than in c.DockerSpawner.volumes.
+    member_of = {group.name for group in spawner.user.groups}
+    mode = "rw,z" if selinux_labels else "rw"
+    volumes = dict(spawner.volumes)
+    for group, host in group_shares.items():
+        if group in member_of:
+            volumes[host] = {"bind": f"/home/jovyan/groups/{group}", "mode": mode}
+    spawner.volumes = volumes
+
+
+if group_shares:
+    pre_spawn_hooks.append(mount_group_shares)
+
+
+def restrict_nbgrader(spawner):
+    # The image enables every nbgrader extension. Students' servers read a
+    # config directory first that turns the formgrader and assignment
+    # creation off; instructors keep them.
+    if nbgrader_instructors in {group.name for group in spawner.user.groups}:
+        return
+    volumes = dict(spawner.volumes)
+    volumes[nbgrader_host + "/student"] = {
+        "bind": "/srv/nbgrader/student",
+        "mode": "ro,z" if selinux_labels else "ro",
+    }
+    spawner.volumes = volumes
+    spawner.environment = {
+        **spawner.environment,
+        "JUPYTER_CONFIG_PATH": "/srv/nbgrader/student",
+    }
+
+
+if nbgrader and nbgrader_host:
+    pre_spawn_hooks.append(restrict_nbgrader)
+
+
+def parse_limits(value):
+    limits = {}
+    for entry in filter(None, value.split(",")):
+        name, spec = entry.split("=", 1)
+        cpu, mem = spec.split(":", 1)
+        limits[name] = (float(cpu) if cpu else None, mem or None)
+    return limits
+
+
+user_limits = parse_limits(os.environ.get("USER_LIMITS", ""))
+group_limits = parse_limits(os.environ.get("GROUP_LIMITS", ""))
+BYTE_UNITS = {"K": 1024, "M": 1024**2, "G": 1024**3, "T": 1024**4}
+
+
+def byte_count(value):
+    value = str(value)
+    if value[-1] in BYTE_UNITS:
+        return float(value[:-1]) * BYTE_UNITS[value[-1]]
+    return float(value)
+
+
+def apply_resource_limits(spawner):
+    """Limits from the [limits] section: the user's own, else the largest
+    of their groups', else CPU_LIMIT/MEM_LIMIT. An options form or API
+    request may ask for less through `cpu_limit`/`mem_limit` in
+    user_options, never for more."""
+    cpu, mem = user_limits.get(spawner.user.name, (None, None))
+    member_of = [group.name for group in spawner.user.groups]
+    group_cpus = [group_limits[g][0] for g in member_of if g in group_limits and group_limits[g][0]]
+    group_mems = [group_limits[g][1] for g in member_of if g in group_limits and group_limits[g][1]]
+    if cpu is None and group_cpus:
+        cpu = max(group_cpus)
+    if mem is None and group_mems:
+        mem = max(group_mems, key=byte_count)
+    if cpu is not None:
+        spawner.cpu_limit = cpu
+    if mem is not None:
+        spawner.mem_limit = mem
+
+    options = spawner.user_options or {}
+    requested_cpu = options.get("cpu_limit")
+    requested_mem = options.get("mem_limit")
+    try:
+        requested = (
+            float(requested_cpu) if requested_cpu else None,
+            byte_count(requested_mem) if requested_mem else None,
+        )
+    except (TypeError, ValueError):
+        raise SpawnRefused("The requested CPU or memory limit is not a number.")
+    if requested[0]:
+        if spawner.cpu_limit and requested[0] > spawner.cpu_limit:
+            raise SpawnRefused(f"You may use at most {spawner.cpu_limit:g} CPUs.")
+        spawner.cpu_limit = requested[0]
+    if requested[1]:
+        if spawner.mem_limit and requested[1] > spawner.mem_limit:
+            raise SpawnRefused(
+                f"You may use at most {spawner.mem_limit / BYTE_UNITS['G']:g} GB of memory."
+            )
+        spawner.mem_limit = int(requested[1])
+
+
+pre_spawn_hooks.append(apply_resource_limits)
+
 env = {"MOSAIC_DATA": dataset_mount}
 if shared_host:
     env["MOSAIC_SHARED"] = shared_mount
+if scratch_root:
+    env["SCRATCH"] = "/home/jovyan/scratch"
+
+# Run notebooks under the UID/GID that may read the dataset. The
+# docker-stacks start script switches jovyan to NB_UID/NB_GID, which needs
+# the container to start as root; it drops to that user before the server
+# starts, and fixes the ownership of the home directory.
+notebook_uid = os.environ.get("NOTEBOOK_UID")
+notebook_gid = os.environ.get("NOTEBOOK_GID")
+if notebook_uid or notebook_gid:
+    c.DockerSpawner.extra_create_kwargs = {"user": "root"}
+    if notebook_uid:
+        env["NB_UID"] = notebook_uid
+    if notebook_gid:
+        env["NB_GID"] = notebook_gid
+    env["CHOWN_HOME"] = "yes"
+    env["CHOWN_HOME_OPTS"] = "-R"
+# Dask Gateway: notebooks reach the API and schedulers directly on the users
+# network; dashboard links go through the hub's proxy.
+dask_gateway = os.environ.get("ENABLE_DASK_GATEWAY", "false").lower() == "true"
+if dask_gateway:
+    env["DASK_GATEWAY__ADDRESS"] = "http://dask-gateway:8000/services/dask-gateway"
+    env["DASK_GATEWAY__PROXY_ADDRESS"] = "gateway://dask-gateway:8786"
+    env["DASK_GATEWAY__PUBLIC_ADDRESS"] = "/services/dask-gateway/"
+    env["DASK_GATEWAY__AUTH__TYPE"] = "jupyterhub"
+if os.environ.get("ENABLE_MLFLOW", "false").lower() == "true":
+    env["MLFLOW_TRACKING_URI"] = "http://mlflow:5000"
+if nbgrader:
+    env["NBGRADER_COURSE"] = os.environ.get("NBGRADER_COURSE", "mosaic")
 c.Spawner.environment = env
 
+
+def add_user_env(spawner):
+    """Variables from `mvre-hub user-env`, read at each spawn so changes
+    apply without a hub restart: the user's groups in name order, then the
+    user's own."""
+    import tomllib
+
+    try:
+        with open("/srv/jupyterhub/user-env.toml", "rb") as f:
+            table = tomllib.load(f)
+    except FileNotFoundError:
+        return
+    except (OSError, tomllib.TOMLDecodeError) as e:
+        spawner.log.warning("Ignoring user-env.toml: %s", e)
+        return
+    extra = {}
+    for group in sorted(g.name for g in spawner.user.groups):
+        extra.update(table.get("groups", {}).get(group, {}))
+    extra.update(table.get("users", {}).get(spawner.user.name, {}))
+    if extra:
+        spawner.environment = {**spawner.environment, **extra}
+
+
+pre_spawn_hooks.append(add_user_env)
+
+
+def pre_spawn_hook(spawner):
+    for hook in pre_spawn_hooks:
+        hook(spawner)
+
+
+c.Spawner.pre_spawn_hook = pre_spawn_hook
+
+extra_host_config = {}
+
+# Seccomp (and optionally AppArmor) profiles for user containers. The
+# Docker API takes the seccomp profile itself rather than a path.
+if os.environ.get("SANDBOX_PROFILES", "false").lower() == "true":
+    with open("/etc/mvre-hub/sandbox/seccomp.json") as profile:
+        security_opt = ["no-new-privileges:true", "seccomp=" + profile.read()]
+    apparmor_profile = os.environ.get("APPARMOR_PROFILE")
+    if apparmor_profile:
+        security_opt.append("apparmor=" + apparmor_profile)
+    extra_host_config["security_opt"] = security_opt
+
+# User servers rotate their logs like the hub's own containers.
+log_driver = os.environ.get("LOG_DRIVER")
+if log_driver:
+    log_options = {}
+    if log_driver != "journald":
+        log_options = {
+            "max-size": os.environ.get("LOG_MAX_SIZE", "50m"),
+            "max-file": os.environ.get("LOG_MAX_FILE", "5"),
+        }
+    extra_host_config["log_config"] = {"type": log_driver, "config": log_options}
+
+if extra_host_config:
+    c.DockerSpawner.extra_host_config = extra_host_config
+
 cpu_limit = os.environ.get("CPU_LIMIT")
 mem_limit = os.environ.get("MEM_LIMIT")
 if cpu_limit:
@@ -193,29 +1374,89 @@ if cpu_limit:
 if mem_limit:
     c.DockerSpawner.mem_limit = mem_limit
 
-cull_timeout = os.environ.get("CULL_TIMEOUT")
-if cull_timeout:
-    cull_every = os.environ.get("CULL_EVERY", "300")
-    c.JupyterHub.services = [
+services = []
+roles = []
+
+api_token = os.environ.get("HUB_API_TOKEN")
+if api_token:
+    services.append({"name": "mvre-hub-cli", "api_token": api_token})
+    roles.append(
         {
-            "name": "idle-culler",
+            "name": "mvre-hub-cli",
+            "services": ["mvre-hub-cli"],
+            "scopes": [
+                "admin:users",
+                "admin:servers",
+                "admin:groups",
+                "tokens",
+                "proxy",
+                "read:hub",
+            ],
+        }
+    )
+
+if dask_gateway:
+    services.append(
+        {
+            "name": "dask-gateway",
+            "api_token": os.environ["DASK_GATEWAY_API_TOKEN"],
+            "url": "http://dask-gateway:8000",
+        }
+    )
+
+# The dataset file browser signs users in with the hub as OAuth provider.
+if os.environ.get("ENABLE_FILE_BROWSER", "false").lower() == "true":
+    services.append(
+        {
+            "name": "file-browser",
+            "oauth_client_id": "service-file-browser",
+            "api_token": os.environ["FILE_BROWSER_OAUTH_SECRET"],
+            "oauth_redirect_uri": f"https://{os.environ.get('HUB_DOMAIN', '')}/files/_oauth",
+            "oauth_no_confirm": True,
+        }
+    )
+
+cull_every = os.environ.get("CULL_EVERY", "300")
+
+
+def add_culler(name, timeout, scope_filter=""):
+    services.append(
+        {
+            "name": name,
             "command": [
                 "python",
                 "-m",
                 "jupyterhub_idle_culler",
-                f"--timeout={cull_timeout}",
+                f"--timeout={timeout}",
                 f"--cull-every={cull_every}",
                 "--cull-users",
             ],
         }
-    ]
-    c.JupyterHub.load_roles = [
+    )
+    roles.append(
         {
-            "name": "idle-culler",
-            "services": ["idle-culler"],
-            "scopes": ["list:users", "read:users", "admin:users"],
+            "name": name,
+            "services": [name],
+            "scopes": [
+                scope + scope_filter
+                for scope in ["list:users", "read:users", "admin:users", "servers"]
+            ],
         }
-    ]
+    )
+
+
+cull_timeout = os.environ.get("CULL_TIMEOUT")
+if cull_timeout:
+    add_culler("idle-culler", cull_timeout)
+
+# Per-group policies: each culler only sees its group's members, so users in
+# several groups are culled after the shortest of their timeouts.
+for policy in filter(None, os.environ.get("CULL_GROUPS", "").split(",")):
+    group, timeout = policy.split("=", 1)
+    add_culler(f"idle-culler-{group}", timeout, f"!group={group}")
+
+c.JupyterHub.services = services
+c.JupyterHub.load_roles = roles
 
 admin_users = os.environ.get("ADMIN_USERS", "")
 if admin_users:
@@ -223,21 +1464,134 @@ if admin_users:
         user.strip() for user in admin_users.split(",") if user.strip()
     }
 
+allowed_users = os.environ.get("ALLOWED_USERS", "")
+if allowed_users:
+    c.Authenticator.allowed_users = {
+        user.strip() for user in allowed_users.split(",") if user.strip()
+    }
+
 authorize_url = os.environ.get("OAUTH_AUTHORIZE_URL")
 token_url = os.environ.get("OAUTH_TOKEN_URL")
 userdata_url = os.environ.get("OAUTH_USERDATA_URL")
 
-if authorize_url and token_url and userdata_url:
-    c.JupyterHub.authenticator_class = GenericOAuthenticator
-    c.GenericOAuthenticator.client_id = os.environ.get("OAUTH_CLIENT_ID")
-    c.GenericOAuthenticator.client_secret = os.environ.get("OAUTH_CLIENT_SECRET")
-    c.GenericOAuthenticator.authorize_url = authorize_url
-    c.GenericOAuthenticator.token_url = token_url
-    c.GenericOAuthenticator.userdata_url = userdata_url
-    c.GenericOAuthenticator.username_key = os.environ.get(
-        "OAUTH_USERNAME_KEY", "preferred_username"
+hub_domain = os.environ.get("HUB_DOMAIN", "")
+oauth_preset = os.environ.get("OAUTH_PRESET", "helmholtz") or "helmholtz"
+
+# Endpoints, scopes and the username claim of providers that need nothing
+# but client credentials. ORCID users get their ORCID iD as username, CILogon
+# users their (lower-cased) email address.
+IDP_PRESETS = {
+    "orcid": {
+        "login_service": "ORCID",
+        "authorize_url": "https://orcid.org/oauth/authorize",
+        "token_url": "https://orcid.org/oauth/token",
+        "userdata_url": "https://orcid.org/oauth/userinfo",
+        "scope": ["openid"],
+        "username_key": "sub",
+    },
+    "cilogon": {
+        "login_service": "CILogon",
+        "authorize_url": "https://cilogon.org/authorize",
+        "token_url": "https://cilogon.org/oauth2/token",
+        "userdata_url": "https://cilogon.org/oauth2/userinfo",
+        "scope": ["openid", "email", "org.cilogon.userinfo"],
+        "username_key": "email",
+    },
+}
+
+
+def require_entitlements(claim, required):
+    # A post-auth hook runs whatever the allow-list says, so allow-listed
+    # users need an entitlement too. Returning None refuses the login.
+    def check(authenticator, handler, authentication):
+        user_info = (authentication.get("auth_state") or {}).get(
+            authenticator.user_auth_state_key
+        ) or {}
+        entitlements = user_info.get(claim) or []
+        if isinstance(entitlements, str):
+            entitlements = [entitlements]
+        if required.isdisjoint(entitlements):
+            return None
+        return authentication
+
+    return check
+
+
+def primary_oauth_settings():
+    if oauth_preset in IDP_PRESETS:
+        settings = dict(IDP_PRESETS[oauth_preset])
+    else:
+        settings = {
+            "login_service": "Helmholtz AAI",
+            "authorize_url": authorize_url,
+            "token_url": token_url,
+            "userdata_url": userdata_url,
+            "username_key": os.environ.get("OAUTH_USERNAME_KEY", "preferred_username"),
+        }
+    settings["client_id"] = os.environ.get("OAUTH_CLIENT_ID")
+    settings["client_secret"] = os.environ.get("OAUTH_CLIENT_SECRET")
+    required_entitlements = os.environ.get("REQUIRED_ENTITLEMENTS", "")
+    if required_entitlements:
+        entitlement_claim = os.environ.get("ENTITLEMENT_CLAIM", "eduperson_entitlement")
+        settings["scope"] = sorted(
+            set(settings.get("scope", ["openid", "profile", "email"])) | {entitlement_claim}
+        )
+        settings["claim_groups_key"] = entitlement_claim
+        required = {
+            entitlement.strip()
+            for entitlement in required_entitlements.split(",")
+            if entitlement.strip()
+        }
+        settings["post_auth_hook"] = require_entitlements(entitlement_claim, required)
+        # Allowed groups and allowed users admit either one; with an
+        # allow-list the entitlement must not let anyone else in.
+        if not allowed_users:
+            settings["allowed_groups"] = required
+    return settings
+
+
+def extra_idp_settings(name):
+    key = name.upper()
+    return dict(
+        IDP_PRESETS[name],
+        client_id=os.environ.get(f"{key}_CLIENT_ID"),
+        client_secret=os.environ.get(f"{key}_CLIENT_SECRET"),
     )
-    hub_domain = os.environ.get("HUB_DOMAIN", "")
+
+
+extra_idps = [
+    name.strip() for name in os.environ.get("EXTRA_IDPS", "").split(",") if name.strip()
+]
+
+primary_configured = oauth_preset in IDP_PRESETS or (
+    authorize_url and token_url and userdata_url
+)
+
+if primary_configured and extra_idps:
+    from multiauthenticator import MultiAuthenticator
+
+    c.JupyterHub.authenticator_class = MultiAuthenticator
+    routes = [(oauth_preset, primary_oauth_settings())] + [
+        (name, extra_idp_settings(name)) for name in extra_idps if name != oauth_preset
+    ]
+    authenticators = []
+    for route, settings in routes:
+        if hub_domain:
+            settings["oauth_callback_url"] = (
+                f"https://{hub_domain}/hub/{route}/oauth_callback"
+            )
+        authenticators.append(
+            {
+                "authenticator_class": GenericOAuthenticator,
+                "url_prefix": f"/{route}",
+                "config": settings,
+            }
+        )
+    c.MultiAuthenticator.authenticators = authenticators
+elif primary_configured:
+    c.JupyterHub.authenticator_class = GenericOAuthenticator
+    for key, value in primary_oauth_settings().items():
+        setattr(c.GenericOAuthenticator, key, value)
     if hub_domain:
         c.GenericOAuthenticator.oauth_callback_url = (
             f"https://{hub_domain}/hub/oauth_callback"
@@ -261,7 +1615,7 @@ pub fn hub_dockerfile() -> String {
     r#"
 FROM jupyterhub/jupyterhub:latest
 
-RUN pip install --no-cache-dir dockerspawner oauthenticator jupyterhub-idle-culler
+RUN pip install --no-cache-dir dockerspawner oauthenticator jupyterhub-multiauthenticator jupyterhub-idle-culler
 
 COPY jupyterhub_config.py /etc/jupyterhub/jupyterhub_config.py
 "#
@@ -281,9 +1635,13 @@ RUN pip install --no-cache-dir -r /tmp/requirements.txt \
     .to_string()
 }
 
-pub fn user_requirements() -> String {
-    "xarray\nnetCDF4\ndask\npandas\nnumpy\nmatplotlib\nscipy\n"
-        .to_string()
+pub fn user_requirements(dask_gateway: bool) -> String {
+    let mut requirements = "xarray\nnetCDF4\ndask\npandas\nnumpy\nmatplotlib\nscipy\n".to_string();
+    if dask_gateway {
+        requirements.push_str(&dask::client_requirement());
+        requirements.push('\n');
+    }
+    requirements
 }
 
 pub fn mosaic_notebook() -> String {
@@ -367,3 +1725,146 @@ Data mount:
     .trim_start()
     .to_string()
 }
+
+/// fail2ban filter matching rejected logins in JupyterHub's request log, e.g.
+/// `403 GET /hub/oauth_callback?code=... (@203.0.113.7) 12.34ms`.
+pub fn fail2ban_filter() -> String {
+    r#"
+[Definition]
+failregex = ^\[[WE] .*\] 40[13] (?:GET|POST) /hub/(?:[\w-]+/)?(?:oauth_callback|login)\S* \([^@)]*@<HOST>\)
+ignoreregex =
+"#
+    .trim_start()
+    .to_string()
+}
+
+pub struct JailValues<'a> {
+    /// Host path of the hub log file, or `None` to read the container's
+    /// journald entries.
+    pub log_path: Option<&'a str>,
+    pub container_name: &'a str,
+    pub max_retry: u32,
+    pub find_time: &'a str,
+    pub ban_time: &'a str,
+}
+
+/// Jail for the filter above. Bans go into Docker's `DOCKER-USER` chain,
+/// because forwarded traffic to Traefik never passes `INPUT`.
+pub fn fail2ban_jail(values: &JailValues) -> String {
+    let source = match values.log_path {
+        Some(path) => format!("backend = auto\nlogpath = {}", path),
+        None => format!("backend = systemd\njournalmatch = CONTAINER_NAME={}", values.container_name),
+    };
+    format!(
+        r#"[mvre-hub]
+enabled = true
+filter = mvre-hub
+{source}
+maxretry = {max_retry}
+findtime = {find_time}
+bantime = {ban_time}
+action = iptables-allports[name=mvre-hub, chain=DOCKER-USER]
+"#,
+        source = source,
+        max_retry = values.max_retry,
+        find_time = values.find_time,
+        ban_time = values.ban_time,
+    )
+}
+
+/// Syscalls user containers may not make, beyond what Docker's default
+/// profile already refuses: kernel and clock administration, namespaces and
+/// mounts, tracing other processes, and interfaces with a history of
+/// privilege escalation bugs.
+const SECCOMP_DENIED: &[&str] = &[
+    "acct", "add_key", "adjtimex", "bpf", "clock_adjtime", "clock_settime", "create_module",
+    "delete_module", "fanotify_init", "finit_module", "fsconfig", "fsmount", "fsopen", "fspick",
+    "get_kernel_syms", "get_mempolicy", "init_module", "io_uring_enter", "io_uring_register",
+    "io_uring_setup", "ioperm", "iopl", "kcmp", "kexec_file_load", "kexec_load", "keyctl",
+    "lookup_dcookie", "mbind", "mount", "mount_setattr", "move_mount", "move_pages",
+    "name_to_handle_at", "nfsservctl", "open_by_handle_at", "open_tree", "perf_event_open",
+    "personality", "pivot_root", "process_vm_readv", "process_vm_writev", "ptrace", "query_module",
+    "quotactl", "reboot", "request_key", "set_mempolicy", "setns", "settimeofday", "stime",
+    "swapoff", "swapon", "syslog", "sysfs", "umount", "umount2", "unshare", "uselib",
+    "userfaultfd", "ustat", "vm86", "vm86old", "_sysctl",
+];
+
+/// `CLONE_NEWUSER`; user namespaces are the usual first step of container
+/// escapes.
+const CLONE_NEWUSER: u64 = 0x1000_0000;
+
+/// Seccomp profile for spawned user containers. Everything not listed is
+/// allowed, so scientific software keeps working; denied calls fail with
+/// `EPERM`. `clone3` reports `ENOSYS` so libc falls back to `clone`, whose
+/// flags can be inspected.
+pub fn seccomp_profile() -> String {
+    let profile = serde_json::json!({
+        "defaultAction": "SCMP_ACT_ALLOW",
+        "architectures": ["SCMP_ARCH_X86_64", "SCMP_ARCH_X86", "SCMP_ARCH_X32", "SCMP_ARCH_AARCH64", "SCMP_ARCH_ARM"],
+        "syscalls": [
+            {
+                "names": SECCOMP_DENIED,
+                "action": "SCMP_ACT_ERRNO",
+                "errnoRet": 1,
+            },
+            {
+                "names": ["clone"],
+                "action": "SCMP_ACT_ERRNO",
+                "errnoRet": 1,
+                "args": [{ "index": 0, "value": CLONE_NEWUSER, "valueTwo": CLONE_NEWUSER, "op": "SCMP_CMP_MASKED_EQ" }],
+            },
+            {
+                "names": ["clone3"],
+                "action": "SCMP_ACT_ERRNO",
+                "errnoRet": 38,
+            },
+        ],
+    });
+    let mut rendered = serde_json::to_string_pretty(&profile).unwrap_or_default();
+    rendered.push('\n');
+    rendered
+}
+
+/// Name of the AppArmor profile for user containers.
+pub const APPARMOR_PROFILE: &str = "mvre-hub-user";
+
+/// AppArmor profile for user containers: Docker's default profile, plus no
+/// raw sockets and no tracing.
+pub fn apparmor_profile() -> String {
+    format!(
+        r#"#include <tunables/global>
+
+profile {name} flags=(attach_disconnected,mediate_deleted) {{
+  #include <abstractions/base>
+
+  network inet,
+  network inet6,
+  network unix,
+  deny network raw,
+  deny network packet,
+  capability,
+  file,
+  umount,
+  signal (receive) peer=unconfined,
+  signal (send,receive) peer={name},
+  deny ptrace,
+
+  deny mount,
+  deny @{{PROC}}/* w,
+  deny @{{PROC}}/{{[^1-9],[^1-9][^0-9],[^1-9s][^0-9y][^0-9s],[^1-9][^0-9][^0-9][^0-9]*}}/** w,
+  deny @{{PROC}}/sys/[^k]** w,
+  deny @{{PROC}}/sys/kernel/{{?,??,[^s][^h][^m]**}} w,
+  deny @{{PROC}}/sysrq-trigger rwklx,
+  deny @{{PROC}}/kcore rwklx,
+  deny /sys/[^f]*/** wklx,
+  deny /sys/f[^s]*/** wklx,
+  deny /sys/fs/[^c]*/** wklx,
+  deny /sys/fs/c[^g]*/** wklx,
+  deny /sys/fs/cg[^r]*/** wklx,
+  deny /sys/firmware/** rwklx,
+  deny /sys/kernel/security/** rwklx,
+}}
+"#,
+        name = APPARMOR_PROFILE
+    )
+}
@@ -0,0 +1,230 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{
+    cli::{DeployOptions, TenantAddOptions, TenantCommand, TenantRemoveOptions},
+    config::{self, AppConfig, Tenant},
+    deploy,
+    engine::Engine,
+    failure, lock, prune, say, util,
+};
+
+const DEFAULT_TENANT_ROOT: &str = "/srv/mvre-hub";
+const BASE_HTTP_PORT: u16 = 8080;
+const BASE_HTTPS_PORT: u16 = 8443;
+
+pub fn run(command: TenantCommand, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    match command {
+        TenantCommand::Add(opts) => add(*opts, config_path, app_config),
+        TenantCommand::Remove(opts) => remove(opts, config_path, app_config),
+        TenantCommand::List => {
+            list(app_config);
+            Ok(())
+        }
+    }
+}
+
+fn add(opts: TenantAddOptions, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    validate_name(&opts.name)?;
+    let domain = opts
+        .deploy
+        .domain
+        .clone()
+        .context("A tenant needs its own domain; pass --domain")?;
+    if app_config.tenants.contains_key(&opts.name) {
+        return Err(failure::validation(format!("Tenant '{}' already exists", opts.name)));
+    }
+    if app_config.tenants.values().any(|tenant| tenant.domain == domain) {
+        return Err(failure::validation(format!("Domain {} is already used by another tenant", domain)));
+    }
+    if !util::is_root() {
+        return Err(failure::permission("Root required to create tenant groups and directories"));
+    }
+
+    let root = opts
+        .root
+        .clone()
+        .or_else(|| app_config.tenant_root.clone())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_TENANT_ROOT));
+    let deploy_dir = root.join(&opts.name);
+    let group = format!("mvre-{}", opts.name);
+    let project_name = format!("mvre-{}", opts.name);
+    let offset = next_port_offset(app_config);
+
+    run_tool("groupadd", &["--force", &group])?;
+
+    let deploy_opts = DeployOptions {
+        http_port: BASE_HTTP_PORT + offset,
+        https_port: BASE_HTTPS_PORT + offset,
+        project_name: Some(project_name.clone()),
+        resource_prefix: Some(resource_prefix(&project_name)),
+        no_systemd: true,
+        ..opts.deploy
+    };
+    deploy::run_in(Some(deploy_dir.clone()), deploy_opts, config_path, app_config)?;
+
+    assign_group(&deploy_dir, &group)?;
+
+    let tenant = Tenant {
+        deploy_dir: deploy_dir.clone(),
+        domain,
+        group: group.clone(),
+        project_name,
+        http_port: BASE_HTTP_PORT + offset,
+        https_port: BASE_HTTPS_PORT + offset,
+    };
+    say!(
+        "{}",
+        style(format!(
+            "Tenant {} ready: {} (group {}, ports {}/{})",
+            opts.name,
+            deploy_dir.display(),
+            group,
+            tenant.http_port,
+            tenant.https_port
+        ))
+        .green()
+    );
+    say!(
+        "{}",
+        style("Route the tenant's domain to its HTTPS port from the host's front proxy").dim()
+    );
+
+    util::set_result(serde_json::json!({ "name": opts.name, "tenant": tenant }));
+    app_config.tenants.insert(opts.name, tenant);
+    if app_config.tenant_root.is_none() {
+        app_config.tenant_root = Some(root);
+    }
+    config::save(config_path, app_config)
+}
+
+fn remove(opts: TenantRemoveOptions, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    let tenant = app_config
+        .tenants
+        .get(&opts.name)
+        .cloned()
+        .with_context(|| format!("Tenant '{}' not found", opts.name))?;
+    if !util::is_root() {
+        return Err(failure::permission("Root required to remove tenant groups and directories"));
+    }
+
+    if tenant.deploy_dir.exists() {
+        // The tenant is not the deployment the global lock covers.
+        let _lock = lock::acquire(&tenant.deploy_dir, &lock::invocation())?;
+        let engine = Engine::new(&tenant.deploy_dir);
+        // Images are only removed when the tenant's prefix names them;
+        // shared ones like traefik stay for the other deployments.
+        let images = if opts.purge { engine.compose_images().unwrap_or_default() } else { Vec::new() };
+        let args: &[&str] = if opts.purge { &["down", "-v"] } else { &["down"] };
+        if let Err(err) = engine.compose(args) {
+            tracing::warn!("failed to stop tenant services: {:#}", err);
+        }
+        if opts.purge {
+            let prefix = resource_prefix(&tenant.project_name);
+            // The full volume prefix, so tenant `geo` leaves `geo-2`'s volumes alone.
+            let volume_prefix = format!("{}{}", prefix, prune::USER_VOLUME_PREFIX);
+            let volumes = engine.volume_names().unwrap_or_default();
+            for volume in volumes.iter().filter(|volume| volume.starts_with(&volume_prefix)) {
+                if let Err(err) = engine.docker_output(&["volume", "rm", volume]) {
+                    tracing::warn!("failed to remove volume {}: {:#}", volume, err);
+                }
+            }
+            for image in images.iter().filter(|image| image.starts_with(&prefix)) {
+                if let Err(err) = engine.docker_output(&["image", "rm", image]) {
+                    tracing::warn!("failed to remove image {}: {:#}", image, err);
+                }
+            }
+            std::fs::remove_dir_all(&tenant.deploy_dir)
+                .with_context(|| format!("failed to remove {}", tenant.deploy_dir.display()))?;
+        }
+    }
+
+    run_tool("groupdel", &[&tenant.group])?;
+
+    app_config.tenants.remove(&opts.name);
+    app_config.forget(&tenant.deploy_dir);
+    config::save(config_path, app_config)?;
+
+    util::set_result(serde_json::json!({ "name": opts.name, "purged": opts.purge }));
+    if opts.purge {
+        say!("{}", style(format!("Tenant {} removed with all data", opts.name)).cyan());
+    } else {
+        say!("{}", style(format!("Tenant {} removed", opts.name)).cyan());
+        say!("Data kept at {}", style(tenant.deploy_dir.display()).dim());
+    }
+    Ok(())
+}
+
+fn list(app_config: &AppConfig) {
+    util::set_result(serde_json::json!({ "tenants": app_config.tenants }));
+    if app_config.tenants.is_empty() {
+        say!("{}", style("No tenants").dim());
+        return;
+    }
+    for (name, tenant) in &app_config.tenants {
+        say!(
+            "  {:<16} {:<28} {:<6}/{:<6} {}",
+            name,
+            tenant.domain,
+            tenant.http_port,
+            tenant.https_port,
+            style(tenant.deploy_dir.display()).dim()
+        );
+    }
+}
+
+/// Smallest port offset not yet taken by another tenant (the untenanted
+/// default deployment keeps offset 0).
+fn next_port_offset(app_config: &AppConfig) -> u16 {
+    let used: Vec<u16> = app_config
+        .tenants
+        .values()
+        .map(|tenant| tenant.https_port.saturating_sub(BASE_HTTPS_PORT))
+        .collect();
+    (1..).find(|offset| !used.contains(offset)).unwrap_or(1)
+}
+
+/// Prefix of the tenant's user volumes, user containers, and user image.
+pub fn resource_prefix(project_name: &str) -> String {
+    format!("{}-", project_name)
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 24
+        && name.starts_with(|ch: char| ch.is_ascii_lowercase())
+        && name.chars().all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-');
+    if !valid {
+        return Err(failure::validation(format!("Invalid tenant name '{}' (lowercase letters, digits, '-'; max 24 chars)", name)));
+    }
+    Ok(())
+}
+
+fn assign_group(deploy_dir: &Path, group: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let gid = nix::unistd::Group::from_name(group)?
+            .with_context(|| format!("group {} not found", group))?
+            .gid;
+        nix::unistd::chown(deploy_dir, None, Some(gid))?;
+        util::set_file_mode(deploy_dir, 0o2770)?;
+    }
+    Ok(())
+}
+
+fn run_tool(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("failed to run {}", program))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(failure::permission(format!("{} failed: {}", program, status)))
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{cli::TlsMode, util};
+
+/// Internal CA and the hub's server certificate, inside the deployment.
+pub const INTERNAL_TLS_DIR: &str = "internal-tls";
+
+impl TlsMode {
+    /// `.env` value.
+    pub fn name(self) -> &'static str {
+        match self {
+            TlsMode::Acme => "acme",
+            TlsMode::SelfSigned => "self-signed",
+            TlsMode::Off => "off",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "acme" => Some(TlsMode::Acme),
+            "self-signed" => Some(TlsMode::SelfSigned),
+            "off" => Some(TlsMode::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Let's Encrypt's staging directory; its certificates chain to an untrusted root.
+pub const ACME_STAGING_SERVER: &str = "https://acme-staging-v02.api.letsencrypt.org/directory";
+pub const ACME_STAGING_NOTICE: &str =
+    "Certificates come from Let's Encrypt staging and are not trusted by browsers; switch with reconfigure --no-acme-staging";
+
+/// Traefik's certificate store under `./traefik`. Staging certificates are
+/// kept apart so switching to production does not serve them.
+pub fn acme_storage(staging: bool) -> &'static str {
+    if staging {
+        "acme-staging.json"
+    } else {
+        "acme.json"
+    }
+}
+
+/// Local CA and the public server certificate of `--tls self-signed`.
+pub const LOCAL_TLS_DIR: &str = "local-tls";
+/// Where Traefik sees the server certificate and key.
+pub const LOCAL_TLS_MOUNT: &str = "/local-tls";
+/// File-provider config in `./traefik`, mounted at `/certs`.
+pub const LOCAL_TLS_CONFIG: &str = "local-tls.yml";
+
+/// Name Traefik uses to reach the hub, and so the certificate's SAN.
+const HUB_HOST: &str = "jupyterhub";
+const CA_DAYS: &str = "3650";
+const CERT_DAYS: &str = "825";
+
+/// Creates the internal CA and a hub certificate signed by it, unless both
+/// already exist. Returns whether anything was generated.
+pub fn ensure_internal_certs(deploy_dir: &Path) -> Result<bool> {
+    let dir = deploy_dir.join(INTERNAL_TLS_DIR);
+    let files = ["ca.key", "ca.crt", "hub.key", "hub.crt"].map(|name| dir.join(name));
+    if files.iter().all(|path| path.exists()) {
+        return Ok(false);
+    }
+    util::ensure_dir(&dir)?;
+    let [ca_key, ca_crt, hub_key, hub_crt] = files;
+
+    create_ca(&ca_key, &ca_crt, "mvre-hub internal CA")?;
+    let extensions = dir.join("hub.ext");
+    util::write_string(&extensions, &format!("subjectAltName=DNS:{}\n", HUB_HOST))?;
+    issue_cert(&ca_key, &ca_crt, &hub_key, &hub_crt, HUB_HOST, &extensions)?;
+    std::fs::remove_file(extensions).ok();
+    Ok(true)
+}
+
+/// Creates the local CA (kept across redeploys, so browsers that trust it
+/// keep doing so) and a certificate for `domain` signed by it. The server
+/// certificate is reissued when the domain changes. Returns whether anything
+/// was generated.
+pub fn ensure_local_certs(deploy_dir: &Path, domain: &str) -> Result<bool> {
+    let dir = deploy_dir.join(LOCAL_TLS_DIR);
+    let [ca_key, ca_crt, server_key, server_crt] =
+        ["ca.key", "ca.crt", "server.key", "server.crt"].map(|name| dir.join(name));
+    let extensions = dir.join("server.ext");
+    let wanted = format!("subjectAltName={}\n", subject_alt_names(domain));
+
+    let mut generated = false;
+    if !ca_key.exists() || !ca_crt.exists() {
+        util::ensure_dir(&dir)?;
+        util::set_file_mode(&dir, 0o700).ok();
+        create_ca(&ca_key, &ca_crt, "mvre-hub local CA")?;
+        generated = true;
+    }
+    let current = util::read_to_string(&extensions).ok();
+    if generated || !server_crt.exists() || !server_key.exists() || current.as_deref() != Some(wanted.as_str()) {
+        util::write_string(&extensions, &wanted)?;
+        issue_cert(&ca_key, &ca_crt, &server_key, &server_crt, domain, &extensions)?;
+        generated = true;
+    }
+    Ok(generated)
+}
+
+/// SAN list of the local server certificate; IP addresses as such, since
+/// LAN deployments are often reached without DNS.
+pub fn subject_alt_names(domain: &str) -> String {
+    if domain.parse::<std::net::IpAddr>().is_ok() {
+        format!("IP:{},DNS:localhost", domain)
+    } else {
+        format!("DNS:{},DNS:localhost", domain)
+    }
+}
+
+/// Traefik file-provider config serving the local certificate for every router.
+pub fn local_tls_config() -> String {
+    format!(
+        r#"tls:
+  stores:
+    default:
+      defaultCertificate:
+        certFile: {dir}/server.crt
+        keyFile: {dir}/server.key
+"#,
+        dir = LOCAL_TLS_MOUNT
+    )
+}
+
+/// How to make clients trust the local CA, one line per platform.
+pub fn trust_instructions(deploy_dir: &Path) -> Vec<String> {
+    let ca = util::path_display(&deploy_dir.join(LOCAL_TLS_DIR).join("ca.crt"));
+    vec![
+        format!(
+            "Debian/Ubuntu: sudo cp {} /usr/local/share/ca-certificates/mvre-hub.crt && sudo update-ca-certificates",
+            ca
+        ),
+        format!(
+            "RHEL/Fedora:   sudo cp {} /etc/pki/ca-trust/source/anchors/mvre-hub.crt && sudo update-ca-trust",
+            ca
+        ),
+        format!(
+            "macOS:         sudo security add-trusted-cert -d -r trustRoot -k /Library/Keychains/System.keychain {}",
+            ca
+        ),
+        format!("Windows:       certutil -addstore -f ROOT {}", ca),
+        "Firefox:       Settings > Privacy & Security > Certificates > View Certificates > Authorities > Import".to_string(),
+    ]
+}
+
+fn create_ca(key: &Path, crt: &Path, name: &str) -> Result<()> {
+    openssl(&[
+        "req", "-x509", "-newkey", "rsa:4096", "-nodes", "-days", CA_DAYS,
+        "-subj", &format!("/CN={}", name),
+        "-keyout", &path_arg(key),
+        "-out", &path_arg(crt),
+    ])?;
+    util::set_file_mode(key, 0o600).ok();
+    Ok(())
+}
+
+fn issue_cert(ca_key: &Path, ca_crt: &Path, key: &Path, crt: &Path, name: &str, extensions: &Path) -> Result<()> {
+    let csr = key.with_extension("csr");
+    openssl(&[
+        "req", "-newkey", "rsa:2048", "-nodes",
+        "-subj", &format!("/CN={}", name),
+        "-keyout", &path_arg(key),
+        "-out", &path_arg(&csr),
+    ])?;
+    openssl(&[
+        "x509", "-req", "-days", CERT_DAYS, "-CAcreateserial",
+        "-in", &path_arg(&csr),
+        "-CA", &path_arg(ca_crt),
+        "-CAkey", &path_arg(ca_key),
+        "-extfile", &path_arg(extensions),
+        "-out", &path_arg(crt),
+    ])?;
+    std::fs::remove_file(csr).ok();
+    std::fs::remove_file(ca_crt.with_extension("srl")).ok();
+    util::set_file_mode(key, 0o600).ok();
+    util::set_file_mode(crt, 0o644).ok();
+    Ok(())
+}
+
+pub fn ca_cert(deploy_dir: &Path) -> PathBuf {
+    deploy_dir.join(INTERNAL_TLS_DIR).join("ca.crt")
+}
+
+fn openssl(args: &[&str]) -> Result<()> {
+    let output = Command::new("openssl")
+        .args(args)
+        .output()
+        .context("failed to run openssl; it is required for --internal-tls and --tls self-signed")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "openssl {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
@@ -0,0 +1,81 @@
+use anyhow::Result;
+use console::style;
+
+use crate::{
+    cli::{TokenCommand, TokenCreateOptions},
+    config::AppConfig,
+    failure,
+    hubapi::{self, HubClient, TokenRequest},
+    say, services, util,
+};
+
+pub fn run(command: TokenCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let client = HubClient::from_deployment(&deploy_dir)?;
+    match command {
+        TokenCommand::Create(opts) => create(&client, opts),
+        TokenCommand::List { user } => list(&client, &user),
+        TokenCommand::Revoke { user, id } => {
+            client.revoke_token(&user, &id)?;
+            say!("{}", style(format!("Token {} of {} revoked", id, user)).green());
+            util::set_result(serde_json::json!({ "user": user, "revoked": id }));
+            Ok(())
+        }
+    }
+}
+
+fn create(client: &HubClient, opts: TokenCreateOptions) -> Result<()> {
+    let expires_in = match &opts.expires_in {
+        Some(value) => {
+            let seconds = util::parse_duration(value)?;
+            if seconds == 0 {
+                return Err(failure::validation("--expires-in must be greater than zero"));
+            }
+            Some(seconds)
+        }
+        None => None,
+    };
+    let request = TokenRequest {
+        expires_in,
+        note: opts.note,
+        scopes: opts.scopes,
+    };
+    let token = client.request_token(&opts.user, &request)?;
+    say!("{}", style(format!("Token {} issued for {}", token.id, opts.user)).green());
+    say!("Expires: {}", token.expires_at.as_deref().unwrap_or("never"));
+    say!("Token (shown once): {}", style(&token.token).cyan());
+    util::set_result(serde_json::json!({
+        "user": opts.user,
+        "id": token.id,
+        "token": token.token,
+        "expires_at": token.expires_at,
+    }));
+    Ok(())
+}
+
+fn list(client: &HubClient, user: &str) -> Result<()> {
+    let tokens = client.list_tokens(user)?;
+    if tokens.is_empty() {
+        say!("{}", style(format!("{} has no API tokens", user)).dim());
+    } else {
+        say!(
+            "{}",
+            style(format!("  {:<10} {:<20} {:<20} {:<20} {}", "ID", "CREATED", "EXPIRES", "LAST USED", "NOTE")).bold()
+        );
+    }
+    for token in &tokens {
+        say!(
+            "  {:<10} {:<20} {:<20} {:<20} {}",
+            token.id,
+            hubapi::display_time(token.created.as_deref()),
+            match token.expires_at {
+                Some(_) => hubapi::display_time(token.expires_at.as_deref()),
+                None => "never".to_string(),
+            },
+            hubapi::display_time(token.last_activity.as_deref()),
+            token.note.as_deref().unwrap_or("")
+        );
+    }
+    util::set_result(serde_json::json!({ "user": user, "tokens": tokens }));
+    Ok(())
+}
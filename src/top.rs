@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, thread, time::Duration};
+
+use anyhow::Result;
+use console::{style, Term};
+use serde::Serialize;
+
+use crate::{
+    cli::{TopOptions, TopSort},
+    config::AppConfig,
+    engine::{ContainerStats, Engine},
+    envfile,
+    hubapi::HubClient,
+    prune, say, services, util,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    pub container: String,
+    pub service: Option<String>,
+    pub user: Option<String>,
+    pub cpu_percent: f64,
+    pub mem_percent: f64,
+    pub mem_usage: String,
+    pub net_io: String,
+}
+
+pub fn run(opts: TopOptions, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let engine = Engine::new(&deploy_dir);
+    let client = HubClient::from_deployment(&deploy_dir).ok();
+    let live = !opts.once && util::human_output() && Term::stdout().is_term();
+
+    loop {
+        let rows = sample(&engine, client.as_ref(), opts.sort)?;
+        if !live {
+            print_rows(&rows);
+            util::set_result(serde_json::json!({ "containers": rows }));
+            return Ok(());
+        }
+        Term::stdout().clear_screen()?;
+        print_rows(&rows);
+        say!("\n{}", style(format!("Refreshing every {}s, Ctrl-C to quit", opts.interval)).dim());
+        thread::sleep(Duration::from_secs(opts.interval.max(1)));
+    }
+}
+
+/// Joins `docker stats` with the deployment's services and the hub's
+/// user-to-container mapping; unrelated containers on the host are dropped.
+pub fn sample(engine: &Engine, client: Option<&HubClient>, sort: TopSort) -> Result<Vec<UsageRow>> {
+    let services: BTreeMap<String, String> = engine
+        .ps()?
+        .into_iter()
+        .map(|container| (container.name, container.service))
+        .collect();
+    let users = client.map(user_containers).unwrap_or_default();
+    // Container names stand in for the hub's mapping when it is unreachable.
+    let prefix = prune::user_container_prefix(&envfile::load(engine.deploy_dir()).unwrap_or_default());
+
+    let mut rows: Vec<UsageRow> = engine
+        .stats()?
+        .into_iter()
+        .filter_map(|stats| row(stats, &services, &users, &prefix))
+        .collect();
+    rows.sort_by(|a, b| {
+        let (a, b) = match sort {
+            TopSort::Cpu => (a.cpu_percent, b.cpu_percent),
+            TopSort::Memory => (a.mem_percent, b.mem_percent),
+        };
+        b.total_cmp(&a)
+    });
+    Ok(rows)
+}
+
+fn row(
+    stats: ContainerStats,
+    services: &BTreeMap<String, String>,
+    users: &BTreeMap<String, String>,
+    prefix: &str,
+) -> Option<UsageRow> {
+    let service = services.get(&stats.name).cloned();
+    let user = users.get(&stats.name).cloned().or_else(|| {
+        stats
+            .name
+            .strip_prefix(prefix)
+            .filter(|_| users.is_empty())
+            .map(String::from)
+    });
+    if service.is_none() && user.is_none() {
+        return None;
+    }
+    Some(UsageRow {
+        cpu_percent: parse_percent(&stats.cpu_percent),
+        mem_percent: parse_percent(&stats.mem_percent),
+        container: stats.name,
+        service,
+        user,
+        mem_usage: stats.mem_usage,
+        net_io: stats.net_io,
+    })
+}
+
+/// Container name to user name for every running server the hub knows about.
+fn user_containers(client: &HubClient) -> BTreeMap<String, String> {
+    match client.list_users() {
+        Ok(users) => users
+            .into_iter()
+            .flat_map(|user| {
+                let name = user.name;
+                user.servers
+                    .into_values()
+                    .filter_map(|server| server.container_name().map(String::from))
+                    .map(move |container| (container, name.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+        Err(err) => {
+            tracing::warn!("hub API unavailable, guessing users from container names: {:#}", err);
+            BTreeMap::new()
+        }
+    }
+}
+
+fn parse_percent(value: &str) -> f64 {
+    value.trim().trim_end_matches('%').parse().unwrap_or(0.0)
+}
+
+fn print_rows(rows: &[UsageRow]) {
+    say!(
+        "{}",
+        style(format!("  {:<24} {:>7} {:>7}  {:<22} {}", "USER / SERVICE", "CPU %", "MEM %", "MEM", "NET I/O")).bold()
+    );
+    for row in rows {
+        let owner = match (&row.user, &row.service) {
+            (Some(user), _) => user.clone(),
+            (None, Some(service)) => format!("[{}]", service),
+            (None, None) => row.container.clone(),
+        };
+        say!(
+            "  {:<24} {:>7.1} {:>7.1}  {:<22} {}",
+            owner,
+            row.cpu_percent,
+            row.mem_percent,
+            row.mem_usage,
+            row.net_io
+        );
+    }
+}
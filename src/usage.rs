@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{ReportCommand, TopSort, UsageReportOptions},
+    config::AppConfig,
+    engine::Engine,
+    failure,
+    hubapi::HubClient,
+    say, services, top, util,
+};
+
+/// Per-month sample files (`<YYYY-MM>.jsonl`) live here, inside the deployment.
+pub const USAGE_DIR: &str = "usage";
+const LAST_SAMPLE_FILE: &str = ".last-sample";
+
+/// Longest gap credited to a single sample, so a stopped timer does not turn
+/// into hours of phantom runtime.
+const MAX_SAMPLE_GAP: u64 = 3600;
+
+/// One user server observed by `report sample`, credited with the time since
+/// the previous sample.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub timestamp: u64,
+    pub user: String,
+    pub interval: u64,
+    pub cpu_percent: f64,
+    pub mem_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UserUsage {
+    pub user: String,
+    pub runtime_hours: f64,
+    pub cpu_core_hours: f64,
+    pub mem_gb_hours: f64,
+    pub peak_mem_bytes: u64,
+}
+
+pub fn run(command: ReportCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        ReportCommand::Sample => sample(&deploy_dir),
+        ReportCommand::Usage(opts) => report(&deploy_dir, opts),
+    }
+}
+
+/// Records the current resource use of every running user server.
+fn sample(deploy_dir: &Path) -> Result<()> {
+    let engine = Engine::new(deploy_dir);
+    let client = HubClient::from_deployment(deploy_dir).ok();
+    let rows = top::sample(&engine, client.as_ref(), TopSort::Cpu)?;
+
+    let dir = deploy_dir.join(USAGE_DIR);
+    util::ensure_dir(&dir)?;
+    let now = util::unix_now();
+    let last_path = dir.join(LAST_SAMPLE_FILE);
+    let interval = fs::read_to_string(&last_path)
+        .ok()
+        .and_then(|raw| raw.trim().parse::<u64>().ok())
+        .map(|last| now.saturating_sub(last).min(MAX_SAMPLE_GAP))
+        .unwrap_or(0);
+
+    let samples: Vec<Sample> = rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(Sample {
+                timestamp: now,
+                user: row.user?,
+                interval,
+                cpu_percent: row.cpu_percent,
+                mem_bytes: row
+                    .mem_usage
+                    .split('/')
+                    .next()
+                    .and_then(|used| util::parse_size(used).ok())
+                    .unwrap_or(0),
+            })
+        })
+        .collect();
+
+    let path = dir.join(format!("{}.jsonl", month_of(now)));
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    for sample in &samples {
+        let line = serde_json::to_string(sample).context("failed to serialize usage sample")?;
+        writeln!(file, "{}", line).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    util::write_string(&last_path, &now.to_string())?;
+
+    say!("Recorded {} user server(s)", samples.len());
+    util::set_result(serde_json::json!({ "samples": samples }));
+    Ok(())
+}
+
+fn report(deploy_dir: &Path, opts: UsageReportOptions) -> Result<()> {
+    let month = match opts.month {
+        Some(month) => {
+            validate_month(&month)?;
+            month
+        }
+        None => month_of(util::unix_now()),
+    };
+    let path = deploy_dir.join(USAGE_DIR).join(format!("{}.jsonl", month));
+    let samples = load_samples(&path)?;
+    let usage = aggregate(&samples);
+
+    match &opts.output {
+        Some(output) => {
+            let contents = if opts.csv {
+                to_csv(&usage)
+            } else {
+                serde_json::to_string_pretty(&usage).context("failed to serialize usage report")?
+            };
+            util::write_string(output, &contents)?;
+            say!("Usage report for {} written to {}", month, output.display());
+        }
+        None if opts.csv && !util::json_output() => print!("{}", to_csv(&usage)),
+        None => print_table(&month, &usage),
+    }
+    util::set_result(serde_json::json!({ "month": month, "users": usage }));
+    Ok(())
+}
+
+pub fn aggregate(samples: &[Sample]) -> Vec<UserUsage> {
+    let mut users: BTreeMap<&str, UserUsage> = BTreeMap::new();
+    for sample in samples {
+        let entry = users.entry(&sample.user).or_insert_with(|| UserUsage {
+            user: sample.user.clone(),
+            ..UserUsage::default()
+        });
+        let hours = sample.interval as f64 / 3600.0;
+        entry.runtime_hours += hours;
+        entry.cpu_core_hours += sample.cpu_percent / 100.0 * hours;
+        entry.mem_gb_hours += sample.mem_bytes as f64 / 1e9 * hours;
+        entry.peak_mem_bytes = entry.peak_mem_bytes.max(sample.mem_bytes);
+    }
+    users.into_values().collect()
+}
+
+pub fn to_csv(usage: &[UserUsage]) -> String {
+    let mut csv = String::from("user,runtime_hours,cpu_core_hours,mem_gb_hours,peak_mem_bytes\n");
+    for row in usage {
+        csv.push_str(&format!(
+            "{},{:.2},{:.2},{:.2},{}\n",
+            row.user, row.runtime_hours, row.cpu_core_hours, row.mem_gb_hours, row.peak_mem_bytes
+        ));
+    }
+    csv
+}
+
+fn print_table(month: &str, usage: &[UserUsage]) {
+    if usage.is_empty() {
+        say!("{}", style(format!("No usage recorded for {}", month)).dim());
+        return;
+    }
+    say!(
+        "{}",
+        style(format!("  {:<24} {:>10} {:>10} {:>10} {:>9}", "USER", "RUNTIME h", "CPU core-h", "MEM GB-h", "PEAK MEM")).bold()
+    );
+    for row in usage {
+        say!(
+            "  {:<24} {:>10.2} {:>10.2} {:>10.2} {:>9}",
+            row.user,
+            row.runtime_hours,
+            row.cpu_core_hours,
+            row.mem_gb_hours,
+            util::format_size(row.peak_mem_bytes)
+        );
+    }
+}
+
+fn load_samples(path: &Path) -> Result<Vec<Sample>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw = util::read_to_string(path)?;
+    Ok(raw
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+fn month_of(secs: u64) -> String {
+    util::format_timestamp(secs)[..7].to_string()
+}
+
+fn validate_month(month: &str) -> Result<()> {
+    let valid = month.len() == 7
+        && month.as_bytes()[4] == b'-'
+        && month[..4].chars().all(|ch| ch.is_ascii_digit())
+        && matches!(month[5..].parse::<u32>(), Ok(1..=12));
+    if !valid {
+        return Err(failure::validation(format!("Invalid month '{}' (expected YYYY-MM)", month)));
+    }
+    Ok(())
+}
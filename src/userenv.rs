@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{UserEnvCommand, UserEnvTarget},
+    config::AppConfig,
+    answers, failure, report, say, services, users, util,
+};
+
+/// Environment variables of particular users and groups, read by the hub's
+/// pre-spawn hook at every spawn; `/srv/jupyterhub/user-env.toml` inside
+/// the container.
+///
+/// ```toml
+/// [groups.course-a]
+/// MOSAIC_DATA = "/data/mosaic/course-a"
+///
+/// [users.alice]
+/// PROJECT_TOKEN = "..."
+/// ```
+pub const USER_ENV_FILE: &str = "jupyterhub_data/user-env.toml";
+/// Variables the hub sets for the server to talk back to it.
+const RESERVED_PREFIX: &str = "JUPYTERHUB_";
+const MASKED: &str = "***";
+
+/// Variables by group and by user; a user's own variables win over their
+/// groups', and groups apply in name order.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserEnv {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub users: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+pub fn run(command: UserEnvCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let mut table = UserEnv::load(&deploy_dir)?;
+    match command {
+        UserEnvCommand::Set { target, vars } => {
+            validate_target(&target)?;
+            let mut parsed = Vec::new();
+            for var in &vars {
+                let (name, value) = var
+                    .split_once('=')
+                    .ok_or_else(|| failure::validation(format!("'{}' is not NAME=VALUE", var)))?;
+                validate_name(name)?;
+                parsed.push((name.to_string(), value.to_string()));
+            }
+            let (kind, owner, entries) = table.entries_mut(&target);
+            let names: Vec<String> = parsed.iter().map(|(name, _)| name.clone()).collect();
+            entries.extend(parsed);
+            table.save(&deploy_dir)?;
+            say!(
+                "{} {} for {} {}; applied at their next server start",
+                style("Set").green(),
+                names.join(", "),
+                kind,
+                owner
+            );
+            util::set_result(serde_json::json!({ "kind": kind, "name": owner, "set": names }));
+            Ok(())
+        }
+        UserEnvCommand::Unset { target, names } => {
+            validate_target(&target)?;
+            let (kind, owner, entries) = table.entries_mut(&target);
+            let removed: Vec<String> = names.into_iter().filter(|name| entries.remove(name).is_some()).collect();
+            if removed.is_empty() {
+                return Err(failure::validation(format!("{} {} has none of these variables", kind, owner)));
+            }
+            table.prune();
+            table.save(&deploy_dir)?;
+            say!("{} {} for {} {}", style("Removed").green(), removed.join(", "), kind, owner);
+            util::set_result(serde_json::json!({ "kind": kind, "name": owner, "removed": removed }));
+            Ok(())
+        }
+        UserEnvCommand::List => {
+            list(&table);
+            Ok(())
+        }
+    }
+}
+
+fn list(table: &UserEnv) {
+    let masked = table.masked();
+    util::set_result(serde_json::to_value(&masked).unwrap_or_default());
+    if masked.groups.is_empty() && masked.users.is_empty() {
+        say!("{}", style("No per-user or per-group variables; add some with mvre-hub user-env set").dim());
+        return;
+    }
+    for (kind, owners) in [("group", &masked.groups), ("user", &masked.users)] {
+        for (owner, entries) in owners {
+            say!("{}", style(format!("{} {}", kind, owner)).cyan().bold());
+            for (name, value) in entries {
+                say!("  {}={}", name, value);
+            }
+        }
+    }
+}
+
+/// Users as the hub names them; groups as the `[groups]` answers section does.
+pub fn validate_target(target: &UserEnvTarget) -> Result<()> {
+    if let Some(user) = &target.user {
+        users::validate_name(user)?;
+    }
+    if let Some(group) = &target.group {
+        answers::validate_group("group", group)?;
+    }
+    Ok(())
+}
+
+/// Names a notebook server accepts and the hub does not set itself.
+pub fn validate_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+    if !valid {
+        return Err(failure::validation(format!(
+            "'{}' is not a valid variable name (letters, digits, and _)",
+            name
+        )));
+    }
+    if name.starts_with(RESERVED_PREFIX) {
+        return Err(failure::validation(format!("{} is set by the hub and cannot be overridden", name)));
+    }
+    Ok(())
+}
+
+impl UserEnv {
+    /// The table of `deploy_dir`; empty when there is none.
+    pub fn load(deploy_dir: &Path) -> Result<Self> {
+        let path = deploy_dir.join(USER_ENV_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::parse(&util::read_to_string(&path)?)
+            .map_err(|err| failure::config(format!("invalid {}: {:#}", path.display(), err)))
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        let table: Self = toml::from_str(raw)?;
+        for entries in table.groups.values().chain(table.users.values()) {
+            for name in entries.keys() {
+                validate_name(name)?;
+            }
+        }
+        Ok(table)
+    }
+
+    /// Writes the table, readable by its owner only since it may hold tokens.
+    pub fn save(&self, deploy_dir: &Path) -> Result<()> {
+        let path = deploy_dir.join(USER_ENV_FILE);
+        let raw = toml::to_string(self).context("failed to serialize the user environment table")?;
+        util::ensure_dir(path.parent().unwrap_or(deploy_dir))?;
+        util::atomic_write(&path, raw.as_bytes())?;
+        util::set_file_mode(&path, 0o600).ok();
+        Ok(())
+    }
+
+    /// Variables a server of `user`, member of `groups`, starts with; the
+    /// same merge the hub's pre-spawn hook does.
+    pub fn for_user(&self, user: &str, groups: &[String]) -> BTreeMap<String, String> {
+        let mut groups: Vec<&String> = groups.iter().collect();
+        groups.sort();
+        let mut env = BTreeMap::new();
+        for group in groups {
+            if let Some(entries) = self.groups.get(group) {
+                env.extend(entries.clone());
+            }
+        }
+        if let Some(entries) = self.users.get(user) {
+            env.extend(entries.clone());
+        }
+        env
+    }
+
+    /// The table with credential values masked, for display.
+    pub fn masked(&self) -> Self {
+        let mask = |owners: &BTreeMap<String, BTreeMap<String, String>>| {
+            owners
+                .iter()
+                .map(|(owner, entries)| {
+                    let entries = entries
+                        .iter()
+                        .map(|(name, value)| {
+                            let shown = if report::is_secret(name) { MASKED.to_string() } else { value.clone() };
+                            (name.clone(), shown)
+                        })
+                        .collect();
+                    (owner.clone(), entries)
+                })
+                .collect()
+        };
+        Self {
+            groups: mask(&self.groups),
+            users: mask(&self.users),
+        }
+    }
+
+    fn entries_mut(&mut self, target: &UserEnvTarget) -> (&'static str, String, &mut BTreeMap<String, String>) {
+        match (&target.user, &target.group) {
+            (Some(user), _) => ("user", user.clone(), self.users.entry(user.clone()).or_default()),
+            (None, group) => {
+                let group = group.clone().unwrap_or_default();
+                ("group", group.clone(), self.groups.entry(group).or_default())
+            }
+        }
+    }
+
+    /// Drops users and groups left without variables.
+    fn prune(&mut self) {
+        self.groups.retain(|_, entries| !entries.is_empty());
+        self.users.retain(|_, entries| !entries.is_empty());
+    }
+}
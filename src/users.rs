@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use anyhow::Result;
+use console::style;
+
+use crate::{
+    cli::{UsersCommand, UsersOptions},
+    config::AppConfig,
+    deploy, envfile, failure,
+    hubapi::{self, HubClient, User},
+    say, services, util,
+};
+
+pub fn run(command: UsersCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        UsersCommand::Allow(opts) => allow(&deploy_dir, opts),
+        UsersCommand::Disallow(opts) => disallow(&deploy_dir, opts),
+        UsersCommand::List => list(&deploy_dir),
+        UsersCommand::Status => status(&deploy_dir),
+    }
+}
+
+fn allow(deploy_dir: &Path, opts: UsersOptions) -> Result<()> {
+    for name in &opts.names {
+        validate_name(name)?;
+    }
+    let (names, changed) = deploy::update_allowed_users(deploy_dir, |names| {
+        for name in opts.names {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    })?;
+    report(&names, changed);
+    Ok(())
+}
+
+fn disallow(deploy_dir: &Path, opts: UsersOptions) -> Result<()> {
+    let (names, changed) = deploy::update_allowed_users(deploy_dir, |names| {
+        names.retain(|name| !opts.names.contains(name));
+    })?;
+    report(&names, changed);
+    if names.is_empty() && changed {
+        say!(
+            "{}",
+            style("The allow-list is now empty: any account of the identity provider can log in").yellow()
+        );
+    }
+    Ok(())
+}
+
+fn list(deploy_dir: &Path) -> Result<()> {
+    let env = envfile::load(deploy_dir)?;
+    let names = split_list(envfile::get(&env, "ALLOWED_USERS").unwrap_or(""));
+    if names.is_empty() {
+        say!("{}", style("No allow-list: any account of the identity provider can log in").dim());
+    }
+    for name in &names {
+        say!("  {}", name);
+    }
+    util::set_result(serde_json::json!({ "allowed_users": names }));
+    Ok(())
+}
+
+fn status(deploy_dir: &Path) -> Result<()> {
+    let client = HubClient::from_deployment(deploy_dir)?;
+    let users = client.list_users()?;
+    if users.is_empty() {
+        say!("{}", style("No hub accounts yet").dim());
+    } else {
+        say!("{}", style(format!("  {:<24} {:<9} {:<20} {}", "USER", "SERVER", "LAST ACTIVITY", "GROUPS")).bold());
+    }
+    for user in &users {
+        say!(
+            "  {:<24} {:<9} {:<20} {}",
+            if user.admin { format!("{} (admin)", user.name) } else { user.name.clone() },
+            server_state(user),
+            hubapi::display_time(user.last_activity.as_deref()),
+            user.groups.join(",")
+        );
+    }
+    util::set_result(serde_json::json!({ "users": users }));
+    Ok(())
+}
+
+/// State of the user's default server: `running`, `spawning`, `stopping`, or `stopped`.
+pub fn server_state(user: &User) -> &'static str {
+    match user.servers.get("") {
+        Some(server) if server.ready => "running",
+        Some(server) if server.pending.as_deref() == Some("stop") => "stopping",
+        Some(_) => "spawning",
+        None => "stopped",
+    }
+}
+
+fn report(names: &[String], changed: bool) {
+    if changed {
+        say!("{}", style(format!("Allow-list updated ({} users)", names.len())).green());
+        say!("Apply with: {}", style("mvre-hub start").cyan());
+    } else {
+        say!("{}", style("Allow-list unchanged").dim());
+    }
+    util::set_result(serde_json::json!({ "allowed_users": names, "changed": changed }));
+}
+
+/// Reads user names from a file, one per line or comma-separated; blank lines
+/// and `#` comments are ignored.
+pub fn read_list_file(path: &Path) -> Result<Vec<String>> {
+    let raw = util::read_to_string(path)?;
+    let mut names = Vec::new();
+    for line in raw.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        for name in split_list(line) {
+            validate_name(&name)?;
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+pub fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().any(|ch| ch.is_whitespace() || ch == ',' || ch == '=') {
+        return Err(failure::validation(format!("Invalid user name '{}'", name)));
+    }
+    Ok(())
+}
@@ -2,6 +2,10 @@ use std::{
     fs::{self, File},
     io::{self, Write},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
 };
 
 use anyhow::{Context, Result};
@@ -9,12 +13,95 @@ use tracing_subscriber::{fmt, EnvFilter};
 
 pub fn init_logging(verbosity: u8) {
     let filter = match verbosity {
+        _ if !human_output() && verbosity == 0 => EnvFilter::new("warn"),
         0 => EnvFilter::new("info"),
         1 => EnvFilter::new("debug"),
         _ => EnvFilter::new("trace"),
     };
 
-    let _ = fmt().with_env_filter(filter).try_init();
+    // Logs go to stderr so stdout stays clean for results.
+    let _ = fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(console::colors_enabled_stderr())
+        .try_init();
+}
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static JSON: AtomicBool = AtomicBool::new(false);
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static CI: AtomicBool = AtomicBool::new(false);
+static RESULT: Mutex<Option<serde_json::Value>> = Mutex::new(None);
+
+/// Sets how commands report: decorative text, nothing (`quiet`), or a single
+/// JSON document on stdout (`json`). `plain` turns off spinners and streams
+/// tool output unchanged.
+pub fn configure_output(quiet: bool, json: bool, plain: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+    JSON.store(json, Ordering::Relaxed);
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+/// `--ci`: no prompts, colors, or spinners, and a summary document with the
+/// failure category on stdout.
+pub fn configure_ci(ci: bool) {
+    CI.store(ci, Ordering::Relaxed);
+    if ci {
+        PLAIN.store(true, Ordering::Relaxed);
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+}
+
+pub fn ci() -> bool {
+    CI.load(Ordering::Relaxed)
+}
+
+/// Fails instead of waiting for an answer nobody will give under `--ci`.
+pub fn ensure_interactive(prompt: &str, hint: &str) -> Result<()> {
+    if ci() {
+        return Err(crate::failure::validation(format!(
+            "'{}' needs an answer, but --ci disables prompts; {}",
+            prompt, hint
+        )));
+    }
+    Ok(())
+}
+
+/// Whether decorative, human-oriented output should be printed.
+pub fn human_output() -> bool {
+    !QUIET.load(Ordering::Relaxed) && !JSON.load(Ordering::Relaxed)
+}
+
+/// Whether long operations should show a spinner instead of raw tool output.
+pub fn progress_enabled() -> bool {
+    human_output() && !PLAIN.load(Ordering::Relaxed) && console::Term::stderr().is_term()
+}
+
+pub fn json_output() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Records the structured result of the running command, printed by
+/// `lib::run` under `--format json`.
+pub fn set_result(value: serde_json::Value) {
+    if let Ok(mut result) = RESULT.lock() {
+        *result = Some(value);
+    }
+}
+
+pub fn take_result() -> Option<serde_json::Value> {
+    RESULT.lock().ok().and_then(|mut result| result.take())
+}
+
+/// `println!` for human-oriented output; silent under `--quiet` and `--format json`.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::util::human_output() {
+            println!($($arg)*);
+        }
+    };
 }
 
 pub fn atomic_write(path: &Path, bytes: &[u8]) -> Result<()> {
@@ -29,6 +116,7 @@ pub fn atomic_write(path: &Path, bytes: &[u8]) -> Result<()> {
     }
 
     fs::rename(&tmp_path, path).with_context(|| format!("failed to rename temp file to {}", path.display()))?;
+    crate::audit::record_file(path);
     Ok(())
 }
 
@@ -47,13 +135,14 @@ pub fn ensure_dir(path: &Path) -> Result<()> {
 
 pub fn validate_non_empty(name: &str, value: &str) -> Result<()> {
     if value.trim().is_empty() {
-        anyhow::bail!("{} must not be empty", name)
+        return Err(crate::failure::validation(format!("{} must not be empty", name)));
     }
     Ok(())
 }
 
 pub fn prompt_or_use(default: Option<String>, prompt: &str, allow_empty: bool) -> Result<String> {
     use dialoguer::{Input, theme::ColorfulTheme};
+    ensure_interactive(prompt, "pass it as a flag")?;
     let theme = ColorfulTheme::default();
 
     let mut input = Input::with_theme(&theme);
@@ -68,6 +157,32 @@ pub fn prompt_or_use(default: Option<String>, prompt: &str, allow_empty: bool) -
     Ok(value)
 }
 
+/// Per-user configuration directory: `$XDG_CONFIG_HOME`, `~/.config`, or
+/// `%APPDATA%` on Windows.
+pub fn config_home() -> Result<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .context("unable to resolve config directory (XDG_CONFIG_HOME, HOME, or APPDATA)")
+}
+
+/// Host path as written into `.env` for compose. Docker Desktop on Windows
+/// accepts forward slashes, which avoids escaping backslashes and keeps the
+/// drive colon from being mistaken for a volume separator.
+pub fn compose_host_path(path: &str) -> String {
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+/// `value` as one single-quoted POSIX shell word.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 pub fn path_display(path: &Path) -> String {
     path.to_string_lossy().to_string()
 }
@@ -83,6 +198,21 @@ pub fn write_string(path: &Path, contents: &str) -> Result<()> {
     atomic_write(path, contents.as_bytes()).with_context(|| format!("failed to write {}", path.display()))
 }
 
+pub fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    ensure_dir(to)?;
+    for entry in fs::read_dir(from).with_context(|| format!("failed to read dir {}", from.display()))? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("failed to copy {} to {}", entry.path().display(), target.display()))?;
+        }
+    }
+    Ok(())
+}
+
 pub fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
     #[cfg(unix)]
     {
@@ -105,6 +235,13 @@ pub fn make_executable(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Whether `program` is an executable file in a `PATH` directory.
+pub fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
 pub fn is_root() -> bool {
     #[cfg(unix)]
     {
@@ -126,3 +263,179 @@ pub fn maybe_symlink(from: &Path, to: &Path) -> io::Result<()> {
         std::os::windows::fs::symlink_file(from, to)
     }
 }
+
+pub fn random_token(bytes: usize) -> Result<String> {
+    let mut buf = vec![0u8; bytes];
+    getrandom::getrandom(&mut buf).map_err(|err| anyhow::anyhow!("failed to read random bytes: {}", err))?;
+    Ok(buf.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+pub fn unix_now() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats unix seconds as an RFC 3339 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Parses timestamps written by [`format_timestamp`], also without the `-`
+/// and `:` separators (as used in file names).
+pub fn parse_timestamp(value: &str) -> Option<u64> {
+    let compact: String = value.chars().filter(|ch| *ch != '-' && *ch != ':').collect();
+    let (date, time) = compact.strip_suffix('Z')?.split_once('T')?;
+    if date.len() != 8 || time.len() != 6 || !date.chars().chain(time.chars()).all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let number = |text: &str| text.parse::<u32>().ok();
+    let (year, month, day) = (date[..4].parse::<i64>().ok()?, number(&date[4..6])?, number(&date[6..])?);
+    let (hour, minute, second) = (number(&time[..2])?, number(&time[2..4])?, number(&time[4..])?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    u64::try_from(days * 86_400 + i64::from(hour * 3600 + minute * 60 + second)).ok()
+}
+
+/// Seconds of an RFC 3339 time with fractional seconds and a UTC offset,
+/// as restic and the hub API print them.
+pub fn parse_rfc3339(value: &str) -> Option<u64> {
+    let base = parse_timestamp(&format!("{}Z", value.get(..19)?))?;
+    let rest = value[19..].trim_start_matches(|ch: char| ch == '.' || ch.is_ascii_digit());
+    if rest == "Z" {
+        return Some(base);
+    }
+    let sign = match rest.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = rest[1..].split_once(':')?;
+    let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+    u64::try_from(base as i64 - sign * offset).ok()
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Parses durations such as `90s`, `30m`, `2h`, `7d`, `2w`, or `1y` into seconds.
+pub fn parse_duration(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let amount: u64 = digits
+        .parse()
+        .map_err(|_| crate::failure::validation(format!("invalid duration '{}'", value)))?;
+    let factor = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        "y" => 365 * 86_400,
+        _ => {
+            return Err(crate::failure::validation(format!(
+                "invalid duration unit in '{}' (use s, m, h, d, w, or y)",
+                value
+            )))
+        }
+    };
+    amount
+        .checked_mul(factor)
+        .ok_or_else(|| crate::failure::validation(format!("duration '{}' is too long", value)))
+}
+
+/// Parses docker's human-readable sizes into bytes: decimal units (`512kB`,
+/// `1.2GB`) as in `docker system df`, binary units (`1.5GiB`) as in `docker stats`.
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let amount: f64 = number
+        .parse()
+        .with_context(|| format!("invalid size '{}'", value))?;
+    let factor: f64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "gb" => 1e9,
+        "tb" => 1e12,
+        "pb" => 1e15,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(crate::failure::validation(format!("invalid size unit in '{}'", value))),
+    };
+    Ok((amount * factor).round() as u64)
+}
+
+/// Formats bytes with decimal units, matching docker's output.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "kB", "MB", "GB", "TB", "PB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit + 1 < UNITS.len() {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+/// Total size of the files under `path`, without following symlinks.
+/// Unreadable entries are skipped.
+pub fn dir_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
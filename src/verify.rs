@@ -0,0 +1,92 @@
+use anyhow::Result;
+use console::style;
+
+use crate::{
+    config::AppConfig,
+    health::{Check, Outcome},
+    hubapi::{HubClient, ProxyRoute, User},
+    say, services, util,
+};
+
+/// Checks the hub end to end through its API: the service token is
+/// accepted, and every ready server has a route in the proxy.
+pub fn run(app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let client = HubClient::from_deployment(&deploy_dir)?;
+    let checks = collect(&client);
+
+    say!("{}", style("Verify").cyan().bold());
+    for check in &checks {
+        let (marker, detail) = match &check.outcome {
+            Outcome::Pass(detail) => (style("ok").green(), detail),
+            Outcome::Fail(detail) => (style("FAIL").red().bold(), detail),
+            Outcome::Skip(detail) => (style("skip").dim(), detail),
+        };
+        say!("  {:<16} {:<5} {}", check.name, marker, detail);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| matches!(check.outcome, Outcome::Fail(_)))
+        .count();
+    util::set_result(serde_json::json!({ "checks": checks }));
+    if failed > 0 {
+        anyhow::bail!("{} verification check(s) failed", failed);
+    }
+    Ok(())
+}
+
+pub fn collect(client: &HubClient) -> Vec<Check> {
+    let api = match client.version() {
+        Ok(info) => Outcome::Pass(format!("JupyterHub {} at {}", info.version, client.base_url())),
+        Err(err) => Outcome::Fail(format!("{:#}", err)),
+    };
+    let reachable = matches!(api, Outcome::Pass(_));
+    let skipped = || Outcome::Skip("hub API unreachable".to_string());
+    let token = if reachable {
+        match client.whoami() {
+            Ok(identity) => Outcome::Pass(format!("{} {}", identity.kind, identity.name)),
+            Err(err) => Outcome::Fail(format!("{:#}", err)),
+        }
+    } else {
+        skipped()
+    };
+    let routes = if reachable { check_routes(client) } else { skipped() };
+    vec![
+        Check { name: "hub API", outcome: api },
+        Check { name: "API token", outcome: token },
+        Check { name: "proxy routes", outcome: routes },
+    ]
+}
+
+fn check_routes(client: &HubClient) -> Outcome {
+    let (users, routes) = match client.list_users().and_then(|users| Ok((users, client.proxy_routes()?))) {
+        Ok(found) => found,
+        Err(err) => return Outcome::Fail(format!("{:#}", err)),
+    };
+    let missing = missing_routes(&users, &routes);
+    if missing.is_empty() {
+        Outcome::Pass(format!("{} running servers routed", running_servers(&users).len()))
+    } else {
+        Outcome::Fail(format!("no route for {}", missing.join(", ")))
+    }
+}
+
+/// URL paths of the servers the hub reports as ready.
+pub fn running_servers(users: &[User]) -> Vec<String> {
+    users
+        .iter()
+        .flat_map(|user| user.servers.values())
+        .filter(|server| server.ready)
+        .map(|server| server.url.clone())
+        .collect()
+}
+
+/// Ready servers without a proxy route. Route specs carry the host when
+/// the hub routes by host, so they are matched by their path suffix.
+pub fn missing_routes(users: &[User], routes: &[ProxyRoute]) -> Vec<String> {
+    running_servers(users)
+        .into_iter()
+        .filter(|url| !routes.iter().any(|route| route.routespec.ends_with(url.as_str())))
+        .collect()
+}
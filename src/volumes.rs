@@ -0,0 +1,317 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+
+use crate::{
+    backup::Destination,
+    cli::{BackupTarget, VolumesCommand, VolumesPruneOptions, WorkspaceBackend},
+    config::AppConfig,
+    engine::Engine,
+    envfile, failure,
+    hubapi::{HubClient, User},
+    prune,
+    repository::Repository,
+    say, services, util,
+    workspace::{self, workspace_name},
+};
+
+/// A user's workspace volume and what is known about its last use. Times are
+/// Unix seconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserVolume {
+    pub volume: String,
+    /// Hub user the volume belongs to; `None` for volumes of deleted users.
+    pub user: Option<String>,
+    /// As docker reports it, e.g. `1.2GB`.
+    pub size: Option<String>,
+    pub created_at: Option<u64>,
+    /// Latest start of a container with the volume mounted. DockerSpawner
+    /// removes stopped containers, so this is often unknown.
+    pub last_started: Option<u64>,
+    pub last_activity: Option<u64>,
+    pub running: bool,
+}
+
+impl UserVolume {
+    /// The later of the last activity and the last start; the volume's
+    /// creation when neither is known.
+    pub fn last_used(&self) -> Option<u64> {
+        self.last_activity.max(self.last_started).or(self.created_at)
+    }
+}
+
+pub fn run(command: VolumesCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        VolumesCommand::Report => report(&deploy_dir),
+        VolumesCommand::Prune(opts) => prune_stale(&deploy_dir, opts),
+    }
+}
+
+/// Pairs every volume in `volumes` named with the deployment's `prefix` with
+/// its hub user, taking the last activity and running servers from `users`.
+pub fn match_users(volumes: &[String], users: &[User], prefix: &str) -> Vec<UserVolume> {
+    let by_volume: BTreeMap<String, &User> = users
+        .iter()
+        .map(|user| (format!("{}{}", prefix, workspace_name(&user.name)), user))
+        .collect();
+    volumes
+        .iter()
+        .filter(|volume| volume.starts_with(prefix))
+        .map(|volume| {
+            let user = by_volume.get(volume);
+            UserVolume {
+                volume: volume.clone(),
+                user: user.map(|user| user.name.clone()),
+                last_activity: user
+                    .and_then(|user| user.last_activity.as_deref())
+                    .and_then(util::parse_rfc3339),
+                running: user.is_some_and(|user| !user.servers.is_empty()),
+                ..UserVolume::default()
+            }
+        })
+        .collect()
+}
+
+/// Volumes of hub users last used more than `older_than` seconds before
+/// `now`. Volumes with a running server, of deleted users, or without any
+/// known time are left alone.
+pub fn stale(volumes: &[UserVolume], older_than: u64, now: u64) -> Vec<&UserVolume> {
+    volumes
+        .iter()
+        .filter(|volume| volume.user.is_some() && !volume.running)
+        .filter(|volume| volume.last_used().is_some_and(|used| used.saturating_add(older_than) < now))
+        .collect()
+}
+
+/// Every user volume of the engine with its size, creation, last container
+/// start, and, when `users` is given, its owner and last hub activity.
+fn gather(engine: &Engine, users: &[User]) -> Result<Vec<UserVolume>> {
+    let names = engine.volume_names()?;
+    let prefix = prune::user_volume_prefix(&envfile::load(engine.deploy_dir())?);
+    let mut volumes = match_users(&names, users, &prefix);
+    if volumes.is_empty() {
+        return Ok(volumes);
+    }
+    let sizes: BTreeMap<String, String> = match engine.disk_usage() {
+        Ok(usage) => usage.volumes.into_iter().map(|volume| (volume.name, volume.size)).collect(),
+        Err(err) => {
+            tracing::warn!("failed to read volume sizes: {:#}", err);
+            BTreeMap::new()
+        }
+    };
+    let mut args = vec!["volume", "inspect", "--format", "{{.Name}} {{.CreatedAt}}"];
+    args.extend(volumes.iter().map(|volume| volume.volume.as_str()));
+    let created: BTreeMap<String, u64> = engine
+        .docker_output(&args)
+        .context("failed to inspect the user volumes")?
+        .lines()
+        .filter_map(|line| {
+            let (name, created) = line.trim().split_once(' ')?;
+            Some((name.to_string(), parse_docker_time(created)?))
+        })
+        .collect();
+    for volume in &mut volumes {
+        volume.size = sizes.get(&volume.volume).cloned();
+        volume.created_at = created.get(&volume.volume).copied();
+        volume.last_started = last_started(engine, &volume.volume);
+    }
+    Ok(volumes)
+}
+
+/// Latest start of the containers, running or stopped, that mount `volume`.
+fn last_started(engine: &Engine, volume: &str) -> Option<u64> {
+    let filter = format!("volume={}", volume);
+    let ids = engine.docker_output(&["ps", "-aq", "--filter", &filter]).ok()?;
+    let ids: Vec<&str> = ids.split_whitespace().collect();
+    if ids.is_empty() {
+        return None;
+    }
+    let mut args = vec!["inspect", "--format", "{{.State.StartedAt}}"];
+    args.extend(ids);
+    let raw = engine.docker_output(&args).ok()?;
+    raw.lines().filter_map(parse_docker_time).max()
+}
+
+/// Docker's RFC 3339 times; the zero time of never-started containers is
+/// `None`.
+fn parse_docker_time(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if value.starts_with("0001-") {
+        return None;
+    }
+    util::parse_rfc3339(value)
+}
+
+fn report(deploy_dir: &Path) -> Result<()> {
+    let engine = Engine::new(deploy_dir);
+    let users = match HubClient::from_deployment(deploy_dir).and_then(|client| client.list_users()) {
+        Ok(users) => users,
+        Err(err) => {
+            tracing::warn!("the hub is not reachable, so owners and activity are unknown: {:#}", err);
+            Vec::new()
+        }
+    };
+    let volumes = gather(&engine, &users)?;
+    let time = |secs: Option<u64>| secs.map(util::format_timestamp);
+    util::set_result(serde_json::json!({
+        "volumes": volumes
+            .iter()
+            .map(|volume| serde_json::json!({
+                "volume": volume.volume,
+                "user": volume.user,
+                "size": volume.size,
+                "created_at": time(volume.created_at),
+                "last_started": time(volume.last_started),
+                "last_activity": time(volume.last_activity),
+                "last_used": time(volume.last_used()),
+                "running": volume.running,
+            }))
+            .collect::<Vec<_>>(),
+    }));
+    if volumes.is_empty() {
+        say!("{}", style("No user volumes").dim());
+        return Ok(());
+    }
+    let shown = |secs: Option<u64>| secs.map(util::format_timestamp).unwrap_or_else(|| "-".to_string());
+    let header = format!(
+        "  {:<32} {:<20} {:>9}  {:<20}  {:<20}",
+        "VOLUME", "USER", "SIZE", "LAST START", "LAST ACTIVITY"
+    );
+    say!("{}", style(header).bold());
+    for volume in &volumes {
+        let user = match (&volume.user, volume.running) {
+            (Some(user), true) => format!("{} (running)", user),
+            (Some(user), false) => user.clone(),
+            (None, _) => "-".to_string(),
+        };
+        say!(
+            "  {:<32} {:<20} {:>9}  {:<20}  {:<20}",
+            volume.volume,
+            user,
+            volume.size.as_deref().unwrap_or("-"),
+            shown(volume.last_started),
+            shown(volume.last_activity)
+        );
+    }
+    if volumes.iter().any(|volume| volume.user.is_none()) && !users.is_empty() {
+        say!(
+            "{}",
+            style("Volumes without a user belong to deleted accounts; remove them with mvre-hub prune volumes").dim()
+        );
+    }
+    Ok(())
+}
+
+/// Removes the volumes of users who have not used them for `--older-than`,
+/// exporting each first with `--archive-first`. A volume is only removed
+/// once its archive is stored.
+fn prune_stale(deploy_dir: &Path, opts: VolumesPruneOptions) -> Result<()> {
+    let older_than = util::parse_duration(&opts.older_than).context("invalid --older-than")?;
+    if older_than == 0 {
+        return Err(failure::validation("--older-than must be greater than zero"));
+    }
+    let env = envfile::load(deploy_dir)?;
+    if workspace::backend(&env) != WorkspaceBackend::Volume {
+        return Err(failure::validation(
+            "volumes prune removes Docker volumes; this deployment keeps workspaces on ZFS or Btrfs",
+        ));
+    }
+    let target = opts.dest.clone().filter(|_| opts.archive_first).map(|dest| BackupTarget {
+        dest,
+        password_file: None,
+        sse: opts.sse,
+        sse_kms_key_id: opts.sse_kms_key_id.clone(),
+        endpoint_url: opts.endpoint_url.clone(),
+    });
+    let destination = match &target {
+        Some(target) if Repository::parse(&target.dest).is_some() => {
+            return Err(failure::validation("volume archives go to a directory or an s3:// destination"));
+        }
+        Some(target) => Some(Destination::parse(&target.dest)?),
+        None => None,
+    };
+
+    let engine = Engine::new(deploy_dir);
+    // Running servers and recent activity only show in the hub; without it
+    // every volume would look unused.
+    let users = HubClient::from_deployment(deploy_dir)?.list_users()?;
+    let now = util::unix_now();
+    let volumes = gather(&engine, &users)?;
+    let candidates = stale(&volumes, older_than, now);
+    if candidates.is_empty() {
+        say!("{}", style(format!("No user volumes unused for {}", opts.older_than)).dim());
+        util::set_result(serde_json::json!({ "dry_run": opts.dry_run, "removed": [], "failed": [] }));
+        return Ok(());
+    }
+    for volume in &candidates {
+        say!(
+            "  {:<24} last used {}  {:>9}  {}",
+            volume.user.as_deref().unwrap_or_default(),
+            volume.last_used().map(util::format_timestamp).unwrap_or_default(),
+            volume.size.as_deref().unwrap_or("-"),
+            style(&volume.volume).dim()
+        );
+    }
+    if opts.dry_run {
+        let names: Vec<&str> = candidates.iter().map(|volume| volume.volume.as_str()).collect();
+        util::set_result(serde_json::json!({ "dry_run": true, "candidates": names }));
+        return Ok(());
+    }
+    if !opts.yes {
+        let prompt = match &target {
+            Some(target) => format!("Archive {} volumes to {} and remove them?", candidates.len(), target.dest),
+            None => format!("Remove {} volumes and the work in them?", candidates.len()),
+        };
+        util::ensure_interactive(&prompt, "pass --yes")?;
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            return Err(failure::validation("Aborted"));
+        }
+    }
+
+    let staging = deploy_dir.join(workspace::ARCHIVE_STAGING_DIR);
+    if destination.is_some() {
+        util::ensure_dir(&staging)?;
+    }
+    let mut removed = Vec::new();
+    let mut failed = Vec::new();
+    for volume in &candidates {
+        let user = volume.user.as_deref().unwrap_or_default();
+        let archived = match (&destination, &target) {
+            (Some(destination), Some(target)) => {
+                workspace::archive_volume(&engine, destination, target, &staging, &volume.volume, user, now).map(Some)
+            }
+            _ => Ok(None),
+        };
+        let result = archived.and_then(|location| {
+            engine.docker_output(&["volume", "rm", &volume.volume]).map(|_| location)
+        });
+        match result {
+            Ok(location) => {
+                match &location {
+                    Some(location) => say!("{} {} to {}", style("Archived").green(), volume.volume, location),
+                    None => say!("{} {}", style("Removed").green(), volume.volume),
+                }
+                removed.push(serde_json::json!({ "user": user, "volume": volume.volume, "archive": location }));
+            }
+            Err(err) => {
+                say!("{} {}: {:#}", style("Failed").yellow(), volume.volume, err);
+                failed.push(serde_json::json!({ "volume": volume.volume, "error": format!("{:#}", err) }));
+            }
+        }
+    }
+    if destination.is_some() {
+        std::fs::remove_dir(&staging).ok();
+    }
+    util::set_result(serde_json::json!({ "dry_run": false, "removed": removed, "failed": failed }));
+    if !failed.is_empty() {
+        anyhow::bail!("{} of {} volumes were not removed", failed.len(), candidates.len());
+    }
+    Ok(())
+}
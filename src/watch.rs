@@ -0,0 +1,237 @@
+use std::{collections::BTreeMap, path::Path, thread, time::Duration};
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{
+    audit,
+    cli::{HealthOptions, WatchOptions},
+    config::AppConfig,
+    engine::{ContainerState, Engine},
+    envfile, failure,
+    health::{self, Outcome},
+    http, say, schedule, services, systemd, util,
+};
+
+const WATCH_SERVICE: &str = "mvre-hub-watch";
+const HUB_SERVICE: &str = "jupyterhub";
+
+/// Restart delays of one service: doubling from the check interval up to
+/// the maximum, and forgotten once the service stayed up that long.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Backoff {
+    pub attempts: u32,
+    pub last_restart: u64,
+    pub next_allowed: u64,
+}
+
+impl Backoff {
+    pub fn ready(&self, now: u64) -> bool {
+        now >= self.next_allowed
+    }
+
+    pub fn restarted(&mut self, now: u64, base: u64, max: u64) {
+        let delay = base.saturating_mul(1u64 << self.attempts.min(20)).min(max);
+        self.attempts += 1;
+        self.last_restart = now;
+        self.next_allowed = now + delay;
+    }
+
+    /// Called while the service is up; resets after `stable_for` seconds.
+    pub fn settle(&mut self, now: u64, stable_for: u64) {
+        if self.attempts > 0 && now >= self.last_restart + stable_for {
+            *self = Backoff::default();
+        }
+    }
+}
+
+/// Why a container needs a restart, if it does. A clean exit is left alone:
+/// one-shot services such as the user image builder end that way.
+pub fn crash_reason(container: &ContainerState) -> Option<String> {
+    match container.state.as_str() {
+        "dead" => Some("container is dead".to_string()),
+        "exited" if container.exit_code != 0 => Some(format!("exited with code {}", container.exit_code)),
+        "running" if container.health == "unhealthy" => Some("health check failing".to_string()),
+        _ => None,
+    }
+}
+
+pub fn run(opts: WatchOptions, app_config: &AppConfig) -> Result<()> {
+    if opts.uninstall {
+        if systemd::remove_daemon(WATCH_SERVICE)? {
+            say!("{}", style(format!("Removed {}.service", WATCH_SERVICE)).green());
+        } else {
+            say!("{}", style("The watch service is not installed").dim());
+        }
+        return Ok(());
+    }
+
+    let interval = util::parse_duration(&opts.interval).context("invalid --interval")?;
+    let max_backoff = util::parse_duration(&opts.max_backoff).context("invalid --max-backoff")?;
+    if interval == 0 {
+        return Err(failure::validation("--interval must be greater than zero"));
+    }
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+
+    if opts.install {
+        return install(&opts);
+    }
+
+    let mut watcher = Watcher {
+        engine: Engine::new(&deploy_dir),
+        deploy_dir: &deploy_dir,
+        opts: &opts,
+        interval,
+        max_backoff,
+        backoff: BTreeMap::new(),
+        hub_failures: 0,
+    };
+    if !opts.once {
+        say!(
+            "{}",
+            style(format!("Watching {} every {}", deploy_dir.display(), opts.interval)).cyan()
+        );
+    }
+    loop {
+        if let Err(err) = watcher.check() {
+            tracing::warn!("watch round failed: {:#}", err);
+            if opts.once {
+                return Err(err);
+            }
+        }
+        if opts.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+fn install(opts: &WatchOptions) -> Result<()> {
+    let scope = schedule::scope_for(opts.user)?;
+    let exe = std::env::current_exe().context("failed to resolve mvre-hub binary path")?;
+    let mut command = format!(
+        "{} watch --interval {} --max-backoff {} --hub-failures {}",
+        exe.display(),
+        schedule::exec_arg(&opts.interval),
+        schedule::exec_arg(&opts.max_backoff),
+        opts.hub_failures
+    );
+    if let Some(url) = &opts.webhook {
+        command.push_str(&format!(" --webhook {}", schedule::exec_arg(url)));
+    }
+    systemd::install_daemon(scope, WATCH_SERVICE, "MVRE-Hub watchdog", &command)?;
+    say!("{}", style(format!("Installed and started {}.service", WATCH_SERVICE)).green());
+    util::set_result(serde_json::json!({ "service": WATCH_SERVICE, "command": command }));
+    Ok(())
+}
+
+struct Watcher<'a> {
+    engine: Engine,
+    deploy_dir: &'a Path,
+    opts: &'a WatchOptions,
+    interval: u64,
+    max_backoff: u64,
+    backoff: BTreeMap<String, Backoff>,
+    hub_failures: u32,
+}
+
+impl Watcher<'_> {
+    fn check(&mut self) -> Result<()> {
+        let now = util::unix_now();
+        let containers = self.engine.ps()?;
+        let mut hub_running = false;
+        for container in &containers {
+            let service = if container.service.is_empty() {
+                &container.name
+            } else {
+                &container.service
+            };
+            match crash_reason(container) {
+                Some(reason) => self.recover(service, &reason, now),
+                None if container.state == "running" => {
+                    hub_running |= service == HUB_SERVICE;
+                    if let Some(backoff) = self.backoff.get_mut(service) {
+                        backoff.settle(now, self.max_backoff);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // A running hub container can still be wedged; restart it after
+        // several failed probes in a row.
+        if hub_running {
+            let env = envfile::load(self.deploy_dir)?;
+            let probe = HealthOptions {
+                insecure: true,
+                max_restarts: 0,
+            };
+            match health::probe_hub(self.deploy_dir, &env, &probe) {
+                Outcome::Fail(detail) => {
+                    self.hub_failures += 1;
+                    if self.hub_failures >= self.opts.hub_failures {
+                        self.hub_failures = 0;
+                        self.recover(HUB_SERVICE, &format!("hub not answering: {}", detail), now);
+                    }
+                }
+                _ => self.hub_failures = 0,
+            }
+        }
+        Ok(())
+    }
+
+    fn recover(&mut self, service: &str, reason: &str, now: u64) {
+        let backoff = self.backoff.entry(service.to_string()).or_default();
+        if !backoff.ready(now) {
+            tracing::debug!("{} is down ({}); next restart in {}s", service, reason, backoff.next_allowed - now);
+            return;
+        }
+        backoff.restarted(now, self.interval, self.max_backoff);
+        let attempt = backoff.attempts;
+
+        let result = self
+            .engine
+            .compose_output(&["up", "-d", "--no-deps", service])
+            .map(|_| ());
+        let error = result.as_ref().err().map(|err| format!("{:#}", err));
+        match &error {
+            None => say!(
+                "{} {} {} ({}, attempt {})",
+                style(util::format_timestamp(now)).dim(),
+                style(service).bold(),
+                style("restarted").yellow(),
+                reason,
+                attempt
+            ),
+            Some(error) => say!(
+                "{} {} {} ({}): {}",
+                style(util::format_timestamp(now)).dim(),
+                style(service).bold(),
+                style("restart failed").red(),
+                reason,
+                error
+            ),
+        }
+
+        if let Err(err) = audit::record_event("watch", &["restart", service, reason], error.clone(), self.deploy_dir) {
+            tracing::warn!("failed to record incident: {:#}", err);
+        }
+        if let Some(url) = &self.opts.webhook {
+            let payload = serde_json::json!({
+                "event": if error.is_none() { "service_restarted" } else { "service_restart_failed" },
+                "service": service,
+                "reason": reason,
+                "attempt": attempt,
+                "error": error,
+                "deploy_dir": util::path_display(self.deploy_dir),
+                "timestamp": util::format_timestamp(now),
+            });
+            let sent = http::request("POST", url, Some(&payload.to_string()), &http::Options::default());
+            match sent {
+                Ok(response) if response.is_success() => {}
+                Ok(response) => tracing::warn!("webhook answered {}", response.status),
+                Err(err) => tracing::warn!("webhook failed: {:#}", err),
+            }
+        }
+    }
+}
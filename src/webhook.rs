@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use anyhow::Result;
+use console::style;
+
+use crate::{
+    cli::{LifecycleEvent, WebhookAddOptions, WebhooksCommand},
+    config::{self, AppConfig, Webhook},
+    envfile, failure, http, say, util,
+};
+
+pub fn run(command: WebhooksCommand, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    match command {
+        WebhooksCommand::Add(opts) => add(opts, config_path, app_config),
+        WebhooksCommand::List => {
+            list(app_config);
+            Ok(())
+        }
+        WebhooksCommand::Remove { url } => {
+            let before = app_config.webhooks.len();
+            app_config.webhooks.retain(|webhook| webhook.url != url);
+            if app_config.webhooks.len() == before {
+                return Err(failure::validation(format!("no webhook {}; see mvre-hub webhooks list", url)));
+            }
+            config::save(config_path, app_config)?;
+            say!("{}", style(format!("Removed webhook {}", url)).green());
+            util::set_result(serde_json::json!({ "removed": url }));
+            Ok(())
+        }
+        WebhooksCommand::Test { url } => test(url.as_deref(), app_config),
+    }
+}
+
+fn add(opts: WebhookAddOptions, config_path: &Path, app_config: &mut AppConfig) -> Result<()> {
+    validate_url(&opts.url)?;
+    let mut events: Vec<LifecycleEvent> = opts.events;
+    events.sort();
+    events.dedup();
+    let webhook = Webhook {
+        url: opts.url,
+        events: events.iter().map(|event| event.name().to_string()).collect(),
+        failures_only: opts.failures_only,
+    };
+    let replaced = match app_config.webhooks.iter_mut().find(|existing| existing.url == webhook.url) {
+        Some(existing) => {
+            *existing = webhook.clone();
+            true
+        }
+        None => {
+            app_config.webhooks.push(webhook.clone());
+            false
+        }
+    };
+    config::save(config_path, app_config)?;
+    say!(
+        "{} {} ({})",
+        style(if replaced { "Updated webhook" } else { "Added webhook" }).green(),
+        webhook.url,
+        describe(&webhook)
+    );
+    util::set_result(serde_json::json!({ "url": webhook.url, "events": webhook.events, "failures_only": webhook.failures_only }));
+    Ok(())
+}
+
+fn list(app_config: &AppConfig) {
+    util::set_result(serde_json::json!({ "webhooks": app_config.webhooks }));
+    if app_config.webhooks.is_empty() {
+        say!("{}", style("No webhooks; add one with mvre-hub webhooks add <url>").dim());
+        return;
+    }
+    for webhook in &app_config.webhooks {
+        say!("  {:<48} {}", webhook.url, style(describe(webhook)).dim());
+    }
+}
+
+fn test(url: Option<&str>, app_config: &AppConfig) -> Result<()> {
+    let targets: Vec<&Webhook> = app_config
+        .webhooks
+        .iter()
+        .filter(|webhook| url.is_none_or(|url| webhook.url == url))
+        .collect();
+    if targets.is_empty() {
+        return Err(failure::validation(match url {
+            Some(url) => format!("no webhook {}; see mvre-hub webhooks list", url),
+            None => "no webhooks configured; add one with mvre-hub webhooks add <url>".to_string(),
+        }));
+    }
+    let now = util::unix_now();
+    let payload = serde_json::json!({
+        "event": "test",
+        "host": host(),
+        "user": whoami::username(),
+        "timestamp": util::format_timestamp(now),
+    });
+    let mut failed = Vec::new();
+    for webhook in targets {
+        match send(&webhook.url, &payload) {
+            Ok(()) => say!("  {} {}", style("ok").green(), webhook.url),
+            Err(err) => {
+                say!("  {} {}: {:#}", style("failed").red(), webhook.url, err);
+                failed.push(webhook.url.clone());
+            }
+        }
+    }
+    util::set_result(serde_json::json!({ "failed": failed }));
+    if !failed.is_empty() {
+        return Err(failure::network(format!("webhook test failed for {}", failed.join(", "))));
+    }
+    Ok(())
+}
+
+/// Tells the webhooks interested in `event` that it finished, successfully
+/// when `error` is `None`. A webhook that cannot be reached is logged and
+/// does not change the outcome of the command.
+pub fn notify(app_config: &AppConfig, event: LifecycleEvent, error: Option<&str>, deploy_dir: Option<&Path>, started: u64) {
+    let targets: Vec<&Webhook> = app_config
+        .webhooks
+        .iter()
+        .filter(|webhook| wants(webhook, event, error.is_none()))
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+    let deployment = deploy_dir.and_then(|dir| {
+        let absolute = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        app_config.deployment_name(&absolute).map(str::to_string)
+    });
+    let domain = deploy_dir
+        .and_then(|dir| envfile::load(dir).ok())
+        .and_then(|env| envfile::get(&env, "HUB_DOMAIN").map(str::to_string));
+    let payload = payload(event, error, deploy_dir, deployment.as_deref(), domain.as_deref(), started, util::unix_now());
+    for webhook in targets {
+        if let Err(err) = send(&webhook.url, &payload) {
+            tracing::warn!("webhook {} failed: {:#}", webhook.url, err);
+        }
+    }
+}
+
+/// Whether `webhook` reports `event` with this outcome.
+pub fn wants(webhook: &Webhook, event: LifecycleEvent, succeeded: bool) -> bool {
+    let listed = webhook.events.is_empty() || webhook.events.iter().any(|name| name == event.name());
+    listed && !(succeeded && webhook.failures_only)
+}
+
+/// JSON document posted for a finished operation: `deploy_succeeded`,
+/// `backup_failed`, and so on.
+pub fn payload(
+    event: LifecycleEvent,
+    error: Option<&str>,
+    deploy_dir: Option<&Path>,
+    deployment: Option<&str>,
+    domain: Option<&str>,
+    started: u64,
+    finished: u64,
+) -> serde_json::Value {
+    let outcome = if error.is_none() { "succeeded" } else { "failed" };
+    serde_json::json!({
+        "event": format!("{}_{}", event.name(), outcome),
+        "operation": event.name(),
+        "ok": error.is_none(),
+        "error": error,
+        "deployment": deployment,
+        "deploy_dir": deploy_dir.map(util::path_display),
+        "domain": domain,
+        "host": host(),
+        "user": whoami::username(),
+        "started_at": util::format_timestamp(started),
+        "finished_at": util::format_timestamp(finished),
+        "duration_secs": finished.saturating_sub(started),
+    })
+}
+
+pub fn validate_url(url: &str) -> Result<()> {
+    let valid = ["http://", "https://"]
+        .iter()
+        .any(|scheme| url.strip_prefix(scheme).is_some_and(|rest| !rest.is_empty()));
+    if !valid {
+        return Err(failure::validation(format!("webhook '{}' must be an http:// or https:// URL", url)));
+    }
+    Ok(())
+}
+
+fn send(url: &str, payload: &serde_json::Value) -> Result<()> {
+    let response = http::request("POST", url, Some(&payload.to_string()), &http::Options::default())?;
+    if !response.is_success() {
+        return Err(failure::network(format!("{} answered {}", url, response.status)));
+    }
+    Ok(())
+}
+
+fn describe(webhook: &Webhook) -> String {
+    let events = if webhook.events.is_empty() {
+        "all operations".to_string()
+    } else {
+        webhook.events.join(", ")
+    };
+    if webhook.failures_only {
+        format!("{}, failures only", events)
+    } else {
+        events
+    }
+}
+
+fn host() -> String {
+    whoami::fallible::hostname().unwrap_or_default()
+}
+
+impl LifecycleEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            LifecycleEvent::Deploy => "deploy",
+            LifecycleEvent::Start => "start",
+            LifecycleEvent::Stop => "stop",
+            LifecycleEvent::Clean => "clean",
+            LifecycleEvent::Upgrade => "upgrade",
+            LifecycleEvent::Backup => "backup",
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{WorkspaceBackend, WorkshopAccess, WorkshopCommand, WorkshopCreateOptions, WorkshopTeardownOptions},
+    config::AppConfig,
+    deploy,
+    engine::Engine,
+    envfile::{self, EnvMap},
+    failure,
+    hubapi::HubClient,
+    open, say, services, util,
+    workspace::{self, Workspaces},
+};
+
+const WORKSHOPS_FILE: &str = "workshops.json";
+/// Accounts one workshop may create; more is almost certainly a typo.
+const MAX_ACCOUNTS: u32 = 500;
+
+/// Temporary accounts created together for a training event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkshopRecord {
+    pub prefix: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub access: String,
+    pub users: Vec<String>,
+}
+
+pub fn run(command: WorkshopCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    match command {
+        WorkshopCommand::Create(opts) => create(&deploy_dir, &opts),
+        WorkshopCommand::List => list(&deploy_dir),
+        WorkshopCommand::Teardown(opts) => teardown(&deploy_dir, &opts),
+    }
+}
+
+/// Account names of a workshop: `ws-01` to `ws-30` for 30 accounts.
+pub fn account_names(prefix: &str, count: u32) -> Vec<String> {
+    let width = count.to_string().len().max(2);
+    (1..=count).map(|n| format!("{}-{:0width$}", prefix, n, width = width)).collect()
+}
+
+/// Prefixes stay within the characters DockerSpawner keeps as they are, so
+/// containers and volumes carry the account names unchanged.
+pub fn validate_prefix(prefix: &str) -> Result<()> {
+    let valid = prefix.chars().next().is_some_and(|ch| ch.is_ascii_lowercase())
+        && prefix.chars().all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-');
+    if !valid {
+        return Err(failure::validation(format!(
+            "workshop prefix '{}' must start with a letter and contain only lowercase letters, digits, and '-'",
+            prefix
+        )));
+    }
+    Ok(())
+}
+
+fn create(deploy_dir: &Path, opts: &WorkshopCreateOptions) -> Result<()> {
+    validate_prefix(&opts.prefix)?;
+    if opts.count == 0 || opts.count > MAX_ACCOUNTS {
+        return Err(failure::validation(format!("--count must be between 1 and {}", MAX_ACCOUNTS)));
+    }
+    let lifetime = util::parse_duration(&opts.expires)?;
+    if lifetime == 0 {
+        return Err(failure::validation("--expires must be greater than zero"));
+    }
+    let mut workshops = load(deploy_dir)?;
+    if workshops.iter().any(|workshop| workshop.prefix == opts.prefix) {
+        return Err(failure::validation(format!(
+            "A workshop with prefix {} exists; tear it down first or pick another prefix",
+            opts.prefix
+        )));
+    }
+
+    let env = envfile::load(deploy_dir)?;
+    let names = account_names(&opts.prefix, opts.count);
+    let client = HubClient::from_deployment(deploy_dir)?;
+    if !opts.no_pull {
+        pull_user_image(&Engine::new(deploy_dir), &env)?;
+    }
+    // The hub refuses to spawn for users without a workspace dataset.
+    if workspace::backend(&env) != WorkspaceBackend::Volume {
+        workspace::create_all(&Workspaces::from_env(&env)?, &env, &names)?;
+    }
+
+    let now = util::unix_now();
+    let mut record = WorkshopRecord {
+        prefix: opts.prefix.clone(),
+        created_at: now,
+        expires_at: now + lifetime,
+        access: opts.access.name().to_string(),
+        users: Vec::new(),
+    };
+    let hub_url = open::access(&env)?.hub_url;
+    let mut links = Vec::new();
+    let created = names.iter().try_for_each(|name| -> Result<()> {
+        client
+            .create_user(name)
+            .with_context(|| format!("failed to create workshop account {}", name))?;
+        record.users.push(name.clone());
+        if opts.access == WorkshopAccess::Link {
+            let note = format!("mvre-hub workshop {}", opts.prefix);
+            let token = client.create_token(name, lifetime, &note)?;
+            links.push((name.clone(), format!("{}/hub/spawn?token={}", hub_url, token)));
+        }
+        Ok(())
+    });
+    // Accounts created before a failure are recorded, so teardown finds them.
+    if !record.users.is_empty() {
+        workshops.push(record.clone());
+        save(deploy_dir, &workshops)?;
+    }
+    created?;
+
+    if opts.access == WorkshopAccess::AllowList {
+        deploy::update_allowed_users(deploy_dir, |allowed| {
+            for name in &names {
+                if !allowed.contains(name) {
+                    allowed.push(name.clone());
+                }
+            }
+        })?;
+    }
+    if opts.spawn {
+        for name in &names {
+            if let Err(err) = client.start_server(name, "") {
+                tracing::warn!("failed to start the server of {}: {:#}", name, err);
+            }
+        }
+    }
+    if let Some(output) = &opts.output {
+        write_links(output, &names, &links)?;
+    }
+
+    say!(
+        "{}",
+        style(format!("Workshop {} created with {} accounts", opts.prefix, names.len())).green()
+    );
+    say!("Expires: {}", util::format_timestamp(record.expires_at));
+    match opts.access {
+        WorkshopAccess::Link if opts.output.is_none() => {
+            for (name, link) in &links {
+                say!("  {:<12} {}", name, style(link).cyan());
+            }
+        }
+        WorkshopAccess::Link => {}
+        WorkshopAccess::AllowList => {
+            say!("Accounts {} to {} are on the allow-list", names[0], names[names.len() - 1]);
+            say!("Apply with: {}", style("mvre-hub start").cyan());
+        }
+    }
+    if let Some(output) = &opts.output {
+        say!("Accounts written to {}", style(output.display()).cyan());
+    }
+    if opts.spawn {
+        say!("{}", style("Servers requested; they may take a few minutes to come up").dim());
+    }
+    say!("Remove afterwards with: {}", style(format!("mvre-hub workshop teardown {}", opts.prefix)).cyan());
+    util::set_result(serde_json::json!({
+        "prefix": opts.prefix,
+        "users": names,
+        "expires_at": util::format_timestamp(record.expires_at),
+        "login_links": links
+            .iter()
+            .map(|(name, link)| serde_json::json!({ "user": name, "login_link": link }))
+            .collect::<Vec<_>>(),
+    }));
+    Ok(())
+}
+
+/// Makes sure the daemon has the user image, so the first spawns of the
+/// workshop do not all wait on the same download. Images only built locally
+/// are already there.
+fn pull_user_image(engine: &Engine, env: &EnvMap) -> Result<()> {
+    let image = envfile::get(env, "USER_IMAGE").unwrap_or("mvre-user:latest");
+    if engine.docker_output(&["image", "inspect", image]).is_ok() {
+        return Ok(());
+    }
+    engine
+        .docker_step(&["pull", image], &format!("Pulling {}", image))
+        .with_context(|| format!("failed to pull {}; run mvre-hub build or image pull first", image))
+}
+
+/// `user,login_link` lines, readable by the owner only since the links log
+/// straight in.
+fn write_links(output: &Path, names: &[String], links: &[(String, String)]) -> Result<()> {
+    let mut csv = String::from("user,login_link\n");
+    for name in names {
+        let link = links.iter().find(|(user, _)| user == name).map(|(_, link)| link.as_str());
+        csv.push_str(&format!("{},{}\n", name, link.unwrap_or("")));
+    }
+    util::atomic_write(output, csv.as_bytes())?;
+    util::set_file_mode(output, 0o600).ok();
+    Ok(())
+}
+
+fn list(deploy_dir: &Path) -> Result<()> {
+    let workshops = load(deploy_dir)?;
+    util::set_result(serde_json::json!({ "workshops": workshops }));
+    if workshops.is_empty() {
+        say!("{}", style("No workshops").dim());
+        return Ok(());
+    }
+    let now = util::unix_now();
+    for workshop in &workshops {
+        let state = if workshop.expires_at <= now {
+            style("expired").red()
+        } else {
+            style("active").green()
+        };
+        say!(
+            "  {:<12} {:>4} accounts  {:<10} {:<8} expires {}",
+            workshop.prefix,
+            workshop.users.len(),
+            workshop.access,
+            state,
+            util::format_timestamp(workshop.expires_at)
+        );
+    }
+    Ok(())
+}
+
+/// Stops and deletes the workshop's accounts, removes their volumes, and
+/// takes them off the allow-list.
+fn teardown(deploy_dir: &Path, opts: &WorkshopTeardownOptions) -> Result<()> {
+    let mut workshops = load(deploy_dir)?;
+    let index = workshops
+        .iter()
+        .position(|workshop| workshop.prefix == opts.prefix)
+        .ok_or_else(|| failure::validation(format!("No workshop with prefix {}", opts.prefix)))?;
+    let workshop = workshops[index].clone();
+
+    if !opts.yes {
+        let prompt = format!(
+            "Delete the {} accounts of workshop {} and their work?",
+            workshop.users.len(),
+            workshop.prefix
+        );
+        util::ensure_interactive(&prompt, "pass --yes")?;
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            return Err(failure::validation("Aborted"));
+        }
+    }
+
+    let env = envfile::load(deploy_dir)?;
+    let client = HubClient::from_deployment(deploy_dir)?;
+    let engine = Engine::new(deploy_dir);
+    let volumes = workspace::backend(&env) == WorkspaceBackend::Volume;
+    let mut removed = Vec::new();
+    let mut remaining = Vec::new();
+    let mut failed_volumes = Vec::new();
+    for name in &workshop.users {
+        let _ = client.stop_server(name, "");
+        match client.delete_user(name) {
+            Ok(()) => {
+                if volumes {
+                    let volume = workspace::user_volume(&env, name);
+                    if let Err(err) = engine.docker_output(&["volume", "rm", &volume]) {
+                        tracing::warn!("failed to remove volume {} of {}: {:#}", volume, name, err);
+                        failed_volumes.push(volume);
+                    }
+                }
+                say!("Removed {}", name);
+                removed.push(name.clone());
+            }
+            Err(err) => {
+                tracing::warn!("failed to remove {}: {:#}", name, err);
+                remaining.push(name.clone());
+            }
+        }
+    }
+
+    if workshop.access == WorkshopAccess::AllowList.name() {
+        deploy::update_allowed_users(deploy_dir, |allowed| allowed.retain(|name| !removed.contains(name)))?;
+    }
+    if remaining.is_empty() {
+        workshops.remove(index);
+    } else {
+        workshops[index].users = remaining.clone();
+    }
+    save(deploy_dir, &workshops)?;
+    if !volumes {
+        say!(
+            "{}",
+            style("Workspace datasets are left in place; remove them with zfs destroy or btrfs subvolume delete").dim()
+        );
+    }
+    util::set_result(serde_json::json!({
+        "removed": removed,
+        "remaining": remaining,
+        "failed_volumes": failed_volumes,
+    }));
+    if !remaining.is_empty() {
+        anyhow::bail!(
+            "{} accounts of workshop {} could not be removed; run teardown again",
+            remaining.len(),
+            workshop.prefix
+        );
+    }
+    say!("{}", style(format!("Workshop {} torn down", workshop.prefix)).green());
+    Ok(())
+}
+
+fn workshops_path(deploy_dir: &Path) -> PathBuf {
+    deploy_dir.join(WORKSHOPS_FILE)
+}
+
+pub fn load(deploy_dir: &Path) -> Result<Vec<WorkshopRecord>> {
+    let path = workshops_path(deploy_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw = util::read_to_string(&path)?;
+    serde_json::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+fn save(deploy_dir: &Path, workshops: &[WorkshopRecord]) -> Result<()> {
+    let serialized = serde_json::to_string_pretty(workshops).context("failed to serialize workshops")?;
+    util::write_string(&workshops_path(deploy_dir), &serialized)
+}
+
+impl WorkshopAccess {
+    pub fn name(self) -> &'static str {
+        match self {
+            WorkshopAccess::Link => "link",
+            WorkshopAccess::AllowList => "allow-list",
+        }
+    }
+}
@@ -0,0 +1,655 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+
+use crate::{
+    access,
+    backup::Destination,
+    cli::{BackupTarget, WorkspaceArchiveOptions, WorkspaceBackend, WorkspaceCommand},
+    config::AppConfig,
+    engine::Engine,
+    envfile::{self, EnvMap},
+    failure,
+    hubapi::{HubClient, User},
+    progress::Spinner,
+    prune,
+    repository::Repository,
+    say, services, users, util,
+};
+
+/// Where the hub sees the workspace root, to refuse spawns for users whose
+/// workspace does not exist yet.
+pub const HUB_WORKSPACE_DIR: &str = "/srv/workspaces";
+
+/// Btrfs snapshots live next to the workspaces, outside any user's subvolume.
+const BTRFS_SNAPSHOT_DIR: &str = ".snapshots";
+/// Throwaway container that reads a workspace for export.
+const EXPORT_IMAGE: &str = "alpine:3.20";
+/// Archives of inactive users' workspaces wait here while they are stored.
+pub const ARCHIVE_STAGING_DIR: &str = ".mvre-archive";
+
+/// Workspace settings recorded in the deployment's `.env`.
+#[derive(Debug, Clone)]
+pub struct Workspaces {
+    pub backend: WorkspaceBackend,
+    /// ZFS parent dataset, or the Btrfs directory holding the subvolumes.
+    pub root: String,
+    pub quota: Option<String>,
+}
+
+pub fn run(command: WorkspaceCommand, app_config: &AppConfig) -> Result<()> {
+    let deploy_dir = services::resolve_deploy_dir(app_config)?;
+    let env = envfile::load(&deploy_dir)?;
+    match command {
+        WorkspaceCommand::Create { names } => {
+            let workspaces = Workspaces::from_env(&env)?;
+            let names = if names.is_empty() {
+                users::split_list(envfile::get(&env, "ALLOWED_USERS").unwrap_or(""))
+            } else {
+                names
+            };
+            if names.is_empty() {
+                return Err(failure::validation("No users given and the allow-list is empty"));
+            }
+            let created = create_all(&workspaces, &env, &names)?;
+            util::set_result(serde_json::json!({ "created": created }));
+            Ok(())
+        }
+        WorkspaceCommand::Snapshot { name, label } => {
+            let workspaces = Workspaces::from_env(&env)?;
+            require_root()?;
+            let label = label.unwrap_or_else(|| util::format_timestamp(util::unix_now()).replace(':', ""));
+            let snapshot = snapshot(&workspaces, &name, &label)?;
+            say!("{} {}", style("Snapshot").green(), snapshot);
+            util::set_result(serde_json::json!({ "snapshot": snapshot }));
+            Ok(())
+        }
+        WorkspaceCommand::List => list(&Workspaces::from_env(&env)?),
+        WorkspaceCommand::Archive(opts) => archive(&deploy_dir, &env, opts),
+        WorkspaceCommand::Export { name, output } => export(&deploy_dir, &env, &name, output),
+        WorkspaceCommand::Import { name, archive, force } => import(&deploy_dir, &env, &name, &archive, force),
+    }
+}
+
+impl Workspaces {
+    pub fn from_env(env: &EnvMap) -> Result<Self> {
+        let backend = backend(env);
+        if backend == WorkspaceBackend::Volume {
+            anyhow::bail!(
+                "This deployment keeps workspaces in Docker volumes; reconfigure with --workspace-backend zfs or btrfs first"
+            );
+        }
+        let root = envfile::get(env, "WORKSPACE_ROOT")
+            .context("WORKSPACE_ROOT is missing from .env")?
+            .to_string();
+        Ok(Self {
+            backend,
+            root,
+            quota: envfile::get(env, "WORKSPACE_QUOTA").map(str::to_string),
+        })
+    }
+
+    /// Dataset or subvolume of `user`.
+    pub fn path(&self, user: &str) -> String {
+        format!("{}/{}", self.root.trim_end_matches('/'), workspace_name(user))
+    }
+}
+
+/// Name of a user's workspace: DockerSpawner's escaped user name, so the
+/// hub finds it under the same name it uses for containers and volumes.
+/// Characters outside `[a-z0-9]` become `-` and the hex of each UTF-8 byte,
+/// which keeps distinct user names distinct.
+pub fn workspace_name(user: &str) -> String {
+    let mut name = String::new();
+    for ch in user.chars() {
+        if ch.is_ascii_lowercase() || ch.is_ascii_digit() {
+            name.push(ch);
+        } else {
+            let mut buf = [0; 4];
+            for byte in ch.encode_utf8(&mut buf).bytes() {
+                name.push_str(&format!("-{:02X}", byte));
+            }
+        }
+    }
+    name
+}
+
+/// Docker volume DockerSpawner mounts as `user`'s workspace.
+pub fn user_volume(env: &EnvMap, user: &str) -> String {
+    format!("{}{}", prune::user_volume_prefix(env), workspace_name(user))
+}
+
+/// Host directory the workspaces are mounted under: the mountpoint of the
+/// ZFS parent dataset, or the Btrfs directory itself.
+pub fn host_path(backend: WorkspaceBackend, root: &str) -> Result<String> {
+    match backend {
+        WorkspaceBackend::Zfs => {
+            let output = tool_output("zfs", &["get", "-H", "-o", "value", "mountpoint", root])?;
+            let mountpoint = output.trim();
+            if !mountpoint.starts_with('/') {
+                return Err(failure::config(format!("ZFS dataset {} has no mountpoint ({})", root, mountpoint)));
+            }
+            Ok(mountpoint.to_string())
+        }
+        WorkspaceBackend::Btrfs => {
+            if !Path::new(root).is_dir() {
+                return Err(failure::config(format!("Btrfs workspace root {} does not exist", root)));
+            }
+            Ok(root.trim_end_matches('/').to_string())
+        }
+        WorkspaceBackend::Volume => Err(failure::config("Docker volumes have no host workspace root")),
+    }
+}
+
+/// Commands that create `user`'s workspace with the configured quota.
+pub fn create_commands(workspaces: &Workspaces, user: &str) -> Vec<Vec<String>> {
+    let path = workspaces.path(user);
+    match workspaces.backend {
+        WorkspaceBackend::Zfs => {
+            let mut create = args(&["zfs", "create"]);
+            if let Some(quota) = &workspaces.quota {
+                create.extend(args(&["-o", &format!("quota={}", quota)]));
+            }
+            create.push(path);
+            vec![create]
+        }
+        WorkspaceBackend::Btrfs => {
+            let mut commands = vec![args(&["btrfs", "subvolume", "create", &path])];
+            if let Some(quota) = &workspaces.quota {
+                commands.insert(0, args(&["btrfs", "quota", "enable", &workspaces.root]));
+                commands.push(args(&["btrfs", "qgroup", "limit", quota, &path]));
+            }
+            commands
+        }
+        WorkspaceBackend::Volume => Vec::new(),
+    }
+}
+
+/// Command that takes a read-only snapshot of `user`'s workspace.
+pub fn snapshot_command(workspaces: &Workspaces, user: &str, label: &str) -> (Vec<String>, String) {
+    let path = workspaces.path(user);
+    match workspaces.backend {
+        WorkspaceBackend::Btrfs => {
+            let target = format!(
+                "{}/{}/{}@{}",
+                workspaces.root.trim_end_matches('/'),
+                BTRFS_SNAPSHOT_DIR,
+                workspace_name(user),
+                label
+            );
+            (args(&["btrfs", "subvolume", "snapshot", "-r", &path, &target]), target)
+        }
+        _ => {
+            let target = format!("{}@{}", path, label);
+            (args(&["zfs", "snapshot", &target]), target)
+        }
+    }
+}
+
+/// Creates the missing workspaces of `names`, owned by the notebook UID/GID,
+/// and returns the users whose workspace was created.
+pub fn create_all(workspaces: &Workspaces, env: &EnvMap, names: &[String]) -> Result<Vec<String>> {
+    require_root()?;
+    let uid = parse_id(env, "NOTEBOOK_UID")?.unwrap_or(access::DEFAULT_NOTEBOOK_UID);
+    let gid = parse_id(env, "NOTEBOOK_GID")?.unwrap_or(access::DEFAULT_NOTEBOOK_GID);
+    let mut created = Vec::new();
+    for name in names {
+        if create(workspaces, name, uid, gid)? {
+            say!("{} {}", style("Created").green(), workspaces.path(name));
+            created.push(name.clone());
+        } else {
+            say!("{} {}", style("Exists ").dim(), workspaces.path(name));
+        }
+    }
+    Ok(created)
+}
+
+fn create(workspaces: &Workspaces, user: &str, uid: u32, gid: u32) -> Result<bool> {
+    users::validate_name(user)?;
+    let mountpoint = PathBuf::from(host_path(workspaces.backend, &workspaces.root)?).join(workspace_name(user));
+    if mountpoint.exists() {
+        return Ok(false);
+    }
+    for command in create_commands(workspaces, user) {
+        run_tool(&command)?;
+    }
+    // The notebook process owns its work directory.
+    #[cfg(unix)]
+    nix::unistd::chown(
+        &mountpoint,
+        Some(nix::unistd::Uid::from_raw(uid)),
+        Some(nix::unistd::Gid::from_raw(gid)),
+    )
+    .with_context(|| format!("failed to chown {}", mountpoint.display()))?;
+    #[cfg(not(unix))]
+    let _ = (uid, gid);
+    Ok(true)
+}
+
+fn snapshot(workspaces: &Workspaces, user: &str, label: &str) -> Result<String> {
+    let (command, target) = snapshot_command(workspaces, user, label);
+    if workspaces.backend == WorkspaceBackend::Btrfs {
+        util::ensure_dir(Path::new(&workspaces.root).join(BTRFS_SNAPSHOT_DIR).as_path())?;
+    }
+    run_tool(&command)?;
+    Ok(target)
+}
+
+/// Streams `user`'s workspace out of a read-only mount in a throwaway
+/// container, so it works for Docker volumes and on remote daemons alike.
+fn export(deploy_dir: &Path, env: &EnvMap, user: &str, output: Option<PathBuf>) -> Result<()> {
+    users::validate_name(user)?;
+    let engine = Engine::new(deploy_dir);
+    let source = export_source(env, user)?;
+    let exists = if source.starts_with('/') {
+        Path::new(&source).is_dir()
+    } else {
+        engine.volume_names()?.contains(&source)
+    };
+    if !exists {
+        return Err(failure::validation(format!("{} has no workspace ({} not found)", user, source)));
+    }
+
+    let output = output.unwrap_or_else(|| PathBuf::from(export_file_name(user, util::unix_now())));
+    if output.exists() {
+        return Err(failure::validation(format!("{} already exists", output.display())));
+    }
+    export_to(&engine, &source, user, &output)?;
+    let bytes = std::fs::metadata(&output).map(|meta| meta.len()).unwrap_or_default();
+    say!(
+        "{} {} ({})",
+        style("Workspace exported to").green(),
+        output.display(),
+        util::format_size(bytes)
+    );
+    util::set_result(serde_json::json!({ "user": user, "source": source, "output": output, "bytes": bytes }));
+    Ok(())
+}
+
+/// Writes the workspace in `source` to the archive `output`, removing a
+/// half-written archive on failure.
+fn export_to(engine: &Engine, source: &str, user: &str, output: &Path) -> Result<()> {
+    let file = File::create(output).with_context(|| format!("failed to create {}", output.display()))?;
+    util::set_file_mode(output, 0o600).ok();
+    let mount = format!("{}:/workspace:ro", source);
+    let result = {
+        let _spinner = Spinner::start(&format!("Exporting the workspace of {}", user));
+        engine.docker_stdio(
+            &["run", "--rm", "--network", "none", "-v", &mount, EXPORT_IMAGE, "tar", "-czf", "-", "-C", "/workspace", "."],
+            Stdio::null(),
+            Stdio::from(file),
+        )
+    };
+    if let Err(err) = result {
+        std::fs::remove_file(output).ok();
+        return Err(err.context(format!("failed to export the workspace of {}", user)));
+    }
+    Ok(())
+}
+
+/// Fills `user`'s workspace from a tar archive, such as one written by
+/// `workspace export` on another hub, and hands the files to the notebook
+/// user. Docker volumes are created when missing; ZFS and Btrfs workspaces
+/// must exist.
+fn import(deploy_dir: &Path, env: &EnvMap, user: &str, archive: &Path, force: bool) -> Result<()> {
+    users::validate_name(user)?;
+    let compressed = archive_compression(archive)?;
+    let file = File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+    let engine = Engine::new(deploy_dir);
+    let source = export_source(env, user)?;
+    if source.starts_with('/') {
+        if !Path::new(&source).is_dir() {
+            return Err(failure::validation(format!(
+                "{} has no workspace yet; create it with mvre-hub workspace create {}",
+                user, user
+            )));
+        }
+    } else {
+        engine.docker_output(&["volume", "create", &source])?;
+    }
+
+    let uid = parse_id(env, "NOTEBOOK_UID")?.unwrap_or(access::DEFAULT_NOTEBOOK_UID);
+    let gid = parse_id(env, "NOTEBOOK_GID")?.unwrap_or(access::DEFAULT_NOTEBOOK_GID);
+    let script = import_script(compressed, force, uid, gid);
+    let mount = format!("{}:/workspace", source);
+    let result = {
+        let _spinner = Spinner::start(&format!("Importing the workspace of {}", user));
+        engine.docker_stdio(
+            &["run", "--rm", "-i", "--network", "none", "-v", &mount, EXPORT_IMAGE, "sh", "-c", &script],
+            Stdio::from(file),
+            Stdio::null(),
+        )
+    };
+    result.with_context(|| format!("failed to import {} into the workspace of {}", archive.display(), user))?;
+    say!("{} {} into {}", style("Imported").green(), archive.display(), source);
+    util::set_result(serde_json::json!({ "user": user, "archive": archive, "workspace": source }));
+    Ok(())
+}
+
+/// Whether `archive` is gzipped, from its name; only tar archives are read.
+pub fn archive_compression(archive: &Path) -> Result<bool> {
+    let name = archive.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Ok(true)
+    } else if name.ends_with(".tar") {
+        Ok(false)
+    } else {
+        Err(failure::validation(format!(
+            "{} is not a .tar, .tar.gz, or .tgz archive",
+            archive.display()
+        )))
+    }
+}
+
+/// Shell script that unpacks the archive on stdin into `/workspace`. It
+/// refuses a workspace that already holds files unless `force`, since the
+/// user has then been working in it.
+pub fn import_script(compressed: bool, force: bool, uid: u32, gid: u32) -> String {
+    let guard = if force {
+        String::new()
+    } else {
+        "if [ -n \"$(ls -A /workspace)\" ]; then echo 'the workspace is not empty; pass --force to unpack over it' >&2; exit 3; fi; ".to_string()
+    };
+    format!(
+        "{}tar -x{}f - -C /workspace && chown -R {}:{} /workspace",
+        guard,
+        if compressed { "z" } else { "" },
+        uid,
+        gid
+    )
+}
+
+/// Exports the volumes of users inactive for `--inactive-for` to the backup
+/// destination, then removes them. A volume is only removed once its
+/// archive is stored.
+fn archive(deploy_dir: &Path, env: &EnvMap, opts: WorkspaceArchiveOptions) -> Result<()> {
+    let inactive_for = util::parse_duration(&opts.inactive_for).context("invalid --inactive-for")?;
+    if inactive_for == 0 {
+        return Err(failure::validation("--inactive-for must be greater than zero"));
+    }
+    if backend(env) != WorkspaceBackend::Volume {
+        return Err(failure::validation(
+            "workspace archive removes Docker volumes; ZFS and Btrfs workspaces are exported one by one with workspace export",
+        ));
+    }
+    if Repository::parse(&opts.target.dest).is_some() {
+        return Err(failure::validation("workspace archives go to a directory or an s3:// destination"));
+    }
+    let destination = Destination::parse(&opts.target.dest)?;
+
+    let engine = Engine::new(deploy_dir);
+    let now = util::unix_now();
+    let users = HubClient::from_deployment(deploy_dir)?.list_users()?;
+    let volumes = engine.volume_names()?;
+    let candidates: Vec<(String, u64, String)> = inactive_users(&users, inactive_for, now)
+        .into_iter()
+        .filter_map(|(user, seen)| {
+            let volume = user_volume(env, &user);
+            volumes.contains(&volume).then_some((user, seen, volume))
+        })
+        .collect();
+    if candidates.is_empty() {
+        say!("{}", style(format!("No workspaces of users inactive for {}", opts.inactive_for)).dim());
+        util::set_result(serde_json::json!({ "dry_run": opts.dry_run, "archived": [], "failed": [] }));
+        return Ok(());
+    }
+    for (user, seen, volume) in &candidates {
+        say!("  {:<24} last active {}  {}", user, util::format_timestamp(*seen), style(volume).dim());
+    }
+    if opts.dry_run {
+        let users: Vec<&str> = candidates.iter().map(|(user, ..)| user.as_str()).collect();
+        util::set_result(serde_json::json!({ "dry_run": true, "candidates": users }));
+        return Ok(());
+    }
+    let prompt = format!(
+        "Archive {} workspaces to {} and remove their volumes?",
+        candidates.len(),
+        opts.target.dest
+    );
+    if !opts.yes {
+        util::ensure_interactive(&prompt, "pass --yes")?;
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            return Err(failure::validation("Aborted"));
+        }
+    }
+
+    // Staged next to the deployment, which has room for its users' data;
+    // the temporary directory often does not.
+    let staging = deploy_dir.join(ARCHIVE_STAGING_DIR);
+    util::ensure_dir(&staging)?;
+    let mut archived = Vec::new();
+    let mut failed = Vec::new();
+    for (user, _, volume) in &candidates {
+        let result = archive_volume(&engine, &destination, &opts.target, &staging, volume, user, now)
+            .and_then(|location| engine.docker_output(&["volume", "rm", volume]).map(|_| location));
+        match result {
+            Ok(location) => {
+                say!("{} {} to {}", style("Archived").green(), user, location);
+                archived.push(serde_json::json!({ "user": user, "volume": volume, "archive": location }));
+            }
+            Err(err) => {
+                say!("{} {}: {:#}", style("Failed").yellow(), user, err);
+                failed.push(serde_json::json!({ "user": user, "error": format!("{:#}", err) }));
+            }
+        }
+    }
+    std::fs::remove_dir(&staging).ok();
+    util::set_result(serde_json::json!({ "dry_run": false, "archived": archived, "failed": failed }));
+    if !failed.is_empty() {
+        anyhow::bail!("{} of {} workspaces were not archived", failed.len(), candidates.len());
+    }
+    Ok(())
+}
+
+/// Exports `user`'s workspace volume through `staging` to `destination` and
+/// returns where the archive went; the volume itself is left for the caller.
+pub fn archive_volume(
+    engine: &Engine,
+    destination: &Destination,
+    target: &BackupTarget,
+    staging: &Path,
+    volume: &str,
+    user: &str,
+    now: u64,
+) -> Result<String> {
+    let name = export_file_name(user, now);
+    let file = staging.join(&name);
+    let result = export_to(engine, volume, user, &file).and_then(|_| destination.store(&file, &name, target));
+    std::fs::remove_file(&file).ok();
+    result.map(|_| destination.location(&name))
+}
+
+/// Users whose last activity, or creation when they were never active, lies
+/// more than `inactive_for` seconds before `now`, with that time. Users
+/// with a server are active whatever the hub recorded, and users without
+/// either time are left alone.
+pub fn inactive_users(users: &[User], inactive_for: u64, now: u64) -> Vec<(String, u64)> {
+    users
+        .iter()
+        .filter(|user| user.servers.is_empty())
+        .filter_map(|user| {
+            let seen = user
+                .last_activity
+                .as_deref()
+                .or(user.created.as_deref())
+                .and_then(util::parse_rfc3339)?;
+            (seen.saturating_add(inactive_for) < now).then(|| (user.name.clone(), seen))
+        })
+        .collect()
+}
+
+/// Where the deployment keeps workspaces; Docker volumes unless `.env` says otherwise.
+pub fn backend(env: &EnvMap) -> WorkspaceBackend {
+    envfile::get(env, "WORKSPACE_BACKEND")
+        .and_then(WorkspaceBackend::from_name)
+        .unwrap_or(WorkspaceBackend::Volume)
+}
+
+/// Docker volume or host directory holding `user`'s workspace.
+pub fn export_source(env: &EnvMap, user: &str) -> Result<String> {
+    if backend(env) == WorkspaceBackend::Volume {
+        return Ok(user_volume(env, user));
+    }
+    let host = envfile::get(env, "WORKSPACE_HOST_PATH")
+        .filter(|path| !path.is_empty())
+        .ok_or_else(|| failure::config("WORKSPACE_HOST_PATH is missing from .env"))?;
+    Ok(format!("{}/{}", host.trim_end_matches('/'), workspace_name(user)))
+}
+
+/// `workspace-alice-20261016T020000Z.tar.gz`
+pub fn export_file_name(user: &str, now: u64) -> String {
+    let stamp = util::format_timestamp(now).replace([':', '-'], "");
+    format!("workspace-{}-{}.tar.gz", workspace_name(user), stamp)
+}
+
+fn list(workspaces: &Workspaces) -> Result<()> {
+    let rows = match workspaces.backend {
+        WorkspaceBackend::Zfs => parse_zfs_list(&tool_output(
+            "zfs",
+            &["list", "-H", "-p", "-o", "name,used,quota", "-r", "-d", "1", &workspaces.root],
+        )?, &workspaces.root),
+        _ => list_btrfs(workspaces)?,
+    };
+    say!("{}", style(format!("Workspaces under {}", workspaces.root)).cyan().bold());
+    for row in &rows {
+        say!(
+            "  {:<32} {:>10} used  {:>10} quota",
+            row.name,
+            row.used.map(util::format_size).unwrap_or_else(|| "-".to_string()),
+            row.quota.map(util::format_size).unwrap_or_else(|| "none".to_string())
+        );
+    }
+    util::set_result(serde_json::json!({
+        "backend": workspaces.backend.name(),
+        "root": workspaces.root,
+        "workspaces": rows
+            .iter()
+            .map(|row| serde_json::json!({ "name": row.name, "used_bytes": row.used, "quota_bytes": row.quota }))
+            .collect::<Vec<_>>(),
+    }));
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceUsage {
+    pub name: String,
+    pub used: Option<u64>,
+    pub quota: Option<u64>,
+}
+
+/// Children of `root` from `zfs list -H -p -o name,used,quota` output; a
+/// quota of 0 means none.
+pub fn parse_zfs_list(output: &str, root: &str) -> Vec<WorkspaceUsage> {
+    let prefix = format!("{}/", root.trim_end_matches('/'));
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let name = fields.next()?.strip_prefix(&prefix)?.to_string();
+            let used = fields.next().and_then(|value| value.parse().ok());
+            let quota = fields.next().and_then(|value| value.parse().ok()).filter(|quota| *quota > 0);
+            Some(WorkspaceUsage { name, used, quota })
+        })
+        .collect()
+}
+
+/// Subvolumes directly under the root, with usage from `btrfs qgroup show`
+/// when quotas are enabled.
+fn list_btrfs(workspaces: &Workspaces) -> Result<Vec<WorkspaceUsage>> {
+    let mut rows = Vec::new();
+    for entry in std::fs::read_dir(&workspaces.root)
+        .with_context(|| format!("failed to read {}", workspaces.root))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == BTRFS_SNAPSHOT_DIR || !entry.path().is_dir() {
+            continue;
+        }
+        let path = entry.path().to_string_lossy().to_string();
+        let (used, quota) = tool_output("btrfs", &["qgroup", "show", "-f", "-r", "--raw", &path])
+            .ok()
+            .and_then(|output| parse_btrfs_qgroup(&output))
+            .unwrap_or((None, None));
+        rows.push(WorkspaceUsage { name, used, quota });
+    }
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(rows)
+}
+
+/// Referenced bytes and limit from `btrfs qgroup show -f -r --raw` output.
+pub fn parse_btrfs_qgroup(output: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let line = output.lines().find(|line| line.trim_start().starts_with("0/"))?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let used = fields.get(1).and_then(|value| value.parse().ok());
+    let quota = fields.get(3).and_then(|value| value.parse().ok());
+    Some((used, quota))
+}
+
+fn parse_id(env: &EnvMap, key: &str) -> Result<Option<u32>> {
+    envfile::get(env, key)
+        .map(|value| value.parse().with_context(|| format!("invalid {} in .env", key)))
+        .transpose()
+}
+
+fn require_root() -> Result<()> {
+    if !util::is_root() {
+        return Err(failure::permission("Root required to manage ZFS datasets and Btrfs subvolumes"));
+    }
+    Ok(())
+}
+
+fn args(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+fn tool_output(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .env("LC_ALL", "C")
+        .output()
+        .with_context(|| format!("failed to run {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!("{} {} failed: {}", program, args[0], String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn run_tool(command: &[String]) -> Result<()> {
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .output()
+        .with_context(|| format!("failed to run {}", command[0]))?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed: {}", command.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+impl WorkspaceBackend {
+    pub fn name(self) -> &'static str {
+        match self {
+            WorkspaceBackend::Volume => "volume",
+            WorkspaceBackend::Zfs => "zfs",
+            WorkspaceBackend::Btrfs => "btrfs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "volume" => Some(WorkspaceBackend::Volume),
+            "zfs" => Some(WorkspaceBackend::Zfs),
+            "btrfs" => Some(WorkspaceBackend::Btrfs),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+
+use mvre_hub::access::{self, Denied, Permissions};
+
+#[test]
+fn mode_bits_are_checked_for_owner_then_group_then_others() {
+    let perms = Permissions {
+        uid: 4100,
+        gid: 4200,
+        mode: 0o750,
+    };
+    assert!(access::mode_allows(&perms, 4100, 100, 0o5));
+    assert!(access::mode_allows(&perms, 1000, 4200, 0o5));
+    assert!(!access::mode_allows(&perms, 1000, 100, 0o1));
+    // Group bits do not apply to the owner.
+    let owner_locked = Permissions { mode: 0o070, ..perms };
+    assert!(!access::mode_allows(&owner_locked, 4100, 4200, 0o4));
+}
+
+#[test]
+fn unreadable_dataset_is_reported_with_its_path() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o755)).expect("chmod");
+    let dataset = dir.path().join("dataset");
+    std::fs::create_dir(&dataset).expect("dataset");
+    std::fs::set_permissions(&dataset, std::fs::Permissions::from_mode(0o700)).expect("chmod");
+
+    assert_eq!(
+        access::check(&dataset, 54321, 54321),
+        Some(Denied::Read(dataset.clone()))
+    );
+    std::fs::set_permissions(&dataset, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+    assert_eq!(access::check(&dataset, 54321, 54321), None);
+}
+
+#[test]
+fn remediation_names_the_blocking_directory() {
+    let denied = Denied::Traverse("/srv/data".into());
+    assert_eq!(
+        access::remediation(&denied, 1000, 100),
+        vec!["setfacl -m u:1000:x /srv/data", "chmod o+x /srv/data"]
+    );
+    let denied = Denied::Read("/srv/data/mosaic".into());
+    assert!(access::remediation(&denied, 1000, 4200)
+        .contains(&"chgrp -R 4200 /srv/data/mosaic && chmod -R g+rX /srv/data/mosaic".to_string()));
+}
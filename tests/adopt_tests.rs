@@ -0,0 +1,77 @@
+use mvre_hub::{adopt, compose, envfile};
+
+const HAND_ROLLED: &str = r#"
+services:
+  proxy:
+    image: traefik:v2.11
+    ports:
+      - "80:80"
+      - "8443:443"
+  hub:
+    image: jupyterhub/jupyterhub:4
+    labels:
+      traefik.http.routers.hub.rule: "Host(`hub.example.org`)"
+  db:
+    image: postgres:15
+"#;
+
+#[test]
+fn hand_rolled_deployment_is_mapped_and_gaps_are_reported() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let deploy_dir = dir.path().join("Legacy Hub");
+    let env = envfile::parse("POSTGRES_USER=hub\n");
+    let file = compose::parse(HAND_ROLLED).expect("parse");
+
+    let inspection = adopt::inspect(&deploy_dir, &env, &file);
+    let added: Vec<(&str, &str)> = inspection
+        .additions
+        .iter()
+        .map(|addition| (addition.key, addition.value.as_str()))
+        .collect();
+    assert_eq!(
+        added,
+        vec![
+            ("COMPOSE_PROJECT_NAME", "legacyhub"),
+            ("HUB_DOMAIN", "hub.example.org"),
+            ("HTTP_PORT", "80"),
+            ("HTTPS_PORT", "8443"),
+        ]
+    );
+
+    let managed = |name: &str| {
+        inspection
+            .capabilities
+            .iter()
+            .find(|capability| capability.name == name)
+            .expect("capability")
+            .clone()
+    };
+    assert!(managed("start, stop, status").managed);
+    assert!(managed("proxy and TLS").managed);
+    let hub = managed("users, tokens, health");
+    assert!(!hub.managed);
+    assert!(hub.detail.contains("named hub"), "{}", hub.detail);
+    let database = managed("database");
+    assert!(!database.managed);
+    assert!(database.detail.contains("db service"), "{}", database.detail);
+    assert!(!managed("reconfigure, upgrade").managed);
+}
+
+#[test]
+fn existing_settings_are_kept_and_postgres_is_recorded() {
+    let file = compose::parse(
+        "services:\n  jupyterhub:\n    image: jupyterhub/jupyterhub:4\n  postgres:\n    image: postgres:15\n",
+    )
+    .expect("parse");
+    let env = envfile::parse("COMPOSE_PROJECT_NAME=hub\nHUB_DOMAIN=old.example.org\nPOSTGRES_DB=jhub\n");
+
+    let inspection = adopt::inspect(std::path::Path::new("/srv/hub"), &env, &file);
+    let keys: Vec<&str> = inspection.additions.iter().map(|addition| addition.key).collect();
+    assert_eq!(keys, vec!["ENABLE_POSTGRES", "DB_HOST", "DB_NAME"]);
+    let merged = inspection.merged_env(&env);
+    assert_eq!(envfile::get(&merged, "HUB_DOMAIN"), Some("old.example.org"));
+    assert!(inspection
+        .capabilities
+        .iter()
+        .any(|capability| capability.name == "database" && capability.managed));
+}
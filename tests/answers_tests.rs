@@ -0,0 +1,191 @@
+use mvre_hub::answers::{self, CullingPolicy, HubGroup, ResourceLimit};
+
+#[test]
+fn culling_groups_resolve_to_seconds() {
+    let raw = r#"
+[culling]
+timeout = "12h"
+
+[culling.groups.students]
+timeout = "1h"
+
+[culling.groups.pis]
+timeout = "12h"
+"#;
+    let culling = answers::parse(raw).expect("parse").culling.expect("culling");
+    assert_eq!(
+        culling.resolve().expect("resolve"),
+        CullingPolicy {
+            timeout: Some(43_200),
+            every: None,
+            groups: vec![("pis".to_string(), 43_200), ("students".to_string(), 3600)],
+        }
+    );
+}
+
+#[test]
+fn culling_rejects_bad_groups_and_unknown_keys() {
+    let culling = answers::parse("[culling.groups.\"sea ice\"]\ntimeout = \"1h\"\n")
+        .expect("parse")
+        .culling
+        .expect("culling");
+    assert!(culling.resolve().is_err());
+
+    let culling = answers::parse("[culling.groups.students]\ntimeout = \"0m\"\n")
+        .expect("parse")
+        .culling
+        .expect("culling");
+    assert!(culling.resolve().is_err());
+
+    assert!(answers::parse("[culling]\ntimout = \"1h\"\n").is_err());
+}
+
+#[test]
+fn cull_groups_round_trip_through_env() {
+    let groups = vec![("students".to_string(), 3600), ("pis".to_string(), 43_200)];
+    let value = answers::format_cull_groups(&groups);
+    assert_eq!(value, "students=3600,pis=43200");
+    assert_eq!(answers::parse_cull_groups(&value).expect("parse"), groups);
+    assert!(answers::parse_cull_groups("").expect("empty").is_empty());
+    assert!(answers::parse_cull_groups("students").is_err());
+}
+
+#[test]
+fn groups_resolve_and_round_trip_through_env() {
+    let raw = r#"
+[groups.sea-ice]
+members = ["alice", "bob"]
+shared = "/srv/groups/sea-ice"
+
+[groups.pis]
+members = ["carol"]
+"#;
+    let groups = answers::resolve_groups(&answers::parse(raw).expect("parse").groups.expect("groups")).expect("resolve");
+    assert_eq!(
+        groups,
+        vec![
+            HubGroup { name: "pis".to_string(), members: vec!["carol".to_string()], shared: None },
+            HubGroup {
+                name: "sea-ice".to_string(),
+                members: vec!["alice".to_string(), "bob".to_string()],
+                shared: Some("/srv/groups/sea-ice".to_string()),
+            },
+        ]
+    );
+
+    let members = answers::format_group_members(&groups);
+    let shares = answers::format_group_shares(&groups);
+    assert_eq!(members, "pis=carol,sea-ice=alice:bob");
+    assert_eq!(shares, "sea-ice=/srv/groups/sea-ice");
+    assert_eq!(answers::parse_groups(&members, &shares).expect("parse"), groups);
+    assert!(answers::parse_groups("", "").expect("empty").is_empty());
+    assert!(answers::parse_groups("pis=carol", "sea-ice=/srv/groups/sea-ice").is_err());
+}
+
+#[test]
+fn groups_reject_what_would_not_survive_env() {
+    for raw in [
+        "[groups.\"sea ice\"]\nmembers = [\"alice\"]\n",
+        "[groups.sea-ice]\nmembers = [\"a:b\"]\n",
+        "[groups.sea-ice]\nmembers = [\"a,b\"]\n",
+        "[groups.sea-ice]\nshared = \"/srv/a,b\"\n",
+    ] {
+        let groups = answers::parse(raw).expect("parse").groups.expect("groups");
+        assert!(answers::resolve_groups(&groups).is_err(), "{}", raw);
+    }
+    assert!(answers::parse("[groups.sea-ice]\nmember = [\"alice\"]\n").is_err());
+}
+
+#[test]
+fn extra_services_round_trip_through_their_file() {
+    let raw = r#"
+[extra_services.docs]
+image = "nginx:alpine"
+volumes = ["./docs:/usr/share/nginx/html:ro"]
+labels = ["traefik.enable=true"]
+
+[extra_services.cron]
+image = "alpine:3.20"
+command = ["crond", "-f"]
+env = { TZ = "Europe/Berlin" }
+networks = []
+"#;
+    let services = answers::parse(raw).expect("parse").extra_services.expect("extra services");
+    answers::validate_extra_services(&services).expect("valid");
+    assert_eq!(services["cron"].environment["TZ"], "Europe/Berlin");
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::write(dir.path().join(answers::EXTRA_SERVICES_FILE), answers::format_extra_services(&services)).unwrap();
+    let loaded = answers::load_extra_services(dir.path(), &["docs".to_string(), "cron".to_string()]).expect("load");
+    assert_eq!(loaded, services);
+    let docs = answers::load_extra_services(dir.path(), &["docs".to_string()]).expect("load");
+    assert_eq!(docs.keys().collect::<Vec<_>>(), vec!["docs"]);
+    assert!(answers::load_extra_services(dir.path(), &["wiki".to_string()]).is_err());
+    assert!(answers::load_extra_services(&dir.path().join("missing"), &[]).expect("none").is_empty());
+}
+
+#[test]
+fn extra_services_reject_clashes_and_what_compose_would_not_take() {
+    for raw in [
+        "[extra_services.traefik]\nimage = \"nginx\"\n",
+        "[extra_services.Docs]\nimage = \"nginx\"\n",
+        "[extra_services.docs]\nimage = \"\"\n",
+        "[extra_services.docs]\nimage = \"nginx\"\nnetworks = [\"logging\"]\n",
+        "[extra_services.docs]\nimage = \"nginx\"\nlabels = [\"traefik.enable\"]\n",
+        "[extra_services.docs]\nimage = \"nginx\"\nenvironment = { \"MY-VAR\" = \"x\" }\n",
+    ] {
+        let services = answers::parse(raw).expect("parse").extra_services.expect("extra services");
+        assert!(answers::validate_extra_services(&services).is_err(), "{}", raw);
+    }
+    assert!(answers::parse("[extra_services.docs]\nimage = \"nginx\"\nports = [\"80:80\"]\n").is_err());
+}
+
+#[test]
+fn limits_resolve_and_round_trip_through_env() {
+    let raw = r#"
+[limits.groups.power-users]
+cpu = 8
+mem = "32G"
+
+[limits.users.alice]
+cpu = 16.5
+"#;
+    let limits = answers::parse(raw).expect("parse").limits.expect("limits").resolve().expect("resolve");
+    let users = answers::format_limits(&limits.users);
+    let groups = answers::format_limits(&limits.groups);
+    assert_eq!(users, "alice=16.5:");
+    assert_eq!(groups, "power-users=8:32G");
+    assert_eq!(
+        answers::ResourceLimits {
+            groups: answers::parse_limits(&groups, "GROUP_LIMITS").expect("groups"),
+            users: answers::parse_limits(&users, "USER_LIMITS").expect("users"),
+        },
+        limits
+    );
+    assert_eq!(
+        limits.users,
+        vec![ResourceLimit {
+            name: "alice".to_string(),
+            cpu: Some(16.5),
+            mem: None,
+        }]
+    );
+}
+
+#[test]
+fn limits_reject_what_the_hub_would_not_read() {
+    for raw in [
+        "[limits.users.alice]\ncpu = 0\n",
+        "[limits.users.alice]\nmem = \"32GB\"\n",
+        "[limits.users.alice]\nmem = \"lots\"\n",
+        "[limits.users.alice]\n",
+        "[limits.users.\"a:b\"]\ncpu = 2\n",
+        "[limits.groups.\"sea ice\"]\ncpu = 2\n",
+    ] {
+        let limits = answers::parse(raw).expect("parse").limits.expect("limits");
+        assert!(limits.resolve().is_err(), "{}", raw);
+    }
+    assert!(answers::validate_memory("512M").is_ok());
+    assert!(answers::validate_memory("1073741824").is_ok());
+    assert!(answers::parse_limits("alice=8", "USER_LIMITS").is_err());
+}
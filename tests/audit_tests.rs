@@ -0,0 +1,25 @@
+use mvre_hub::audit;
+
+#[test]
+fn credential_flags_are_redacted() {
+    let args = ["deploy", "--client-secret", "s3cret", "--domain", "hub.example.org", "--db-password=pw"]
+        .iter()
+        .map(|arg| arg.to_string());
+
+    assert_eq!(
+        audit::redact_args(args),
+        vec!["deploy", "--client-secret", "***", "--domain", "hub.example.org", "--db-password=***"]
+    );
+}
+
+#[test]
+fn credential_assignments_are_redacted() {
+    let args = ["user-env", "set", "--user", "alice", "PROJECT_TOKEN=abc", "MOSAIC_DATA=/data/a"]
+        .iter()
+        .map(|arg| arg.to_string());
+
+    assert_eq!(
+        audit::redact_args(args),
+        vec!["user-env", "set", "--user", "alice", "PROJECT_TOKEN=***", "MOSAIC_DATA=/data/a"]
+    );
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use mvre_hub::{
+    backup::{self, Backup, Destination, RetentionPolicy},
+    cli::{BackupTarget, ServerSideEncryption},
+};
+
+const DAY: u64 = 86_400;
+/// 2026-10-12, a Monday.
+const MONDAY: u64 = 20_738 * DAY;
+
+fn target(sse: Option<ServerSideEncryption>, key: Option<&str>) -> BackupTarget {
+    BackupTarget {
+        dest: "s3://hub-backups/mosaic".to_string(),
+        sse,
+        sse_kms_key_id: key.map(str::to_string),
+        password_file: None,
+        endpoint_url: None,
+    }
+}
+
+#[test]
+fn destinations_are_parsed_from_urls_and_paths() {
+    let s3 = Destination::parse("s3://hub-backups/mosaic/nightly/").expect("s3");
+    assert_eq!(
+        s3,
+        Destination::S3 {
+            bucket: "hub-backups".to_string(),
+            prefix: "mosaic/nightly".to_string(),
+        }
+    );
+    assert_eq!(s3.location("a.tar.gz"), "s3://hub-backups/mosaic/nightly/a.tar.gz");
+    assert_eq!(
+        Destination::parse("s3://hub-backups").expect("bucket").location("a.tar.gz"),
+        "s3://hub-backups/a.tar.gz"
+    );
+    assert_eq!(
+        Destination::parse("/srv/backups").expect("local"),
+        Destination::Local(PathBuf::from("/srv/backups"))
+    );
+    assert!(Destination::parse("s3:///prefix").is_err());
+}
+
+#[test]
+fn backup_names_round_trip_their_timestamp() {
+    let backup = Backup::new(MONDAY + 3723);
+    assert_eq!(backup.name, "mvre-hub-20261012T010203Z.tar.gz");
+    assert_eq!(Backup::from_name(&backup.name), Some(backup));
+    assert_eq!(Backup::from_name("mvre-hub-20261012T010203Z.tar.gz.partial"), None);
+    assert_eq!(Backup::from_name("notes.txt"), None);
+}
+
+#[test]
+fn retention_keeps_the_newest_backup_per_day_and_week() {
+    // Two backups a day for three weeks, newest on Sunday of the third week.
+    let backups: Vec<Backup> = (0..21)
+        .flat_map(|day| [Backup::new(MONDAY + day * DAY + 3600), Backup::new(MONDAY + day * DAY + 7200)])
+        .collect();
+    let expired = backup::expired(&backups, RetentionPolicy { daily: 3, weekly: 2 });
+    let kept: Vec<u64> = backups
+        .iter()
+        .filter(|backup| !expired.contains(backup))
+        .map(|backup| backup.created_at)
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            MONDAY + 13 * DAY + 7200, // newest of the second week
+            MONDAY + 18 * DAY + 7200,
+            MONDAY + 19 * DAY + 7200,
+            MONDAY + 20 * DAY + 7200, // newest of the third week and day
+        ]
+    );
+}
+
+#[test]
+fn s3_listings_yield_object_names() {
+    let output = "                           PRE old/\n2026-10-12 01:02:05   10485760 mvre-hub-20261012T010203Z.tar.gz\n2026-10-13 01:02:04       2048 notes.txt\n";
+    assert_eq!(
+        backup::parse_s3_listing(output),
+        vec!["mvre-hub-20261012T010203Z.tar.gz", "notes.txt"]
+    );
+}
+
+#[test]
+fn server_side_encryption_flags() {
+    assert!(backup::sse_args(&target(None, None)).is_empty());
+    assert_eq!(backup::sse_args(&target(Some(ServerSideEncryption::Aes256), None)), vec!["--sse", "AES256"]);
+    assert_eq!(
+        backup::sse_args(&target(Some(ServerSideEncryption::Kms), Some("alias/hub"))),
+        vec!["--sse", "aws:kms", "--sse-kms-key-id", "alias/hub"]
+    );
+}
@@ -0,0 +1,71 @@
+use mvre_hub::{bluegreen, compose};
+
+const COMPOSE: &str = r#"x-logging: &default-logging
+  driver: local
+
+services:
+  jupyterhub:
+    logging: *default-logging
+    build: ./hub
+    env_file: .env
+    volumes:
+      - ./hub/jupyterhub_config.py:/etc/jupyterhub/jupyterhub_config.py:ro
+    depends_on:
+      - postgres
+    networks:
+      - proxy
+      - hub
+      - users
+    labels:
+      - "traefik.enable=true"
+      - "traefik.http.routers.jupyterhub.rule=Host(`hub.example.org`)"
+
+  postgres:
+    image: postgres:15
+    networks:
+      - hub
+
+networks:
+  proxy:
+    name: ${PROXY_NETWORK_NAME:-mvre-hub_proxy}
+  hub:
+    internal: true
+  users:
+    name: ${DOCKER_NETWORK_NAME:-mvre-hub_users}
+"#;
+
+#[test]
+fn green_hub_runs_the_new_image_on_the_live_networks() {
+    let rendered = bluegreen::green_compose(COMPOSE, "mosaic", 18000, false).expect("green compose");
+    let file = compose::parse(&rendered).expect("valid compose");
+
+    assert_eq!(file.services.keys().collect::<Vec<_>>(), vec!["jupyterhub"]);
+    let hub = &file.services["jupyterhub"];
+    assert_eq!(hub.image.as_deref(), Some("mosaic-jupyterhub"));
+    assert!(hub.build.is_none());
+    assert!(hub.dependencies().is_empty());
+    assert_eq!(hub.published_port(8000), Some(18000));
+    assert!(rendered.contains("127.0.0.1:18000:8000"));
+    assert!(rendered.contains("traefik.http.routers.jupyterhub.rule"));
+    assert!(rendered.contains("http://localhost:8000/hub/health"));
+    assert!(rendered.contains("driver: local"));
+
+    assert_eq!(file.networks.len(), 3);
+    let name = |network: &str| file.networks[network]["name"].as_str().map(str::to_string);
+    assert_eq!(name("hub").as_deref(), Some("mosaic_hub"));
+    assert_eq!(name("proxy").as_deref(), Some("${PROXY_NETWORK_NAME:-mvre-hub_proxy}"));
+    assert!(file.networks.values().all(|network| network["external"].as_bool() == Some(true)));
+}
+
+#[test]
+fn internal_tls_hubs_are_probed_over_https() {
+    let rendered = bluegreen::green_compose(COMPOSE, "mosaic", 18000, true).expect("green compose");
+    assert!(rendered.contains("https://localhost:8000/hub/health"));
+    assert_eq!(bluegreen::health_url(true, "127.0.0.1", 18000), "https://127.0.0.1:18000/hub/health");
+    assert_eq!(bluegreen::green_project("mosaic"), "mosaic-green");
+}
+
+#[test]
+fn compose_files_without_a_hub_are_rejected() {
+    assert!(bluegreen::green_compose("services:\n  postgres:\n    image: postgres:15\n", "mosaic", 18000, false).is_err());
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use mvre_hub::branding::{self, BrandingSources};
+
+#[test]
+fn logos_keep_a_known_extension() {
+    assert_eq!(branding::logo_name(Path::new("/tmp/AWI-Logo.SVG")).expect("svg"), "logo.svg");
+    assert_eq!(branding::logo_name(Path::new("logo.jpeg")).expect("jpeg"), "logo.jpeg");
+    assert!(branding::logo_name(Path::new("logo.pdf")).is_err());
+    assert!(branding::logo_name(Path::new("logo")).is_err());
+}
+
+#[test]
+fn colors_and_texts_are_validated() {
+    assert!(branding::validate_color("#005aa0").is_ok());
+    assert!(branding::validate_color("#fff").is_ok());
+    assert!(branding::validate_color("005aa0").is_err());
+    assert!(branding::validate_color("#00zzaa").is_err());
+    assert!(branding::validate_text("--welcome-text", "Welcome, MOSAiC team").is_ok());
+    assert!(branding::validate_text("--welcome-text", "two\nlines").is_err());
+}
+
+#[test]
+fn assets_are_copied_into_the_deployment() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let logo = dir.path().join("consortium.png");
+    let terms = dir.path().join("terms.html");
+    std::fs::write(&logo, b"\x89PNG").expect("logo");
+    std::fs::write(&terms, "<p>Data may only be used for research.</p>").expect("terms");
+
+    let deploy = dir.path().join("deploy");
+    let sources = BrandingSources {
+        logo: Some(logo),
+        terms: Some(terms),
+    };
+    branding::install(&deploy, &sources).expect("install");
+    assert_eq!(std::fs::read(deploy.join("branding/logo.png")).expect("copied logo"), b"\x89PNG");
+    assert!(std::fs::read_to_string(deploy.join("branding/terms.html")).expect("copied terms").contains("research"));
+
+    let templates = branding::template_files(&deploy);
+    assert!(templates.iter().any(|(path, contents)| path.ends_with("templates/login.html")
+        && contents.contains(r#"{% extends "templates/login.html" %}"#)));
+}
@@ -0,0 +1,37 @@
+use mvre_hub::{
+    build::{self, EnvRepo},
+    envfile,
+};
+
+#[test]
+fn user_environment_repository_is_read_from_env() {
+    let env = envfile::parse("USER_ENV_REPO=https://git.example.org/geo/env.git\nUSER_ENV_REF=\n");
+    let repo = EnvRepo::from_env(&env).expect("repo");
+    assert_eq!(repo.url, "https://git.example.org/geo/env.git");
+    assert_eq!(repo.git_ref, None);
+
+    assert_eq!(EnvRepo::from_env(&envfile::parse("USER_ENV_REPO=\n")), None);
+}
+
+#[test]
+fn repo2docker_builds_the_user_image_for_jovyan() {
+    let repo = EnvRepo {
+        url: "https://git.example.org/geo/env.git".to_string(),
+        git_ref: Some("v2".to_string()),
+    };
+    assert_eq!(
+        build::repo2docker_args(&repo, "mvre-user:latest", "4100"),
+        vec![
+            "--no-run",
+            "--image-name",
+            "mvre-user:latest",
+            "--user-name",
+            "jovyan",
+            "--user-id",
+            "4100",
+            "--ref",
+            "v2",
+            "https://git.example.org/geo/env.git",
+        ]
+    );
+}
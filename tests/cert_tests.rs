@@ -0,0 +1,35 @@
+use mvre_hub::cert::{self, AcmeStore};
+
+#[test]
+fn acme_store_lists_account_and_domains_of_every_resolver() {
+    let raw = r#"{
+        "letsencrypt": {
+            "Account": {"Email": "ops@example.org", "Registration": {}, "PrivateKey": "MIIE", "KeyType": "4096"},
+            "Certificates": [
+                {"domain": {"main": "hub.example.org", "sans": ["mlflow.example.org"]}, "certificate": "LS0t", "key": "LS0t", "Store": "default"}
+            ]
+        },
+        "staging": {"Account": null, "Certificates": null}
+    }"#;
+    assert_eq!(
+        cert::parse_acme_store(raw).unwrap(),
+        AcmeStore {
+            account: Some("ops@example.org".to_string()),
+            domains: vec!["hub.example.org".to_string(), "mlflow.example.org".to_string()],
+        }
+    );
+    assert_eq!(cert::parse_acme_store("{}").unwrap(), AcmeStore::default());
+    assert!(cert::parse_acme_store("[]").is_err());
+}
+
+#[test]
+fn restore_accepts_only_certificate_files() {
+    assert!(cert::is_cert_entry("traefik/acme.json"));
+    assert!(cert::is_cert_entry("./traefik/acme-staging.json"));
+    assert!(cert::is_cert_entry("local-tls/"));
+    assert!(cert::is_cert_entry("local-tls/ca.key"));
+    assert!(!cert::is_cert_entry("traefik/"));
+    assert!(!cert::is_cert_entry(".env"));
+    assert!(!cert::is_cert_entry("local-tls-other/ca.key"));
+    assert!(!cert::is_cert_entry("local-tls/../.env"));
+}
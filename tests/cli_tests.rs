@@ -0,0 +1,255 @@
+use clap::Parser;
+use mvre_hub::cli::{Cli, Commands, LifecycleEvent, WebhooksCommand};
+
+#[test]
+fn deploy_inputs_prefer_flags_over_environment() {
+    std::env::set_var("MVRE_HUB_DOMAIN", "env.example.org");
+    std::env::set_var("MVRE_HUB_CLIENT_SECRET", "from-vault");
+    std::env::set_var("MVRE_HUB_HTTPS_PORT", "9443");
+
+    let cli = Cli::try_parse_from(["mvre-hub", "deploy", "--domain", "flag.example.org"]).expect("parse");
+    let Commands::Deploy { opts } = cli.command else {
+        panic!("expected deploy");
+    };
+    assert_eq!(opts.domain.as_deref(), Some("flag.example.org"));
+    assert_eq!(opts.client_secret.as_deref(), Some("from-vault"));
+    assert_eq!(opts.https_port, 9443);
+    assert_eq!(opts.acme_email, None);
+}
+
+#[test]
+fn deploy_takes_a_compose_project_name() {
+    let cli = Cli::try_parse_from(["mvre-hub", "deploy", "--project-name", "geo-hub"]).expect("parse");
+    let Commands::Deploy { opts } = cli.command else {
+        panic!("expected deploy");
+    };
+    assert_eq!(opts.project_name.as_deref(), Some("geo-hub"));
+}
+
+#[test]
+fn notebook_ids_cannot_be_set_and_reset_together() {
+    let cli = Cli::try_parse_from(["mvre-hub", "deploy", "--notebook-uid", "4100", "--notebook-gid", "4200"]).expect("parse");
+    let Commands::Deploy { opts } = cli.command else {
+        panic!("expected deploy");
+    };
+    assert_eq!((opts.notebook_uid, opts.notebook_gid), (Some(4100), Some(4200)));
+
+    assert!(Cli::try_parse_from(["mvre-hub", "reconfigure", "--notebook-gid", "4200", "--default-notebook-ids"]).is_err());
+}
+
+#[test]
+fn dataset_workspace_backends_need_a_root() {
+    assert!(Cli::try_parse_from(["mvre-hub", "deploy", "--workspace-backend", "zfs"]).is_err());
+    assert!(Cli::try_parse_from(["mvre-hub", "deploy", "--workspace-backend", "btrfs", "--workspace-root", "/srv/workspaces"]).is_ok());
+}
+
+#[test]
+fn announcements_need_a_message_or_clear() {
+    assert!(Cli::try_parse_from(["mvre-hub", "announce"]).is_err());
+    assert!(Cli::try_parse_from(["mvre-hub", "announce", "Maintenance at 18:00", "--clear"]).is_err());
+    assert!(Cli::try_parse_from(["mvre-hub", "announce", "--clear"]).is_ok());
+}
+
+#[test]
+fn dev_removal_takes_no_other_options() {
+    let cli = Cli::try_parse_from(["mvre-hub", "dev", "--remove", "/tmp/mvre-hub-dev-1a2b3c"]).expect("parse");
+    let Commands::Dev { opts } = cli.command else {
+        panic!("expected dev");
+    };
+    assert_eq!(opts.remove.as_deref(), Some(std::path::Path::new("/tmp/mvre-hub-dev-1a2b3c")));
+    assert!(Cli::try_parse_from(["mvre-hub", "dev", "--remove", "/tmp/a", "--no-start"]).is_err());
+}
+
+#[test]
+fn ci_mode_is_a_global_flag() {
+    let cli = Cli::try_parse_from(["mvre-hub", "status", "--ci"]).expect("parse");
+    assert!(cli.ci);
+}
+
+#[test]
+fn deploy_dir_is_a_global_flag() {
+    let cli = Cli::try_parse_from(["mvre-hub", "status", "--deploy-dir", "/srv/geo"]).expect("parse");
+    assert_eq!(cli.deploy_dir.as_deref(), Some(std::path::Path::new("/srv/geo")));
+}
+
+#[test]
+fn listing_deployments_is_not_audited() {
+    let list = Cli::try_parse_from(["mvre-hub", "deployments", "list"]).expect("parse");
+    assert_eq!(list.command.audit_name(), None);
+    let switch = Cli::try_parse_from(["mvre-hub", "use", "geo"]).expect("parse");
+    assert_eq!(switch.command.audit_name(), Some("use"));
+}
+
+#[test]
+fn plan_reads_the_manifest_and_apply_is_audited() {
+    let plan = Cli::try_parse_from(["mvre-hub", "plan"]).expect("parse");
+    let Commands::Plan { opts } = &plan.command else {
+        panic!("expected plan");
+    };
+    assert_eq!(opts.file, std::path::Path::new("mvre-hub.toml"));
+    assert_eq!(plan.command.audit_name(), None);
+
+    let apply = Cli::try_parse_from(["mvre-hub", "apply", "-f", "hubs/geo.toml", "--yes"]).expect("parse");
+    let Commands::Apply { opts } = &apply.command else {
+        panic!("expected apply");
+    };
+    assert!(opts.yes);
+    assert_eq!(opts.manifest.file, std::path::Path::new("hubs/geo.toml"));
+    assert_eq!(apply.command.audit_name(), Some("apply"));
+}
+
+#[test]
+fn lifecycle_commands_are_reported_to_webhooks() {
+    let event = |args: &[&str]| {
+        let cli = Cli::try_parse_from(std::iter::once("mvre-hub").chain(args.iter().copied())).expect("parse");
+        cli.command.lifecycle_event()
+    };
+    assert_eq!(event(&["stop"]), Some(LifecycleEvent::Stop));
+    assert_eq!(event(&["backup", "create", "--dest", "/mnt/backups"]), Some(LifecycleEvent::Backup));
+    assert_eq!(event(&["backup", "list", "--dest", "/mnt/backups"]), None);
+    assert_eq!(event(&["start", "--all"]), None);
+    assert_eq!(event(&["status"]), None);
+
+    let cli = Cli::try_parse_from(["mvre-hub", "webhooks", "add", "https://chat.example.org/h", "--events", "deploy,upgrade"])
+        .expect("parse");
+    let Commands::Webhooks {
+        command: WebhooksCommand::Add(opts),
+    } = cli.command
+    else {
+        panic!("expected webhooks add");
+    };
+    assert_eq!(opts.events, vec![LifecycleEvent::Deploy, LifecycleEvent::Upgrade]);
+}
+
+#[test]
+fn gitops_needs_a_repository_unless_uninstalling() {
+    assert!(Cli::try_parse_from(["mvre-hub", "gitops"]).is_err());
+    assert!(Cli::try_parse_from(["mvre-hub", "gitops", "--uninstall"]).is_ok());
+    let cli = Cli::try_parse_from(["mvre-hub", "gitops", "--repo", "git@git.example.org:hub/config.git"]).expect("parse");
+    let Commands::Gitops { opts } = &cli.command else {
+        panic!("expected gitops");
+    };
+    assert_eq!((opts.interval.as_str(), opts.path.to_str()), ("5m", Some("mvre-hub.toml")));
+    assert_eq!(cli.command.audit_name(), None);
+    assert!(!cli.command.locks_deployment());
+}
+
+#[test]
+fn only_changes_to_a_local_deployment_take_its_lock() {
+    let locks = |args: &[&str]| {
+        let cli = Cli::try_parse_from(std::iter::once("mvre-hub").chain(args.iter().copied())).expect("parse");
+        cli.command.locks_deployment()
+    };
+    assert!(locks(&["start"]));
+    assert!(locks(&["clean", "--full-ice"]));
+    assert!(locks(&["db", "restore", "dump.sql"]));
+    assert!(locks(&["prune", "volumes"]));
+    assert!(locks(&["guest", "prune"]));
+    assert!(locks(&["workspace", "import", "alice", "alice.tar.gz"]));
+    assert!(locks(&["workspace", "snapshot", "alice"]));
+    assert!(locks(&["users", "allow", "alice"]));
+    assert!(locks(&["users", "disallow", "alice"]));
+    assert!(locks(&["user-env", "set", "--group", "staff", "FOO=1"]));
+    assert!(locks(&["user-env", "unset", "--user", "alice", "FOO"]));
+    assert!(!locks(&["user-env", "list"]));
+    assert!(!locks(&["users", "list"]));
+    assert!(!locks(&["prune", "volumes", "--dry-run"]));
+    assert!(!locks(&["guest", "list"]));
+    assert!(!locks(&["start", "--all"]));
+    assert!(!locks(&["rollback", "--list"]));
+    assert!(!locks(&["status"]));
+    assert!(!locks(&["backup", "list", "--dest", "/mnt/backups"]));
+}
+
+#[test]
+fn stop_waits_for_idle_only_when_asked() {
+    let cli = Cli::try_parse_from(["mvre-hub", "stop", "--when-idle", "--max-wait", "30m"]).expect("parse");
+    let Commands::Stop { opts, .. } = cli.command else {
+        panic!("expected stop");
+    };
+    assert!(opts.when_idle);
+    assert_eq!(opts.to_args(), vec!["stop", "--when-idle", "--max-wait", "30m"]);
+
+    assert!(Cli::try_parse_from(["mvre-hub", "stop", "--max-wait", "1h"]).is_err());
+    let plain = Cli::try_parse_from(["mvre-hub", "stop"]).expect("parse");
+    let Commands::Stop { opts, .. } = plain.command else {
+        panic!("expected stop");
+    };
+    assert_eq!(opts.to_args(), vec!["stop"]);
+}
+
+#[test]
+fn start_waits_for_the_hub_only_when_asked() {
+    let cli = Cli::try_parse_from(["mvre-hub", "start", "--wait", "--wait-timeout", "10m"]).expect("parse");
+    let Commands::Start { opts, .. } = cli.command else {
+        panic!("expected start");
+    };
+    assert_eq!(opts.to_args(), vec!["start", "--wait", "--wait-timeout", "10m"]);
+
+    assert!(Cli::try_parse_from(["mvre-hub", "start", "--wait-timeout", "10m"]).is_err());
+    let plain = Cli::try_parse_from(["mvre-hub", "start"]).expect("parse");
+    let Commands::Start { opts, .. } = plain.command else {
+        panic!("expected start");
+    };
+    assert!(!opts.wait);
+    assert_eq!(opts.to_args(), vec!["start"]);
+}
+
+#[test]
+fn open_is_read_only_and_start_open_stays_local() {
+    let cli = Cli::try_parse_from(["mvre-hub", "open", "--no-browser"]).expect("parse");
+    assert_eq!(cli.command.audit_name(), None);
+    assert!(!cli.command.locks_deployment());
+
+    let start = Cli::try_parse_from(["mvre-hub", "start", "--wait", "--open"]).expect("parse");
+    let Commands::Start { opts, .. } = start.command else {
+        panic!("expected start");
+    };
+    assert!(opts.open);
+    // A browser on an inventory host would open in front of nobody.
+    assert_eq!(opts.to_args(), vec!["start", "--wait", "--wait-timeout", "5m"]);
+}
+
+#[test]
+fn workshop_changes_lock_and_list_is_read_only() {
+    let create = Cli::try_parse_from(["mvre-hub", "workshop", "create", "--count", "30", "--expires", "7d"]).expect("parse");
+    assert!(create.command.locks_deployment());
+    assert_eq!(create.command.audit_name(), Some("workshop"));
+    let teardown = Cli::try_parse_from(["mvre-hub", "workshop", "teardown", "ws", "--yes"]).expect("parse");
+    assert!(teardown.command.locks_deployment());
+
+    let list = Cli::try_parse_from(["mvre-hub", "workshop", "list"]).expect("parse");
+    assert_eq!(list.command.audit_name(), None);
+    assert!(!list.command.locks_deployment());
+    assert!(Cli::try_parse_from(["mvre-hub", "workshop", "create"]).is_err());
+}
+
+#[test]
+fn volumes_prune_locks_unless_dry_run_and_archives_need_a_destination() {
+    let prune = Cli::try_parse_from(["mvre-hub", "volumes", "prune", "--older-than", "1y", "--yes"]).expect("parse");
+    assert!(prune.command.locks_deployment());
+    assert_eq!(prune.command.audit_name(), Some("volumes"));
+
+    let dry_run = Cli::try_parse_from(["mvre-hub", "volumes", "prune", "--older-than", "1y", "--dry-run"]).expect("parse");
+    assert!(!dry_run.command.locks_deployment());
+    assert_eq!(dry_run.command.audit_name(), None);
+    let report = Cli::try_parse_from(["mvre-hub", "volumes", "report"]).expect("parse");
+    assert_eq!(report.command.audit_name(), None);
+
+    assert!(Cli::try_parse_from(["mvre-hub", "volumes", "prune", "--older-than", "1y", "--archive-first"]).is_err());
+    let archive = Cli::try_parse_from([
+        "mvre-hub", "volumes", "prune", "--older-than", "1y", "--archive-first", "--dest", "s3://archive/hub",
+    ])
+    .expect("parse");
+    assert!(archive.command.locks_deployment());
+}
+
+#[test]
+fn user_units_need_auto_start() {
+    let cli = Cli::try_parse_from(["mvre-hub", "deploy", "--user"]).expect("parse");
+    let Commands::Deploy { opts } = cli.command else {
+        panic!("expected deploy");
+    };
+    assert!(opts.user);
+    assert!(Cli::try_parse_from(["mvre-hub", "deploy", "--user", "--no-systemd"]).is_err());
+}
@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+use mvre_hub::config::{self, AppConfig, Tenant};
+
+// Sets XDG_CONFIG_HOME, so it lives apart from config_roundtrip.
+#[test]
+fn loaded_config_keeps_tenants_and_names_the_active_deployment() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::env::set_var("XDG_CONFIG_HOME", dir.path());
+    let path = config::resolve_config_path().expect("resolve config path");
+
+    let mut tenants = std::collections::BTreeMap::new();
+    tenants.insert(
+        "geo".to_string(),
+        Tenant {
+            deploy_dir: PathBuf::from("/srv/mvre-hub/geo"),
+            domain: "geo.example.org".to_string(),
+            group: "mvre-geo".to_string(),
+            project_name: "mvre-geo".to_string(),
+            http_port: 8081,
+            https_port: 8444,
+        },
+    );
+    let cfg = AppConfig {
+        last_deploy_dir: Some(PathBuf::from("/tmp/mvre")),
+        last_domain: Some("hub.example.org".to_string()),
+        deploy_dir_override: Some(PathBuf::from("/srv/other")),
+        tenants,
+        ..Default::default()
+    };
+
+    config::save(&path, &cfg).expect("save");
+    let loaded = config::load().expect("load");
+
+    assert_eq!(loaded.tenants, cfg.tenants);
+    // Configs from before named deployments get their last one registered.
+    assert_eq!(loaded.active.as_deref(), Some("mvre"));
+    assert_eq!(loaded.active_dir(), Some(Path::new("/tmp/mvre")));
+    // --deploy-dir lasts one invocation.
+    assert_eq!(loaded.deploy_dir_override, None);
+}
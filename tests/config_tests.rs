@@ -1,6 +1,9 @@
+// AppConfig has outgrown what these tests set.
+#![allow(clippy::field_reassign_with_default)]
+
 use std::path::PathBuf;
 
-use mvre_hub::config::{self, AppConfig};
+use mvre_hub::config::{self, AppConfig, MaintenanceWindow};
 
 #[test]
 fn config_roundtrip() {
@@ -18,3 +21,49 @@ fn config_roundtrip() {
     assert_eq!(loaded.last_deploy_dir, cfg.last_deploy_dir);
     assert_eq!(loaded.last_domain, cfg.last_domain);
 }
+
+#[test]
+fn deployments_get_unique_names_and_one_is_active() {
+    let mut cfg = AppConfig::default();
+    let first = cfg.register(&PathBuf::from("/srv/hubs/Polar Hub"), Some("polar.example.org"));
+    let second = cfg.register(&PathBuf::from("/opt/polar-hub"), None);
+    assert_eq!((first.as_str(), second.as_str()), ("polar-hub", "polar-hub-2"));
+    assert_eq!(cfg.register(&PathBuf::from("/opt/polar-hub"), None), "polar-hub-2");
+    assert_eq!(cfg.active_dir(), None);
+
+    cfg.activate("polar-hub").expect("activate");
+    assert_eq!(cfg.last_deploy_dir, Some(PathBuf::from("/srv/hubs/Polar Hub")));
+    assert_eq!(cfg.last_domain.as_deref(), Some("polar.example.org"));
+    assert!(cfg.activate("missing").is_err());
+
+    // Remembering another directory does not steal the active slot.
+    cfg.remember(&PathBuf::from("/opt/other"));
+    assert_eq!(cfg.active.as_deref(), Some("polar-hub"));
+
+    cfg.forget(&PathBuf::from("/srv/hubs/Polar Hub"));
+    assert_eq!(cfg.active, None);
+    assert_eq!(cfg.active_dir(), None);
+    assert_eq!(cfg.deployments.len(), 2);
+}
+
+#[test]
+fn maintenance_windows_belong_to_one_deployment() {
+    let mut cfg = AppConfig::default();
+    let geo = cfg.register(&PathBuf::from("/srv/hubs/geo"), None);
+    cfg.register(&PathBuf::from("/srv/hubs/bio"), None);
+    let window = MaintenanceWindow {
+        stop: "Sat 02:00".to_string(),
+        start: "Sat 04:00".to_string(),
+        notice: "1h".to_string(),
+    };
+    cfg.deployments.get_mut(&geo).expect("geo").maintenance = Some(window.clone());
+    assert_eq!(cfg.maintenance(&PathBuf::from("/srv/hubs/geo")), Some(&window));
+    assert_eq!(cfg.maintenance(&PathBuf::from("/srv/hubs/bio")), None);
+
+    // The old global window is read but never written back.
+    cfg.legacy_maintenance = Some(window);
+    let saved = serde_json::to_value(&cfg).expect("serialize");
+    assert!(saved.get("maintenance").is_none());
+    assert!(saved["deployments"]["geo"]["maintenance"].is_object());
+    assert!(saved["deployments"]["bio"].get("maintenance").is_none());
+}
@@ -0,0 +1,52 @@
+use std::path::{Path, PathBuf};
+
+use mvre_hub::{db::Database, envfile};
+
+fn database(env: &str) -> Database {
+    Database::from_env(Path::new("/srv/mvre-hub"), &envfile::parse(env))
+}
+
+#[test]
+fn the_database_follows_the_deployment_settings() {
+    assert_eq!(
+        database("ENABLE_POSTGRES=true\nDB_HOST=postgres\nDB_USER=mvre\nDB_NAME=mvre_hub\n"),
+        Database::Compose {
+            user: "mvre".to_string(),
+            name: "mvre_hub".to_string(),
+        }
+    );
+    assert_eq!(
+        database("ENABLE_POSTGRES=true\nDB_HOST=db.example.org\nJUPYTERHUB_DB_URL=postgresql://mvre:pw@db.example.org:5432/hub\n"),
+        Database::External {
+            url: "postgresql://mvre:pw@db.example.org:5432/hub".to_string(),
+        }
+    );
+    assert_eq!(
+        database("ENABLE_POSTGRES=false\nJUPYTERHUB_DB_URL=\n"),
+        Database::Sqlite {
+            path: PathBuf::from("/srv/mvre-hub/jupyterhub_data/jupyterhub.sqlite"),
+        }
+    );
+}
+
+#[test]
+fn compose_databases_are_reached_through_the_postgres_service() {
+    let postgres = database("ENABLE_POSTGRES=true\nDB_HOST=postgres\nDB_USER=mvre\nDB_NAME=mvre_hub\n");
+    assert_eq!(postgres.shell_command(), vec!["docker-compose", "exec", "postgres", "psql", "-U", "mvre", "mvre_hub"]);
+    assert_eq!(
+        postgres.dump_command(),
+        vec!["docker-compose", "exec", "-T", "postgres", "pg_dump", "--clean", "--if-exists", "-U", "mvre", "mvre_hub"]
+    );
+    assert!(postgres.restore_command().contains(&"ON_ERROR_STOP=1".to_string()));
+    assert_eq!(postgres.vacuum_command().last().map(String::as_str), Some("VACUUM ANALYZE"));
+}
+
+#[test]
+fn sqlite_databases_use_the_sqlite_client() {
+    let sqlite = database("");
+    assert_eq!(
+        sqlite.dump_command(),
+        vec!["sqlite3", "/srv/mvre-hub/jupyterhub_data/jupyterhub.sqlite", ".dump"]
+    );
+    assert_eq!(sqlite.restore_command()[..2], ["sqlite3", "-bail"]);
+}
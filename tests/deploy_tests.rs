@@ -0,0 +1,102 @@
+use clap::Parser;
+use mvre_hub::{
+    cli::{Cli, Commands, DeployOptions},
+    deploy,
+    envfile::EnvMap,
+};
+
+fn deploy_options(args: &[&str]) -> DeployOptions {
+    let cli = Cli::try_parse_from(["mvre-hub", "deploy"].iter().chain(args)).expect("parse");
+    let Commands::Deploy { opts } = cli.command else {
+        panic!("expected deploy");
+    };
+    opts
+}
+
+#[test]
+fn forced_redeploy_reads_the_previous_env() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::write(dir.path().join(".env"), "HUB_DOMAIN=hub.example.org\nHUB_API_TOKEN=abc\n").expect("env");
+
+    let target = Some(dir.path().to_path_buf());
+    assert!(deploy::resolve_deploy_dir(&deploy_options(&[]), target.clone(), None).is_err());
+    let (path, previous) = deploy::resolve_deploy_dir(&deploy_options(&["--force"]), target, None).expect("resolve");
+    assert_eq!(path, dir.path());
+    let previous = previous.expect("previous env");
+    assert_eq!(previous.get("HUB_DOMAIN").map(String::as_str), Some("hub.example.org"));
+    // Nothing is removed before the new inputs are confirmed.
+    assert!(dir.path().join(".env").exists());
+}
+
+#[test]
+fn forced_redeploy_keeps_generated_secrets() {
+    let mut previous = EnvMap::new();
+    previous.insert("HUB_API_TOKEN".to_string(), "abc".to_string());
+    assert_eq!(deploy::kept_or_generated(Some(&previous), "HUB_API_TOKEN", 32).expect("kept"), "abc");
+
+    let fresh = deploy::kept_or_generated(Some(&previous), "GRAFANA_ADMIN_PASSWORD", 24).expect("generated");
+    assert_eq!(fresh.len(), 48);
+    assert_ne!(fresh, deploy::kept_or_generated(None, "GRAFANA_ADMIN_PASSWORD", 24).expect("generated"));
+}
+
+#[test]
+fn review_masks_secrets() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let env: EnvMap = [
+        ("HUB_DOMAIN", "hub.example.org"),
+        ("ACME_EMAIL", "admin@example.org"),
+        ("OAUTH_CLIENT_ID", "mvre"),
+        ("OAUTH_CLIENT_SECRET", "s3cret-value"),
+        ("DATASET_HOST_PATH", "/data/mosaic"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+
+    let rows = deploy::review_summary(dir.path(), &env).expect("summary");
+    let value = |label: &str| {
+        rows.iter()
+            .find(|(row, _)| row == label)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_else(|| panic!("no {} row", label))
+    };
+    assert_eq!(value("Deployment directory"), dir.path().to_string_lossy());
+    assert_eq!(value("Domain"), "hub.example.org");
+    assert_eq!(value("OAuth client secret"), "********");
+    assert_eq!(value("Shared path"), "(none)");
+    assert!(rows.iter().all(|(_, value)| !value.contains("s3cret")));
+}
+
+#[test]
+fn review_shows_required_entitlements_with_their_claim() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let mut env: EnvMap = [
+        ("HUB_DOMAIN", "hub.example.org"),
+        ("ACME_EMAIL", "admin@example.org"),
+        ("OAUTH_CLIENT_ID", "mvre"),
+        ("OAUTH_CLIENT_SECRET", "secret"),
+        ("DATASET_HOST_PATH", "/data/mosaic"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    let entitlements = |env: &EnvMap| {
+        deploy::review_summary(dir.path(), env)
+            .expect("summary")
+            .into_iter()
+            .find(|(label, _)| label == "Required entitlements")
+            .map(|(_, value)| value)
+    };
+    assert_eq!(entitlements(&env).as_deref(), Some("(none)"));
+
+    env.insert("REQUIRED_ENTITLEMENTS".to_string(), "urn:geant:helmholtz.de:group:MOSAiC".to_string());
+    assert_eq!(
+        entitlements(&env).as_deref(),
+        Some("urn:geant:helmholtz.de:group:MOSAiC (claim eduperson_entitlement)")
+    );
+    env.insert("ENTITLEMENT_CLAIM".to_string(), "groups".to_string());
+    assert_eq!(
+        entitlements(&env).as_deref(),
+        Some("urn:geant:helmholtz.de:group:MOSAiC (claim groups)")
+    );
+}
@@ -0,0 +1,36 @@
+use mvre_hub::{disk, engine::DiskUsage};
+
+#[test]
+fn engine_entries_keep_only_this_deployment() {
+    let usage: DiskUsage = serde_json::from_str(
+        r#"{
+            "Images": [
+                {"Repository": "mvre-jupyterhub", "Tag": "latest", "Size": "1.2GB"},
+                {"Repository": "traefik", "Tag": "v2.9", "Size": "130MB"},
+                {"Repository": "unrelated", "Tag": "1.0", "Size": "5GB"}
+            ],
+            "Volumes": [
+                {"Name": "jupyterhub-user-alice", "Size": "3.4GB"},
+                {"Name": "mvre_postgres_data", "Size": "80MB"},
+                {"Name": "other_postgres_data", "Size": "1GB"}
+            ]
+        }"#,
+    )
+    .expect("parse");
+    let images = vec!["mvre-jupyterhub".to_string(), "traefik:v2.9".to_string()];
+
+    let entries = disk::engine_entries(&usage, "mvre", "jupyterhub-user-", &images);
+    let names: Vec<(&str, &str, u64)> = entries
+        .iter()
+        .map(|entry| (entry.kind, entry.name.as_str(), entry.bytes))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("volume", "jupyterhub-user-alice", 3_400_000_000),
+            ("postgres", "mvre_postgres_data", 80_000_000),
+            ("image", "mvre-jupyterhub:latest", 1_200_000_000),
+            ("image", "traefik:v2.9", 130_000_000),
+        ]
+    );
+}
@@ -0,0 +1,61 @@
+use std::process::Command;
+
+use mvre_hub::{
+    engine::{self, ContainerState, Remote},
+    envfile,
+};
+
+#[test]
+fn ps_output_parses_array_and_ndjson() {
+    let array = r#"[{"Name":"mvre-hub-jupyterhub-1","Service":"jupyterhub","State":"running","Health":"","ExitCode":0}]"#;
+    let ndjson = "{\"Name\":\"a\",\"Service\":\"traefik\",\"State\":\"running\"}\n{\"Name\":\"b\",\"Service\":\"postgres\",\"State\":\"exited\",\"ExitCode\":1}\n";
+
+    let from_array: Vec<ContainerState> = engine::parse_json_records(array).expect("array");
+    assert_eq!(from_array.len(), 1);
+    assert_eq!(from_array[0].service, "jupyterhub");
+
+    let from_lines: Vec<ContainerState> = engine::parse_json_records(ndjson).expect("ndjson");
+    assert_eq!(from_lines.len(), 2);
+    assert_eq!(from_lines[1].exit_code, 1);
+
+    let empty: Vec<ContainerState> = engine::parse_json_records("  \n").expect("empty");
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn stats_rows_parse_docker_field_names() {
+    let raw = "{\"Name\":\"jupyter-alice\",\"CPUPerc\":\"12.50%\",\"MemUsage\":\"1.2GiB / 4GiB\",\"MemPerc\":\"30.00%\",\"NetIO\":\"1kB / 2kB\"}\n";
+    let rows: Vec<engine::ContainerStats> = engine::parse_json_records(raw).expect("stats");
+    assert_eq!(rows[0].name, "jupyter-alice");
+    assert_eq!(rows[0].cpu_percent, "12.50%");
+    assert_eq!(rows[0].mem_usage, "1.2GiB / 4GiB");
+}
+
+#[test]
+fn remote_daemon_settings_reach_docker_calls() {
+    let env = envfile::parse("REMOTE_DOCKER_HOST=tcp://hub.example.org:2376\nREMOTE_DOCKER_CERT_PATH=/etc/mvre/certs\n");
+    let remote = Remote::from_env(&env);
+    assert!(remote.is_set());
+    assert_eq!(remote.describe().as_deref(), Some("tcp://hub.example.org:2376"));
+
+    let mut command = Command::new("docker-compose");
+    remote.apply(&mut command);
+    let envs: Vec<(String, Option<String>)> = command
+        .get_envs()
+        .map(|(key, value)| {
+            (
+                key.to_string_lossy().to_string(),
+                value.map(|value| value.to_string_lossy().to_string()),
+            )
+        })
+        .collect();
+    assert!(envs.contains(&("DOCKER_HOST".to_string(), Some("tcp://hub.example.org:2376".to_string()))));
+    assert!(envs.contains(&("DOCKER_TLS_VERIFY".to_string(), Some("1".to_string()))));
+    assert!(envs.contains(&("DOCKER_CONTEXT".to_string(), None)));
+
+    let local = Remote::from_env(&envfile::parse("REMOTE_DOCKER_HOST=\n"));
+    assert!(!local.is_set());
+    let mut command = Command::new("docker");
+    local.apply(&mut command);
+    assert_eq!(command.get_envs().count(), 0);
+}
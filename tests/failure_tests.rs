@@ -0,0 +1,63 @@
+use anyhow::Context;
+use mvre_hub::failure::{self, Category};
+
+#[test]
+fn tags_survive_added_context() {
+    let err = Err::<(), _>(failure::validation("--log-max-file must be at least 1"))
+        .context("failed to collect deploy inputs")
+        .unwrap_err();
+    assert_eq!(failure::classify(&err), Category::Validation);
+    assert_eq!(failure::classify(&err).exit_code(), 2);
+}
+
+#[test]
+fn unreadable_docker_socket_is_a_permission_failure() {
+    let err = failure::docker(
+        "docker failed: permission denied while trying to connect to the Docker daemon socket at unix:///var/run/docker.sock",
+    );
+    assert_eq!(failure::classify(&err), Category::Permission);
+    assert_eq!(failure::classify(&failure::docker("docker-compose exited with status 1")), Category::Docker);
+}
+
+#[test]
+fn io_errors_are_classified_by_kind() {
+    let denied = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied)).context("failed to write .env");
+    assert_eq!(failure::classify(&denied), Category::Permission);
+    let refused = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+    assert_eq!(failure::classify(&refused), Category::Network);
+    assert_eq!(failure::classify(&anyhow::anyhow!("something else")), Category::Other);
+}
+
+#[test]
+fn categories_have_distinct_exit_codes() {
+    let codes: std::collections::BTreeSet<u8> = [
+        Category::Other,
+        Category::Validation,
+        Category::Docker,
+        Category::Network,
+        Category::Permission,
+        Category::Busy,
+        Category::Config,
+    ]
+    .iter()
+    .map(|category| category.exit_code())
+    .collect();
+    assert_eq!(codes.len(), 7);
+}
+
+#[test]
+fn config_and_permission_failures_have_their_own_codes() {
+    let missing = failure::config("Deployment not found. Run 'mvre-hub deploy' first.");
+    assert_eq!(failure::classify(&missing), Category::Config);
+    assert_eq!(mvre_hub::exit_code(&missing), 7);
+    let root = failure::permission("Root required to remove system timers");
+    assert_eq!(mvre_hub::exit_code(&root.context("failed to remove job")), 5);
+}
+
+#[test]
+fn unparsable_compose_file_is_a_config_failure() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::write(dir.path().join("docker-compose.yml"), "services: [unclosed").unwrap();
+    let err = mvre_hub::compose::load(dir.path()).unwrap_err();
+    assert_eq!(failure::classify(&err), Category::Config);
+}
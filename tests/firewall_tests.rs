@@ -0,0 +1,53 @@
+use mvre_hub::firewall::{self, FirewallRules};
+
+fn rules() -> FirewallRules {
+    FirewallRules {
+        interface: "eth0".to_string(),
+        ssh_port: 22,
+        web_ports: vec![80, 443],
+    }
+}
+
+#[test]
+fn docker_forwarded_ports_are_limited_to_web_ports() {
+    let block = firewall::docker_user_block(&rules());
+    assert!(block.starts_with("# BEGIN mvre-hub\n*filter\n:DOCKER-USER - [0:0]\n"));
+    assert!(block.contains("-A DOCKER-USER -i eth0 -p tcp -m conntrack --ctorigdstport 443 -j RETURN\n"));
+    assert!(block.contains("-A DOCKER-USER -i eth0 -j DROP\n"));
+    assert!(block.ends_with("COMMIT\n# END mvre-hub\n"));
+
+    let nft = firewall::nftables_ruleset(&rules());
+    assert!(nft.contains("tcp dport { 22, 80, 443 } accept"));
+    assert!(nft.contains("iifname \"eth0\" ct status dnat ct original proto-dst != { 80, 443 } drop"));
+}
+
+#[test]
+fn ufw_opens_ssh_and_web_ports_only() {
+    let commands: Vec<String> = firewall::ufw_commands(&rules())
+        .into_iter()
+        .map(|command| command.join(" "))
+        .collect();
+    assert!(commands.contains(&"ufw default deny incoming".to_string()));
+    let allowed: Vec<&String> = commands.iter().filter(|command| command.starts_with("ufw allow")).collect();
+    assert_eq!(allowed, ["ufw allow 22/tcp", "ufw allow 80/tcp", "ufw allow 443/tcp"]);
+}
+
+#[test]
+fn managed_block_is_replaced_in_place() {
+    let block = firewall::docker_user_block(&rules());
+    let original = "*nat\nCOMMIT\n";
+    let once = firewall::replace_block(original, &block);
+    assert_eq!(once, format!("{}{}", original, block));
+    let mut narrowed = rules();
+    narrowed.web_ports = vec![443];
+    let twice = firewall::replace_block(&once, &firewall::docker_user_block(&narrowed));
+    assert_eq!(twice.matches("# BEGIN mvre-hub").count(), 1);
+    assert!(!twice.contains("--ctorigdstport 80 "));
+}
+
+#[test]
+fn default_interface_comes_from_the_default_route() {
+    let output = "default via 10.0.0.1 dev ens3 proto dhcp src 10.0.0.5 metric 100\n";
+    assert_eq!(firewall::parse_default_interface(output).as_deref(), Some("ens3"));
+    assert_eq!(firewall::parse_default_interface(""), None);
+}
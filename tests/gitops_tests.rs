@@ -0,0 +1,23 @@
+use std::path::Path;
+
+use mvre_hub::gitops;
+
+#[test]
+fn clones_are_named_after_the_repository() {
+    let https = gitops::checkout_name("https://git.example.org/hub/config.git");
+    let ssh = gitops::checkout_name("git@git.example.org:hub/config.git");
+    assert!(https.starts_with("config-"));
+    assert!(ssh.starts_with("config-"));
+    assert_ne!(https, ssh);
+    assert_eq!(https, gitops::checkout_name("https://git.example.org/hub/config.git"));
+    assert!(gitops::checkout_name("/srv/git/hub config/").starts_with("hub-config-"));
+}
+
+#[test]
+fn manifest_path_stays_inside_the_repository() {
+    assert!(gitops::validate_manifest_path(Path::new("mvre-hub.toml")).is_ok());
+    assert!(gitops::validate_manifest_path(Path::new("hubs/geo/mvre-hub.toml")).is_ok());
+    assert!(gitops::validate_manifest_path(Path::new("../mvre-hub.toml")).is_err());
+    assert!(gitops::validate_manifest_path(Path::new("/etc/mvre-hub.toml")).is_err());
+    assert!(gitops::validate_manifest_path(Path::new("")).is_err());
+}
@@ -0,0 +1,103 @@
+use mvre_hub::{
+    cli::{GraphFormat, ProxyVersion, TlsMode},
+    graph,
+    templates::{self, ComposeValues},
+};
+
+#[test]
+fn graph_includes_services_volumes_and_spawner() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let compose = templates::docker_compose(&ComposeValues {
+        domain: "hub.example.org",
+        acme_email: "admin@example.org",
+        acme_staging: false,
+        tls: TlsMode::Acme,
+        production: true,
+        publishing: false,
+        http_port: 8080,
+        https_port: 8443,
+        proxy_version: ProxyVersion::V2,
+        security_headers: false,
+        csp: None,
+        rate_limit: None,
+        internal_tls: false,
+        selinux: false,
+        sandbox_profiles: false,
+        workspaces: false,
+        scratch: false,
+        branding: false,
+        landing: None,
+        dask_gateway: false,
+        mlflow: false,
+        file_browser: false,
+        logging_stack: false,
+        logging: None,
+        extra_services: &[],
+    });
+    std::fs::write(dir.path().join("docker-compose.yml"), compose).expect("write compose");
+    std::fs::write(
+        dir.path().join(".env"),
+        "USER_IMAGE=mvre-user:latest\nDATASET_HOST_PATH=/data/mosaic\n",
+    )
+    .expect("write env");
+
+    let dot = graph::render(dir.path(), GraphFormat::Dot).expect("render dot");
+    assert!(dot.contains("svc_jupyterhub -> svc_postgres [label=\"depends_on\"]"));
+    assert!(dot.contains("vol_postgres_data"));
+    assert!(dot.contains("host__data_mosaic -> spawner"));
+    assert!(dot.contains("spawner -> net_users"));
+    assert!(dot.contains("svc_postgres -> net_hub"));
+    assert!(!dot.contains("svc_postgres -> net_users"));
+    assert!(dot.contains("-> svc_traefik [label=\"/var/run/docker.sock (ro)\"]"));
+
+    let mermaid = graph::render(dir.path(), GraphFormat::Mermaid).expect("render mermaid");
+    assert!(mermaid.starts_with("flowchart LR"));
+    assert!(mermaid.contains("svc_traefik"));
+}
+
+#[test]
+fn spawner_mounts_follow_the_deployment_env() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let compose = templates::docker_compose(&ComposeValues {
+        domain: "hub.example.org",
+        acme_email: "admin@example.org",
+        acme_staging: false,
+        tls: TlsMode::Acme,
+        production: true,
+        publishing: false,
+        http_port: 8080,
+        https_port: 8443,
+        proxy_version: ProxyVersion::V2,
+        security_headers: false,
+        csp: None,
+        rate_limit: None,
+        internal_tls: false,
+        selinux: false,
+        sandbox_profiles: false,
+        workspaces: true,
+        scratch: true,
+        branding: false,
+        landing: None,
+        dask_gateway: false,
+        mlflow: false,
+        file_browser: false,
+        logging_stack: false,
+        logging: None,
+        extra_services: &[],
+    });
+    std::fs::write(dir.path().join("docker-compose.yml"), compose).expect("write compose");
+    std::fs::write(
+        dir.path().join(".env"),
+        "WORKSPACE_HOST_PATH=/tank/work\nSCRATCH_ROOT=/scratch\nENABLE_NBGRADER=true\n\
+NBGRADER_HOST_PATH=/srv/course\nGROUP_SHARES=lab=/data/lab\n",
+    )
+    .expect("write env");
+
+    let dot = graph::render(dir.path(), GraphFormat::Dot).expect("render dot");
+    assert!(dot.contains("host__tank_work__username_ -> spawner [label=\"/home/jovyan/work\"]"));
+    assert!(!dot.contains("vol_jupyterhub_user"));
+    assert!(dot.contains("host__scratch__username_ -> spawner [label=\"/home/jovyan/scratch\"]"));
+    assert!(dot.contains("host__srv_course_exchange -> spawner"));
+    assert!(dot.contains("[label=\"/etc/jupyter/nbgrader_config.py (ro)\"]"));
+    assert!(dot.contains("host__data_lab -> spawner [label=\"/home/jovyan/groups/lab\"]"));
+}
@@ -0,0 +1,15 @@
+use mvre_hub::{envfile::EnvMap, guest, workspace};
+
+#[test]
+fn expiry_timers_are_named_per_deployment() {
+    assert_eq!(guest::prune_timer("mvre-geo"), "mvre-hub-guest-prune-mvre-geo");
+    assert_ne!(guest::prune_timer("mvre-geo"), guest::prune_timer("mvre-bio"));
+}
+
+#[test]
+fn guest_volumes_use_the_escaped_name() {
+    let mut env = EnvMap::new();
+    assert_eq!(workspace::user_volume(&env, "guest.one"), "jupyterhub-user-guest-2Eone");
+    env.insert("RESOURCE_PREFIX".to_string(), "mvre-geo-".to_string());
+    assert_eq!(workspace::user_volume(&env, "guest.one"), "mvre-geo-jupyterhub-user-guest-2Eone");
+}
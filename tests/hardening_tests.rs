@@ -0,0 +1,65 @@
+use mvre_hub::{
+    hardening,
+    templates::{self, JailValues},
+};
+
+#[test]
+fn deploy_writes_filter_and_file_based_jail() {
+    let files = hardening::fail2ban_files(std::path::Path::new("/opt/mvre-hub"));
+    let paths: Vec<String> = files.iter().map(|(path, _)| path.display().to_string()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/opt/mvre-hub/fail2ban/filter.d/mvre-hub.conf",
+            "/opt/mvre-hub/fail2ban/jail.d/mvre-hub.local",
+        ]
+    );
+    assert!(files[0].1.contains("oauth_callback|login"));
+    assert!(files[1].1.contains("logpath = /opt/mvre-hub/jupyterhub_data/jupyterhub.log\n"));
+    assert!(files[1].1.contains("chain=DOCKER-USER"));
+}
+
+#[test]
+fn journald_jail_matches_the_hub_container() {
+    let jail = templates::fail2ban_jail(&JailValues {
+        log_path: None,
+        container_name: "mvre-hub-jupyterhub-1",
+        max_retry: 3,
+        find_time: "5m",
+        ban_time: "1d",
+    });
+    assert!(jail.contains("backend = systemd\njournalmatch = CONTAINER_NAME=mvre-hub-jupyterhub-1\n"));
+    assert!(jail.contains("maxretry = 3\nfindtime = 5m\nbantime = 1d\n"));
+    assert!(!jail.contains("logpath"));
+}
+
+#[test]
+fn seccomp_profile_denies_namespace_and_tracing_syscalls() {
+    let profile: serde_json::Value = serde_json::from_str(&templates::seccomp_profile()).expect("valid json");
+    assert_eq!(profile["defaultAction"], "SCMP_ACT_ALLOW");
+    let denied: Vec<&str> = profile["syscalls"][0]["names"]
+        .as_array()
+        .expect("names")
+        .iter()
+        .filter_map(|name| name.as_str())
+        .collect();
+    for syscall in ["ptrace", "unshare", "setns", "mount", "bpf", "keyctl"] {
+        assert!(denied.contains(&syscall), "{} not denied", syscall);
+    }
+    assert_eq!(profile["syscalls"][1]["names"][0], "clone");
+    assert_eq!(profile["syscalls"][1]["args"][0]["op"], "SCMP_CMP_MASKED_EQ");
+}
+
+#[test]
+fn apparmor_profile_is_only_written_on_request() {
+    let dir = std::path::Path::new("/srv/mvre-hub");
+    let names = |apparmor| -> Vec<String> {
+        hardening::sandbox_files(dir, apparmor)
+            .into_iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    };
+    assert_eq!(names(false), vec!["seccomp.json"]);
+    assert_eq!(names(true), vec!["seccomp.json", "apparmor.profile"]);
+    assert!(templates::apparmor_profile().contains(&format!("profile {} ", templates::APPARMOR_PROFILE)));
+}
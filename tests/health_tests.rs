@@ -0,0 +1,73 @@
+use mvre_hub::{
+    cli::HealthOptions,
+    compose,
+    engine::ContainerState,
+    envfile::EnvMap,
+    health::{self, Outcome, Readiness},
+};
+
+fn hub(state: &str, health: &str) -> ContainerState {
+    ContainerState {
+        name: "mvre-hub-jupyterhub-1".to_string(),
+        service: "jupyterhub".to_string(),
+        state: state.to_string(),
+        health: health.to_string(),
+        status: String::new(),
+        exit_code: 0,
+    }
+}
+
+#[test]
+fn hub_container_must_run_before_it_is_probed() {
+    assert!(matches!(health::container_readiness(None), Some(Readiness::Starting(_))));
+    assert_eq!(
+        health::container_readiness(Some(&hub("restarting", ""))),
+        Some(Readiness::Starting("hub container restarting".to_string()))
+    );
+    assert!(matches!(
+        health::container_readiness(Some(&hub("running", "unhealthy"))),
+        Some(Readiness::Starting(_))
+    ));
+    assert_eq!(health::container_readiness(Some(&hub("running", "starting"))), None);
+    assert_eq!(health::container_readiness(Some(&hub("running", ""))), None);
+}
+
+#[test]
+fn published_ports_come_from_short_and_long_syntax() {
+    let file = compose::parse(
+        r#"
+services:
+  traefik:
+    ports:
+      - "8080:80"
+      - "127.0.0.1:8443:443/tcp"
+      - target: 8081
+        published: "9081"
+      - target: 8082
+        published: 9082
+      - "9000"
+"#,
+    )
+    .expect("parse");
+    let traefik = &file.services["traefik"];
+    assert_eq!(traefik.published_port(80), Some(8080));
+    assert_eq!(traefik.published_port(443), Some(8443));
+    assert_eq!(traefik.published_port(8081), Some(9081));
+    assert_eq!(traefik.published_port(8082), Some(9082));
+    assert_eq!(traefik.published_port(9000), None);
+    assert_eq!(traefik.published_port(22), None);
+}
+
+#[test]
+fn hub_probe_needs_a_domain_and_reports_as_json() {
+    let opts = HealthOptions {
+        insecure: false,
+        max_restarts: 3,
+    };
+    let outcome = health::probe_hub(std::path::Path::new("/nonexistent"), &EnvMap::new(), &opts);
+    assert!(matches!(&outcome, Outcome::Fail(detail) if detail.contains("HUB_DOMAIN")));
+    assert_eq!(
+        serde_json::to_value(&outcome).expect("serialize"),
+        serde_json::json!({ "outcome": "fail", "detail": "HUB_DOMAIN missing from .env" })
+    );
+}
@@ -0,0 +1,35 @@
+use mvre_hub::http::{self, Options};
+
+#[test]
+fn curl_config_quotes_every_value() {
+    let opts = Options {
+        timeout_secs: 5,
+        headers: vec!["Authorization: token a\"b\\c".to_string()],
+        resolve: Some("hub.example.org:443:127.0.0.1".to_string()),
+        ..Options::default()
+    };
+    let config = http::curl_config(
+        "POST",
+        "https://hub.example.org/hub/api",
+        Some(("application/json", "{\"note\":\"line\none\ttab\"}\r")),
+        &opts,
+    );
+    assert_eq!(
+        config,
+        concat!(
+            "url = \"https://hub.example.org/hub/api\"\n",
+            "request = \"POST\"\n",
+            "max-time = 5\n",
+            "header = \"Authorization: token a\\\"b\\\\c\"\n",
+            "resolve = \"hub.example.org:443:127.0.0.1\"\n",
+            "header = \"Content-Type: application/json\"\n",
+            "data-binary = \"{\\\"note\\\":\\\"line\\none\\ttab\\\"}\\r\"\n",
+        )
+    );
+}
+
+#[test]
+fn a_newline_cannot_inject_curl_options() {
+    let config = http::curl_config("GET", "https://hub/\noutput = /etc/passwd", None, &Options::default());
+    assert_eq!(config.lines().filter(|line| line.starts_with("output")).count(), 0);
+}
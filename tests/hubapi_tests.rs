@@ -0,0 +1,95 @@
+use mvre_hub::{
+    hubapi::{self, HubClient, ProxyRoute, TokenRequest, User},
+    server, users, verify,
+};
+
+fn user(raw: &str) -> User {
+    serde_json::from_str(raw).expect("user")
+}
+
+#[test]
+fn user_names_are_escaped_in_paths() {
+    assert_eq!(hubapi::user_path("alice", "/server"), "/users/alice/server");
+    assert_eq!(hubapi::user_path("a.b@awi.de", ""), "/users/a.b%40awi.de");
+    assert_eq!(hubapi::group_path("sea ice", "/users"), "/groups/sea%20ice/users");
+}
+
+#[test]
+fn client_debug_output_hides_the_token() {
+    let client = HubClient::new("https://hub.example.org/hub/api/", "s3cret");
+    assert_eq!(client.base_url(), "https://hub.example.org/hub/api");
+    assert!(!format!("{:?}", client).contains("s3cret"));
+}
+
+#[test]
+fn users_parse_with_servers_and_optional_fields() {
+    let alice = user(
+        r#"{"kind":"user","name":"alice","admin":true,"groups":["pi"],"last_activity":"2026-10-12T08:15:00.123456Z",
+            "pending":null,"servers":{"":{"name":"","ready":true,"pending":null,"url":"/user/alice/",
+            "started":"2026-10-12T08:00:00Z","state":{"object_name":"jupyter-alice"}}}}"#,
+    );
+    assert_eq!(alice.groups, vec!["pi"]);
+    assert_eq!(alice.servers[""].container_name(), Some("jupyter-alice"));
+    assert_eq!(users::server_state(&alice), "running");
+    assert_eq!(hubapi::display_time(alice.last_activity.as_deref()), "2026-10-12T08:15:00Z");
+
+    let bob = user(r#"{"name":"bob","servers":{"":{"ready":false,"pending":"spawn"}}}"#);
+    assert_eq!(users::server_state(&bob), "spawning");
+    assert_eq!(users::server_state(&user(r#"{"name":"carol"}"#)), "stopped");
+    assert_eq!(hubapi::display_time(None), "-");
+}
+
+#[test]
+fn token_requests_omit_unset_fields() {
+    let request = TokenRequest {
+        note: "ci".to_string(),
+        ..TokenRequest::default()
+    };
+    assert_eq!(serde_json::to_value(&request).expect("json"), serde_json::json!({ "note": "ci" }));
+
+    let request = TokenRequest {
+        expires_in: Some(3600),
+        note: "ci".to_string(),
+        scopes: vec!["read:users".to_string()],
+    };
+    assert_eq!(
+        serde_json::to_value(&request).expect("json"),
+        serde_json::json!({ "expires_in": 3600, "note": "ci", "scopes": ["read:users"] })
+    );
+}
+
+#[test]
+fn ready_servers_without_routes_are_reported() {
+    let users = vec![
+        user(r#"{"name":"alice","servers":{"":{"ready":true,"url":"/user/alice/"}}}"#),
+        user(r#"{"name":"bob","servers":{"":{"ready":true,"url":"/user/bob/"}}}"#),
+        user(r#"{"name":"carol","servers":{"":{"ready":false,"pending":"spawn","url":"/user/carol/"}}}"#),
+    ];
+    let routes: Vec<ProxyRoute> = vec![
+        serde_json::from_str(r#"{"routespec":"/","target":"http://jupyterhub:8081"}"#).expect("route"),
+        serde_json::from_str(r#"{"routespec":"hub.example.org/user/alice/","target":"http://10.0.0.5:8888"}"#).expect("route"),
+    ];
+    assert_eq!(verify::running_servers(&users).len(), 2);
+    assert_eq!(verify::missing_routes(&users, &routes), vec!["/user/bob/"]);
+}
+
+#[test]
+fn named_servers_have_their_own_path() {
+    assert_eq!(hubapi::server_path("alice", ""), "/users/alice/server");
+    assert_eq!(hubapi::server_path("alice", "gpu run"), "/users/alice/servers/gpu%20run");
+
+    let alice = user(r#"{"name":"alice","servers":{"gpu":{"name":"gpu","ready":false,"pending":"spawn"}}}"#);
+    assert!(server::is_active(&alice, "gpu"));
+    assert!(!server::is_ready(&alice, "gpu"));
+    assert!(!server::is_active(&alice, ""));
+}
+
+#[test]
+fn active_servers_include_named_and_pending_ones() {
+    let users = vec![
+        user(r#"{"name":"alice","servers":{"":{"ready":true},"gpu":{"ready":false,"pending":"stop"}}}"#),
+        user(r#"{"name":"bob","servers":{}}"#),
+        user(r#"{"name":"carol","servers":{"":{"ready":false,"pending":"spawn"}}}"#),
+    ];
+    assert_eq!(server::active_servers(&users), vec!["alice", "alice/gpu", "carol"]);
+}
@@ -0,0 +1,56 @@
+use mvre_hub::{
+    cli::{AuthPreset, IdpPreset},
+    envfile,
+    idp::{self, ExtraIdp},
+};
+
+#[test]
+fn extra_providers_roundtrip_through_env() {
+    let env = envfile::parse("EXTRA_IDPS=orcid,unknown\nORCID_CLIENT_ID=APP-1\nORCID_CLIENT_SECRET=s3\n");
+    let extra = idp::from_env(&env);
+    assert_eq!(extra.len(), 1);
+    assert_eq!(extra[0].preset, IdpPreset::Orcid);
+    assert_eq!(extra[0].client_id, "APP-1");
+    assert_eq!(extra[0].client_secret, "s3");
+}
+
+#[test]
+fn callback_urls_move_under_routes_with_several_providers() {
+    assert_eq!(
+        idp::callback_urls("hub.example.org", AuthPreset::Helmholtz, &[]),
+        vec![("Helmholtz AAI".to_string(), "https://hub.example.org/hub/oauth_callback".to_string())]
+    );
+
+    let orcid = ExtraIdp {
+        preset: IdpPreset::Orcid,
+        client_id: "APP-1".to_string(),
+        client_secret: "s3".to_string(),
+    };
+    let urls: Vec<String> = idp::callback_urls("hub.example.org", AuthPreset::Helmholtz, &[orcid])
+        .into_iter()
+        .map(|(_, url)| url)
+        .collect();
+    assert_eq!(
+        urls,
+        vec![
+            "https://hub.example.org/hub/helmholtz/oauth_callback",
+            "https://hub.example.org/hub/orcid/oauth_callback",
+        ]
+    );
+}
+
+#[test]
+fn presets_name_the_primary_provider() {
+    assert_eq!(AuthPreset::from_name("cilogon"), Some(AuthPreset::Cilogon));
+    assert_eq!(AuthPreset::from_name("unknown"), None);
+    assert!(!AuthPreset::Helmholtz.has_endpoints());
+
+    let orcid = ExtraIdp {
+        preset: IdpPreset::Orcid,
+        client_id: "APP-1".to_string(),
+        client_secret: "s3".to_string(),
+    };
+    let urls = idp::callback_urls("hub.example.org", AuthPreset::Cilogon, &[orcid]);
+    assert_eq!(urls[0].0, "CILogon");
+    assert_eq!(urls[0].1, "https://hub.example.org/hub/cilogon/oauth_callback");
+}
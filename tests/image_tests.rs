@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use mvre_hub::{
+    cli::{BuildService, ImageOptions},
+    envfile, image,
+};
+
+fn options(registry: &str, service: Option<BuildService>) -> ImageOptions {
+    ImageOptions {
+        registry: registry.to_string(),
+        tag: "2024.6".to_string(),
+        service,
+        username: None,
+    }
+}
+
+#[test]
+fn registry_names_do_not_depend_on_the_project() {
+    let env = envfile::parse("COMPOSE_PROJECT_NAME=geo\nUSER_IMAGE=mvre-user:custom\n");
+    let images = image::refs(Path::new("/srv/geo"), &env, &options("registry.example.org/mvre/", None)).expect("refs");
+
+    let pairs: Vec<(&str, &str, &str)> = images
+        .iter()
+        .map(|image| (image.service, image.local.as_str(), image.remote.as_str()))
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("jupyterhub", "geo-jupyterhub", "registry.example.org/mvre/mvre-hub:2024.6"),
+            ("user-image", "mvre-user:custom", "registry.example.org/mvre/mvre-user:2024.6"),
+        ]
+    );
+}
+
+#[test]
+fn one_image_can_be_selected_and_urls_are_rejected() {
+    let env = envfile::parse("");
+    let images = image::refs(Path::new("/srv/hub"), &env, &options("ghcr.io/org", Some(BuildService::User))).expect("refs");
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].local, "mvre-user:latest");
+
+    assert!(image::refs(Path::new("/srv/hub"), &env, &options("https://ghcr.io/org", None)).is_err());
+}
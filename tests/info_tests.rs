@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use mvre_hub::{compose, envfile, info};
+
+const ENV: &str = "HUB_DOMAIN=hub.example.org\nTLS_MODE=acme\nACME_STAGING=true\nOAUTH_PRESET=helmholtz\n\
+OAUTH_CLIENT_SECRET=hunter2\nADMIN_USERS=alice,bob\nALLOWED_USERS=carol\nUSER_IMAGE=mvre-user:latest\n\
+DATASET_HOST_PATH=/srv/data\nDATASET_MOUNT_PATH=/data\nWORKSPACE_BACKEND=zfs\nWORKSPACE_ROOT=tank/home\n\
+ENABLE_POSTGRES=true\nDB_HOST=postgres\nDB_PORT=5432\nDB_NAME=jupyterhub\nDB_PASSWORD=secret\n\
+ENABLE_MLFLOW=true\nENABLE_DASK_GATEWAY=false\nINTERNAL_TLS=true\nCULL_TIMEOUT=3600\n\
+GROUP_MEMBERS=sea-ice=alice:bob\nGROUP_SHARES=sea-ice=/srv/groups/sea-ice\nEXTRA_SERVICES=docs\nCPU_LIMIT=2\nMEM_LIMIT=4G\nGROUP_LIMITS=power-users=8:32G\n";
+
+const COMPOSE: &str = "services:\n  traefik:\n    image: traefik:v2.11\n  jupyterhub:\n    build: ./jupyterhub\n    image: mvre-hub:latest\n";
+
+#[test]
+fn overview_comes_from_env_and_compose_file() {
+    let env = envfile::parse(ENV);
+    let file = compose::parse(COMPOSE).expect("compose");
+    let info = info::gather(Path::new("/srv/mvre-hub"), &env, Some(&file));
+
+    assert_eq!(info.project, "mvre-hub");
+    assert_eq!(info.hub_url.as_deref(), Some("https://hub.example.org"));
+    assert!(info.acme_staging);
+    assert_eq!(info.auth.provider.as_deref(), Some("helmholtz"));
+    assert_eq!(info.auth.admin_users, vec!["alice", "bob"]);
+    assert_eq!(info.auth.allowed_users, vec!["carol"]);
+    assert_eq!(info.images["traefik"], "traefik:v2.11");
+    assert_eq!(info.images["jupyterhub"], "mvre-hub:latest (built)");
+    assert_eq!(info.services, vec!["postgres", "mlflow", "docs (extra)"]);
+
+    let storage: Vec<(&str, &str)> = info.storage.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    assert!(storage.contains(&("Dataset", "/srv/data -> /data")));
+    assert!(storage.contains(&("Workspaces", "zfs at tank/home")));
+    assert!(storage.contains(&("Database", "postgres:5432/jupyterhub")));
+    assert!(storage.contains(&("Group sea-ice", "/srv/groups/sea-ice -> /home/jovyan/groups/sea-ice")));
+    let production: Vec<(&str, &str)> = info.production.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    assert!(production.contains(&("Internal TLS", "on")));
+    assert!(production.contains(&("Idle culling", "after 3600s")));
+    assert!(production.contains(&("Resource limits", "2 CPU, 4G memory (1 overrides)")));
+    assert_eq!(info.compose_override, None);
+}
+
+#[test]
+fn operator_override_is_listed_and_layered_on_compose_calls() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let deploy = dir.path();
+    assert!(compose::file_args(deploy, &["up", "-d"]).is_empty());
+
+    std::fs::write(
+        deploy.join(compose::OVERRIDE_FILE),
+        "services:\n  jupyterhub:\n    environment:\n      SITE: x\n  traefik:\n    dns: [10.0.0.1]\n",
+    )
+    .unwrap();
+    assert_eq!(
+        compose::file_args(deploy, &["up", "-d"]),
+        vec!["-f", "docker-compose.yml", "-f", "docker-compose.override.yml"]
+    );
+    assert!(compose::file_args(deploy, &["-p", "green", "-f", "docker-compose.green.yml", "up"]).is_empty());
+
+    let info = info::gather(deploy, &envfile::parse(ENV), None);
+    assert_eq!(info.compose_override, Some(vec!["jupyterhub".to_string(), "traefik".to_string()]));
+}
+
+#[test]
+fn overview_never_includes_secrets() {
+    let info = info::gather(Path::new("/srv/mvre-hub"), &envfile::parse(ENV), None);
+    let json = serde_json::to_string(&info).expect("json");
+    assert!(!json.contains("hunter2"));
+    assert!(!json.contains("secret"));
+    assert!(info.images.is_empty());
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use mvre_hub::{cli::InitKind, init, launchd, openrc, runit, taskscheduler};
+
+#[test]
+fn user_units_are_systemd_only() {
+    let user = init::select(InitKind::Systemd, true).expect("systemd user unit");
+    assert_eq!(user.name(), "systemd user unit");
+    assert!(!user.needs_root());
+    assert!(init::select(InitKind::Systemd, false).expect("systemd").needs_root());
+
+    assert_eq!(init::select(InitKind::Openrc, false).expect("openrc").name(), "openrc");
+    assert_eq!(init::select(InitKind::Runit, false).expect("runit").name(), "runit");
+    assert!(init::select(InitKind::Openrc, true).is_err());
+    assert!(init::select(InitKind::Runit, true).is_err());
+}
+
+#[test]
+fn openrc_script_brings_the_stack_up_and_down() {
+    let script = openrc::init_script(Path::new("/srv/mvre-hub"));
+    assert!(script.starts_with("#!/sbin/openrc-run\n"));
+    assert!(script.contains("command_args=\"docker-compose -f /srv/mvre-hub/docker-compose.yml up\"\n"));
+    assert!(script.contains(&format!("command_user=\"{}\"\n", whoami::username())));
+    assert!(script.contains("\tneed docker\n"));
+    assert!(script.contains("stop_post() {\n\t/usr/bin/env docker-compose -f /srv/mvre-hub/docker-compose.yml down\n}"));
+}
+
+#[test]
+fn runit_service_runs_in_the_foreground() {
+    let [(run_name, run), (finish_name, finish)] = runit::service_scripts(Path::new("/srv/mvre-hub"));
+    assert_eq!((run_name, finish_name), ("run", "finish"));
+    assert!(run.contains("cd /srv/mvre-hub\n"));
+    assert!(run.contains(&format!(
+        "exec chpst -u {} /usr/bin/env docker-compose -f /srv/mvre-hub/docker-compose.yml up\n",
+        whoami::username()
+    )));
+    assert!(finish.ends_with("docker-compose -f /srv/mvre-hub/docker-compose.yml down\n"));
+}
+
+#[test]
+fn desktop_managers_run_per_user() {
+    for kind in [InitKind::Launchd, InitKind::TaskScheduler] {
+        assert!(!init::select(kind, false).expect("manager").needs_root());
+        assert!(init::select(kind, true).is_err());
+    }
+}
+
+#[test]
+fn launch_agent_escapes_the_deployment_path() {
+    let plist = launchd::plist(Path::new("/Users/ada/R&D <hub>"));
+    assert!(plist.contains("<string>org.mvre.hub</string>"));
+    assert!(plist.contains("<string>/Users/ada/R&amp;D &lt;hub&gt;/docker-compose.yml</string>"));
+    assert!(plist.contains("<key>WorkingDirectory</key>\n\t<string>/Users/ada/R&amp;D &lt;hub&gt;</string>"));
+    assert!(plist.contains("<key>RunAtLoad</key>\n\t<true/>"));
+}
+
+#[test]
+fn logon_task_quotes_the_compose_file() {
+    let action = taskscheduler::logon_action(Path::new("/Users/Ada Lovelace/mvre-hub"));
+    assert_eq!(action, "docker-compose -f \"/Users/Ada Lovelace/mvre-hub/docker-compose.yml\" up -d");
+}
@@ -0,0 +1,30 @@
+use mvre_hub::inventory;
+
+#[test]
+fn host_vars_override_globals() {
+    let inventory = inventory::parse(
+        r#"
+[vars]
+MVRE_HUB_DOMAIN = "hub.example.org"
+MVRE_HUB_ACME_EMAIL = "it@example.org"
+
+[hosts.ship]
+address = "admin@ship"
+[hosts.ship.vars]
+MVRE_HUB_DOMAIN = "hub.ship.example.org"
+
+[hosts.test]
+address = "local"
+"#,
+    )
+    .expect("parse inventory");
+
+    let ship = &inventory.hosts["ship"];
+    assert_eq!(ship.binary, "mvre-hub");
+    let vars = inventory.vars_for(ship);
+    assert_eq!(vars["MVRE_HUB_DOMAIN"], "hub.ship.example.org");
+    assert_eq!(vars["MVRE_HUB_ACME_EMAIL"], "it@example.org");
+
+    assert_eq!(inventory.select(&[]).expect("select all").len(), 2);
+    assert!(inventory.select(&["shore".to_string()]).is_err());
+}
@@ -0,0 +1,22 @@
+use mvre_hub::keycloak;
+
+#[test]
+fn realm_endpoints_follow_openid_connect_layout() {
+    let (authorize, token, userinfo) = keycloak::realm_endpoints("https://sso.example.org/", "polar");
+    assert_eq!(authorize, "https://sso.example.org/realms/polar/protocol/openid-connect/auth");
+    assert_eq!(token, "https://sso.example.org/realms/polar/protocol/openid-connect/token");
+    assert_eq!(userinfo, "https://sso.example.org/realms/polar/protocol/openid-connect/userinfo");
+}
+
+#[test]
+fn client_is_confidential_and_redirects_to_the_hub() {
+    let client = keycloak::client_representation(
+        "mvre-hub",
+        "hub.example.org",
+        "https://hub.example.org/hub/oauth_callback",
+    );
+    assert_eq!(client["clientId"], "mvre-hub");
+    assert_eq!(client["publicClient"], false);
+    assert_eq!(client["redirectUris"][0], "https://hub.example.org/hub/oauth_callback");
+    assert_eq!(client["webOrigins"][0], "https://hub.example.org");
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use mvre_hub::{
+    cli::LandingPath,
+    landing::{self, LandingPage},
+};
+
+fn page() -> LandingPage<'static> {
+    LandingPage {
+        title: "MOSAiC <VRE>",
+        domain: "hub.example.org",
+        dataset_mount: "/home/jovyan/data",
+        support_email: None,
+        data_citation: None,
+    }
+}
+
+#[test]
+fn router_rules_follow_the_chosen_path() {
+    assert_eq!(
+        LandingPath::Root.router_rule("hub.example.org"),
+        "Host(`hub.example.org`) && (Path(`/`) || PathPrefix(`/docs`))"
+    );
+    assert_eq!(LandingPath::Docs.router_rule("hub.example.org"), "Host(`hub.example.org`) && PathPrefix(`/docs`)");
+    for path in [LandingPath::Root, LandingPath::Docs] {
+        assert_eq!(LandingPath::from_name(path.name()), Some(path));
+    }
+    assert_eq!(LandingPath::from_name(""), None);
+}
+
+#[test]
+fn page_is_escaped_and_lists_only_given_contacts() {
+    let html = landing::render_page(&page());
+    assert!(html.contains("<title>MOSAiC &lt;VRE&gt;</title>"));
+    assert!(html.contains("https://hub.example.org/hub/"));
+    assert!(html.contains("<code>/home/jovyan/data</code>"));
+    assert!(!html.contains("Citing the data") && !html.contains("mailto:"));
+
+    let html = landing::render_page(&LandingPage {
+        support_email: Some("vre-support@awi.de"),
+        data_citation: Some("Nicolaus & Hoppmann (2022)"),
+        ..page()
+    });
+    assert!(html.contains("<blockquote>Nicolaus &amp; Hoppmann (2022)</blockquote>"));
+    assert!(html.contains("mailto:vre-support@awi.de"));
+}
+
+#[test]
+fn page_is_only_generated_without_an_operator_site() {
+    let deploy = Path::new("/srv/hub");
+    let generated = landing::files(deploy, Some(&page()));
+    assert_eq!(generated.len(), 2);
+    assert!(generated.iter().any(|(path, _)| path == Path::new("/srv/hub/landing/site/index.html")));
+
+    let own = landing::files(deploy, None);
+    assert_eq!(own.len(), 1);
+    assert_eq!(own[0].0, Path::new("/srv/hub/landing/nginx.conf"));
+    assert!(own[0].1.contains("alias /srv/landing/;"));
+}
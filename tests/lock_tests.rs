@@ -0,0 +1,44 @@
+use std::fs::{File, OpenOptions};
+
+use mvre_hub::{
+    failure::{self, Category},
+    lock,
+};
+
+fn open_lock_file(dir: &std::path::Path) -> File {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dir.join(lock::LOCK_FILE))
+        .expect("open lock file")
+}
+
+#[test]
+fn lock_records_its_holder_and_is_released_on_drop() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let held = lock::acquire(dir.path(), "mvre-hub upgrade").expect("acquire");
+    let holder = lock::holder(dir.path()).expect("holder");
+    assert_eq!(holder.command, "mvre-hub upgrade");
+    assert_eq!(holder.pid, std::process::id());
+
+    // Nested operations of the same process do not wait on themselves.
+    drop(lock::acquire(dir.path(), "mvre-hub start").expect("nested acquire"));
+    let other = open_lock_file(dir.path());
+    assert!(other.try_lock().is_err(), "the outer lock is still held");
+
+    drop(held);
+    other.try_lock().expect("released on drop");
+}
+
+#[test]
+fn held_lock_rejects_a_second_operation_as_busy() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let other = open_lock_file(dir.path());
+    other.try_lock().expect("lock");
+
+    let err = lock::acquire(dir.path(), "mvre-hub start").expect_err("busy");
+    assert_eq!(failure::classify(&err), Category::Busy);
+    assert!(err.to_string().contains("another operation is in progress"), "{}", err);
+}
@@ -0,0 +1,67 @@
+use mvre_hub::{
+    cli::LogDriver,
+    logconfig::{self, ContainerLogging},
+};
+
+const COMPOSE: &str = "services:
+  jupyterhub:
+    build: ./hub
+    networks:
+      - proxy
+    command: [\"jupyterhub\"]
+
+  traefik:
+    image: traefik:v3.1
+    labels:
+      - \"traefik.enable=true\"
+
+networks:
+  proxy:
+    name: proxy
+";
+
+#[test]
+fn every_service_refers_to_the_logging_extension() {
+    let logging = ContainerLogging {
+        driver: LogDriver::JsonFile,
+        max_size: "50m",
+        max_file: 5,
+    };
+    let compose = logconfig::apply(COMPOSE, &logging);
+    assert_eq!(compose.matches("    logging: *default-logging\n").count(), 2);
+
+    let file: serde_yaml::Value = serde_yaml::from_str(&compose).expect("valid compose");
+    for service in ["jupyterhub", "traefik"] {
+        let options = &file["services"][service]["logging"];
+        assert_eq!(options["driver"].as_str(), Some("json-file"));
+        assert_eq!(options["options"]["max-size"].as_str(), Some("50m"));
+        assert_eq!(options["options"]["max-file"].as_str(), Some("5"));
+    }
+    assert!(file["networks"]["proxy"].get("logging").is_none());
+}
+
+#[test]
+fn journald_takes_no_rotation_options() {
+    let logging = ContainerLogging {
+        driver: LogDriver::Journald,
+        max_size: "50m",
+        max_file: 5,
+    };
+    let file: serde_yaml::Value = serde_yaml::from_str(&logconfig::apply(COMPOSE, &logging)).expect("valid compose");
+    let options = &file["services"]["traefik"]["logging"];
+    assert_eq!(options["driver"].as_str(), Some("journald"));
+    assert!(options.get("options").is_none());
+}
+
+#[test]
+fn sizes_use_docker_syntax() {
+    for size in ["50m", "1g", "512k", "1048576"] {
+        assert!(logconfig::validate_max_size(size).is_ok(), "{}", size);
+    }
+    for size in ["", "m", "50mb", "5.5m", "-1m"] {
+        assert!(logconfig::validate_max_size(size).is_err(), "{}", size);
+    }
+    for driver in [LogDriver::JsonFile, LogDriver::Local, LogDriver::Journald] {
+        assert_eq!(LogDriver::from_name(driver.name()), Some(driver));
+    }
+}
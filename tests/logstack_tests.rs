@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use mvre_hub::logstack;
+
+#[test]
+fn retention_is_whole_hours_of_at_least_a_day() {
+    assert_eq!(logstack::retention_hours("30d").expect("30d"), 720);
+    assert_eq!(logstack::retention_hours("36h").expect("36h"), 36);
+    assert!(logstack::retention_hours("12h").is_err());
+    assert!(logstack::retention_hours("soon").is_err());
+}
+
+#[test]
+fn configs_wire_promtail_and_grafana_to_loki() {
+    let files = logstack::files(Path::new("/srv/hub"), 336);
+    let content = |name: &str| {
+        files
+            .iter()
+            .find(|(path, _)| path == &Path::new("/srv/hub/logging").join(name))
+            .map(|(_, content)| content.clone())
+            .expect(name)
+    };
+    let loki: serde_yaml::Value = serde_yaml::from_str(&content("loki.yaml")).expect("loki yaml");
+    assert_eq!(loki["limits_config"]["retention_period"].as_str(), Some("336h"));
+    assert_eq!(loki["compactor"]["retention_enabled"].as_bool(), Some(true));
+
+    let promtail: serde_yaml::Value = serde_yaml::from_str(&content("promtail.yaml")).expect("promtail yaml");
+    assert_eq!(promtail["clients"][0]["url"].as_str(), Some("http://loki:3100/loki/api/v1/push"));
+    let labels: Vec<&str> = promtail["scrape_configs"][0]["relabel_configs"]
+        .as_sequence()
+        .expect("relabel configs")
+        .iter()
+        .filter_map(|rule| rule["target_label"].as_str())
+        .collect();
+    assert_eq!(labels, vec!["container", "service", "project", "stream"]);
+
+    let grafana: serde_yaml::Value = serde_yaml::from_str(&content("grafana-datasources.yaml")).expect("grafana yaml");
+    assert_eq!(grafana["datasources"][0]["url"].as_str(), Some("http://loki:3100"));
+}
@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, path::Path};
+
+use mvre_hub::manifest::{self, SettingChange};
+
+fn env(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+#[test]
+fn settings_become_options_of_the_command() {
+    let manifest = manifest::parse(
+        r#"
+        deploy_dir = "/srv/mvre-hub"
+
+        [settings]
+        domain = "hub.example.org"
+        extra_idp = ["orcid", "cilogon"]
+        concurrent-spawn-limit = 20
+        enable-mlflow = false
+        internal-tls = true
+        allowed-users = "users.txt"
+        shared-path = "/srv/shared"
+        "#,
+    )
+    .unwrap();
+    let (args, skipped) = manifest::to_args(&manifest, "reconfigure", Path::new("/etc/hub")).unwrap();
+    assert_eq!(
+        args,
+        vec![
+            "--allowed-users=/etc/hub/users.txt",
+            "--concurrent-spawn-limit=20",
+            "--domain=hub.example.org",
+            "--disable-mlflow",
+            "--extra-idp=orcid,cilogon",
+            "--internal-tls",
+        ]
+    );
+    assert_eq!(skipped, vec!["shared-path"]);
+
+    let (args, skipped) = manifest::to_args(&manifest, "deploy", Path::new("/etc/hub")).unwrap();
+    assert!(!args.iter().any(|arg| arg.contains("mlflow")));
+    assert!(args.contains(&"--shared-path=/srv/shared".to_string()));
+    assert!(skipped.is_empty());
+
+    let opts = manifest::reconfigure_options(&manifest, Path::new("/etc/hub")).unwrap();
+    assert!(opts.yes && opts.disable_mlflow && opts.internal_tls);
+    assert_eq!(opts.concurrent_spawn_limit, Some(20));
+}
+
+#[test]
+fn manifest_rejects_credentials_and_unknown_settings() {
+    let error = |raw: &str| {
+        let manifest = manifest::parse(raw).unwrap();
+        manifest::to_args(&manifest, "deploy", Path::new(".")).unwrap_err().to_string()
+    };
+    assert!(error("[settings]\nclient-secret = \"s3cret\"").contains("MVRE_HUB_CLIENT_SECRET"));
+    assert!(error("[settings]\ndomian = \"hub.example.org\"").contains("unknown setting 'domian'"));
+    assert!(error("[settings]\nyes = true").contains("not a deployment setting"));
+    assert!(error("[settings]\nenable-mlflow = \"yes\"").contains("switch"));
+    assert!(manifest::parse("deploy-dir = \"/srv\"").is_err());
+
+    let manifest = manifest::parse("[settings]\ntls = \"sometimes\"").unwrap();
+    assert!(manifest::deploy_options(&manifest, Path::new("."), false).is_err());
+}
+
+#[test]
+fn env_changes_mask_credentials() {
+    let current = env(&[("DOMAIN", "old.example.org"), ("DB_PASSWORD", "a"), ("RATE_LIMIT", "10")]);
+    let desired = env(&[("DOMAIN", "new.example.org"), ("DB_PASSWORD", "b"), ("CSP", "default-src 'self'")]);
+    let changes = manifest::env_changes(&current, &desired);
+    let change = |key: &str, from: Option<&str>, to: Option<&str>| SettingChange {
+        key: key.to_string(),
+        from: from.map(str::to_string),
+        to: to.map(str::to_string),
+    };
+    assert_eq!(changes.len(), 4);
+    assert_eq!(changes[0], change("CSP", None, Some("default-src 'self'")));
+    assert_eq!(changes[1].key, "DB_PASSWORD");
+    assert_ne!(changes[1].to.as_deref(), Some("b"));
+    assert_eq!(changes[2], change("DOMAIN", Some("old.example.org"), Some("new.example.org")));
+    assert_eq!(changes[3], change("RATE_LIMIT", Some("10"), None));
+    assert!(manifest::env_changes(&current, &current).is_empty());
+}
@@ -0,0 +1,77 @@
+use std::fs;
+
+use mvre_hub::{
+    cli::OnConflict,
+    merge::{self, Action},
+};
+
+const BASE: &str = "c.Spawner.default_url = '/lab'\nc.Spawner.cpu_limit = 2\nc.Spawner.mem_limit = '4G'\nc.JupyterHub.log_level = 'INFO'\n";
+
+#[test]
+fn edits_and_template_changes_in_different_places_merge() {
+    let ours = BASE.replace("'/lab'", "'/tree'");
+    let theirs = format!("{}c.JupyterHub.shutdown_on_logout = True\n", BASE.replace("'INFO'", "'WARN'"));
+
+    let merged = merge::merge3(BASE, &ours, &theirs);
+    assert_eq!(merged.conflicts, 0);
+    assert_eq!(
+        merged.text,
+        "c.Spawner.default_url = '/tree'\nc.Spawner.cpu_limit = 2\nc.Spawner.mem_limit = '4G'\nc.JupyterHub.log_level = 'WARN'\nc.JupyterHub.shutdown_on_logout = True\n"
+    );
+}
+
+#[test]
+fn overlapping_changes_become_a_conflict() {
+    let ours = BASE.replace("cpu_limit = 2", "cpu_limit = 4");
+    let theirs = BASE.replace("cpu_limit = 2", "cpu_limit = 1");
+
+    let merged = merge::merge3(BASE, &ours, &theirs);
+    assert_eq!(merged.conflicts, 1);
+    assert!(merged.text.contains(
+        "<<<<<<< your edits\nc.Spawner.cpu_limit = 4\n=======\nc.Spawner.cpu_limit = 1\n>>>>>>> regenerated\n"
+    ));
+    assert!(merged.text.starts_with("c.Spawner.default_url = '/lab'\n"));
+    assert!(merged.text.ends_with("c.JupyterHub.log_level = 'INFO'\n"));
+
+    // The same change on both sides is no conflict.
+    assert_eq!(merge::merge3(BASE, &ours, &ours).text, ours);
+}
+
+#[test]
+fn plan_keeps_edits_unless_the_template_moved() {
+    let edited = BASE.replace("'/lab'", "'/tree'");
+    let updated = BASE.replace("'INFO'", "'WARN'");
+
+    assert_eq!(merge::plan(Some(BASE), Some(BASE), &updated), Action::Replace);
+    assert_eq!(merge::plan(Some(BASE), Some(&edited), BASE), Action::KeepEdits);
+    assert_eq!(merge::plan(Some(BASE), Some(&updated), &updated), Action::Unchanged);
+    assert!(matches!(merge::plan(Some(BASE), Some(&edited), &updated), Action::Merged(_)));
+    // Without a recorded rendering, edits cannot be told apart from old templates.
+    assert_eq!(merge::plan(None, Some(&edited), &updated), Action::Replace);
+    assert_eq!(merge::plan(Some(BASE), None, &updated), Action::Replace);
+}
+
+#[test]
+fn conflicts_need_a_choice_when_not_interactive() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("hub").join("jupyterhub_config.py");
+
+    let updates = merge::plan_files(dir.path(), vec![(config.clone(), BASE.to_string())]);
+    merge::write(dir.path(), &updates).expect("first render");
+    fs::write(&config, BASE.replace("cpu_limit = 2", "cpu_limit = 4")).unwrap();
+
+    let rendered = BASE.replace("cpu_limit = 2", "cpu_limit = 1");
+    let mut updates = merge::plan_files(dir.path(), vec![(config.clone(), rendered.clone())]);
+    let err = merge::resolve(dir.path(), &mut updates, None, false).unwrap_err();
+    assert!(format!("{:#}", err).contains("hub/jupyterhub_config.py"));
+
+    merge::resolve(dir.path(), &mut updates, Some(OnConflict::Keep), false).expect("keep");
+    // Written even after the file was removed, as deploy --force does.
+    fs::remove_file(&config).unwrap();
+    merge::write(dir.path(), &updates).expect("write");
+    assert_eq!(fs::read_to_string(&config).unwrap(), BASE.replace("cpu_limit = 2", "cpu_limit = 4"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(merge::RENDERED_DIR).join("hub").join("jupyterhub_config.py")).unwrap(),
+        rendered
+    );
+}
@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use mvre_hub::nbgrader;
+
+#[test]
+fn course_and_group_names_stay_simple() {
+    assert!(nbgrader::validate_name("--nbgrader-course", "sea_ice-101").is_ok());
+    for value in ["", "sea ice", "a/b", "a.b"] {
+        assert!(nbgrader::validate_name("--nbgrader-course", value).is_err(), "{}", value);
+    }
+}
+
+#[test]
+fn student_config_turns_off_the_instructor_extensions() {
+    let files = nbgrader::files(Path::new("/srv/mvre-hub"));
+    let paths: Vec<_> = files.iter().map(|(path, _)| path.to_string_lossy().to_string()).collect();
+    assert!(paths.contains(&"/srv/mvre-hub/nbgrader/nbgrader_config.py".to_string()));
+    assert!(paths.contains(&"/srv/mvre-hub/nbgrader/student/labconfig/page_config.json".to_string()));
+
+    let server: serde_json::Value = serde_json::from_str(&nbgrader::student_server_config()).expect("json");
+    assert_eq!(server["ServerApp"]["jpserver_extensions"]["nbgrader.server_extensions.formgrader"], false);
+    let page: serde_json::Value = serde_json::from_str(&nbgrader::student_page_config()).expect("json");
+    assert_eq!(page["disabledExtensions"]["@jupyter/nbgrader:create-assignment"], true);
+    assert!(nbgrader::config().contains(r#"c.Exchange.root = "/srv/nbgrader/exchange""#));
+}
+
+#[test]
+fn exchange_is_writable_by_every_server() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    nbgrader::ensure_exchange(dir.path()).expect("exchange");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.path().join("nbgrader/exchange")).expect("metadata").permissions().mode();
+        assert_eq!(mode & 0o777, 0o777);
+    }
+}
@@ -0,0 +1,34 @@
+use mvre_hub::{envfile, open};
+
+fn access(raw: &str) -> open::Access {
+    open::access(&envfile::parse(raw)).expect("access")
+}
+
+#[test]
+fn hub_url_follows_the_tls_mode() {
+    assert_eq!(access("HUB_DOMAIN=hub.example.org\nTLS_MODE=letsencrypt\nHTTP_PORT=8080\n").hub_url, "https://hub.example.org");
+    assert_eq!(access("HUB_DOMAIN=localhost\nTLS_MODE=off\nHTTP_PORT=8080\n").hub_url, "http://localhost:8080");
+    assert_eq!(access("HUB_DOMAIN=localhost\nTLS_MODE=off\nHTTP_PORT=80\n").hub_url, "http://localhost");
+    assert!(open::access(&envfile::parse("TLS_MODE=off\n")).is_err());
+}
+
+#[test]
+fn summary_lists_admins_mounts_and_enabled_services() {
+    let access = access(
+        "HUB_DOMAIN=hub.example.org\nTLS_MODE=self-signed\nADMIN_USERS=alice, bob\n\
+         DATASET_HOST_PATH=/srv/data\nDATASET_MOUNT_PATH=/home/jovyan/data\n\
+         ENABLE_MLFLOW=true\nENABLE_LOGGING_STACK=true\nENABLE_FILE_BROWSER=false\n",
+    );
+    assert_eq!(access.admin_users, vec!["alice", "bob"]);
+    assert_eq!(access.dataset, Some(("/home/jovyan/data".to_string(), "/srv/data".to_string())));
+    assert_eq!(access.shared, None);
+    assert_eq!(access.mlflow_url.as_deref(), Some("https://mlflow.hub.example.org"));
+    assert_eq!(access.grafana_url.as_deref(), Some("https://hub.example.org/grafana/"));
+    assert_eq!(access.files_url, None);
+    assert!(access.untrusted_certificate);
+
+    let bare = self::access("HUB_DOMAIN=hub.example.org\nTLS_MODE=letsencrypt\n");
+    assert!(bare.admin_users.is_empty());
+    assert_eq!(bare.mlflow_url, None);
+    assert!(!bare.untrusted_certificate);
+}
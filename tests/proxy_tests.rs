@@ -0,0 +1,73 @@
+use mvre_hub::{
+    cli::{ProxyVersion, TlsMode},
+    envfile, proxy,
+    templates::{self, ComposeValues},
+};
+
+#[test]
+fn v2_rules_are_rewritten_for_v3() {
+    assert_eq!(proxy::migrate_rule("Host(`a.org`)"), "Host(`a.org`)");
+    assert_eq!(
+        proxy::migrate_rule("Host(`a.org`, `b.org`) && PathPrefix(`/x`)"),
+        "(Host(`a.org`) || Host(`b.org`)) && PathPrefix(`/x`)"
+    );
+    assert_eq!(
+        proxy::migrate_rule("Headers(`X-Env`, `prod`) && HostHeader(`a.org`)"),
+        "Header(`X-Env`, `prod`) && Host(`a.org`)"
+    );
+    assert_eq!(
+        proxy::migrate_rule("HostRegexp(`{sub:[a-z]+}.example.org`)"),
+        "HostRegexp(`^[a-z]+\\.example\\.org$`)"
+    );
+}
+
+#[test]
+fn compose_migration_switches_image_and_keeps_other_lines() {
+    let v2 = templates::docker_compose(&ComposeValues {
+        domain: "hub.example.org",
+        acme_email: "admin@example.org",
+        acme_staging: false,
+        tls: TlsMode::Acme,
+        production: false,
+        publishing: true,
+        http_port: 8080,
+        https_port: 8443,
+        proxy_version: ProxyVersion::V2,
+        security_headers: false,
+        csp: None,
+        rate_limit: None,
+        internal_tls: false,
+        selinux: false,
+        sandbox_profiles: false,
+        workspaces: false,
+        scratch: false,
+        branding: false,
+        landing: None,
+        dask_gateway: false,
+        mlflow: false,
+        file_browser: false,
+        logging_stack: false,
+        logging: None,
+        extra_services: &[],
+    });
+    let custom = format!(
+        "{}\n  extra:\n    image: nginx\n    labels:\n      - \"traefik.http.routers.extra.rule=Host(`a.org`,`b.org`)\"\n",
+        v2.trim_end()
+    );
+
+    let migrated = proxy::migrate_compose_to_v3(&custom);
+    assert!(migrated.contains("image: traefik:v3.1"));
+    assert!(!migrated.contains("traefik:v2"));
+    assert!(migrated.contains("traefik.http.routers.extra.rule=(Host(`a.org`) || Host(`b.org`))"));
+    assert!(migrated.contains("traefik.http.routers.published.rule=Host(`hub.example.org`) && PathPrefix(`/published`)"));
+    assert_eq!(proxy::migrate_compose_to_v3(&migrated), migrated);
+}
+
+#[test]
+fn env_value_is_replaced_or_appended() {
+    let raw = "# settings\nHUB_DOMAIN=a.org\nPROXY_VERSION=v2\n";
+    let updated = envfile::with_value(raw, "PROXY_VERSION", "v3");
+    assert_eq!(updated, "# settings\nHUB_DOMAIN=a.org\nPROXY_VERSION=v3\n");
+    let appended = envfile::with_value("HUB_DOMAIN=a.org\n", "PROXY_VERSION", "v3");
+    assert_eq!(envfile::get(&envfile::parse(&appended), "PROXY_VERSION"), Some("v3"));
+}
@@ -0,0 +1,24 @@
+use mvre_hub::{hubapi::User, prune};
+
+#[test]
+fn orphans_match_escaped_user_names_of_this_deployment_only() {
+    let users: Vec<User> = serde_json::from_str(r#"[{"name": "jane.doe"}, {"name": "Bob_Smith"}, {"name": "carol"}]"#)
+        .expect("users");
+    let volumes: Vec<String> = [
+        "jupyterhub-user-jane-2Edoe",
+        "jupyterhub-user--42ob-5F-53mith",
+        "jupyterhub-user-carol",
+        "jupyterhub-user-dave",
+        "mvre-geo-jupyterhub-user-erin",
+        "mvre_postgres_data",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect();
+
+    assert_eq!(prune::orphaned_volumes(&volumes, &users, "jupyterhub-user-"), vec!["jupyterhub-user-dave"]);
+    assert_eq!(
+        prune::orphaned_volumes(&volumes, &users, "mvre-geo-jupyterhub-user-"),
+        vec!["mvre-geo-jupyterhub-user-erin"]
+    );
+}
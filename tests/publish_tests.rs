@@ -0,0 +1,51 @@
+use std::{fs, path::Path};
+
+use mvre_hub::publish;
+
+#[test]
+fn symlinks_are_left_out_of_published_copies() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let secret = dir.path().join("secret.env");
+    fs::write(&secret, "OAUTH_CLIENT_SECRET=hunter2\n").unwrap();
+    let source = dir.path().join("shared").join("results");
+    fs::create_dir_all(source.join("plots")).unwrap();
+    fs::write(source.join("summary.csv"), "drift,12\n").unwrap();
+    fs::write(source.join("plots").join("drift.png"), "png").unwrap();
+    std::os::unix::fs::symlink(&secret, source.join("x")).unwrap();
+    std::os::unix::fs::symlink(dir.path(), source.join("plots").join("up")).unwrap();
+
+    let target = dir.path().join("published").join("results").join("v1");
+    let mut skipped = publish::copy_tree(&source, &target).expect("copy");
+    skipped.sort();
+    assert_eq!(skipped, vec![source.join("plots").join("up"), source.join("x")]);
+    assert_eq!(fs::read_to_string(target.join("summary.csv")).unwrap(), "drift,12\n");
+    assert!(target.join("plots").join("drift.png").exists());
+    assert!(fs::symlink_metadata(target.join("x")).is_err());
+    assert!(fs::symlink_metadata(target.join("plots").join("up")).is_err());
+}
+
+#[test]
+fn versions_count_up_from_the_highest_published() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let collection = dir.path().join("drift-2020");
+    assert_eq!(publish::next_version(&collection).unwrap(), 1);
+    for version in ["v1", "v2", "v10", "latest", "vnext"] {
+        fs::create_dir_all(collection.join(version)).unwrap();
+    }
+    assert_eq!(publish::next_version(&collection).unwrap(), 11);
+}
+
+#[test]
+fn names_and_paths_stay_inside_their_directories() {
+    for name in ["drift-2020", "run_3", "v1.2"] {
+        assert!(publish::validate_name(name).is_ok(), "{}", name);
+    }
+    for name in ["", ".hidden", "a/b", "..", "drift 2020"] {
+        assert!(publish::validate_name(name).is_err(), "{}", name);
+    }
+    assert!(publish::validate_path(Path::new("results/drift-2020")).is_ok());
+    assert!(publish::validate_path(Path::new("./results")).is_ok());
+    assert!(publish::validate_path(Path::new("../deploy/.env")).is_err());
+    assert!(publish::validate_path(Path::new("results/../../etc")).is_err());
+    assert!(publish::validate_path(Path::new("/etc")).is_err());
+}
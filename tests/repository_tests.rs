@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use mvre_hub::{
+    backup::RetentionPolicy,
+    repository::{self, Repository, RepositoryEngine},
+};
+
+#[test]
+fn repository_destinations_name_their_engine() {
+    assert_eq!(
+        Repository::parse("restic:s3:https://s3.example.org/hub"),
+        Some(Repository {
+            engine: RepositoryEngine::Restic,
+            location: "s3:https://s3.example.org/hub".to_string(),
+        })
+    );
+    assert_eq!(
+        Repository::parse("borg:ssh://backup@nas/./hub").map(|repository| repository.engine),
+        Some(RepositoryEngine::Borg)
+    );
+    assert_eq!(Repository::parse("s3://bucket/prefix"), None);
+    assert_eq!(Repository::parse("/srv/backups"), None);
+}
+
+#[test]
+fn backups_exclude_snapshots_and_name_borg_archives() {
+    let paths = [PathBuf::from("/srv/mvre-hub"), PathBuf::from("/var/lib/docker/volumes/jupyterhub-user-alice/_data")];
+    let excluded = [PathBuf::from("/srv/mvre-hub/.mvre-snapshots")];
+
+    let restic = Repository::parse("restic:/mnt/restic").expect("restic");
+    assert_eq!(
+        restic.backup_args("mvre-hub-20261012T010203Z", &paths, &excluded),
+        vec![
+            "-r", "/mnt/restic", "backup", "--tag", "mvre-hub",
+            "--exclude", "/srv/mvre-hub/.mvre-snapshots",
+            "/srv/mvre-hub", "/var/lib/docker/volumes/jupyterhub-user-alice/_data",
+        ]
+    );
+
+    let borg = Repository::parse("borg:/mnt/borg").expect("borg");
+    assert_eq!(
+        borg.backup_args("mvre-hub-20261012T010203Z", &paths, &excluded),
+        vec![
+            "create", "--compression", "zstd",
+            "--exclude", "/srv/mvre-hub/.mvre-snapshots",
+            "/mnt/borg::mvre-hub-20261012T010203Z",
+            "/srv/mvre-hub", "/var/lib/docker/volumes/jupyterhub-user-alice/_data",
+        ]
+    );
+}
+
+#[test]
+fn retention_is_delegated_to_the_engine() {
+    let policy = RetentionPolicy { daily: 7, weekly: 0 };
+    let restic = Repository::parse("restic:/mnt/restic").expect("restic");
+    assert_eq!(
+        restic.prune_args(policy, false),
+        vec![vec!["-r", "/mnt/restic", "forget", "--tag", "mvre-hub", "--keep-daily", "7", "--prune"]]
+    );
+
+    let borg = Repository::parse("borg:/mnt/borg").expect("borg");
+    assert_eq!(
+        borg.prune_args(RetentionPolicy { daily: 7, weekly: 4 }, false),
+        vec![
+            vec!["prune", "--list", "--glob-archives", "mvre-hub-*", "--keep-daily", "7", "--keep-weekly", "4", "/mnt/borg"],
+            vec!["compact", "/mnt/borg"],
+        ]
+    );
+    assert_eq!(borg.prune_args(policy, true).len(), 1);
+}
+
+#[test]
+fn repository_listings_are_parsed() {
+    let restic = r#"[{"time":"2026-10-12T03:02:03.123456789+02:00","short_id":"4cb35f1a","id":"4cb35f1a00"}]"#;
+    let snapshots = repository::parse_restic_snapshots(restic).expect("restic");
+    assert_eq!(snapshots[0].name, "4cb35f1a");
+    assert_eq!(snapshots[0].created_at, 1_791_766_923);
+
+    let borg = r#"{"archives":[{"name":"mvre-hub-20261012T010203Z","time":"2026-10-12T03:02:03.000000"},{"name":"manual","time":"2026-10-12T04:00:00.000000"}]}"#;
+    let archives = repository::parse_borg_archives(borg).expect("borg");
+    assert_eq!(archives.len(), 1);
+    assert_eq!(archives[0].created_at, 1_791_766_923);
+}
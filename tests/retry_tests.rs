@@ -0,0 +1,73 @@
+use std::{
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use mvre_hub::{
+    failure::{self, Category},
+    progress::Spinner,
+    retry::{self, Policy},
+};
+
+#[test]
+fn operation_is_the_docker_subcommand() {
+    assert_eq!(retry::operation("docker-compose", &["pull", "--ignore-buildable"]), Some("pull"));
+    assert_eq!(
+        retry::operation("docker-compose", &["-p", "hub-green", "-f", "docker-compose.green.yml", "up", "-d"]),
+        Some("up")
+    );
+    assert_eq!(retry::operation("docker", &["pull", "ghcr.io/example/hub:5"]), Some("pull"));
+    assert_eq!(retry::operation("repo2docker", &["--no-run", "https://github.com/example/env"]), Some("build"));
+    assert_eq!(retry::operation("docker-compose", &["-f", "docker-compose.yml"]), None);
+    assert_eq!(retry::operation("trivy", &["image", "hub"]), None);
+}
+
+#[test]
+fn only_network_bound_operations_are_retried() {
+    let policy = Policy::default();
+    assert_eq!(policy.attempts(Some("pull")), 3);
+    assert_eq!(policy.attempts(Some("up")), 3);
+    assert_eq!(policy.attempts(Some("exec")), 1);
+    assert_eq!(policy.attempts(None), 1);
+
+    let policy = Policy::from_flags(5, true, "10s", &[]).unwrap();
+    assert_eq!(policy.attempts(Some("pull")), 1);
+}
+
+#[test]
+fn backoff_doubles_up_to_a_limit() {
+    let policy = Policy::from_flags(8, false, "30s", &[]).unwrap();
+    assert_eq!(policy.delay(1), Duration::from_secs(30));
+    assert_eq!(policy.delay(2), Duration::from_secs(60));
+    assert_eq!(policy.delay(3), Duration::from_secs(120));
+    assert_eq!(policy.delay(7), Duration::from_secs(600));
+    assert_eq!(policy.delay(200), Duration::from_secs(600));
+}
+
+#[test]
+fn timeouts_can_be_changed_per_operation() {
+    let policy = Policy::from_flags(2, false, "10s", &["pull=45m".to_string(), "up=0".to_string()]).unwrap();
+    assert_eq!(policy.timeout(Some("pull")), Some(Duration::from_secs(45 * 60)));
+    assert_eq!(policy.timeout(Some("up")), None);
+    assert_eq!(policy.timeout(Some("build")), Some(Duration::from_secs(3600)));
+    assert_eq!(policy.timeout(Some("exec")), None);
+
+    for bad in ["pull", "exec=5m", "pull=soon"] {
+        let err = Policy::from_flags(2, false, "10s", &[bad.to_string()]).unwrap_err();
+        assert_eq!(failure::classify(&err), Category::Validation, "{}", bad);
+    }
+}
+
+#[test]
+fn stalled_command_is_stopped_at_its_time_limit() {
+    let started = Instant::now();
+    let mut command = Command::new("sh");
+    command.args(["-c", "echo pulling; sleep 30"]);
+    let err = Spinner::hidden()
+        .run(&mut command, "sh", None, None, Some(Duration::from_millis(300)))
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(format!("{:#}", err).contains("did not finish"));
+    assert!(retry::is_transient(&err));
+    assert!(!retry::is_transient(&failure::validation("--retries must be a number")));
+}
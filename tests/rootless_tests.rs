@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+
+use mvre_hub::{
+    doctor::{self, Finding},
+    rootless::{self, DockerMode},
+    systemd::{self, Scope},
+};
+
+#[test]
+fn socket_prefers_docker_host_then_runtime_dir() {
+    assert_eq!(
+        rootless::socket_path(true, Some("unix:///home/ada/.docker/run/docker.sock"), Some("/run/user/1000")),
+        PathBuf::from("/home/ada/.docker/run/docker.sock")
+    );
+    assert_eq!(
+        rootless::socket_path(true, Some("tcp://10.0.0.2:2376"), Some("/run/user/1000")),
+        PathBuf::from("/run/user/1000/docker.sock")
+    );
+    assert_eq!(rootless::socket_path(false, None, Some("/run/user/1000")), PathBuf::from(rootless::ROOTFUL_SOCKET));
+}
+
+#[test]
+fn only_non_default_sockets_need_docker_host() {
+    assert_eq!(DockerMode::from_socket(rootless::ROOTFUL_SOCKET).docker_host(), None);
+    let mode = DockerMode::from_socket("/run/user/1000/docker.sock");
+    assert!(mode.rootless);
+    assert_eq!(mode.docker_host().as_deref(), Some("unix:///run/user/1000/docker.sock"));
+}
+
+#[test]
+fn privileged_ports_block_rootless_deployments() {
+    assert_eq!(rootless::privileged_ports(&[80, 443, 8443], 1024), vec![80, 443]);
+    assert!(matches!(doctor::check_ports(&[80, 443], 1024), Finding::Blocker(_)));
+    assert!(matches!(doctor::check_ports(&[80, 443], 80), Finding::Ok(_)));
+}
+
+#[test]
+fn port_driver_is_read_from_the_unit_environment() {
+    let output = "Environment=PATH=/usr/bin DOCKERD_ROOTLESS_ROOTLESSKIT_PORT_DRIVER=slirp4netns\n";
+    assert_eq!(rootless::parse_port_driver(output).as_deref(), Some("slirp4netns"));
+    assert_eq!(rootless::parse_port_driver("Environment=\n"), None);
+    assert!(matches!(doctor::check_port_driver("builtin"), Finding::Warn(_)));
+}
+
+#[test]
+fn rootless_unit_points_compose_at_the_user_daemon() {
+    let unit = systemd::service_unit(
+        Path::new("/home/ada/mvre-hub"),
+        Scope::User,
+        Some("unix:///run/user/1000/docker.sock"),
+    );
+    assert!(unit.contains("Environment=DOCKER_HOST=unix:///run/user/1000/docker.sock\n"));
+    assert!(unit.contains("After=network.target docker.service\n"));
+    assert!(unit.contains("WantedBy=default.target\n"));
+
+    let rootful = systemd::service_unit(Path::new("/srv/mvre-hub"), Scope::User, None);
+    assert!(!rootful.contains("DOCKER_HOST"));
+}
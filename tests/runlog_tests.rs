@@ -0,0 +1,45 @@
+use std::{fs, process::Command};
+
+use mvre_hub::{progress::Spinner, runlog};
+
+#[test]
+fn run_logs_sort_by_start_time() {
+    assert_eq!(runlog::file_name("upgrade", 1_700_000_000), "20231114T221320Z-upgrade.log");
+    assert_eq!(runlog::file_name("backup create", 0), "19700101T000000Z-backup-create.log");
+}
+
+#[test]
+fn prune_keeps_the_newest_logs() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for started in [30, 10, 20] {
+        fs::write(dir.path().join(runlog::file_name("deploy", started)), "").unwrap();
+    }
+    fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+    let removed = runlog::prune(dir.path(), 2);
+    assert_eq!(removed, vec![dir.path().join(runlog::file_name("deploy", 10))]);
+    assert!(dir.path().join(runlog::file_name("deploy", 30)).exists());
+    assert!(dir.path().join("notes.txt").exists());
+}
+
+#[test]
+fn command_output_is_kept_in_the_run_log() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    runlog::start("upgrade", 1_700_000_000);
+
+    let log = runlog::Writer::open(dir.path(), "sh", &["-c", "..."]).expect("log");
+    let path = log.path().to_path_buf();
+    assert_eq!(path, dir.path().join(runlog::LOG_DIR).join("20231114T221320Z-upgrade.log"));
+    let mut command = Command::new("sh");
+    command.args(["-c", "cat; echo 'pull failed' >&2; exit 3"]);
+    let err = Spinner::hidden()
+        .run(&mut command, "sh", Some(b"Pulling jupyterhub\n"), Some(log), None)
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains(&path.display().to_string()));
+
+    let written = fs::read_to_string(&path).unwrap();
+    assert!(written.starts_with("==> ") && written.contains("sh -c ..."));
+    assert!(written.contains("Pulling jupyterhub\n"));
+    assert!(written.contains("pull failed\n"));
+    assert!(written.trim_end().ends_with("exit status: 3"));
+}
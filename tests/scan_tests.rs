@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use mvre_hub::{cli::Severity, compose, envfile, scan};
+
+#[test]
+fn trivy_findings_are_deduplicated_and_sorted_by_severity() {
+    let raw = r#"{
+      "Results": [
+        {"Target": "debian", "Vulnerabilities": [
+          {"VulnerabilityID": "CVE-2024-0002", "PkgName": "zlib1g", "InstalledVersion": "1.2.13", "Severity": "MEDIUM"},
+          {"VulnerabilityID": "CVE-2024-0001", "PkgName": "openssl", "InstalledVersion": "3.0.11", "FixedVersion": "3.0.13", "Severity": "CRITICAL"}
+        ]},
+        {"Target": "python", "Vulnerabilities": [
+          {"VulnerabilityID": "CVE-2024-0001", "PkgName": "openssl", "InstalledVersion": "3.0.11", "FixedVersion": "3.0.13", "Severity": "CRITICAL"}
+        ]},
+        {"Target": "clean"}
+      ]
+    }"#;
+
+    let findings = scan::parse_trivy(raw).expect("parse");
+    let summary: Vec<(&str, Severity, Option<&str>)> = findings
+        .iter()
+        .map(|finding| (finding.id.as_str(), finding.severity, finding.fixed.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("CVE-2024-0001", Severity::Critical, Some("3.0.13")),
+            ("CVE-2024-0002", Severity::Medium, None),
+        ]
+    );
+}
+
+#[test]
+fn grype_severities_map_onto_the_same_scale() {
+    let raw = r#"{
+      "matches": [
+        {"vulnerability": {"id": "GHSA-xxxx", "severity": "High", "fix": {"versions": ["2.32.0"]}},
+         "artifact": {"name": "requests", "version": "2.31.0"}},
+        {"vulnerability": {"id": "CVE-2023-9999", "severity": "Negligible", "fix": {"versions": []}},
+         "artifact": {"name": "tar", "version": "1.34"}}
+      ]
+    }"#;
+
+    let findings = scan::parse_grype(raw).expect("parse");
+    assert_eq!(findings.len(), 2);
+    assert_eq!(findings[0].package, "requests");
+    assert_eq!(findings[0].severity, Severity::High);
+    assert_eq!(findings[0].fixed.as_deref(), Some("2.32.0"));
+    assert_eq!(findings[1].severity, Severity::Low);
+    assert_eq!(findings[1].fixed, None);
+    assert!(Severity::Critical > Severity::High && Severity::Low > Severity::Unknown);
+}
+
+#[test]
+fn built_and_pulled_images_are_scanned() {
+    let env = envfile::parse("COMPOSE_PROJECT_NAME=geo\n");
+    let file = compose::parse(
+        "services:\n  traefik:\n    image: traefik:v2.11\n  postgres:\n    image: ${POSTGRES_IMAGE}\n",
+    )
+    .expect("parse");
+
+    let images = scan::images(Path::new("/srv/geo"), &env, &file);
+    let roles: Vec<(&str, &str)> = images.iter().map(|(role, image)| (*role, image.as_str())).collect();
+    assert_eq!(
+        roles,
+        vec![("hub", "geo-jupyterhub"), ("user", "mvre-user:latest"), ("proxy", "traefik:v2.11")]
+    );
+}
@@ -0,0 +1,55 @@
+use mvre_hub::schedule::{self, MaintenanceTime};
+
+#[test]
+fn maintenance_times_become_calendar_expressions() {
+    let weekly = MaintenanceTime::parse("sat 2:00").expect("weekly");
+    assert_eq!(weekly, MaintenanceTime { weekday: Some(5), minutes: 120 });
+    assert_eq!(weekly.calendar(), "Sat *-*-* 02:00:00");
+    assert_eq!(weekly.to_string(), "Sat 02:00");
+
+    let daily = MaintenanceTime::parse("23:45").expect("daily");
+    assert_eq!(daily.calendar(), "*-*-* 23:45:00");
+
+    assert!(MaintenanceTime::parse("Caturday 02:00").is_err());
+    assert!(MaintenanceTime::parse("Sat 24:00").is_err());
+    assert!(MaintenanceTime::parse("Sat 02:00 UTC").is_err());
+}
+
+#[test]
+fn announcements_move_to_the_previous_day() {
+    let stop = MaintenanceTime::parse("Mon 00:30").expect("stop");
+    assert_eq!(stop.earlier_by(60).to_string(), "Sun 23:30");
+    assert_eq!(stop.earlier_by(15).to_string(), "Mon 00:15");
+    assert_eq!(MaintenanceTime::parse("00:30").expect("daily").earlier_by(60).to_string(), "23:30");
+}
+
+#[test]
+fn exec_arguments_are_quoted_for_systemd() {
+    assert_eq!(
+        schedule::exec_arg(r#"Down 100% at "02:00", cost $0"#),
+        r#""Down 100%% at \"02:00\", cost $$0""#
+    );
+}
+
+#[test]
+fn custom_commands_keep_shell_variables_from_systemd() {
+    assert_eq!(
+        schedule::shell_exec(r#"echo "$HOME" > /tmp/100%"#),
+        r#"/bin/sh -c "echo \"$$HOME\" > /tmp/100%%""#
+    );
+}
+
+#[test]
+fn hub_jobs_are_pinned_to_their_deployment() {
+    let command = schedule::hub_command(std::path::Path::new("/srv/mvre hub"), "prune volumes").expect("command");
+    assert!(command.ends_with(r#" --deploy-dir "/srv/mvre hub" prune volumes"#), "{}", command);
+}
+
+#[test]
+fn maintenance_timers_are_named_per_deployment() {
+    assert_eq!(schedule::maintenance_unit("maintenance-stop", "geo"), "mvre-hub-job-maintenance-stop-geo");
+    assert_ne!(
+        schedule::maintenance_unit("maintenance-stop", "geo"),
+        schedule::maintenance_unit("maintenance-stop", "bio")
+    );
+}
@@ -0,0 +1,32 @@
+use std::collections::BTreeSet;
+
+use mvre_hub::secrets;
+
+fn set(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn database_restarts_before_its_clients_and_only_when_running() {
+    let defined = set(&["traefik", "postgres", "jupyterhub"]);
+    let running = set(&["traefik", "postgres", "jupyterhub"]);
+    let affected = ["jupyterhub", "postgres", "jupyterhub", "mlflow"];
+    assert_eq!(
+        secrets::restart_order(&["postgres", "jupyterhub", "mlflow", "jupyterhub"], &defined, &running),
+        vec!["postgres", "jupyterhub"]
+    );
+    assert_eq!(secrets::restart_order(&affected, &defined, &set(&["postgres"])), vec!["postgres"]);
+    assert!(secrets::restart_order(&affected, &defined, &set(&[])).is_empty());
+}
+
+#[test]
+fn role_and_password_are_quoted() {
+    assert_eq!(
+        secrets::alter_role_sql("hub", "0a1b"),
+        "ALTER ROLE \"hub\" WITH PASSWORD '0a1b';\n"
+    );
+    assert_eq!(
+        secrets::alter_role_sql("we\"ird", "it's"),
+        "ALTER ROLE \"we\"\"ird\" WITH PASSWORD 'it''s';\n"
+    );
+}
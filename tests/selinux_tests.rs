@@ -0,0 +1,21 @@
+use mvre_hub::selinux;
+
+#[test]
+fn enforcing_follows_the_enforce_flag() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let enforce = dir.path().join("enforce");
+    assert!(!selinux::enforcing(&enforce));
+    std::fs::write(&enforce, "0\n").expect("write");
+    assert!(!selinux::enforcing(&enforce));
+    std::fs::write(&enforce, "1\n").expect("write");
+    assert!(selinux::enforcing(&enforce));
+}
+
+#[test]
+fn doctor_blocks_unlabelled_mounts_on_enforcing_hosts() {
+    use mvre_hub::doctor::{self, Finding};
+
+    assert!(matches!(doctor::check_selinux(true, false), Finding::Blocker(_)));
+    assert!(matches!(doctor::check_selinux(true, true), Finding::Ok(_)));
+    assert!(matches!(doctor::check_selinux(false, false), Finding::Ok(_)));
+}
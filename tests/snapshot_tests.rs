@@ -0,0 +1,102 @@
+use std::fs;
+
+use mvre_hub::{cli::RollbackOptions, config::AppConfig, snapshot};
+
+#[test]
+fn rollback_restores_config_and_keeps_data() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let deploy = dir.path();
+    fs::write(deploy.join(".env"), "HUB_DOMAIN=hub.example.org\n").unwrap();
+    fs::write(deploy.join("docker-compose.yml"), "services: {}\n").unwrap();
+    fs::create_dir_all(deploy.join("jupyterhub_data")).unwrap();
+    fs::write(deploy.join("jupyterhub_data").join("state"), "data").unwrap();
+    fs::write(deploy.join("docker-compose.override.yml"), "services: {}\n").unwrap();
+
+    let info = snapshot::create(deploy, "test", false).expect("snapshot");
+    let stored = deploy.join(snapshot::SNAPSHOT_DIR).join(&info.id);
+    assert!(stored.join(".env").exists());
+    assert!(!stored.join("jupyterhub_data").exists());
+    assert!(!stored.join("docker-compose.override.yml").exists());
+
+    fs::write(deploy.join(".env"), "HUB_DOMAIN=typo.example.org\n").unwrap();
+    fs::write(deploy.join("extra.yml"), "x").unwrap();
+    fs::write(deploy.join("docker-compose.override.yml"), "services:\n  jupyterhub: {}\n").unwrap();
+
+    let app_config = AppConfig {
+        last_deploy_dir: Some(deploy.to_path_buf()),
+        ..AppConfig::default()
+    };
+    let opts = RollbackOptions {
+        to: Some(info.id.clone()),
+        list: false,
+        with_db: false,
+    };
+    snapshot::rollback(opts, &app_config).expect("rollback");
+
+    assert_eq!(fs::read_to_string(deploy.join(".env")).unwrap(), "HUB_DOMAIN=hub.example.org\n");
+    assert!(!deploy.join("extra.yml").exists());
+    assert!(deploy.join("jupyterhub_data").join("state").exists());
+    assert_eq!(
+        fs::read_to_string(deploy.join("docker-compose.override.yml")).unwrap(),
+        "services:\n  jupyterhub: {}\n"
+    );
+    assert_eq!(snapshot::list(deploy).unwrap().len(), 2);
+
+    snapshot::clear_all_but_snapshots(deploy).expect("clear");
+    assert!(!deploy.join(".env").exists());
+    assert!(deploy.join("docker-compose.override.yml").exists());
+}
+
+#[test]
+fn data_directories_survive_rollback_and_forced_redeploy() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let deploy = dir.path();
+    fs::write(deploy.join(".env"), "HUB_DOMAIN=hub.example.org\n").unwrap();
+    let submission = deploy.join("nbgrader").join("exchange").join("mosaic").join("inbound");
+    fs::create_dir_all(&submission).unwrap();
+    fs::create_dir_all(deploy.join("mlflow").join("data")).unwrap();
+
+    let info = snapshot::create(deploy, "test", false).expect("snapshot");
+    let stored = deploy.join(snapshot::SNAPSHOT_DIR).join(&info.id);
+    assert!(!stored.join("nbgrader").exists());
+    assert!(!stored.join("mlflow").exists());
+
+    // Made after the snapshot, so only the live directories have them.
+    fs::write(submission.join("alice+ps1.ipynb"), "{}").unwrap();
+    fs::write(deploy.join("mlflow").join("data").join("mlflow.db"), "runs").unwrap();
+    let app_config = AppConfig {
+        last_deploy_dir: Some(deploy.to_path_buf()),
+        ..AppConfig::default()
+    };
+    let opts = RollbackOptions {
+        to: Some(info.id),
+        list: false,
+        with_db: false,
+    };
+    snapshot::rollback(opts, &app_config).expect("rollback");
+    assert!(submission.join("alice+ps1.ipynb").exists());
+    assert!(deploy.join("mlflow").join("data").join("mlflow.db").exists());
+
+    snapshot::clear_all_but_snapshots(deploy).expect("clear");
+    assert!(!deploy.join(".env").exists());
+    assert!(submission.join("alice+ps1.ipynb").exists());
+    assert!(deploy.join("mlflow").join("data").join("mlflow.db").exists());
+}
+
+#[test]
+fn database_snapshots_copy_sqlite_and_leave_nothing_when_the_dump_fails() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let deploy = dir.path();
+    fs::write(deploy.join(".env"), "ENABLE_POSTGRES=false\n").unwrap();
+    fs::create_dir_all(deploy.join("jupyterhub_data")).unwrap();
+    fs::write(deploy.join("jupyterhub_data").join("jupyterhub.sqlite"), "db").unwrap();
+
+    let info = snapshot::create(deploy, "pre-upgrade", true).expect("snapshot");
+    assert!(info.includes_db);
+    assert!(deploy.join(snapshot::SNAPSHOT_DIR).join(&info.id).join("jupyterhub.sqlite").exists());
+
+    // No compose project to exec pg_dump in.
+    fs::write(deploy.join(".env"), "ENABLE_POSTGRES=true\nDB_HOST=postgres\n").unwrap();
+    assert!(snapshot::create(deploy, "pre-upgrade", true).is_err());
+    assert_eq!(fs::read_dir(deploy.join(snapshot::SNAPSHOT_DIR)).unwrap().count(), 1);
+}
@@ -0,0 +1,44 @@
+use mvre_hub::systemd::{self, Scope};
+
+#[test]
+fn user_timers_are_listed_from_unit_files() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::env::set_var("XDG_CONFIG_HOME", dir.path());
+    let unit_dir = dir.path().join("systemd").join("user");
+    std::fs::create_dir_all(&unit_dir).expect("unit dir");
+    std::fs::write(
+        unit_dir.join("mvre-hub-job-mirror.timer"),
+        "[Timer]\nOnCalendar=*-*-* 02:30\nPersistent=true\n",
+    )
+    .expect("timer");
+    std::fs::write(
+        unit_dir.join("mvre-hub-job-mirror.service"),
+        "[Service]\nType=oneshot\nExecStart=/bin/sh -c \"rsync -a /src /dst\"\n",
+    )
+    .expect("service");
+    std::fs::write(unit_dir.join("unrelated.timer"), "[Timer]\nOnCalendar=daily\n").expect("other");
+
+    let timers = systemd::list_timers("mvre-hub-job-").expect("list");
+    let mirror = timers
+        .iter()
+        .find(|timer| timer.name == "mvre-hub-job-mirror")
+        .expect("mirror timer");
+    assert_eq!(mirror.scope, Scope::User);
+    assert_eq!(mirror.on_calendar, "*-*-* 02:30");
+    assert_eq!(mirror.command, "/bin/sh -c \"rsync -a /src /dst\"");
+    assert!(timers.iter().all(|timer| timer.name != "unrelated"));
+    assert!(systemd::timer_installed(Scope::User, "mvre-hub-job-mirror"));
+}
+
+#[test]
+fn user_units_run_as_the_manager_owner_and_start_with_it() {
+    let dir = std::path::Path::new("/srv/mvre-hub");
+    let system = systemd::service_unit(dir, Scope::System, None);
+    assert!(system.contains(&format!("User={}\n", whoami::username())));
+    assert!(system.contains("WantedBy=multi-user.target\n"));
+
+    let user = systemd::service_unit(dir, Scope::User, None);
+    assert!(!user.contains("User="));
+    assert!(user.contains("WantedBy=default.target\n"));
+    assert!(user.contains("ExecStart=/usr/bin/env docker-compose -f /srv/mvre-hub/docker-compose.yml up\n"));
+}
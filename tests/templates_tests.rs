@@ -0,0 +1,544 @@
+use mvre_hub::{
+    answers,
+    cli::{LandingPath, ProxyVersion, TlsMode},
+    templates::{self, ComposeValues},
+};
+
+fn values() -> ComposeValues<'static> {
+    ComposeValues {
+        domain: "hub.example.org",
+        acme_email: "admin@example.org",
+        acme_staging: false,
+        tls: TlsMode::Acme,
+        production: false,
+        publishing: false,
+        http_port: 80,
+        https_port: 443,
+        proxy_version: ProxyVersion::V2,
+        security_headers: false,
+        csp: None,
+        rate_limit: None,
+        internal_tls: false,
+        selinux: false,
+        sandbox_profiles: false,
+        workspaces: false,
+        scratch: false,
+        branding: false,
+        landing: None,
+        dask_gateway: false,
+        mlflow: false,
+        file_browser: false,
+        logging_stack: false,
+        logging: None,
+        extra_services: &[],
+    }
+}
+
+fn service_list(values: &ComposeValues, service: &str, key: &str) -> Vec<String> {
+    let file: serde_yaml::Value = serde_yaml::from_str(&templates::docker_compose(values)).expect("valid compose");
+    serde_yaml::from_value(file["services"][service][key].clone()).expect("string list")
+}
+
+fn compose_service(values: &ComposeValues, service: &str) -> serde_yaml::Value {
+    let file: serde_yaml::Value = serde_yaml::from_str(&templates::docker_compose(values)).expect("valid compose");
+    file["services"][service].clone()
+}
+
+fn postgres_dependency(values: &ComposeValues, service: &str) -> String {
+    compose_service(values, service)["depends_on"]["postgres"]["condition"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn hub_labels(values: &ComposeValues) -> Vec<String> {
+    service_list(values, "jupyterhub", "labels")
+}
+
+#[test]
+fn hub_router_has_no_middlewares_by_default() {
+    assert!(!hub_labels(&values())
+        .iter()
+        .any(|label| label.contains("middlewares")));
+}
+
+#[test]
+fn middleware_chain_combines_rate_limit_and_headers() {
+    let labels = hub_labels(&ComposeValues {
+        security_headers: true,
+        csp: Some("default-src 'self'; img-src 'self' data: https://$cdn"),
+        rate_limit: Some((20, 40)),
+        ..values()
+    });
+    for expected in [
+        "traefik.http.middlewares.hub-ratelimit.ratelimit.average=20",
+        "traefik.http.middlewares.hub-ratelimit.ratelimit.burst=40",
+        "traefik.http.middlewares.hub-headers.headers.stsSeconds=31536000",
+        "traefik.http.middlewares.hub-headers.headers.customFrameOptionsValue=SAMEORIGIN",
+        "traefik.http.middlewares.hub-headers.headers.contentSecurityPolicy=default-src 'self'; img-src 'self' data: https://$$cdn",
+        "traefik.http.middlewares.hub-secure.chain.middlewares=hub-ratelimit,hub-headers",
+        "traefik.http.routers.jupyterhub.middlewares=hub-secure",
+    ] {
+        assert!(labels.iter().any(|label| label == expected), "missing {}", expected);
+    }
+}
+
+#[test]
+fn internal_tls_points_traefik_at_the_internal_ca() {
+    let values = ComposeValues {
+        internal_tls: true,
+        ..values()
+    };
+    assert!(hub_labels(&values)
+        .contains(&"traefik.http.services.jupyterhub.loadbalancer.server.scheme=https".to_string()));
+    assert!(service_list(&values, "jupyterhub", "volumes").contains(&"./internal-tls:/etc/mvre-hub/tls:ro".to_string()));
+    assert!(service_list(&values, "traefik", "command")
+        .contains(&"--serversTransport.rootCAs=/internal-ca/ca.crt".to_string()));
+    assert!(service_list(&values, "traefik", "volumes")
+        .contains(&"./internal-tls/ca.crt:/internal-ca/ca.crt:ro".to_string()));
+}
+
+#[test]
+fn hub_is_plain_http_without_internal_tls() {
+    assert!(!hub_labels(&values()).iter().any(|label| label.contains("scheme=https")));
+    assert!(!service_list(&values(), "traefik", "command")
+        .iter()
+        .any(|arg| arg.contains("rootCAs")));
+}
+
+#[test]
+fn acme_staging_uses_the_staging_endpoint_and_its_own_store() {
+    let production = service_list(&values(), "traefik", "command");
+    assert!(production.contains(&"--certificatesresolvers.letsencrypt.acme.storage=/certs/acme.json".to_string()));
+    assert!(!production.iter().any(|arg| arg.contains("caserver")));
+
+    let staging = service_list(
+        &ComposeValues {
+            acme_staging: true,
+            ..values()
+        },
+        "traefik",
+        "command",
+    );
+    assert!(staging.contains(
+        &"--certificatesresolvers.letsencrypt.acme.caserver=https://acme-staging-v02.api.letsencrypt.org/directory"
+            .to_string()
+    ));
+    assert!(staging.contains(&"--certificatesresolvers.letsencrypt.acme.storage=/certs/acme-staging.json".to_string()));
+}
+
+#[test]
+fn self_signed_mode_serves_the_local_certificate_without_acme() {
+    let values = ComposeValues {
+        tls: TlsMode::SelfSigned,
+        ..values()
+    };
+    let command = service_list(&values, "traefik", "command");
+    assert!(command.contains(&"--providers.file.filename=/certs/local-tls.yml".to_string()));
+    assert!(!command.iter().any(|arg| arg.contains("certificatesresolvers")));
+    assert!(service_list(&values, "traefik", "volumes")
+        .contains(&"./local-tls/server.key:/local-tls/server.key:ro".to_string()));
+    assert!(!templates::docker_compose(&values).contains("certresolver"));
+    assert!(hub_labels(&values).contains(&"traefik.http.routers.jupyterhub.tls=true".to_string()));
+}
+
+#[test]
+fn tls_off_routes_plain_http() {
+    let values = ComposeValues {
+        tls: TlsMode::Off,
+        ..values()
+    };
+    assert!(service_list(&values, "traefik", "command").contains(&"--entrypoints.web.address=:80".to_string()));
+    let labels = hub_labels(&values);
+    assert!(labels.contains(&"traefik.http.routers.jupyterhub.entrypoints=web".to_string()));
+    assert!(!labels.iter().any(|label| label.contains(".tls")));
+}
+
+#[test]
+fn extra_services_are_appended_with_their_named_volumes() {
+    let services = answers::parse(
+        r#"
+[extra_services.docs]
+image = "nginx:alpine"
+volumes = ["./docs:/usr/share/nginx/html:ro", "docs_cache:/var/cache/nginx"]
+labels = ["traefik.enable=true", "traefik.http.routers.docs.rule=PathPrefix(`/docs`)", "traefik.http.routers.docs.tls.certresolver=letsencrypt"]
+
+[extra_services.cron]
+image = "alpine:3.20"
+command = ["sh", "-c", "echo \"nightly: $$HOME\""]
+environment = { TZ = "Europe/Berlin" }
+networks = []
+"#,
+    )
+    .expect("parse")
+    .extra_services
+    .expect("extra services");
+    let extra: Vec<_> = services.into_iter().collect();
+    let values = ComposeValues {
+        tls: TlsMode::SelfSigned,
+        extra_services: &extra,
+        ..values()
+    };
+    let file: serde_yaml::Value = serde_yaml::from_str(&templates::docker_compose(&values)).expect("valid compose");
+
+    assert_eq!(file["services"]["docs"]["image"], "nginx:alpine");
+    assert_eq!(service_list(&values, "docs", "networks"), vec!["proxy"]);
+    assert_eq!(
+        service_list(&values, "docs", "labels"),
+        vec!["traefik.enable=true", "traefik.http.routers.docs.rule=PathPrefix(`/docs`)"]
+    );
+    assert!(file["volumes"]["docs_cache"].is_null());
+    assert!(file["volumes"].get("./docs").is_none());
+    assert_eq!(
+        service_list(&values, "cron", "command"),
+        vec!["sh", "-c", "echo \"nightly: $$HOME\""]
+    );
+    assert_eq!(file["services"]["cron"]["environment"]["TZ"], "Europe/Berlin");
+    assert!(file["services"]["cron"].get("networks").is_none());
+    assert!(file["services"]["jupyterhub"].is_mapping());
+}
+
+#[test]
+fn selinux_relabels_bind_mounts_but_not_the_docker_socket() {
+    let values = ComposeValues {
+        selinux: true,
+        publishing: true,
+        ..values()
+    };
+    let hub = service_list(&values, "jupyterhub", "volumes");
+    assert!(hub.contains(&"./hub/jupyterhub_config.py:/etc/jupyterhub/jupyterhub_config.py:ro,Z".to_string()));
+    assert!(hub.contains(&"./jupyterhub_data:/srv/jupyterhub:Z".to_string()));
+    assert!(hub.contains(&"${DOCKER_SOCKET:-/var/run/docker.sock}:/var/run/docker.sock".to_string()));
+    assert_eq!(service_list(&values, "jupyterhub", "security_opt"), vec!["label=disable"]);
+    assert_eq!(service_list(&values, "traefik", "security_opt"), vec!["label=disable"]);
+    assert!(service_list(&values, "published", "volumes").contains(&"./published:/srv/published:ro,z".to_string()));
+}
+
+#[test]
+fn bind_mounts_are_unlabelled_without_selinux() {
+    let compose = templates::docker_compose(&ComposeValues {
+        publishing: true,
+        internal_tls: true,
+        ..values()
+    });
+    assert!(!compose.contains(",z") && !compose.contains(",Z") && !compose.contains(":Z"));
+    assert!(!compose.contains("security_opt"));
+}
+
+#[test]
+fn sandbox_profiles_are_mounted_into_the_hub() {
+    let sandboxed = ComposeValues {
+        sandbox_profiles: true,
+        ..values()
+    };
+    assert!(service_list(&sandboxed, "jupyterhub", "volumes").contains(&"./sandbox:/etc/mvre-hub/sandbox:ro".to_string()));
+    assert!(!service_list(&values(), "jupyterhub", "volumes")
+        .iter()
+        .any(|volume| volume.contains("sandbox")));
+}
+
+#[test]
+fn workspace_root_is_visible_to_the_hub() {
+    let volumes = service_list(&ComposeValues { workspaces: true, ..values() }, "jupyterhub", "volumes");
+    assert!(volumes.contains(&"${WORKSPACE_HOST_PATH}:/srv/workspaces:ro,rslave".to_string()));
+    let labelled = service_list(&ComposeValues { workspaces: true, selinux: true, ..values() }, "jupyterhub", "volumes");
+    assert!(labelled.contains(&"${WORKSPACE_HOST_PATH}:/srv/workspaces:ro,z,rslave".to_string()));
+}
+
+#[test]
+fn hub_reads_the_announcement_on_each_render() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"path = "/srv/jupyterhub/announcement.html""#));
+    assert!(config.contains(r#"c.JupyterHub.template_vars = {"announcement": Announcement()}"#));
+}
+
+#[test]
+fn hub_reads_user_env_at_each_spawn() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"open("/srv/jupyterhub/user-env.toml", "rb")"#));
+    assert!(config.contains("pre_spawn_hooks.append(add_user_env)"));
+    let hook = config.find("pre_spawn_hooks.append(add_user_env)").unwrap();
+    assert!(hook > config.find("c.Spawner.environment = env").unwrap());
+}
+
+#[test]
+fn group_shares_are_mounted_for_members_only() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains("c.JupyterHub.load_groups = hub_groups"));
+    assert!(config.contains("pre_spawn_hooks.append(mount_group_shares)"));
+    assert!(config.contains(r#"volumes[host] = {"bind": f"/home/jovyan/groups/{group}", "mode": mode}"#));
+}
+
+#[test]
+fn nbgrader_hides_instructor_tools_from_students() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"volumes[nbgrader_host + "/exchange"] = {"bind": "/srv/nbgrader/exchange", "mode": "rw"}"#));
+    assert!(config.contains("hub_groups.setdefault(nbgrader_instructors, {\"users\": []})"));
+    assert!(config.contains("pre_spawn_hooks.append(restrict_nbgrader)"));
+    assert!(config.contains(r#""JUPYTER_CONFIG_PATH": "/srv/nbgrader/student","#));
+}
+
+#[test]
+fn entitlements_restrict_logins_by_their_claim() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"required_entitlements = os.environ.get("REQUIRED_ENTITLEMENTS", "")"#));
+    assert!(config.contains(r#"entitlement_claim = os.environ.get("ENTITLEMENT_CLAIM", "eduperson_entitlement")"#));
+    assert!(config.contains(r#"settings["claim_groups_key"] = entitlement_claim"#));
+    assert!(config.contains(r#"for entitlement in required_entitlements.split(",")"#));
+}
+
+#[test]
+fn entitlements_apply_to_allow_listed_users_too() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"settings["post_auth_hook"] = require_entitlements(entitlement_claim, required)"#));
+    assert!(config.contains("if required.isdisjoint(entitlements):\n            return None"));
+    // The allow-list alone decides who is allowed; entitlements only narrow it.
+    assert!(config.contains("if not allowed_users:\n            settings[\"allowed_groups\"] = required"));
+}
+
+#[test]
+fn low_storage_or_memory_refuses_spawns_with_a_message() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"min_free_disk_gb = os.environ.get("SPAWN_MIN_FREE_DISK_GB")"#));
+    assert!(config.contains(r#"min_free_mem_gb = os.environ.get("SPAWN_MIN_FREE_MEM_GB")"#));
+    assert!(config.contains(r#"shutil.disk_usage("/srv/jupyterhub").free"#));
+    assert!(config.contains(r#"if line.startswith("MemAvailable:"):"#));
+    assert!(config.contains("self.jupyterhub_message = message"));
+    // Without a threshold the hook is not installed at all.
+    assert!(config.contains("if min_free_disk_gb or min_free_mem_gb:\n    pre_spawn_hooks.append(check_capacity)"));
+}
+
+#[test]
+fn resource_limits_follow_users_then_groups_and_cap_requests() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"user_limits = parse_limits(os.environ.get("USER_LIMITS", ""))"#));
+    assert!(config.contains(r#"group_limits = parse_limits(os.environ.get("GROUP_LIMITS", ""))"#));
+    assert!(config.contains("mem = max(group_mems, key=byte_count)"));
+    assert!(config.contains(r#"raise SpawnRefused(f"You may use at most {spawner.cpu_limit:g} CPUs.")"#));
+    assert!(config.contains("pre_spawn_hooks.append(apply_resource_limits)"));
+}
+
+#[test]
+fn tenants_prefix_user_containers_and_volumes() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"resource_prefix = os.environ.get("RESOURCE_PREFIX", "")"#));
+    assert!(config.contains(r#"c.DockerSpawner.prefix = resource_prefix + "jupyter""#));
+    assert!(config.contains(r#"volumes = {resource_prefix + "jupyterhub-user-{username}": "/home/jovyan/work"}"#));
+}
+
+#[test]
+fn group_cullers_only_see_their_group() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"add_culler(f"idle-culler-{group}", timeout, f"!group={group}")"#));
+    assert!(config.contains(r#"add_culler("idle-culler", cull_timeout)"#));
+}
+
+#[test]
+fn branding_is_mounted_read_only() {
+    let volumes = service_list(&ComposeValues { branding: true, ..values() }, "jupyterhub", "volumes");
+    assert!(volumes.contains(&"./branding:/etc/mvre-hub/branding:ro".to_string()));
+    let volumes = service_list(&values(), "jupyterhub", "volumes");
+    assert!(!volumes.iter().any(|volume| volume.contains("branding")));
+}
+
+#[test]
+fn landing_service_serves_the_site_next_to_the_hub() {
+    assert!(!templates::docker_compose(&values()).contains("landing:"));
+    let values = ComposeValues {
+        landing: Some(LandingPath::Docs),
+        ..values()
+    };
+    let volumes = service_list(&values, "landing", "volumes");
+    assert!(volumes.contains(&"${LANDING_SITE}:/srv/landing:ro".to_string()));
+    assert!(volumes.contains(&"./landing/nginx.conf:/etc/nginx/conf.d/default.conf:ro".to_string()));
+    assert!(service_list(&values, "landing", "labels")
+        .contains(&"traefik.http.routers.landing.rule=Host(`hub.example.org`) && PathPrefix(`/docs`)".to_string()));
+}
+
+#[test]
+fn dask_gateway_joins_only_the_users_network() {
+    assert!(!templates::docker_compose(&values()).contains("dask-gateway:"));
+    let values = ComposeValues {
+        dask_gateway: true,
+        internal_tls: true,
+        ..values()
+    };
+    let file: serde_yaml::Value = serde_yaml::from_str(&templates::docker_compose(&values)).expect("valid compose");
+    let gateway = &file["services"]["dask-gateway"];
+    assert_eq!(gateway["networks"], serde_yaml::from_str::<serde_yaml::Value>("[users]").unwrap());
+    assert!(gateway["env_file"].is_null());
+    assert_eq!(gateway["build"]["dockerfile"].as_str(), Some("dask-gateway/Dockerfile"));
+    let volumes = service_list(&values, "dask-gateway", "volumes");
+    assert!(volumes.contains(&"./internal-tls/ca.crt:/etc/mvre-hub/tls/ca.crt:ro".to_string()));
+    assert!(!volumes.iter().any(|volume| volume.contains("hub.key")));
+}
+
+#[test]
+fn dask_gateway_client_matches_the_server() {
+    assert!(!templates::user_requirements(false).contains("dask-gateway"));
+    let requirements = templates::user_requirements(true);
+    assert!(requirements.lines().any(|line| line == mvre_hub::dask::client_requirement()));
+    assert!(mvre_hub::dask::dockerfile().contains(&format!("dask-gateway-server[local]=={}", mvre_hub::dask::GATEWAY_VERSION)));
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#""url": "http://dask-gateway:8000""#));
+    assert!(mvre_hub::dask::gateway_config().contains(&format!(r#"c.Proxy.prefix = "{}""#, mvre_hub::dask::SERVICE_PREFIX)));
+}
+
+#[test]
+fn mlflow_is_routed_at_its_subdomain() {
+    assert!(!templates::docker_compose(&values()).contains("mlflow:"));
+    let values = ComposeValues {
+        mlflow: true,
+        ..values()
+    };
+    assert!(service_list(&values, "mlflow", "labels")
+        .contains(&"traefik.http.routers.mlflow.rule=Host(`mlflow.hub.example.org`)".to_string()));
+    assert!(service_list(&values, "mlflow", "volumes").contains(&"${MLFLOW_ARTIFACTS}:/mlflow/artifacts".to_string()));
+    assert_eq!(service_list(&values, "mlflow", "networks"), vec!["proxy", "users"]);
+
+    let production = ComposeValues {
+        production: true,
+        ..values
+    };
+    assert_eq!(service_list(&production, "mlflow", "networks"), vec!["proxy", "users", "hub"]);
+    assert_eq!(postgres_dependency(&production, "mlflow"), "service_healthy");
+    let tracking = format!(r#"env["MLFLOW_TRACKING_URI"] = "{}""#, mvre_hub::mlflow::TRACKING_URI);
+    assert!(templates::jupyterhub_config().contains(&tracking));
+}
+
+#[test]
+fn mlflow_keeps_its_own_database() {
+    let script = mvre_hub::mlflow::start_script();
+    assert!(script.contains(r#"cur.execute('CREATE DATABASE "mlflow"')"#));
+    assert!(script.contains(r#""postgresql://{}:{}@{}:{}/mlflow".format("#));
+    assert!(script.contains("--serve-artifacts"));
+}
+
+#[test]
+fn file_browser_sits_behind_hub_login() {
+    assert!(!templates::docker_compose(&values()).contains("files-auth"));
+    let values = ComposeValues {
+        file_browser: true,
+        ..values()
+    };
+    let labels = service_list(&values, "files", "labels");
+    assert!(labels.contains(&"traefik.http.routers.files.middlewares=files-auth".to_string()));
+    assert!(labels.contains(&"traefik.http.routers.files.rule=Host(`hub.example.org`) && PathPrefix(`/files`)".to_string()));
+    assert!(service_list(&values, "files", "volumes").contains(&"${DATASET_HOST_PATH}:/srv/files:ro".to_string()));
+
+    let file: serde_yaml::Value = serde_yaml::from_str(&templates::docker_compose(&values)).expect("valid compose");
+    let auth = &file["services"]["files-auth"]["environment"];
+    assert_eq!(auth["URL_PATH"].as_str(), Some("/files/_oauth"));
+    assert_eq!(auth["PROVIDERS_GENERIC_OAUTH_TOKEN_URL"].as_str(), Some("http://jupyterhub:8000/hub/api/oauth2/token"));
+    assert_eq!(auth["PROVIDERS_GENERIC_OAUTH_CLIENT_ID"].as_str(), Some(mvre_hub::filebrowser::OAUTH_CLIENT_ID));
+
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#""oauth_client_id": "service-file-browser""#));
+    assert!(config.contains(r#"/files/_oauth""#));
+}
+
+#[test]
+fn scratch_root_is_mounted_into_the_hub_for_the_spawn_hook() {
+    let volumes = service_list(&ComposeValues { scratch: true, ..values() }, "jupyterhub", "volumes");
+    assert!(volumes.contains(&"${SCRATCH_ROOT}:/srv/scratch".to_string()));
+    assert!(!templates::docker_compose(&values()).contains("SCRATCH_ROOT"));
+
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"volumes[scratch_root + "/{username}"] = {"bind": "/home/jovyan/scratch", "mode": "rw"}"#));
+    assert!(config.contains("pre_spawn_hooks.append(create_scratch)"));
+}
+
+#[test]
+fn logging_stack_keeps_loki_internal() {
+    let compose = templates::docker_compose(&values());
+    assert!(!compose.contains("loki") && !compose.contains("\nvolumes:"));
+
+    let values = ComposeValues {
+        logging_stack: true,
+        ..values()
+    };
+    let file: serde_yaml::Value = serde_yaml::from_str(&templates::docker_compose(&values)).expect("valid compose");
+    assert_eq!(file["networks"]["logging"]["internal"].as_bool(), Some(true));
+    for volume in ["loki_data", "promtail_data", "grafana_data"] {
+        assert!(file["volumes"].get(volume).is_some(), "{} declared", volume);
+    }
+    assert_eq!(service_list(&values, "loki", "networks"), vec!["logging"]);
+    assert_eq!(service_list(&values, "grafana", "networks"), vec!["proxy", "logging"]);
+    assert!(service_list(&values, "promtail", "volumes")
+        .contains(&"${DOCKER_SOCKET:-/var/run/docker.sock}:/var/run/docker.sock:ro".to_string()));
+    assert!(service_list(&values, "grafana", "labels")
+        .contains(&"traefik.http.routers.grafana.rule=Host(`hub.example.org`) && PathPrefix(`/grafana`)".to_string()));
+
+    let production: serde_yaml::Value = serde_yaml::from_str(&templates::docker_compose(&ComposeValues {
+        production: true,
+        ..values
+    }))
+    .expect("valid compose");
+    assert!(production["volumes"].get("postgres_data").is_some());
+}
+
+#[test]
+fn rendered_compose_rotates_logs_of_all_services() {
+    let values = ComposeValues {
+        production: true,
+        logging: Some(mvre_hub::logconfig::ContainerLogging {
+            driver: mvre_hub::cli::LogDriver::Local,
+            max_size: "20m",
+            max_file: 3,
+        }),
+        ..values()
+    };
+    let compose = templates::docker_compose(&values);
+    assert!(mvre_hub::compose::parse(&compose).is_ok());
+    let file: serde_yaml::Value = serde_yaml::from_str(&compose).expect("valid compose");
+    let services = file["services"].as_mapping().expect("services");
+    assert!(services.len() >= 4);
+    for (name, service) in services {
+        assert_eq!(service["logging"]["driver"].as_str(), Some("local"), "{:?}", name);
+        assert_eq!(service["logging"]["options"]["max-size"].as_str(), Some("20m"));
+    }
+    assert!(templates::jupyterhub_config().contains(r#"extra_host_config["log_config"] = {"type": log_driver, "config": log_options}"#));
+}
+
+#[test]
+fn spawn_limits_are_only_set_when_given() {
+    let config = templates::jupyterhub_config();
+    assert!(config.contains(r#"concurrent_spawn_limit = os.environ.get("CONCURRENT_SPAWN_LIMIT")"#));
+    assert!(config.contains("    c.JupyterHub.concurrent_spawn_limit = int(concurrent_spawn_limit)"));
+    assert!(config.contains("    c.JupyterHub.active_server_limit = int(active_server_limit)"));
+}
+
+#[test]
+fn services_wait_for_a_healthy_database() {
+    let development = values();
+    assert!(compose_service(&development, "jupyterhub")["depends_on"].is_null());
+    assert!(compose_service(&development, "postgres").is_null());
+
+    let production = ComposeValues {
+        production: true,
+        ..values()
+    };
+    assert_eq!(postgres_dependency(&production, "jupyterhub"), "service_healthy");
+    let check = &compose_service(&production, "postgres")["healthcheck"];
+    assert_eq!(check["test"][0], "CMD-SHELL");
+    assert!(check["test"][1].as_str().unwrap().starts_with("pg_isready"));
+}
+
+#[test]
+fn hub_and_proxy_report_their_health() {
+    let hub = compose_service(&values(), "jupyterhub");
+    let probe = hub["healthcheck"]["test"][3].as_str().unwrap();
+    assert!(probe.contains("http://localhost:8000/hub/health"));
+    let internal = compose_service(
+        &ComposeValues {
+            internal_tls: true,
+            ..values()
+        },
+        "jupyterhub",
+    );
+    assert!(internal["healthcheck"]["test"][3].as_str().unwrap().contains("https://localhost:8000/hub/health"));
+
+    let proxy: Vec<String> =
+        serde_yaml::from_value(compose_service(&values(), "traefik")["healthcheck"]["test"].clone()).unwrap();
+    assert_eq!(proxy, vec!["CMD", "traefik", "healthcheck", "--ping"]);
+}
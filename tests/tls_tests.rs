@@ -0,0 +1,72 @@
+use std::process::Command;
+
+use mvre_hub::tls;
+
+fn openssl_available() -> bool {
+    Command::new("openssl").arg("version").output().is_ok()
+}
+
+#[test]
+fn internal_certs_are_generated_once_and_chain_to_the_ca() {
+    if !openssl_available() {
+        return;
+    }
+    let dir = tempfile::tempdir().expect("tempdir");
+    assert!(tls::ensure_internal_certs(dir.path()).expect("generate"));
+    let ca = tls::ca_cert(dir.path());
+    let before = std::fs::read(&ca).expect("ca");
+    assert!(!tls::ensure_internal_certs(dir.path()).expect("regenerate"));
+    assert_eq!(std::fs::read(&ca).expect("ca"), before);
+
+    let certs = dir.path().join(tls::INTERNAL_TLS_DIR);
+    let verify = Command::new("openssl")
+        .arg("verify")
+        .arg("-CAfile")
+        .arg(&ca)
+        .arg(certs.join("hub.crt"))
+        .output()
+        .expect("openssl verify");
+    assert!(verify.status.success());
+    assert!(!certs.join("hub.csr").exists());
+}
+
+#[test]
+fn local_certs_keep_the_ca_and_follow_the_domain() {
+    if !openssl_available() {
+        return;
+    }
+    let dir = tempfile::tempdir().expect("tempdir");
+    let certs = dir.path().join(tls::LOCAL_TLS_DIR);
+    assert!(tls::ensure_local_certs(dir.path(), "hub.ship.lan").expect("generate"));
+    assert!(!tls::ensure_local_certs(dir.path(), "hub.ship.lan").expect("unchanged"));
+    let ca = std::fs::read(certs.join("ca.crt")).expect("ca");
+
+    assert!(tls::ensure_local_certs(dir.path(), "10.0.0.5").expect("new domain"));
+    assert_eq!(std::fs::read(certs.join("ca.crt")).expect("ca"), ca);
+    let verify = Command::new("openssl")
+        .arg("verify")
+        .arg("-CAfile")
+        .arg(certs.join("ca.crt"))
+        .arg("-verify_ip")
+        .arg("10.0.0.5")
+        .arg(certs.join("server.crt"))
+        .output()
+        .expect("openssl verify");
+    assert!(verify.status.success(), "{}", String::from_utf8_lossy(&verify.stdout));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |name: &str| std::fs::metadata(certs.join(name)).expect("metadata").permissions().mode() & 0o777;
+        assert_eq!(mode("ca.key"), 0o600);
+        assert_eq!(mode("server.key"), 0o600);
+        assert_eq!(mode("server.crt"), 0o644);
+    }
+}
+
+#[test]
+fn local_certificates_name_ip_addresses_as_such() {
+    assert_eq!(tls::subject_alt_names("hub.ship.lan"), "DNS:hub.ship.lan,DNS:localhost");
+    assert_eq!(tls::subject_alt_names("192.168.1.20"), "IP:192.168.1.20,DNS:localhost");
+    assert!(tls::local_tls_config().contains("certFile: /local-tls/server.crt"));
+}
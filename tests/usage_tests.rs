@@ -0,0 +1,36 @@
+use mvre_hub::usage::{self, Sample};
+
+fn sample(user: &str, interval: u64, cpu_percent: f64, mem_bytes: u64) -> Sample {
+    Sample {
+        timestamp: 0,
+        user: user.to_string(),
+        interval,
+        cpu_percent,
+        mem_bytes,
+    }
+}
+
+#[test]
+fn samples_aggregate_into_hours_per_user() {
+    let samples = vec![
+        sample("alice", 1800, 200.0, 2_000_000_000),
+        sample("bob", 3600, 50.0, 1_000_000_000),
+        sample("alice", 1800, 100.0, 4_000_000_000),
+    ];
+
+    let usage = usage::aggregate(&samples);
+    assert_eq!(usage.len(), 2);
+    assert_eq!(usage[0].user, "alice");
+    assert!((usage[0].runtime_hours - 1.0).abs() < 1e-9);
+    assert!((usage[0].cpu_core_hours - 1.5).abs() < 1e-9);
+    assert!((usage[0].mem_gb_hours - 3.0).abs() < 1e-9);
+    assert_eq!(usage[0].peak_mem_bytes, 4_000_000_000);
+
+    let csv = usage::to_csv(&usage);
+    assert_eq!(
+        csv,
+        "user,runtime_hours,cpu_core_hours,mem_gb_hours,peak_mem_bytes\n\
+         alice,1.00,1.50,3.00,4000000000\n\
+         bob,1.00,0.50,1.00,1000000000\n"
+    );
+}
@@ -0,0 +1,74 @@
+use mvre_hub::{
+    cli::UserEnvTarget,
+    userenv::{self, UserEnv},
+};
+
+const TABLE: &str = r#"
+[groups.course-a]
+MOSAIC_DATA = "/data/mosaic/course-a"
+LEVEL = "a"
+
+[groups.course-b]
+MOSAIC_DATA = "/data/mosaic/course-b"
+
+[users.alice]
+MOSAIC_DATA = "/data/mosaic/alice"
+PROJECT_TOKEN = "abc"
+"#;
+
+#[test]
+fn users_override_their_groups_and_groups_apply_in_name_order() {
+    let table = UserEnv::parse(TABLE).expect("parse");
+    let groups = vec!["course-b".to_string(), "course-a".to_string()];
+
+    let bob = table.for_user("bob", &groups);
+    assert_eq!(bob["MOSAIC_DATA"], "/data/mosaic/course-b");
+    assert_eq!(bob["LEVEL"], "a");
+
+    let alice = table.for_user("alice", &groups);
+    assert_eq!(alice["MOSAIC_DATA"], "/data/mosaic/alice");
+    assert_eq!(alice["PROJECT_TOKEN"], "abc");
+
+    assert!(table.for_user("carol", &[]).is_empty());
+}
+
+#[test]
+fn credentials_are_masked_for_display() {
+    let masked = UserEnv::parse(TABLE).expect("parse").masked();
+    assert_eq!(masked.users["alice"]["PROJECT_TOKEN"], "***");
+    assert_eq!(masked.users["alice"]["MOSAIC_DATA"], "/data/mosaic/alice");
+}
+
+#[test]
+fn names_must_be_plain_and_not_the_hubs() {
+    assert!(userenv::validate_name("MOSAIC_DATA").is_ok());
+    assert!(userenv::validate_name("_private1").is_ok());
+    for name in ["", "1ST", "MY-VAR", "A B", "JUPYTERHUB_API_TOKEN"] {
+        assert!(userenv::validate_name(name).is_err(), "{}", name);
+    }
+    assert!(UserEnv::parse("[users.alice]\n\"BAD-NAME\" = \"x\"\n").is_err());
+    assert!(UserEnv::parse("[people.alice]\nA = \"x\"\n").is_err());
+}
+
+#[test]
+fn table_round_trips_through_the_deployment() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    assert_eq!(UserEnv::load(dir.path()).expect("load"), UserEnv::default());
+
+    let table = UserEnv::parse(TABLE).expect("parse");
+    table.save(dir.path()).expect("save");
+    assert!(dir.path().join(userenv::USER_ENV_FILE).exists());
+    assert_eq!(UserEnv::load(dir.path()).expect("load"), table);
+}
+
+#[test]
+fn groups_are_named_as_in_the_groups_section() {
+    let group = |name: &str| UserEnvTarget {
+        user: None,
+        group: Some(name.to_string()),
+    };
+    assert!(userenv::validate_target(&group("course-a")).is_ok());
+    for name in ["", "course a", "staff]", "a.b", "x\"y"] {
+        assert!(userenv::validate_target(&group(name)).is_err(), "{}", name);
+    }
+}
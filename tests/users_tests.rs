@@ -0,0 +1,13 @@
+use mvre_hub::users;
+
+#[test]
+fn allow_list_files_accept_lines_commas_and_comments() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("allowed.txt");
+    std::fs::write(&path, "# MOSAiC PIs\nalice\nbob, carol  # shore team\n\nalice\n").expect("write");
+
+    assert_eq!(users::read_list_file(&path).expect("read"), vec!["alice", "bob", "carol"]);
+
+    std::fs::write(&path, "not allowed\n").expect("write");
+    assert!(users::read_list_file(&path).is_err());
+}
@@ -0,0 +1,46 @@
+use mvre_hub::util;
+
+#[test]
+fn durations_parse_with_units() {
+    assert_eq!(util::parse_duration("90").unwrap(), 90);
+    assert_eq!(util::parse_duration("30m").unwrap(), 1800);
+    assert_eq!(util::parse_duration("2d").unwrap(), 172_800);
+    assert_eq!(util::parse_duration("1y").unwrap(), 31_536_000);
+    assert!(util::parse_duration("2 days").is_err());
+    assert!(util::parse_duration("h").is_err());
+    assert!(util::parse_duration("99999999999999999d").is_err());
+}
+
+#[test]
+fn timestamps_format_as_utc() {
+    assert_eq!(util::format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(util::format_timestamp(1_709_210_096), "2024-02-29T12:34:56Z");
+}
+
+#[test]
+fn timestamps_parse_back_with_or_without_separators() {
+    assert_eq!(util::parse_timestamp("2024-02-29T12:34:56Z"), Some(1_709_210_096));
+    assert_eq!(util::parse_timestamp("20240229T123456Z"), Some(1_709_210_096));
+    assert_eq!(util::parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+    assert_eq!(util::parse_timestamp("2024-13-01T00:00:00Z"), None);
+    assert_eq!(util::parse_timestamp("20240229"), None);
+}
+
+#[test]
+fn docker_sizes_parse_and_format() {
+    assert_eq!(util::parse_size("0B").unwrap(), 0);
+    assert_eq!(util::parse_size("512kB").unwrap(), 512_000);
+    assert_eq!(util::parse_size("1.5GB").unwrap(), 1_500_000_000);
+    assert_eq!(util::parse_size("1.5GiB").unwrap(), 1_610_612_736);
+    assert!(util::parse_size("12 parsecs").is_err());
+    assert_eq!(util::format_size(999), "999B");
+    assert_eq!(util::format_size(1_500_000_000), "1.5GB");
+}
+
+#[test]
+fn rfc3339_offsets_are_applied() {
+    assert_eq!(util::parse_rfc3339("1970-01-01T00:00:00Z"), Some(0));
+    assert_eq!(util::parse_rfc3339("1970-01-01T01:00:00.5+01:00"), Some(0));
+    assert_eq!(util::parse_rfc3339("1970-01-01T00:30:00-00:30"), Some(3600));
+    assert_eq!(util::parse_rfc3339("yesterday"), None);
+}
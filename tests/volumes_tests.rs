@@ -0,0 +1,72 @@
+use mvre_hub::{
+    hubapi::User,
+    volumes::{self, UserVolume},
+};
+
+#[test]
+fn volumes_are_matched_to_hub_users_by_workspace_name() {
+    let users: Vec<User> = serde_json::from_str(
+        r#"[
+            {"name": "alice", "last_activity": "2025-01-01T00:00:00Z"},
+            {"name": "bob@example.org", "servers": {"": {"name": "", "ready": true, "url": "/user/bob@example.org/"}}}
+        ]"#,
+    )
+    .expect("users");
+    let names = vec![
+        "jupyterhub-user-alice".to_string(),
+        "jupyterhub-user-bob-40example-2Eorg".to_string(),
+        "jupyterhub-user-carol".to_string(),
+        // Another tenant's user of the same name.
+        "mvre-geo-jupyterhub-user-alice".to_string(),
+        "mvre_hub_data".to_string(),
+    ];
+    let matched = volumes::match_users(&names, &users, "jupyterhub-user-");
+    assert_eq!(matched.len(), 3);
+    assert_eq!(matched[0].user.as_deref(), Some("alice"));
+    assert_eq!(matched[0].last_activity, Some(1_735_689_600));
+    assert!(!matched[0].running);
+    assert_eq!(matched[1].user.as_deref(), Some("bob@example.org"));
+    assert!(matched[1].running);
+    assert_eq!(matched[2].user, None);
+}
+
+#[test]
+fn stale_volumes_are_judged_by_their_last_use() {
+    let volume = |name: &str, user: Option<&str>| UserVolume {
+        volume: format!("jupyterhub-user-{}", name),
+        user: user.map(str::to_string),
+        created_at: Some(1_600_000_000),
+        ..UserVolume::default()
+    };
+    let now = 1_792_000_000; // 2026-10-14
+    let year = 365 * 86_400;
+    let volumes = vec![
+        UserVolume {
+            last_activity: Some(now - 2 * year),
+            ..volume("gone", Some("gone"))
+        },
+        // A recent container start counts even when the hub saw no activity.
+        UserVolume {
+            last_activity: Some(now - 2 * year),
+            last_started: Some(now - 30 * 86_400),
+            ..volume("started", Some("started"))
+        },
+        volume("never-used", Some("never-used")),
+        UserVolume {
+            running: true,
+            ..volume("running", Some("running"))
+        },
+        volume("deleted", None),
+        UserVolume {
+            created_at: None,
+            ..volume("unknown", Some("unknown"))
+        },
+    ];
+    let stale: Vec<&str> = volumes::stale(&volumes, year, now)
+        .iter()
+        .map(|volume| volume.volume.as_str())
+        .collect();
+    assert_eq!(stale, vec!["jupyterhub-user-gone", "jupyterhub-user-never-used"]);
+    assert_eq!(volumes[1].last_used(), Some(now - 30 * 86_400));
+    assert_eq!(volumes[2].last_used(), Some(1_600_000_000));
+}
@@ -0,0 +1,56 @@
+use mvre_hub::{
+    engine::ContainerState,
+    watch::{crash_reason, Backoff},
+};
+
+fn container(state: &str, health: &str, exit_code: i64) -> ContainerState {
+    ContainerState {
+        name: "mvre-hub-jupyterhub-1".to_string(),
+        service: "jupyterhub".to_string(),
+        state: state.to_string(),
+        health: health.to_string(),
+        status: String::new(),
+        exit_code,
+    }
+}
+
+#[test]
+fn only_crashed_or_unhealthy_containers_are_restarted() {
+    assert_eq!(crash_reason(&container("running", "healthy", 0)), None);
+    assert_eq!(crash_reason(&container("running", "", 0)), None);
+    assert_eq!(crash_reason(&container("exited", "", 0)), None);
+    assert_eq!(crash_reason(&container("exited", "", 137)).as_deref(), Some("exited with code 137"));
+    assert_eq!(crash_reason(&container("dead", "", 0)).as_deref(), Some("container is dead"));
+    assert_eq!(crash_reason(&container("running", "unhealthy", 0)).as_deref(), Some("health check failing"));
+}
+
+#[test]
+fn restart_delays_double_up_to_the_maximum() {
+    let mut backoff = Backoff::default();
+    assert!(backoff.ready(0));
+
+    backoff.restarted(100, 30, 600);
+    assert_eq!(backoff.next_allowed, 130);
+    assert!(!backoff.ready(129));
+    assert!(backoff.ready(130));
+
+    backoff.restarted(130, 30, 600);
+    assert_eq!(backoff.next_allowed, 190);
+    for now in [190, 310, 550, 1030, 1630] {
+        backoff.restarted(now, 30, 600);
+    }
+    assert_eq!(backoff.attempts, 7);
+    assert_eq!(backoff.next_allowed, 1630 + 600);
+}
+
+#[test]
+fn backoff_resets_once_the_service_stays_up() {
+    let mut backoff = Backoff::default();
+    backoff.restarted(100, 30, 600);
+    backoff.restarted(130, 30, 600);
+
+    backoff.settle(600, 600);
+    assert_eq!(backoff.attempts, 2);
+    backoff.settle(730, 600);
+    assert_eq!(backoff, Backoff::default());
+}
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use mvre_hub::{cli::LifecycleEvent, config::Webhook, webhook};
+
+fn hook(events: &[&str], failures_only: bool) -> Webhook {
+    Webhook {
+        url: "https://chat.example.org/hooks/hub".to_string(),
+        events: events.iter().map(|event| event.to_string()).collect(),
+        failures_only,
+    }
+}
+
+#[test]
+fn webhooks_hear_the_events_they_asked_for() {
+    assert!(webhook::wants(&hook(&[], false), LifecycleEvent::Backup, true));
+    assert!(webhook::wants(&hook(&["deploy", "upgrade"], false), LifecycleEvent::Upgrade, true));
+    assert!(!webhook::wants(&hook(&["deploy", "upgrade"], false), LifecycleEvent::Stop, false));
+    assert!(!webhook::wants(&hook(&[], true), LifecycleEvent::Start, true));
+    assert!(webhook::wants(&hook(&[], true), LifecycleEvent::Start, false));
+}
+
+#[test]
+fn payload_names_the_outcome() {
+    let failed = webhook::payload(
+        LifecycleEvent::Backup,
+        Some("aws: access denied"),
+        Some(Path::new("/srv/mvre-hub")),
+        Some("mvre-hub"),
+        Some("hub.example.org"),
+        1_700_000_000,
+        1_700_000_042,
+    );
+    assert_eq!(failed["event"], "backup_failed");
+    assert_eq!(failed["operation"], "backup");
+    assert_eq!(failed["ok"], false);
+    assert_eq!(failed["error"], "aws: access denied");
+    assert_eq!(failed["deploy_dir"], "/srv/mvre-hub");
+    assert_eq!(failed["domain"], "hub.example.org");
+    assert_eq!(failed["duration_secs"], 42);
+
+    let done = webhook::payload(LifecycleEvent::Deploy, None, None, None, None, 10, 10);
+    assert_eq!(done["event"], "deploy_succeeded");
+    assert!(done["error"].is_null() && done["deploy_dir"].is_null());
+}
+
+#[test]
+fn webhook_urls_must_be_http() {
+    assert!(webhook::validate_url("https://chat.example.org/hooks/hub").is_ok());
+    assert!(webhook::validate_url("http://10.0.0.5:8080/").is_ok());
+    assert!(webhook::validate_url("chat.example.org/hooks").is_err());
+    assert!(webhook::validate_url("https://").is_err());
+}
@@ -0,0 +1,42 @@
+use mvre_hub::workshop::{self, WorkshopRecord};
+
+#[test]
+fn accounts_are_numbered_with_a_fixed_width() {
+    assert_eq!(workshop::account_names("ws", 3), vec!["ws-01", "ws-02", "ws-03"]);
+    let names = workshop::account_names("course", 120);
+    assert_eq!(names.len(), 120);
+    assert_eq!(names[0], "course-001");
+    assert_eq!(names[119], "course-120");
+}
+
+#[test]
+fn prefixes_keep_account_names_unescaped() {
+    for prefix in ["ws", "mosaic-2026", "a1"] {
+        assert!(workshop::validate_prefix(prefix).is_ok(), "{}", prefix);
+    }
+    for prefix in ["", "WS", "1ws", "ws_1", "ws.a", "ws a"] {
+        assert!(workshop::validate_prefix(prefix).is_err(), "{}", prefix);
+    }
+}
+
+#[test]
+fn records_are_read_from_the_deployment() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    assert!(workshop::load(dir.path()).expect("load").is_empty());
+
+    std::fs::write(
+        dir.path().join("workshops.json"),
+        r#"[{"prefix":"ws","created_at":1,"expires_at":2,"access":"link","users":["ws-01"]}]"#,
+    )
+    .expect("write");
+    assert_eq!(
+        workshop::load(dir.path()).expect("load"),
+        vec![WorkshopRecord {
+            prefix: "ws".to_string(),
+            created_at: 1,
+            expires_at: 2,
+            access: "link".to_string(),
+            users: vec!["ws-01".to_string()],
+        }]
+    );
+}
@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use mvre_hub::{
+    cli::WorkspaceBackend,
+    envfile,
+    hubapi::User,
+    workspace::{self, WorkspaceUsage, Workspaces},
+};
+
+fn workspaces(backend: WorkspaceBackend, root: &str) -> Workspaces {
+    Workspaces {
+        backend,
+        root: root.to_string(),
+        quota: Some("20G".to_string()),
+    }
+}
+
+#[test]
+fn workspace_names_match_the_spawner_escaping() {
+    assert_eq!(workspace::workspace_name("alice"), "alice");
+    assert_eq!(workspace::workspace_name("Alice.B"), "-41lice-2E-42");
+    assert_eq!(workspace::workspace_name("jürgen"), "j-C3-BCrgen");
+    // Escaping is reversible, so two users never share a workspace.
+    assert_ne!(workspace::workspace_name("a-2E"), workspace::workspace_name("a."));
+}
+
+#[test]
+fn zfs_workspaces_are_child_datasets_with_a_quota() {
+    let zfs = workspaces(WorkspaceBackend::Zfs, "tank/mvre-hub");
+    assert_eq!(
+        workspace::create_commands(&zfs, "bob"),
+        vec![vec!["zfs", "create", "-o", "quota=20G", "tank/mvre-hub/bob"]]
+    );
+    let (command, target) = workspace::snapshot_command(&zfs, "bob", "before-course");
+    assert_eq!(command, vec!["zfs", "snapshot", "tank/mvre-hub/bob@before-course"]);
+    assert_eq!(target, "tank/mvre-hub/bob@before-course");
+}
+
+#[test]
+fn btrfs_workspaces_are_subvolumes_with_a_qgroup_limit() {
+    let btrfs = workspaces(WorkspaceBackend::Btrfs, "/srv/workspaces/");
+    assert_eq!(
+        workspace::create_commands(&btrfs, "bob"),
+        vec![
+            vec!["btrfs", "quota", "enable", "/srv/workspaces/"],
+            vec!["btrfs", "subvolume", "create", "/srv/workspaces/bob"],
+            vec!["btrfs", "qgroup", "limit", "20G", "/srv/workspaces/bob"],
+        ]
+    );
+    let (command, target) = workspace::snapshot_command(&btrfs, "bob", "daily");
+    assert_eq!(target, "/srv/workspaces/.snapshots/bob@daily");
+    assert_eq!(command[..4], ["btrfs", "subvolume", "snapshot", "-r"]);
+}
+
+#[test]
+fn zfs_list_output_is_parsed_into_usage() {
+    let output = "tank/mvre-hub\t4096\t0\ntank/mvre-hub/alice\t1048576\t21474836480\ntank/mvre-hub/bob\t2048\t0\n";
+    assert_eq!(
+        workspace::parse_zfs_list(output, "tank/mvre-hub"),
+        vec![
+            WorkspaceUsage {
+                name: "alice".to_string(),
+                used: Some(1_048_576),
+                quota: Some(21_474_836_480),
+            },
+            WorkspaceUsage {
+                name: "bob".to_string(),
+                used: Some(2048),
+                quota: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn btrfs_qgroup_output_is_parsed_into_usage() {
+    let output = "qgroupid         rfer         excl     max_rfer \n--------         ----         ----     -------- \n0/259         1638400        16384  21474836480 \n";
+    assert_eq!(workspace::parse_btrfs_qgroup(output), Some((Some(1_638_400), Some(21_474_836_480))));
+}
+
+#[test]
+fn exports_read_the_volume_or_host_directory_of_the_user() {
+    let volume = envfile::parse("WORKSPACE_BACKEND=volume\n");
+    assert_eq!(workspace::export_source(&volume, "Alice.B").expect("source"), "jupyterhub-user--41lice-2E-42");
+    assert_eq!(workspace::export_source(&envfile::parse(""), "bob").expect("source"), "jupyterhub-user-bob");
+
+    let zfs = envfile::parse("WORKSPACE_BACKEND=zfs\nWORKSPACE_ROOT=tank/home\nWORKSPACE_HOST_PATH=/tank/home/\n");
+    assert_eq!(workspace::export_source(&zfs, "bob").expect("source"), "/tank/home/bob");
+    assert!(workspace::export_source(&envfile::parse("WORKSPACE_BACKEND=btrfs\n"), "bob").is_err());
+
+    assert_eq!(workspace::export_file_name("bob", 1_700_000_000), "workspace-bob-20231114T221320Z.tar.gz");
+}
+
+#[test]
+fn inactive_users_are_judged_by_last_activity_and_running_servers() {
+    let users: Vec<User> = serde_json::from_str(
+        r#"[
+            {"name": "gone", "last_activity": "2026-01-01T00:00:00Z"},
+            {"name": "recent", "last_activity": "2026-10-01T00:00:00.123Z"},
+            {"name": "never-active", "created": "2025-12-01T00:00:00Z"},
+            {"name": "running", "last_activity": "2026-01-01T00:00:00Z", "servers": {"": {"name": "", "ready": true, "url": "/user/running/"}}},
+            {"name": "unknown"}
+        ]"#,
+    )
+    .expect("users");
+    let now = 1_792_000_000; // 2026-10-14
+    let inactive = workspace::inactive_users(&users, 180 * 86_400, now);
+    let names: Vec<&str> = inactive.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["gone", "never-active"]);
+    assert_eq!(inactive[0].1, 1_767_225_600);
+}
+
+#[test]
+fn imports_unpack_tar_archives_into_an_empty_workspace() {
+    assert!(workspace::archive_compression(Path::new("carol.tar.gz")).expect("gzip"));
+    assert!(workspace::archive_compression(Path::new("/tmp/Carol.TGZ")).expect("gzip"));
+    assert!(!workspace::archive_compression(Path::new("carol.tar")).expect("tar"));
+    assert!(workspace::archive_compression(Path::new("carol.zip")).is_err());
+
+    let script = workspace::import_script(true, false, 1000, 100);
+    assert!(script.starts_with("if [ -n \"$(ls -A /workspace)\" ]"));
+    assert!(script.ends_with("tar -xzf - -C /workspace && chown -R 1000:100 /workspace"));
+    assert_eq!(
+        workspace::import_script(false, true, 1001, 1001),
+        "tar -xf - -C /workspace && chown -R 1001:1001 /workspace"
+    );
+}